@@ -0,0 +1,76 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use deltachat::{
+    chat::{self, ChatId},
+    config::Config,
+    contact::Contact,
+    context::Context,
+    message::{Message, MsgId, Viewtype},
+    stock_str::StockStrings,
+    Events,
+};
+use tempfile::{tempdir, TempDir};
+
+/// Size of the forwarded attachment.
+const FILE_SIZE: usize = 10 * 1024 * 1024;
+
+async fn create_context() -> (Context, TempDir) {
+    let dir = tempdir().unwrap();
+    let dbfile = dir.path().join("db.sqlite");
+    let context = Context::new(dbfile.as_path(), 100, Events::new(), StockStrings::new())
+        .await
+        .unwrap();
+
+    let addr = "alice@example.com";
+    context.set_config(Config::Addr, Some(addr)).await.unwrap();
+    context
+        .set_config(Config::ConfiguredAddr, Some(addr))
+        .await
+        .unwrap();
+    context
+        .set_config(Config::Configured, Some("1"))
+        .await
+        .unwrap();
+    (context, dir)
+}
+
+/// Sends a large file message to a new chat, returns the chat and the message.
+async fn send_large_file(context: &Context) -> (ChatId, MsgId) {
+    let contact_id = Contact::create(context, "Bob", "bob@example.net")
+        .await
+        .unwrap();
+    let chat_id = ChatId::create_for_contact(context, contact_id)
+        .await
+        .unwrap();
+
+    let file = context.get_blobdir().join("large.bin");
+    tokio::fs::write(&file, vec![0x42; FILE_SIZE])
+        .await
+        .unwrap();
+    let mut msg = Message::new(Viewtype::File);
+    msg.set_file(file.to_str().unwrap(), None);
+    let msg_id = chat::send_msg(context, chat_id, &mut msg).await.unwrap();
+    (chat_id, msg_id)
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Forward messages");
+    group.sample_size(10);
+    group.bench_function("Forward a 10 MiB file message", |b| {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (context, _dir) = rt.block_on(create_context());
+        let (chat_id, msg_id) = rt.block_on(send_large_file(&context));
+
+        b.to_async(&rt).iter(|| {
+            let ctx = context.clone();
+            async move {
+                chat::forward_msgs(&ctx, &[black_box(msg_id)], chat_id)
+                    .await
+                    .unwrap();
+            }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,155 @@
+use std::path::Path;
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use deltachat::chatlist::Chatlist;
+use deltachat::config::Config;
+use deltachat::context::Context;
+use deltachat::receive_imf::receive_imf;
+use deltachat::stock_str::StockStrings;
+use deltachat::Events;
+use tempfile::tempdir;
+
+/// Number of chats in the generated database.
+const CHATS: usize = 100;
+
+/// Number of messages in the generated database.
+const MESSAGES: usize = 100_000;
+
+/// Every n-th generated message is fresh, the others are seen.
+const FRESH_EVERY: usize = 100;
+
+/// Creates a context with one chat per sender.
+async fn create_context(dbfile: &Path) -> Context {
+    let context = Context::new(dbfile, 100, Events::new(), StockStrings::new())
+        .await
+        .unwrap();
+    let addr = "alice@example.org";
+    context.set_config(Config::Addr, Some(addr)).await.unwrap();
+    context
+        .set_config(Config::ConfiguredAddr, Some(addr))
+        .await
+        .unwrap();
+    context
+        .set_config(Config::Configured, Some("1"))
+        .await
+        .unwrap();
+
+    for i in 0..CHATS {
+        let imf_raw = format!(
+            "Subject: Benchmark
+Message-ID: chat.{i}@example.net
+Date: Sat, 07 Dec 2019 19:00:27 +0000
+To: alice@example.org
+From: Sender {i} <sender{i}@example.net>
+Chat-Version: 1.0
+MIME-Version: 1.0
+Content-Type: text/plain; charset=utf-8
+
+Hello {i}"
+        );
+        receive_imf(&context, imf_raw.as_bytes(), false)
+            .await
+            .unwrap();
+    }
+    for chat_id in list_chats(&context).await {
+        chat_id.accept(&context).await.unwrap();
+    }
+    context
+}
+
+/// Adds generated messages to the chats directly,
+/// as receiving them one by one would take too long.
+fn fill_database(dbfile: &Path) {
+    let mut conn = rusqlite::Connection::open(dbfile).unwrap();
+    conn.busy_timeout(Duration::from_secs(60)).unwrap();
+    let tx = conn.transaction().unwrap();
+    {
+        let senders: Vec<(u32, u32, i64)> = tx
+            .prepare("SELECT chat_id, from_id, timestamp FROM msgs WHERE chat_id>9")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO msgs
+                 (rfc724_mid, chat_id, from_id, to_id, timestamp, timestamp_sent, timestamp_rcvd,
+                  type, state, txt)
+                 VALUES (?, ?, ?, 1, ?, ?, ?, 10, ?, ?)",
+            )
+            .unwrap();
+        for i in 0..MESSAGES {
+            let (chat_id, from_id, timestamp) = senders[i % senders.len()];
+            let timestamp = timestamp + i as i64;
+            // 10 is `MessageState::InFresh`, 16 is `MessageState::InSeen`.
+            let state = if i % FRESH_EVERY == 0 { 10 } else { 16 };
+            stmt.execute(rusqlite::params![
+                format!("generated.{i}@example.net"),
+                chat_id,
+                from_id,
+                timestamp,
+                timestamp,
+                timestamp,
+                state,
+                format!("Generated message {i}"),
+            ])
+            .unwrap();
+        }
+    }
+    tx.commit().unwrap();
+}
+
+async fn list_chats(context: &Context) -> Vec<deltachat::chat::ChatId> {
+    let chatlist = Chatlist::try_load(context, 0, None, None).await.unwrap();
+    (0..chatlist.len())
+        .map(|i| chatlist.get_chat_id(i).unwrap())
+        .collect()
+}
+
+async fn get_fresh_msg_cnt_benchmark(context: &Context) {
+    for chat_id in list_chats(context).await {
+        chat_id.get_fresh_msg_cnt(context).await.unwrap();
+    }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let dir = tempdir().unwrap();
+    let dbfile = dir.path().join("db.sqlite");
+    let context = rt.block_on(async {
+        let context = create_context(&dbfile).await;
+        fill_database(&dbfile);
+        context
+    });
+
+    let mut group = c.benchmark_group("Large database");
+    group.bench_function("Chatlist::try_load", |b| {
+        b.to_async(&rt).iter(|| async {
+            Chatlist::try_load(black_box(&context), 0, None, None)
+                .await
+                .unwrap();
+        })
+    });
+    group.bench_function("Chatlist::try_load with query", |b| {
+        b.to_async(&rt).iter(|| async {
+            Chatlist::try_load(black_box(&context), 0, Some("Sender 1"), None)
+                .await
+                .unwrap();
+        })
+    });
+    group.bench_function("ChatId::get_fresh_msg_cnt for all chats", |b| {
+        b.to_async(&rt)
+            .iter(|| get_fresh_msg_cnt_benchmark(black_box(&context)))
+    });
+    group.bench_function("Context::get_fresh_msgs", |b| {
+        b.to_async(&rt).iter(|| async {
+            black_box(&context).get_fresh_msgs().await.unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use deltachat::{
@@ -91,6 +92,50 @@ async fn recv_groupmembership_emails(context: Context) -> Context {
     context
 }
 
+/// Representative messages from different clients and servers.
+const CORPUS: &[&[u8]] = &[
+    include_bytes!("../test-data/message/text_plain_flowed.eml"),
+    include_bytes!("../test-data/message/text_alt_plain_html.eml"),
+    include_bytes!("../test-data/message/thunderbird_with_autocrypt.eml"),
+    include_bytes!("../test-data/message/mail_with_user_and_group_avatars.eml"),
+    include_bytes!("../test-data/message/many_images_amazon_via_apple_mail.eml"),
+    include_bytes!("../test-data/message/mailinglist_dhl.eml"),
+    include_bytes!("../test-data/message/gmail_ndn.eml"),
+    include_bytes!("../test-data/message/webxdc_good_extension.eml"),
+];
+
+/// Replaces the Message-ID of `imf_raw` so that the message is not skipped as a duplicate.
+fn with_message_id(imf_raw: &[u8], message_id: &str) -> Vec<u8> {
+    let mut res = format!("Message-ID: <{message_id}>\r\n").into_bytes();
+    let mut in_header = true;
+    let mut skipping = false;
+    for line in imf_raw.split_inclusive(|&b| b == b'\n') {
+        if in_header {
+            if line == b"\r\n" || line == b"\n" {
+                in_header = false;
+            } else if !(skipping && (line.starts_with(b" ") || line.starts_with(b"\t"))) {
+                skipping = line.len() >= 11 && line[..11].eq_ignore_ascii_case(b"message-id:");
+            }
+            if in_header && skipping {
+                continue;
+            }
+        }
+        res.extend_from_slice(line);
+    }
+    res
+}
+
+/// Receives each message of the corpus once.
+async fn recv_corpus(context: Context, iteration: usize) -> Context {
+    for (i, imf_raw) in CORPUS.iter().enumerate() {
+        let imf_raw = with_message_id(imf_raw, &format!("corpus.{iteration}.{i}@example.org"));
+        receive_imf(&context, black_box(&imf_raw), false)
+            .await
+            .unwrap();
+    }
+    context
+}
+
 async fn create_context() -> Context {
     let dir = tempdir().unwrap();
     let dbfile = dir.path().join("db.sqlite");
@@ -150,6 +195,19 @@ fn criterion_benchmark(c: &mut Criterion) {
             });
         },
     );
+    group.bench_function("Receive representative MIME messages", |b| {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let context = rt.block_on(create_context());
+        let iteration = AtomicUsize::new(0);
+
+        b.to_async(&rt).iter(|| {
+            let ctx = context.clone();
+            let iteration = iteration.fetch_add(1, Ordering::Relaxed);
+            async move {
+                recv_corpus(black_box(ctx), iteration).await;
+            }
+        });
+    });
     group.finish();
 }
 
@@ -395,6 +395,13 @@ char*           dc_get_blobdir               (const dc_context_t* context);
  * - `e2ee_enabled` = 0=no end-to-end-encryption, 1=prefer end-to-end-encryption (default)
  * - `mdns_enabled` = 0=do not send or request read receipts,
  *                    1=send and request read receipts (default)
+ * - `share_last_seen` = 0=ask contacts not to track when we were last seen
+ *                    and do not reveal in read receipts when messages were read;
+ *                    in return, dc_contact_get_last_seen() returns 0 for all contacts,
+ *                    1=share last-seen status with contacts (default)
+ * - `device_name`  = name of this device, e.g. "Desktop", shown in the device list of other devices.
+ *                    The device list is stored encrypted on the server
+ *                    if the server supports the IMAP METADATA extension.
  * - `bcc_self`     = 0=do not send a copy of outgoing messages to self (default),
  *                    1=send a copy of outgoing messages to self.
  *                    Sending messages to self is needed for a proper multi-account setup,
@@ -413,6 +420,14 @@ char*           dc_get_blobdir               (const dc_context_t* context);
  *                    if enabled.
  *                    0=watch all folders normally (default)
  *                    changes require restarting IO by calling dc_stop_io() and then dc_start_io().
+ * - `mvbox_folder` = name of the folder for chat messages to use instead of the `DeltaChat` folder,
+ *                    unset to use the default. Applied by dc_configure().
+ * - `sentbox_folder` = name of the `Sent` folder to use instead of detecting it,
+ *                    unset to detect the folder (default).
+ *                    Applied by dc_configure() and on the next folder scan.
+ * - `trash_folder` = name of the `Trash` folder to use instead of detecting it,
+ *                    unset to detect the folder (default).
+ *                    Applied by dc_configure() and on the next folder scan.
  * - `show_emails`  = DC_SHOW_EMAILS_OFF (0)=
  *                    show direct replies to chats only (default),
  *                    DC_SHOW_EMAILS_ACCEPTED_CONTACTS (1)=
@@ -447,19 +462,98 @@ char*           dc_get_blobdir               (const dc_context_t* context);
  *                    The library uses the `media_quality` setting to use different defaults
  *                    for recoding images sent with type #DC_MSG_IMAGE.
  *                    If needed, recoding other file types is up to the UI.
+ * - `max_msg_size` = maximum size of outgoing messages in bytes,
+ *                    0 or unset to use the limit of the provider database (default).
+ *                    Sending messages with larger attachments fails early,
+ *                    the size includes the overhead of encoding and encryption,
+ *                    dc_send_msg() and dc_prepare_msg() return 0 and the error mentions the limit.
+ * - `oversized_attachment` = 0=scale down images sent with type #DC_MSG_IMAGE
+ *                    exceeding `max_msg_size` until they fit,
+ *                    refuse to send other attachments (default),
+ *                    1=refuse to send all attachments exceeding `max_msg_size`.
+ * - `p2p_file_transfer` = 1=allow offering files for direct download with dc_send_file_p2p(),
+ *                    0=do not offer files peer-to-peer (default).
+ * - `strip_exif`   = 1=remove metadata as GPS location and camera model from outgoing JPEG, PNG
+ *                    and WebP images sent with type #DC_MSG_IMAGE (default);
+ *                    sending fails if the metadata of an image cannot be removed,
+ *                    0=send images with all metadata.
+ *                    The removed metadata can be queried using dc_msg_get_removed_metadata().
+ * - `exif_keep`    = space-separated list of metadata retained when `strip_exif` is enabled,
+ *                    possible values are `orientation` and `date`, defaults to `orientation`.
+ * - `recode_voice` = 1=re-encode outgoing voice messages sent with type #DC_MSG_VOICE
+ *                    to Opus in an Ogg container with normalized loudness,
+ *                    formats that cannot be decoded are sent as recorded,
+ *                    0=send voice messages as recorded (default).
+ *                    Only supported if the core is built with the `recode-voice` feature.
  * - `webrtc_instance` = webrtc instance to use for videochats in the form
  *                    `[basicwebrtc:|jitsi:]https://example.com/subdir#roomname=$ROOM`
  *                    if the URL is prefixed by `basicwebrtc`, the server is assumed to be of the type
  *                    https://github.com/cracker0dks/basicwebrtc which some UIs have native support for.
  *                    The type `jitsi:` may be handled by external apps.
  *                    If no type is prefixed, the videochat is handled completely in a browser.
+ * - `webrtc_instances` = additional webrtc instances to offer for videochats,
+ *                    one per line in the form of `webrtc_instance`,
+ *                    see dc_send_videochat_invitation_ex().
+ * - `gif_search_endpoint` = HTTPS endpoint used to search GIFs,
+ *                    the search is available via the JSON-RPC API only (`searchGifs()`, `sendGif()`).
+ * - `gif_search_api_key` = API key passed to `gif_search_endpoint` as bearer token.
+ * - `summary_max_chars` = approximate maximum number of characters of message summaries
+ *                    as returned by dc_chatlist_get_summary() or dc_msg_get_summary(),
+ *                    0=no limit (default).
+ * - `summary_emoji` = 1=prefix summaries of media messages with an emoji, e.g. "📷 Image",
+ *                    0=no emoji prefix (default).
+ *                    The labels themselves can be localized using dc_set_stock_translation().
+ * - `summary_show_sender` = 1=summaries of group messages have the sender name as prefix (default),
+ *                    0=no sender name prefix in groups, dc_lot_get_text1() is NULL then.
+ * - `encrypted_subject` = subject of the unencrypted outer part of encrypted messages,
+ *                    the real subject is always sent in the encrypted part:
+ *                    DC_ENCRYPTED_SUBJECT_HIDDEN (0) = use "..." as subject (default),
+ *                    DC_ENCRYPTED_SUBJECT_CHAT_NAME (1) = use the chat name as subject,
+ *                    DC_ENCRYPTED_SUBJECT_PLAIN (2) = use the real subject,
+ *                    e.g. for bots that need to filter messages on the server.
+ * - `notify_muted_mentions` = 1=notify about mentions and replies in muted chats (default),
+ *                    0=do not notify about messages in muted chats at all,
+ *                    see dc_get_notification_intent(). The setting is synchronized to other devices.
+ * - `notify_bots`  = 1=notify silently about messages from bots (default),
+ *                    0=do not notify about messages from bots,
+ *                    see dc_get_notification_intent(). The setting is synchronized to other devices.
+ * - `quiet_hours`  = quiet hours in local time as `HH:MM-HH:MM`, e.g. `22:00-07:00`,
+ *                    unset or empty to disable quiet hours (default).
+ *                    During quiet hours, dc_get_notification_intent() returns #DC_NOTIFICATION_INTENT_SUPPRESS
+ *                    and #DC_EVENT_NOTIFICATIONS_SUPPRESSED is emitted.
+ *                    The setting is synchronized to other devices.
+ * - `quiet_hours_allow_mentions` = 1=notify about mentions and replies during quiet hours (default),
+ *                    0=suppress them as well. The setting is synchronized to other devices.
+ * - `quiet_hours_allow_verified` = 1=notify about messages from verified contacts during quiet hours (default),
+ *                    0=suppress them as well. The setting is synchronized to other devices.
+ * - `notification_digest` = 1=add a device message after the quiet hours summarizing the messages
+ *                    whose notifications were suppressed during quiet hours,
+ *                    0=do not add a summary (default).
+ * - `clock_skew_correction` = 1=add the estimated difference between the server time and the device time
+ *                    to the timestamps of sent messages if it exceeds 5 minutes,
+ *                    0=use the device time (default), see #DC_EVENT_CLOCK_SKEW.
+ * - `abuse_addr`   = address to send reports created by dc_report_msg() to,
+ *                    unset or empty to use `abuse@` the domain of the configured address (default).
  * - `bot`          = Set to "1" if this is a bot.
  *                    Prevents adding the "Device messages" and "Saved messages" chats,
  *                    adds Auto-Submitted header to outgoing messages
  *                    and accepts contact requests automatically (calling dc_accept_chat() is not needed for bots).
+ * - `only_accept_verified` = 1=turn the account into a closed network of verified contacts:
+ *                    messages from unverified senders go to contact requests even if the sender is known,
+ *                    sending to chats with unverified members is not possible
+ *                    and messages that cannot be encrypted are not sent,
+ *                    0=accept messages from all contacts as usual (default).
  * - `fetch_existing_msgs` = 1=fetch most recent existing messages on configure (default),
  *                    0=do not fetch existing messages on configure.
  *                    In both cases, existing recipients are added to the contact database.
+ * - `fetch_existing_msgs_count` = maximum number of existing messages fetched from each folder
+ *                    if `fetch_existing_msgs` is set, defaults to 100.
+ *                    Bots do not fetch existing messages unless this option is set explicitly.
+ * - `fetch_existing_msgs_folders` = space-separated list of folders existing messages are fetched from,
+ *                    possible values are `mvbox`, `inbox` and `sent` (default: all of them).
+ * - `fetch_max_age` = messages which arrived on the server more than this number of seconds ago
+ *                    are never downloaded, including messages found when scanning other folders.
+ *                    0=no limit (default).
  * - `download_limit` = Messages up to this number of bytes are downloaded automatically.
  *                    For larger messages, only the header is downloaded and a placeholder is shown.
  *                    These messages can be downloaded fully using dc_download_full_msg() later.
@@ -468,6 +562,12 @@ char*           dc_get_blobdir               (const dc_context_t* context);
  *                    to not mess up with non-delivery-reports or read-receipts.
  *                    0=no limit (default).
  *                    Changes affect future messages only.
+ * - `poll_interval` = Check for new messages every this number of minutes
+ *                    instead of keeping a permanent IDLE connection to the server.
+ *                    Between the checks, the connection is closed to save battery.
+ *                    The time until the next check is shown in dc_get_connectivity_html().
+ *                    dc_maybe_network() and sending messages still trigger an immediate check.
+ *                    0=use IDLE (default).
  * - `ui.*`         = All keys prefixed by `ui.` can be used by the user-interfaces for system-specific purposes.
  *                    The prefix should be followed by the system and maybe subsystem,
  *                    e.g. `ui.desktop.foo`, `ui.desktop.linux.bar`, `ui.android.foo`, `ui.dc40.bar`, `ui.bot.simplebot.baz`.
@@ -502,6 +602,16 @@ int             dc_set_config                (dc_context_t* context, const char*
  * - `quota_exceeding` = 0: quota is unknown or in normal range;
  *                    >=80: quota is about to exceed, the value is the concrete percentage,
  *                    a device message is added when that happens, however, that value may still be interesting for bots.
+ * - `is_chatmail` = 1=the account is on a chatmail relay,
+ *                    which only delivers end-to-end encrypted chat messages and does not exchange messages with classic email servers;
+ *                    UIs may e.g. hide email-specific options.
+ *                    0=classic email server or not configured.
+ *                    Determined by dc_configure().
+ *                    On chatmail relays, `mvbox_move` defaults to 0
+ *                    and `delete_server_after` defaults to 1 (at once) if `bcc_self` is disabled.
+ * - `clock_skew` = estimated difference between the server time and the device time in seconds,
+ *                    positive if the device clock is behind, 0 if unknown.
+ *                    Determined from the `Received` headers of fetched messages, see #DC_EVENT_CLOCK_SKEW.
  *
  * @memberof dc_context_t
  * @param context The context object. For querying system values, this can be NULL.
@@ -527,15 +637,55 @@ char*           dc_get_config                (dc_context_t* context, const char*
 int             dc_set_stock_translation(dc_context_t* context, uint32_t stock_id, const char* stock_msg);
 
 
+/**
+ * Set stock string translation in ICU MessageFormat syntax.
+ *
+ * Unlike dc_set_stock_translation(), this allows to define plural forms,
+ * e.g. `{1, plural, one {# minute} other {# minutes}}`.
+ * Arguments are referred to by the number of the placeholder in the default string,
+ * i.e. `{1}` is substituted where the default string has `%1$s`
+ * and `#` is the number inside a plural branch.
+ * Besides plural categories, exact values as `=0` can be used as selectors.
+ * Literal braces can be quoted using apostrophes, e.g. `'{'`.
+ *
+ * The plural forms are selected according to the language set by dc_set_stock_language().
+ * A translation set by this function replaces a translation set by dc_set_stock_translation()
+ * for the same ID and vice versa.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param stock_id The integer ID of the stock message, one of the @ref DC_STR constants.
+ * @param format The message in ICU MessageFormat syntax.
+ * @return 1=success, 0=error, e.g. the format cannot be parsed or uses unknown arguments.
+ */
+int             dc_set_stock_format          (dc_context_t* context, uint32_t stock_id, const char* format);
+
+
+/**
+ * Set the language used to select plural forms
+ * of translations set by dc_set_stock_format().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param language Language code as `de` or `pt-BR`, defaults to `en`.
+ */
+void            dc_set_stock_language        (dc_context_t* context, const char* language);
+
+
 /**
  * Set configuration values from a QR code.
  * Before this function is called, dc_check_qr() should confirm the type of the
- * QR code is DC_QR_ACCOUNT, DC_QR_LOGIN or DC_QR_WEBRTC_INSTANCE.
+ * QR code is DC_QR_ACCOUNT, DC_QR_LOGIN, DC_QR_PROFILE or DC_QR_WEBRTC_INSTANCE.
  *
  * Internally, the function will call dc_set_config() with the appropriate keys,
  * e.g. `addr` and `mail_pw` for DC_QR_ACCOUNT and DC_QR_LOGIN
  * or `webrtc_instance` for DC_QR_WEBRTC_INSTANCE.
  *
+ * For DC_QR_PROFILE, the server settings and the display name are applied atomically,
+ * the function fails if they cannot be applied.
+ * Failures to set the avatar or to add contacts and groups of the profile
+ * are reported as #DC_EVENT_WARNING.
+ *
  * @memberof dc_context_t
  * @param context The context object.
  * @param qr The scanned QR code.
@@ -561,6 +711,48 @@ int             dc_set_config_from_qr   (dc_context_t* context, const char* qr);
 char*           dc_get_info                  (const dc_context_t* context);
 
 
+/**
+ * Export preferences and per-chat settings as a JSON document.
+ *
+ * The document contains explicitly set preferences as the display name,
+ * the signature and the `ui.*` values set by dc_set_config(),
+ * as well as the visibility, mute duration, archive mode and threading of chats.
+ * Credentials, server settings, keys and messages are not included,
+ * use dc_imex() to create a full backup.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @return JSON document, must be released using dc_str_unref() after usage.
+ *     Returns NULL on errors.
+ */
+char*           dc_export_settings           (dc_context_t* context);
+
+
+/**
+ * Import a settings document created by dc_export_settings().
+ *
+ * Settings files of the apps can be imported as well to switch platforms:
+ * `config.json` of Delta Chat Desktop, the SharedPreferences XML file of Delta Chat Android
+ * and the property list of Delta Chat iOS.
+ * The format is detected automatically.
+ * Preferences available on all platforms, as the language or whether the enter key sends,
+ * are imported to common UI config keys as `ui.language` or `ui.enter_key_sends`,
+ * preferences which are account settings, as read receipts, are imported to the account settings.
+ * All preferences are imported as `ui.desktop.*`, `ui.android.*` or `ui.ios.*` as well,
+ * which can be read using dc_get_config().
+ *
+ * Preferences missing from the document are left unchanged.
+ * Settings of chats that do not exist in this account are skipped,
+ * no chats are created.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param json Document as returned by dc_export_settings() or settings file of an app.
+ * @return 1=success, 0=error.
+ */
+int             dc_import_settings           (dc_context_t* context, const char* json);
+
+
 /**
  * Get URL that can be used to initiate an OAuth2 authorization.
  *
@@ -777,6 +969,28 @@ void            dc_stop_io(dc_context_t* context);
 void            dc_maybe_network             (dc_context_t* context);
 
 
+/**
+ * Set the push notification token of this device,
+ * e.g. the token of a notification proxy service.
+ *
+ * The token is stored and registered on the server in the background
+ * if the server supports push notifications (`XDELTAPUSH` capability).
+ * The server then wakes up the device when new messages arrive;
+ * on wake-up, call dc_maybe_network() or dc_accounts_maybe_network()
+ * to fetch the messages.
+ *
+ * The token should be set whenever the UI gets a new one;
+ * setting the same token again does not result in any network traffic.
+ *
+ * @memberof dc_context_t
+ * @param context The context as created by dc_context_new().
+ * @param token The push notification token,
+ *     an empty string or NULL unregisters the device from push notifications.
+ * @return 1=success, 0=error, e.g. the token contains invalid characters.
+ */
+int             dc_set_push_device_token     (dc_context_t* context, const char* token);
+
+
 
 /**
  * Save a keypair as the default keys for the user.
@@ -977,6 +1191,27 @@ uint32_t        dc_prepare_msg               (dc_context_t* context, uint32_t ch
  */
 uint32_t        dc_send_msg                  (dc_context_t* context, uint32_t chat_id, dc_msg_t* msg);
 
+/**
+ * Send a message defined by a dc_msg_t object to a chat
+ * without waiting for the message to be rendered and encrypted.
+ *
+ * Unlike dc_send_msg(), the message is added to the chat and its ID is returned immediately,
+ * rendering is done later by the SMTP loop.
+ * If rendering fails, the event #DC_EVENT_MSG_FAILED is emitted.
+ * Forwarded messages prepared with dc_prepare_msg() cannot be sent this way.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param chat_id The chat ID to send the message to.
+ *     If dc_prepare_msg() was called before, this parameter can be 0.
+ * @param msg The message object to send to the chat defined by the chat ID.
+ *     On success, msg_id of the object is set up,
+ *     The function does not take ownership of the object,
+ *     so you have to free it using dc_msg_unref() as usual.
+ * @return The ID of the message that is about to be sent. 0 in case of errors.
+ */
+uint32_t        dc_send_msg_deferred         (dc_context_t* context, uint32_t chat_id, dc_msg_t* msg);
+
 /**
  * Send a message defined by a dc_msg_t object to a chat, synchronously.
  * This bypasses the IO scheduler and creates its own SMTP connection. Which means
@@ -1021,8 +1256,10 @@ uint32_t        dc_send_text_msg             (dc_context_t* context, uint32_t ch
  * Send invitation to a videochat.
  *
  * This function reads the `webrtc_instance` config value,
+ * falling back to the first line of `webrtc_instances` if unset,
  * may check that the server is working in some way
  * and creates a unique room for this chat, if needed doing a TOKEN roundtrip for that.
+ * Room names carry at least 128 random bits so they cannot be guessed.
  *
  * After that, the function sends out a message that contains information to join the room:
  *
@@ -1053,6 +1290,100 @@ uint32_t        dc_send_text_msg             (dc_context_t* context, uint32_t ch
 uint32_t dc_send_videochat_invitation (dc_context_t* context, uint32_t chat_id);
 
 
+/**
+ * Send invitation to a videochat on a given webrtc instance.
+ *
+ * Works as dc_send_videochat_invitation(),
+ * but uses the given instance instead of the `webrtc_instance` config value.
+ * UIs may offer the user to choose the instance from `webrtc_instance` and `webrtc_instances`.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The chat to start a videochat for.
+ * @param instance The webrtc instance in the form described at `webrtc_instance` in dc_set_config(),
+ *     the instance does not need to be configured.
+ * @return The ID of the message sent out
+ *     or 0 for errors.
+ */
+uint32_t dc_send_videochat_invitation_ex (dc_context_t* context, uint32_t chat_id, const char* instance);
+
+
+/**
+ * Offer the file of a message for direct peer-to-peer download
+ * instead of attaching it.
+ *
+ * This is meant for files exceeding the maximum message size, see the config `max_msg_size`.
+ * The function sends a small text message carrying a ticket,
+ * the recipients can use dc_download_file_p2p() to download the file directly from this device
+ * while IO is running, see dc_start_io() and dc_stop_io().
+ * The file is offered for 7 days, also after restarting the app.
+ * An interrupted download starts from the beginning when it is retried.
+ * For each download, #DC_EVENT_P2P_FILE_PROGRESS events are sent.
+ *
+ * Sending files peer-to-peer is opt-in, the config `p2p_file_transfer` must be enabled.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The chat to send the file to.
+ * @param msg The message object with the file, see dc_msg_set_file().
+ *     The message is sent with the type #DC_MSG_TEXT.
+ * @return The ID of the message sent out
+ *     or 0 for errors.
+ */
+uint32_t dc_send_file_p2p (dc_context_t* context, uint32_t chat_id, dc_msg_t* msg);
+
+
+/**
+ * Send images and videos as an album.
+ *
+ * Each file is sent as a message of its own,
+ * the caption is added to the first message.
+ * All messages get the same grouping ID, see dc_msg_get_album_id(),
+ * so that UIs can show them as one group on all devices.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The chat ID to send the album to.
+ * @param files An array of the full paths of the files to send.
+ * @param files_cnt The number of files in the files array.
+ * @param caption The text to send along with the first file. NULL for no caption.
+ * @return Array of the IDs of the messages sent out, must be dc_array_unref()'d after usage.
+ *     NULL on errors, e.g. if a file is no image or video.
+ */
+dc_array_t*     dc_send_album                (dc_context_t* context, uint32_t chat_id, const char* const* files, int files_cnt, const char* caption);
+
+
+/**
+ * Get the messages of the album a message belongs to.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The ID of any message of the album.
+ * @return Array of message IDs in the order they were sent,
+ *     only msg_id if the message does not belong to an album.
+ *     Must be dc_array_unref()'d after usage.
+ */
+dc_array_t*     dc_get_album_msgs            (dc_context_t* context, uint32_t msg_id);
+
+
+/**
+ * Download a file offered peer-to-peer by a message,
+ * see dc_msg_get_p2p_filename().
+ *
+ * The download only succeeds while the sender is online,
+ * failed downloads can be retried.
+ * The function is blocking and reports the progress with #DC_EVENT_P2P_FILE_PROGRESS events,
+ * so the UIs will typically call the function from within a thread.
+ * Afterwards, the file is returned by dc_msg_get_file().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The ID of the message offering the file.
+ * @return 1=success, 0=error
+ */
+int dc_download_file_p2p (dc_context_t* context, uint32_t msg_id);
+
+
 /**
  * Send a reaction to message.
  *
@@ -1070,6 +1401,77 @@ uint32_t dc_send_videochat_invitation (dc_context_t* context, uint32_t chat_id);
 uint32_t dc_send_reaction (dc_context_t* context, uint32_t msg_id, char *reaction);
 
 
+/**
+ * Offer a call in a 1:1 chat.
+ *
+ * The core only transports the signaling,
+ * the payload is typically a WebRTC session description created by the UI.
+ * It is passed to the recipient with #DC_EVENT_INCOMING_CALL_OFFER.
+ *
+ * If the call is not answered within 60 seconds,
+ * #DC_EVENT_CALL_MISSED is emitted on both sides
+ * and a missed call is recorded in the chat of the callee, see #DC_MSG_CALL.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The ID of the 1:1 chat to call.
+ * @param payload The payload to pass to the recipient.
+ * @return The ID of the hidden offer message identifying the call or 0 for errors.
+ */
+uint32_t dc_send_call_offer (dc_context_t* context, uint32_t chat_id, const char* payload);
+
+
+/**
+ * Answer a call offered by #DC_EVENT_INCOMING_CALL_OFFER.
+ *
+ * The payload is passed to the caller with #DC_EVENT_INCOMING_CALL_ANSWER.
+ * Fails if the call is not ringing anymore.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The ID of the offer message identifying the call.
+ * @param payload The payload to pass to the caller.
+ * @return The ID of the message sent out or 0 for errors.
+ */
+uint32_t dc_send_call_answer (dc_context_t* context, uint32_t msg_id, const char* payload);
+
+
+/**
+ * Send an ICE candidate to the peer of a call.
+ *
+ * The payload is passed to the peer with #DC_EVENT_INCOMING_CALL_ICE.
+ * As every signal is sent as a message,
+ * candidates passed within 500 milliseconds are sent together in one message.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The ID of the offer message identifying the call.
+ * @param payload The payload to pass to the peer.
+ * @return 1 if the candidate is queued for sending, 0 for errors.
+ */
+int dc_send_call_ice (dc_context_t* context, uint32_t msg_id, const char* payload);
+
+
+/**
+ * Add a record of a call to a 1:1 chat.
+ *
+ * The record is a local message of type #DC_MSG_CALL and is not sent.
+ * This allows videochat and VoIP integrations to keep the call history in the chat.
+ * Incoming missed calls are added as fresh messages and notified with #DC_EVENT_INCOMING_MSG,
+ * calls offered by dc_send_call_offer() and not answered in time are recorded automatically.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The ID of the 1:1 chat.
+ * @param incoming 1=the call was incoming, 0=the call was outgoing.
+ * @param state One of #DC_CALL_STATE_ACCEPTED, #DC_CALL_STATE_MISSED or #DC_CALL_STATE_DECLINED.
+ * @param duration The duration of an accepted call in milliseconds.
+ * @param timestamp The start of the call as a unix timestamp.
+ * @return The ID of the added message or 0 for errors.
+ */
+uint32_t dc_add_call_record (dc_context_t* context, uint32_t chat_id, int incoming, int state, int duration, int64_t timestamp);
+
+
 /**
  * Get a structure with reactions to the message.
  *
@@ -1138,11 +1540,26 @@ int dc_send_webxdc_status_update (dc_context_t* context, uint32_t msg_id, const
  *     - `update.max_serial`: the maximum serial currently known.
  *        If `max_serial` equals `serial` this update is the last update (until new network messages arrive).
  *     - `update.info`: optional, short, informational message.
+ *     - `update.href`: optional, passed to the webxdc when the info message is clicked,
+ *        see dc_msg_get_webxdc_href().
  *     - `update.summary`: optional, short text, shown beside app icon.
  *        If there are no updates, an empty JSON-array is returned.
  */
 char* dc_get_webxdc_status_updates (dc_context_t* context, uint32_t msg_id, uint32_t serial);
 
+
+/**
+ * Mark the info messages of a webxdc instance as seen.
+ *
+ * This resets the `unread_info_cnt` returned by dc_msg_get_webxdc_info(),
+ * UIs should call this function when the webxdc is opened.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The ID of the message with the webxdc instance.
+ */
+void dc_mark_webxdc_infos_seen (dc_context_t* context, uint32_t msg_id);
+
 /**
  * Save a draft for a chat in the database.
  *
@@ -1246,8 +1663,118 @@ int             dc_was_device_msg_ever_added (dc_context_t* context, const char*
 dc_msg_t*       dc_get_draft                 (dc_context_t* context, uint32_t chat_id);
 
 
+/**
+ * Add a canned response.
+ * Canned responses are texts, optionally with a file, that are sent frequently,
+ * e.g. answers to common questions.
+ *
+ * Titles and texts are synchronized to other devices
+ * if the config option `send_sync_msgs` is enabled;
+ * files are only available on the device that added them.
+ * Changes are announced by #DC_EVENT_CANNED_RESPONSES_CHANGED.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param title Title to choose the canned response by, must not be empty.
+ * @param text Text to send, may be NULL.
+ * @param file Path of a file to send, NULL for none.
+ *     The file is copied to the blob directory if needed.
+ * @return ID of the canned response, 0 on errors.
+ */
+uint32_t        dc_add_canned_response       (dc_context_t* context, const char* title, const char* text, const char* file);
+
+
+/**
+ * Save text and file of an existing message as canned response,
+ * see dc_add_canned_response().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The message to save.
+ * @param title Title to choose the canned response by, must not be empty.
+ * @return ID of the canned response, 0 on errors.
+ */
+uint32_t        dc_save_msg_as_canned_response (dc_context_t* context, uint32_t msg_id, const char* title);
+
+
+/**
+ * Change title, text and file of a canned response.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param canned_response_id ID of the canned response to change.
+ * @param title New title, must not be empty.
+ * @param text New text, may be NULL.
+ * @param file Path of the new file, NULL for none.
+ * @return 1=success, 0=error.
+ */
+int             dc_update_canned_response    (dc_context_t* context, uint32_t canned_response_id, const char* title, const char* text, const char* file);
+
+
+/**
+ * Delete a canned response.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param canned_response_id ID of the canned response to delete.
+ * @return 1=success, 0=error.
+ */
+int             dc_delete_canned_response    (dc_context_t* context, uint32_t canned_response_id);
+
+
+/**
+ * Get the IDs of all canned responses, ordered by title.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @return Array of canned response IDs.
+ *     Must be freed using dc_array_unref() after usage.
+ */
+dc_array_t*     dc_get_canned_responses      (dc_context_t* context);
+
+
+/**
+ * Get the title of a canned response.
+ * To get text and file, use dc_canned_response_to_msg().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param canned_response_id ID of the canned response.
+ * @return The title, empty string if the canned response does not exist.
+ *     Must be released using dc_str_unref().
+ */
+char*           dc_get_canned_response_title (dc_context_t* context, uint32_t canned_response_id);
+
+
+/**
+ * Create a message from a canned response.
+ * The message can be edited and set as draft using dc_set_draft()
+ * or sent using dc_send_msg().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param canned_response_id ID of the canned response.
+ * @return Message object with text and file of the canned response, NULL on errors.
+ *     Must be freed using dc_msg_unref() after usage.
+ */
+dc_msg_t*       dc_canned_response_to_msg    (dc_context_t* context, uint32_t canned_response_id);
+
+
+/**
+ * Send a canned response to a chat.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The chat to send the canned response to.
+ * @param canned_response_id ID of the canned response.
+ * @return ID of the sent message, 0 on errors.
+ */
+uint32_t        dc_send_canned_response      (dc_context_t* context, uint32_t chat_id, uint32_t canned_response_id);
+
+
 #define         DC_GCM_ADDDAYMARKER          0x01
 #define         DC_GCM_INFO_ONLY             0x02
+#define         DC_GCM_CORRECTED_ORDER       0x04
 
 
 /**
@@ -1267,6 +1794,9 @@ dc_msg_t*       dc_get_draft                 (dc_context_t* context, uint32_t ch
  *     be added before each day (regarding the local timezone). Set this to 0 if you do not want this behaviour.
  *     To get the concrete time of the marker, use dc_array_get_timestamp().
  *     If set to DC_GCM_INFO_ONLY, only system messages will be returned, can be combined with DC_GCM_ADDDAYMARKER.
+ *     If set to DC_GCM_CORRECTED_ORDER, messages delivered out of order are sorted
+ *     by the time they were sent once they are noticed or seen,
+ *     can be combined with the other flags, see also dc_repair_msgs_order().
  * @param marker1before Deprecated, set this to 0.
  * @return Array of message IDs, must be dc_array_unref()'d when no longer used.
  */
@@ -1304,6 +1834,57 @@ int             dc_get_msg_cnt               (dc_context_t* context, uint32_t ch
 int             dc_get_fresh_msg_cnt         (dc_context_t* context, uint32_t chat_id);
 
 
+/**
+ * Store the corrected order of messages delivered out of order.
+ *
+ * Fresh messages are always sorted to the end of a chat so they are not overlooked,
+ * even if they were sent before other messages, e.g. when they were queued by a provider
+ * or resent.
+ * Once noticed or seen, dc_get_chat_msgs() with DC_GCM_CORRECTED_ORDER
+ * sorts these messages by the time they were sent.
+ * This function stores this order, so it is also used without the flag and in the chatlist.
+ *
+ * UIs may call this function e.g. when a chat is left,
+ * so that messages do not move while the user reads them.
+ * If messages were moved, #DC_EVENT_MSGS_CHANGED is emitted.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param chat_id The ID of the chat to repair.
+ * @return Number of messages moved. 0 for errors or if nothing was moved.
+ */
+int             dc_repair_msgs_order         (dc_context_t* context, uint32_t chat_id);
+
+
+/**
+ * Get all messages of the thread a message belongs to.
+ *
+ * Threads are built from the `In-Reply-To` chains of the messages inside one chat.
+ * The returned array starts with the thread root
+ * and contains all its direct and indirect replies ordered by timestamp.
+ * Use dc_chat_is_threaded() to check if the user wants to see a chat grouped by threads.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param msg_id The ID of any message of the thread.
+ * @return Array of message IDs, must be dc_array_unref()'d when no longer used.
+ *     On errors, an empty array is returned.
+ */
+dc_array_t*     dc_get_thread                (dc_context_t* context, uint32_t msg_id);
+
+
+/**
+ * Get the number of _fresh_ messages in the thread a message belongs to,
+ * see dc_get_thread() and dc_get_fresh_msg_cnt().
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param msg_id The ID of any message of the thread.
+ * @return Number of fresh messages in the thread. 0 for errors or if there are no fresh messages.
+ */
+int             dc_get_thread_fresh_msg_cnt  (dc_context_t* context, uint32_t msg_id);
+
+
 
 /**
  * Estimate the number of messages that will be deleted
@@ -1343,6 +1924,38 @@ int             dc_estimate_deletion_cnt    (dc_context_t* context, int from_ser
 dc_array_t*     dc_get_fresh_msgs            (dc_context_t* context);
 
 
+/**
+ * Returns the message IDs of all starred messages of any chat,
+ * see dc_set_msg_starred().
+ * The list starts with the most recent starred message.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @return An array of message IDs, must be dc_array_unref()'d when no longer used.
+ *     On errors, the list is empty. NULL is never returned.
+ */
+dc_array_t*     dc_get_starred_msgs          (dc_context_t* context);
+
+
+/**
+ * Star or unstar a message.
+ *
+ * The star is stored as `\Flagged` flag on the IMAP server
+ * and stars set or removed by other devices or other e-mail clients
+ * are imported when flags are synchronized,
+ * so stars are shared with classic e-mail clients.
+ *
+ * Sends out #DC_EVENT_MSGS_CHANGED on success.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param msg_id The ID of the message to star or unstar.
+ * @param starred 1=star the message, 0=remove the star.
+ * @return 1=success, 0=error.
+ */
+int             dc_set_msg_starred           (dc_context_t* context, uint32_t msg_id, int starred);
+
+
 /**
  * Mark all messages in a chat as _noticed_.
  * _Noticed_ messages are no longer _fresh_ and do not count as being unseen
@@ -1360,12 +1973,29 @@ void            dc_marknoticed_chat          (dc_context_t* context, uint32_t ch
 
 
 /**
- * Returns all message IDs of the given types in a given chat or any chat.
- * Typically used to show a gallery.
- * The result must be dc_array_unref()'d
+ * Mark all messages in several chats as _noticed_.
+ * This is the same as calling dc_marknoticed_chat() for every chat,
+ * however, all chats are updated at once,
+ * so this function should be preferred when the user selects many chats.
  *
- * The list is already sorted and starts with the oldest message.
- * Clients should not try to re-sort the list as this would be an expensive action
+ * The event #DC_EVENT_MSGS_NOTICED is emitted for every chat
+ * that had fresh messages.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param chat_ids An array of uint32_t containing the IDs of the chats to mark as noticed.
+ * @param chat_cnt The number of chat IDs in the chat_ids array.
+ */
+void            dc_marknoticed_chats         (dc_context_t* context, const uint32_t* chat_ids, int chat_cnt);
+
+
+/**
+ * Returns all message IDs of the given types in a given chat or any chat.
+ * Typically used to show a gallery.
+ * The result must be dc_array_unref()'d
+ *
+ * The list is already sorted and starts with the oldest message.
+ * Clients should not try to re-sort the list as this would be an expensive action
  * and would result in inconsistencies between clients.
  *
  * @memberof dc_context_t
@@ -1435,6 +2065,78 @@ int             dc_set_chat_protection       (dc_context_t* context, uint32_t ch
 void            dc_set_chat_visibility       (dc_context_t* context, uint32_t chat_id, int visibility);
 
 
+/**
+ * Archive or unarchive several chats at once.
+ *
+ * All chats are updated in a single database transaction
+ * and #DC_EVENT_MSGS_CHANGED is emitted only once,
+ * so this function should be preferred over calling dc_set_chat_visibility()
+ * for every chat when the user selects many chats.
+ *
+ * Unarchiving does not change chats that are not archived,
+ * pinned chats stay pinned.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param chat_ids An array of uint32_t containing the IDs of the chats to archive or unarchive.
+ * @param chat_cnt The number of chat IDs in the chat_ids array.
+ * @param archive 1=archive the chats, 0=unarchive the chats.
+ */
+void            dc_archive_chats             (dc_context_t* context, const uint32_t* chat_ids, int chat_cnt, int archive);
+
+
+/**
+ * Set whether new messages unarchive an archived chat.
+ * By default, any new message unarchives a chat that is not muted.
+ * See @ref DC_ARCHIVE_MODE for the possible modes.
+ *
+ * Sends out #DC_EVENT_CHAT_MODIFIED.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param chat_id The ID of the chat to change the archive mode for.
+ * @param archive_mode One of @ref DC_ARCHIVE_MODE
+ * @return 1=success, 0=error
+ */
+int             dc_set_chat_archive_mode     (dc_context_t* context, uint32_t chat_id, int archive_mode);
+
+
+/**
+ * Enable or disable threaded display of a chat.
+ *
+ * The setting is only stored locally and is not sent to other chat members;
+ * the UI can query it using dc_chat_is_threaded()
+ * and load the threads using dc_get_thread().
+ *
+ * Sends out #DC_EVENT_CHAT_MODIFIED.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param chat_id The ID of the chat to change the setting for.
+ * @param threaded 1=show messages grouped by threads, 0=show messages linearly.
+ */
+void            dc_set_chat_threaded         (dc_context_t* context, uint32_t chat_id, int threaded);
+
+
+/**
+ * Set the signature sent as footer of messages in a chat.
+ * The signature overrides the `selfstatus` set using dc_set_config().
+ *
+ * The setting is synchronized to other devices of the user
+ * if `send_sync_msgs` is enabled.
+ *
+ * Sends out #DC_EVENT_CHAT_MODIFIED.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param chat_id The ID of the chat to change the signature for.
+ * @param signature The signature to use in this chat.
+ *     An empty string disables the footer for this chat,
+ *     NULL uses `selfstatus` again.
+ */
+void            dc_set_chat_signature        (dc_context_t* context, uint32_t chat_id, const char* signature);
+
+
 /**
  * Delete a chat.
  *
@@ -1491,6 +2193,24 @@ void            dc_block_chat                (dc_context_t* context, uint32_t ch
  */
 void            dc_accept_chat               (dc_context_t* context, uint32_t chat_id);
 
+
+/**
+ * Get how likely a contact request is spam.
+ *
+ * The score is computed locally from signals such as failed DKIM,
+ * groups shared with the sender and the text of the latest message.
+ * UIs may use it to order contact requests or to move likely spam out of the way.
+ * Nothing is blocked automatically.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param chat_id The ID of the contact request chat,
+ *     see dc_chat_is_contact_request().
+ * @return Score from 0 (spam unlikely) to 100 (spam likely),
+ *     -1 if the chat is not a contact request of a single contact or on errors.
+ */
+int             dc_get_chat_spam_score       (dc_context_t* context, uint32_t chat_id);
+
 /**
  * Get the contact IDs belonging to a chat.
  *
@@ -1540,6 +2260,21 @@ char*           dc_get_chat_encrinfo (dc_context_t* context, uint32_t chat_id);
  */
 uint32_t dc_get_chat_ephemeral_timer (dc_context_t* context, uint32_t chat_id);
 
+/**
+ * Get the chat members who acknowledged the current ephemeral message timer,
+ * i.e. whose latest message in the chat uses the same timer.
+ *
+ * Members not returned have not sent a message since the timer was changed
+ * or use a client ignoring the timer.
+ * DC_CONTACT_ID_SELF is never returned.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The chat ID.
+ * @return An array of contact IDs; must be freed using dc_array_unref() when done.
+ */
+dc_array_t*     dc_get_ephemeral_timer_acks  (dc_context_t* context, uint32_t chat_id);
+
 /**
  * Search messages containing the given query string.
  * Searching can be done globally (chat_id=0) or in a specified chat only (chat_id set).
@@ -1565,6 +2300,61 @@ uint32_t dc_get_chat_ephemeral_timer (dc_context_t* context, uint32_t chat_id);
 dc_array_t*     dc_search_msgs               (dc_context_t* context, uint32_t chat_id, const char* query);
 
 
+/**
+ * Add a tag to a message in the "saved messages" chat.
+ * Tags are compared case-insensitively and must not contain newlines.
+ *
+ * Sends out #DC_EVENT_MSGS_CHANGED on success.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param msg_id The ID of the saved message to tag.
+ * @param tag The tag to add.
+ * @return 1=success, 0=error, e.g. the message is not in the "saved messages" chat.
+ */
+int             dc_add_saved_tag             (dc_context_t* context, uint32_t msg_id, const char* tag);
+
+
+/**
+ * Remove a tag added by dc_add_saved_tag() from a message.
+ *
+ * Sends out #DC_EVENT_MSGS_CHANGED on success.
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param msg_id The ID of the saved message.
+ * @param tag The tag to remove.
+ * @return 1=success, 0=error.
+ */
+int             dc_remove_saved_tag          (dc_context_t* context, uint32_t msg_id, const char* tag);
+
+
+/**
+ * Get the tags of a message in the "saved messages" chat, see dc_add_saved_tag().
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param msg_id The ID of the saved message.
+ * @return The tags sorted alphabetically and separated by newlines,
+ *     empty string if the message has no tags.
+ *     Must be released using dc_str_unref() after usage.
+ */
+char*           dc_get_saved_tags            (dc_context_t* context, uint32_t msg_id);
+
+
+/**
+ * Get all messages of the "saved messages" chat tagged with the given tag,
+ * see dc_add_saved_tag().
+ *
+ * @memberof dc_context_t
+ * @param context The context object as returned from dc_context_new().
+ * @param tag The tag to search for.
+ * @return An array of message IDs, newest first.
+ *     Must be freed using dc_array_unref() when no longer needed.
+ */
+dc_array_t*     dc_get_saved_by_tag          (dc_context_t* context, const char* tag);
+
+
 /**
  * Get a chat object by a chat ID.
  *
@@ -1610,6 +2400,33 @@ dc_chat_t*      dc_get_chat                  (dc_context_t* context, uint32_t ch
 uint32_t        dc_create_group_chat         (dc_context_t* context, int protect, const char* name);
 
 
+/**
+ * Create a new group chat with initial members and settings.
+ *
+ * Unlike calling dc_create_group_chat(), dc_add_contact_to_chat(),
+ * dc_set_chat_profile_image() and dc_set_chat_ephemeral_timer() one after another,
+ * the group is created at once:
+ * either all settings are applied or no group is created at all.
+ *
+ * The group is in _unpromoted_ state, see dc_create_group_chat();
+ * the first message sent to the group carries the complete member list.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param protect If set to 1 the function creates group with protection initially enabled.
+ *     All members must be verified then.
+ * @param name The name of the group chat to create.
+ * @param contact_ids An array of uint32_t containing the IDs of the members to add.
+ *     DC_CONTACT_ID_SELF is always added and need not be given.
+ *     May be NULL if contact_cnt is 0.
+ * @param contact_cnt The number of contact IDs in the contact_ids array.
+ * @param avatar Full path of the group image, NULL for no image.
+ * @param ephemeral_timer Ephemeral timer in seconds, 0 to disable ephemeral messages.
+ * @return The chat ID of the new group chat, 0 on errors.
+ */
+uint32_t        dc_create_group_chat_ex      (dc_context_t* context, int protect, const char* name, const uint32_t* contact_ids, int contact_cnt, const char* avatar, uint32_t ephemeral_timer);
+
+
 /**
  * Create a new broadcast list.
  *
@@ -1689,6 +2506,52 @@ int             dc_add_contact_to_chat       (dc_context_t* context, uint32_t ch
 int             dc_remove_contact_from_chat  (dc_context_t* context, uint32_t chat_id, uint32_t contact_id);
 
 
+/**
+ * Remove a member from a group and ban them.
+ *
+ * Like dc_remove_contact_from_chat(),
+ * but the contact is not added to the group again,
+ * neither by dc_add_contact_to_chat() nor by messages of other members adding them.
+ * The ban is sent to the other members, so their devices enforce it as well.
+ * As groups have no admins, any member can ban and unban members.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The chat ID to remove the contact from. Must be a group chat.
+ * @param contact_id The contact ID to remove and ban, must not be DC_CONTACT_ID_SELF.
+ * @return 1=member removed and banned, 0=error
+ */
+int             dc_remove_member_and_ban     (dc_context_t* context, uint32_t chat_id, uint32_t contact_id);
+
+
+/**
+ * Lift the ban of a member banned with dc_remove_member_and_ban()
+ * and add them to the group again.
+ *
+ * The unban is sent to the other members with the member-added message,
+ * so their devices lift the ban as well.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The ID of the group.
+ * @param contact_id The contact ID to unban.
+ * @return 1=member unbanned and added, 0=error, e.g. if the contact is not banned
+ */
+int             dc_unban_member              (dc_context_t* context, uint32_t chat_id, uint32_t contact_id);
+
+
+/**
+ * Check if a contact is banned from a group, see dc_remove_member_and_ban().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The chat ID of the group.
+ * @param contact_id The contact ID to check.
+ * @return 1=contact is banned, 0=contact is not banned or error
+ */
+int             dc_is_member_banned          (dc_context_t* context, uint32_t chat_id, uint32_t contact_id);
+
+
 /**
  * Set group name.
  *
@@ -1787,6 +2650,24 @@ int             dc_set_chat_mute_duration             (dc_context_t* context, ui
 char*           dc_get_msg_info              (dc_context_t* context, uint32_t msg_id);
 
 
+/**
+ * Get how the user should be notified about a message.
+ *
+ * The decision considers the mute state of the chat, mentions, replies to own messages,
+ * messages from bots and info messages
+ * as well as the settings `notify_muted_mentions`, `notify_bots` and `quiet_hours`,
+ * so that all platforms behave the same.
+ * The intent is also available in the JSON-RPC variant of #DC_EVENT_INCOMING_MSG.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The message ID, typically from #DC_EVENT_INCOMING_MSG.
+ * @return One of the DC_NOTIFICATION_INTENT_* constants.
+ *     Messages which are not fresh incoming messages return #DC_NOTIFICATION_INTENT_SUPPRESS.
+ */
+int             dc_get_notification_intent   (dc_context_t* context, uint32_t msg_id);
+
+
 /**
  * Get uncut message, if available.
  *
@@ -1837,6 +2718,87 @@ char*           dc_get_msg_info              (dc_context_t* context, uint32_t ms
 char*           dc_get_msg_html              (dc_context_t* context, uint32_t msg_id);
 
 
+/**
+ * Get the untruncated text of a message.
+ *
+ * Received texts that are too long to be displayed in a bubble
+ * are truncated and end with `[...]`, dc_msg_has_html() returns 1 in this case.
+ * While dc_get_msg_html() returns the whole message as HTML,
+ * this function returns the full text as plain text,
+ * e.g. to be copied or shared.
+ * Quotes and footers are removed the same way as for dc_msg_get_text().
+ *
+ * If the message was not truncated, this is the same as dc_msg_get_text().
+ *
+ * Outgoing texts of more than 200 KB
+ * are sent as a `.txt` file with the truncated text as preview.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The message ID for which the full text should be loaded.
+ * @return Untruncated text.
+ *     On errors, an empty string is returned.
+ *     The result must be released using dc_str_unref().
+ */
+char*           dc_get_msg_full_text         (dc_context_t* context, uint32_t msg_id);
+
+
+/**
+ * Report a received message as spam or abuse.
+ *
+ * The report is sent to the address set in the `abuse_addr` config option,
+ * by default `abuse@` the domain of the configured address.
+ * The report is an RFC 5965 feedback report
+ * containing the sender, the Message-ID and the time of arrival of the message.
+ * If the user agrees, the original message is attached.
+ * If the original message is not stored on the device, see the `save_mime_headers` config option,
+ * it is fetched from the server and the report is sent in the background;
+ * if it cannot be fetched, the report is sent without it.
+ *
+ * The report is not shown in any chat.
+ * Each message can be reported only once, see dc_is_msg_reported().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The ID of a received message.
+ * @param category One of the @ref DC_REPORT_CATEGORY constants.
+ * @param include_original 1=attach the original message, 0=do not attach it.
+ * @return 1=report sent or queued, 0=error.
+ */
+int             dc_report_msg                (dc_context_t* context, uint32_t msg_id, int category, int include_original);
+
+
+/**
+ * Check if a message was reported using dc_report_msg().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The message ID.
+ * @return 1=message was reported, 0=message was not reported or error.
+ */
+int             dc_is_msg_reported           (dc_context_t* context, uint32_t msg_id);
+
+
+/**
+ * Get the media of a view-once message, see dc_msg_set_view_once().
+ *
+ * For incoming messages, the file is deleted from the device afterwards
+ * and the message is marked as seen and viewed,
+ * so this function succeeds only once per message.
+ * The media of received view-once messages is kept outside of the blob directory
+ * and dc_msg_get_file() returns an empty string for them,
+ * so UIs have to display the returned data from memory.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param msg_id The ID of the view-once message.
+ * @param ret_bytes A pointer to a size_t. The size of the data will be written here.
+ * @return The media data. NULL on errors, e.g. if the media was already viewed.
+ *     The result must be released using dc_str_unref().
+ */
+char*           dc_open_view_once            (dc_context_t* context, uint32_t msg_id, size_t* ret_bytes);
+
+
 /**
   * Asks the core to start downloading a message fully.
   * This function is typically called when the user hits the "Download" button
@@ -1880,6 +2842,9 @@ char*           dc_get_mime_headers          (dc_context_t* context, uint32_t ms
  * Delete messages. The messages are deleted on the current device and
  * on the IMAP server.
  *
+ * All messages are deleted in a single database transaction
+ * and #DC_EVENT_MSGS_CHANGED is emitted only once.
+ *
  * @memberof dc_context_t
  * @param context The context object.
  * @param msg_ids An array of uint32_t containing all message IDs that should be deleted.
@@ -2095,6 +3060,99 @@ int             dc_get_blocked_cnt           (dc_context_t* context);
 dc_array_t*     dc_get_blocked_contacts      (dc_context_t* context);
 
 
+/**
+ * Export the addresses of all blocked contacts
+ * as a list signed with the own key.
+ *
+ * The list can be imported with dc_import_blocklist()
+ * or published to be subscribed to with dc_add_blocklist_feed().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @return The signed list, NULL on errors.
+ *     Must be released using dc_str_unref() after usage.
+ */
+char*           dc_export_blocklist          (dc_context_t* context);
+
+
+/**
+ * Block the addresses of a list exported by dc_export_blocklist().
+ *
+ * Lists without signature with one address per line are accepted, too.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param data The list.
+ * @return The number of newly blocked contacts, -1 on errors.
+ */
+int             dc_import_blocklist          (dc_context_t* context, const char* data);
+
+
+/**
+ * Subscribe to a blocklist maintained by a contact, e.g. by a bot of a community.
+ *
+ * The list is fetched from `url` once a day
+ * and must be exported by dc_export_blocklist() of the contact.
+ * Messages from unknown senders on the list go to a blocked chat and are not shown;
+ * contacts the user already talked to are not affected.
+ * Domain entries covering the user's own provider are ignored.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param url HTTPS URL of the list.
+ * @param contact_id The contact the list must be signed by.
+ *     The key of the contact must be known.
+ * @return The ID of the feed, 0 on errors.
+ */
+uint32_t        dc_add_blocklist_feed        (dc_context_t* context, const char* url, uint32_t contact_id);
+
+
+/**
+ * Get the blocklists subscribed to with dc_add_blocklist_feed().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @return An array of feed IDs. Must be dc_array_unref()'d after usage.
+ */
+dc_array_t*     dc_get_blocklist_feeds       (dc_context_t* context);
+
+
+/**
+ * Get the URL of a blocklist feed.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param feed_id The ID of the feed as returned by dc_get_blocklist_feeds().
+ * @return The URL, NULL if the feed does not exist.
+ *     Must be released using dc_str_unref() after usage.
+ */
+char*           dc_get_blocklist_feed_url    (dc_context_t* context, uint32_t feed_id);
+
+
+/**
+ * Get the error of the last update of a blocklist feed.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param feed_id The ID of the feed as returned by dc_get_blocklist_feeds().
+ * @return The error, empty string if the last update succeeded
+ *     or the feed was not fetched yet.
+ *     Must be released using dc_str_unref() after usage.
+ */
+char*           dc_get_blocklist_feed_error  (dc_context_t* context, uint32_t feed_id);
+
+
+/**
+ * Unsubscribe from a blocklist.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param feed_id The ID of the feed as returned by dc_get_blocklist_feeds().
+ * @return 1=success, 0=error
+ */
+int             dc_delete_blocklist_feed     (dc_context_t* context, uint32_t feed_id);
+
+
 /**
  * Block or unblock a contact.
  * May result in a #DC_EVENT_CONTACTS_CHANGED event.
@@ -2107,6 +3165,22 @@ dc_array_t*     dc_get_blocked_contacts      (dc_context_t* context);
 void            dc_block_contact             (dc_context_t* context, uint32_t contact_id, int block);
 
 
+/**
+ * Set whether our last-seen status is shared with a contact.
+ *
+ * If not shared, messages to the contact, including messages to groups the contact is member of,
+ * are sent as with the config option `share_last_seen` set to 0.
+ * In return, dc_contact_get_last_seen() returns 0 for the contact.
+ * May result in a #DC_EVENT_CONTACTS_CHANGED event.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param contact_id The ID of the contact.
+ * @param share 1=share last-seen status (default), 0=do not share last-seen status
+ */
+void            dc_set_contact_share_last_seen (dc_context_t* context, uint32_t contact_id, int share);
+
+
 /**
  * Get encryption info for a contact.
  * Get a multi-line encryption info, containing your fingerprint and the
@@ -2366,6 +3440,7 @@ void            dc_stop_ongoing_process      (dc_context_t* context);
 #define         DC_QR_REVIVE_VERIFYCONTACT   510
 #define         DC_QR_REVIVE_VERIFYGROUP     512 // text1=groupname
 #define         DC_QR_LOGIN                  520 // text1=email_address
+#define         DC_QR_PROFILE                530 // text1=email_address
 
 /**
  * Check a scanned QR code.
@@ -2446,6 +3521,12 @@ void            dc_stop_ongoing_process      (dc_context_t* context);
  *   ask the user if they want to login with the email_address,
  *   if so, call dc_set_config_from_qr() and then dc_configure().
  *
+ * - DC_QR_PROFILE with dc_lot_t::text1=email_address:
+ *   an account provisioning profile with server settings, display name, avatar
+ *   and initial contacts and groups;
+ *   ask the user if they want to set up the account with the email_address,
+ *   if so, call dc_set_config_from_qr() and then dc_configure().
+ *
  * @memberof dc_context_t
  * @param context The context object.
  * @param qr The text of the scanned QR code.
@@ -2485,39 +3566,232 @@ char*           dc_get_securejoin_qr         (dc_context_t* context, uint32_t ch
  *
  * @memberof dc_context_t
  * @param context The context object.
- * @param chat_id group-chat-id for secure-join or 0 for setup-contact,
- *     see dc_get_securejoin_qr() for details.
- * @return SVG-Image with the QR code.
- *     On errors, an empty string is returned.
- *     The returned string must be released using dc_str_unref() after usage.
+ * @param chat_id group-chat-id for secure-join or 0 for setup-contact,
+ *     see dc_get_securejoin_qr() for details.
+ * @return SVG-Image with the QR code.
+ *     On errors, an empty string is returned.
+ *     The returned string must be released using dc_str_unref() after usage.
+ */
+char*           dc_get_securejoin_qr_svg         (dc_context_t* context, uint32_t chat_id);
+
+/**
+ * Continue a Setup-Contact or Verified-Group-Invite protocol
+ * started on another device with dc_get_securejoin_qr().
+ * This function is typically called when dc_check_qr() returns
+ * lot.state=DC_QR_ASK_VERIFYCONTACT or lot.state=DC_QR_ASK_VERIFYGROUP.
+ *
+ * The function returns immediately and the handshake runs in background,
+ * sending and receiving several messages.
+ * During the handshake, info messages are added to the chat,
+ * showing progress, success or errors.
+ *
+ * Subsequent calls of dc_join_securejoin() will abort previous, unfinished handshakes.
+ *
+ * See https://countermitm.readthedocs.io/en/latest/new.html
+ * for details about both protocols.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param qr The text of the scanned QR code. Typically, the same string as given
+ *     to dc_check_qr().
+ * @return The chat ID of the joined chat, the UI may redirect to the this chat.
+ *     On errors, 0 is returned, however, most errors will happen during handshake later on.
+ *     A returned chat ID does not guarantee that the chat is protected or the belonging contact is verified.
+ */
+uint32_t        dc_join_securejoin           (dc_context_t* context, const char* qr);
+
+
+/**
+ * Invite a verified contact to a protected group without a QR code.
+ *
+ * The contact receives a DC_EVENT_SECUREJOIN_INVITE_RECEIVED event
+ * and is added to the group after calling dc_accept_group_invite().
+ * DC_EVENT_SECUREJOIN_INVITER_PROGRESS events are emitted when the contact is added.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The ID of the protected group, self must be a member.
+ * @param contact_id The ID of the contact to invite, the contact must be verified.
+ * @return 1 on success, 0 on errors.
+ */
+int             dc_forward_group_invite      (dc_context_t* context, uint32_t chat_id, uint32_t contact_id);
+
+
+/**
+ * Get the name of the group of an invite
+ * received with DC_EVENT_SECUREJOIN_INVITE_RECEIVED.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param invite_id The ID of the invite.
+ * @return The name of the group, NULL if the invite does not exist.
+ *     The returned string must be released using dc_str_unref() after usage.
+ */
+char*           dc_get_group_invite_name     (dc_context_t* context, uint32_t invite_id);
+
+
+/**
+ * Accept an invite received with DC_EVENT_SECUREJOIN_INVITE_RECEIVED.
+ *
+ * The group is created once the inviting contact has added us to it.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param invite_id The ID of the invite.
+ * @return 1 on success, 0 on errors.
+ */
+int             dc_accept_group_invite       (dc_context_t* context, uint32_t invite_id);
+
+
+/**
+ * Enable or disable the join gate of a group.
+ *
+ * QR codes of groups may be published, where spam bots can scan them as well.
+ * With the join gate enabled, contacts joining via QR code
+ * receive #DC_EVENT_SECUREJOIN_CHALLENGE_RECEIVED and are only added to the group
+ * after selecting the right option.
+ * After 3 wrong answers, further requests of the contact to join the group are refused.
+ * For contacts using apps that cannot answer challenges,
+ * an info message is added to the group instead, so they can be added manually.
+ * Contacts added otherwise are not affected.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The ID of the group.
+ * @param enabled 1=enable the join gate, 0=disable it.
+ * @return 1 on success, 0 on errors.
+ */
+int             dc_set_join_gate             (dc_context_t* context, uint32_t chat_id, int enabled);
+
+
+/**
+ * Check if the join gate of a group is enabled, see dc_set_join_gate().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The ID of the group.
+ * @return 1=join gate enabled, 0=join gate disabled or error.
+ */
+int             dc_is_join_gate_enabled      (dc_context_t* context, uint32_t chat_id);
+
+
+/**
+ * Get the question of a challenge received with #DC_EVENT_SECUREJOIN_CHALLENGE_RECEIVED.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param challenge_id The ID of the challenge.
+ * @return The question, NULL if the challenge does not exist.
+ *     The returned string must be released using dc_str_unref() after usage.
+ */
+char*           dc_get_join_challenge_question (dc_context_t* context, uint32_t challenge_id);
+
+
+/**
+ * Get the options to answer a challenge received with #DC_EVENT_SECUREJOIN_CHALLENGE_RECEIVED.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param challenge_id The ID of the challenge.
+ * @return The options separated by spaces, in the order to show them,
+ *     NULL if the challenge does not exist.
+ *     The returned string must be released using dc_str_unref() after usage.
+ */
+char*           dc_get_join_challenge_options (dc_context_t* context, uint32_t challenge_id);
+
+
+/**
+ * Answer a challenge received with #DC_EVENT_SECUREJOIN_CHALLENGE_RECEIVED.
+ *
+ * If the answer is wrong, the inviter may send a new challenge.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param challenge_id The ID of the challenge.
+ * @param answer The selected option, one of dc_get_join_challenge_options().
+ * @return 1 on success, 0 on errors.
+ */
+int             dc_answer_join_challenge     (dc_context_t* context, uint32_t challenge_id, const char* answer);
+
+
+/**
+ * Create an invite link for a group which can be used by a limited number of contacts.
+ *
+ * Unlike the QR code returned by dc_get_securejoin_qr(),
+ * each call creates a new link, which can be deleted using dc_delete_invite_link()
+ * without affecting other links.
+ * Invite links are not synchronized to other devices.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The ID of the group.
+ * @param max_uses Maximum number of contacts who may join through the link, must be at least 1.
+ * @return The QR code of the link, to be passed to dc_join_securejoin() by the joiner,
+ *     NULL on errors.
+ *     The returned string must be released using dc_str_unref() after usage.
+ */
+char*           dc_create_invite_link        (dc_context_t* context, uint32_t chat_id, int max_uses);
+
+
+/**
+ * Get the invite links of a group created using dc_create_invite_link()
+ * which can still be used.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param chat_id The ID of the group.
+ * @return An array of invite link IDs, newest first.
+ *     Must be released using dc_array_unref() after usage.
+ */
+dc_array_t*     dc_get_invite_links          (dc_context_t* context, uint32_t chat_id);
+
+
+/**
+ * Get the QR code of an invite link.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param link_id The ID of the invite link as returned by dc_get_invite_links().
+ * @return The QR code, NULL if the link does not exist.
+ *     The returned string must be released using dc_str_unref() after usage.
+ */
+char*           dc_get_invite_link_qr        (dc_context_t* context, uint32_t link_id);
+
+
+/**
+ * Get the maximum number of contacts who may join through an invite link.
+ * The number of contacts who joined already
+ * is the count of dc_get_invite_link_joins().
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param link_id The ID of the invite link as returned by dc_get_invite_links().
+ * @return The maximum number of uses, 0 if the link does not exist.
+ */
+int             dc_get_invite_link_max_uses  (dc_context_t* context, uint32_t link_id);
+
+
+/**
+ * Get the contacts who joined a group through an invite link.
+ *
+ * @memberof dc_context_t
+ * @param context The context object.
+ * @param link_id The ID of the invite link as returned by dc_get_invite_links().
+ * @return An array of contact IDs in the order the contacts joined.
+ *     Must be released using dc_array_unref() after usage.
  */
-char*           dc_get_securejoin_qr_svg         (dc_context_t* context, uint32_t chat_id);
+dc_array_t*     dc_get_invite_link_joins     (dc_context_t* context, uint32_t link_id);
+
 
 /**
- * Continue a Setup-Contact or Verified-Group-Invite protocol
- * started on another device with dc_get_securejoin_qr().
- * This function is typically called when dc_check_qr() returns
- * lot.state=DC_QR_ASK_VERIFYCONTACT or lot.state=DC_QR_ASK_VERIFYGROUP.
- *
- * The function returns immediately and the handshake runs in background,
- * sending and receiving several messages.
- * During the handshake, info messages are added to the chat,
- * showing progress, success or errors.
- *
- * Subsequent calls of dc_join_securejoin() will abort previous, unfinished handshakes.
- *
- * See https://countermitm.readthedocs.io/en/latest/new.html
- * for details about both protocols.
+ * Delete an invite link, it cannot be used to join the group anymore.
  *
  * @memberof dc_context_t
  * @param context The context object.
- * @param qr The text of the scanned QR code. Typically, the same string as given
- *     to dc_check_qr().
- * @return The chat ID of the joined chat, the UI may redirect to the this chat.
- *     On errors, 0 is returned, however, most errors will happen during handshake later on.
- *     A returned chat ID does not guarantee that the chat is protected or the belonging contact is verified.
+ * @param link_id The ID of the invite link as returned by dc_get_invite_links().
+ * @return 1 on success, 0 on errors.
  */
-uint32_t        dc_join_securejoin           (dc_context_t* context, const char* qr);
+int             dc_delete_invite_link        (dc_context_t* context, uint32_t link_id);
 
 
 // location streaming
@@ -2936,6 +4210,24 @@ uint32_t       dc_accounts_migrate_account      (dc_accounts_t* accounts, const
 int            dc_accounts_remove_account       (dc_accounts_t* accounts, uint32_t account_id);
 
 
+/**
+ * Remove an account from the account manager, optionally shredding its data.
+ *
+ * Works like dc_accounts_remove_account(),
+ * but if `shred` is set, the database and all blobs are overwritten with zeros
+ * before they are deleted, so that the data cannot be recovered from the file system.
+ * On flash storage this is best effort
+ * as the storage may keep copies of overwritten blocks.
+ *
+ * @memberof dc_accounts_t
+ * @param accounts The account manager as created by dc_accounts_new().
+ * @param account_id The account ID as returned e.g. by dc_accounts_add_account().
+ * @param shred 1=overwrite the account data before deleting it, 0=just delete it.
+ * @return 1=success, 0=error
+ */
+int            dc_accounts_purge_account        (dc_accounts_t* accounts, uint32_t account_id, int shred);
+
+
 /**
  * List all accounts.
  *
@@ -3057,6 +4349,58 @@ void           dc_accounts_maybe_network        (dc_accounts_t* accounts);
 void           dc_accounts_maybe_network_lost    (dc_accounts_t* accounts);
 
 
+/**
+ * Coalesce #DC_EVENT_MSGS_CHANGED and #DC_EVENT_CHAT_MODIFIED events
+ * of the same chat, e.g. to keep the UI responsive while thousands of messages are deleted.
+ *
+ * The first event of a chat is emitted immediately,
+ * further events of the chat within the interval are emitted as a single event at its end.
+ * #DC_EVENT_MSGS_CHANGED events of different messages are merged into one with `msg_id` 0,
+ * so all messages of the chat should be reloaded.
+ *
+ * By default, events are not coalesced.
+ *
+ * @memberof dc_accounts_t
+ * @param accounts The account manager as created by dc_accounts_new().
+ * @param milliseconds The interval in milliseconds, 0 to disable coalescing.
+ */
+void           dc_accounts_set_event_coalescing_interval (dc_accounts_t* accounts, uint32_t milliseconds);
+
+
+/**
+ * Lock all encrypted accounts, e.g. when the app lock of the UI is activated.
+ *
+ * IO is stopped and the databases are closed,
+ * so neither the data nor the database keys stay in memory
+ * until dc_accounts_unlock() is called with the correct passphrase.
+ * Unencrypted accounts cannot be protected and stay open.
+ *
+ * Emits #DC_EVENT_ACCOUNTS_LOCKED.
+ *
+ * @memberof dc_accounts_t
+ * @param accounts The account manager as created by dc_accounts_new().
+ * @return 1=success, 0=error, e.g. if the accounts are already locked.
+ */
+int            dc_accounts_lock                 (dc_accounts_t* accounts);
+
+
+/**
+ * Unlock the accounts locked by dc_accounts_lock().
+ *
+ * All locked accounts that can be decrypted with the passphrase are opened again
+ * and IO is restarted if it was running before locking.
+ * If accounts use different passphrases, call this function once for each passphrase.
+ *
+ * Emits #DC_EVENT_ACCOUNTS_UNLOCKED once no locked accounts are left.
+ *
+ * @memberof dc_accounts_t
+ * @param accounts The account manager as created by dc_accounts_new().
+ * @param passphrase The passphrase of the locked accounts.
+ * @return 1=all accounts are unlocked, 0=some accounts are still locked or an error occurred.
+ */
+int            dc_accounts_unlock               (dc_accounts_t* accounts, const char* passphrase);
+
+
 /**
  * Create the event emitter that is used to receive events.
  *
@@ -3552,6 +4896,17 @@ uint32_t        dc_chat_get_color            (const dc_chat_t* chat);
 int             dc_chat_get_visibility       (const dc_chat_t* chat);
 
 
+/**
+ * Get whether new messages unarchive the chat,
+ * see dc_set_chat_archive_mode().
+ *
+ * @memberof dc_chat_t
+ * @param chat The chat object.
+ * @return One of @ref DC_ARCHIVE_MODE
+ */
+int             dc_chat_get_archive_mode     (const dc_chat_t* chat);
+
+
 /**
  * Check if a chat is a contact request chat.
  *
@@ -3622,6 +4977,29 @@ int             dc_chat_is_self_talk         (const dc_chat_t* chat);
 int             dc_chat_is_device_talk       (const dc_chat_t* chat);
 
 
+/**
+ * Check if the chat should be displayed grouped by threads.
+ * The setting can be changed using dc_set_chat_threaded().
+ *
+ * @memberof dc_chat_t
+ * @param chat The chat object.
+ * @return 1=chat is threaded, 0=chat is displayed linearly.
+ */
+int             dc_chat_is_threaded          (const dc_chat_t* chat);
+
+
+/**
+ * Get the signature set for the chat using dc_set_chat_signature().
+ *
+ * @memberof dc_chat_t
+ * @param chat The chat object.
+ * @return The signature, an empty string if the footer is disabled for this chat,
+ *     NULL if `selfstatus` is used.
+ *     Must be released using dc_str_unref() after usage.
+ */
+char*           dc_chat_get_signature        (const dc_chat_t* chat);
+
+
 /**
  * Check if messages can be sent to a given chat.
  * This is not true e.g. for contact requests or for the device-talk, cmp. dc_chat_is_device_talk().
@@ -3649,6 +5027,19 @@ int             dc_chat_can_send              (const dc_chat_t* chat);
 int             dc_chat_is_protected         (const dc_chat_t* chat);
 
 
+/**
+ * Check if the chat name looks like an attempt to impersonate someone,
+ * e.g. a word of the name mixes Latin and Cyrillic letters
+ * or the name contains invisible characters.
+ * UIs may show a warning next to the name in this case.
+ *
+ * @memberof dc_chat_t
+ * @param chat The chat object.
+ * @return 1=name is suspicious, 0=name is not suspicious.
+ */
+int             dc_chat_is_name_suspicious   (const dc_chat_t* chat);
+
+
 /**
  * Check if locations are sent to the chat
  * at the time the object was created using dc_get_chat().
@@ -3760,6 +5151,42 @@ uint32_t        dc_msg_get_from_id            (const dc_msg_t* msg);
 uint32_t        dc_msg_get_chat_id            (const dc_msg_t* msg);
 
 
+/**
+ * Get the ID of the chat a message in the "saved messages" chat was saved from.
+ * The information is stored when forwarding a message to the "saved messages" chat
+ * and is not sent to other devices.
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return The ID of the original chat, 0 if the message was not saved this way.
+ */
+uint32_t        dc_msg_get_saved_from_chat_id (const dc_msg_t* msg);
+
+
+/**
+ * Get the ID of the original author of a message in the "saved messages" chat,
+ * see dc_msg_get_saved_from_chat_id().
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return The ID of the original author, 0 if the message was not saved this way.
+ */
+uint32_t        dc_msg_get_saved_from_contact_id (const dc_msg_t* msg);
+
+
+/**
+ * Get the metadata removed from an outgoing image,
+ * see dc_set_config()-option `strip_exif`.
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return Space-separated names of the removed metadata, e.g. `GPSLatitude Model`,
+ *     empty string if nothing was removed.
+ *     Must be released using dc_str_unref() after usage.
+ */
+char*           dc_msg_get_removed_metadata  (const dc_msg_t* msg);
+
+
 /**
  * Get the type of the message.
  *
@@ -3922,6 +5349,32 @@ char*           dc_msg_get_subject            (const dc_msg_t* msg);
 char*           dc_msg_get_file               (const dc_msg_t* msg);
 
 
+/**
+ * Get the number of files of a message.
+ *
+ * Messages may have several files if they were composed with dc_msg_add_attachment().
+ * The first file is the one returned by dc_msg_get_file().
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return The number of files, 0 if the message has no file.
+ */
+int             dc_msg_get_attachment_cnt     (const dc_msg_t* msg);
+
+
+/**
+ * Get the full path of a file of a message.
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @param index The index of the file, from 0 to dc_msg_get_attachment_cnt()-1.
+ * @return The full path of the file.
+ *     If there is no file with the given index, an empty string is returned.
+ *     NULL is never returned and the returned value must be released using dc_str_unref().
+ */
+char*           dc_msg_get_attachment         (const dc_msg_t* msg, int index);
+
+
 /**
  * Get a base file name without the path. The base file name includes the extension; the path
  * is not returned. To get the full path, use dc_msg_get_file().
@@ -3988,6 +5441,15 @@ char*             dc_msg_get_webxdc_blob      (const dc_msg_t* msg, const char*
  *   currently, this is only true for encrypted Webxdc's in the self chat
  *   that have requested internet access in the manifest.
  *   this is useful for development and maybe for internal integrations at some point.
+ * - max_update_size:
+ *   maximal size in bytes of a single status update as declared in the manifest,
+ *   null if the webxdc declares no limit.
+ *   Larger updates are rejected by dc_send_webxdc_status_update()
+ *   and ignored when received.
+ * - unread_info_cnt:
+ *   number of info messages sent by other members
+ *   since dc_mark_webxdc_infos_seen() was called,
+ *   UIs may show it as a badge on the app icon.
  *
  * @memberof dc_msg_t
  * @param msg The webxdc instance.
@@ -3998,6 +5460,21 @@ char*             dc_msg_get_webxdc_blob      (const dc_msg_t* msg, const char*
 char*             dc_msg_get_webxdc_info      (const dc_msg_t* msg);
 
 
+/**
+ * Get the `href` of a webxdc info message.
+ *
+ * If the user clicks the info message,
+ * UIs should open the webxdc instance returned by dc_msg_get_parent()
+ * and pass the `href` to it, e.g. as the URL fragment.
+ *
+ * @memberof dc_msg_t
+ * @param msg The info message object.
+ * @return The `href` or NULL if the info message has none.
+ *     Must be freed using dc_str_unref().
+ */
+char*             dc_msg_get_webxdc_href      (const dc_msg_t* msg);
+
+
 /**
  * Get the size of the file. Returns the size of the file associated with a
  * message, if applicable.
@@ -4011,6 +5488,28 @@ char*             dc_msg_get_webxdc_info      (const dc_msg_t* msg);
 uint64_t        dc_msg_get_filebytes          (const dc_msg_t* msg);
 
 
+/**
+ * Get the name of a file offered peer-to-peer by the message,
+ * see dc_send_file_p2p() and dc_download_file_p2p().
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return The file name or NULL if the message does not offer a file peer-to-peer.
+ *     The returned value must be released using dc_str_unref().
+ */
+char*           dc_msg_get_p2p_filename       (const dc_msg_t* msg);
+
+
+/**
+ * Get the size of a file offered peer-to-peer by the message.
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return The file size in bytes, 0 if the message does not offer a file peer-to-peer.
+ */
+uint64_t        dc_msg_get_p2p_filebytes      (const dc_msg_t* msg);
+
+
 /**
  * Get the width of an image or a video. The width is returned in pixels.
  * If the width is unknown or if the associated file is no image or video file,
@@ -4049,6 +5548,7 @@ int             dc_msg_get_height             (const dc_msg_t* msg);
  * Get the duration of audio or video. The duration is returned in milliseconds (ms).
  * If the duration is unknown or if the associated file is no audio or video file,
  * 0 is returned.
+ * For records of accepted calls (#DC_MSG_CALL), the duration of the call is returned.
  *
  * See also dc_msg_get_width() and dc_msg_get_height().
  *
@@ -4059,6 +5559,34 @@ int             dc_msg_get_height             (const dc_msg_t* msg);
 int             dc_msg_get_duration           (const dc_msg_t* msg);
 
 
+/**
+ * Get the state of a call record of type #DC_MSG_CALL
+ * or of a call offered by dc_send_call_offer().
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return One of the @ref DC_CALL_STATE constants,
+ *     -1 if the message is no call record.
+ */
+int             dc_msg_get_call_state         (const dc_msg_t* msg);
+
+
+/**
+ * Get the delay of a message delivered late.
+ *
+ * Messages are considered late if they reached the provider
+ * more than two hours after they were sent, e.g. because they were queued.
+ * As such messages may appear out of order,
+ * UIs may show a "delivered late" marker beside them.
+ * Statistics about late deliveries per domain are shown in dc_get_connectivity_html().
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return The delay in seconds, 0 if the message was not delivered late.
+ */
+int64_t         dc_msg_get_delivery_delay     (const dc_msg_t* msg);
+
+
 /**
  * Check if a padlock should be shown beside the message.
  *
@@ -4177,6 +5705,30 @@ char*           dc_msg_get_summarytext        (const dc_msg_t* msg, int approx_c
 char*           dc_msg_get_override_sender_name(const dc_msg_t* msg);
 
 
+/**
+ * Get the action a device message offers to the user.
+ *
+ * UIs may render device messages with an action as a card
+ * with a button triggering the action.
+ * Device messages with actions always have a text, so UIs not supporting actions
+ * or not knowing the returned action can show them as usual.
+ *
+ * The following actions are defined, more may be added in the future:
+ *
+ * - `backup` - create a backup
+ * - `notification_settings` - open the notification settings
+ * - `account_settings` - open the account settings, e.g. to change the password
+ * - `manage_storage` - free up storage, e.g. by deleting old messages
+ * - `update_app` - update the app
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return The action or NULL if the message offers no action.
+ *     The returned string must be released using dc_str_unref().
+ */
+char*           dc_msg_get_device_action      (const dc_msg_t* msg);
+
+
 
 /**
  * Check if a message has a deviating timestamp.
@@ -4239,6 +5791,40 @@ int             dc_msg_is_sent                (const dc_msg_t* msg);
 int             dc_msg_is_forwarded           (const dc_msg_t* msg);
 
 
+/**
+ * Check if the message is starred, see dc_set_msg_starred().
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return 1=message is starred, 0=message is not starred.
+ */
+int             dc_msg_is_starred             (const dc_msg_t* msg);
+
+
+/**
+ * Check if the message is a view-once message, see dc_msg_set_view_once().
+ *
+ * The UI should not show the media of view-once messages in the chat view
+ * but offer to open them using dc_open_view_once().
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return 1=message is a view-once message, 0=message is a normal message.
+ */
+int             dc_msg_is_view_once           (const dc_msg_t* msg);
+
+
+/**
+ * Check if the media of a received view-once message was already viewed
+ * and is deleted from the device.
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return 1=media was viewed, 0=media was not viewed yet or message is not a view-once message.
+ */
+int             dc_msg_is_view_once_viewed    (const dc_msg_t* msg);
+
+
 /**
  * Check if the message is an informational message, created by the
  * device or by another users. Such messages are not "typed" by the user but
@@ -4283,6 +5869,23 @@ int             dc_msg_is_info                (const dc_msg_t* msg);
 int             dc_msg_get_info_type          (const dc_msg_t* msg);
 
 
+/**
+ * Get the group of consecutive informational messages of the same type
+ * the message belongs to, e.g. several "member added" messages in a row.
+ *
+ * UIs can collapse consecutive messages with the same group
+ * and show them expanded on tap.
+ * The group is updated when new informational messages are added,
+ * so UIs should reload the messages on #DC_EVENT_MSGS_CHANGED as usual.
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return ID of the first message of the group,
+ *     0 if the message is not part of a group of informational messages.
+ */
+uint32_t        dc_msg_get_info_group         (const dc_msg_t* msg);
+
+
 // DC_INFO* uses the same values as SystemMessage in rust-land
 #define         DC_INFO_UNKNOWN                    0
 #define         DC_INFO_GROUP_NAME_CHANGED         2
@@ -4363,6 +5966,37 @@ char*           dc_msg_get_setupcodebegin     (const dc_msg_t* msg);
 char*           dc_msg_get_videochat_url (const dc_msg_t* msg);
 
 
+/**
+ * Get the room of a videochat invitation.
+ *
+ * The room is parsed from the URL returned by dc_msg_get_videochat_url(),
+ * this allows UIs to join the videochat using a native client.
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return The room name.
+ *     If the message is no videochat invitation or the URL contains no room, NULL is returned.
+ *     Must be released using dc_str_unref() when done.
+ */
+char*           dc_msg_get_videochat_room (const dc_msg_t* msg);
+
+
+/**
+ * Get the grouping ID of the album a message belongs to.
+ *
+ * Messages with the same grouping ID were sent with dc_send_album()
+ * and should be shown as one group, e.g. as a grid of images in one bubble.
+ * Use dc_get_album_msgs() to get all messages of the album.
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @return The grouping ID.
+ *     If the message does not belong to an album, NULL is returned.
+ *     Must be released using dc_str_unref() when done.
+ */
+char*           dc_msg_get_album_id       (const dc_msg_t* msg);
+
+
 /**
  * Gets the error status of the message.
  * If there is no error associated with the message, NULL is returned.
@@ -4482,6 +6116,20 @@ int dc_msg_get_download_state (const dc_msg_t* msg);
 void            dc_msg_set_text               (dc_msg_t* msg, const char* text);
 
 
+/**
+ * Set the action a message added by dc_add_device_msg() offers,
+ * see dc_msg_get_device_action() for the defined actions.
+ * Unknown actions are ignored.
+ *
+ * If no text is set, dc_add_device_msg() sets the text to a description of the action.
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @param action The action, e.g. `backup`.
+ */
+void            dc_msg_set_device_action      (dc_msg_t* msg, const char* action);
+
+
 /**
  * Set the HTML part of a message object.
  * As for all other dc_msg_t setters,
@@ -4551,6 +6199,20 @@ void            dc_msg_set_override_sender_name(dc_msg_t* msg, const char* name)
 void            dc_msg_set_file               (dc_msg_t* msg, const char* file, const char* filemime);
 
 
+/**
+ * Add another file to a message object which already has a file set with dc_msg_set_file().
+ *
+ * All files are sent in one message, as classic email clients do,
+ * and can be accessed with dc_msg_get_attachment() then.
+ * As for dc_msg_set_file(), the file is not checked or copied before dc_send_msg().
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @param file The full path of the file to add.
+ */
+void            dc_msg_add_attachment         (dc_msg_t* msg, const char* file);
+
+
 /**
  * Set the dimensions associated with message object.
  * Typically this is the width and the height of an image or video associated using dc_msg_set_file().
@@ -4571,9 +6233,24 @@ void            dc_msg_set_dimension          (dc_msg_t* msg, int width, int hei
  *
  * @memberof dc_msg_t
  * @param msg The message object.
- * @param duration The duration in milliseconds. 0 if you don't know or don't care.
+ * @param duration The duration in milliseconds. 0 if you don't know or don't care.
+ */
+void            dc_msg_set_duration           (dc_msg_t* msg, int duration);
+
+
+/**
+ * Mark an image, GIF, voice or video message as view-once.
+ *
+ * The receiving device deletes the media after it was viewed once using dc_open_view_once().
+ * The text of view-once messages is not sent,
+ * other clients get a text explaining the message instead.
+ * This does not alter any information in the database; this may be done by dc_send_msg() later.
+ *
+ * @memberof dc_msg_t
+ * @param msg The message object.
+ * @param view_once 1=message can be viewed only once, 0=normal message.
  */
-void            dc_msg_set_duration           (dc_msg_t* msg, int duration);
+void            dc_msg_set_view_once          (dc_msg_t* msg, int view_once);
 
 
 /**
@@ -4854,6 +6531,22 @@ char*           dc_contact_get_name_n_addr   (const dc_contact_t* contact);
 char*           dc_contact_get_profile_image (const dc_contact_t* contact);
 
 
+/**
+ * Get a small thumbnail of the contact's profile image.
+ * Use this instead of dc_contact_get_profile_image()
+ * to show avatars in lists.
+ * The thumbnail is created on first use
+ * and its path does not change as long as the profile image does not change.
+ *
+ * @memberof dc_contact_t
+ * @param contact The contact object.
+ * @return The path and the file of the thumbnail, if any.
+ *     NULL otherwise.
+ *     Must be released using dc_str_unref() after usage.
+ */
+char*           dc_contact_get_profile_image_thumbnail (const dc_contact_t* contact);
+
+
 /**
  * Get a color for the contact.
  * The color is calculated from the contact's e-mail address
@@ -4919,6 +6612,32 @@ int             dc_contact_was_seen_recently (const dc_contact_t* contact);
 int             dc_contact_is_blocked        (const dc_contact_t* contact);
 
 
+/**
+ * Check if our last-seen status is shared with a contact,
+ * see dc_set_contact_share_last_seen().
+ *
+ * @memberof dc_contact_t
+ * @param contact The contact object.
+ * @return 1=last-seen status is shared, 0=last-seen status is not shared.
+ */
+int             dc_contact_shares_last_seen  (const dc_contact_t* contact);
+
+
+/**
+ * Check if the name set by the contact looks like an attempt to impersonate someone,
+ * e.g. a word of the name mixes Latin and Cyrillic letters
+ * or the name contains invisible characters.
+ * UIs may show a warning next to the name in this case.
+ *
+ * Names given to the contact by the user are never flagged.
+ *
+ * @memberof dc_contact_t
+ * @param contact The contact object.
+ * @return 1=name is suspicious, 0=name is not suspicious.
+ */
+int             dc_contact_is_name_suspicious (const dc_contact_t* contact);
+
+
 /**
  * Check if a contact was verified. E.g. by a secure-join QR code scan
  * and if the key has not changed since this verification.
@@ -5073,6 +6792,10 @@ void            dc_provider_unref                     (dc_provider_t* provider);
 #define         DC_TEXT1_USERNAME  2
 #define         DC_TEXT1_SELF      3
 
+#define         DC_TEXT_DIRECTION_NEUTRAL 0
+#define         DC_TEXT_DIRECTION_LTR     1
+#define         DC_TEXT_DIRECTION_RTL     2
+
 
 /**
  * Frees an object containing a set of parameters.
@@ -5110,6 +6833,22 @@ char*           dc_lot_get_text1         (const dc_lot_t* lot);
 char*           dc_lot_get_text2         (const dc_lot_t* lot);
 
 
+/**
+ * Get the direction of the second string
+ * as determined by its first letter.
+ * For summaries returned by dc_chatlist_get_summary() or dc_msg_get_summary(),
+ * this can be used to align the summary text.
+ * Names and other user-provided parts embedded by the core
+ * are isolated using Unicode bidi isolates and do not affect the direction.
+ *
+ * @memberof dc_lot_t
+ * @param lot The lot object.
+ * @return One of DC_TEXT_DIRECTION_LTR, DC_TEXT_DIRECTION_RTL
+ *     or DC_TEXT_DIRECTION_NEUTRAL if there are no letters, there is no second string or on errors.
+ */
+int             dc_lot_get_text2_direction (const dc_lot_t* lot);
+
+
 /**
  * Get the meaning of the first string. Possible meanings of the string are defined by the creator of the object and may be returned e.g.
  * as DC_TEXT1_DRAFT, DC_TEXT1_USERNAME or DC_TEXT1_SELF.
@@ -5305,6 +7044,18 @@ void            dc_reactions_unref       (dc_reactions_t* reactions);
 #define DC_MSG_VIDEOCHAT_INVITATION 70
 
 
+/**
+ * The message is a record of a call,
+ * added by dc_add_call_record() or for missed calls offered by dc_send_call_offer().
+ *
+ * The message is not sent, it is only a local record in the chat.
+ * dc_msg_get_from_id() tells if the call was incoming or outgoing,
+ * dc_msg_get_call_state() returns the state of the call
+ * and dc_msg_get_duration() the duration of accepted calls.
+ */
+#define DC_MSG_CALL      71
+
+
 /**
  * The message is a webxdc instance.
  *
@@ -5695,6 +7446,23 @@ char* dc_event_get_data2_str(dc_event_t* event);
 uint32_t dc_event_get_account_id(dc_event_t* event);
 
 
+/**
+ * Get the sequence number of the event among the events of its account.
+ * The numbers start with 1 and increase by one with every event of the account.
+ *
+ * Events are dropped if they are not retrieved fast enough,
+ * so a gap in the numbers means that events were missed
+ * and the UI should reload the chatlist and the open chat.
+ * Gaps are only meaningful if all events of the account are retrieved;
+ * consumers that skip events see the skipped numbers as gaps.
+ *
+ * @memberof dc_event_t
+ * @param event The event object as returned from dc_get_next_event().
+ * @return The sequence number of the event.
+ */
+uint64_t dc_event_get_seq(dc_event_t* event);
+
+
 /**
  * Free memory used by an event object.
  * If you forget to do this for an event, this will result in memory leakage.
@@ -5864,6 +7632,7 @@ void dc_event_unref(dc_event_t* event);
 /**
  * There is a fresh message. Typically, the user will show an notification
  * when receiving this message.
+ * Use dc_get_notification_intent() to decide how to notify the user.
  *
  * There is no extra #DC_EVENT_MSGS_CHANGED event send together with this event.
  *
@@ -5899,6 +7668,19 @@ void dc_event_unref(dc_event_t* event);
 #define DC_EVENT_MSGS_NOTICED             2008
 
 
+/**
+ * Notifications were suppressed because of the `quiet_hours` setting.
+ * The UI may show a summary of the missed messages when the quiet hours are over.
+ *
+ * The JSON-RPC API provides the suppressed messages
+ * with `get_suppressed_notifications()` and `clear_suppressed_notifications()`.
+ *
+ * @param data1 (int) Number of suppressed notifications since the last summary.
+ * @param data2 0
+ */
+#define DC_EVENT_NOTIFICATIONS_SUPPRESSED 2009
+
+
 /**
  * A single message is sent successfully. State changed from @ref DC_STATE_OUT_PENDING to
  * @ref DC_STATE_OUT_DELIVERED.
@@ -5930,6 +7712,18 @@ void dc_event_unref(dc_event_t* event);
 #define DC_EVENT_MSG_READ                 2015
 
 
+/**
+ * Messages of a chat were deleted,
+ * e.g. by dc_delete_msgs() or because their ephemeral timer expired.
+ * Messages deleted at once are reported by a single event per chat.
+ * A #DC_EVENT_MSGS_CHANGED event is emitted as well.
+ *
+ * @param data1 (int) chat_id of the chat the messages belonged to
+ * @param data2 (char*) msg_ids, a json array with the IDs of the deleted messages.
+ */
+#define DC_EVENT_MSGS_DELETED             2016
+
+
 /**
  * Chat changed. The name or the image of a chat group was changed or members were added or removed.
  * Or the verify state of a chat has changed.
@@ -5949,6 +7743,17 @@ void dc_event_unref(dc_event_t* event);
  */
 #define DC_EVENT_CHAT_EPHEMERAL_TIMER_MODIFIED 2021
 
+/**
+ * A received message quotes an ephemeral message after the timer of the message expired,
+ * so the sender's device probably does not delete ephemeral messages.
+ *
+ * UIs may inform the user that the ephemeral timer is not honored by the sender.
+ *
+ * @param data1 (int) chat_id
+ * @param data2 (int) msg_id of the received message quoting the expired message
+ */
+#define DC_EVENT_EPHEMERAL_TIMER_NOT_HONORED 2022
+
 
 /**
  * Contact(s) created, renamed, verified, blocked or deleted.
@@ -6002,6 +7807,17 @@ void dc_event_unref(dc_event_t* event);
 #define DC_EVENT_IMEX_FILE_WRITTEN        2052
 
 
+/**
+ * Progress of a peer-to-peer file transfer.
+ * Sent by the sender for each download of a file sent with dc_send_file_p2p()
+ * and by the recipient while dc_download_file_p2p() is running.
+ *
+ * @param data1 (int) ID of the message offering the file.
+ * @param data2 (int) 0=error, 1-999=progress in permille, 1000=success and done
+ */
+#define DC_EVENT_P2P_FILE_PROGRESS        2055
+
+
 /**
  * Progress information of a secure-join handshake from the view of the inviter
  * (Alice, the person who shows the QR code).
@@ -6034,6 +7850,34 @@ void dc_event_unref(dc_event_t* event);
 #define DC_EVENT_SECUREJOIN_JOINER_PROGRESS       2061
 
 
+/**
+ * A verified contact forwarded an invite to a protected group
+ * using dc_forward_group_invite().
+ *
+ * The UI may ask the user whether to join the group,
+ * the name of the group is returned by dc_get_group_invite_name().
+ * To join, call dc_accept_group_invite().
+ *
+ * @param data1 (int) The ID of the inviting contact.
+ * @param data2 (int) The ID of the invite.
+ */
+#define DC_EVENT_SECUREJOIN_INVITE_RECEIVED       2062
+
+
+/**
+ * The inviter of a group with join gate, see dc_set_join_gate(),
+ * asks to answer a challenge before adding us to the group.
+ *
+ * The UI should show the question returned by dc_get_join_challenge_question()
+ * and the options returned by dc_get_join_challenge_options() as buttons.
+ * The selected option is sent with dc_answer_join_challenge().
+ *
+ * @param data1 (int) The ID of the inviting contact.
+ * @param data2 (int) The ID of the challenge.
+ */
+#define DC_EVENT_SECUREJOIN_CHALLENGE_RECEIVED    2063
+
+
 /**
  * The connectivity to the server changed.
  * This means that you should refresh the connectivity view
@@ -6078,13 +7922,111 @@ void dc_event_unref(dc_event_t* event);
 #define DC_EVENT_WEBXDC_INSTANCE_DELETED          2121
 
 
+/**
+ * A webxdc instance wants attention,
+ * another member sent an update with an info message.
+ *
+ * UIs may badge the app icon with the number of unread info messages,
+ * which is also returned as `unread_info_cnt` by dc_msg_get_webxdc_info().
+ *
+ * @param data1 (int) msg_id of the webxdc instance
+ * @param data2 (int) number of unread info messages,
+ *     reset by dc_mark_webxdc_infos_seen()
+ */
+#define DC_EVENT_WEBXDC_ATTENTION                 2122
+
+
+/**
+ * The accounts were locked using dc_accounts_lock().
+ * The UI should show its lock screen.
+ *
+ * @param data1 0
+ * @param data2 0
+ */
+#define DC_EVENT_ACCOUNTS_LOCKED                  2130
+
+
+/**
+ * All locked accounts were unlocked using dc_accounts_unlock().
+ *
+ * @param data1 0
+ * @param data2 0
+ */
+#define DC_EVENT_ACCOUNTS_UNLOCKED                2131
+
+
+/**
+ * A call was offered using dc_send_call_offer().
+ * The UI should ring until the call is answered using dc_send_call_answer()
+ * or #DC_EVENT_CALL_MISSED is emitted.
+ *
+ * @param data1 (int) ID of the offer message identifying the call.
+ * @param data2 (char*) The payload of the offer.
+ */
+#define DC_EVENT_INCOMING_CALL_OFFER              2140
+
+
+/**
+ * An offered call was answered using dc_send_call_answer().
+ *
+ * @param data1 (int) ID of the offer message identifying the call.
+ * @param data2 (char*) The payload of the answer.
+ */
+#define DC_EVENT_INCOMING_CALL_ANSWER             2141
+
+
+/**
+ * The peer of a call sent an ICE candidate using dc_send_call_ice().
+ *
+ * @param data1 (int) ID of the offer message identifying the call.
+ * @param data2 (char*) The payload of the ICE candidate.
+ */
+#define DC_EVENT_INCOMING_CALL_ICE                2142
+
+
+/**
+ * An offered call was not answered in time.
+ * The UI should stop ringing.
+ *
+ * @param data1 (int) ID of the offer message identifying the call.
+ * @param data2 0
+ */
+#define DC_EVENT_CALL_MISSED                      2143
+
+
+/**
+ * The device clock differs from the time of the server by more than 5 minutes.
+ * This breaks disappearing messages and the order of messages,
+ * the UI may ask the user to adjust the clock.
+ * A warning is also added to the device chat.
+ *
+ * The current estimate can be retrieved with dc_get_config() using the key `clock_skew`.
+ *
+ * @param data1 (int) Server time minus device time in seconds,
+ *     positive if the device clock is behind.
+ * @param data2 0
+ */
+#define DC_EVENT_CLOCK_SKEW                       2150
+
+
+/**
+ * Canned responses were added, changed or deleted,
+ * either on this device or on another device of the same account.
+ * The UI should reload the list returned by dc_get_canned_responses().
+ *
+ * @param data1 0
+ * @param data2 0
+ */
+#define DC_EVENT_CANNED_RESPONSES_CHANGED         2160
+
+
 /**
  * @}
  */
 
 
 #define DC_EVENT_DATA1_IS_STRING(e)  0    // not used anymore 
-#define DC_EVENT_DATA2_IS_STRING(e)  ((e)==DC_EVENT_CONFIGURE_PROGRESS || (e)==DC_EVENT_IMEX_FILE_WRITTEN || ((e)>=100 && (e)<=499))
+#define DC_EVENT_DATA2_IS_STRING(e)  ((e)==DC_EVENT_CONFIGURE_PROGRESS || (e)==DC_EVENT_IMEX_FILE_WRITTEN || (e)==DC_EVENT_MSGS_DELETED || ((e)>=100 && (e)<=499) || ((e)>=DC_EVENT_INCOMING_CALL_OFFER && (e)<=DC_EVENT_INCOMING_CALL_ICE))
 
 
 /*
@@ -6102,6 +8044,41 @@ void dc_event_unref(dc_event_t* event);
 #define DC_MEDIA_QUALITY_WORSE    1
 
 
+/*
+ * Values for dc_get|set_config("encrypted_subject")
+ */
+#define DC_ENCRYPTED_SUBJECT_HIDDEN    0
+#define DC_ENCRYPTED_SUBJECT_CHAT_NAME 1
+#define DC_ENCRYPTED_SUBJECT_PLAIN     2
+
+
+/*
+ * Values returned by dc_get_notification_intent()
+ */
+#define DC_NOTIFICATION_INTENT_SUPPRESS 0
+#define DC_NOTIFICATION_INTENT_SILENT   1
+#define DC_NOTIFICATION_INTENT_NOTIFY   2
+#define DC_NOTIFICATION_INTENT_MENTION  3
+
+
+/**
+ * @defgroup DC_REPORT_CATEGORY DC_REPORT_CATEGORY
+ *
+ * These constants are used as categories for dc_report_msg().
+ *
+ * @addtogroup DC_REPORT_CATEGORY
+ * @{
+ */
+#define DC_REPORT_CATEGORY_SPAM  1
+#define DC_REPORT_CATEGORY_ABUSE 2
+#define DC_REPORT_CATEGORY_FRAUD 3
+#define DC_REPORT_CATEGORY_VIRUS 4
+#define DC_REPORT_CATEGORY_OTHER 5
+/**
+ * @}
+ */
+
+
 /*
  * Values for dc_get|set_config("key_gen_type")
  */
@@ -6207,6 +8184,41 @@ void dc_event_unref(dc_event_t* event);
  */
 
 
+/**
+ * @defgroup DC_ARCHIVE_MODE DC_ARCHIVE_MODE
+ *
+ * These constants describe whether new messages unarchive an archived chat.
+ * The archive mode can be get using dc_chat_get_archive_mode()
+ * and set using dc_set_chat_archive_mode().
+ *
+ * @addtogroup DC_ARCHIVE_MODE
+ * @{
+ */
+
+/**
+ * Any new message unarchives the chat, unless the chat is muted.
+ * This is the default.
+ */
+#define         DC_ARCHIVE_MODE_ALWAYS_UNARCHIVE   0
+
+/**
+ * The chat stays archived when new messages arrive.
+ * Fresh messages are counted at the DC_CHAT_ID_ARCHIVED_LINK pseudo-chat.
+ */
+#define         DC_ARCHIVE_MODE_STAY_ARCHIVED      1
+
+/**
+ * Only messages mentioning the user unarchive the chat,
+ * these are replies to the user's messages
+ * and messages containing the user's address or `@` followed by the user's display name.
+ */
+#define         DC_ARCHIVE_MODE_UNARCHIVE_ON_MENTION 2
+
+/**
+ * @}
+ */
+
+
 /**
   * @defgroup DC_DOWNLOAD DC_DOWNLOAD
   *
@@ -6240,6 +8252,41 @@ void dc_event_unref(dc_event_t* event);
 
 
 
+/**
+ * @}
+ */
+
+
+/**
+  * @defgroup DC_CALL_STATE DC_CALL_STATE
+  *
+  * These constants describe the state of a call,
+  * see dc_msg_get_call_state() and dc_add_call_record().
+  *
+  * @addtogroup DC_CALL_STATE
+  * @{
+  */
+
+/**
+ * The call is offered and not answered yet.
+ */
+#define DC_CALL_STATE_RINGING    0
+
+/**
+ * The call was answered.
+ */
+#define DC_CALL_STATE_ACCEPTED   1
+
+/**
+ * The call was not answered in time.
+ */
+#define DC_CALL_STATE_MISSED     2
+
+/**
+ * The call was declined by the callee.
+ */
+#define DC_CALL_STATE_DECLINED   3
+
 /**
  * @}
  */
@@ -7074,6 +9121,156 @@ void dc_event_unref(dc_event_t* event);
 /// Used as a device message after a successful backup transfer.
 #define DC_STR_BACKUP_TRANSFER_MSG_BODY 163
 
+/// "This media can only be viewed once. Use Delta Chat to view it."
+///
+/// Sent as message text of view-once messages for clients not supporting them.
+#define DC_STR_VIEW_ONCE_FALLBACK 164
+
+/// "⚠️ Device "%1$s" was removed, settings changed on it are ignored from now on. As it may still have access to your account, consider changing your password."
+///
+/// Added as a device message after revoking an own device.
+/// `%1$s` will be replaced by the name of the device.
+#define DC_STR_OWN_DEVICE_REVOKED 165
+
+/// "%1$s new messages in %2$s chats while you were away."
+///
+/// Added as a device message summarizing notifications suppressed during quiet hours,
+/// see the `notification_digest` setting.
+/// `%1$s` will be replaced by the number of messages, `%2$s` by the number of chats.
+/// Use dc_set_stock_format() to translate the plural forms.
+#define DC_STR_NOTIFICATION_DIGEST 166
+
+/// "You can create a backup in the settings."
+///
+/// Used as text of device messages with the action `backup` added without text.
+#define DC_STR_DEVICE_ACTION_BACKUP 167
+
+/// "You can change notifications in the settings."
+///
+/// Used as text of device messages with the action `notification_settings` added without text.
+#define DC_STR_DEVICE_ACTION_NOTIFICATION_SETTINGS 168
+
+/// "You can change your account in the settings."
+///
+/// Used as text of device messages with the action `account_settings` added without text.
+#define DC_STR_DEVICE_ACTION_ACCOUNT_SETTINGS 169
+
+/// "You can free up storage by deleting old messages."
+///
+/// Used as text of device messages with the action `manage_storage` added without text.
+#define DC_STR_DEVICE_ACTION_MANAGE_STORAGE 170
+
+/// "Please update the app."
+///
+/// Used as text of device messages with the action `update_app` added without text.
+#define DC_STR_DEVICE_ACTION_UPDATE_APP 171
+
+/// "Missed call"
+///
+/// Used in summaries of incoming calls not answered in time.
+#define DC_STR_MISSED_CALL 172
+
+/// "Outgoing call"
+///
+/// Used in summaries of outgoing calls.
+#define DC_STR_OUTGOING_CALL 173
+
+/// "Incoming call"
+///
+/// Used in summaries of incoming calls.
+#define DC_STR_INCOMING_CALL 174
+
+/// "Declined call"
+///
+/// Used in summaries of declined calls.
+#define DC_STR_DECLINED_CALL 175
+
+/// "Late Deliveries"
+///
+/// Used as a headline in the connectivity view.
+#define DC_STR_LATE_DELIVERIES 176
+
+/// "%1$s: %2$s messages delivered up to %3$s hours late"
+///
+/// Used in the connectivity view.
+/// - %1$s will be replaced by the domain of the senders
+/// - %2$s will be replaced by the number of messages delivered late
+/// - %3$s will be replaced by the maximum delay in hours
+#define DC_STR_LATE_DELIVERIES_FROM_DOMAIN 177
+
+/// "⚠️ The clock of your device differs by about %1$s minutes from the time of your server. …"
+///
+/// Added to the device chat if the clock of the device is wrong.
+/// - %1$s will be replaced by the difference in minutes
+#define DC_STR_CLOCK_SKEW_MSG_BODY 178
+
+/// "%1$s queued messages will be sent from your new address %2$s."
+///
+/// Added to the device chat if queued messages were prepared again after the address was changed.
+/// - %1$s will be replaced by the number of messages
+/// - %2$s will be replaced by the new address
+#define DC_STR_REQUEUED_MSGS 179
+
+/// "⚠️ %1$s queued messages could not be prepared for your new address and were not sent."
+///
+/// Added to the device chat if queued messages failed after the address was changed.
+/// - %1$s will be replaced by the number of messages
+#define DC_STR_REQUEUED_MSGS_FAILED 180
+
+/// "To join the group, select the %1$s."
+///
+/// Sent to contacts joining a group with join gate, see dc_set_join_gate().
+/// - %1$s will be replaced by the name of the option to select,
+///   one of #DC_STR_JOIN_CHALLENGE_APPLE to #DC_STR_JOIN_CHALLENGE_KEY
+#define DC_STR_JOIN_CHALLENGE_QUESTION 181
+
+/// "apple"
+///
+/// Name of the 🍎 option of join challenges, used in #DC_STR_JOIN_CHALLENGE_QUESTION.
+#define DC_STR_JOIN_CHALLENGE_APPLE 182
+
+/// "bicycle"
+///
+/// Name of the 🚲 option of join challenges, used in #DC_STR_JOIN_CHALLENGE_QUESTION.
+#define DC_STR_JOIN_CHALLENGE_BICYCLE 183
+
+/// "cat"
+///
+/// Name of the 🐱 option of join challenges, used in #DC_STR_JOIN_CHALLENGE_QUESTION.
+#define DC_STR_JOIN_CHALLENGE_CAT 184
+
+/// "tree"
+///
+/// Name of the 🌳 option of join challenges, used in #DC_STR_JOIN_CHALLENGE_QUESTION.
+#define DC_STR_JOIN_CHALLENGE_TREE 185
+
+/// "house"
+///
+/// Name of the 🏠 option of join challenges, used in #DC_STR_JOIN_CHALLENGE_QUESTION.
+#define DC_STR_JOIN_CHALLENGE_HOUSE 186
+
+/// "star"
+///
+/// Name of the ⭐ option of join challenges, used in #DC_STR_JOIN_CHALLENGE_QUESTION.
+#define DC_STR_JOIN_CHALLENGE_STAR 187
+
+/// "balloon"
+///
+/// Name of the 🎈 option of join challenges, used in #DC_STR_JOIN_CHALLENGE_QUESTION.
+#define DC_STR_JOIN_CHALLENGE_BALLOON 188
+
+/// "key"
+///
+/// Name of the 🔑 option of join challenges, used in #DC_STR_JOIN_CHALLENGE_QUESTION.
+#define DC_STR_JOIN_CHALLENGE_KEY 189
+
+/// "%1$s wants to join the group, but their app cannot answer the join challenge. Add them manually if you know them."
+///
+/// Added to a group with join gate if a contact joining via QR code uses an app
+/// that does not support join challenges.
+/// - %1$s will be replaced by the name and address of the contact
+#define DC_STR_JOIN_CHALLENGE_UNSUPPORTED 190
+
 /**
  * @}
  */
@@ -23,7 +23,9 @@
 use std::time::{Duration, SystemTime};
 
 use anyhow::Context as _;
-use deltachat::chat::{ChatId, ChatVisibility, MessageListOptions, MuteDuration, ProtectionStatus};
+use deltachat::chat::{
+    ArchiveMode, ChatId, ChatVisibility, MessageListOptions, MuteDuration, ProtectionStatus,
+};
 use deltachat::constants::DC_MSG_ID_LAST_SPECIAL;
 use deltachat::contact::{Contact, ContactId, Origin};
 use deltachat::context::Context;
@@ -63,6 +65,7 @@
 
 const DC_GCM_ADDDAYMARKER: u32 = 0x01;
 const DC_GCM_INFO_ONLY: u32 = 0x02;
+const DC_GCM_CORRECTED_ORDER: u32 = 0x04;
 
 // dc_context_t
 
@@ -310,6 +313,49 @@ fn spawn<T>(fut: T) -> JoinHandle<T::Output>
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_set_stock_format(
+    context: *mut dc_context_t,
+    stock_id: u32,
+    format: *const libc::c_char,
+) -> libc::c_int {
+    if context.is_null() || format.is_null() {
+        eprintln!("ignoring careless call to dc_set_stock_format()");
+        return 0;
+    }
+    let format = to_string_lossy(format);
+    let ctx = &*context;
+
+    block_on(async move {
+        match StockMessage::from_u32(stock_id) {
+            Some(id) => match ctx.set_stock_format(id, &format).await {
+                Ok(()) => 1,
+                Err(err) => {
+                    warn!(ctx, "set_stock_format failed: {err:#}");
+                    0
+                }
+            },
+            None => {
+                warn!(ctx, "invalid stock message id {stock_id}");
+                0
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_set_stock_language(
+    context: *mut dc_context_t,
+    language: *const libc::c_char,
+) {
+    if context.is_null() || language.is_null() {
+        eprintln!("ignoring careless call to dc_set_stock_language()");
+        return;
+    }
+    let ctx = &*context;
+    block_on(ctx.set_stock_language(&to_string_lossy(language)))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_set_config_from_qr(
     context: *mut dc_context_t,
@@ -362,6 +408,38 @@ fn render_info(
     Ok(res)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_export_settings(context: *mut dc_context_t) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_export_settings()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+    match block_on(ctx.export_settings())
+        .context("Failed to export settings")
+        .log_err(ctx)
+    {
+        Ok(json) => json.strdup(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_import_settings(
+    context: *mut dc_context_t,
+    json: *const libc::c_char,
+) -> libc::c_int {
+    if context.is_null() || json.is_null() {
+        eprintln!("ignoring careless call to dc_import_settings()");
+        return 0;
+    }
+    let ctx = &*context;
+    block_on(ctx.import_settings(&to_string_lossy(json)))
+        .context("Failed to import settings")
+        .log_err(ctx)
+        .is_ok() as libc::c_int
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_get_connectivity(context: *const dc_context_t) -> libc::c_int {
     if context.is_null() {
@@ -523,22 +601,37 @@ fn spawn_configure(ctx: Context) {
         EventType::IncomingMsg { .. } => 2005,
         EventType::IncomingMsgBunch { .. } => 2006,
         EventType::MsgsNoticed { .. } => 2008,
+        EventType::NotificationsSuppressed { .. } => 2009,
         EventType::MsgDelivered { .. } => 2010,
         EventType::MsgFailed { .. } => 2012,
         EventType::MsgRead { .. } => 2015,
+        EventType::MsgsDeleted { .. } => 2016,
         EventType::ChatModified(_) => 2020,
         EventType::ChatEphemeralTimerModified { .. } => 2021,
+        EventType::EphemeralTimerNotHonored { .. } => 2022,
         EventType::ContactsChanged(_) => 2030,
         EventType::LocationChanged(_) => 2035,
         EventType::ConfigureProgress { .. } => 2041,
         EventType::ImexProgress(_) => 2051,
         EventType::ImexFileWritten(_) => 2052,
+        EventType::P2pFileProgress { .. } => 2055,
         EventType::SecurejoinInviterProgress { .. } => 2060,
         EventType::SecurejoinJoinerProgress { .. } => 2061,
+        EventType::SecurejoinInviteReceived { .. } => 2062,
+        EventType::SecurejoinChallengeReceived { .. } => 2063,
         EventType::ConnectivityChanged => 2100,
         EventType::SelfavatarChanged => 2110,
         EventType::WebxdcStatusUpdate { .. } => 2120,
         EventType::WebxdcInstanceDeleted { .. } => 2121,
+        EventType::WebxdcAttention { .. } => 2122,
+        EventType::AccountsLocked => 2130,
+        EventType::AccountsUnlocked => 2131,
+        EventType::IncomingCallOffer { .. } => 2140,
+        EventType::IncomingCallAnswer { .. } => 2141,
+        EventType::IncomingCallIce { .. } => 2142,
+        EventType::CallMissed { .. } => 2143,
+        EventType::ClockSkew { .. } => 2150,
+        EventType::CannedResponsesChanged => 2160,
     }
 }
 
@@ -564,7 +657,10 @@ fn spawn_configure(ctx: Context) {
         | EventType::Error(_)
         | EventType::ConnectivityChanged
         | EventType::SelfavatarChanged
+        | EventType::AccountsLocked
+        | EventType::AccountsUnlocked
         | EventType::IncomingMsgBunch { .. }
+        | EventType::CannedResponsesChanged
         | EventType::ErrorSelfNotInGroup(_) => 0,
         EventType::MsgsChanged { chat_id, .. }
         | EventType::ReactionsChanged { chat_id, .. }
@@ -573,8 +669,10 @@ fn spawn_configure(ctx: Context) {
         | EventType::MsgDelivered { chat_id, .. }
         | EventType::MsgFailed { chat_id, .. }
         | EventType::MsgRead { chat_id, .. }
+        | EventType::MsgsDeleted { chat_id, .. }
         | EventType::ChatModified(chat_id)
-        | EventType::ChatEphemeralTimerModified { chat_id, .. } => chat_id.to_u32() as libc::c_int,
+        | EventType::ChatEphemeralTimerModified { chat_id, .. }
+        | EventType::EphemeralTimerNotHonored { chat_id, .. } => chat_id.to_u32() as libc::c_int,
         EventType::ContactsChanged(id) | EventType::LocationChanged(id) => {
             let id = id.unwrap_or_default();
             id.to_u32() as libc::c_int
@@ -584,11 +682,23 @@ fn spawn_configure(ctx: Context) {
         }
         EventType::ImexFileWritten(_) => 0,
         EventType::SecurejoinInviterProgress { contact_id, .. }
-        | EventType::SecurejoinJoinerProgress { contact_id, .. } => {
+        | EventType::SecurejoinJoinerProgress { contact_id, .. }
+        | EventType::SecurejoinInviteReceived { contact_id, .. }
+        | EventType::SecurejoinChallengeReceived { contact_id, .. } => {
             contact_id.to_u32() as libc::c_int
         }
+        EventType::NotificationsSuppressed { count } => *count as libc::c_int,
         EventType::WebxdcStatusUpdate { msg_id, .. } => msg_id.to_u32() as libc::c_int,
-        EventType::WebxdcInstanceDeleted { msg_id, .. } => msg_id.to_u32() as libc::c_int,
+        EventType::WebxdcInstanceDeleted { msg_id, .. }
+        | EventType::WebxdcAttention { msg_id, .. }
+        | EventType::P2pFileProgress { msg_id, .. }
+        | EventType::IncomingCallOffer { msg_id, .. }
+        | EventType::IncomingCallAnswer { msg_id, .. }
+        | EventType::IncomingCallIce { msg_id, .. }
+        | EventType::CallMissed { msg_id } => msg_id.to_u32() as libc::c_int,
+        EventType::ClockSkew { skew } => {
+            (*skew).clamp(libc::c_int::MIN.into(), libc::c_int::MAX.into()) as libc::c_int
+        }
     }
 }
 
@@ -620,9 +730,19 @@ fn spawn_configure(ctx: Context) {
         | EventType::ImexProgress(_)
         | EventType::ImexFileWritten(_)
         | EventType::MsgsNoticed(_)
+        | EventType::NotificationsSuppressed { .. }
         | EventType::ConnectivityChanged
         | EventType::WebxdcInstanceDeleted { .. }
         | EventType::IncomingMsgBunch { .. }
+        | EventType::MsgsDeleted { .. }
+        | EventType::AccountsLocked
+        | EventType::AccountsUnlocked
+        | EventType::IncomingCallOffer { .. }
+        | EventType::IncomingCallAnswer { .. }
+        | EventType::IncomingCallIce { .. }
+        | EventType::CallMissed { .. }
+        | EventType::ClockSkew { .. }
+        | EventType::CannedResponsesChanged
         | EventType::SelfavatarChanged => 0,
         EventType::ChatModified(_) => 0,
         EventType::MsgsChanged { msg_id, .. }
@@ -630,10 +750,17 @@ fn spawn_configure(ctx: Context) {
         | EventType::IncomingMsg { msg_id, .. }
         | EventType::MsgDelivered { msg_id, .. }
         | EventType::MsgFailed { msg_id, .. }
-        | EventType::MsgRead { msg_id, .. } => msg_id.to_u32() as libc::c_int,
+        | EventType::MsgRead { msg_id, .. }
+        | EventType::EphemeralTimerNotHonored { msg_id, .. } => msg_id.to_u32() as libc::c_int,
         EventType::SecurejoinInviterProgress { progress, .. }
-        | EventType::SecurejoinJoinerProgress { progress, .. } => *progress as libc::c_int,
+        | EventType::SecurejoinJoinerProgress { progress, .. }
+        | EventType::P2pFileProgress { progress, .. } => *progress as libc::c_int,
+        EventType::SecurejoinInviteReceived { invite_id, .. } => *invite_id as libc::c_int,
+        EventType::SecurejoinChallengeReceived { challenge_id, .. } => *challenge_id as libc::c_int,
         EventType::ChatEphemeralTimerModified { timer, .. } => timer.to_u32() as libc::c_int,
+        EventType::WebxdcAttention {
+            unread_info_cnt, ..
+        } => *unread_info_cnt as libc::c_int,
         EventType::WebxdcStatusUpdate {
             status_update_serial,
             ..
@@ -670,6 +797,7 @@ fn spawn_configure(ctx: Context) {
         | EventType::IncomingMsg { .. }
         | EventType::ImapInboxIdle
         | EventType::MsgsNoticed(_)
+        | EventType::NotificationsSuppressed { .. }
         | EventType::MsgDelivered { .. }
         | EventType::MsgFailed { .. }
         | EventType::MsgRead { .. }
@@ -677,13 +805,23 @@ fn spawn_configure(ctx: Context) {
         | EventType::ContactsChanged(_)
         | EventType::LocationChanged(_)
         | EventType::ImexProgress(_)
+        | EventType::P2pFileProgress { .. }
         | EventType::SecurejoinInviterProgress { .. }
         | EventType::SecurejoinJoinerProgress { .. }
+        | EventType::SecurejoinInviteReceived { .. }
+        | EventType::SecurejoinChallengeReceived { .. }
         | EventType::ConnectivityChanged
         | EventType::SelfavatarChanged
         | EventType::WebxdcStatusUpdate { .. }
         | EventType::WebxdcInstanceDeleted { .. }
-        | EventType::ChatEphemeralTimerModified { .. } => ptr::null_mut(),
+        | EventType::WebxdcAttention { .. }
+        | EventType::AccountsLocked
+        | EventType::AccountsUnlocked
+        | EventType::ChatEphemeralTimerModified { .. }
+        | EventType::EphemeralTimerNotHonored { .. }
+        | EventType::CallMissed { .. }
+        | EventType::ClockSkew { .. }
+        | EventType::CannedResponsesChanged => ptr::null_mut(),
         EventType::ConfigureProgress { comment, .. } => {
             if let Some(comment) = comment {
                 comment.to_c_string().unwrap_or_default().into_raw()
@@ -695,11 +833,18 @@ fn spawn_configure(ctx: Context) {
             let data2 = file.to_c_string().unwrap_or_default();
             data2.into_raw()
         }
-        EventType::IncomingMsgBunch { msg_ids } => serde_json::to_string(msg_ids)
-            .unwrap_or_default()
-            .to_c_string()
-            .unwrap_or_default()
-            .into_raw(),
+        EventType::IncomingCallOffer { payload, .. }
+        | EventType::IncomingCallAnswer { payload, .. }
+        | EventType::IncomingCallIce { payload, .. } => {
+            payload.to_c_string().unwrap_or_default().into_raw()
+        }
+        EventType::IncomingMsgBunch { msg_ids } | EventType::MsgsDeleted { msg_ids, .. } => {
+            serde_json::to_string(msg_ids)
+                .unwrap_or_default()
+                .to_c_string()
+                .unwrap_or_default()
+                .into_raw()
+        }
     }
 }
 
@@ -713,6 +858,16 @@ fn spawn_configure(ctx: Context) {
     (*event).id
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_event_get_seq(event: *mut dc_event_t) -> u64 {
+    if event.is_null() {
+        eprintln!("ignoring careless call to dc_event_get_seq()");
+        return 0;
+    }
+
+    (*event).seq
+}
+
 pub type dc_event_emitter_t = EventEmitter;
 
 #[no_mangle]
@@ -778,6 +933,24 @@ fn spawn_configure(ctx: Context) {
     block_on(async move { ctx.maybe_network().await })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_set_push_device_token(
+    context: *mut dc_context_t,
+    token: *const libc::c_char,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_set_push_device_token()");
+        return 0;
+    }
+    let ctx = &*context;
+    let token = to_opt_string_lossy(token).unwrap_or_default();
+
+    block_on(ctx.set_push_device_token(&token))
+        .context("Failed to set push device token")
+        .log_err(ctx)
+        .is_ok() as libc::c_int
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_preconfigure_keypair(
     context: *mut dc_context_t,
@@ -927,6 +1100,27 @@ fn spawn_configure(ctx: Context) {
     .to_u32()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_send_msg_deferred(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    msg: *mut dc_msg_t,
+) -> u32 {
+    if context.is_null() || msg.is_null() {
+        eprintln!("ignoring careless call to dc_send_msg_deferred()");
+        return 0;
+    }
+    let ctx = &mut *context;
+    let ffi_msg = &mut *msg;
+
+    block_on(async move {
+        chat::send_msg_deferred(ctx, ChatId::new(chat_id), &mut ffi_msg.message)
+            .await
+            .unwrap_or_log_default(ctx, "Failed to send message")
+    })
+    .to_u32()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_send_msg_sync(
     context: *mut dc_context_t,
@@ -988,6 +1182,117 @@ fn spawn_configure(ctx: Context) {
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_send_videochat_invitation_ex(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    instance: *const libc::c_char,
+) -> u32 {
+    if context.is_null() || instance.is_null() {
+        eprintln!("ignoring careless call to dc_send_videochat_invitation_ex()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        chat::send_videochat_invitation_ex(ctx, ChatId::new(chat_id), &to_string_lossy(instance))
+            .await
+            .map(|msg_id| msg_id.to_u32())
+            .unwrap_or_log_default(ctx, "Failed to send video chat invitation")
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_send_album(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    files: *const *const libc::c_char,
+    files_cnt: libc::c_int,
+    caption: *const libc::c_char,
+) -> *mut dc_array::dc_array_t {
+    if context.is_null() || files.is_null() || files_cnt <= 0 {
+        eprintln!("ignoring careless call to dc_send_album()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+    let files: Vec<String> = std::slice::from_raw_parts(files, files_cnt as usize)
+        .iter()
+        .map(|file| to_string_lossy(*file))
+        .collect();
+    let caption = to_opt_string_lossy(caption);
+
+    block_on(async move {
+        match chat::send_album(ctx, ChatId::new(chat_id), &files, caption).await {
+            Ok(msg_ids) => Box::into_raw(Box::new(msg_ids.into())),
+            Err(err) => {
+                error!(ctx, "Failed to send album: {err:#}");
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_album_msgs(
+    context: *mut dc_context_t,
+    msg_id: u32,
+) -> *mut dc_array::dc_array_t {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_album_msgs()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        Box::into_raw(Box::new(
+            chat::get_album_msgs(ctx, MsgId::new(msg_id))
+                .await
+                .unwrap_or_log_default(ctx, "Failed to get album msgs")
+                .into(),
+        ))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_send_file_p2p(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    msg: *mut dc_msg_t,
+) -> u32 {
+    if context.is_null() || msg.is_null() {
+        eprintln!("ignoring careless call to dc_send_file_p2p()");
+        return 0;
+    }
+    let ctx = &*context;
+    let ffi_msg = &mut *msg;
+
+    block_on(async move {
+        peer_transfer::send_file_p2p(ctx, ChatId::new(chat_id), &mut ffi_msg.message)
+            .await
+            .map(|msg_id| msg_id.to_u32())
+            .unwrap_or_log_default(ctx, "Failed to send file peer-to-peer")
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_download_file_p2p(
+    context: *mut dc_context_t,
+    msg_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_download_file_p2p()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        peer_transfer::download_file_p2p(ctx, MsgId::new(msg_id))
+            .await
+            .log_err(ctx)
+            .is_ok() as libc::c_int
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_send_reaction(
     context: *mut dc_context_t,
@@ -1008,6 +1313,103 @@ fn spawn_configure(ctx: Context) {
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_send_call_offer(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    payload: *const libc::c_char,
+) -> u32 {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_send_call_offer()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        calls::send_call_offer(ctx, ChatId::new(chat_id), &to_string_lossy(payload))
+            .await
+            .map(|msg_id| msg_id.to_u32())
+            .unwrap_or_log_default(ctx, "Failed to send call offer")
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_send_call_answer(
+    context: *mut dc_context_t,
+    msg_id: u32,
+    payload: *const libc::c_char,
+) -> u32 {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_send_call_answer()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        calls::send_call_answer(ctx, MsgId::new(msg_id), &to_string_lossy(payload))
+            .await
+            .map(|msg_id| msg_id.to_u32())
+            .unwrap_or_log_default(ctx, "Failed to send call answer")
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_send_call_ice(
+    context: *mut dc_context_t,
+    msg_id: u32,
+    payload: *const libc::c_char,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_send_call_ice()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        calls::send_call_ice(ctx, MsgId::new(msg_id), &to_string_lossy(payload))
+            .await
+            .map(|()| 1)
+            .unwrap_or_log_default(ctx, "Failed to send ICE candidate")
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_add_call_record(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    incoming: libc::c_int,
+    state: libc::c_int,
+    duration: libc::c_int,
+    timestamp: i64,
+) -> u32 {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_add_call_record()");
+        return 0;
+    }
+    let ctx = &*context;
+    let state = match calls::CallState::from_i32(state) {
+        Some(state) => state,
+        None => {
+            eprintln!("ignoring dc_add_call_record() with unknown state {state}");
+            return 0;
+        }
+    };
+
+    block_on(async move {
+        calls::add_call_record(
+            ctx,
+            ChatId::new(chat_id),
+            incoming != 0,
+            state,
+            duration,
+            timestamp,
+        )
+        .await
+        .map(|msg_id| msg_id.to_u32())
+        .unwrap_or_log_default(ctx, "Failed to add call record")
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_get_msg_reactions(
     context: *mut dc_context_t,
@@ -1074,6 +1476,20 @@ fn spawn_configure(ctx: Context) {
     .strdup()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_mark_webxdc_infos_seen(context: *mut dc_context_t, msg_id: u32) {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_mark_webxdc_infos_seen()");
+        return;
+    }
+    let ctx = &*context;
+
+    block_on(ctx.mark_webxdc_infos_seen(MsgId::new(msg_id)))
+        .context("Failed to mark webxdc infos as seen")
+        .log_err(ctx)
+        .ok();
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_set_draft(
     context: *mut dc_context_t,
@@ -1171,10 +1587,185 @@ fn spawn_configure(ctx: Context) {
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_get_chat_msgs(
+pub unsafe extern "C" fn dc_add_canned_response(
     context: *mut dc_context_t,
-    chat_id: u32,
-    flags: u32,
+    title: *const libc::c_char,
+    text: *const libc::c_char,
+    file: *const libc::c_char,
+) -> u32 {
+    if context.is_null() || title.is_null() {
+        eprintln!("ignoring careless call to dc_add_canned_response()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(canned_response::add_canned_response(
+        ctx,
+        &to_string_lossy(title),
+        &to_opt_string_lossy(text).unwrap_or_default(),
+        to_opt_string_lossy(file).as_deref(),
+    ))
+    .unwrap_or_log_default(ctx, "Failed to add canned response")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_save_msg_as_canned_response(
+    context: *mut dc_context_t,
+    msg_id: u32,
+    title: *const libc::c_char,
+) -> u32 {
+    if context.is_null() || title.is_null() {
+        eprintln!("ignoring careless call to dc_save_msg_as_canned_response()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(canned_response::save_msg_as_canned_response(
+        ctx,
+        MsgId::new(msg_id),
+        &to_string_lossy(title),
+    ))
+    .unwrap_or_log_default(ctx, "Failed to save message as canned response")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_update_canned_response(
+    context: *mut dc_context_t,
+    canned_response_id: u32,
+    title: *const libc::c_char,
+    text: *const libc::c_char,
+    file: *const libc::c_char,
+) -> libc::c_int {
+    if context.is_null() || title.is_null() {
+        eprintln!("ignoring careless call to dc_update_canned_response()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(canned_response::update_canned_response(
+        ctx,
+        canned_response_id,
+        &to_string_lossy(title),
+        &to_opt_string_lossy(text).unwrap_or_default(),
+        to_opt_string_lossy(file).as_deref(),
+    ))
+    .map(|_| 1)
+    .unwrap_or_log_default(ctx, "Failed to update canned response")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_delete_canned_response(
+    context: *mut dc_context_t,
+    canned_response_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_delete_canned_response()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(canned_response::delete_canned_response(
+        ctx,
+        canned_response_id,
+    ))
+    .map(|_| 1)
+    .unwrap_or_log_default(ctx, "Failed to delete canned response")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_canned_responses(
+    context: *mut dc_context_t,
+) -> *mut dc_array::dc_array_t {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_canned_responses()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        let ids: Vec<u32> = canned_response::get_canned_responses(ctx)
+            .await
+            .unwrap_or_log_default(ctx, "Failed to get canned responses")
+            .iter()
+            .map(|response| response.id)
+            .collect();
+        Box::into_raw(Box::new(ids.into()))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_canned_response_title(
+    context: *mut dc_context_t,
+    canned_response_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_canned_response_title()");
+        return "".strdup();
+    }
+    let ctx = &*context;
+
+    block_on(canned_response::get_canned_response(
+        ctx,
+        canned_response_id,
+    ))
+    .unwrap_or_log_default(ctx, "Failed to get canned response")
+    .map(|response| response.title)
+    .unwrap_or_default()
+    .strdup()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_canned_response_to_msg(
+    context: *mut dc_context_t,
+    canned_response_id: u32,
+) -> *mut dc_msg_t {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_canned_response_to_msg()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    match block_on(canned_response::canned_response_to_msg(
+        ctx,
+        canned_response_id,
+    )) {
+        Ok(message) => Box::into_raw(Box::new(MessageWrapper { context, message })),
+        Err(err) => {
+            error!(
+                ctx,
+                "Failed to create message from canned response: {err:#}"
+            );
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_send_canned_response(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    canned_response_id: u32,
+) -> u32 {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_send_canned_response()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(canned_response::send_canned_response(
+        ctx,
+        ChatId::new(chat_id),
+        canned_response_id,
+    ))
+    .map(|msg_id| msg_id.to_u32())
+    .unwrap_or_log_default(ctx, "Failed to send canned response")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_chat_msgs(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    flags: u32,
     _marker1before: u32,
 ) -> *mut dc_array::dc_array_t {
     if context.is_null() {
@@ -1185,6 +1776,7 @@ fn spawn_configure(ctx: Context) {
 
     let info_only = (flags & DC_GCM_INFO_ONLY) != 0;
     let add_daymarker = (flags & DC_GCM_ADDDAYMARKER) != 0;
+    let corrected_order = (flags & DC_GCM_CORRECTED_ORDER) != 0;
     block_on(async move {
         Box::into_raw(Box::new(
             chat::get_chat_msgs_ex(
@@ -1193,6 +1785,7 @@ fn spawn_configure(ctx: Context) {
                 MessageListOptions {
                     info_only,
                     add_daymarker,
+                    corrected_order,
                 },
             )
             .await
@@ -1237,6 +1830,71 @@ fn spawn_configure(ctx: Context) {
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_repair_msgs_order(
+    context: *mut dc_context_t,
+    chat_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_repair_msgs_order()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        ChatId::new(chat_id)
+            .repair_msgs_order(ctx)
+            .await
+            .unwrap_or_log_default(ctx, "failed to repair msgs order") as libc::c_int
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_thread(
+    context: *mut dc_context_t,
+    msg_id: u32,
+) -> *mut dc_array::dc_array_t {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_thread()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        let arr = dc_array_t::from(
+            MsgId::new(msg_id)
+                .get_thread(ctx)
+                .await
+                .context("Failed to get thread")
+                .log_err(ctx)
+                .unwrap_or_default()
+                .iter()
+                .map(|msg_id| msg_id.to_u32())
+                .collect::<Vec<u32>>(),
+        );
+        Box::into_raw(Box::new(arr))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_thread_fresh_msg_cnt(
+    context: *mut dc_context_t,
+    msg_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_thread_fresh_msg_cnt()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        MsgId::new(msg_id)
+            .get_thread_fresh_msg_cnt(ctx)
+            .await
+            .unwrap_or_log_default(ctx, "failed to get thread fresh msg cnt") as libc::c_int
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_estimate_deletion_cnt(
     context: *mut dc_context_t,
@@ -1280,6 +1938,49 @@ fn spawn_configure(ctx: Context) {
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_starred_msgs(
+    context: *mut dc_context_t,
+) -> *mut dc_array::dc_array_t {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_starred_msgs()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        let arr = dc_array_t::from(
+            ctx.get_starred_msgs()
+                .await
+                .context("Failed to get starred messages")
+                .log_err(ctx)
+                .unwrap_or_default()
+                .iter()
+                .map(|msg_id| msg_id.to_u32())
+                .collect::<Vec<u32>>(),
+        );
+        Box::into_raw(Box::new(arr))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_set_msg_starred(
+    context: *mut dc_context_t,
+    msg_id: u32,
+    starred: libc::c_int,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_set_msg_starred()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(MsgId::new(msg_id).set_starred(ctx, starred != 0))
+        .context("Failed to set message starred")
+        .log_err(ctx)
+        .is_ok() as libc::c_int
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_marknoticed_chat(context: *mut dc_context_t, chat_id: u32) {
     if context.is_null() {
@@ -1297,6 +1998,45 @@ fn spawn_configure(ctx: Context) {
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_marknoticed_chats(
+    context: *mut dc_context_t,
+    chat_ids: *const u32,
+    chat_cnt: libc::c_int,
+) {
+    if context.is_null() || chat_ids.is_null() || chat_cnt <= 0 {
+        eprintln!("ignoring careless call to dc_marknoticed_chats()");
+        return;
+    }
+    let ctx = &*context;
+    let chat_ids = convert_and_prune_chat_ids(chat_ids, chat_cnt);
+
+    block_on(chat::marknoticed_chats(ctx, &chat_ids))
+        .context("failed dc_marknoticed_chats() call")
+        .log_err(ctx)
+        .ok();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_archive_chats(
+    context: *mut dc_context_t,
+    chat_ids: *const u32,
+    chat_cnt: libc::c_int,
+    archive: libc::c_int,
+) {
+    if context.is_null() || chat_ids.is_null() || chat_cnt <= 0 {
+        eprintln!("ignoring careless call to dc_archive_chats()");
+        return;
+    }
+    let ctx = &*context;
+    let chat_ids = convert_and_prune_chat_ids(chat_ids, chat_cnt);
+
+    block_on(chat::archive_chats(ctx, &chat_ids, archive != 0))
+        .context("failed dc_archive_chats() call")
+        .log_err(ctx)
+        .ok();
+}
+
 fn from_prim<S, T>(s: S) -> Option<T>
 where
     T: FromPrimitive,
@@ -1406,6 +2146,30 @@ fn from_prim<S, T>(s: S) -> Option<T>
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_set_chat_archive_mode(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    archive_mode: libc::c_int,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_set_chat_archive_mode()");
+        return 0;
+    }
+    let ctx = &*context;
+    let archive_mode = if let Some(archive_mode) = ArchiveMode::from_i32(archive_mode) {
+        archive_mode
+    } else {
+        warn!(ctx, "bad archive-mode for dc_set_chat_archive_mode()");
+        return 0;
+    };
+
+    block_on(ChatId::new(chat_id).set_archive_mode(ctx, archive_mode))
+        .context("Failed to set chat archive mode")
+        .log_err(ctx)
+        .is_ok() as libc::c_int
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_set_chat_visibility(
     context: *mut dc_context_t,
@@ -1441,52 +2205,97 @@ fn from_prim<S, T>(s: S) -> Option<T>
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_delete_chat(context: *mut dc_context_t, chat_id: u32) {
+pub unsafe extern "C" fn dc_set_chat_threaded(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    threaded: libc::c_int,
+) {
     if context.is_null() {
-        eprintln!("ignoring careless call to dc_delete_chat()");
+        eprintln!("ignoring careless call to dc_set_chat_threaded()");
         return;
     }
     let ctx = &*context;
 
     block_on(async move {
         ChatId::new(chat_id)
-            .delete(ctx)
+            .set_threaded(ctx, threaded != 0)
             .await
-            .context("Failed chat delete")
+            .context("Failed setting chat threaded")
             .log_err(ctx)
-            .ok();
+            .unwrap_or(())
     })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_block_chat(context: *mut dc_context_t, chat_id: u32) {
+pub unsafe extern "C" fn dc_set_chat_signature(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    signature: *const libc::c_char,
+) {
     if context.is_null() {
-        eprintln!("ignoring careless call to dc_block_chat()");
+        eprintln!("ignoring careless call to dc_set_chat_signature()");
         return;
     }
     let ctx = &*context;
+    let signature = to_opt_string_lossy(signature);
 
     block_on(async move {
         ChatId::new(chat_id)
-            .block(ctx)
+            .set_signature(ctx, signature.as_deref())
             .await
-            .context("Failed chat block")
+            .context("Failed setting chat signature")
             .log_err(ctx)
-            .ok();
+            .unwrap_or(())
     })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_accept_chat(context: *mut dc_context_t, chat_id: u32) {
+pub unsafe extern "C" fn dc_delete_chat(context: *mut dc_context_t, chat_id: u32) {
     if context.is_null() {
-        eprintln!("ignoring careless call to dc_accept_chat()");
+        eprintln!("ignoring careless call to dc_delete_chat()");
         return;
     }
     let ctx = &*context;
 
     block_on(async move {
         ChatId::new(chat_id)
-            .accept(ctx)
+            .delete(ctx)
+            .await
+            .context("Failed chat delete")
+            .log_err(ctx)
+            .ok();
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_block_chat(context: *mut dc_context_t, chat_id: u32) {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_block_chat()");
+        return;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        ChatId::new(chat_id)
+            .block(ctx)
+            .await
+            .context("Failed chat block")
+            .log_err(ctx)
+            .ok();
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_accept_chat(context: *mut dc_context_t, chat_id: u32) {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_accept_chat()");
+        return;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        ChatId::new(chat_id)
+            .accept(ctx)
             .await
             .context("Failed chat accept")
             .log_err(ctx)
@@ -1494,6 +2303,25 @@ fn from_prim<S, T>(s: S) -> Option<T>
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_chat_spam_score(
+    context: *mut dc_context_t,
+    chat_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_chat_spam_score()");
+        return -1;
+    }
+    let ctx = &*context;
+
+    block_on(reputation::get_spam_score(ctx, ChatId::new(chat_id)))
+        .context("Failed to get spam score")
+        .log_err(ctx)
+        .ok()
+        .flatten()
+        .map_or(-1, |score| score as libc::c_int)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_get_chat_contacts(
     context: *mut dc_context_t,
@@ -1595,6 +2423,53 @@ fn from_prim<S, T>(s: S) -> Option<T>
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_create_group_chat_ex(
+    context: *mut dc_context_t,
+    protect: libc::c_int,
+    name: *const libc::c_char,
+    contact_ids: *const u32,
+    contact_cnt: libc::c_int,
+    avatar: *const libc::c_char,
+    ephemeral_timer: u32,
+) -> u32 {
+    if context.is_null() || name.is_null() || (contact_ids.is_null() && contact_cnt > 0) {
+        eprintln!("ignoring careless call to dc_create_group_chat_ex()");
+        return 0;
+    }
+    let ctx = &*context;
+    let protect = if let Some(s) = ProtectionStatus::from_i32(protect) {
+        s
+    } else {
+        warn!(ctx, "bad protect-value for dc_create_group_chat_ex()");
+        return 0;
+    };
+    let members = if contact_cnt > 0 {
+        std::slice::from_raw_parts(contact_ids, contact_cnt as usize)
+            .iter()
+            .map(|id| ContactId::new(*id))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let params = chat::GroupParams {
+        name: to_string_lossy(name),
+        protect,
+        members,
+        avatar: to_opt_string_lossy(avatar).map(std::path::PathBuf::from),
+        ephemeral_timer: EphemeralTimer::from_u32(ephemeral_timer),
+    };
+
+    block_on(async move {
+        chat::create_group_chat_ex(ctx, params)
+            .await
+            .context("Failed to create group chat")
+            .log_err(ctx)
+            .map(|id| id.to_u32())
+            .unwrap_or(0)
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_create_broadcast_list(context: *mut dc_context_t) -> u32 {
     if context.is_null() {
@@ -1675,6 +2550,70 @@ fn from_prim<S, T>(s: S) -> Option<T>
     .is_ok() as libc::c_int
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_remove_member_and_ban(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    contact_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_remove_member_and_ban()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(chat::remove_member_and_ban(
+        ctx,
+        ChatId::new(chat_id),
+        ContactId::new(contact_id),
+    ))
+    .context("Failed to remove and ban member")
+    .log_err(ctx)
+    .is_ok() as libc::c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_unban_member(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    contact_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_unban_member()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(chat::unban_member(
+        ctx,
+        ChatId::new(chat_id),
+        ContactId::new(contact_id),
+    ))
+    .context("Failed to unban member")
+    .log_err(ctx)
+    .is_ok() as libc::c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_is_member_banned(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    contact_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_is_member_banned()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(chat::is_member_banned(
+        ctx,
+        ChatId::new(chat_id),
+        ContactId::new(contact_id),
+    ))
+    .unwrap_or_log_default(ctx, "Failed dc_is_member_banned()") as libc::c_int
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_set_chat_name(
     context: *mut dc_context_t,
@@ -1796,6 +2735,31 @@ fn from_prim<S, T>(s: S) -> Option<T>
         .to_u32()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_ephemeral_timer_acks(
+    context: *mut dc_context_t,
+    chat_id: u32,
+) -> *mut dc_array::dc_array_t {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_ephemeral_timer_acks()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        let arr = dc_array_t::from(
+            ChatId::new(chat_id)
+                .get_ephemeral_timer_acks(ctx)
+                .await
+                .unwrap_or_log_default(ctx, "Failed get_ephemeral_timer_acks")
+                .iter()
+                .map(|id| id.to_u32())
+                .collect::<Vec<u32>>(),
+        );
+        Box::into_raw(Box::new(arr))
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_set_chat_ephemeral_timer(
     context: *mut dc_context_t,
@@ -1838,125 +2802,311 @@ fn from_prim<S, T>(s: S) -> Option<T>
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_get_msg_html(
+pub unsafe extern "C" fn dc_get_notification_intent(
     context: *mut dc_context_t,
     msg_id: u32,
-) -> *mut libc::c_char {
+) -> libc::c_int {
     if context.is_null() {
-        eprintln!("ignoring careless call to dc_get_msg_html()");
-        return ptr::null_mut();
+        eprintln!("ignoring careless call to dc_get_notification_intent()");
+        return 0;
     }
     let ctx = &*context;
 
-    block_on(MsgId::new(msg_id).get_html(ctx))
-        .unwrap_or_log_default(ctx, "Failed get_msg_html")
-        .strdup()
+    block_on(async move {
+        notification::get_notification_intent(ctx, MsgId::new(msg_id))
+            .await
+            .context("Failed to get notification intent")
+            .log_err(ctx)
+            .map(|intent| intent as libc::c_int)
+            .unwrap_or_default()
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_get_mime_headers(
+pub unsafe extern "C" fn dc_add_saved_tag(
     context: *mut dc_context_t,
     msg_id: u32,
-) -> *mut libc::c_char {
-    if context.is_null() {
-        eprintln!("ignoring careless call to dc_get_mime_headers()");
-        return ptr::null_mut(); // NULL explicitly defined as "no mime headers"
+    tag: *const libc::c_char,
+) -> libc::c_int {
+    if context.is_null() || tag.is_null() {
+        eprintln!("ignoring careless call to dc_add_saved_tag()");
+        return 0;
     }
     let ctx = &*context;
 
-    block_on(async move {
-        let mime = message::get_mime_headers(ctx, MsgId::new(msg_id))
-            .await
-            .unwrap_or_log_default(ctx, "failed to get mime headers");
-        if mime.is_empty() {
-            return ptr::null_mut();
-        }
-        mime.strdup()
-    })
+    block_on(MsgId::new(msg_id).add_saved_tag(ctx, &to_string_lossy(tag)))
+        .context("Failed to add saved tag")
+        .log_err(ctx)
+        .is_ok() as libc::c_int
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_delete_msgs(
+pub unsafe extern "C" fn dc_remove_saved_tag(
     context: *mut dc_context_t,
-    msg_ids: *const u32,
-    msg_cnt: libc::c_int,
-) {
-    if context.is_null() || msg_ids.is_null() || msg_cnt <= 0 {
-        eprintln!("ignoring careless call to dc_delete_msgs()");
-        return;
+    msg_id: u32,
+    tag: *const libc::c_char,
+) -> libc::c_int {
+    if context.is_null() || tag.is_null() {
+        eprintln!("ignoring careless call to dc_remove_saved_tag()");
+        return 0;
     }
     let ctx = &*context;
-    let msg_ids = convert_and_prune_message_ids(msg_ids, msg_cnt);
 
-    block_on(message::delete_msgs(ctx, &msg_ids))
-        .context("failed dc_delete_msgs() call")
+    block_on(MsgId::new(msg_id).remove_saved_tag(ctx, &to_string_lossy(tag)))
+        .context("Failed to remove saved tag")
         .log_err(ctx)
-        .ok();
+        .is_ok() as libc::c_int
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_forward_msgs(
+pub unsafe extern "C" fn dc_get_saved_tags(
     context: *mut dc_context_t,
-    msg_ids: *const u32,
-    msg_cnt: libc::c_int,
-    chat_id: u32,
-) {
-    if context.is_null()
-        || msg_ids.is_null()
-        || msg_cnt <= 0
-        || chat_id <= constants::DC_CHAT_ID_LAST_SPECIAL.to_u32()
-    {
-        eprintln!("ignoring careless call to dc_forward_msgs()");
-        return;
+    msg_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_saved_tags()");
+        return "".strdup();
     }
-    let msg_ids = convert_and_prune_message_ids(msg_ids, msg_cnt);
     let ctx = &*context;
 
-    block_on(async move {
-        chat::forward_msgs(ctx, &msg_ids[..], ChatId::new(chat_id))
-            .await
-            .unwrap_or_log_default(ctx, "Failed to forward message")
-    })
+    block_on(MsgId::new(msg_id).get_saved_tags(ctx))
+        .unwrap_or_log_default(ctx, "Failed to get saved tags")
+        .join("\n")
+        .strdup()
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_resend_msgs(
+pub unsafe extern "C" fn dc_get_saved_by_tag(
     context: *mut dc_context_t,
-    msg_ids: *const u32,
-    msg_cnt: libc::c_int,
-) -> libc::c_int {
-    if context.is_null() || msg_ids.is_null() || msg_cnt <= 0 {
-        eprintln!("ignoring careless call to dc_resend_msgs()");
-        return 0;
+    tag: *const libc::c_char,
+) -> *mut dc_array::dc_array_t {
+    if context.is_null() || tag.is_null() {
+        eprintln!("ignoring careless call to dc_get_saved_by_tag()");
+        return ptr::null_mut();
     }
     let ctx = &*context;
-    let msg_ids = convert_and_prune_message_ids(msg_ids, msg_cnt);
 
-    if let Err(err) = block_on(chat::resend_msgs(ctx, &msg_ids)) {
-        error!(ctx, "Resending failed: {err:#}");
-        0
-    } else {
-        1
-    }
+    block_on(async move {
+        let arr = dc_array_t::from(
+            ctx.get_saved_by_tag(&to_string_lossy(tag))
+                .await
+                .unwrap_or_log_default(ctx, "Failed to get saved messages by tag")
+                .iter()
+                .map(|msg_id| msg_id.to_u32())
+                .collect::<Vec<u32>>(),
+        );
+        Box::into_raw(Box::new(arr))
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_markseen_msgs(
+pub unsafe extern "C" fn dc_get_msg_html(
     context: *mut dc_context_t,
-    msg_ids: *const u32,
-    msg_cnt: libc::c_int,
-) {
-    if context.is_null() || msg_ids.is_null() || msg_cnt <= 0 {
-        eprintln!("ignoring careless call to dc_markseen_msgs()");
-        return;
+    msg_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_msg_html()");
+        return ptr::null_mut();
     }
-    let msg_ids = convert_and_prune_message_ids(msg_ids, msg_cnt);
     let ctx = &*context;
 
-    block_on(message::markseen_msgs(ctx, msg_ids))
-        .context("failed dc_markseen_msgs() call")
-        .log_err(ctx)
-        .ok();
+    block_on(MsgId::new(msg_id).get_html(ctx))
+        .unwrap_or_log_default(ctx, "Failed get_msg_html")
+        .strdup()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_msg_full_text(
+    context: *mut dc_context_t,
+    msg_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_msg_full_text()");
+        return "".strdup();
+    }
+    let ctx = &*context;
+
+    block_on(MsgId::new(msg_id).get_full_text(ctx))
+        .unwrap_or_log_default(ctx, "Failed get_msg_full_text")
+        .strdup()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_report_msg(
+    context: *mut dc_context_t,
+    msg_id: u32,
+    category: libc::c_int,
+    include_original: libc::c_int,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_report_msg()");
+        return 0;
+    }
+    let ctx = &*context;
+    let category = match report::ReportCategory::from_i32(category) {
+        Some(category) => category,
+        None => {
+            error!(ctx, "Unknown report category {category}");
+            return 0;
+        }
+    };
+
+    block_on(report::report_msg(
+        ctx,
+        MsgId::new(msg_id),
+        category,
+        include_original != 0,
+    ))
+    .context("Failed dc_report_msg()")
+    .log_err(ctx)
+    .is_ok() as libc::c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_is_msg_reported(
+    context: *mut dc_context_t,
+    msg_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_is_msg_reported()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(report::is_msg_reported(ctx, MsgId::new(msg_id)))
+        .unwrap_or_log_default(ctx, "Failed dc_is_msg_reported()") as libc::c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_open_view_once(
+    context: *mut dc_context_t,
+    msg_id: u32,
+    ret_bytes: *mut libc::size_t,
+) -> *mut libc::c_char {
+    if context.is_null() || ret_bytes.is_null() {
+        eprintln!("ignoring careless call to dc_open_view_once()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    match block_on(MsgId::new(msg_id).open_view_once(ctx))
+        .context("Failed to open view-once message")
+        .log_err(ctx)
+    {
+        Ok(data) => {
+            *ret_bytes = data.len();
+            let ptr = libc::malloc(*ret_bytes);
+            libc::memcpy(ptr, data.as_ptr() as *mut libc::c_void, *ret_bytes);
+            ptr as *mut libc::c_char
+        }
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_mime_headers(
+    context: *mut dc_context_t,
+    msg_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_mime_headers()");
+        return ptr::null_mut(); // NULL explicitly defined as "no mime headers"
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        let mime = message::get_mime_headers(ctx, MsgId::new(msg_id))
+            .await
+            .unwrap_or_log_default(ctx, "failed to get mime headers");
+        if mime.is_empty() {
+            return ptr::null_mut();
+        }
+        mime.strdup()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_delete_msgs(
+    context: *mut dc_context_t,
+    msg_ids: *const u32,
+    msg_cnt: libc::c_int,
+) {
+    if context.is_null() || msg_ids.is_null() || msg_cnt <= 0 {
+        eprintln!("ignoring careless call to dc_delete_msgs()");
+        return;
+    }
+    let ctx = &*context;
+    let msg_ids = convert_and_prune_message_ids(msg_ids, msg_cnt);
+
+    block_on(message::delete_msgs(ctx, &msg_ids))
+        .context("failed dc_delete_msgs() call")
+        .log_err(ctx)
+        .ok();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_forward_msgs(
+    context: *mut dc_context_t,
+    msg_ids: *const u32,
+    msg_cnt: libc::c_int,
+    chat_id: u32,
+) {
+    if context.is_null()
+        || msg_ids.is_null()
+        || msg_cnt <= 0
+        || chat_id <= constants::DC_CHAT_ID_LAST_SPECIAL.to_u32()
+    {
+        eprintln!("ignoring careless call to dc_forward_msgs()");
+        return;
+    }
+    let msg_ids = convert_and_prune_message_ids(msg_ids, msg_cnt);
+    let ctx = &*context;
+
+    block_on(async move {
+        chat::forward_msgs(ctx, &msg_ids[..], ChatId::new(chat_id))
+            .await
+            .unwrap_or_log_default(ctx, "Failed to forward message")
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_resend_msgs(
+    context: *mut dc_context_t,
+    msg_ids: *const u32,
+    msg_cnt: libc::c_int,
+) -> libc::c_int {
+    if context.is_null() || msg_ids.is_null() || msg_cnt <= 0 {
+        eprintln!("ignoring careless call to dc_resend_msgs()");
+        return 0;
+    }
+    let ctx = &*context;
+    let msg_ids = convert_and_prune_message_ids(msg_ids, msg_cnt);
+
+    if let Err(err) = block_on(chat::resend_msgs(ctx, &msg_ids)) {
+        error!(ctx, "Resending failed: {err:#}");
+        0
+    } else {
+        1
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_markseen_msgs(
+    context: *mut dc_context_t,
+    msg_ids: *const u32,
+    msg_cnt: libc::c_int,
+) {
+    if context.is_null() || msg_ids.is_null() || msg_cnt <= 0 {
+        eprintln!("ignoring careless call to dc_markseen_msgs()");
+        return;
+    }
+    let msg_ids = convert_and_prune_message_ids(msg_ids, msg_cnt);
+    let ctx = &*context;
+
+    block_on(message::markseen_msgs(ctx, msg_ids))
+        .context("failed dc_markseen_msgs() call")
+        .log_err(ctx)
+        .ok();
 }
 
 #[no_mangle]
@@ -2135,292 +3285,744 @@ fn from_prim<S, T>(s: S) -> Option<T>
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_block_contact(
-    context: *mut dc_context_t,
-    contact_id: u32,
-    block: libc::c_int,
-) {
-    let contact_id = ContactId::new(contact_id);
-    if context.is_null() || contact_id.is_special() {
-        eprintln!("ignoring careless call to dc_block_contact()");
-        return;
+pub unsafe extern "C" fn dc_export_blocklist(context: *mut dc_context_t) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_export_blocklist()");
+        return ptr::null_mut();
     }
     let ctx = &*context;
+
     block_on(async move {
-        if block == 0 {
-            Contact::unblock(ctx, contact_id)
-                .await
-                .context("Can't unblock contact")
-                .log_err(ctx)
-                .ok();
-        } else {
-            Contact::block(ctx, contact_id)
-                .await
-                .context("Can't block contact")
-                .log_err(ctx)
-                .ok();
+        match blocklist::export_blocklist(ctx)
+            .await
+            .context("failed dc_export_blocklist() call")
+            .log_err(ctx)
+        {
+            Ok(list) => list.strdup(),
+            Err(_) => ptr::null_mut(),
         }
-    });
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_get_contact_encrinfo(
+pub unsafe extern "C" fn dc_import_blocklist(
     context: *mut dc_context_t,
-    contact_id: u32,
-) -> *mut libc::c_char {
-    if context.is_null() {
-        eprintln!("ignoring careless call to dc_get_contact_encrinfo()");
-        return "".strdup();
+    data: *const libc::c_char,
+) -> libc::c_int {
+    if context.is_null() || data.is_null() {
+        eprintln!("ignoring careless call to dc_import_blocklist()");
+        return -1;
     }
     let ctx = &*context;
 
-    block_on(async move {
-        Contact::get_encrinfo(ctx, ContactId::new(contact_id))
-            .await
-            .map(|s| s.strdup())
-            .unwrap_or_else(|e| {
-                error!(ctx, "{e:#}");
-                ptr::null_mut()
-            })
-    })
+    block_on(blocklist::import_blocklist(ctx, &to_string_lossy(data)))
+        .context("failed dc_import_blocklist() call")
+        .log_err(ctx)
+        .map_or(-1, |blocked| blocked as libc::c_int)
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_delete_contact(
+pub unsafe extern "C" fn dc_add_blocklist_feed(
     context: *mut dc_context_t,
+    url: *const libc::c_char,
     contact_id: u32,
-) -> libc::c_int {
-    let contact_id = ContactId::new(contact_id);
-    if context.is_null() || contact_id.is_special() {
-        eprintln!("ignoring careless call to dc_delete_contact()");
+) -> u32 {
+    if context.is_null() || url.is_null() {
+        eprintln!("ignoring careless call to dc_add_blocklist_feed()");
         return 0;
     }
     let ctx = &*context;
 
-    block_on(async move {
-        match Contact::delete(ctx, contact_id).await {
-            Ok(_) => 1,
-            Err(err) => {
-                error!(ctx, "cannot delete contact: {err:#}");
-                0
-            }
-        }
-    })
+    block_on(blocklist::add_blocklist_feed(
+        ctx,
+        &to_string_lossy(url),
+        ContactId::new(contact_id),
+    ))
+    .unwrap_or_log_default(ctx, "Failed dc_add_blocklist_feed()")
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_get_contact(
+pub unsafe extern "C" fn dc_get_blocklist_feeds(
     context: *mut dc_context_t,
-    contact_id: u32,
-) -> *mut dc_contact_t {
+) -> *mut dc_array::dc_array_t {
     if context.is_null() {
-        eprintln!("ignoring careless call to dc_get_contact()");
+        eprintln!("ignoring careless call to dc_get_blocklist_feeds()");
         return ptr::null_mut();
     }
     let ctx = &*context;
 
     block_on(async move {
-        Contact::get_by_id(ctx, ContactId::new(contact_id))
-            .await
-            .map(|contact| Box::into_raw(Box::new(ContactWrapper { context, contact })))
-            .unwrap_or_else(|_| ptr::null_mut())
+        let arr = dc_array_t::from(
+            blocklist::get_blocklist_feeds(ctx)
+                .await
+                .unwrap_or_log_default(ctx, "Failed get_blocklist_feeds")
+                .iter()
+                .map(|feed| feed.id)
+                .collect::<Vec<u32>>(),
+        );
+        Box::into_raw(Box::new(arr))
     })
 }
 
-fn spawn_imex(ctx: Context, what: imex::ImexMode, param1: String, passphrase: Option<String>) {
-    spawn(async move {
-        imex::imex(&ctx, what, param1.as_ref(), passphrase)
-            .await
-            .context("IMEX failed")
-            .log_err(&ctx)
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_blocklist_feed_url(
+    context: *mut dc_context_t,
+    feed_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_blocklist_feed_url()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    block_on(blocklist::get_blocklist_feeds(ctx))
+        .unwrap_or_log_default(ctx, "Failed dc_get_blocklist_feed_url()")
+        .into_iter()
+        .find(|feed| feed.id == feed_id)
+        .map_or(ptr::null_mut(), |feed| feed.url.strdup())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_blocklist_feed_error(
+    context: *mut dc_context_t,
+    feed_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_blocklist_feed_error()");
+        return "".strdup();
+    }
+    let ctx = &*context;
+
+    block_on(blocklist::get_blocklist_feeds(ctx))
+        .unwrap_or_log_default(ctx, "Failed dc_get_blocklist_feed_error()")
+        .into_iter()
+        .find(|feed| feed.id == feed_id)
+        .map(|feed| feed.last_error)
+        .unwrap_or_default()
+        .strdup()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_delete_blocklist_feed(
+    context: *mut dc_context_t,
+    feed_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_delete_blocklist_feed()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(blocklist::delete_blocklist_feed(ctx, feed_id))
+        .context("failed dc_delete_blocklist_feed() call")
+        .log_err(ctx)
+        .is_ok() as libc::c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_block_contact(
+    context: *mut dc_context_t,
+    contact_id: u32,
+    block: libc::c_int,
+) {
+    let contact_id = ContactId::new(contact_id);
+    if context.is_null() || contact_id.is_special() {
+        eprintln!("ignoring careless call to dc_block_contact()");
+        return;
+    }
+    let ctx = &*context;
+    block_on(async move {
+        if block == 0 {
+            Contact::unblock(ctx, contact_id)
+                .await
+                .context("Can't unblock contact")
+                .log_err(ctx)
+                .ok();
+        } else {
+            Contact::block(ctx, contact_id)
+                .await
+                .context("Can't block contact")
+                .log_err(ctx)
+                .ok();
+        }
     });
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_imex(
+pub unsafe extern "C" fn dc_set_contact_share_last_seen(
+    context: *mut dc_context_t,
+    contact_id: u32,
+    share: libc::c_int,
+) {
+    let contact_id = ContactId::new(contact_id);
+    if context.is_null() || contact_id.is_special() {
+        eprintln!("ignoring careless call to dc_set_contact_share_last_seen()");
+        return;
+    }
+    let ctx = &*context;
+    block_on(Contact::set_share_last_seen(ctx, contact_id, share != 0))
+        .context("Can't set last-seen sharing")
+        .log_err(ctx)
+        .ok();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_contact_encrinfo(
+    context: *mut dc_context_t,
+    contact_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_contact_encrinfo()");
+        return "".strdup();
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        Contact::get_encrinfo(ctx, ContactId::new(contact_id))
+            .await
+            .map(|s| s.strdup())
+            .unwrap_or_else(|e| {
+                error!(ctx, "{e:#}");
+                ptr::null_mut()
+            })
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_delete_contact(
+    context: *mut dc_context_t,
+    contact_id: u32,
+) -> libc::c_int {
+    let contact_id = ContactId::new(contact_id);
+    if context.is_null() || contact_id.is_special() {
+        eprintln!("ignoring careless call to dc_delete_contact()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        match Contact::delete(ctx, contact_id).await {
+            Ok(_) => 1,
+            Err(err) => {
+                error!(ctx, "cannot delete contact: {err:#}");
+                0
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_contact(
+    context: *mut dc_context_t,
+    contact_id: u32,
+) -> *mut dc_contact_t {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_contact()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        Contact::get_by_id(ctx, ContactId::new(contact_id))
+            .await
+            .map(|contact| Box::into_raw(Box::new(ContactWrapper { context, contact })))
+            .unwrap_or_else(|_| ptr::null_mut())
+    })
+}
+
+fn spawn_imex(ctx: Context, what: imex::ImexMode, param1: String, passphrase: Option<String>) {
+    spawn(async move {
+        imex::imex(&ctx, what, param1.as_ref(), passphrase)
+            .await
+            .context("IMEX failed")
+            .log_err(&ctx)
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_imex(
+    context: *mut dc_context_t,
+    what_raw: libc::c_int,
+    param1: *const libc::c_char,
+    param2: *const libc::c_char,
+) {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_imex()");
+        return;
+    }
+    let what = match imex::ImexMode::from_i32(what_raw) {
+        Some(what) => what,
+        None => {
+            eprintln!("ignoring invalid argument {what_raw} to dc_imex");
+            return;
+        }
+    };
+    let passphrase = to_opt_string_lossy(param2);
+
+    let ctx = &*context;
+
+    if let Some(param1) = to_opt_string_lossy(param1) {
+        spawn_imex(ctx.clone(), what, param1, passphrase);
+    } else {
+        eprintln!("dc_imex called without a valid directory");
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_imex_has_backup(
+    context: *mut dc_context_t,
+    dir: *const libc::c_char,
+) -> *mut libc::c_char {
+    if context.is_null() || dir.is_null() {
+        eprintln!("ignoring careless call to dc_imex_has_backup()");
+        return ptr::null_mut(); // NULL explicitly defined as "has no backup"
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        match imex::has_backup(ctx, to_string_lossy(dir).as_ref()).await {
+            Ok(res) => res.strdup(),
+            Err(err) => {
+                // do not bubble up error to the user,
+                // the ui will expect that the file does not exist or cannot be accessed
+                warn!(ctx, "dc_imex_has_backup: {err:#}");
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_initiate_key_transfer(context: *mut dc_context_t) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_initiate_key_transfer()");
+        return ptr::null_mut(); // NULL explicitly defined as "error"
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        match imex::initiate_key_transfer(ctx).await {
+            Ok(res) => res.strdup(),
+            Err(err) => {
+                error!(ctx, "dc_initiate_key_transfer(): {err:#}");
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_continue_key_transfer(
+    context: *mut dc_context_t,
+    msg_id: u32,
+    setup_code: *const libc::c_char,
+) -> libc::c_int {
+    if context.is_null() || msg_id <= constants::DC_MSG_ID_LAST_SPECIAL || setup_code.is_null() {
+        eprintln!("ignoring careless call to dc_continue_key_transfer()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        match imex::continue_key_transfer(ctx, MsgId::new(msg_id), &to_string_lossy(setup_code))
+            .await
+        {
+            Ok(()) => 1,
+            Err(err) => {
+                warn!(ctx, "dc_continue_key_transfer: {err:#}");
+                0
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_stop_ongoing_process(context: *mut dc_context_t) {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_stop_ongoing_process()");
+        return;
+    }
+    let ctx = &*context;
+    block_on(ctx.stop_ongoing());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_check_qr(
+    context: *mut dc_context_t,
+    qr: *const libc::c_char,
+) -> *mut dc_lot_t {
+    if context.is_null() || qr.is_null() {
+        eprintln!("ignoring careless call to dc_check_qr()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    let lot = match block_on(qr::check_qr(ctx, &to_string_lossy(qr))) {
+        Ok(qr) => qr.into(),
+        Err(err) => err.into(),
+    };
+    Box::into_raw(Box::new(lot))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_securejoin_qr(
+    context: *mut dc_context_t,
+    chat_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_securejoin_qr()");
+        return "".strdup();
+    }
+    let ctx = &*context;
+    let chat_id = if chat_id == 0 {
+        None
+    } else {
+        Some(ChatId::new(chat_id))
+    };
+
+    block_on(securejoin::get_securejoin_qr(ctx, chat_id))
+        .unwrap_or_else(|_| "".to_string())
+        .strdup()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_securejoin_qr_svg(
+    context: *mut dc_context_t,
+    chat_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to generate_verification_qr()");
+        return "".strdup();
+    }
+    let ctx = &*context;
+    let chat_id = if chat_id == 0 {
+        None
+    } else {
+        Some(ChatId::new(chat_id))
+    };
+
+    block_on(get_securejoin_qr_svg(ctx, chat_id))
+        .unwrap_or_else(|_| "".to_string())
+        .strdup()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_join_securejoin(
+    context: *mut dc_context_t,
+    qr: *const libc::c_char,
+) -> u32 {
+    if context.is_null() || qr.is_null() {
+        eprintln!("ignoring careless call to dc_join_securejoin()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        securejoin::join_securejoin(ctx, &to_string_lossy(qr))
+            .await
+            .map(|chatid| chatid.to_u32())
+            .context("failed dc_join_securejoin() call")
+            .log_err(ctx)
+            .unwrap_or_default()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_forward_group_invite(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    contact_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_forward_group_invite()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        securejoin::forward_group_invite(ctx, ChatId::new(chat_id), ContactId::new(contact_id))
+            .await
+            .context("failed dc_forward_group_invite() call")
+            .log_err(ctx)
+            .is_ok() as libc::c_int
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_get_group_invite_name(
+    context: *mut dc_context_t,
+    invite_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_group_invite_name()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        match securejoin::get_group_invite(ctx, invite_id)
+            .await
+            .context("failed dc_get_group_invite_name() call")
+            .log_err(ctx)
+        {
+            Ok(Some(invite)) => invite.group_name.strdup(),
+            Ok(None) | Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_accept_group_invite(
+    context: *mut dc_context_t,
+    invite_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_accept_group_invite()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        securejoin::accept_group_invite(ctx, invite_id)
+            .await
+            .context("failed dc_accept_group_invite() call")
+            .log_err(ctx)
+            .is_ok() as libc::c_int
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_set_join_gate(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    enabled: libc::c_int,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_set_join_gate()");
+        return 0;
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        securejoin::set_join_gate(ctx, ChatId::new(chat_id), enabled != 0)
+            .await
+            .context("failed dc_set_join_gate() call")
+            .log_err(ctx)
+            .is_ok() as libc::c_int
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_is_join_gate_enabled(
     context: *mut dc_context_t,
-    what_raw: libc::c_int,
-    param1: *const libc::c_char,
-    param2: *const libc::c_char,
-) {
+    chat_id: u32,
+) -> libc::c_int {
     if context.is_null() {
-        eprintln!("ignoring careless call to dc_imex()");
-        return;
+        eprintln!("ignoring careless call to dc_is_join_gate_enabled()");
+        return 0;
     }
-    let what = match imex::ImexMode::from_i32(what_raw) {
-        Some(what) => what,
-        None => {
-            eprintln!("ignoring invalid argument {what_raw} to dc_imex");
-            return;
-        }
-    };
-    let passphrase = to_opt_string_lossy(param2);
-
     let ctx = &*context;
 
-    if let Some(param1) = to_opt_string_lossy(param1) {
-        spawn_imex(ctx.clone(), what, param1, passphrase);
-    } else {
-        eprintln!("dc_imex called without a valid directory");
-    }
+    block_on(securejoin::is_join_gate_enabled(ctx, ChatId::new(chat_id)))
+        .unwrap_or_log_default(ctx, "Failed dc_is_join_gate_enabled()") as libc::c_int
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_imex_has_backup(
+pub unsafe extern "C" fn dc_get_join_challenge_question(
     context: *mut dc_context_t,
-    dir: *const libc::c_char,
+    challenge_id: u32,
 ) -> *mut libc::c_char {
-    if context.is_null() || dir.is_null() {
-        eprintln!("ignoring careless call to dc_imex_has_backup()");
-        return ptr::null_mut(); // NULL explicitly defined as "has no backup"
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_join_challenge_question()");
+        return ptr::null_mut();
     }
     let ctx = &*context;
 
     block_on(async move {
-        match imex::has_backup(ctx, to_string_lossy(dir).as_ref()).await {
-            Ok(res) => res.strdup(),
-            Err(err) => {
-                // do not bubble up error to the user,
-                // the ui will expect that the file does not exist or cannot be accessed
-                warn!(ctx, "dc_imex_has_backup: {err:#}");
-                ptr::null_mut()
-            }
+        match securejoin::get_join_challenge(ctx, challenge_id)
+            .await
+            .context("failed dc_get_join_challenge_question() call")
+            .log_err(ctx)
+        {
+            Ok(Some(challenge)) => challenge.question.strdup(),
+            Ok(None) | Err(_) => ptr::null_mut(),
         }
     })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_initiate_key_transfer(context: *mut dc_context_t) -> *mut libc::c_char {
+pub unsafe extern "C" fn dc_get_join_challenge_options(
+    context: *mut dc_context_t,
+    challenge_id: u32,
+) -> *mut libc::c_char {
     if context.is_null() {
-        eprintln!("ignoring careless call to dc_initiate_key_transfer()");
-        return ptr::null_mut(); // NULL explicitly defined as "error"
+        eprintln!("ignoring careless call to dc_get_join_challenge_options()");
+        return ptr::null_mut();
     }
     let ctx = &*context;
 
     block_on(async move {
-        match imex::initiate_key_transfer(ctx).await {
-            Ok(res) => res.strdup(),
-            Err(err) => {
-                error!(ctx, "dc_initiate_key_transfer(): {err:#}");
-                ptr::null_mut()
-            }
+        match securejoin::get_join_challenge(ctx, challenge_id)
+            .await
+            .context("failed dc_get_join_challenge_options() call")
+            .log_err(ctx)
+        {
+            Ok(Some(challenge)) => challenge.options.join(" ").strdup(),
+            Ok(None) | Err(_) => ptr::null_mut(),
         }
     })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_continue_key_transfer(
+pub unsafe extern "C" fn dc_answer_join_challenge(
     context: *mut dc_context_t,
-    msg_id: u32,
-    setup_code: *const libc::c_char,
+    challenge_id: u32,
+    answer: *const libc::c_char,
 ) -> libc::c_int {
-    if context.is_null() || msg_id <= constants::DC_MSG_ID_LAST_SPECIAL || setup_code.is_null() {
-        eprintln!("ignoring careless call to dc_continue_key_transfer()");
+    if context.is_null() || answer.is_null() {
+        eprintln!("ignoring careless call to dc_answer_join_challenge()");
         return 0;
     }
     let ctx = &*context;
 
     block_on(async move {
-        match imex::continue_key_transfer(ctx, MsgId::new(msg_id), &to_string_lossy(setup_code))
+        securejoin::answer_join_challenge(ctx, challenge_id, &to_string_lossy(answer))
+            .await
+            .context("failed dc_answer_join_challenge() call")
+            .log_err(ctx)
+            .is_ok() as libc::c_int
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_create_invite_link(
+    context: *mut dc_context_t,
+    chat_id: u32,
+    max_uses: libc::c_int,
+) -> *mut libc::c_char {
+    if context.is_null() || max_uses <= 0 {
+        eprintln!("ignoring careless call to dc_create_invite_link()");
+        return ptr::null_mut();
+    }
+    let ctx = &*context;
+
+    block_on(async move {
+        match securejoin::create_invite_link(ctx, ChatId::new(chat_id), max_uses as u32)
             .await
+            .context("failed dc_create_invite_link() call")
+            .log_err(ctx)
         {
-            Ok(()) => 1,
-            Err(err) => {
-                warn!(ctx, "dc_continue_key_transfer: {err:#}");
-                0
-            }
+            Ok(qr) => qr.strdup(),
+            Err(_) => ptr::null_mut(),
         }
     })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_stop_ongoing_process(context: *mut dc_context_t) {
+pub unsafe extern "C" fn dc_get_invite_links(
+    context: *mut dc_context_t,
+    chat_id: u32,
+) -> *mut dc_array::dc_array_t {
     if context.is_null() {
-        eprintln!("ignoring careless call to dc_stop_ongoing_process()");
-        return;
+        eprintln!("ignoring careless call to dc_get_invite_links()");
+        return ptr::null_mut();
     }
     let ctx = &*context;
-    block_on(ctx.stop_ongoing());
+
+    block_on(async move {
+        let arr = dc_array_t::from(
+            securejoin::get_invite_links(ctx, ChatId::new(chat_id))
+                .await
+                .unwrap_or_log_default(ctx, "Failed get_invite_links")
+                .iter()
+                .map(|link| link.id)
+                .collect::<Vec<u32>>(),
+        );
+        Box::into_raw(Box::new(arr))
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_check_qr(
+pub unsafe extern "C" fn dc_get_invite_link_qr(
     context: *mut dc_context_t,
-    qr: *const libc::c_char,
-) -> *mut dc_lot_t {
-    if context.is_null() || qr.is_null() {
-        eprintln!("ignoring careless call to dc_check_qr()");
+    link_id: u32,
+) -> *mut libc::c_char {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_get_invite_link_qr()");
         return ptr::null_mut();
     }
     let ctx = &*context;
 
-    let lot = match block_on(qr::check_qr(ctx, &to_string_lossy(qr))) {
-        Ok(qr) => qr.into(),
-        Err(err) => err.into(),
-    };
-    Box::into_raw(Box::new(lot))
+    block_on(async move {
+        match securejoin::get_invite_link(ctx, link_id)
+            .await
+            .context("failed dc_get_invite_link_qr() call")
+            .log_err(ctx)
+        {
+            Ok(Some(link)) => link.qr.strdup(),
+            Ok(None) | Err(_) => ptr::null_mut(),
+        }
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_get_securejoin_qr(
+pub unsafe extern "C" fn dc_get_invite_link_max_uses(
     context: *mut dc_context_t,
-    chat_id: u32,
-) -> *mut libc::c_char {
+    link_id: u32,
+) -> libc::c_int {
     if context.is_null() {
-        eprintln!("ignoring careless call to dc_get_securejoin_qr()");
-        return "".strdup();
+        eprintln!("ignoring careless call to dc_get_invite_link_max_uses()");
+        return 0;
     }
     let ctx = &*context;
-    let chat_id = if chat_id == 0 {
-        None
-    } else {
-        Some(ChatId::new(chat_id))
-    };
 
-    block_on(securejoin::get_securejoin_qr(ctx, chat_id))
-        .unwrap_or_else(|_| "".to_string())
-        .strdup()
+    block_on(securejoin::get_invite_link(ctx, link_id))
+        .unwrap_or_log_default(ctx, "Failed dc_get_invite_link_max_uses()")
+        .map(|link| link.max_uses as libc::c_int)
+        .unwrap_or_default()
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_get_securejoin_qr_svg(
+pub unsafe extern "C" fn dc_get_invite_link_joins(
     context: *mut dc_context_t,
-    chat_id: u32,
-) -> *mut libc::c_char {
+    link_id: u32,
+) -> *mut dc_array::dc_array_t {
     if context.is_null() {
-        eprintln!("ignoring careless call to generate_verification_qr()");
-        return "".strdup();
+        eprintln!("ignoring careless call to dc_get_invite_link_joins()");
+        return ptr::null_mut();
     }
     let ctx = &*context;
-    let chat_id = if chat_id == 0 {
-        None
-    } else {
-        Some(ChatId::new(chat_id))
-    };
 
-    block_on(get_securejoin_qr_svg(ctx, chat_id))
-        .unwrap_or_else(|_| "".to_string())
-        .strdup()
+    block_on(async move {
+        let arr = dc_array_t::from(
+            securejoin::get_invite_link_joins(ctx, link_id)
+                .await
+                .unwrap_or_log_default(ctx, "Failed get_invite_link_joins")
+                .iter()
+                .map(|join| join.contact_id.to_u32())
+                .collect::<Vec<u32>>(),
+        );
+        Box::into_raw(Box::new(arr))
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_join_securejoin(
+pub unsafe extern "C" fn dc_delete_invite_link(
     context: *mut dc_context_t,
-    qr: *const libc::c_char,
-) -> u32 {
-    if context.is_null() || qr.is_null() {
-        eprintln!("ignoring careless call to dc_join_securejoin()");
+    link_id: u32,
+) -> libc::c_int {
+    if context.is_null() {
+        eprintln!("ignoring careless call to dc_delete_invite_link()");
         return 0;
     }
     let ctx = &*context;
 
     block_on(async move {
-        securejoin::join_securejoin(ctx, &to_string_lossy(qr))
+        securejoin::delete_invite_link(ctx, link_id)
             .await
-            .map(|chatid| chatid.to_u32())
-            .context("failed dc_join_securejoin() call")
+            .context("failed dc_delete_invite_link() call")
             .log_err(ctx)
-            .unwrap_or_default()
+            .is_ok() as libc::c_int
     })
 }
 
@@ -2974,6 +4576,20 @@ pub struct ChatWrapper {
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_chat_get_archive_mode(chat: *mut dc_chat_t) -> libc::c_int {
+    if chat.is_null() {
+        eprintln!("ignoring careless call to dc_chat_get_archive_mode()");
+        return 0;
+    }
+    let ffi_chat = &*chat;
+    ffi_chat
+        .chat
+        .get_archive_mode()
+        .to_i32()
+        .unwrap_or_default()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_chat_is_contact_request(chat: *mut dc_chat_t) -> libc::c_int {
     if chat.is_null() {
@@ -3014,6 +4630,29 @@ pub struct ChatWrapper {
     ffi_chat.chat.is_device_talk() as libc::c_int
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_chat_is_threaded(chat: *mut dc_chat_t) -> libc::c_int {
+    if chat.is_null() {
+        eprintln!("ignoring careless call to dc_chat_is_threaded()");
+        return 0;
+    }
+    let ffi_chat = &*chat;
+    ffi_chat.chat.is_threaded() as libc::c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_chat_get_signature(chat: *mut dc_chat_t) -> *mut libc::c_char {
+    if chat.is_null() {
+        eprintln!("ignoring careless call to dc_chat_get_signature()");
+        return ptr::null_mut();
+    }
+    let ffi_chat = &*chat;
+    match ffi_chat.chat.get_signature() {
+        Some(signature) => signature.strdup(),
+        None => ptr::null_mut(),
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_chat_can_send(chat: *mut dc_chat_t) -> libc::c_int {
     if chat.is_null() {
@@ -3038,6 +4677,16 @@ pub struct ChatWrapper {
     ffi_chat.chat.is_protected() as libc::c_int
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_chat_is_name_suspicious(chat: *mut dc_chat_t) -> libc::c_int {
+    if chat.is_null() {
+        eprintln!("ignoring careless call to dc_chat_is_name_suspicious()");
+        return 0;
+    }
+    let ffi_chat = &*chat;
+    ffi_chat.chat.is_name_suspicious() as libc::c_int
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_chat_is_sending_locations(chat: *mut dc_chat_t) -> libc::c_int {
     if chat.is_null() {
@@ -3188,6 +4837,44 @@ pub struct MessageWrapper {
     ffi_msg.message.get_chat_id().to_u32()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_saved_from_chat_id(msg: *mut dc_msg_t) -> u32 {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_saved_from_chat_id()");
+        return 0;
+    }
+    let ffi_msg = &*msg;
+    ffi_msg
+        .message
+        .get_saved_from_chat_id()
+        .map(|chat_id| chat_id.to_u32())
+        .unwrap_or_default()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_saved_from_contact_id(msg: *mut dc_msg_t) -> u32 {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_saved_from_contact_id()");
+        return 0;
+    }
+    let ffi_msg = &*msg;
+    ffi_msg
+        .message
+        .get_saved_from_contact_id()
+        .map(|contact_id| contact_id.to_u32())
+        .unwrap_or_default()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_removed_metadata(msg: *mut dc_msg_t) -> *mut libc::c_char {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_removed_metadata()");
+        return "".strdup();
+    }
+    let ffi_msg = &*msg;
+    ffi_msg.message.get_removed_metadata().join(" ").strdup()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_get_viewtype(msg: *mut dc_msg_t) -> libc::c_int {
     if msg.is_null() {
@@ -3263,26 +4950,55 @@ pub struct MessageWrapper {
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_msg_get_subject(msg: *mut dc_msg_t) -> *mut libc::c_char {
+pub unsafe extern "C" fn dc_msg_get_subject(msg: *mut dc_msg_t) -> *mut libc::c_char {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_subject()");
+        return "".strdup();
+    }
+    let ffi_msg = &*msg;
+    ffi_msg.message.get_subject().strdup()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_file(msg: *mut dc_msg_t) -> *mut libc::c_char {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_file()");
+        return "".strdup();
+    }
+    let ffi_msg = &*msg;
+    let ctx = &*ffi_msg.context;
+    ffi_msg
+        .message
+        .get_file(ctx)
+        .map(|p| p.to_string_lossy().strdup())
+        .unwrap_or_else(|| "".strdup())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_attachment_cnt(msg: *mut dc_msg_t) -> libc::c_int {
     if msg.is_null() {
-        eprintln!("ignoring careless call to dc_msg_get_subject()");
-        return "".strdup();
+        eprintln!("ignoring careless call to dc_msg_get_attachment_cnt()");
+        return 0;
     }
     let ffi_msg = &*msg;
-    ffi_msg.message.get_subject().strdup()
+    let ctx = &*ffi_msg.context;
+    ffi_msg.message.get_attachments(ctx).len() as libc::c_int
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn dc_msg_get_file(msg: *mut dc_msg_t) -> *mut libc::c_char {
+pub unsafe extern "C" fn dc_msg_get_attachment(
+    msg: *mut dc_msg_t,
+    index: libc::c_int,
+) -> *mut libc::c_char {
     if msg.is_null() {
-        eprintln!("ignoring careless call to dc_msg_get_file()");
+        eprintln!("ignoring careless call to dc_msg_get_attachment()");
         return "".strdup();
     }
     let ffi_msg = &*msg;
     let ctx = &*ffi_msg.context;
-    ffi_msg
-        .message
-        .get_file(ctx)
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| ffi_msg.message.get_attachments(ctx).get(index).cloned())
         .map(|p| p.to_string_lossy().strdup())
         .unwrap_or_else(|| "".strdup())
 }
@@ -3352,6 +5068,16 @@ pub struct MessageWrapper {
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_webxdc_href(msg: *mut dc_msg_t) -> *mut libc::c_char {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_webxdc_href()");
+        return ptr::null_mut();
+    }
+    let ffi_msg = &*msg;
+    ffi_msg.message.get_webxdc_href().strdup()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_get_filemime(msg: *mut dc_msg_t) -> *mut libc::c_char {
     if msg.is_null() {
@@ -3380,6 +5106,34 @@ pub struct MessageWrapper {
         .unwrap_or_default()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_p2p_filename(msg: *mut dc_msg_t) -> *mut libc::c_char {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_p2p_filename()");
+        return ptr::null_mut();
+    }
+    let ffi_msg = &*msg;
+    ffi_msg
+        .message
+        .get_p2p_file()
+        .map(|(name, _)| name)
+        .strdup()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_p2p_filebytes(msg: *mut dc_msg_t) -> u64 {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_p2p_filebytes()");
+        return 0;
+    }
+    let ffi_msg = &*msg;
+    ffi_msg
+        .message
+        .get_p2p_file()
+        .map(|(_, bytes)| bytes)
+        .unwrap_or_default()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_get_width(msg: *mut dc_msg_t) -> libc::c_int {
     if msg.is_null() {
@@ -3410,6 +5164,29 @@ pub struct MessageWrapper {
     ffi_msg.message.get_duration()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_call_state(msg: *mut dc_msg_t) -> libc::c_int {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_call_state()");
+        return -1;
+    }
+    let ffi_msg = &*msg;
+    ffi_msg
+        .message
+        .get_call_state()
+        .map_or(-1, |state| state as libc::c_int)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_delivery_delay(msg: *mut dc_msg_t) -> i64 {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_delivery_delay()");
+        return 0;
+    }
+    let ffi_msg = &*msg;
+    ffi_msg.message.get_delivery_delay().unwrap_or_default()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_get_showpadlock(msg: *mut dc_msg_t) -> libc::c_int {
     if msg.is_null() {
@@ -3508,6 +5285,20 @@ pub struct MessageWrapper {
     ffi_msg.message.get_override_sender_name().strdup()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_device_action(msg: *mut dc_msg_t) -> *mut libc::c_char {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_device_action()");
+        return ptr::null_mut();
+    }
+    let ffi_msg = &*msg;
+    ffi_msg
+        .message
+        .get_device_action()
+        .map(|action| action.as_ref().to_string())
+        .strdup()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_has_deviating_timestamp(msg: *mut dc_msg_t) -> libc::c_int {
     if msg.is_null() {
@@ -3548,6 +5339,36 @@ pub struct MessageWrapper {
     ffi_msg.message.is_forwarded().into()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_is_starred(msg: *mut dc_msg_t) -> libc::c_int {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_is_starred()");
+        return 0;
+    }
+    let ffi_msg = &*msg;
+    ffi_msg.message.is_starred().into()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_is_view_once(msg: *mut dc_msg_t) -> libc::c_int {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_is_view_once()");
+        return 0;
+    }
+    let ffi_msg = &*msg;
+    ffi_msg.message.is_view_once().into()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_is_view_once_viewed(msg: *mut dc_msg_t) -> libc::c_int {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_is_view_once_viewed()");
+        return 0;
+    }
+    let ffi_msg = &*msg;
+    ffi_msg.message.is_view_once_viewed().into()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_is_info(msg: *mut dc_msg_t) -> libc::c_int {
     if msg.is_null() {
@@ -3568,6 +5389,20 @@ pub struct MessageWrapper {
     ffi_msg.message.get_info_type() as libc::c_int
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_info_group(msg: *mut dc_msg_t) -> u32 {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_info_group()");
+        return 0;
+    }
+    let ffi_msg = &*msg;
+    ffi_msg
+        .message
+        .get_info_group()
+        .map(|msg_id| msg_id.to_u32())
+        .unwrap_or_default()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_is_increation(msg: *mut dc_msg_t) -> libc::c_int {
     if msg.is_null() {
@@ -3613,6 +5448,31 @@ pub struct MessageWrapper {
         .strdup()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_videochat_room(msg: *mut dc_msg_t) -> *mut libc::c_char {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_videochat_room()");
+        return ptr::null_mut();
+    }
+    let ffi_msg = &*msg;
+
+    ffi_msg
+        .message
+        .get_videochat_invitation()
+        .and_then(|invitation| invitation.room)
+        .strdup()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_get_album_id(msg: *mut dc_msg_t) -> *mut libc::c_char {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_get_album_id()");
+        return ptr::null_mut();
+    }
+    let ffi_msg = &*msg;
+    ffi_msg.message.get_album_id().strdup()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_get_videochat_type(msg: *mut dc_msg_t) -> libc::c_int {
     if msg.is_null() {
@@ -3647,6 +5507,19 @@ pub struct MessageWrapper {
     ffi_msg.message.set_text(to_opt_string_lossy(text))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_set_device_action(msg: *mut dc_msg_t, action: *const libc::c_char) {
+    if msg.is_null() || action.is_null() {
+        eprintln!("ignoring careless call to dc_msg_set_device_action()");
+        return;
+    }
+    let ffi_msg = &mut *msg;
+    match to_string_lossy(action).parse::<message::DeviceAction>() {
+        Ok(action) => ffi_msg.message.set_device_action(action),
+        Err(_) => eprintln!("ignoring unknown action in dc_msg_set_device_action()"),
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_set_html(msg: *mut dc_msg_t, html: *const libc::c_char) {
     if msg.is_null() {
@@ -3699,6 +5572,16 @@ pub struct MessageWrapper {
     )
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_add_attachment(msg: *mut dc_msg_t, file: *const libc::c_char) {
+    if msg.is_null() || file.is_null() {
+        eprintln!("ignoring careless call to dc_msg_add_attachment()");
+        return;
+    }
+    let ffi_msg = &mut *msg;
+    ffi_msg.message.add_attachment(&to_string_lossy(file))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_set_dimension(
     msg: *mut dc_msg_t,
@@ -3723,6 +5606,16 @@ pub struct MessageWrapper {
     ffi_msg.message.set_duration(duration)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_msg_set_view_once(msg: *mut dc_msg_t, view_once: libc::c_int) {
+    if msg.is_null() {
+        eprintln!("ignoring careless call to dc_msg_set_view_once()");
+        return;
+    }
+    let ffi_msg = &mut *msg;
+    ffi_msg.message.set_view_once(view_once != 0)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_msg_set_location(
     msg: *mut dc_msg_t,
@@ -3985,6 +5878,28 @@ pub struct ContactWrapper {
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_contact_get_profile_image_thumbnail(
+    contact: *mut dc_contact_t,
+) -> *mut libc::c_char {
+    if contact.is_null() {
+        eprintln!("ignoring careless call to dc_contact_get_profile_image_thumbnail()");
+        return ptr::null_mut();
+    }
+    let ffi_contact = &*contact;
+    let ctx = &*ffi_contact.context;
+
+    block_on(async move {
+        ffi_contact
+            .contact
+            .get_profile_image_thumbnail(ctx)
+            .await
+            .unwrap_or_log_default(ctx, "failed to get profile image thumbnail")
+            .map(|p| p.to_string_lossy().strdup())
+            .unwrap_or_else(std::ptr::null_mut)
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_contact_get_color(contact: *mut dc_contact_t) -> u32 {
     if contact.is_null() {
@@ -4035,6 +5950,26 @@ pub struct ContactWrapper {
     ffi_contact.contact.is_blocked() as libc::c_int
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_contact_shares_last_seen(contact: *mut dc_contact_t) -> libc::c_int {
+    if contact.is_null() {
+        eprintln!("ignoring careless call to dc_contact_shares_last_seen()");
+        return 0;
+    }
+    let ffi_contact = &*contact;
+    ffi_contact.contact.shares_last_seen() as libc::c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_contact_is_name_suspicious(contact: *mut dc_contact_t) -> libc::c_int {
+    if contact.is_null() {
+        eprintln!("ignoring careless call to dc_contact_is_name_suspicious()");
+        return 0;
+    }
+    let ffi_contact = &*contact;
+    ffi_contact.contact.is_name_suspicious() as libc::c_int
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_contact_is_verified(contact: *mut dc_contact_t) -> libc::c_int {
     if contact.is_null() {
@@ -4119,6 +6054,17 @@ pub struct ContactWrapper {
     lot.get_text2().strdup()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_lot_get_text2_direction(lot: *mut dc_lot_t) -> libc::c_int {
+    if lot.is_null() {
+        eprintln!("ignoring careless call to dc_lot_get_text2_direction()");
+        return 0;
+    }
+
+    let lot = &*lot;
+    lot.get_text2_direction() as libc::c_int
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_lot_get_text1_meaning(lot: *mut dc_lot_t) -> libc::c_int {
     if lot.is_null() {
@@ -4426,6 +6372,14 @@ fn convert_and_prune_message_ids(msg_ids: *const u32, msg_cnt: libc::c_int) -> V
     msg_ids
 }
 
+fn convert_and_prune_chat_ids(chat_ids: *const u32, chat_cnt: libc::c_int) -> Vec<ChatId> {
+    let ids = unsafe { std::slice::from_raw_parts(chat_ids, chat_cnt as usize) };
+    ids.iter()
+        .map(|id| ChatId::new(*id))
+        .filter(|chat_id| !chat_id.is_special())
+        .collect()
+}
+
 // dc_provider_t
 
 pub type dc_provider_t = provider::Provider;
@@ -4716,6 +6670,33 @@ fn new(accounts: Accounts) -> Self {
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_accounts_purge_account(
+    accounts: *mut dc_accounts_t,
+    id: u32,
+    shred: libc::c_int,
+) -> libc::c_int {
+    if accounts.is_null() {
+        eprintln!("ignoring careless call to dc_accounts_purge_account()");
+        return 0;
+    }
+
+    let accounts = &mut *accounts;
+
+    block_on(async move {
+        let mut accounts = accounts.write().await;
+        match accounts.purge_account(id, shred != 0).await {
+            Ok(()) => 1,
+            Err(err) => {
+                accounts.emit_event(EventType::Error(format!(
+                    "Failed to purge account: {err:#}"
+                )));
+                0
+            }
+        }
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_accounts_migrate_account(
     accounts: *mut dc_accounts_t,
@@ -4814,6 +6795,73 @@ fn new(accounts: Accounts) -> Self {
     block_on(async move { accounts.write().await.maybe_network_lost().await });
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn dc_accounts_set_event_coalescing_interval(
+    accounts: *mut dc_accounts_t,
+    milliseconds: u32,
+) {
+    if accounts.is_null() {
+        eprintln!("ignoring careless call to dc_accounts_set_event_coalescing_interval()");
+        return;
+    }
+
+    let accounts = &*accounts;
+    block_on(async move {
+        accounts
+            .read()
+            .await
+            .set_event_coalescing_interval(Duration::from_millis(milliseconds.into()))
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_accounts_lock(accounts: *mut dc_accounts_t) -> libc::c_int {
+    if accounts.is_null() {
+        eprintln!("ignoring careless call to dc_accounts_lock()");
+        return 0;
+    }
+
+    let accounts = &*accounts;
+    block_on(async move {
+        let mut accounts = accounts.write().await;
+        match accounts.lock().await {
+            Ok(()) => 1,
+            Err(err) => {
+                accounts.emit_event(EventType::Error(format!(
+                    "Failed to lock accounts: {err:#}"
+                )));
+                0
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dc_accounts_unlock(
+    accounts: *mut dc_accounts_t,
+    passphrase: *const libc::c_char,
+) -> libc::c_int {
+    if accounts.is_null() {
+        eprintln!("ignoring careless call to dc_accounts_unlock()");
+        return 0;
+    }
+
+    let accounts = &*accounts;
+    let passphrase = to_string_lossy(passphrase);
+    block_on(async move {
+        let mut accounts = accounts.write().await;
+        match accounts.unlock(passphrase).await {
+            Ok(unlocked) => unlocked as libc::c_int,
+            Err(err) => {
+                accounts.emit_event(EventType::Error(format!(
+                    "Failed to unlock accounts: {err:#}"
+                )));
+                0
+            }
+        }
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn dc_accounts_get_event_emitter(
     accounts: *mut dc_accounts_t,
@@ -4856,11 +6904,19 @@ pub struct dc_jsonrpc_instance_t {
         let events = block_on(account_manager.read()).get_event_emitter();
         let cmd_api = deltachat_jsonrpc::api::CommandApi::from_arc(account_manager.inner.clone());
 
+        let chat_subscriptions = cmd_api.chat_subscriptions();
+
         let (request_handle, receiver) = RpcClient::new();
         let handle = RpcSession::new(request_handle.clone(), cmd_api);
 
         let event_thread = spawn(async move {
             while let Some(event) = events.recv().await {
+                if let Some(notification) = chat_subscriptions.chat_event_notification(&event).await
+                {
+                    request_handle
+                        .send_notification("chatEvent", Some(notification))
+                        .await?;
+                }
                 let event = event_to_json_rpc_notification(event);
                 request_handle
                     .send_notification("event", Some(event))
@@ -7,6 +7,7 @@
 use crate::message::MessageState;
 use crate::qr::Qr;
 use crate::summary::{Summary, SummaryPrefix};
+use crate::tools::TextDirection;
 
 /// An object containing a set of values.
 /// The meaning of the values is defined by the function returning the object.
@@ -59,6 +60,7 @@ pub fn get_text1(&self) -> Option<&str> {
                 Qr::ReviveVerifyContact { .. } => None,
                 Qr::ReviveVerifyGroup { grpname, .. } => Some(grpname),
                 Qr::Login { address, .. } => Some(address),
+                Qr::Profile { address, .. } => Some(address),
             },
             Self::Error(err) => Some(err),
         }
@@ -72,6 +74,14 @@ pub fn get_text2(&self) -> Option<Cow<str>> {
         }
     }
 
+    pub fn get_text2_direction(&self) -> TextDirection {
+        match self {
+            Self::Summary(summary) => summary.text_direction(),
+            Self::Qr(_) => TextDirection::Neutral,
+            Self::Error(_) => TextDirection::Neutral,
+        }
+    }
+
     pub fn get_text1_meaning(&self) -> Meaning {
         match self {
             Self::Summary(summary) => match &summary.prefix {
@@ -111,6 +121,7 @@ pub fn get_state(&self) -> LotState {
                 Qr::ReviveVerifyContact { .. } => LotState::QrReviveVerifyContact,
                 Qr::ReviveVerifyGroup { .. } => LotState::QrReviveVerifyGroup,
                 Qr::Login { .. } => LotState::QrLogin,
+                Qr::Profile { .. } => LotState::QrProfile,
             },
             Self::Error(_err) => LotState::QrError,
         }
@@ -136,6 +147,7 @@ pub fn get_id(&self) -> u32 {
                 Qr::ReviveVerifyContact { contact_id, .. } => contact_id.to_u32(),
                 Qr::ReviveVerifyGroup { .. } => Default::default(),
                 Qr::Login { .. } => Default::default(),
+                Qr::Profile { .. } => Default::default(),
             },
             Self::Error(_) => Default::default(),
         }
@@ -205,6 +217,9 @@ pub enum LotState {
     /// text1=email_address
     QrLogin = 520,
 
+    /// text1=email_address
+    QrProfile = 530,
+
     // Message States
     MsgInFresh = 10,
     MsgInNoticed = 13,
@@ -0,0 +1,40 @@
+use futures::StreamExt;
+use yerpc::{RpcClient, RpcSession};
+
+use super::CommandApi;
+
+/// Response to an empty batch as required by JSON-RPC 2.0.
+pub const INVALID_BATCH_RESPONSE: &str =
+    r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32600,"message":"Invalid Request"}}"#;
+
+/// Splits a JSON-RPC batch into its requests, returns `None` if the message is not a batch.
+pub fn parse_batch(message: &str) -> Option<Vec<String>> {
+    if !message.trim_start().starts_with('[') {
+        return None;
+    }
+    let requests: Vec<serde_json::Value> = serde_json::from_str(message).ok()?;
+    Some(requests.iter().map(|request| request.to_string()).collect())
+}
+
+/// Handles a request of a batch and returns its response, `None` for notifications.
+pub async fn handle_batch_request(state: CommandApi, request: &str) -> Option<serde_json::Value> {
+    let (client, mut out_receiver) = RpcClient::new();
+    let session = RpcSession::new(client, state);
+    session.handle_incoming(request).await;
+
+    // Notifications have no response, the channel is closed once the session is dropped.
+    drop(session);
+    let response = out_receiver.next().await?;
+    serde_json::to_value(&response).ok()
+}
+
+/// Returns the response to a batch from the responses to its requests,
+/// `None` if the batch only contained notifications.
+pub fn batch_response(responses: Vec<Option<serde_json::Value>>) -> Option<String> {
+    let responses: Vec<_> = responses.into_iter().flatten().collect();
+    if responses.is_empty() {
+        None
+    } else {
+        Some(serde_json::Value::Array(responses).to_string())
+    }
+}
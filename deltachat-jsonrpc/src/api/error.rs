@@ -0,0 +1,87 @@
+use deltachat::error_code::ErrorCode;
+use serde::Serialize;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error returned by the JSON-RPC methods.
+///
+/// It converts from any error like [`anyhow::Error`],
+/// but keeps the error until it is sent to the client,
+/// so that its [`ErrorCode`] can be added to the `data` of the error response:
+///
+/// ```json
+/// {"code":-1,"message":"Failed to load message 10 from the database: ...","data":{"code":"NotFound"}}
+/// ```
+#[derive(Debug)]
+pub struct Error(anyhow::Error);
+
+impl<E> From<E> for Error
+where
+    E: Into<anyhow::Error>,
+{
+    fn from(error: E) -> Self {
+        Self(error.into())
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl From<Error> for yerpc::Error {
+    fn from(Error(error): Error) -> Self {
+        let code = ErrorCode::of(&error).map(JsonrpcErrorCode::from);
+        let mut error = yerpc::Error::from(error);
+        error.data = code.map(|code| serde_json::json!({ "code": code }));
+        error
+    }
+}
+
+/// Stable code of an error response, sent in its `data`.
+#[derive(Serialize)]
+pub enum JsonrpcErrorCode {
+    /// The account is not configured or the configuration is invalid.
+    Configuration,
+
+    /// The server could not be reached, retrying later may help.
+    Network,
+
+    /// The server rejected the credentials.
+    Auth,
+
+    /// The requested chat, message, contact or account does not exist.
+    NotFound,
+
+    /// A size limit or quota is exceeded.
+    Quota,
+
+    /// Encryption, decryption or signing failed.
+    Crypto,
+}
+
+impl From<ErrorCode> for JsonrpcErrorCode {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::Configuration => Self::Configuration,
+            ErrorCode::Network => Self::Network,
+            ErrorCode::Auth => Self::Auth,
+            ErrorCode::NotFound => Self::NotFound,
+            ErrorCode::Quota => Self::Quota,
+            ErrorCode::Crypto => Self::Crypto,
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_error_data() {
+    let error = ErrorCode::NotFound.wrap(anyhow::anyhow!("Chat not found"));
+    let error = yerpc::Error::from(Error::from(error));
+    assert_eq!(error.message, "Chat not found");
+    assert_eq!(error.data, Some(serde_json::json!({"code": "NotFound"})));
+
+    let error = yerpc::Error::from(Error::from(anyhow::anyhow!("Something failed")));
+    assert_eq!(error.data, None);
+}
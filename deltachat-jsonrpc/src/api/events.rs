@@ -1,16 +1,118 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use deltachat::chat::ChatId;
 use deltachat::{Event, EventType};
 use serde::Serialize;
 use serde_json::{json, Value};
+use tokio::sync::Mutex;
 use typescript_type_def::TypeDef;
 
+use super::types::notification::JsonrpcNotificationIntent;
+
 pub fn event_to_json_rpc_notification(event: Event) -> Value {
     let id: JSONRPCEventType = event.typ.into();
     json!({
         "event": id,
         "contextId": event.id,
+        "seq": event.seq,
     })
 }
 
+/// Returns the parameters of a `log` notification if the event is a core log message,
+/// i.e. an `Info`, `Warning` or `Error` event.
+pub fn log_notification(event: &Event) -> Option<Value> {
+    let (level, msg) = match &event.typ {
+        EventType::Info(msg) => ("info", msg),
+        EventType::Warning(msg) => ("warning", msg),
+        EventType::Error(msg) => ("error", msg),
+        _ => return None,
+    };
+    Some(json!({
+        "level": level,
+        "accountId": event.id,
+        "message": msg,
+    }))
+}
+
+/// Chats subscribed with `subscribe_chat()`.
+#[derive(Clone, Debug, Default)]
+pub struct ChatSubscriptions(Arc<Mutex<BTreeSet<(u32, ChatId)>>>);
+
+impl ChatSubscriptions {
+    pub(crate) async fn subscribe(&self, account_id: u32, chat_id: ChatId) {
+        self.0.lock().await.insert((account_id, chat_id));
+    }
+
+    pub(crate) async fn unsubscribe(&self, account_id: u32, chat_id: ChatId) {
+        self.0.lock().await.remove(&(account_id, chat_id));
+    }
+
+    /// Returns a `chatEvent` notification if the event concerns a subscribed chat.
+    pub async fn chat_event_notification(&self, event: &Event) -> Option<Value> {
+        let chat_id = event_chat_id(&event.typ)?;
+        if !self.0.lock().await.contains(&(event.id, chat_id)) {
+            return None;
+        }
+        let typ: JSONRPCEventType = event.typ.clone().into();
+        Some(json!({
+            "event": typ,
+            "contextId": event.id,
+            "seq": event.seq,
+            "chatId": chat_id.to_u32(),
+        }))
+    }
+}
+
+/// Accounts and event kinds set with `set_event_filter()`.
+///
+/// `None` lets the events of all accounts or of all kinds pass.
+#[derive(Clone, Debug, Default)]
+pub struct EventFilter(Arc<Mutex<(Option<BTreeSet<u32>>, Option<BTreeSet<String>>)>>);
+
+impl EventFilter {
+    pub(crate) async fn set(
+        &self,
+        account_ids: Option<BTreeSet<u32>>,
+        event_kinds: Option<BTreeSet<String>>,
+    ) {
+        *self.0.lock().await = (account_ids, event_kinds);
+    }
+
+    /// Returns true if the event should be sent as `event` notification.
+    ///
+    /// Check this before converting the event to avoid serializing filtered events.
+    pub async fn matches(&self, event: &Event) -> bool {
+        let filter = self.0.lock().await;
+        let (account_ids, event_kinds) = &*filter;
+        account_ids
+            .as_ref()
+            .map_or(true, |account_ids| account_ids.contains(&event.id))
+            && event_kinds
+                .as_ref()
+                .map_or(true, |event_kinds| event_kinds.contains(event.typ.as_ref()))
+    }
+}
+
+/// Returns the chat affected by an event, if the event is about a single chat.
+fn event_chat_id(typ: &EventType) -> Option<ChatId> {
+    let chat_id = match typ {
+        EventType::MsgsChanged { chat_id, .. }
+        | EventType::ReactionsChanged { chat_id, .. }
+        | EventType::IncomingMsg { chat_id, .. }
+        | EventType::MsgsNoticed(chat_id)
+        | EventType::MsgDelivered { chat_id, .. }
+        | EventType::MsgFailed { chat_id, .. }
+        | EventType::MsgRead { chat_id, .. }
+        | EventType::MsgsDeleted { chat_id, .. }
+        | EventType::ChatModified(chat_id)
+        | EventType::ChatEphemeralTimerModified { chat_id, .. }
+        | EventType::EphemeralTimerNotHonored { chat_id, .. } => *chat_id,
+        _ => return None,
+    };
+    Some(chat_id).filter(|chat_id| !chat_id.is_unset())
+}
+
 #[derive(Serialize, TypeDef)]
 #[serde(tag = "type", rename = "Event")]
 pub enum JSONRPCEventType {
@@ -121,6 +223,8 @@ pub enum JSONRPCEventType {
     IncomingMsg {
         chat_id: u32,
         msg_id: u32,
+        /// How the user should be notified about the message.
+        intent: JsonrpcNotificationIntent,
     },
 
     /// Downloading a bunch of messages just finished. This is an experimental
@@ -140,6 +244,13 @@ pub enum JSONRPCEventType {
         chat_id: u32,
     },
 
+    /// Notifications were suppressed because of quiet hours.
+    /// The suppressed messages can be retrieved with `get_suppressed_notifications()`.
+    #[serde(rename_all = "camelCase")]
+    NotificationsSuppressed {
+        count: usize,
+    },
+
     /// A single message is sent successfully. State changed from  DC_STATE_OUT_PENDING to
     /// DC_STATE_OUT_DELIVERED, see `Message.state`.
     #[serde(rename_all = "camelCase")]
@@ -164,6 +275,15 @@ pub enum JSONRPCEventType {
         msg_id: u32,
     },
 
+    /// Messages of a chat were deleted, e.g. by the user or because their ephemeral timer expired.
+    /// Messages deleted at once are reported by a single event per chat.
+    /// `MsgsChanged` is emitted as well.
+    #[serde(rename_all = "camelCase")]
+    MsgsDeleted {
+        chat_id: u32,
+        msg_ids: Vec<u32>,
+    },
+
     /// Chat changed.  The name or the image of a chat group was changed or members were added or removed.
     /// Or the verify state of a chat has changed.
     /// See setChatName(), setChatProfileImage(), addContactToChat()
@@ -183,6 +303,14 @@ pub enum JSONRPCEventType {
         timer: u32,
     },
 
+    /// A received message quotes an ephemeral message after the timer of the message expired,
+    /// so the sender's device probably does not delete ephemeral messages.
+    #[serde(rename_all = "camelCase")]
+    EphemeralTimerNotHonored {
+        chat_id: u32,
+        msg_id: u32,
+    },
+
     /// Contact(s) created, renamed, blocked or deleted.
     ///
     /// @param data1 (int) If set, this is the contact_id of an added contact that should be selected.
@@ -233,6 +361,17 @@ pub enum JSONRPCEventType {
         path: String,
     },
 
+    /// Progress of a peer-to-peer file transfer.
+    /// Sent by the sender for each download of a file sent with `sendFileP2p()`
+    /// and by the recipient while `downloadFileP2p()` is running.
+    ///
+    /// @param progress 0=error, 1-999=progress in permille, 1000=success and done
+    #[serde(rename_all = "camelCase")]
+    P2pFileProgress {
+        msg_id: u32,
+        progress: usize,
+    },
+
     /// Progress information of a secure-join handshake from the view of the inviter
     /// (Alice, the person who shows the QR code).
     ///
@@ -265,6 +404,22 @@ pub enum JSONRPCEventType {
         progress: usize,
     },
 
+    /// A verified contact forwarded an invite to a protected group,
+    /// it can be accepted with acceptGroupInvite().
+    #[serde(rename_all = "camelCase")]
+    SecurejoinInviteReceived {
+        contact_id: u32,
+        invite_id: u32,
+    },
+
+    /// The inviter of a group with join gate sent a challenge,
+    /// get it with getJoinChallenge() and answer it with answerJoinChallenge().
+    #[serde(rename_all = "camelCase")]
+    SecurejoinChallengeReceived {
+        contact_id: u32,
+        challenge_id: u32,
+    },
+
     /// The connectivity to the server changed.
     /// This means that you should refresh the connectivity view
     /// and possibly the connectivtiy HTML; see getConnectivity() and
@@ -284,6 +439,61 @@ pub enum JSONRPCEventType {
     WebxdcInstanceDeleted {
         msg_id: u32,
     },
+
+    /// A webxdc instance wants attention, another member sent an info message.
+    /// UIs may badge the app icon with `unreadInfoCnt`, reset by markWebxdcInfosSeen().
+    #[serde(rename_all = "camelCase")]
+    WebxdcAttention {
+        msg_id: u32,
+        unread_info_cnt: u32,
+    },
+
+    /// The accounts were locked with lockAccounts(), UIs should show their lock screen.
+    AccountsLocked,
+
+    /// All locked accounts were unlocked with unlockAccounts().
+    AccountsUnlocked,
+
+    /// A call was offered with `sendCallOffer()`.
+    /// The UI should ring until the call is answered or `CallMissed` is received.
+    ///
+    /// @param msg_id ID of the offer message identifying the call.
+    #[serde(rename_all = "camelCase")]
+    IncomingCallOffer {
+        msg_id: u32,
+        payload: String,
+    },
+
+    /// An offered call was answered with `sendCallAnswer()`.
+    #[serde(rename_all = "camelCase")]
+    IncomingCallAnswer {
+        msg_id: u32,
+        payload: String,
+    },
+
+    /// The peer of a call sent an ICE candidate with `sendCallIce()`.
+    #[serde(rename_all = "camelCase")]
+    IncomingCallIce {
+        msg_id: u32,
+        payload: String,
+    },
+
+    /// An offered call was not answered in time.
+    #[serde(rename_all = "camelCase")]
+    CallMissed {
+        msg_id: u32,
+    },
+
+    /// The device clock differs from the server time by more than 5 minutes.
+    /// A warning is also added to the device chat.
+    #[serde(rename_all = "camelCase")]
+    ClockSkew {
+        /// Server time minus device time in seconds, positive if the device clock is behind.
+        skew: i64,
+    },
+
+    /// Canned responses were added, changed or deleted, also on another device.
+    CannedResponsesChanged,
 }
 
 impl From<EventType> for JSONRPCEventType {
@@ -315,9 +525,14 @@ fn from(event: EventType) -> Self {
                 msg_id: msg_id.to_u32(),
                 contact_id: contact_id.to_u32(),
             },
-            EventType::IncomingMsg { chat_id, msg_id } => IncomingMsg {
+            EventType::IncomingMsg {
+                chat_id,
+                msg_id,
+                intent,
+            } => IncomingMsg {
                 chat_id: chat_id.to_u32(),
                 msg_id: msg_id.to_u32(),
+                intent: intent.into(),
             },
             EventType::IncomingMsgBunch { msg_ids } => IncomingMsgBunch {
                 msg_ids: msg_ids.into_iter().map(|id| id.to_u32()).collect(),
@@ -325,6 +540,7 @@ fn from(event: EventType) -> Self {
             EventType::MsgsNoticed(chat_id) => MsgsNoticed {
                 chat_id: chat_id.to_u32(),
             },
+            EventType::NotificationsSuppressed { count } => NotificationsSuppressed { count },
             EventType::MsgDelivered { chat_id, msg_id } => MsgDelivered {
                 chat_id: chat_id.to_u32(),
                 msg_id: msg_id.to_u32(),
@@ -337,6 +553,10 @@ fn from(event: EventType) -> Self {
                 chat_id: chat_id.to_u32(),
                 msg_id: msg_id.to_u32(),
             },
+            EventType::MsgsDeleted { chat_id, msg_ids } => MsgsDeleted {
+                chat_id: chat_id.to_u32(),
+                msg_ids: msg_ids.into_iter().map(|id| id.to_u32()).collect(),
+            },
             EventType::ChatModified(chat_id) => ChatModified {
                 chat_id: chat_id.to_u32(),
             },
@@ -346,6 +566,10 @@ fn from(event: EventType) -> Self {
                     timer: timer.to_u32(),
                 }
             }
+            EventType::EphemeralTimerNotHonored { chat_id, msg_id } => EphemeralTimerNotHonored {
+                chat_id: chat_id.to_u32(),
+                msg_id: msg_id.to_u32(),
+            },
             EventType::ContactsChanged(contact) => ContactsChanged {
                 contact_id: contact.map(|c| c.to_u32()),
             },
@@ -359,6 +583,10 @@ fn from(event: EventType) -> Self {
             EventType::ImexFileWritten(path) => ImexFileWritten {
                 path: path.to_str().unwrap_or_default().to_owned(),
             },
+            EventType::P2pFileProgress { msg_id, progress } => P2pFileProgress {
+                msg_id: msg_id.to_u32(),
+                progress,
+            },
             EventType::SecurejoinInviterProgress {
                 contact_id,
                 progress,
@@ -373,6 +601,20 @@ fn from(event: EventType) -> Self {
                 contact_id: contact_id.to_u32(),
                 progress,
             },
+            EventType::SecurejoinInviteReceived {
+                contact_id,
+                invite_id,
+            } => SecurejoinInviteReceived {
+                contact_id: contact_id.to_u32(),
+                invite_id,
+            },
+            EventType::SecurejoinChallengeReceived {
+                contact_id,
+                challenge_id,
+            } => SecurejoinChallengeReceived {
+                contact_id: contact_id.to_u32(),
+                challenge_id,
+            },
             EventType::ConnectivityChanged => ConnectivityChanged,
             EventType::SelfavatarChanged => SelfavatarChanged,
             EventType::WebxdcStatusUpdate {
@@ -385,6 +627,32 @@ fn from(event: EventType) -> Self {
             EventType::WebxdcInstanceDeleted { msg_id } => WebxdcInstanceDeleted {
                 msg_id: msg_id.to_u32(),
             },
+            EventType::WebxdcAttention {
+                msg_id,
+                unread_info_cnt,
+            } => WebxdcAttention {
+                msg_id: msg_id.to_u32(),
+                unread_info_cnt,
+            },
+            EventType::AccountsLocked => AccountsLocked,
+            EventType::AccountsUnlocked => AccountsUnlocked,
+            EventType::IncomingCallOffer { msg_id, payload } => IncomingCallOffer {
+                msg_id: msg_id.to_u32(),
+                payload,
+            },
+            EventType::IncomingCallAnswer { msg_id, payload } => IncomingCallAnswer {
+                msg_id: msg_id.to_u32(),
+                payload,
+            },
+            EventType::IncomingCallIce { msg_id, payload } => IncomingCallIce {
+                msg_id: msg_id.to_u32(),
+                payload,
+            },
+            EventType::CallMissed { msg_id } => CallMissed {
+                msg_id: msg_id.to_u32(),
+            },
+            EventType::ClockSkew { skew } => ClockSkew { skew },
+            EventType::CannedResponsesChanged => CannedResponsesChanged,
         }
     }
 }
@@ -404,3 +672,68 @@ fn generate_events_ts_types_definition() {
     };
     std::fs::write("typescript/generated/events.ts", events).unwrap();
 }
+
+#[cfg(test)]
+#[test]
+fn test_log_notification() {
+    let event = Event {
+        id: 3,
+        seq: 0,
+        typ: EventType::Warning("IMAP connection lost".to_string()),
+    };
+    assert_eq!(
+        log_notification(&event),
+        Some(json!({
+            "level": "warning",
+            "accountId": 3,
+            "message": "IMAP connection lost",
+        }))
+    );
+    let event = Event {
+        id: 3,
+        seq: 0,
+        typ: EventType::ConnectivityChanged,
+    };
+    assert_eq!(log_notification(&event), None);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_event_filter() {
+    let filter = EventFilter::default();
+    let event = |id, typ| Event { id, seq: 0, typ };
+    assert!(
+        filter
+            .matches(&event(1, EventType::ConnectivityChanged))
+            .await
+    );
+
+    filter
+        .set(
+            Some([2].into_iter().collect()),
+            Some(["IncomingMsg".to_string(), "ConnectivityChanged".to_string()].into()),
+        )
+        .await;
+    assert!(
+        !filter
+            .matches(&event(1, EventType::ConnectivityChanged))
+            .await
+    );
+    assert!(
+        filter
+            .matches(&event(2, EventType::ConnectivityChanged))
+            .await
+    );
+    assert!(
+        !filter
+            .matches(&event(2, EventType::SelfavatarChanged))
+            .await
+    );
+
+    filter.set(None, None).await;
+    assert!(
+        filter
+            .matches(&event(1, EventType::SelfavatarChanged))
+            .await
+    );
+}
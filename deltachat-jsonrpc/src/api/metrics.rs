@@ -0,0 +1,287 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use deltachat::{Event, EventType};
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+/// Maximum number of methods tracked separately,
+/// further method names sent by clients are counted as `other`.
+const MAX_METHODS: usize = 500;
+
+/// Server-wide counters returned by `get_metrics()`.
+///
+/// Unlike [`RpcStats`](super::rpc_stats::RpcStats), metrics are shared by all connections
+/// of a server, see [`CommandApi::with_metrics`](super::CommandApi::with_metrics).
+#[derive(Clone, Debug, Default)]
+pub struct Metrics(Arc<MetricsInner>);
+
+#[derive(Debug, Default)]
+struct MetricsInner {
+    received_messages: AtomicU64,
+    sent_messages: AtomicU64,
+    event_queue_depth: AtomicUsize,
+    methods: Mutex<BTreeMap<String, MethodMetrics>>,
+    accounts: Mutex<BTreeMap<u32, AccountMetrics>>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct MethodMetrics {
+    count: u64,
+    duration: Duration,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct AccountMetrics {
+    imap_connections: u64,
+    smtp_send_failures: u64,
+}
+
+impl Metrics {
+    /// Counts a JSON-RPC message received from a client.
+    pub fn message_received(&self) {
+        self.0.received_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a JSON-RPC message sent to a client, including notifications.
+    pub fn message_sent(&self) {
+        self.0.sent_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the time it took to handle a request.
+    pub fn request_handled(&self, method: &str, duration: Duration) {
+        let mut methods = self.0.methods.lock().unwrap();
+        let method = if methods.contains_key(method) || methods.len() < MAX_METHODS {
+            method
+        } else {
+            "other"
+        };
+        let metrics = methods.entry(method.to_string()).or_default();
+        metrics.count += 1;
+        metrics.duration += duration;
+    }
+
+    /// Sets the number of events waiting to be sent to a client.
+    pub fn set_event_queue_depth(&self, depth: usize) {
+        self.0.event_queue_depth.store(depth, Ordering::Relaxed);
+    }
+
+    /// Counts IMAP connections and SMTP send failures.
+    ///
+    /// Servers should call this once for every event emitted by the accounts.
+    pub fn observe_event(&self, event: &Event) {
+        let mut accounts = self.0.accounts.lock().unwrap();
+        match event.typ {
+            EventType::ImapConnected(_) => {
+                accounts.entry(event.id).or_default().imap_connections += 1;
+            }
+            EventType::MsgFailed { .. } => {
+                accounts.entry(event.id).or_default().smtp_send_failures += 1;
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> JsonrpcMetrics {
+        let methods = self
+            .0
+            .methods
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(method, metrics)| JsonrpcMethodMetrics {
+                method: method.clone(),
+                count: metrics.count,
+                total_seconds: metrics.duration.as_secs_f64(),
+            })
+            .collect();
+        let accounts = self
+            .0
+            .accounts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(account_id, metrics)| JsonrpcAccountMetrics {
+                account_id: *account_id,
+                imap_connections: metrics.imap_connections,
+                smtp_send_failures: metrics.smtp_send_failures,
+            })
+            .collect();
+        JsonrpcMetrics {
+            received_messages: self.0.received_messages.load(Ordering::Relaxed),
+            sent_messages: self.0.sent_messages.load(Ordering::Relaxed),
+            event_queue_depth: self.0.event_queue_depth.load(Ordering::Relaxed),
+            methods,
+            accounts,
+        }
+    }
+
+    /// Returns the metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let metrics = self.snapshot();
+        let mut out = String::new();
+        let mut metric = |name: &str, typ: &str, help: &str, values: Vec<(String, String)>| {
+            writeln!(out, "# HELP {name} {help}").ok();
+            writeln!(out, "# TYPE {name} {typ}").ok();
+            for (labels, value) in values {
+                writeln!(out, "{name}{labels} {value}").ok();
+            }
+        };
+        metric(
+            "deltachat_rpc_received_messages_total",
+            "counter",
+            "JSON-RPC messages received from clients.",
+            vec![(String::new(), metrics.received_messages.to_string())],
+        );
+        metric(
+            "deltachat_rpc_sent_messages_total",
+            "counter",
+            "JSON-RPC messages sent to clients, including notifications.",
+            vec![(String::new(), metrics.sent_messages.to_string())],
+        );
+        metric(
+            "deltachat_rpc_event_queue_depth",
+            "gauge",
+            "Events waiting to be sent to a client.",
+            vec![(String::new(), metrics.event_queue_depth.to_string())],
+        );
+        metric(
+            "deltachat_rpc_request_duration_seconds",
+            "summary",
+            "Time spent handling requests by method.",
+            metrics
+                .methods
+                .iter()
+                .flat_map(|m| {
+                    let labels = format!("{{method=\"{}\"}}", escape_label(&m.method));
+                    [
+                        (format!("_sum{labels}"), m.total_seconds.to_string()),
+                        (format!("_count{labels}"), m.count.to_string()),
+                    ]
+                })
+                .collect(),
+        );
+        metric(
+            "deltachat_imap_connections_total",
+            "counter",
+            "IMAP connections established, including reconnects.",
+            metrics
+                .accounts
+                .iter()
+                .map(|a| {
+                    (
+                        format!("{{account=\"{}\"}}", a.account_id),
+                        a.imap_connections.to_string(),
+                    )
+                })
+                .collect(),
+        );
+        metric(
+            "deltachat_smtp_send_failures_total",
+            "counter",
+            "Messages which failed to be sent over SMTP.",
+            metrics
+                .accounts
+                .iter()
+                .map(|a| {
+                    (
+                        format!("{{account=\"{}\"}}", a.account_id),
+                        a.smtp_send_failures.to_string(),
+                    )
+                })
+                .collect(),
+        );
+        out
+    }
+}
+
+/// Escapes a label value, method names are chosen by clients.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "Metrics", rename_all = "camelCase")]
+pub struct JsonrpcMetrics {
+    /// JSON-RPC messages received from clients.
+    pub received_messages: u64,
+
+    /// JSON-RPC messages sent to clients, including notifications.
+    pub sent_messages: u64,
+
+    /// Events waiting to be sent to a client.
+    pub event_queue_depth: usize,
+
+    /// Request durations by method.
+    pub methods: Vec<JsonrpcMethodMetrics>,
+
+    /// Counters by account.
+    pub accounts: Vec<JsonrpcAccountMetrics>,
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "MethodMetrics", rename_all = "camelCase")]
+pub struct JsonrpcMethodMetrics {
+    pub method: String,
+
+    /// Number of handled requests.
+    pub count: u64,
+
+    /// Total time spent handling the requests in seconds.
+    pub total_seconds: f64,
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "AccountMetrics", rename_all = "camelCase")]
+pub struct JsonrpcAccountMetrics {
+    pub account_id: u32,
+
+    /// IMAP connections established, including reconnects.
+    pub imap_connections: u64,
+
+    /// Messages which failed to be sent over SMTP.
+    pub smtp_send_failures: u64,
+}
+
+#[cfg(test)]
+#[test]
+fn test_metrics() {
+    let metrics = Metrics::default();
+    metrics.message_received();
+    metrics.message_sent();
+    metrics.message_sent();
+    metrics.request_handled("get_info", Duration::from_millis(500));
+    metrics.request_handled("get_info", Duration::from_millis(250));
+    metrics.observe_event(&Event {
+        id: 1,
+        seq: 0,
+        typ: EventType::ImapConnected("connected".to_string()),
+    });
+    metrics.observe_event(&Event {
+        id: 1,
+        seq: 0,
+        typ: EventType::ConnectivityChanged,
+    });
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.received_messages, 1);
+    assert_eq!(snapshot.sent_messages, 2);
+    assert_eq!(snapshot.methods.len(), 1);
+    assert_eq!(snapshot.methods[0].count, 2);
+    assert_eq!(snapshot.methods[0].total_seconds, 0.75);
+    assert_eq!(snapshot.accounts.len(), 1);
+    assert_eq!(snapshot.accounts[0].imap_connections, 1);
+    assert_eq!(snapshot.accounts[0].smtp_send_failures, 0);
+
+    let text = metrics.to_prometheus();
+    assert!(text.contains("deltachat_rpc_sent_messages_total 2\n"));
+    assert!(text.contains("deltachat_rpc_request_duration_seconds_count{method=\"get_info\"} 2\n"));
+    assert!(text.contains("deltachat_imap_connections_total{account=\"1\"} 1\n"));
+    assert!(text.contains("# TYPE deltachat_smtp_send_failures_total counter\n"));
+}
@@ -1,59 +1,90 @@
 use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, str::FromStr};
 
-use anyhow::{anyhow, bail, ensure, Context, Result};
+use anyhow::{anyhow, bail, ensure, Context};
+use constant_time_eq::constant_time_eq;
 pub use deltachat::accounts::Accounts;
 use deltachat::qr::Qr;
 use deltachat::{
+    account_stats, aeap, blocklist, calls, canned_response,
     chat::{
         self, add_contact_to_chat, forward_msgs, get_chat_media, get_chat_msgs, get_chat_msgs_ex,
         marknoticed_chat, remove_contact_from_chat, Chat, ChatId, ChatItem, MessageListOptions,
-        ProtectionStatus,
+        ProtectionStatus, Retention,
     },
+    chat_export,
     chatlist::Chatlist,
     config::Config,
     constants::DC_MSG_ID_DAYMARKER,
     contact::{may_be_valid_addr, Contact, ContactId, Origin},
     context::get_info,
-    ephemeral::Timer,
-    imex, location,
+    ephemeral::{self, Timer},
+    error_code::ErrorCode,
+    gif_search, imap_jobs, imex, location,
     message::{
         self, delete_msgs, get_msg_info, markseen_msgs, Message, MessageState, MsgId, Viewtype,
     },
+    notification, peer_transfer,
     provider::get_provider_info,
     qr,
     qr_code_generator::{generate_backup_qr, get_securejoin_qr_svg},
     reaction::send_reaction,
-    securejoin,
+    report::{self, ReportCategory},
+    reputation, securejoin,
     stock_str::StockMessage,
     webxdc::StatusUpdateSerial,
 };
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
 use sanitize_filename::is_sanitized;
+use tempfile::NamedTempFile;
 use tokio::fs;
 use tokio::sync::{watch, Mutex, RwLock};
 use walkdir::WalkDir;
 use yerpc::rpc;
 
+pub mod batch;
+pub mod error;
 pub mod events;
+pub mod metrics;
+pub mod rpc_stats;
 pub mod types;
 
 use num_traits::FromPrimitive;
 use types::account::Account;
+use types::account_stats::JsonrpcAccountStats;
+use types::aeap::JsonrpcAeapMigration;
+use types::blocklist::JsonrpcBlocklistFeed;
+use types::canned_response::JsonrpcCannedResponse;
 use types::chat::FullChat;
 use types::chat_list::ChatListEntry;
-use types::contact::ContactObject;
+use types::contact::{ContactObject, KeyHistoryEntryObject};
+use types::device::JsonrpcDevice;
+use types::gif_search::JsonrpcGifSearchResult;
+use types::imap_job::JsonrpcImapJob;
+use types::invite_link::{JsonrpcInviteLink, JsonrpcInviteLinkJoin};
+use types::join_challenge::JsonrpcJoinChallenge;
 use types::message::MessageData;
 use types::message::MessageObject;
 use types::provider_info::ProviderInfo;
+use types::server_deletion::JsonrpcServerDeletion;
 use types::webxdc::WebxdcMessageInfo;
 
+use self::error::Result;
+use self::events::{ChatSubscriptions, EventFilter};
+use self::metrics::{JsonrpcMetrics, Metrics};
+use self::rpc_stats::{JsonrpcRpcStats, RpcStats};
 use self::types::message::MessageLoadResult;
 use self::types::{
-    chat::{BasicChat, JSONRPCChatVisibility, MuteDuration},
+    chat::{BasicChat, JSONRPCArchiveMode, JSONRPCChatVisibility, MediaMonth, MuteDuration},
     location::JsonrpcLocation,
     message::{
-        JSONRPCMessageListItem, MessageNotificationInfo, MessageSearchResult, MessageViewtype,
+        message_list_page, CallState, JSONRPCMessageListItem, JsonrpcMessageListPage,
+        MessageNotificationInfo, MessageSearchResult, MessageViewtype,
     },
 };
 use crate::api::types::chat_list::{get_chat_list_item_by_id, ChatListItemFetchResult};
@@ -66,6 +97,20 @@ struct AccountState {
     /// If there currently is a call to [`CommandApi::provide_backup`] this will be
     /// `Pending` or `Ready`, otherwise `NoProvider`.
     backup_provider_qr: watch::Sender<ProviderQr>,
+
+    /// Unfinished uploads started with [`CommandApi::blob_upload_start`].
+    ///
+    /// Files of uploads which are never finished are removed
+    /// when the upload expires or the state is dropped.
+    blob_uploads: HashMap<u32, BlobUpload>,
+
+    /// ID of the next upload.
+    next_blob_upload_id: u32,
+
+    /// Token authorizing access to the blobs of the account via blob URLs.
+    ///
+    /// The token is not persisted, so blob URLs become invalid when the server is restarted.
+    blob_token: String,
 }
 
 impl Default for AccountState {
@@ -73,15 +118,72 @@ fn default() -> Self {
         let (tx, _rx) = watch::channel(ProviderQr::NoProvider);
         Self {
             backup_provider_qr: tx,
+            blob_uploads: HashMap::new(),
+            next_blob_upload_id: 1,
+            blob_token: (0..32)
+                .map(|_| char::from(rand::thread_rng().sample(Alphanumeric)))
+                .collect(),
         }
     }
 }
 
+impl AccountState {
+    /// Removes uploads which were not appended to for [`BLOB_UPLOAD_TIMEOUT`]
+    /// and deletes their files.
+    fn expire_blob_uploads(&mut self, now: Instant) {
+        self.blob_uploads.retain(|_, upload| {
+            now.saturating_duration_since(upload.last_used) < BLOB_UPLOAD_TIMEOUT
+        });
+    }
+}
+
+/// Unfinished upload started with [`CommandApi::blob_upload_start`].
+#[derive(Debug)]
+struct BlobUpload {
+    /// Temporary file in the blob directory, deleted when dropped.
+    file: NamedTempFile,
+
+    /// Time of the last start or append.
+    last_used: Instant,
+}
+
+/// Maximum number of unfinished uploads per account.
+const MAX_BLOB_UPLOADS: usize = 16;
+
+/// Time after which an upload that is not appended to is removed.
+const BLOB_UPLOAD_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum number of bytes returned by [`CommandApi::blob_read_range`] at once.
+const MAX_BLOB_READ_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Default maximum size of a file uploaded with [`CommandApi::blob_upload_append`].
+const DEFAULT_MAX_BLOB_UPLOAD_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Prefix of blob URLs if the HTTP endpoint is not enabled.
+const BLOB_URL_SCHEME: &str = "dc-blob://";
+
 #[derive(Clone, Debug)]
 pub struct CommandApi {
     pub(crate) accounts: Arc<RwLock<Accounts>>,
 
     states: Arc<Mutex<BTreeMap<u32, AccountState>>>,
+
+    /// Base URL of the HTTP endpoint serving blobs, if enabled.
+    blob_http_base: Option<String>,
+
+    /// Maximum size of an uploaded file, see [`CommandApi::with_max_blob_upload_size`].
+    max_blob_upload_size: u64,
+
+    chat_subscriptions: ChatSubscriptions,
+
+    event_filter: EventFilter,
+
+    rpc_stats: RpcStats,
+
+    metrics: Metrics,
+
+    /// Account the API is restricted to, see [`CommandApi::with_single_account`].
+    single_account: Option<u32>,
 }
 
 impl CommandApi {
@@ -89,6 +191,13 @@ pub fn new(accounts: Accounts) -> Self {
         CommandApi {
             accounts: Arc::new(RwLock::new(accounts)),
             states: Arc::new(Mutex::new(BTreeMap::new())),
+            blob_http_base: None,
+            max_blob_upload_size: DEFAULT_MAX_BLOB_UPLOAD_SIZE,
+            chat_subscriptions: Default::default(),
+            event_filter: Default::default(),
+            rpc_stats: Default::default(),
+            metrics: Default::default(),
+            single_account: None,
         }
     }
 
@@ -97,29 +206,232 @@ pub fn from_arc(accounts: Arc<RwLock<Accounts>>) -> Self {
         CommandApi {
             accounts,
             states: Arc::new(Mutex::new(BTreeMap::new())),
+            blob_http_base: None,
+            max_blob_upload_size: DEFAULT_MAX_BLOB_UPLOAD_SIZE,
+            chat_subscriptions: Default::default(),
+            event_filter: Default::default(),
+            rpc_stats: Default::default(),
+            metrics: Default::default(),
+            single_account: None,
+        }
+    }
+
+    /// Makes `get_blob_url()` return URLs of the HTTP endpoint at `base`
+    /// instead of `dc-blob://` URLs.
+    ///
+    /// The endpoint has to serve `<base>/blobs/<account_id>/<token>/<name>`
+    /// using [`CommandApi::get_blob_path`].
+    pub fn with_blob_http_base(mut self, base: String) -> Self {
+        self.blob_http_base = Some(base);
+        self
+    }
+
+    /// Returns an API for a new client connection.
+    ///
+    /// The accounts, their state and the metrics are shared with `self`,
+    /// but the chats subscribed with [`CommandApi::subscribe_chat`],
+    /// the filter set with [`CommandApi::set_event_filter`]
+    /// and the request counters returned by [`CommandApi::get_rpc_stats`] are not,
+    /// so servers with several connections should call this for each of them.
+    pub fn for_connection(&self) -> Self {
+        Self {
+            chat_subscriptions: Default::default(),
+            event_filter: Default::default(),
+            rpc_stats: Default::default(),
+            ..self.clone()
         }
     }
 
-    async fn get_context(&self, id: u32) -> Result<deltachat::context::Context> {
+    /// Limits the size of files uploaded with `blob_upload_append()` to `max_size` bytes,
+    /// so clients cannot fill up the disk. Defaults to 512 MiB.
+    pub fn with_max_blob_upload_size(mut self, max_size: u64) -> Self {
+        self.max_blob_upload_size = max_size;
+        self
+    }
+
+    /// Makes `get_metrics()` return `metrics`,
+    /// so servers can share the metrics between all connections.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Restricts the API to the account `account_id`,
+    /// e.g. to let untrusted bots share an accounts directory.
+    ///
+    /// Other accounts are treated as if they did not exist
+    /// and methods adding, removing or affecting all accounts fail.
+    /// Files can only be read from and written to the directory of the account,
+    /// `get_metrics()` only returns the counters of the account.
+    /// Servers should only send events of the account to the client.
+    pub fn with_single_account(mut self, account_id: u32) -> Self {
+        self.single_account = Some(account_id);
+        self
+    }
+
+    /// Returns the chats subscribed with [`CommandApi::subscribe_chat`].
+    ///
+    /// Event loops should send [`ChatSubscriptions::chat_event_notification`]
+    /// in addition to the `event` notification.
+    /// Unlike the API, the subscriptions do not keep the accounts alive.
+    pub fn chat_subscriptions(&self) -> ChatSubscriptions {
+        self.chat_subscriptions.clone()
+    }
+
+    /// Returns the filter set with [`CommandApi::set_event_filter`].
+    ///
+    /// Event loops should only send `event` notifications for events
+    /// passing [`EventFilter::matches`].
+    pub fn event_filter(&self) -> EventFilter {
+        self.event_filter.clone()
+    }
+
+    /// Returns the request counters returned by [`CommandApi::get_rpc_stats`],
+    /// to be updated by servers limiting concurrent requests.
+    pub fn rpc_stats(&self) -> RpcStats {
+        self.rpc_stats.clone()
+    }
+
+    /// Returns the metrics returned by [`CommandApi::get_metrics`], to be updated by servers.
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.clone()
+    }
+
+    /// Returns the path of a blob if `token` is the blob token of the account.
+    pub async fn get_blob_path(
+        &self,
+        account_id: u32,
+        token: &str,
+        name: &str,
+    ) -> anyhow::Result<PathBuf> {
+        let ctx = self.get_context(account_id).await?;
+        let valid = self
+            .with_state(account_id, |state| {
+                constant_time_eq(state.blob_token.as_bytes(), token.as_bytes())
+            })
+            .await;
+        ensure!(valid, "invalid blob token");
+        ensure!(is_blob_name(Path::new(name)), "invalid blob name {name:?}");
+        Ok(ctx.get_blobdir().join(name))
+    }
+
+    async fn get_context(&self, id: u32) -> anyhow::Result<deltachat::context::Context> {
         let sc = self
-            .accounts
-            .read()
-            .await
             .get_account(id)
-            .ok_or_else(|| anyhow!("account with id {} not found", id))?;
+            .await
+            .ok_or_else(|| ErrorCode::NotFound.wrap(anyhow!("account with id {} not found", id)))?;
         Ok(sc)
     }
 
+    /// Returns the account if it exists and the API is not restricted to another account.
+    async fn get_account(&self, id: u32) -> Option<deltachat::context::Context> {
+        if self.is_account_allowed(id) {
+            self.accounts.read().await.get_account(id)
+        } else {
+            None
+        }
+    }
+
+    /// Returns false if the API is restricted to another account,
+    /// see [`CommandApi::with_single_account`].
+    fn is_account_allowed(&self, id: u32) -> bool {
+        self.single_account
+            .map_or(true, |single_account| single_account == id)
+    }
+
+    /// Returns an error if the API is restricted to a single account.
+    fn ensure_all_accounts_allowed(&self) -> anyhow::Result<()> {
+        if let Some(id) = self.single_account {
+            bail!("Not allowed, the API is restricted to account {id}");
+        }
+        Ok(())
+    }
+
+    /// Returns an error if the API is restricted to a single account
+    /// and `path` is outside of the directory of the account,
+    /// so that clients cannot read or write files of other accounts.
+    ///
+    /// Paths starting with `$BLOBDIR` are relative to the blob directory.
+    async fn ensure_path_allowed(
+        &self,
+        ctx: &deltachat::context::Context,
+        path: &str,
+    ) -> anyhow::Result<()> {
+        let id = match self.single_account {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let path = match path.strip_prefix("$BLOBDIR") {
+            Some(name) => ctx.get_blobdir().join(name.trim_start_matches(['/', '\\'])),
+            None => PathBuf::from(path),
+        };
+        ensure!(
+            path.is_absolute() && !path.components().any(|c| c == Component::ParentDir),
+            "Path {} is not allowed, it must be absolute and must not contain \"..\"",
+            path.display()
+        );
+        let account_dir = ctx
+            .get_blobdir()
+            .parent()
+            .context("account directory not found")?;
+        let account_dir = fs::canonicalize(account_dir).await?;
+
+        // Resolve symlinks in the part of the path that already exists.
+        let mut existing = path.as_path();
+        while fs::symlink_metadata(existing).await.is_err() {
+            existing = match existing.parent() {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+        let resolved = fs::canonicalize(existing)
+            .await?
+            .join(path.strip_prefix(existing)?);
+        ensure!(
+            resolved.starts_with(&account_dir),
+            "Path {} is not allowed, the API is restricted to the directory of account {id}",
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Checks the files of a message with [`CommandApi::ensure_path_allowed`].
+    async fn ensure_msg_data_allowed(
+        &self,
+        ctx: &deltachat::context::Context,
+        data: &MessageData,
+    ) -> anyhow::Result<()> {
+        for file in data.file.iter().chain(data.attachments.iter().flatten()) {
+            self.ensure_path_allowed(ctx, file).await?;
+        }
+        Ok(())
+    }
+
+    /// Checks the path of the avatar set with `set_config()` with [`CommandApi::ensure_path_allowed`].
+    async fn ensure_config_allowed(
+        &self,
+        ctx: &deltachat::context::Context,
+        key: &str,
+        value: Option<&str>,
+    ) -> anyhow::Result<()> {
+        match (key, value) {
+            ("selfavatar", Some(path)) if !path.is_empty() => {
+                self.ensure_path_allowed(ctx, path).await
+            }
+            _ => Ok(()),
+        }
+    }
+
     async fn with_state<F, T>(&self, id: u32, with_state: F) -> T
     where
-        F: FnOnce(&AccountState) -> T,
+        F: FnOnce(&mut AccountState) -> T,
     {
         let mut states = self.states.lock().await;
         let state = states.entry(id).or_insert_with(Default::default);
         with_state(state)
     }
 
-    async fn inner_get_backup_qr(&self, account_id: u32) -> Result<Qr> {
+    async fn inner_get_backup_qr(&self, account_id: u32) -> anyhow::Result<Qr> {
         let mut receiver = self
             .with_state(account_id, |state| state.backup_provider_qr.subscribe())
             .await;
@@ -164,15 +476,36 @@ async fn get_system_info(&self) -> BTreeMap<&'static str, String> {
         get_info()
     }
 
+    /// Get the request counters of this connection,
+    /// e.g. to see how many requests wait because the concurrency limit of the server is reached.
+    async fn get_rpc_stats(&self) -> JsonrpcRpcStats {
+        self.rpc_stats.snapshot()
+    }
+
+    /// Get the counters of the server, e.g. request latencies and IMAP reconnects.
+    ///
+    /// Unlike `get_rpc_stats()`, the counters are shared by all connections of the server.
+    ///
+    /// If the API is restricted to a single account, only the counters of that account are returned.
+    async fn get_metrics(&self) -> JsonrpcMetrics {
+        let mut metrics = self.metrics.snapshot();
+        if let Some(id) = self.single_account {
+            metrics.accounts.retain(|account| account.account_id == id);
+        }
+        metrics
+    }
+
     // ---------------------------------------------
     // Account Management
     // ---------------------------------------------
 
     async fn add_account(&self) -> Result<u32> {
-        self.accounts.write().await.add_account().await
+        self.ensure_all_accounts_allowed()?;
+        Ok(self.accounts.write().await.add_account().await?)
     }
 
     async fn remove_account(&self, account_id: u32) -> Result<()> {
+        self.ensure_all_accounts_allowed()?;
         self.accounts
             .write()
             .await
@@ -182,27 +515,51 @@ async fn remove_account(&self, account_id: u32) -> Result<()> {
         Ok(())
     }
 
+    /// Removes the account and, if `shred` is true,
+    /// overwrites its database and blobs before deleting them.
+    async fn purge_account(&self, account_id: u32, shred: bool) -> Result<()> {
+        self.ensure_all_accounts_allowed()?;
+        self.accounts
+            .write()
+            .await
+            .purge_account(account_id, shred)
+            .await?;
+        self.states.lock().await.remove(&account_id);
+        Ok(())
+    }
+
     async fn get_all_account_ids(&self) -> Vec<u32> {
-        self.accounts.read().await.get_all()
+        self.accounts
+            .read()
+            .await
+            .get_all()
+            .into_iter()
+            .filter(|id| self.is_account_allowed(*id))
+            .collect()
     }
 
     /// Select account id for internally selected state.
     /// TODO: Likely this is deprecated as all methods take an account id now.
     async fn select_account(&self, id: u32) -> Result<()> {
-        self.accounts.write().await.select_account(id).await
+        self.ensure_all_accounts_allowed()?;
+        Ok(self.accounts.write().await.select_account(id).await?)
     }
 
     /// Get the selected account id of the internal state..
     /// TODO: Likely this is deprecated as all methods take an account id now.
     async fn get_selected_account_id(&self) -> Option<u32> {
-        self.accounts.read().await.get_selected_account_id()
+        self.accounts
+            .read()
+            .await
+            .get_selected_account_id()
+            .filter(|id| self.is_account_allowed(*id))
     }
 
     /// Get a list of all configured accounts.
     async fn get_all_accounts(&self) -> Result<Vec<Account>> {
         let mut accounts = Vec::new();
-        for id in self.accounts.read().await.get_all() {
-            let context_option = self.accounts.read().await.get_account(id);
+        for id in self.get_all_account_ids().await {
+            let context_option = self.get_account(id).await;
             if let Some(ctx) = context_option {
                 accounts.push(Account::from_context(&ctx, id).await?)
             } else {
@@ -213,15 +570,55 @@ async fn get_all_accounts(&self) -> Result<Vec<Account>> {
     }
 
     async fn start_io_for_all_accounts(&self) -> Result<()> {
+        self.ensure_all_accounts_allowed()?;
         self.accounts.read().await.start_io().await;
         Ok(())
     }
 
     async fn stop_io_for_all_accounts(&self) -> Result<()> {
+        self.ensure_all_accounts_allowed()?;
         self.accounts.read().await.stop_io().await;
         Ok(())
     }
 
+    /// Locks all encrypted accounts, e.g. when the app lock of the UI is activated.
+    ///
+    /// IO is stopped and the databases are closed until `unlock_accounts()`
+    /// is called with the correct passphrase. Unencrypted accounts stay open.
+    async fn lock_accounts(&self) -> Result<()> {
+        self.ensure_all_accounts_allowed()?;
+        Ok(self.accounts.write().await.lock().await?)
+    }
+
+    /// Unlocks the accounts locked with `lock_accounts()`.
+    ///
+    /// Returns true once no locked accounts are left.
+    /// If accounts use different passphrases, call this once for each passphrase.
+    async fn unlock_accounts(&self, passphrase: String) -> Result<bool> {
+        self.ensure_all_accounts_allowed()?;
+        Ok(self.accounts.write().await.unlock(passphrase).await?)
+    }
+
+    /// Coalesces `MsgsChanged` and `ChatModified` events of the same chat
+    /// within `milliseconds`, 0 to disable coalescing.
+    ///
+    /// The first event of a chat is emitted immediately,
+    /// further events of the chat within the interval are emitted as a single event at its end.
+    /// `MsgsChanged` events of different messages are merged into one with `msgId` 0.
+    async fn set_event_coalescing_interval(&self, milliseconds: u32) -> Result<()> {
+        self.ensure_all_accounts_allowed()?;
+        self.accounts
+            .read()
+            .await
+            .set_event_coalescing_interval(std::time::Duration::from_millis(milliseconds.into()));
+        Ok(())
+    }
+
+    /// Returns true if the accounts are locked with `lock_accounts()`.
+    async fn are_accounts_locked(&self) -> bool {
+        self.accounts.read().await.is_locked()
+    }
+
     // ---------------------------------------------
     // Methods that work on individual accounts
     // ---------------------------------------------
@@ -240,14 +637,16 @@ async fn stop_io(&self, id: u32) -> Result<()> {
 
     /// Get top-level info for an account.
     async fn get_account_info(&self, account_id: u32) -> Result<Account> {
-        let context_option = self.accounts.read().await.get_account(account_id);
+        let context_option = self.get_account(account_id).await;
         if let Some(ctx) = context_option {
             Ok(Account::from_context(&ctx, account_id).await?)
         } else {
-            Err(anyhow!(
-                "account with id {} doesn't exist anymore",
-                account_id
-            ))
+            Err(ErrorCode::NotFound
+                .wrap(anyhow!(
+                    "account with id {} doesn't exist anymore",
+                    account_id
+                ))
+                .into())
         }
     }
 
@@ -291,18 +690,43 @@ async fn get_provider_info(
     /// Checks if the context is already configured.
     async fn is_configured(&self, account_id: u32) -> Result<bool> {
         let ctx = self.get_context(account_id).await?;
-        ctx.is_configured().await
+        Ok(ctx.is_configured().await?)
+    }
+
+    /// Checks if the account is on a chatmail relay,
+    /// which does not exchange messages with classic email servers.
+    async fn is_chatmail(&self, account_id: u32) -> Result<bool> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ctx.is_chatmail().await?)
     }
 
     /// Get system info for an account.
     async fn get_info(&self, account_id: u32) -> Result<BTreeMap<&'static str, String>> {
         let ctx = self.get_context(account_id).await?;
-        ctx.get_info().await
+        Ok(ctx.get_info().await?)
+    }
+
+    /// Returns usage statistics of the messages sent or received
+    /// from `timestamp_start` (inclusive) to `timestamp_end` (exclusive),
+    /// e.g. for a "year in review" screen.
+    ///
+    /// The statistics are computed locally.
+    async fn get_account_stats(
+        &self,
+        account_id: u32,
+        timestamp_start: i64,
+        timestamp_end: i64,
+    ) -> Result<JsonrpcAccountStats> {
+        let ctx = self.get_context(account_id).await?;
+        let stats = account_stats::get_account_stats(&ctx, timestamp_start, timestamp_end).await?;
+        Ok(stats.into())
     }
 
     async fn set_config(&self, account_id: u32, key: String, value: Option<String>) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        set_config(&ctx, &key, value.as_deref()).await
+        self.ensure_config_allowed(&ctx, &key, value.as_deref())
+            .await?;
+        Ok(set_config(&ctx, &key, value.as_deref()).await?)
     }
 
     async fn batch_set_config(
@@ -311,6 +735,10 @@ async fn batch_set_config(
         config: HashMap<String, Option<String>>,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
+        for (key, value) in &config {
+            self.ensure_config_allowed(&ctx, key, value.as_deref())
+                .await?;
+        }
         for (key, value) in config.into_iter() {
             set_config(&ctx, &key, value.as_deref())
                 .await
@@ -326,7 +754,22 @@ async fn batch_set_config(
     /// Internally, the function will call dc_set_config() with the appropriate keys,
     async fn set_config_from_qr(&self, account_id: u32, qr_content: String) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        qr::set_config_from_qr(&ctx, &qr_content).await
+        Ok(qr::set_config_from_qr(&ctx, &qr_content).await?)
+    }
+
+    /// Applies a `DCPROFILE:` account provisioning QR code to an unconfigured account.
+    ///
+    /// The server settings and the display name are applied atomically.
+    /// Returns the descriptions of the avatar, contacts and groups
+    /// which could not be applied.
+    /// Call `configure()` afterwards.
+    async fn set_profile_from_qr(
+        &self,
+        account_id: u32,
+        qr_content: String,
+    ) -> Result<Vec<String>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(qr::set_profile_from_qr(&ctx, &qr_content).await?)
     }
 
     async fn check_qr(&self, account_id: u32, qr_content: String) -> Result<QrObject> {
@@ -338,7 +781,7 @@ async fn check_qr(&self, account_id: u32, qr_content: String) -> Result<QrObject
 
     async fn get_config(&self, account_id: u32, key: String) -> Result<Option<String>> {
         let ctx = self.get_context(account_id).await?;
-        get_config(&ctx, &key).await
+        Ok(get_config(&ctx, &key).await?)
     }
 
     async fn batch_get_config(
@@ -354,7 +797,52 @@ async fn batch_get_config(
         Ok(result)
     }
 
+    /// Searches GIFs using the provider configured in `gif_search_endpoint`.
+    ///
+    /// The attribution of the results should be shown next to them.
+    async fn search_gifs(
+        &self,
+        account_id: u32,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<JsonrpcGifSearchResult>> {
+        let ctx = self.get_context(account_id).await?;
+        let results = gif_search::search_gifs(&ctx, &query, limit).await?;
+        Ok(results.into_iter().map(Into::into).collect())
+    }
+
+    /// Downloads the GIF of a search result returned by `search_gifs()`
+    /// and sends it to the chat.
+    async fn send_gif(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        result: JsonrpcGifSearchResult,
+    ) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        let msg_id = gif_search::send_gif(&ctx, ChatId::new(chat_id), &result.into()).await?;
+        Ok(msg_id.to_u32())
+    }
+
+    /// Exports preferences and per-chat settings as a JSON document.
+    ///
+    /// Credentials, server settings, keys and messages are not included.
+    async fn export_settings(&self, account_id: u32) -> Result<String> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ctx.export_settings().await?)
+    }
+
+    /// Imports a settings document created by `export_settings()`
+    /// or a settings file of Delta Chat Desktop, Android or iOS.
+    ///
+    /// Settings of chats that do not exist in the account are skipped.
+    async fn import_settings(&self, account_id: u32, json: String) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ctx.import_settings(&json).await?)
+    }
+
     async fn set_stock_strings(&self, strings: HashMap<u32, String>) -> Result<()> {
+        self.ensure_all_accounts_allowed()?;
         let accounts = self.accounts.read().await;
         for (stock_id, stock_message) in strings {
             if let Some(stock_id) = StockMessage::from_u32(stock_id) {
@@ -366,17 +854,36 @@ async fn set_stock_strings(&self, strings: HashMap<u32, String>) -> Result<()> {
         Ok(())
     }
 
+    /// Sets stock strings in ICU MessageFormat syntax supporting plural forms,
+    /// e.g. `{1, plural, one {# minute} other {# minutes}}`.
+    ///
+    /// `language` is used to select the plural forms, e.g. `de` or `pt-BR`.
+    async fn set_stock_formats(
+        &self,
+        language: String,
+        formats: HashMap<u32, String>,
+    ) -> Result<()> {
+        self.ensure_all_accounts_allowed()?;
+        let accounts = self.accounts.read().await;
+        accounts.set_stock_language(&language).await;
+        for (stock_id, format) in formats {
+            if let Some(stock_id) = StockMessage::from_u32(stock_id) {
+                accounts.set_stock_format(stock_id, &format).await?;
+            }
+        }
+        Ok(())
+    }
+
     /// Configures this account with the currently set parameters.
     /// Setup the credential config before calling this.
     async fn configure(&self, account_id: u32) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
         ctx.stop_io().await;
-        let result = ctx.configure().await;
-        if result.is_err() {
+        if let Err(err) = ctx.configure().await {
             if let Ok(true) = ctx.is_configured().await {
                 ctx.start_io().await;
             }
-            return result;
+            return Err(err.into());
         }
         ctx.start_io().await;
         Ok(())
@@ -396,13 +903,14 @@ async fn export_self_keys(
         passphrase: Option<String>,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        imex::imex(
+        self.ensure_path_allowed(&ctx, &path).await?;
+        Ok(imex::imex(
             &ctx,
             imex::ImexMode::ExportSelfKeys,
             path.as_ref(),
             passphrase,
         )
-        .await
+        .await?)
     }
 
     async fn import_self_keys(
@@ -412,13 +920,14 @@ async fn import_self_keys(
         passphrase: Option<String>,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        imex::imex(
+        self.ensure_path_allowed(&ctx, &path).await?;
+        Ok(imex::imex(
             &ctx,
             imex::ImexMode::ImportSelfKeys,
             path.as_ref(),
             passphrase,
         )
-        .await
+        .await?)
     }
 
     /// Returns the message IDs of all _fresh_ messages of any chat.
@@ -442,6 +951,24 @@ async fn get_fresh_msgs(&self, account_id: u32) -> Result<Vec<u32>> {
             .collect())
     }
 
+    /// Returns the message IDs of all starred messages, newest first.
+    async fn get_starred_msgs(&self, account_id: u32) -> Result<Vec<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ctx
+            .get_starred_msgs()
+            .await?
+            .iter()
+            .map(|msg_id| msg_id.to_u32())
+            .collect())
+    }
+
+    /// Stars or unstars a message.
+    /// The star is synchronized with the `\Flagged` flag on the IMAP server.
+    async fn set_message_starred(&self, account_id: u32, msg_id: u32, starred: bool) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(MsgId::new(msg_id).set_starred(&ctx, starred).await?)
+    }
+
     /// Get the number of _fresh_ messages in a chat.
     /// Typically used to implement a badge with a number in the chatlist.
     ///
@@ -450,7 +977,22 @@ async fn get_fresh_msgs(&self, account_id: u32) -> Result<Vec<u32>> {
     /// e.g. using "gray" instead of "red" color.
     async fn get_fresh_msg_cnt(&self, account_id: u32, chat_id: u32) -> Result<usize> {
         let ctx = self.get_context(account_id).await?;
-        ChatId::new(chat_id).get_fresh_msg_cnt(&ctx).await
+        Ok(ChatId::new(chat_id).get_fresh_msg_cnt(&ctx).await?)
+    }
+
+    /// Get the number of _fresh_ messages in the thread the given message belongs to.
+    async fn get_thread_fresh_msg_cnt(&self, account_id: u32, msg_id: u32) -> Result<usize> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(MsgId::new(msg_id).get_thread_fresh_msg_cnt(&ctx).await?)
+    }
+
+    /// Store the corrected order of messages delivered out of order,
+    /// see `get_corrected_message_list_items()`.
+    ///
+    /// Returns the number of messages moved.
+    async fn repair_msgs_order(&self, account_id: u32, chat_id: u32) -> Result<usize> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ChatId::new(chat_id).repair_msgs_order(&ctx).await?)
     }
 
     /// Estimate the number of messages that will be deleted
@@ -464,7 +1006,106 @@ async fn estimate_auto_deletion_count(
         seconds: i64,
     ) -> Result<usize> {
         let ctx = self.get_context(account_id).await?;
-        message::estimate_deletion_cnt(&ctx, from_server, seconds).await
+        Ok(message::estimate_deletion_cnt(&ctx, from_server, seconds).await?)
+    }
+
+    /// Sets how long messages are kept in the given IMAP folder,
+    /// overriding the set_config()-option `delete_server_after` for this folder.
+    ///
+    /// The value has the same meaning as `delete_server_after`,
+    /// `null` removes the override.
+    async fn set_folder_delete_server_after(
+        &self,
+        account_id: u32,
+        folder: String,
+        delete_server_after: Option<i64>,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ephemeral::set_folder_delete_server_after(&ctx, &folder, delete_server_after).await?)
+    }
+
+    /// Returns the folders with a `delete_server_after` override
+    /// set using `set_folder_delete_server_after()`.
+    async fn get_folder_delete_server_after(
+        &self,
+        account_id: u32,
+    ) -> Result<BTreeMap<String, i64>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ephemeral::get_folder_delete_server_after(&ctx).await?)
+    }
+
+    /// Returns the messages which would be deleted from the server next,
+    /// without deleting anything.
+    async fn get_server_deletions(&self, account_id: u32) -> Result<Vec<JsonrpcServerDeletion>> {
+        let ctx = self.get_context(account_id).await?;
+        let deletions = ephemeral::get_server_deletions(&ctx).await?;
+        Ok(deletions.into_iter().map(Into::into).collect())
+    }
+
+    /// Returns pending moves, deletions and `\Seen` flag updates on the server, oldest first.
+    async fn get_imap_jobs(&self, account_id: u32) -> Result<Vec<JsonrpcImapJob>> {
+        let ctx = self.get_context(account_id).await?;
+        let jobs = imap_jobs::get_imap_jobs(&ctx).await?;
+        Ok(jobs.into_iter().map(Into::into).collect())
+    }
+
+    /// Clears the error of a job returned by `get_imap_jobs()` and tries to execute it again.
+    async fn retry_imap_job(&self, account_id: u32, job_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(imap_jobs::retry_imap_job(&ctx, job_id).await?)
+    }
+
+    /// Drops a job returned by `get_imap_jobs()` without executing it.
+    async fn drop_imap_job(&self, account_id: u32, job_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(imap_jobs::drop_imap_job(&ctx, job_id).await?)
+    }
+
+    /// Subscribes to the changes of a single chat.
+    ///
+    /// Afterwards, events about messages, reactions and members of the chat
+    /// are additionally delivered as `chatEvent` notifications
+    /// containing `contextId`, `chatId` and the `event`,
+    /// so the UI can update a single message instead of reloading the whole message list.
+    /// Member changes are delivered as `ChatModified`.
+    /// Subscriptions only apply to the connection they were made on.
+    async fn subscribe_chat(&self, account_id: u32, chat_id: u32) -> Result<()> {
+        self.get_context(account_id).await?;
+        self.chat_subscriptions
+            .subscribe(account_id, ChatId::new(chat_id))
+            .await;
+        Ok(())
+    }
+
+    /// Stops delivering `chatEvent` notifications for a chat subscribed with `subscribe_chat()`.
+    async fn unsubscribe_chat(&self, account_id: u32, chat_id: u32) -> Result<()> {
+        self.chat_subscriptions
+            .unsubscribe(account_id, ChatId::new(chat_id))
+            .await;
+        Ok(())
+    }
+
+    /// Only delivers `event` notifications of the given accounts and event kinds,
+    /// e.g. `["IncomingMsg", "MsgsChanged"]` as in the `type` field of the event.
+    /// `null` delivers the events of all accounts or all kinds, which is the default.
+    ///
+    /// `chatEvent` notifications of subscribed chats are not affected.
+    ///
+    /// The `seq` of notifications is numbered before filtering,
+    /// so filtered-out events show up as gaps in `seq`
+    /// and gaps cannot be used to detect dropped events while a filter is set.
+    async fn set_event_filter(
+        &self,
+        account_ids: Option<Vec<u32>>,
+        event_kinds: Option<Vec<String>>,
+    ) -> Result<()> {
+        self.event_filter
+            .set(
+                account_ids.map(|ids| ids.into_iter().collect()),
+                event_kinds.map(|kinds| kinds.into_iter().collect()),
+            )
+            .await;
+        Ok(())
     }
 
     // ---------------------------------------------
@@ -473,7 +1114,7 @@ async fn estimate_auto_deletion_count(
 
     async fn initiate_autocrypt_key_transfer(&self, account_id: u32) -> Result<String> {
         let ctx = self.get_context(account_id).await?;
-        deltachat::imex::initiate_key_transfer(&ctx).await
+        Ok(deltachat::imex::initiate_key_transfer(&ctx).await?)
     }
 
     async fn continue_autocrypt_key_transfer(
@@ -483,7 +1124,10 @@ async fn continue_autocrypt_key_transfer(
         setup_code: String,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        deltachat::imex::continue_key_transfer(&ctx, MsgId::new(message_id), &setup_code).await
+        Ok(
+            deltachat::imex::continue_key_transfer(&ctx, MsgId::new(message_id), &setup_code)
+                .await?,
+        )
     }
 
     // ---------------------------------------------
@@ -545,24 +1189,32 @@ async fn get_chatlist_items_by_entries(
 
     async fn get_full_chat_by_id(&self, account_id: u32, chat_id: u32) -> Result<FullChat> {
         let ctx = self.get_context(account_id).await?;
-        FullChat::try_from_dc_chat_id(&ctx, chat_id).await
+        Ok(FullChat::try_from_dc_chat_id(&ctx, chat_id).await?)
     }
 
     /// get basic info about a chat,
     /// use chatlist_get_full_chat_by_id() instead if you need more information
     async fn get_basic_chat_info(&self, account_id: u32, chat_id: u32) -> Result<BasicChat> {
         let ctx = self.get_context(account_id).await?;
-        BasicChat::try_from_dc_chat_id(&ctx, chat_id).await
+        Ok(BasicChat::try_from_dc_chat_id(&ctx, chat_id).await?)
     }
 
     async fn accept_chat(&self, account_id: u32, chat_id: u32) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        ChatId::new(chat_id).accept(&ctx).await
+        Ok(ChatId::new(chat_id).accept(&ctx).await?)
+    }
+
+    /// Returns how likely a contact request is spam, from 0 (unlikely) to 100 (likely).
+    ///
+    /// Returns `null` if the chat is not a contact request of a single contact.
+    async fn get_chat_spam_score(&self, account_id: u32, chat_id: u32) -> Result<Option<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(reputation::get_spam_score(&ctx, ChatId::new(chat_id)).await?)
     }
 
     async fn block_chat(&self, account_id: u32, chat_id: u32) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        ChatId::new(chat_id).block(&ctx).await
+        Ok(ChatId::new(chat_id).block(&ctx).await?)
     }
 
     /// Delete a chat.
@@ -585,7 +1237,7 @@ async fn block_chat(&self, account_id: u32, chat_id: u32) -> Result<()> {
     /// To leave a chat explicitly, use leave_group()
     async fn delete_chat(&self, account_id: u32, chat_id: u32) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        ChatId::new(chat_id).delete(&ctx).await
+        Ok(ChatId::new(chat_id).delete(&ctx).await?)
     }
 
     /// Get encryption info for a chat.
@@ -595,7 +1247,7 @@ async fn delete_chat(&self, account_id: u32, chat_id: u32) -> Result<()> {
     /// returns Multi-line text
     async fn get_chat_encryption_info(&self, account_id: u32, chat_id: u32) -> Result<String> {
         let ctx = self.get_context(account_id).await?;
-        ChatId::new(chat_id).get_encryption_info(&ctx).await
+        Ok(ChatId::new(chat_id).get_encryption_info(&ctx).await?)
     }
 
     /// Get QR code (text and SVG) that will offer an Setup-Contact or Verified-Group invitation.
@@ -654,9 +1306,130 @@ async fn secure_join(&self, account_id: u32, qr: String) -> Result<u32> {
         Ok(chat_id.to_u32())
     }
 
-    async fn leave_group(&self, account_id: u32, chat_id: u32) -> Result<()> {
+    /// Invite a verified contact to a protected group without a QR code.
+    ///
+    /// The contact receives a `SecurejoinInviteReceived` event
+    /// and is added to the group after calling `accept_group_invite()`.
+    async fn forward_group_invite(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        contact_id: u32,
+    ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        remove_contact_from_chat(&ctx, ChatId::new(chat_id), ContactId::SELF).await
+        Ok(
+            securejoin::forward_group_invite(
+                &ctx,
+                ChatId::new(chat_id),
+                ContactId::new(contact_id),
+            )
+            .await?,
+        )
+    }
+
+    /// Get the name of the group of an invite received with the `SecurejoinInviteReceived` event.
+    async fn get_group_invite_name(
+        &self,
+        account_id: u32,
+        invite_id: u32,
+    ) -> Result<Option<String>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(securejoin::get_group_invite(&ctx, invite_id)
+            .await?
+            .map(|invite| invite.group_name))
+    }
+
+    /// Accept an invite received with the `SecurejoinInviteReceived` event.
+    /// The group is created once the inviting contact has added us to it.
+    async fn accept_group_invite(&self, account_id: u32, invite_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(securejoin::accept_group_invite(&ctx, invite_id).await?)
+    }
+
+    /// Enables or disables the join gate of a group:
+    /// contacts joining via QR code have to answer a challenge before they are added.
+    async fn set_join_gate(&self, account_id: u32, chat_id: u32, enabled: bool) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(securejoin::set_join_gate(&ctx, ChatId::new(chat_id), enabled).await?)
+    }
+
+    async fn is_join_gate_enabled(&self, account_id: u32, chat_id: u32) -> Result<bool> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(securejoin::is_join_gate_enabled(&ctx, ChatId::new(chat_id)).await?)
+    }
+
+    /// Get a challenge received with the `SecurejoinChallengeReceived` event.
+    async fn get_join_challenge(
+        &self,
+        account_id: u32,
+        challenge_id: u32,
+    ) -> Result<Option<JsonrpcJoinChallenge>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(securejoin::get_join_challenge(&ctx, challenge_id)
+            .await?
+            .map(Into::into))
+    }
+
+    /// Answer a challenge received with the `SecurejoinChallengeReceived` event
+    /// with one of its options.
+    async fn answer_join_challenge(
+        &self,
+        account_id: u32,
+        challenge_id: u32,
+        answer: String,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(securejoin::answer_join_challenge(&ctx, challenge_id, &answer).await?)
+    }
+
+    /// Creates an invite link for a group which can be used at most `max_uses` times
+    /// and returns its QR code.
+    async fn create_invite_link(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        max_uses: u32,
+    ) -> Result<String> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(securejoin::create_invite_link(&ctx, ChatId::new(chat_id), max_uses).await?)
+    }
+
+    /// Returns the invite links of a group which can still be used, newest first.
+    async fn get_invite_links(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+    ) -> Result<Vec<JsonrpcInviteLink>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(securejoin::get_invite_links(&ctx, ChatId::new(chat_id))
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Returns the contacts who joined through an invite link, in the order they joined.
+    async fn get_invite_link_joins(
+        &self,
+        account_id: u32,
+        link_id: u32,
+    ) -> Result<Vec<JsonrpcInviteLinkJoin>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(securejoin::get_invite_link_joins(&ctx, link_id)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    async fn delete_invite_link(&self, account_id: u32, link_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(securejoin::delete_invite_link(&ctx, link_id).await?)
+    }
+
+    async fn leave_group(&self, account_id: u32, chat_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(remove_contact_from_chat(&ctx, ChatId::new(chat_id), ContactId::SELF).await?)
     }
 
     /// Remove a member from a group.
@@ -672,7 +1445,47 @@ async fn remove_contact_from_chat(
         contact_id: u32,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        remove_contact_from_chat(&ctx, ChatId::new(chat_id), ContactId::new(contact_id)).await
+        Ok(
+            remove_contact_from_chat(&ctx, ChatId::new(chat_id), ContactId::new(contact_id))
+                .await?,
+        )
+    }
+
+    /// Remove a member from a group and ban them,
+    /// so they are not added again by this device or other members.
+    ///
+    /// The ban is sent to the other members together with the member-removed message.
+    /// As groups have no admins, any member can ban and unban members.
+    async fn remove_member_and_ban(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        contact_id: u32,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(
+            chat::remove_member_and_ban(&ctx, ChatId::new(chat_id), ContactId::new(contact_id))
+                .await?,
+        )
+    }
+
+    /// Lift the ban of a member banned with `remove_member_and_ban()`
+    /// and add them to the group again.
+    ///
+    /// The unban is sent to the other members together with the member-added message.
+    async fn unban_member(&self, account_id: u32, chat_id: u32, contact_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(chat::unban_member(&ctx, ChatId::new(chat_id), ContactId::new(contact_id)).await?)
+    }
+
+    /// Returns the contacts banned from a group with `remove_member_and_ban()`.
+    async fn get_banned_members(&self, account_id: u32, chat_id: u32) -> Result<Vec<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(chat::get_banned_members(&ctx, ChatId::new(chat_id))
+            .await?
+            .into_iter()
+            .map(|id| id.to_u32())
+            .collect())
     }
 
     /// Add a member to a group.
@@ -690,7 +1503,7 @@ async fn add_contact_to_chat(
         contact_id: u32,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        add_contact_to_chat(&ctx, ChatId::new(chat_id), ContactId::new(contact_id)).await
+        Ok(add_contact_to_chat(&ctx, ChatId::new(chat_id), ContactId::new(contact_id)).await?)
     }
 
     /// Get the contact IDs belonging to a chat.
@@ -739,9 +1552,43 @@ async fn create_group_chat(&self, account_id: u32, name: String, protect: bool)
             true => ProtectionStatus::Protected,
             false => ProtectionStatus::Unprotected,
         };
-        chat::create_group_chat(&ctx, protect, &name)
-            .await
-            .map(|id| id.to_u32())
+        Ok(chat::create_group_chat(&ctx, protect, &name)
+            .await?
+            .to_u32())
+    }
+
+    /// Create a new group chat with initial members, avatar and ephemeral timer.
+    ///
+    /// Either all settings are applied or no group is created at all.
+    /// The group is in _unpromoted_ state, see `create_group_chat()`;
+    /// the first message sent to the group carries the complete member list.
+    ///
+    /// @param avatar Full path of the group image, `null` for no image.
+    /// @param ephemeral_timer Ephemeral timer in seconds, 0 to disable ephemeral messages.
+    async fn create_group_chat_ex(
+        &self,
+        account_id: u32,
+        name: String,
+        protect: bool,
+        members: Vec<u32>,
+        avatar: Option<String>,
+        ephemeral_timer: u32,
+    ) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        if let Some(avatar) = &avatar {
+            self.ensure_path_allowed(&ctx, avatar).await?;
+        }
+        let params = chat::GroupParams {
+            name,
+            protect: match protect {
+                true => ProtectionStatus::Protected,
+                false => ProtectionStatus::Unprotected,
+            },
+            members: members.into_iter().map(ContactId::new).collect(),
+            avatar: avatar.map(PathBuf::from),
+            ephemeral_timer: Timer::from_u32(ephemeral_timer),
+        };
+        Ok(chat::create_group_chat_ex(&ctx, params).await?.to_u32())
     }
 
     /// Create a new broadcast list.
@@ -767,9 +1614,7 @@ async fn create_group_chat(&self, account_id: u32, name: String, protect: bool)
     /// All in all, this is also what other messengers are doing here.
     async fn create_broadcast_list(&self, account_id: u32) -> Result<u32> {
         let ctx = self.get_context(account_id).await?;
-        chat::create_broadcast_list(&ctx)
-            .await
-            .map(|id| id.to_u32())
+        Ok(chat::create_broadcast_list(&ctx).await?.to_u32())
     }
 
     /// Set group name.
@@ -780,7 +1625,7 @@ async fn create_broadcast_list(&self, account_id: u32) -> Result<u32> {
     /// Sends out #DC_EVENT_CHAT_MODIFIED and #DC_EVENT_MSGS_CHANGED if a status message was sent.
     async fn set_chat_name(&self, account_id: u32, chat_id: u32, new_name: String) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        chat::set_chat_name(&ctx, ChatId::new(chat_id), &new_name).await
+        Ok(chat::set_chat_name(&ctx, ChatId::new(chat_id), &new_name).await?)
     }
 
     /// Set group profile image.
@@ -803,8 +1648,31 @@ async fn set_chat_profile_image(
         image_path: Option<String>,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        chat::set_chat_profile_image(&ctx, ChatId::new(chat_id), &image_path.unwrap_or_default())
-            .await
+        if let Some(image_path) = image_path.as_ref().filter(|path| !path.is_empty()) {
+            self.ensure_path_allowed(&ctx, image_path).await?;
+        }
+        Ok(chat::set_chat_profile_image(
+            &ctx,
+            ChatId::new(chat_id),
+            &image_path.unwrap_or_default(),
+        )
+        .await?)
+    }
+
+    /// Sets the signature sent as footer of messages in the chat, overriding `selfstatus`.
+    ///
+    /// An empty string disables the footer for this chat, `null` uses `selfstatus` again.
+    /// The setting is synchronized to other devices.
+    async fn set_chat_signature(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        signature: Option<String>,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ChatId::new(chat_id)
+            .set_signature(&ctx, signature.as_deref())
+            .await?)
     }
 
     async fn set_chat_visibility(
@@ -815,9 +1683,44 @@ async fn set_chat_visibility(
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
 
-        ChatId::new(chat_id)
+        Ok(ChatId::new(chat_id)
             .set_visibility(&ctx, visibility.into_core_type())
-            .await
+            .await?)
+    }
+
+    /// Archives or unarchives several chats at once.
+    ///
+    /// All chats are updated in a single transaction and `MsgsChanged` is emitted only once.
+    /// Unarchiving keeps pinned chats pinned.
+    async fn archive_chats(
+        &self,
+        account_id: u32,
+        chat_ids: Vec<u32>,
+        archive: bool,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        let chat_ids: Vec<ChatId> = chat_ids.into_iter().map(ChatId::new).collect();
+        Ok(chat::archive_chats(&ctx, &chat_ids, archive).await?)
+    }
+
+    /// Set whether new messages unarchive the archived chat.
+    async fn set_chat_archive_mode(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        archive_mode: JSONRPCArchiveMode,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ChatId::new(chat_id)
+            .set_archive_mode(&ctx, archive_mode.into_core_type())
+            .await?)
+    }
+
+    /// Enable or disable threaded display of a chat.
+    /// The setting is local and is returned as `isThreaded` in `FullChat`.
+    async fn set_chat_threaded(&self, account_id: u32, chat_id: u32, threaded: bool) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ChatId::new(chat_id).set_threaded(&ctx, threaded).await?)
     }
 
     async fn set_chat_ephemeral_timer(
@@ -827,9 +1730,9 @@ async fn set_chat_ephemeral_timer(
         timer: u32,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        ChatId::new(chat_id)
+        Ok(ChatId::new(chat_id)
             .set_ephemeral_timer(&ctx, Timer::from_u32(timer))
-            .await
+            .await?)
     }
 
     async fn get_chat_ephemeral_timer(&self, account_id: u32, chat_id: u32) -> Result<u32> {
@@ -840,6 +1743,14 @@ async fn get_chat_ephemeral_timer(&self, account_id: u32, chat_id: u32) -> Resul
             .to_u32())
     }
 
+    /// Returns the IDs of chat members who acknowledged the current ephemeral timer,
+    /// i.e. whose latest message in the chat uses the same timer.
+    async fn get_ephemeral_timer_acks(&self, account_id: u32, chat_id: u32) -> Result<Vec<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        let contact_ids = ChatId::new(chat_id).get_ephemeral_timer_acks(&ctx).await?;
+        Ok(contact_ids.into_iter().map(|id| id.to_u32()).collect())
+    }
+
     // for now only text messages, because we only used text messages in desktop thusfar
     async fn add_device_message(
         &self,
@@ -864,7 +1775,89 @@ async fn add_device_message(
     ///  See also markseen_msgs().
     async fn marknoticed_chat(&self, account_id: u32, chat_id: u32) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        marknoticed_chat(&ctx, ChatId::new(chat_id)).await
+        Ok(marknoticed_chat(&ctx, ChatId::new(chat_id)).await?)
+    }
+
+    /// Marks all messages in the given chats as _noticed_.
+    ///
+    /// Same as calling `marknoticed_chat()` for every chat,
+    /// but all chats are updated at once.
+    /// `MsgsNoticed` is emitted for every chat that had fresh messages.
+    async fn mark_chats_noticed(&self, account_id: u32, chat_ids: Vec<u32>) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        let chat_ids: Vec<ChatId> = chat_ids.into_iter().map(ChatId::new).collect();
+        Ok(chat::marknoticed_chats(&ctx, &chat_ids).await?)
+    }
+
+    /// Requests to fetch up to `count` older messages of the chat from the server.
+    ///
+    /// After configuration only the newest messages are fetched,
+    /// use this to load more history of a chat, e.g. when the user scrolls up.
+    /// New messages are announced by the usual `IncomingMsgBunch` and `MsgsChanged` events.
+    async fn load_older_messages_from_server(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        count: usize,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ChatId::new(chat_id)
+            .load_older_from_server(&ctx, count)
+            .await?)
+    }
+
+    /// Moves messages of the chat older than `seconds` to the archive folder on the server.
+    ///
+    /// 0 disables archiving for the chat.
+    /// Messages are only moved if the server has an archive folder.
+    async fn set_chat_server_archive_after(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        seconds: i64,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ChatId::new(chat_id)
+            .set_server_archive_after(&ctx, seconds)
+            .await?)
+    }
+
+    /// Returns the age in seconds after which messages of the chat are moved
+    /// to the archive folder on the server, 0 if disabled.
+    async fn get_chat_server_archive_after(&self, account_id: u32, chat_id: u32) -> Result<i64> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ChatId::new(chat_id).get_server_archive_after(&ctx).await?)
+    }
+
+    /// Sets the message retention policy of the chat.
+    ///
+    /// `keep_last` is the number of newest messages kept on the device,
+    /// `server_days` is the number of days after which messages are deleted from the server.
+    /// 0 disables the corresponding limit.
+    async fn set_chat_retention(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        keep_last: u32,
+        server_days: u32,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ChatId::new(chat_id)
+            .set_retention(
+                &ctx,
+                Retention {
+                    keep_last,
+                    server_days,
+                },
+            )
+            .await?)
+    }
+
+    /// Returns the message retention policy of the chat as `[keep_last, server_days]`.
+    async fn get_chat_retention(&self, account_id: u32, chat_id: u32) -> Result<(u32, u32)> {
+        let ctx = self.get_context(account_id).await?;
+        let retention = ChatId::new(chat_id).get_retention(&ctx).await?;
+        Ok((retention.keep_last, retention.server_days))
     }
 
     async fn get_first_unread_message_of_chat(
@@ -910,7 +1903,7 @@ async fn set_chat_mute_duration(
         duration: MuteDuration,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        chat::set_muted(&ctx, ChatId::new(chat_id), duration.try_into_core_type()?).await
+        Ok(chat::set_muted(&ctx, ChatId::new(chat_id), duration.try_into_core_type()?).await?)
     }
 
     /// Check whether the chat is currently muted (can be changed by set_chat_mute_duration()).
@@ -945,9 +1938,24 @@ async fn is_chat_muted(&self, account_id: u32, chat_id: u32) -> Result<bool> {
     /// This also happens for contact requests chats.
     ///
     /// One #DC_EVENT_MSGS_NOTICED event is emitted per modified chat.
+    ///
+    /// All messages are updated in a single database transaction,
+    /// so pass all presented messages at once instead of calling this for every message.
     async fn markseen_msgs(&self, account_id: u32, msg_ids: Vec<u32>) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        markseen_msgs(&ctx, msg_ids.into_iter().map(MsgId::new).collect()).await
+        Ok(markseen_msgs(&ctx, msg_ids.into_iter().map(MsgId::new).collect()).await?)
+    }
+
+    /// Returns the IDs of all messages of the thread the given message belongs to,
+    /// starting with the thread root.
+    async fn get_message_thread(&self, account_id: u32, msg_id: u32) -> Result<Vec<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(MsgId::new(msg_id)
+            .get_thread(&ctx)
+            .await?
+            .iter()
+            .map(|msg_id| msg_id.to_u32())
+            .collect())
     }
 
     async fn get_message_ids(
@@ -964,6 +1972,7 @@ async fn get_message_ids(
             MessageListOptions {
                 info_only,
                 add_daymarker,
+                corrected_order: false,
             },
         )
         .await?;
@@ -992,6 +2001,102 @@ async fn get_message_list_items(
             MessageListOptions {
                 info_only,
                 add_daymarker,
+                corrected_order: false,
+            },
+        )
+        .await?;
+        Ok(msg
+            .iter()
+            .map(|chat_item| (*chat_item).into())
+            .collect::<Vec<JSONRPCMessageListItem>>())
+    }
+
+    /// Returns a page of up to `limit` messages of `get_message_list_items()`
+    /// together with the loaded messages.
+    ///
+    /// Without `cursor` the page contains the newest messages,
+    /// pass the `nextCursor` of a page to get the older messages before it.
+    /// Fails with the `NotFound` error code if the message of the cursor was deleted,
+    /// the list should be loaded from the newest messages again then.
+    ///
+    /// This avoids transferring the whole list of large chats at once.
+    async fn get_message_list_page(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        cursor: Option<String>,
+        limit: u32,
+        info_only: bool,
+        add_daymarker: bool,
+    ) -> Result<JsonrpcMessageListPage> {
+        let ctx = self.get_context(account_id).await?;
+        if limit == 0 {
+            return Err(anyhow!("limit must be positive").into());
+        }
+        let cursor = match cursor {
+            Some(cursor) => Some(MsgId::new(
+                cursor
+                    .parse()
+                    .with_context(|| format!("Invalid cursor {cursor:?}"))?,
+            )),
+            None => None,
+        };
+        let items = get_chat_msgs_ex(
+            &ctx,
+            ChatId::new(chat_id),
+            MessageListOptions {
+                info_only,
+                add_daymarker,
+                corrected_order: false,
+            },
+        )
+        .await?;
+        let range = message_list_page(&items, cursor, limit as usize).ok_or_else(|| {
+            ErrorCode::NotFound.wrap(anyhow!("Cursor message not found in chat {chat_id}"))
+        })?;
+        let items = &items[range.clone()];
+
+        let mut messages = HashMap::new();
+        for item in items {
+            if let ChatItem::Message { msg_id } = item {
+                let msg_id = msg_id.to_u32();
+                messages.insert(msg_id, MessageLoadResult::load(&ctx, msg_id).await);
+            }
+        }
+        let next_cursor = if range.start > 0 {
+            items.iter().find_map(|item| match item {
+                ChatItem::Message { msg_id } => Some(msg_id.to_u32().to_string()),
+                ChatItem::DayMarker { .. } => None,
+            })
+        } else {
+            None
+        };
+        Ok(JsonrpcMessageListPage {
+            items: items.iter().map(|item| (*item).into()).collect(),
+            messages,
+            next_cursor,
+        })
+    }
+
+    /// Like `get_message_list_items()`, but messages delivered out of order
+    /// are sorted by the time they were sent once they are noticed or seen.
+    ///
+    /// Fresh messages are still sorted to the end of the chat, so they are not overlooked.
+    async fn get_corrected_message_list_items(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        info_only: bool,
+        add_daymarker: bool,
+    ) -> Result<Vec<JSONRPCMessageListItem>> {
+        let ctx = self.get_context(account_id).await?;
+        let msg = get_chat_msgs_ex(
+            &ctx,
+            ChatId::new(chat_id),
+            MessageListOptions {
+                info_only,
+                add_daymarker,
+                corrected_order: true,
             },
         )
         .await?;
@@ -1003,12 +2108,57 @@ async fn get_message_list_items(
 
     async fn get_message(&self, account_id: u32, message_id: u32) -> Result<MessageObject> {
         let ctx = self.get_context(account_id).await?;
-        MessageObject::from_message_id(&ctx, message_id).await
+        Ok(MessageObject::from_message_id(&ctx, message_id).await?)
     }
 
     async fn get_message_html(&self, account_id: u32, message_id: u32) -> Result<Option<String>> {
         let ctx = self.get_context(account_id).await?;
-        MsgId::new(message_id).get_html(&ctx).await
+        Ok(MsgId::new(message_id).get_html(&ctx).await?)
+    }
+
+    /// Returns the untruncated text of a message.
+    ///
+    /// Received texts that are too long are truncated with `[...]`,
+    /// this returns the full text without quotes and footers.
+    async fn get_message_full_text(&self, account_id: u32, message_id: u32) -> Result<String> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(MsgId::new(message_id).get_full_text(&ctx).await?)
+    }
+
+    /// Reports a received message as spam or abuse to the abuse address of the provider
+    /// or the one set in the `abuse_addr` config option.
+    ///
+    /// `category` is one of the `DC_REPORT_CATEGORY_*` constants, e.g. 1 for spam.
+    /// With `include_original`, the original message is attached;
+    /// if it is not stored, it is fetched from the server first.
+    async fn report_message(
+        &self,
+        account_id: u32,
+        message_id: u32,
+        category: u32,
+        include_original: bool,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        let category = ReportCategory::from_u32(category)
+            .with_context(|| format!("Unknown report category {category}"))?;
+        Ok(report::report_msg(&ctx, MsgId::new(message_id), category, include_original).await?)
+    }
+
+    async fn is_message_reported(&self, account_id: u32, message_id: u32) -> Result<bool> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(report::is_msg_reported(&ctx, MsgId::new(message_id)).await?)
+    }
+
+    /// Returns the media of a view-once message encoded as base64.
+    ///
+    /// For incoming messages the media is deleted afterwards,
+    /// so this succeeds only once per message.
+    async fn open_view_once(&self, account_id: u32, message_id: u32) -> Result<String> {
+        let ctx = self.get_context(account_id).await?;
+        let data = MsgId::new(message_id).open_view_once(&ctx).await?;
+
+        use base64::{engine::general_purpose, Engine as _};
+        Ok(general_purpose::STANDARD_NO_PAD.encode(data))
     }
 
     /// get multiple messages in one call,
@@ -1023,16 +2173,7 @@ async fn get_messages(
         let ctx = self.get_context(account_id).await?;
         let mut messages: HashMap<u32, MessageLoadResult> = HashMap::new();
         for message_id in message_ids {
-            let message_result = MessageObject::from_message_id(&ctx, message_id).await;
-            messages.insert(
-                message_id,
-                match message_result {
-                    Ok(message) => MessageLoadResult::Message(message),
-                    Err(error) => MessageLoadResult::LoadingError {
-                        error: format!("{error:#}"),
-                    },
-                },
-            );
+            messages.insert(message_id, MessageLoadResult::load(&ctx, message_id).await);
         }
         Ok(messages)
     }
@@ -1044,15 +2185,34 @@ async fn get_message_notification_info(
         message_id: u32,
     ) -> Result<MessageNotificationInfo> {
         let ctx = self.get_context(account_id).await?;
-        MessageNotificationInfo::from_msg_id(&ctx, MsgId::new(message_id)).await
+        Ok(MessageNotificationInfo::from_msg_id(&ctx, MsgId::new(message_id)).await?)
+    }
+
+    /// Returns the IDs of the messages whose notifications were suppressed
+    /// because of the `quiet_hours` setting, oldest first.
+    ///
+    /// Call `clear_suppressed_notifications()` after showing a summary.
+    async fn get_suppressed_notifications(&self, account_id: u32) -> Result<Vec<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        let msg_ids = notification::get_suppressed_notifications(&ctx).await?;
+        Ok(msg_ids.into_iter().map(|id| id.to_u32()).collect())
+    }
+
+    /// Forgets the messages returned by `get_suppressed_notifications()`.
+    async fn clear_suppressed_notifications(&self, account_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(notification::clear_suppressed_notifications(&ctx).await?)
     }
 
     /// Delete messages. The messages are deleted on the current device and
     /// on the IMAP server.
+    ///
+    /// All messages are deleted in a single database transaction,
+    /// so pass all messages to delete at once instead of calling this for every message.
     async fn delete_messages(&self, account_id: u32, message_ids: Vec<u32>) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
         let msgs: Vec<MsgId> = message_ids.into_iter().map(MsgId::new).collect();
-        delete_msgs(&ctx, &msgs).await
+        Ok(delete_msgs(&ctx, &msgs).await?)
     }
 
     /// Get an informational text for a single message. The text is multiline and may
@@ -1062,7 +2222,7 @@ async fn delete_messages(&self, account_id: u32, message_ids: Vec<u32>) -> Resul
     /// max. text returned by dc_msg_get_text() (about 30000 characters).
     async fn get_message_info(&self, account_id: u32, message_id: u32) -> Result<String> {
         let ctx = self.get_context(account_id).await?;
-        get_msg_info(&ctx, MsgId::new(message_id)).await
+        Ok(get_msg_info(&ctx, MsgId::new(message_id)).await?)
     }
 
     /// Asks the core to start downloading a message fully.
@@ -1078,7 +2238,7 @@ async fn get_message_info(&self, account_id: u32, message_id: u32) -> Result<Str
     /// To reflect these changes a @ref DC_EVENT_MSGS_CHANGED event will be emitted.
     async fn download_full_message(&self, account_id: u32, message_id: u32) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        MsgId::new(message_id).download_full(&ctx).await
+        Ok(MsgId::new(message_id).download_full(&ctx).await?)
     }
 
     /// Search messages containing the given query string.
@@ -1107,6 +2267,34 @@ async fn search_messages(
             .collect::<Vec<u32>>())
     }
 
+    /// Adds a tag to a message in the saved messages chat.
+    async fn add_saved_tag(&self, account_id: u32, msg_id: u32, tag: String) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(MsgId::new(msg_id).add_saved_tag(&ctx, &tag).await?)
+    }
+
+    /// Removes a tag from a message in the saved messages chat.
+    async fn remove_saved_tag(&self, account_id: u32, msg_id: u32, tag: String) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(MsgId::new(msg_id).remove_saved_tag(&ctx, &tag).await?)
+    }
+
+    /// Returns the tags of a message in the saved messages chat, sorted alphabetically.
+    async fn get_saved_tags(&self, account_id: u32, msg_id: u32) -> Result<Vec<String>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(MsgId::new(msg_id).get_saved_tags(&ctx).await?)
+    }
+
+    /// Returns the IDs of the saved messages tagged with `tag`, newest first.
+    async fn get_saved_by_tag(&self, account_id: u32, tag: String) -> Result<Vec<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        let messages = ctx.get_saved_by_tag(&tag).await?;
+        Ok(messages
+            .iter()
+            .map(|msg_id| msg_id.to_u32())
+            .collect::<Vec<u32>>())
+    }
+
     async fn message_ids_to_search_results(
         &self,
         account_id: u32,
@@ -1133,11 +2321,11 @@ async fn get_contact(&self, account_id: u32, contact_id: u32) -> Result<ContactO
         let ctx = self.get_context(account_id).await?;
         let contact_id = ContactId::new(contact_id);
 
-        ContactObject::try_from_dc_contact(
+        Ok(ContactObject::try_from_dc_contact(
             &ctx,
             deltachat::contact::Contact::get_by_id(&ctx, contact_id).await?,
         )
-        .await
+        .await?)
     }
 
     /// Add a single contact as a result of an explicit user action.
@@ -1151,9 +2339,7 @@ async fn create_contact(
     ) -> Result<u32> {
         let ctx = self.get_context(account_id).await?;
         if !may_be_valid_addr(&email) {
-            bail!(anyhow!(
-                "provided email address is not a valid email address"
-            ))
+            return Err(anyhow!("provided email address is not a valid email address").into());
         }
         let contact_id = Contact::create(&ctx, &name.unwrap_or_default(), &email).await?;
         Ok(contact_id.to_u32())
@@ -1163,19 +2349,30 @@ async fn create_contact(
     async fn create_chat_by_contact_id(&self, account_id: u32, contact_id: u32) -> Result<u32> {
         let ctx = self.get_context(account_id).await?;
         let contact = Contact::get_by_id(&ctx, ContactId::new(contact_id)).await?;
-        ChatId::create_for_contact(&ctx, contact.id)
-            .await
-            .map(|id| id.to_u32())
+        Ok(ChatId::create_for_contact(&ctx, contact.id).await?.to_u32())
     }
 
     async fn block_contact(&self, account_id: u32, contact_id: u32) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        Contact::block(&ctx, ContactId::new(contact_id)).await
+        Ok(Contact::block(&ctx, ContactId::new(contact_id)).await?)
     }
 
     async fn unblock_contact(&self, account_id: u32, contact_id: u32) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        Contact::unblock(&ctx, ContactId::new(contact_id)).await
+        Ok(Contact::unblock(&ctx, ContactId::new(contact_id)).await?)
+    }
+
+    /// Sets whether our last-seen status is shared with the contact.
+    ///
+    /// If not shared, the last-seen status of the contact is not shown in return.
+    async fn set_contact_share_last_seen(
+        &self,
+        account_id: u32,
+        contact_id: u32,
+        share: bool,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(Contact::set_share_last_seen(&ctx, ContactId::new(contact_id), share).await?)
     }
 
     async fn get_blocked_contacts(&self, account_id: u32) -> Result<Vec<ContactObject>> {
@@ -1194,6 +2391,50 @@ async fn get_blocked_contacts(&self, account_id: u32) -> Result<Vec<ContactObjec
         Ok(contacts)
     }
 
+    /// Returns the addresses of all blocked contacts as a list signed with the own key,
+    /// to be imported with `import_blocklist()` or published as a blocklist feed.
+    async fn export_blocklist(&self, account_id: u32) -> Result<String> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(blocklist::export_blocklist(&ctx).await?)
+    }
+
+    /// Blocks the addresses of a list exported by `export_blocklist()`
+    /// and returns the number of newly blocked contacts.
+    async fn import_blocklist(&self, account_id: u32, data: String) -> Result<usize> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(blocklist::import_blocklist(&ctx, &data).await?)
+    }
+
+    /// Subscribes to the blocklist served at the HTTPS `url`,
+    /// which must be exported by the contact `contact_id`, and returns the ID of the feed.
+    ///
+    /// The list is fetched once a day.
+    /// Messages from unknown senders on the list are not shown.
+    async fn add_blocklist_feed(
+        &self,
+        account_id: u32,
+        url: String,
+        contact_id: u32,
+    ) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(blocklist::add_blocklist_feed(&ctx, &url, ContactId::new(contact_id)).await?)
+    }
+
+    async fn get_blocklist_feeds(&self, account_id: u32) -> Result<Vec<JsonrpcBlocklistFeed>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(blocklist::get_blocklist_feeds(&ctx)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Unsubscribes from a blocklist.
+    async fn delete_blocklist_feed(&self, account_id: u32, feed_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(blocklist::delete_blocklist_feed(&ctx, feed_id).await?)
+    }
+
     async fn get_contact_ids(
         &self,
         account_id: u32,
@@ -1280,7 +2521,24 @@ async fn get_contact_encryption_info(
         contact_id: u32,
     ) -> Result<String> {
         let ctx = self.get_context(account_id).await?;
-        Contact::get_encrinfo(&ctx, ContactId::new(contact_id)).await
+        Ok(Contact::get_encrinfo(&ctx, ContactId::new(contact_id)).await?)
+    }
+
+    /// Get all Autocrypt keys ever seen for the address of a contact,
+    /// most recently seen first, to audit unexpected key changes.
+    async fn get_contact_key_history(
+        &self,
+        account_id: u32,
+        contact_id: u32,
+    ) -> Result<Vec<KeyHistoryEntryObject>> {
+        let ctx = self.get_context(account_id).await?;
+        let contact = Contact::get_by_id(&ctx, ContactId::new(contact_id)).await?;
+        Ok(contact
+            .get_key_history(&ctx)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
     }
 
     /// Check if an e-mail address belongs to a known and unblocked contact.
@@ -1298,10 +2556,55 @@ async fn lookup_contact_id_by_addr(
         Ok(contact_id.map(|id| id.to_u32()))
     }
 
+    /// Returns the ID of the contact with the given stable identifier, see `Contact.uuid`.
+    ///
+    /// Unlike contact IDs, stable identifiers survive backup export and import,
+    /// so bots should store them in their own databases.
+    async fn lookup_contact_id_by_uuid(
+        &self,
+        account_id: u32,
+        uuid: String,
+    ) -> Result<Option<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        let contact_id = Contact::lookup_id_by_uuid(&ctx, &uuid).await?;
+        Ok(contact_id.map(|id| id.to_u32()))
+    }
+
+    /// Returns the chats in which a contact was replaced because of an address change (AEAP)
+    /// and the chats which were skipped, newest first.
+    ///
+    /// If `contact_id` is given, only changes from or to this contact are returned.
+    async fn get_aeap_migrations(
+        &self,
+        account_id: u32,
+        contact_id: Option<u32>,
+    ) -> Result<Vec<JsonrpcAeapMigration>> {
+        let ctx = self.get_context(account_id).await?;
+        let migrations = aeap::get_aeap_migrations(&ctx, contact_id.map(ContactId::new)).await?;
+        Ok(migrations.into_iter().map(Into::into).collect())
+    }
+
+    /// Undoes a migration returned by `get_aeap_migrations()`,
+    /// putting the old contact back into the chat.
+    async fn undo_aeap_migration(&self, account_id: u32, migration_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(aeap::undo_aeap_migration(&ctx, migration_id).await?)
+    }
+
     // ---------------------------------------------
     //                   chat
     // ---------------------------------------------
 
+    /// Returns the ID of the chat with the given stable identifier, see `FullChat.uuid`.
+    ///
+    /// Unlike chat IDs, stable identifiers survive backup export and import,
+    /// so bots should store them in their own databases.
+    async fn lookup_chat_id_by_uuid(&self, account_id: u32, uuid: String) -> Result<Option<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        let chat_id = ChatId::lookup_by_uuid(&ctx, &uuid).await?;
+        Ok(chat_id.map(|id| id.to_u32()))
+    }
+
     /// Returns all message IDs of the given types in a chat.
     /// Typically used to show a gallery.
     ///
@@ -1333,6 +2636,38 @@ async fn get_chat_media(
         Ok(media.iter().map(|msg_id| msg_id.to_u32()).collect())
     }
 
+    /// Returns media message ids grouped by month, oldest first,
+    /// for gallery views with section headers.
+    ///
+    /// An empty list of `message_types` returns all media types.
+    /// `since` and `until` optionally restrict the timestamps of the messages,
+    /// `until` is exclusive.
+    ///
+    /// Setting `chat_id` to `None` (`null` in typescript) means get messages with media
+    /// from any chat of the currently used account.
+    async fn get_chat_media_by_month(
+        &self,
+        account_id: u32,
+        chat_id: Option<u32>,
+        message_types: Vec<MessageViewtype>,
+        since: Option<i64>,
+        until: Option<i64>,
+    ) -> Result<Vec<MediaMonth>> {
+        let ctx = self.get_context(account_id).await?;
+
+        let chat_id = match chat_id {
+            None | Some(0) => None,
+            Some(id) => Some(ChatId::new(id)),
+        };
+        let options = chat::MediaListOptions {
+            viewtypes: message_types.into_iter().map(Into::into).collect(),
+            since,
+            until,
+        };
+        let months = chat::get_chat_media_ex(&ctx, chat_id, options).await?;
+        Ok(months.into_iter().map(Into::into).collect())
+    }
+
     /// Search next/previous message based on a given message and a list of types.
     /// Typically used to implement the "next" and "previous" buttons
     /// in a gallery or in a media player.
@@ -1382,6 +2717,23 @@ async fn get_neighboring_chat_media(
     //                   backup
     // ---------------------------------------------
 
+    /// Exports all messages of a chat as HTML page with copies of their files into `dest_dir`
+    /// and returns the path of the page.
+    ///
+    /// `dest_dir` is created if it does not exist, it must be empty otherwise.
+    async fn export_chat_html(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        dest_dir: String,
+    ) -> Result<String> {
+        let ctx = self.get_context(account_id).await?;
+        self.ensure_path_allowed(&ctx, &dest_dir).await?;
+        let path =
+            chat_export::export_chat_html(&ctx, ChatId::new(chat_id), Path::new(&dest_dir)).await?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
     async fn export_backup(
         &self,
         account_id: u32,
@@ -1389,13 +2741,14 @@ async fn export_backup(
         passphrase: Option<String>,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        imex::imex(
+        self.ensure_path_allowed(&ctx, &destination).await?;
+        Ok(imex::imex(
             &ctx,
             imex::ImexMode::ExportBackup,
             destination.as_ref(),
             passphrase,
         )
-        .await
+        .await?)
     }
 
     async fn import_backup(
@@ -1405,13 +2758,14 @@ async fn import_backup(
         passphrase: Option<String>,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        imex::imex(
+        self.ensure_path_allowed(&ctx, &path).await?;
+        Ok(imex::imex(
             &ctx,
             imex::ImexMode::ImportBackup,
             path.as_ref(),
             passphrase,
         )
-        .await
+        .await?)
     }
 
     /// Offers a backup for remote devices to retrieve.
@@ -1438,7 +2792,7 @@ async fn provide_backup(&self, account_id: u32) -> Result<()> {
         })
         .await;
 
-        provider.await
+        Ok(provider.await?)
     }
 
     /// Returns the text of the QR code for the running [`CommandApi::provide_backup`].
@@ -1451,7 +2805,7 @@ async fn provide_backup(&self, account_id: u32) -> Result<()> {
     /// ready.
     async fn get_backup_qr(&self, account_id: u32) -> Result<String> {
         let qr = self.inner_get_backup_qr(account_id).await?;
-        qr::format_backup(&qr)
+        Ok(qr::format_backup(&qr)?)
     }
 
     /// Returns the rendered QR code for the running [`CommandApi::provide_backup`].
@@ -1467,7 +2821,7 @@ async fn get_backup_qr(&self, account_id: u32) -> Result<String> {
     async fn get_backup_qr_svg(&self, account_id: u32) -> Result<String> {
         let ctx = self.get_context(account_id).await?;
         let qr = self.inner_get_backup_qr(account_id).await?;
-        generate_backup_qr(&ctx, &qr).await
+        Ok(generate_backup_qr(&ctx, &qr).await?)
     }
 
     /// Gets a backup from a remote provider.
@@ -1490,7 +2844,13 @@ async fn get_backup(&self, account_id: u32, qr_text: String) -> Result<()> {
     /// Indicate that the network likely has come back.
     /// or just that the network conditions might have changed
     async fn maybe_network(&self) -> Result<()> {
-        self.accounts.read().await.maybe_network().await;
+        if let Some(id) = self.single_account {
+            if let Some(ctx) = self.get_account(id).await {
+                ctx.maybe_network().await;
+            }
+        } else {
+            self.accounts.read().await.maybe_network().await;
+        }
         Ok(())
     }
 
@@ -1524,7 +2884,40 @@ async fn get_connectivity(&self, account_id: u32) -> Result<u32> {
     /// and the improvement instantly reaches all UIs.
     async fn get_connectivity_html(&self, account_id: u32) -> Result<String> {
         let ctx = self.get_context(account_id).await?;
-        ctx.get_connectivity_html().await
+        Ok(ctx.get_connectivity_html().await?)
+    }
+
+    /// Sets the push notification token of this device,
+    /// e.g. the token of a notification proxy service.
+    ///
+    /// The token is registered on the server in the background
+    /// if the server supports push notifications.
+    /// An empty token unregisters the device from push notifications.
+    async fn set_push_device_token(&self, account_id: u32, token: String) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ctx.set_push_device_token(&token).await?)
+    }
+
+    /// Returns the devices connected to the account which are not revoked.
+    ///
+    /// The list is stored on the server using the IMAP METADATA extension
+    /// and is updated about once a day.
+    /// Moreover, devices are added when synchronization messages are received from them.
+    async fn get_own_devices(&self, account_id: u32) -> Result<Vec<JsonrpcDevice>> {
+        let ctx = self.get_context(account_id).await?;
+        let devices = ctx.get_own_devices().await?;
+        Ok(devices.into_iter().map(Into::into).collect())
+    }
+
+    /// Revokes another own device and returns the setup code for the new key.
+    ///
+    /// Synchronization messages from the device are ignored from now on
+    /// and a device message warns about the removed device.
+    /// The account key is replaced and sent to the own devices in an Autocrypt Setup Message,
+    /// the returned setup code has to be entered on the remaining devices.
+    async fn revoke_own_device(&self, account_id: u32, device_id: String) -> Result<String> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ctx.revoke_own_device(&device_id).await?)
     }
 
     // ---------------------------------------------
@@ -1565,8 +2958,9 @@ async fn send_webxdc_status_update(
         description: String,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        ctx.send_webxdc_status_update(MsgId::new(instance_msg_id), &update_str, &description)
-            .await
+        Ok(ctx
+            .send_webxdc_status_update(MsgId::new(instance_msg_id), &update_str, &description)
+            .await?)
     }
 
     async fn get_webxdc_status_updates(
@@ -1576,11 +2970,12 @@ async fn get_webxdc_status_updates(
         last_known_serial: u32,
     ) -> Result<String> {
         let ctx = self.get_context(account_id).await?;
-        ctx.get_webxdc_status_updates(
-            MsgId::new(instance_msg_id),
-            StatusUpdateSerial::new(last_known_serial),
-        )
-        .await
+        Ok(ctx
+            .get_webxdc_status_updates(
+                MsgId::new(instance_msg_id),
+                StatusUpdateSerial::new(last_known_serial),
+            )
+            .await?)
     }
 
     /// Get info from a webxdc message
@@ -1590,7 +2985,17 @@ async fn get_webxdc_info(
         instance_msg_id: u32,
     ) -> Result<WebxdcMessageInfo> {
         let ctx = self.get_context(account_id).await?;
-        WebxdcMessageInfo::get_for_message(&ctx, MsgId::new(instance_msg_id)).await
+        Ok(WebxdcMessageInfo::get_for_message(&ctx, MsgId::new(instance_msg_id)).await?)
+    }
+
+    /// Mark the info messages of a webxdc instance as seen,
+    /// resetting `unreadInfoCnt` of the webxdc info.
+    /// UIs should call this when the webxdc is opened.
+    async fn mark_webxdc_infos_seen(&self, account_id: u32, instance_msg_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(ctx
+            .mark_webxdc_infos_seen(MsgId::new(instance_msg_id))
+            .await?)
     }
 
     /// Get blob encoded as base64 from a webxdc message
@@ -1616,6 +3021,9 @@ async fn get_webxdc_blob(
     /// however, they will be flagged as such (dc_msg_is_forwarded() is set).
     ///
     /// Original sender, info-state and webxdc updates are not forwarded on purpose.
+    ///
+    /// The SMTP loop is interrupted and `MsgsChanged` is emitted only once for all messages,
+    /// however, each message is still written to the database separately.
     async fn forward_messages(
         &self,
         account_id: u32,
@@ -1624,7 +3032,7 @@ async fn forward_messages(
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
         let message_ids: Vec<MsgId> = message_ids.into_iter().map(MsgId::new).collect();
-        forward_msgs(&ctx, &message_ids, ChatId::new(chat_id)).await
+        Ok(forward_msgs(&ctx, &message_ids, ChatId::new(chat_id)).await?)
     }
 
     async fn send_sticker(
@@ -1634,6 +3042,7 @@ async fn send_sticker(
         sticker_path: String,
     ) -> Result<u32> {
         let ctx = self.get_context(account_id).await?;
+        self.ensure_path_allowed(&ctx, &sticker_path).await?;
 
         let mut msg = Message::new(Viewtype::Sticker);
         msg.set_file(&sticker_path, None);
@@ -1659,48 +3068,119 @@ async fn send_reaction(
         Ok(message_id.to_u32())
     }
 
+    /// Offers a call in a 1:1 chat.
+    ///
+    /// The payload, typically a WebRTC session description,
+    /// is passed to the recipient with the `IncomingCallOffer` event.
+    /// Returns the ID of the hidden offer message identifying the call.
+    async fn send_call_offer(&self, account_id: u32, chat_id: u32, payload: String) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        let msg_id = calls::send_call_offer(&ctx, ChatId::new(chat_id), &payload).await?;
+        Ok(msg_id.to_u32())
+    }
+
+    /// Answers a call offered with the `IncomingCallOffer` event.
+    async fn send_call_answer(
+        &self,
+        account_id: u32,
+        call_id: u32,
+        payload: String,
+    ) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        let msg_id = calls::send_call_answer(&ctx, MsgId::new(call_id), &payload).await?;
+        Ok(msg_id.to_u32())
+    }
+
+    /// Sends an ICE candidate to the peer of a call.
+    ///
+    /// Candidates passed within 500 milliseconds are sent together in one message.
+    async fn send_call_ice(&self, account_id: u32, call_id: u32, payload: String) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        calls::send_call_ice(&ctx, MsgId::new(call_id), &payload).await?;
+        Ok(())
+    }
+
+    /// Adds a local record of a call to a 1:1 chat, a message of view type `Call`.
+    ///
+    /// `duration` is the duration of an accepted call in milliseconds,
+    /// `timestamp` the start of the call.
+    async fn add_call_record(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        incoming: bool,
+        state: CallState,
+        duration: i32,
+        timestamp: i64,
+    ) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        let msg_id = calls::add_call_record(
+            &ctx,
+            ChatId::new(chat_id),
+            incoming,
+            state.into(),
+            duration,
+            timestamp,
+        )
+        .await?;
+        Ok(msg_id.to_u32())
+    }
+
     async fn send_msg(&self, account_id: u32, chat_id: u32, data: MessageData) -> Result<u32> {
         let ctx = self.get_context(account_id).await?;
-        let mut message = Message::new(if let Some(viewtype) = data.viewtype {
-            viewtype.into()
-        } else if data.file.is_some() {
-            Viewtype::File
-        } else {
-            Viewtype::Text
-        });
-        if data.text.is_some() {
-            message.set_text(data.text);
-        }
-        if data.html.is_some() {
-            message.set_html(data.html);
-        }
-        if data.override_sender_name.is_some() {
-            message.set_override_sender_name(data.override_sender_name);
-        }
-        if let Some(file) = data.file {
-            message.set_file(file, None);
-        }
-        if let Some((latitude, longitude)) = data.location {
-            message.set_location(latitude, longitude);
-        }
-        if let Some(id) = data.quoted_message_id {
-            message
-                .set_quote(
-                    &ctx,
-                    Some(
-                        &Message::load_from_db(&ctx, MsgId::new(id))
-                            .await
-                            .context("message to quote could not be loaded")?,
-                    ),
-                )
-                .await?;
-        }
+        self.ensure_msg_data_allowed(&ctx, &data).await?;
+        let mut message = data.create_message(&ctx).await?;
         let msg_id = chat::send_msg(&ctx, ChatId::new(chat_id), &mut message)
             .await?
             .to_u32();
         Ok(msg_id)
     }
 
+    /// Offers the file of the message for direct peer-to-peer download instead of attaching it,
+    /// e.g. if it exceeds the maximum message size.
+    ///
+    /// Sends a small text message carrying a ticket,
+    /// the recipients download the file with `download_file_p2p()` while IO is running.
+    /// Requires the `p2p_file_transfer` config to be enabled.
+    async fn send_file_p2p(&self, account_id: u32, chat_id: u32, data: MessageData) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        self.ensure_msg_data_allowed(&ctx, &data).await?;
+        let mut message = data.create_message(&ctx).await?;
+        let msg_id = peer_transfer::send_file_p2p(&ctx, ChatId::new(chat_id), &mut message)
+            .await?
+            .to_u32();
+        Ok(msg_id)
+    }
+
+    /// Downloads a file offered peer-to-peer, see `MessageObject.p2pFileName`.
+    ///
+    /// Only succeeds while the sender is online, reports progress with `P2pFileProgress` events.
+    /// Afterwards, the file is available as `MessageObject.file`.
+    async fn download_file_p2p(&self, account_id: u32, message_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(peer_transfer::download_file_p2p(&ctx, MsgId::new(message_id)).await?)
+    }
+
+    /// Sends a message without waiting for it to be rendered and encrypted.
+    ///
+    /// Returns the ID of the message, which is added to the chat immediately.
+    /// If rendering fails later, a `MsgFailed` event is emitted.
+    /// This improves the perceived latency compared to `send_msg()` and `misc_send_text_message()`.
+    async fn send_msg_deferred(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        data: MessageData,
+    ) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        self.ensure_msg_data_allowed(&ctx, &data).await?;
+        let mut message = data.create_message(&ctx).await?;
+        let msg_id = chat::send_msg_deferred(&ctx, ChatId::new(chat_id), &mut message)
+            .await?
+            .to_u32();
+        Ok(msg_id)
+    }
+
     /// Checks if messages can be sent to a given chat.
     async fn can_send(&self, account_id: u32, chat_id: u32) -> Result<bool> {
         let ctx = self.get_context(account_id).await?;
@@ -1710,6 +3190,198 @@ async fn can_send(&self, account_id: u32, chat_id: u32) -> Result<bool> {
         Ok(can_send)
     }
 
+    // ---------------------------------------------
+    //                    blobs
+    // ---------------------------------------------
+
+    /// Starts uploading a file to the blob directory in chunks.
+    ///
+    /// This allows clients which do not share the filesystem with the server
+    /// to send large files. Append the content with `blob_upload_append()`
+    /// and call `blob_upload_finish()` to get the path to use e.g. in `MessageData.file`.
+    ///
+    /// At most 16 uploads can be unfinished at the same time per account.
+    /// Uploads which are not appended to for an hour are cancelled and their files deleted.
+    ///
+    /// Returns the upload ID.
+    async fn blob_upload_start(&self, account_id: u32, filename: String) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        let filename = sanitize_filename::sanitize(
+            Path::new(&filename)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default(),
+        );
+        let (stem, ext) = match filename.split_once('.') {
+            Some((stem, ext)) => (stem.to_string(), format!(".{ext}")),
+            None => (filename, String::new()),
+        };
+        let stem = if stem.is_empty() { "file" } else { &stem };
+        let file = tempfile::Builder::new()
+            .prefix(&format!("{stem}-"))
+            .suffix(&ext)
+            .tempfile_in(ctx.get_blobdir())
+            .context("failed to create upload file")?;
+
+        let id = self
+            .with_state(account_id, |state| {
+                let now = Instant::now();
+                state.expire_blob_uploads(now);
+                ensure!(
+                    state.blob_uploads.len() < MAX_BLOB_UPLOADS,
+                    "too many unfinished uploads, at most {MAX_BLOB_UPLOADS} are allowed"
+                );
+                let id = state.next_blob_upload_id;
+                state.next_blob_upload_id += 1;
+                state.blob_uploads.insert(
+                    id,
+                    BlobUpload {
+                        file,
+                        last_used: now,
+                    },
+                );
+                Ok(id)
+            })
+            .await?;
+        Ok(id)
+    }
+
+    /// Appends base64 encoded `data` to an upload started with `blob_upload_start()`.
+    ///
+    /// Fails without appending anything if the file would exceed the maximum upload size
+    /// of the server, 512 MiB by default.
+    async fn blob_upload_append(
+        &self,
+        account_id: u32,
+        upload_id: u32,
+        data: String,
+    ) -> Result<()> {
+        use base64::{engine::general_purpose, Engine as _};
+        use tokio::io::AsyncWriteExt;
+
+        let data = general_purpose::STANDARD
+            .decode(data)
+            .context("invalid base64 data")?;
+        let path = self
+            .with_state(account_id, |state| {
+                let now = Instant::now();
+                state.expire_blob_uploads(now);
+                state.blob_uploads.get_mut(&upload_id).map(|upload| {
+                    upload.last_used = now;
+                    upload.file.path().to_path_buf()
+                })
+            })
+            .await
+            .with_context(|| format!("upload {upload_id} not found"))?;
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).await?;
+        let size = file.metadata().await?.len();
+        if size.saturating_add(data.len() as u64) > self.max_blob_upload_size {
+            return Err(anyhow!(
+                "upload {upload_id} exceeds the maximum size of {} bytes",
+                self.max_blob_upload_size
+            )
+            .into());
+        }
+        file.write_all(&data).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Finishes an upload started with `blob_upload_start()`.
+    ///
+    /// Returns the path of the uploaded file in the blob directory.
+    async fn blob_upload_finish(&self, account_id: u32, upload_id: u32) -> Result<String> {
+        let upload = self
+            .with_state(account_id, |state| {
+                state.expire_blob_uploads(Instant::now());
+                state.blob_uploads.remove(&upload_id)
+            })
+            .await
+            .with_context(|| format!("upload {upload_id} not found"))?;
+        let (_file, path) = upload.file.keep().context("failed to keep uploaded file")?;
+        Ok(path
+            .into_os_string()
+            .into_string()
+            .map_err(|path| anyhow!("invalid upload path {:?}", path))?)
+    }
+
+    /// Reads up to `length` bytes starting at `offset` from a file in the blob directory.
+    ///
+    /// `path` may be an absolute path or start with `$BLOBDIR/`,
+    /// e.g. the `file` of a message.
+    /// At most 4 MiB are returned at once.
+    /// Returns the base64 encoded data, which is empty at the end of the file.
+    async fn blob_read_range(
+        &self,
+        account_id: u32,
+        path: String,
+        offset: u64,
+        length: u64,
+    ) -> Result<String> {
+        let ctx = self.get_context(account_id).await?;
+        let blobdir = ctx.get_blobdir();
+        let name = get_blob_name(blobdir, &path)?;
+        Ok(read_blob_range(&blobdir.join(name), offset, length).await?)
+    }
+
+    /// Returns a URL to retrieve a file of the blob directory,
+    /// e.g. to display images in web-based frontends without a shared filesystem.
+    ///
+    /// If the server provides an HTTP endpoint for blobs, an HTTP URL is returned.
+    /// Otherwise a `dc-blob://` URL is returned which can be resolved with `read_blob_url()`.
+    /// The URL contains a token protecting the blobs of the account,
+    /// it is valid until the server is restarted.
+    ///
+    /// `path` may be an absolute path or start with `$BLOBDIR/`.
+    async fn get_blob_url(&self, account_id: u32, path: String) -> Result<String> {
+        let ctx = self.get_context(account_id).await?;
+        let name = get_blob_name(ctx.get_blobdir(), &path)?
+            .to_str()
+            .context("blob name is not valid UTF-8")?;
+        let token = self
+            .with_state(account_id, |state| state.blob_token.clone())
+            .await;
+        let base = match &self.blob_http_base {
+            Some(base) => format!("{base}/blobs/"),
+            None => BLOB_URL_SCHEME.to_string(),
+        };
+        Ok(format!(
+            "{base}{account_id}/{token}/{}",
+            utf8_percent_encode(name, NON_ALPHANUMERIC)
+        ))
+    }
+
+    /// Reads up to `length` bytes starting at `offset` from a blob URL returned by `get_blob_url()`.
+    ///
+    /// At most 4 MiB are returned at once.
+    /// Returns the base64 encoded data, which is empty at the end of the file.
+    async fn read_blob_url(&self, url: String, offset: u64, length: u64) -> Result<String> {
+        let rest = url
+            .strip_prefix(BLOB_URL_SCHEME)
+            .or_else(|| {
+                url.strip_prefix(self.blob_http_base.as_deref()?)?
+                    .strip_prefix("/blobs/")
+            })
+            .context("not a blob URL")?;
+        let mut parts = rest.splitn(3, '/');
+        let (account_id, token, name) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(account_id), Some(token), Some(name)) => (account_id, token, name),
+            _ => return Err(anyhow!("invalid blob URL").into()),
+        };
+        let account_id = account_id.parse().context("invalid account ID")?;
+        let path = self
+            .get_blob_path(
+                account_id,
+                token,
+                &percent_decode_str(name)
+                    .decode_utf8()
+                    .context("invalid UTF-8 in blob URL")?,
+            )
+            .await?;
+        Ok(read_blob_range(&path, offset, length).await?)
+    }
+
     // ---------------------------------------------
     //           functions for the composer
     //    the composer is the message input field
@@ -1717,7 +3389,7 @@ async fn can_send(&self, account_id: u32, chat_id: u32) -> Result<bool> {
 
     async fn remove_draft(&self, account_id: u32, chat_id: u32) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
-        ChatId::new(chat_id).set_draft(&ctx, None).await
+        Ok(ChatId::new(chat_id).set_draft(&ctx, None).await?)
     }
 
     ///  Get draft for a chat, if any.
@@ -1734,9 +3406,150 @@ async fn get_draft(&self, account_id: u32, chat_id: u32) -> Result<Option<Messag
 
     async fn send_videochat_invitation(&self, account_id: u32, chat_id: u32) -> Result<u32> {
         let ctx = self.get_context(account_id).await?;
-        chat::send_videochat_invitation(&ctx, ChatId::new(chat_id))
-            .await
-            .map(|msg_id| msg_id.to_u32())
+        Ok(chat::send_videochat_invitation(&ctx, ChatId::new(chat_id))
+            .await?
+            .to_u32())
+    }
+
+    /// Sends a videochat invitation on the given webrtc instance,
+    /// e.g. one returned by `get_videochat_instances()`.
+    async fn send_videochat_invitation_with_instance(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        instance: String,
+    ) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(
+            chat::send_videochat_invitation_ex(&ctx, ChatId::new(chat_id), &instance)
+                .await?
+                .to_u32(),
+        )
+    }
+
+    /// Sends images and videos as an album, the caption is added to the first message.
+    ///
+    /// Returns the IDs of the messages sent, which share the same `albumId`.
+    async fn send_album(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        files: Vec<String>,
+        caption: Option<String>,
+    ) -> Result<Vec<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        for file in &files {
+            self.ensure_path_allowed(&ctx, file).await?;
+        }
+        let msg_ids = chat::send_album(&ctx, ChatId::new(chat_id), &files, caption).await?;
+        Ok(msg_ids.iter().map(|msg_id| msg_id.to_u32()).collect())
+    }
+
+    /// Returns the messages of the album the given message belongs to, in the order they were sent.
+    async fn get_album_msgs(&self, account_id: u32, msg_id: u32) -> Result<Vec<u32>> {
+        let ctx = self.get_context(account_id).await?;
+        let msg_ids = chat::get_album_msgs(&ctx, MsgId::new(msg_id)).await?;
+        Ok(msg_ids.iter().map(|msg_id| msg_id.to_u32()).collect())
+    }
+
+    /// Returns the configured webrtc instances,
+    /// `webrtc_instance` first followed by the lines of `webrtc_instances`.
+    async fn get_videochat_instances(&self, account_id: u32) -> Result<Vec<String>> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(chat::get_videochat_instances(&ctx).await?)
+    }
+
+    /// Returns all canned responses ordered by title.
+    async fn get_canned_responses(&self, account_id: u32) -> Result<Vec<JsonrpcCannedResponse>> {
+        let ctx = self.get_context(account_id).await?;
+        let responses = canned_response::get_canned_responses(&ctx).await?;
+        Ok(responses
+            .into_iter()
+            .map(|response| JsonrpcCannedResponse::from_canned_response(&ctx, response))
+            .collect())
+    }
+
+    /// Adds a canned response and returns its ID.
+    ///
+    /// Titles and texts are synchronized to other devices, files are not.
+    async fn add_canned_response(
+        &self,
+        account_id: u32,
+        title: String,
+        text: String,
+        file: Option<String>,
+    ) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        if let Some(file) = &file {
+            self.ensure_path_allowed(&ctx, file).await?;
+        }
+        Ok(canned_response::add_canned_response(&ctx, &title, &text, file.as_deref()).await?)
+    }
+
+    /// Saves text and file of a message as canned response and returns its ID.
+    async fn save_msg_as_canned_response(
+        &self,
+        account_id: u32,
+        msg_id: u32,
+        title: String,
+    ) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(canned_response::save_msg_as_canned_response(&ctx, MsgId::new(msg_id), &title).await?)
+    }
+
+    async fn update_canned_response(
+        &self,
+        account_id: u32,
+        canned_response_id: u32,
+        title: String,
+        text: String,
+        file: Option<String>,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        if let Some(file) = &file {
+            self.ensure_path_allowed(&ctx, file).await?;
+        }
+        Ok(canned_response::update_canned_response(
+            &ctx,
+            canned_response_id,
+            &title,
+            &text,
+            file.as_deref(),
+        )
+        .await?)
+    }
+
+    async fn delete_canned_response(&self, account_id: u32, canned_response_id: u32) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(canned_response::delete_canned_response(&ctx, canned_response_id).await?)
+    }
+
+    /// Sets a canned response as draft of a chat, so it can be edited before sending.
+    async fn set_draft_from_canned_response(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        canned_response_id: u32,
+    ) -> Result<()> {
+        let ctx = self.get_context(account_id).await?;
+        let mut draft = canned_response::canned_response_to_msg(&ctx, canned_response_id).await?;
+        Ok(ChatId::new(chat_id)
+            .set_draft(&ctx, Some(&mut draft))
+            .await?)
+    }
+
+    async fn send_canned_response(
+        &self,
+        account_id: u32,
+        chat_id: u32,
+        canned_response_id: u32,
+    ) -> Result<u32> {
+        let ctx = self.get_context(account_id).await?;
+        Ok(
+            canned_response::send_canned_response(&ctx, ChatId::new(chat_id), canned_response_id)
+                .await?
+                .to_u32(),
+        )
     }
 
     // ---------------------------------------------
@@ -1752,10 +3565,10 @@ async fn misc_get_sticker_folder(&self, account_id: u32) -> Result<String> {
             .context("account folder not found")?;
         let sticker_folder_path = account_folder.join("stickers");
         fs::create_dir_all(&sticker_folder_path).await?;
-        sticker_folder_path
+        Ok(sticker_folder_path
             .to_str()
             .map(|s| s.to_owned())
-            .context("path conversion to string failed")
+            .context("path conversion to string failed")?)
     }
 
     /// save a sticker to a collection/folder in the account's sticker folder
@@ -1767,19 +3580,16 @@ async fn misc_save_sticker(
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
         let message = Message::load_from_db(&ctx, MsgId::new(msg_id)).await?;
-        ensure!(
-            message.get_viewtype() == Viewtype::Sticker,
-            "message {} is not a sticker",
-            msg_id
-        );
+        if message.get_viewtype() != Viewtype::Sticker {
+            return Err(anyhow!("message {} is not a sticker", msg_id).into());
+        }
         let account_folder = ctx
             .get_dbfile()
             .parent()
             .context("account folder not found")?;
-        ensure!(
-            is_sanitized(&collection),
-            "illegal characters in collection name"
-        );
+        if !is_sanitized(&collection) {
+            return Err(anyhow!("illegal characters in collection name").into());
+        }
         let destination_path = account_folder.join("stickers").join(collection);
         fs::create_dir_all(&destination_path).await?;
         let file = message.get_file(&ctx).context("no file")?;
@@ -1869,6 +3679,9 @@ async fn misc_send_msg(
         quoted_message_id: Option<u32>,
     ) -> Result<(u32, MessageObject)> {
         let ctx = self.get_context(account_id).await?;
+        if let Some(file) = &file {
+            self.ensure_path_allowed(&ctx, file).await?;
+        }
         let mut message = Message::new(if file.is_some() {
             Viewtype::File
         } else {
@@ -1915,6 +3728,9 @@ async fn misc_set_draft(
         quoted_message_id: Option<u32>,
     ) -> Result<()> {
         let ctx = self.get_context(account_id).await?;
+        if let Some(file) = &file {
+            self.ensure_path_allowed(&ctx, file).await?;
+        }
         let mut draft = Message::new(if file.is_some() {
             Viewtype::File
         } else {
@@ -1939,7 +3755,9 @@ async fn misc_set_draft(
                 .await?;
         }
 
-        ChatId::new(chat_id).set_draft(&ctx, Some(&mut draft)).await
+        Ok(ChatId::new(chat_id)
+            .set_draft(&ctx, Some(&mut draft))
+            .await?)
     }
 }
 
@@ -1991,3 +3809,55 @@ enum ProviderQr {
     /// There is a provider and QR code.
     Ready(Qr),
 }
+
+/// Returns true if `name` is a file name without directory components.
+fn is_blob_name(name: &Path) -> bool {
+    let mut components = name.components();
+    matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none()
+}
+
+/// Returns the name of a blob given as an absolute path or as a path starting with `$BLOBDIR/`.
+fn get_blob_name<'a>(blobdir: &Path, path: &'a str) -> anyhow::Result<&'a Path> {
+    let name = match path.strip_prefix("$BLOBDIR/") {
+        Some(name) => Path::new(name),
+        None => Path::new(path)
+            .strip_prefix(blobdir)
+            .context("file is not in the blob directory")?,
+    };
+    ensure!(is_blob_name(name), "invalid blob name {}", name.display());
+    Ok(name)
+}
+
+/// Reads up to `length` bytes starting at `offset` from a file, returning base64 encoded data.
+async fn read_blob_range(path: &Path, offset: u64, length: u64) -> anyhow::Result<String> {
+    use base64::{engine::general_purpose, Engine as _};
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let mut file = fs::File::open(path).await?;
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+    let mut data = Vec::new();
+    file.take(length.min(MAX_BLOB_READ_SIZE))
+        .read_to_end(&mut data)
+        .await?;
+    Ok(general_purpose::STANDARD.encode(data))
+}
+
+#[cfg(test)]
+#[test]
+fn test_expire_blob_uploads() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut state = AccountState::default();
+    let start = Instant::now();
+    for (id, last_used) in [(1, start), (2, start + BLOB_UPLOAD_TIMEOUT)] {
+        let file = NamedTempFile::new_in(dir.path()).unwrap();
+        state
+            .blob_uploads
+            .insert(id, BlobUpload { file, last_used });
+    }
+    let stale_path = state.blob_uploads[&1].file.path().to_path_buf();
+
+    state.expire_blob_uploads(start + BLOB_UPLOAD_TIMEOUT);
+    assert_eq!(state.blob_uploads.len(), 1);
+    assert!(state.blob_uploads.contains_key(&2));
+    assert!(!stale_path.exists());
+}
@@ -0,0 +1,108 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+/// Request counters of a connection, returned by `get_rpc_stats()`.
+///
+/// Servers limiting the number of concurrently handled requests
+/// update them with [`RpcStats::request_queued`] and [`RpcStats::request_started`].
+#[derive(Clone, Debug, Default)]
+pub struct RpcStats(Arc<RpcStatsInner>);
+
+#[derive(Debug, Default)]
+struct RpcStatsInner {
+    max_concurrent_requests: AtomicUsize,
+    waiting_requests: AtomicUsize,
+    running_requests: AtomicUsize,
+    total_requests: AtomicU64,
+}
+
+impl RpcStats {
+    /// Sets the maximum number of concurrently handled requests, 0 if unlimited.
+    pub fn set_max_concurrent_requests(&self, max: usize) {
+        self.0.max_concurrent_requests.store(max, Ordering::Relaxed);
+    }
+
+    /// Counts a request waiting to be handled.
+    ///
+    /// The request stops waiting when the returned guard is dropped.
+    pub fn request_queued(&self) -> WaitingRequest {
+        self.0.waiting_requests.fetch_add(1, Ordering::Relaxed);
+        WaitingRequest(self.clone())
+    }
+
+    /// Counts a request being handled.
+    ///
+    /// The request is finished when the returned guard is dropped.
+    pub fn request_started(&self) -> RunningRequest {
+        self.0.running_requests.fetch_add(1, Ordering::Relaxed);
+        self.0.total_requests.fetch_add(1, Ordering::Relaxed);
+        RunningRequest(self.clone())
+    }
+
+    pub(crate) fn snapshot(&self) -> JsonrpcRpcStats {
+        JsonrpcRpcStats {
+            max_concurrent_requests: self.0.max_concurrent_requests.load(Ordering::Relaxed),
+            waiting_requests: self.0.waiting_requests.load(Ordering::Relaxed),
+            running_requests: self.0.running_requests.load(Ordering::Relaxed),
+            total_requests: self.0.total_requests.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Guard of a request waiting to be handled, see [`RpcStats::request_queued`].
+#[derive(Debug)]
+pub struct WaitingRequest(RpcStats);
+
+impl Drop for WaitingRequest {
+    fn drop(&mut self) {
+        self.0 .0.waiting_requests.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Guard of a request being handled, see [`RpcStats::request_started`].
+#[derive(Debug)]
+pub struct RunningRequest(RpcStats);
+
+impl Drop for RunningRequest {
+    fn drop(&mut self) {
+        self.0 .0.running_requests.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "RpcStats", rename_all = "camelCase")]
+pub struct JsonrpcRpcStats {
+    /// Maximum number of concurrently handled requests, 0 if unlimited.
+    pub max_concurrent_requests: usize,
+
+    /// Requests read from the client and waiting for a running request to finish.
+    pub waiting_requests: usize,
+
+    /// Requests being handled, including this one.
+    pub running_requests: usize,
+
+    /// Requests handled since the connection was established, including this one.
+    pub total_requests: u64,
+}
+
+#[cfg(test)]
+#[test]
+fn test_rpc_stats() {
+    let stats = RpcStats::default();
+    stats.set_max_concurrent_requests(2);
+    let waiting = stats.request_queued();
+    assert_eq!(stats.snapshot().waiting_requests, 1);
+    drop(waiting);
+    let running = stats.request_started();
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.max_concurrent_requests, 2);
+    assert_eq!(snapshot.waiting_requests, 0);
+    assert_eq!(snapshot.running_requests, 1);
+    drop(running);
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.running_requests, 0);
+    assert_eq!(snapshot.total_requests, 1);
+}
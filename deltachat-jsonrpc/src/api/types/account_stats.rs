@@ -0,0 +1,70 @@
+use deltachat::account_stats::{AccountStats, ChatStats, ContactStats};
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "AccountStats", rename_all = "camelCase")]
+pub struct JsonrpcAccountStats {
+    pub timestamp_start: i64,
+    pub timestamp_end: i64,
+    pub msgs_sent: usize,
+    pub msgs_received: usize,
+    pub msgs_encrypted: usize,
+    /// Share of end-to-end encrypted messages, between 0.0 and 1.0.
+    pub encryption_ratio: f64,
+    pub media_bytes: u64,
+    /// Most active chat first.
+    pub chats: Vec<JsonrpcChatStats>,
+    /// Most active contact first.
+    pub top_contacts: Vec<JsonrpcContactStats>,
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "ChatStats", rename_all = "camelCase")]
+pub struct JsonrpcChatStats {
+    pub chat_id: u32,
+    pub msgs_sent: usize,
+    pub msgs_received: usize,
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "ContactStats", rename_all = "camelCase")]
+pub struct JsonrpcContactStats {
+    pub contact_id: u32,
+    pub msgs: usize,
+}
+
+impl From<AccountStats> for JsonrpcAccountStats {
+    fn from(stats: AccountStats) -> Self {
+        Self {
+            timestamp_start: stats.timestamp_start,
+            timestamp_end: stats.timestamp_end,
+            msgs_sent: stats.msgs_sent,
+            msgs_received: stats.msgs_received,
+            msgs_encrypted: stats.msgs_encrypted,
+            encryption_ratio: stats.encryption_ratio(),
+            media_bytes: stats.media_bytes,
+            chats: stats.chats.into_iter().map(Into::into).collect(),
+            top_contacts: stats.top_contacts.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<ChatStats> for JsonrpcChatStats {
+    fn from(stats: ChatStats) -> Self {
+        Self {
+            chat_id: stats.chat_id.to_u32(),
+            msgs_sent: stats.msgs_sent,
+            msgs_received: stats.msgs_received,
+        }
+    }
+}
+
+impl From<ContactStats> for JsonrpcContactStats {
+    fn from(stats: ContactStats) -> Self {
+        Self {
+            contact_id: stats.contact_id.to_u32(),
+            msgs: stats.msgs,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use deltachat::aeap::{AeapMigration, AeapMigrationState};
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "AeapMigration", rename_all = "camelCase")]
+pub struct JsonrpcAeapMigration {
+    pub id: u32,
+    pub timestamp: i64,
+    pub chat_id: u32,
+    pub old_contact_id: u32,
+    /// `None` if the chat was skipped.
+    pub new_contact_id: Option<u32>,
+    pub old_addr: String,
+    pub new_addr: String,
+    pub state: JsonrpcAeapMigrationState,
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "AeapMigrationState")]
+pub enum JsonrpcAeapMigrationState {
+    Migrated,
+    Undone,
+    SkippedSingleChat,
+    SkippedUnprotectedGroup,
+    SkippedInvalidAddress,
+    SkippedUnsupportedChat,
+}
+
+impl From<AeapMigrationState> for JsonrpcAeapMigrationState {
+    fn from(state: AeapMigrationState) -> Self {
+        match state {
+            AeapMigrationState::Migrated => Self::Migrated,
+            AeapMigrationState::Undone => Self::Undone,
+            AeapMigrationState::SkippedSingleChat => Self::SkippedSingleChat,
+            AeapMigrationState::SkippedUnprotectedGroup => Self::SkippedUnprotectedGroup,
+            AeapMigrationState::SkippedInvalidAddress => Self::SkippedInvalidAddress,
+            AeapMigrationState::SkippedUnsupportedChat => Self::SkippedUnsupportedChat,
+        }
+    }
+}
+
+impl From<AeapMigration> for JsonrpcAeapMigration {
+    fn from(migration: AeapMigration) -> Self {
+        Self {
+            id: migration.id,
+            timestamp: migration.timestamp,
+            chat_id: migration.chat_id.to_u32(),
+            old_contact_id: migration.old_contact_id.to_u32(),
+            new_contact_id: migration.new_contact_id.map(|id| id.to_u32()),
+            old_addr: migration.old_addr,
+            new_addr: migration.new_addr,
+            state: migration.state.into(),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use deltachat::blocklist::BlocklistFeed;
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "BlocklistFeed", rename_all = "camelCase")]
+pub struct JsonrpcBlocklistFeed {
+    pub id: u32,
+    pub url: String,
+    pub fingerprint: String,
+    pub entries: u32,
+    pub last_update: i64,
+    pub last_error: String,
+}
+
+impl From<BlocklistFeed> for JsonrpcBlocklistFeed {
+    fn from(feed: BlocklistFeed) -> Self {
+        Self {
+            id: feed.id,
+            url: feed.url,
+            fingerprint: feed.fingerprint,
+            entries: feed.entries,
+            last_update: feed.last_update,
+            last_error: feed.last_error,
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use deltachat::canned_response::CannedResponse;
+use deltachat::context::Context;
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "CannedResponse", rename_all = "camelCase")]
+pub struct JsonrpcCannedResponse {
+    pub id: u32,
+    pub title: String,
+    pub text: String,
+    pub file: Option<String>,
+}
+
+impl JsonrpcCannedResponse {
+    pub fn from_canned_response(context: &Context, response: CannedResponse) -> Self {
+        let file = response
+            .get_file(context)
+            .map(|path| path.to_string_lossy().into_owned());
+        Self {
+            id: response.id,
+            title: response.title,
+            text: response.text,
+            file,
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::time::{Duration, SystemTime};
 
 use anyhow::{anyhow, bail, Result};
-use deltachat::chat::{self, get_chat_contacts, ChatVisibility};
+use deltachat::chat::{self, get_chat_contacts, ArchiveMode, ChatVisibility};
 use deltachat::chat::{Chat, ChatId};
 use deltachat::constants::Chattype;
 use deltachat::contact::{Contact, ContactId};
@@ -18,9 +18,13 @@
 pub struct FullChat {
     id: u32,
     name: String,
+    /// True if the chat name looks like an impersonation attempt.
+    is_name_suspicious: bool,
     is_protected: bool,
     profile_image: Option<String>, //BLOBS ?
     archived: bool,
+    /// Signature overriding `selfstatus` in this chat, see `set_chat_signature()`.
+    signature: Option<String>,
     // subtitle  - will be moved to frontend because it uses translation functions
     chat_type: u32,
     is_unpromoted: bool,
@@ -34,6 +38,10 @@ pub struct FullChat {
     is_device_chat: bool,
     self_in_group: bool,
     is_muted: bool,
+    is_threaded: bool,
+    archive_mode: JSONRPCArchiveMode,
+    /// Stable identifier of the chat which, unlike `id`, survives backup export and import.
+    uuid: String,
     ephemeral_timer: u32, //TODO look if there are more important properties in newer core versions
     can_send: bool,
     was_seen_recently: bool,
@@ -86,9 +94,11 @@ pub async fn try_from_dc_chat_id(context: &Context, chat_id: u32) -> Result<Self
         Ok(FullChat {
             id: chat_id,
             name: chat.name.clone(),
+            is_name_suspicious: chat.is_name_suspicious(),
             is_protected: chat.is_protected(),
             profile_image, //BLOBS ?
             archived: chat.get_visibility() == chat::ChatVisibility::Archived,
+            signature: chat.get_signature().map(|s| s.to_string()),
             chat_type: chat
                 .get_type()
                 .to_u32()
@@ -103,6 +113,9 @@ pub async fn try_from_dc_chat_id(context: &Context, chat_id: u32) -> Result<Self
             is_device_chat: chat.is_device_talk(),
             self_in_group: contact_ids.contains(&ContactId::SELF),
             is_muted: chat.is_muted(),
+            is_threaded: chat.is_threaded(),
+            archive_mode: chat.get_archive_mode().into(),
+            uuid: chat.get_uuid().to_owned(),
             ephemeral_timer,
             can_send,
             was_seen_recently,
@@ -126,6 +139,8 @@ pub async fn try_from_dc_chat_id(context: &Context, chat_id: u32) -> Result<Self
 pub struct BasicChat {
     id: u32,
     name: String,
+    /// True if the chat name looks like an impersonation attempt.
+    is_name_suspicious: bool,
     is_protected: bool,
     profile_image: Option<String>, //BLOBS ?
     archived: bool,
@@ -136,6 +151,8 @@ pub struct BasicChat {
     is_contact_request: bool,
     is_device_chat: bool,
     is_muted: bool,
+    /// Stable identifier of the chat which, unlike `id`, survives backup export and import.
+    uuid: String,
 }
 
 impl BasicChat {
@@ -152,6 +169,7 @@ pub async fn try_from_dc_chat_id(context: &Context, chat_id: u32) -> Result<Self
         Ok(BasicChat {
             id: chat_id,
             name: chat.name.clone(),
+            is_name_suspicious: chat.is_name_suspicious(),
             is_protected: chat.is_protected(),
             profile_image, //BLOBS ?
             archived: chat.get_visibility() == chat::ChatVisibility::Archived,
@@ -165,6 +183,7 @@ pub async fn try_from_dc_chat_id(context: &Context, chat_id: u32) -> Result<Self
             is_contact_request: chat.is_contact_request(),
             is_device_chat: chat.is_device_talk(),
             is_muted: chat.is_muted(),
+            uuid: chat.get_uuid().to_owned(),
         })
     }
 }
@@ -211,3 +230,56 @@ pub fn into_core_type(self) -> ChatVisibility {
         }
     }
 }
+
+#[derive(Clone, Copy, Serialize, Deserialize, TypeDef)]
+#[serde(rename = "ArchiveMode")]
+pub enum JSONRPCArchiveMode {
+    AlwaysUnarchive,
+    StayArchived,
+    UnarchiveOnMention,
+}
+
+impl JSONRPCArchiveMode {
+    pub fn into_core_type(self) -> ArchiveMode {
+        match self {
+            JSONRPCArchiveMode::AlwaysUnarchive => ArchiveMode::AlwaysUnarchive,
+            JSONRPCArchiveMode::StayArchived => ArchiveMode::StayArchived,
+            JSONRPCArchiveMode::UnarchiveOnMention => ArchiveMode::UnarchiveOnMention,
+        }
+    }
+}
+
+impl From<ArchiveMode> for JSONRPCArchiveMode {
+    fn from(archive_mode: ArchiveMode) -> Self {
+        match archive_mode {
+            ArchiveMode::AlwaysUnarchive => JSONRPCArchiveMode::AlwaysUnarchive,
+            ArchiveMode::StayArchived => JSONRPCArchiveMode::StayArchived,
+            ArchiveMode::UnarchiveOnMention => JSONRPCArchiveMode::UnarchiveOnMention,
+        }
+    }
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaMonth {
+    year: i32,
+    /// Month from 1 to 12.
+    month: u32,
+    count: usize,
+    message_ids: Vec<u32>,
+}
+
+impl From<chat::MediaMonth> for MediaMonth {
+    fn from(media_month: chat::MediaMonth) -> Self {
+        Self {
+            year: media_month.year,
+            month: media_month.month,
+            count: media_month.msg_ids.len(),
+            message_ids: media_month
+                .msg_ids
+                .iter()
+                .map(|msg_id| msg_id.to_u32())
+                .collect(),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use deltachat::constants::*;
 use deltachat::contact::{Contact, ContactId};
+use deltachat::tools;
 use deltachat::{
     chat::{get_chat_contacts, ChatVisibility},
     chatlist::Chatlist,
@@ -13,11 +14,30 @@
 use serde::{Deserialize, Serialize};
 use typescript_type_def::TypeDef;
 
+use super::chat::JSONRPCArchiveMode;
 use super::color_int_to_hex_string;
 
 #[derive(Deserialize, Serialize, TypeDef)]
 pub struct ChatListEntry(pub u32, pub u32);
 
+#[derive(Clone, Copy, Serialize, TypeDef)]
+#[serde(rename_all = "camelCase")]
+pub enum TextDirection {
+    Neutral,
+    Ltr,
+    Rtl,
+}
+
+impl From<tools::TextDirection> for TextDirection {
+    fn from(direction: tools::TextDirection) -> Self {
+        match direction {
+            tools::TextDirection::Neutral => TextDirection::Neutral,
+            tools::TextDirection::Ltr => TextDirection::Ltr,
+            tools::TextDirection::Rtl => TextDirection::Rtl,
+        }
+    }
+}
+
 #[derive(Serialize, TypeDef)]
 #[serde(tag = "type")]
 pub enum ChatListItemFetchResult {
@@ -30,6 +50,8 @@ pub enum ChatListItemFetchResult {
         last_updated: Option<i64>,
         summary_text1: String,
         summary_text2: String,
+        /// Direction of `summary_text2`, to align it without analyzing the text.
+        summary_text2_direction: TextDirection,
         summary_status: u32,
         is_protected: bool,
         is_group: bool,
@@ -40,6 +62,7 @@ pub enum ChatListItemFetchResult {
         is_self_in_group: bool,
         is_archived: bool,
         is_pinned: bool,
+        archive_mode: JSONRPCArchiveMode,
         is_muted: bool,
         is_contact_request: bool,
         /// true when chat is a broadcastlist
@@ -74,6 +97,7 @@ pub(crate) async fn get_chat_list_item_by_id(
 
     let chat = Chat::load_from_db(ctx, chat_id).await?;
     let summary = Chatlist::get_summary2(ctx, chat_id, last_msgid, Some(&chat)).await?;
+    let summary_text2_direction = summary.text_direction().into();
 
     let summary_text1 = summary.prefix.map_or_else(String::new, |s| s.to_string());
     let summary_text2 = summary.text.to_owned();
@@ -123,6 +147,7 @@ pub(crate) async fn get_chat_list_item_by_id(
         last_updated,
         summary_text1,
         summary_text2,
+        summary_text2_direction,
         summary_status: summary.state.to_u32().expect("impossible"), // idea and a function to transform the constant to strings? or return string enum
         is_protected: chat.is_protected(),
         is_group: chat.get_type() == Chattype::Group,
@@ -133,6 +158,7 @@ pub(crate) async fn get_chat_list_item_by_id(
         is_sending_location: chat.is_sending_locations(),
         is_archived: visibility == ChatVisibility::Archived,
         is_pinned: visibility == ChatVisibility::Pinned,
+        archive_mode: chat.get_archive_mode().into(),
         is_muted: chat.is_muted(),
         is_contact_request: chat.is_contact_request(),
         is_broadcast: chat.get_type() == Chattype::Broadcast,
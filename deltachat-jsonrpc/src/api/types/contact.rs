@@ -1,5 +1,5 @@
 use anyhow::Result;
-use deltachat::contact::VerifiedStatus;
+use deltachat::contact::{KeyHistoryEntry, VerifiedStatus};
 use deltachat::context::Context;
 use serde::Serialize;
 use typescript_type_def::TypeDef;
@@ -17,8 +17,13 @@ pub struct ContactObject {
     id: u32,
     name: String,
     profile_image: Option<String>, // BLOBS
+    /// Small version of `profile_image` for lists, its path is stable
+    /// as long as the profile image does not change.
+    profile_image_thumbnail: Option<String>, // BLOBS
     name_and_addr: String,
     is_blocked: bool,
+    /// True if the name set by the contact looks like an impersonation attempt.
+    is_name_suspicious: bool,
     is_verified: bool,
     /// the address that verified this contact
     verifier_addr: Option<String>,
@@ -27,6 +32,11 @@ pub struct ContactObject {
     /// the contact's last seen timestamp
     last_seen: i64,
     was_seen_recently: bool,
+    /// False if our last-seen status is not shared with the contact,
+    /// see `set_contact_share_last_seen()`.
+    shares_last_seen: bool,
+    /// Stable identifier of the contact which, unlike `id`, survives backup export and import.
+    uuid: String,
 }
 
 impl ContactObject {
@@ -38,6 +48,12 @@ pub async fn try_from_dc_contact(
             Some(path_buf) => path_buf.to_str().map(|s| s.to_owned()),
             None => None,
         };
+        // A broken image should not make the whole contact unavailable.
+        let profile_image_thumbnail = contact
+            .get_profile_image_thumbnail(context)
+            .await
+            .unwrap_or_default()
+            .and_then(|path_buf| path_buf.to_str().map(|s| s.to_owned()));
         let is_verified = contact.is_verified(context).await? == VerifiedStatus::BidirectVerified;
 
         let (verifier_addr, verifier_id) = if is_verified {
@@ -60,14 +76,40 @@ pub async fn try_from_dc_contact(
             display_name: contact.get_display_name().to_owned(),
             id: contact.id.to_u32(),
             name: contact.get_name().to_owned(),
-            profile_image, //BLOBS
+            profile_image,           //BLOBS
+            profile_image_thumbnail, //BLOBS
             name_and_addr: contact.get_name_n_addr(),
             is_blocked: contact.is_blocked(),
+            is_name_suspicious: contact.is_name_suspicious(),
             is_verified,
             verifier_addr,
             verifier_id,
             last_seen: contact.last_seen(),
             was_seen_recently: contact.was_seen_recently(),
+            shares_last_seen: contact.shares_last_seen(),
+            uuid: contact.get_uuid().to_owned(),
         })
     }
 }
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "KeyHistoryEntry", rename_all = "camelCase")]
+pub struct KeyHistoryEntryObject {
+    /// hex fingerprint of the key
+    fingerprint: String,
+    first_seen: i64,
+    last_seen: i64,
+    /// whether the message the key was first seen in passed the DKIM check
+    dkim_passed: bool,
+}
+
+impl From<KeyHistoryEntry> for KeyHistoryEntryObject {
+    fn from(entry: KeyHistoryEntry) -> Self {
+        KeyHistoryEntryObject {
+            fingerprint: entry.fingerprint.hex(),
+            first_seen: entry.first_seen,
+            last_seen: entry.last_seen,
+            dkim_passed: entry.dkim_passed,
+        }
+    }
+}
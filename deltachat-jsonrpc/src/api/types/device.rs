@@ -0,0 +1,29 @@
+use deltachat::devices::DeviceInfo;
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "Device", rename_all = "camelCase")]
+pub struct JsonrpcDevice {
+    pub id: String,
+    pub name: String,
+    pub last_seen: i64,
+    pub is_stale: bool,
+}
+
+impl From<DeviceInfo> for JsonrpcDevice {
+    fn from(device: DeviceInfo) -> Self {
+        let is_stale = device.is_stale();
+        let DeviceInfo {
+            id,
+            name,
+            last_seen,
+        } = device;
+        Self {
+            id,
+            name,
+            last_seen,
+            is_stale,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use deltachat::gif_search::GifSearchResult;
+use serde::{Deserialize, Serialize};
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, Deserialize, TypeDef)]
+#[serde(rename = "GifSearchResult", rename_all = "camelCase")]
+pub struct JsonrpcGifSearchResult {
+    pub id: String,
+    pub url: String,
+    pub preview_url: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub attribution: Option<String>,
+}
+
+impl From<GifSearchResult> for JsonrpcGifSearchResult {
+    fn from(result: GifSearchResult) -> Self {
+        let GifSearchResult {
+            id,
+            url,
+            preview_url,
+            width,
+            height,
+            attribution,
+        } = result;
+        Self {
+            id,
+            url,
+            preview_url,
+            width,
+            height,
+            attribution,
+        }
+    }
+}
+
+impl From<JsonrpcGifSearchResult> for GifSearchResult {
+    fn from(result: JsonrpcGifSearchResult) -> Self {
+        let JsonrpcGifSearchResult {
+            id,
+            url,
+            preview_url,
+            width,
+            height,
+            attribution,
+        } = result;
+        Self {
+            id,
+            url,
+            preview_url,
+            width,
+            height,
+            attribution,
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use deltachat::imap_jobs::{ImapJob, ImapJobKind};
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "ImapJob", rename_all = "camelCase")]
+pub struct JsonrpcImapJob {
+    pub id: u32,
+    pub kind: JsonrpcImapJobKind,
+    pub folder: String,
+    pub uid: u32,
+    pub rfc724_mid: String,
+    pub target: Option<String>,
+    /// Timestamp when the job was scheduled, 0 if unknown.
+    pub timestamp: i64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "ImapJobKind")]
+pub enum JsonrpcImapJobKind {
+    Move,
+    Delete,
+    MarkSeen,
+}
+
+impl From<ImapJobKind> for JsonrpcImapJobKind {
+    fn from(kind: ImapJobKind) -> Self {
+        match kind {
+            ImapJobKind::Move => Self::Move,
+            ImapJobKind::Delete => Self::Delete,
+            ImapJobKind::MarkSeen => Self::MarkSeen,
+        }
+    }
+}
+
+impl From<ImapJob> for JsonrpcImapJob {
+    fn from(job: ImapJob) -> Self {
+        Self {
+            id: job.id,
+            kind: job.kind.into(),
+            folder: job.folder,
+            uid: job.uid,
+            rfc724_mid: job.rfc724_mid,
+            target: job.target,
+            timestamp: job.timestamp,
+            last_error: job.last_error,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use deltachat::securejoin::{InviteLink, InviteLinkJoin};
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "InviteLink", rename_all = "camelCase")]
+pub struct JsonrpcInviteLink {
+    pub id: u32,
+    pub qr: String,
+    pub max_uses: u32,
+    pub uses: u32,
+    pub timestamp: i64,
+}
+
+impl From<InviteLink> for JsonrpcInviteLink {
+    fn from(link: InviteLink) -> Self {
+        Self {
+            id: link.id,
+            qr: link.qr,
+            max_uses: link.max_uses,
+            uses: link.uses,
+            timestamp: link.timestamp,
+        }
+    }
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "InviteLinkJoin", rename_all = "camelCase")]
+pub struct JsonrpcInviteLinkJoin {
+    pub contact_id: u32,
+    pub timestamp: i64,
+}
+
+impl From<InviteLinkJoin> for JsonrpcInviteLinkJoin {
+    fn from(join: InviteLinkJoin) -> Self {
+        Self {
+            contact_id: join.contact_id.to_u32(),
+            timestamp: join.timestamp,
+        }
+    }
+}
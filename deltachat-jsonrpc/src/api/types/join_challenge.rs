@@ -0,0 +1,23 @@
+use deltachat::securejoin::JoinChallenge;
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "JoinChallenge", rename_all = "camelCase")]
+pub struct JsonrpcJoinChallenge {
+    pub id: u32,
+    pub contact_id: u32,
+    pub question: String,
+    pub options: Vec<String>,
+}
+
+impl From<JoinChallenge> for JsonrpcJoinChallenge {
+    fn from(challenge: JoinChallenge) -> Self {
+        Self {
+            id: challenge.id,
+            contact_id: challenge.contact_id.to_u32(),
+            question: challenge.question,
+            options: challenge.options,
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context as _, Result};
+use deltachat::calls;
 use deltachat::chat::Chat;
 use deltachat::chat::ChatItem;
 use deltachat::constants::Chattype;
@@ -8,6 +11,7 @@
 use deltachat::message::Message;
 use deltachat::message::MsgId;
 use deltachat::message::Viewtype;
+use deltachat::notification::get_notification_intent;
 use deltachat::reaction::get_msg_reactions;
 use num_traits::cast::ToPrimitive;
 use serde::Deserialize;
@@ -16,6 +20,7 @@
 
 use super::color_int_to_hex_string;
 use super::contact::ContactObject;
+use super::notification::JsonrpcNotificationIntent;
 use super::reactions::JSONRPCReactions;
 use super::webxdc::WebxdcMessageInfo;
 
@@ -26,6 +31,18 @@ pub enum MessageLoadResult {
     LoadingError { error: String },
 }
 
+impl MessageLoadResult {
+    /// Loads a message, storing the error if this fails.
+    pub async fn load(context: &Context, msg_id: u32) -> Self {
+        match MessageObject::from_message_id(context, msg_id).await {
+            Ok(message) => MessageLoadResult::Message(message),
+            Err(error) => MessageLoadResult::LoadingError {
+                error: format!("{error:#}"),
+            },
+        }
+    }
+}
+
 #[derive(Serialize, TypeDef)]
 #[serde(rename = "Message", rename_all = "camelCase")]
 pub struct MessageObject {
@@ -55,19 +72,55 @@ pub struct MessageObject {
     is_setupmessage: bool,
     is_info: bool,
     is_forwarded: bool,
+    is_starred: bool,
+
+    /// True if the message is a view-once message,
+    /// its media should be retrieved using `open_view_once()`.
+    is_view_once: bool,
+
+    /// True if the media of a received view-once message was viewed and deleted.
+    is_view_once_viewed: bool,
 
     /// True if the message was sent by a bot.
     is_bot: bool,
 
+    /// For device messages, the action the UI can offer, e.g. as a button below the message.
+    /// One of `backup`, `notification_settings`, `account_settings`, `manage_storage`
+    /// and `update_app`, more actions may be added in the future.
+    device_action: Option<String>,
+
+    /// Name of the file offered for peer-to-peer download with `download_file_p2p()`.
+    p2p_file_name: Option<String>,
+
+    /// Size in bytes of the file offered for peer-to-peer download.
+    p2p_file_bytes: Option<u64>,
+
+    /// State of a call record of view type `Call`, the duration is in `duration`.
+    call_state: Option<CallState>,
+
+    /// Delay in seconds if the message was delivered late.
+    delivery_delay: Option<i64>,
+
     /// when is_info is true this describes what type of system message it is
     system_message_type: SystemMessageType,
 
+    /// First message of the run of consecutive info messages of the same type
+    /// this message belongs to, messages with the same info group can be collapsed.
+    info_group: Option<u32>,
+
     duration: i32,
     dimensions_height: i32,
     dimensions_width: i32,
 
     videochat_type: Option<u32>,
     videochat_url: Option<String>,
+    /// Host of the videochat instance, `None` if the message is not a videochat invitation.
+    videochat_host: Option<String>,
+    /// Room of the videochat, `None` if unknown or the message is not a videochat invitation.
+    videochat_room: Option<String>,
+
+    /// Grouping ID of the album the message belongs to, see `send_album()`.
+    album_id: Option<String>,
 
     override_sender_name: Option<String>,
     sender: ContactObject,
@@ -79,8 +132,23 @@ pub struct MessageObject {
     file_bytes: u64,
     file_name: Option<String>,
 
+    /// Paths of all files of the message, starting with `file`.
+    attachments: Vec<String>,
+
     webxdc_info: Option<WebxdcMessageInfo>,
 
+    /// For webxdc info messages, the `href` to pass to the webxdc instance
+    /// (the parent message) when the info message is clicked.
+    webxdc_href: Option<String>,
+
+    /// For messages in the saved messages chat, the chat and the original author
+    /// the message was saved from.
+    saved_from_chat_id: Option<u32>,
+    saved_from_contact_id: Option<u32>,
+
+    /// For outgoing images, names of the metadata removed from the image.
+    removed_metadata: Vec<String>,
+
     download_state: DownloadState,
 
     reactions: Option<JSONRPCReactions>,
@@ -118,6 +186,7 @@ pub async fn from_msg_id(context: &Context, msg_id: MsgId) -> Result<Self> {
         let sender = ContactObject::try_from_dc_contact(context, sender_contact).await?;
         let file_bytes = message.get_filebytes(context).await?.unwrap_or_default();
         let override_sender_name = message.get_override_sender_name();
+        let videochat_invitation = message.get_videochat_invitation();
 
         let webxdc_info = if message.get_viewtype() == Viewtype::Webxdc {
             Some(WebxdcMessageInfo::get_for_message(context, msg_id).await?)
@@ -193,8 +262,19 @@ pub async fn from_msg_id(context: &Context, msg_id: MsgId) -> Result<Self> {
             is_setupmessage: message.is_setupmessage(),
             is_info: message.is_info(),
             is_forwarded: message.is_forwarded(),
+            is_starred: message.is_starred(),
+            is_view_once: message.is_view_once(),
+            is_view_once_viewed: message.is_view_once_viewed(),
             is_bot: message.is_bot(),
+            device_action: message
+                .get_device_action()
+                .map(|action| action.as_ref().to_string()),
+            p2p_file_name: message.get_p2p_file().map(|(name, _)| name),
+            p2p_file_bytes: message.get_p2p_file().map(|(_, bytes)| bytes),
+            call_state: message.get_call_state().map(Into::into),
+            delivery_delay: message.get_delivery_delay(),
             system_message_type: message.get_info_type().into(),
+            info_group: message.get_info_group().map(|msg_id| msg_id.to_u32()),
 
             duration: message.get_duration(),
             dimensions_height: message.get_height(),
@@ -208,6 +288,11 @@ pub async fn from_msg_id(context: &Context, msg_id: MsgId) -> Result<Self> {
                 None => None,
             },
             videochat_url: message.get_videochat_url(),
+            videochat_host: videochat_invitation
+                .as_ref()
+                .map(|invitation| invitation.host.clone()),
+            videochat_room: videochat_invitation.and_then(|invitation| invitation.room),
+            album_id: message.get_album_id().map(|album_id| album_id.to_string()),
 
             override_sender_name,
             sender,
@@ -221,7 +306,16 @@ pub async fn from_msg_id(context: &Context, msg_id: MsgId) -> Result<Self> {
             file_mime: message.get_filemime(),
             file_bytes,
             file_name: message.get_filename(),
+            attachments: message
+                .get_attachments(context)
+                .iter()
+                .filter_map(|path| path.to_str().map(|s| s.to_owned()))
+                .collect(),
             webxdc_info,
+            webxdc_href: message.get_webxdc_href(),
+            saved_from_chat_id: message.get_saved_from_chat_id().map(|id| id.to_u32()),
+            saved_from_contact_id: message.get_saved_from_contact_id().map(|id| id.to_u32()),
+            removed_metadata: message.get_removed_metadata(),
 
             download_state,
 
@@ -266,6 +360,9 @@ pub enum MessageViewtype {
     /// Message is an invitation to a videochat.
     VideochatInvitation,
 
+    /// Message is a record of a call, see `addCallRecord()`.
+    Call,
+
     /// Message is an webxdc instance.
     Webxdc,
 }
@@ -283,6 +380,7 @@ fn from(viewtype: Viewtype) -> Self {
             Viewtype::Video => MessageViewtype::Video,
             Viewtype::File => MessageViewtype::File,
             Viewtype::VideochatInvitation => MessageViewtype::VideochatInvitation,
+            Viewtype::Call => MessageViewtype::Call,
             Viewtype::Webxdc => MessageViewtype::Webxdc,
         }
     }
@@ -301,6 +399,7 @@ fn from(viewtype: MessageViewtype) -> Self {
             MessageViewtype::Video => Viewtype::Video,
             MessageViewtype::File => Viewtype::File,
             MessageViewtype::VideochatInvitation => Viewtype::VideochatInvitation,
+            MessageViewtype::Call => Viewtype::Call,
             MessageViewtype::Webxdc => Viewtype::Webxdc,
         }
     }
@@ -325,6 +424,36 @@ fn from(state: download::DownloadState) -> Self {
     }
 }
 
+#[derive(Serialize, Deserialize, TypeDef)]
+pub enum CallState {
+    Ringing,
+    Accepted,
+    Missed,
+    Declined,
+}
+
+impl From<calls::CallState> for CallState {
+    fn from(state: calls::CallState) -> Self {
+        match state {
+            calls::CallState::Ringing => CallState::Ringing,
+            calls::CallState::Accepted => CallState::Accepted,
+            calls::CallState::Missed => CallState::Missed,
+            calls::CallState::Declined => CallState::Declined,
+        }
+    }
+}
+
+impl From<CallState> for calls::CallState {
+    fn from(state: CallState) -> Self {
+        match state {
+            CallState::Ringing => calls::CallState::Ringing,
+            CallState::Accepted => calls::CallState::Accepted,
+            CallState::Missed => calls::CallState::Missed,
+            CallState::Declined => calls::CallState::Declined,
+        }
+    }
+}
+
 #[derive(Serialize, TypeDef)]
 pub enum SystemMessageType {
     Unknown,
@@ -344,6 +473,9 @@ pub enum SystemMessageType {
     ChatProtectionEnabled,
     ChatProtectionDisabled,
 
+    /// Hidden message gossiping the new verified key of a member.
+    KeyGossip,
+
     /// Self-sent-message that contains only json used for multi-device-sync;
     /// if possible, we attach that to other messages as for locations.
     MultiDeviceSync,
@@ -355,6 +487,14 @@ pub enum SystemMessageType {
 
     /// Webxdc info added with `info` set in `send_webxdc_status_update()`.
     WebxdcInfoMessage,
+
+    /// Hidden call signaling messages.
+    CallOffer,
+    CallAnswer,
+    CallIceCandidate,
+
+    /// Hidden feedback report about a received message.
+    AbuseReport,
 }
 
 impl From<deltachat::mimeparser::SystemMessage> for SystemMessageType {
@@ -373,9 +513,14 @@ fn from(system_message_type: deltachat::mimeparser::SystemMessage) -> Self {
             SystemMessage::EphemeralTimerChanged => SystemMessageType::EphemeralTimerChanged,
             SystemMessage::ChatProtectionEnabled => SystemMessageType::ChatProtectionEnabled,
             SystemMessage::ChatProtectionDisabled => SystemMessageType::ChatProtectionDisabled,
+            SystemMessage::KeyGossip => SystemMessageType::KeyGossip,
             SystemMessage::MultiDeviceSync => SystemMessageType::MultiDeviceSync,
             SystemMessage::WebxdcStatusUpdate => SystemMessageType::WebxdcStatusUpdate,
             SystemMessage::WebxdcInfoMessage => SystemMessageType::WebxdcInfoMessage,
+            SystemMessage::CallOffer => SystemMessageType::CallOffer,
+            SystemMessage::CallAnswer => SystemMessageType::CallAnswer,
+            SystemMessage::CallIceCandidate => SystemMessageType::CallIceCandidate,
+            SystemMessage::AbuseReport => SystemMessageType::AbuseReport,
         }
     }
 }
@@ -397,6 +542,9 @@ pub struct MessageNotificationInfo {
     summary_prefix: Option<String>,
     /// also known as summary_text2
     summary_text: String,
+
+    /// How the user should be notified about the message.
+    intent: JsonrpcNotificationIntent,
 }
 
 impl MessageNotificationInfo {
@@ -423,6 +571,7 @@ pub async fn from_msg_id(context: &Context, msg_id: MsgId) -> Result<Self> {
             .unwrap_or_default();
 
         let summary = message.get_summary(context, Some(&chat)).await?;
+        let intent = get_notification_intent(context, msg_id).await?;
 
         Ok(MessageNotificationInfo {
             id: msg_id.to_u32(),
@@ -434,6 +583,7 @@ pub async fn from_msg_id(context: &Context, msg_id: MsgId) -> Result<Self> {
             chat_profile_image,
             summary_prefix: summary.prefix.map(|s| s.to_string()),
             summary_text: summary.text,
+            intent: intent.into(),
         })
     }
 }
@@ -503,6 +653,55 @@ fn from(item: ChatItem) -> Self {
     }
 }
 
+/// Page of a message list returned by `get_message_list_page()`.
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "MessageListPage", rename_all = "camelCase")]
+pub struct JsonrpcMessageListPage {
+    /// Items of the page, oldest first.
+    pub items: Vec<JSONRPCMessageListItem>,
+
+    /// Messages of the page by their ID.
+    pub messages: HashMap<u32, MessageLoadResult>,
+
+    /// Cursor to get the page of older messages,
+    /// `None` if the page contains the oldest message of the chat.
+    pub next_cursor: Option<String>,
+}
+
+/// Returns the range of `items` forming the page of up to `limit` messages
+/// before the message `cursor`, or the newest messages if there is no cursor.
+///
+/// A day marker belongs to the page of the message following it.
+/// Returns `None` if the cursor message is not in the list anymore.
+pub(crate) fn message_list_page(
+    items: &[ChatItem],
+    cursor: Option<MsgId>,
+    limit: usize,
+) -> Option<std::ops::Range<usize>> {
+    let end = match cursor {
+        Some(cursor) => items
+            .iter()
+            .position(|item| *item == ChatItem::Message { msg_id: cursor })?,
+        None => items.len(),
+    };
+    let end = match end.checked_sub(1).map(|i| &items[i]) {
+        Some(ChatItem::DayMarker { .. }) => end - 1,
+        _ => end,
+    };
+    let mut start = end;
+    let mut count = 0;
+    while start > 0 && count < limit {
+        start -= 1;
+        if let ChatItem::Message { .. } = items[start] {
+            count += 1;
+        }
+    }
+    if let Some(ChatItem::DayMarker { .. }) = start.checked_sub(1).map(|i| &items[i]) {
+        start -= 1;
+    }
+    Some(start..end)
+}
+
 #[derive(Deserialize, TypeDef)]
 #[serde(rename_all = "camelCase")]
 pub struct MessageData {
@@ -510,7 +709,84 @@ pub struct MessageData {
     pub html: Option<String>,
     pub viewtype: Option<MessageViewtype>,
     pub file: Option<String>,
+    /// Additional files sent in the same message as `file`.
+    pub attachments: Option<Vec<String>>,
     pub location: Option<(f64, f64)>,
     pub override_sender_name: Option<String>,
     pub quoted_message_id: Option<u32>,
+    pub view_once: Option<bool>,
+}
+
+impl MessageData {
+    /// Creates a message to send from the data.
+    pub(crate) async fn create_message(self, context: &Context) -> Result<Message> {
+        let mut message = Message::new(if let Some(viewtype) = self.viewtype {
+            viewtype.into()
+        } else if self.file.is_some() {
+            Viewtype::File
+        } else {
+            Viewtype::Text
+        });
+        if self.text.is_some() {
+            message.set_text(self.text);
+        }
+        if self.html.is_some() {
+            message.set_html(self.html);
+        }
+        if self.override_sender_name.is_some() {
+            message.set_override_sender_name(self.override_sender_name);
+        }
+        if let Some(file) = self.file {
+            message.set_file(file, None);
+        }
+        for attachment in self.attachments.unwrap_or_default() {
+            message.add_attachment(&attachment);
+        }
+        if let Some((latitude, longitude)) = self.location {
+            message.set_location(latitude, longitude);
+        }
+        if let Some(view_once) = self.view_once {
+            message.set_view_once(view_once);
+        }
+        if let Some(id) = self.quoted_message_id {
+            message
+                .set_quote(
+                    context,
+                    Some(
+                        &Message::load_from_db(context, MsgId::new(id))
+                            .await
+                            .context("message to quote could not be loaded")?,
+                    ),
+                )
+                .await?;
+        }
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_message_list_page() {
+    let msg = |id| ChatItem::Message {
+        msg_id: MsgId::new(id),
+    };
+    let marker = ChatItem::DayMarker { timestamp: 0 };
+    let items = [marker, msg(10), msg(11), marker, msg(12), msg(13), msg(14)];
+
+    assert_eq!(message_list_page(&items, None, 2), Some(5..7));
+    assert_eq!(message_list_page(&items, None, 3), Some(3..7));
+    assert_eq!(
+        message_list_page(&items, Some(MsgId::new(12)), 2),
+        Some(0..3)
+    );
+    assert_eq!(
+        message_list_page(&items, Some(MsgId::new(11)), 5),
+        Some(0..2)
+    );
+    assert_eq!(
+        message_list_page(&items, Some(MsgId::new(10)), 5),
+        Some(0..0)
+    );
+    assert_eq!(message_list_page(&items, Some(MsgId::new(99)), 5), None);
+    assert_eq!(message_list_page(&items, None, 100), Some(0..7));
 }
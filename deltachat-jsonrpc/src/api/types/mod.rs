@@ -1,12 +1,23 @@
 pub mod account;
+pub mod account_stats;
+pub mod aeap;
+pub mod blocklist;
+pub mod canned_response;
 pub mod chat;
 pub mod chat_list;
 pub mod contact;
+pub mod device;
+pub mod gif_search;
+pub mod imap_job;
+pub mod invite_link;
+pub mod join_challenge;
 pub mod location;
 pub mod message;
+pub mod notification;
 pub mod provider_info;
 pub mod qr;
 pub mod reactions;
+pub mod server_deletion;
 pub mod webxdc;
 
 pub fn color_int_to_hex_string(color: u32) -> String {
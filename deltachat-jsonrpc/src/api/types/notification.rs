@@ -0,0 +1,26 @@
+use deltachat::notification::NotificationIntent;
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "NotificationIntent")]
+pub enum JsonrpcNotificationIntent {
+    /// Do not show a notification.
+    Suppress,
+    /// Show a notification without sound or vibration.
+    Silent,
+    Notify,
+    /// The message mentions the user or replies to a message of the user.
+    Mention,
+}
+
+impl From<NotificationIntent> for JsonrpcNotificationIntent {
+    fn from(intent: NotificationIntent) -> Self {
+        match intent {
+            NotificationIntent::Suppress => Self::Suppress,
+            NotificationIntent::Silent => Self::Silent,
+            NotificationIntent::Notify => Self::Notify,
+            NotificationIntent::Mention => Self::Mention,
+        }
+    }
+}
@@ -80,6 +80,10 @@ pub enum QrObject {
     Login {
         address: String,
     },
+    Profile {
+        address: String,
+        display_name: Option<String>,
+    },
 }
 
 impl From<Qr> for QrObject {
@@ -214,6 +218,10 @@ fn from(qr: Qr) -> Self {
                 }
             }
             Qr::Login { address, .. } => QrObject::Login { address },
+            Qr::Profile { address, profile } => QrObject::Profile {
+                address,
+                display_name: profile.display_name,
+            },
         }
     }
 }
@@ -0,0 +1,43 @@
+use deltachat::ephemeral::{ServerDeletion, ServerDeletionReason};
+use serde::Serialize;
+use typescript_type_def::TypeDef;
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "ServerDeletion", rename_all = "camelCase")]
+pub struct JsonrpcServerDeletion {
+    pub msg_id: u32,
+    pub folder: String,
+    pub uid: u32,
+    pub reason: JsonrpcServerDeletionReason,
+}
+
+#[derive(Serialize, TypeDef)]
+#[serde(rename = "ServerDeletionReason")]
+pub enum JsonrpcServerDeletionReason {
+    Ephemeral,
+    DeleteServerAfter,
+    FolderPolicy,
+    Retention,
+}
+
+impl From<ServerDeletionReason> for JsonrpcServerDeletionReason {
+    fn from(reason: ServerDeletionReason) -> Self {
+        match reason {
+            ServerDeletionReason::Ephemeral => Self::Ephemeral,
+            ServerDeletionReason::DeleteServerAfter => Self::DeleteServerAfter,
+            ServerDeletionReason::FolderPolicy => Self::FolderPolicy,
+            ServerDeletionReason::Retention => Self::Retention,
+        }
+    }
+}
+
+impl From<ServerDeletion> for JsonrpcServerDeletion {
+    fn from(deletion: ServerDeletion) -> Self {
+        Self {
+            msg_id: deletion.msg_id.to_u32(),
+            folder: deletion.folder,
+            uid: deletion.uid,
+            reason: deletion.reason.into(),
+        }
+    }
+}
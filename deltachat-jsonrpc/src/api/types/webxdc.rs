@@ -35,6 +35,12 @@ pub struct WebxdcMessageInfo {
     source_code_url: Option<String>,
     /// True if full internet access should be granted to the app.
     internet_access: bool,
+    /// Maximal size in bytes of a single status update as declared in the manifest,
+    /// larger updates are rejected on sending and ignored on receiving.
+    max_update_size: Option<u32>,
+    /// Number of info messages sent by other members since markWebxdcInfosSeen() was called,
+    /// may be shown as a badge on the app icon.
+    unread_info_cnt: u32,
 }
 
 impl WebxdcMessageInfo {
@@ -48,8 +54,10 @@ pub async fn get_for_message(
             icon,
             document,
             summary,
+            unread_info_cnt,
             source_code_url,
             internet_access,
+            max_update_size,
         } = message.get_webxdc_info(context).await?;
 
         Ok(Self {
@@ -59,6 +67,8 @@ pub async fn get_for_message(
             summary: maybe_empty_string_to_option(summary),
             source_code_url: maybe_empty_string_to_option(source_code_url),
             internet_access,
+            max_update_size,
+            unread_info_cnt,
         })
     }
 }
@@ -9,6 +9,7 @@ mod tests {
     use tempfile::TempDir;
     use yerpc::{RpcClient, RpcSession};
 
+    use super::api::batch::{batch_response, handle_batch_request, parse_batch};
     use super::api::{Accounts, CommandApi};
 
     #[tokio::test(flavor = "multi_thread")]
@@ -90,4 +91,462 @@ async fn test_batch_set_config() -> anyhow::Result<()> {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_single_account() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new().unwrap().path().into();
+        let mut accounts = Accounts::new(tmp_dir).await?;
+        let account_id = accounts.add_account().await?;
+        let other_account_id = accounts.add_account().await?;
+        let account_dir = accounts
+            .get_account(account_id)
+            .unwrap()
+            .get_blobdir()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+        let other_account_dir = accounts
+            .get_account(other_account_id)
+            .unwrap()
+            .get_blobdir()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+        let api = CommandApi::new(accounts).with_single_account(account_id);
+
+        let (sender, mut receiver) = unbounded::<String>();
+
+        let (client, mut rx) = RpcClient::new();
+        let session = RpcSession::new(client, api);
+        tokio::spawn({
+            async move {
+                while let Some(message) = rx.next().await {
+                    let message = serde_json::to_string(&message)?;
+                    sender.send(message).await?;
+                }
+                let res: Result<(), anyhow::Error> = Ok(());
+                res
+            }
+        });
+
+        let request = r#"{"jsonrpc":"2.0","method":"get_all_account_ids","params":[],"id":1}"#;
+        let response = format!(r#"{{"jsonrpc":"2.0","id":1,"result":[{account_id}]}}"#);
+        session.handle_incoming(request).await;
+        assert_eq!(receiver.next().await, Some(response));
+
+        let request = r#"{"jsonrpc":"2.0","method":"add_account","params":[],"id":2}"#;
+        session.handle_incoming(request).await;
+        assert!(receiver.next().await.unwrap().contains(r#""error""#));
+
+        for method in ["get_account_info", "remove_account", "is_configured"] {
+            let request = format!(
+                r#"{{"jsonrpc":"2.0","method":"{method}","params":[{other_account_id}],"id":3}}"#
+            );
+            session.handle_incoming(&request).await;
+            let result = receiver.next().await.unwrap();
+            assert!(
+                result.contains(r#""error""#),
+                "{method} succeeded: {result}"
+            );
+        }
+
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"is_configured","params":[{account_id}],"id":4}}"#
+        );
+        let response = r#"{"jsonrpc":"2.0","id":4,"result":false}"#;
+        session.handle_incoming(&request).await;
+        assert_eq!(receiver.next().await, Some(response.to_owned()));
+
+        // Files outside of the account directory cannot be accessed.
+        for path in [
+            other_account_dir.join("keys"),
+            account_dir.join("..").join("keys"),
+            "keys".into(),
+            account_dir.join("keys"),
+        ] {
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "import_self_keys",
+                "params": [account_id, path, null],
+                "id": 5,
+            });
+            session.handle_incoming(&request.to_string()).await;
+            let result = receiver.next().await.unwrap();
+            assert!(result.contains(r#""error""#), "{result}");
+            assert_eq!(
+                result.contains("is not allowed"),
+                !path.starts_with(&account_dir) || path.ends_with("../keys"),
+                "{result}"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Every method taking a path checks it if the API is restricted to a single account.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_single_account_paths() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new().unwrap().path().into();
+        let mut accounts = Accounts::new(tmp_dir).await?;
+        let account_id = accounts.add_account().await?;
+        let other_account_id = accounts.add_account().await?;
+        let path = accounts
+            .get_account(other_account_id)
+            .unwrap()
+            .get_blobdir()
+            .join("image.png");
+        let api = CommandApi::new(accounts).with_single_account(account_id);
+
+        let (sender, mut receiver) = unbounded::<String>();
+
+        let (client, mut rx) = RpcClient::new();
+        let session = RpcSession::new(client, api);
+        tokio::spawn({
+            async move {
+                while let Some(message) = rx.next().await {
+                    let message = serde_json::to_string(&message)?;
+                    sender.send(message).await?;
+                }
+                let res: Result<(), anyhow::Error> = Ok(());
+                res
+            }
+        });
+
+        let chat_id = 10;
+        let requests = [
+            (
+                "export_self_keys",
+                serde_json::json!([account_id, path, null]),
+            ),
+            (
+                "import_self_keys",
+                serde_json::json!([account_id, path, null]),
+            ),
+            ("export_backup", serde_json::json!([account_id, path, null])),
+            ("import_backup", serde_json::json!([account_id, path, null])),
+            (
+                "set_config",
+                serde_json::json!([account_id, "selfavatar", path]),
+            ),
+            (
+                "batch_set_config",
+                serde_json::json!([account_id, { "selfavatar": path }]),
+            ),
+            (
+                "create_group_chat_ex",
+                serde_json::json!([account_id, "Group", false, [], path, 0]),
+            ),
+            (
+                "set_chat_profile_image",
+                serde_json::json!([account_id, chat_id, path]),
+            ),
+            (
+                "export_chat_html",
+                serde_json::json!([account_id, chat_id, path]),
+            ),
+            (
+                "send_sticker",
+                serde_json::json!([account_id, chat_id, path]),
+            ),
+            (
+                "send_album",
+                serde_json::json!([account_id, chat_id, [path], null]),
+            ),
+            (
+                "send_msg",
+                serde_json::json!([account_id, chat_id, { "file": path }]),
+            ),
+            (
+                "send_msg",
+                serde_json::json!([account_id, chat_id, { "attachments": [path] }]),
+            ),
+            (
+                "send_file_p2p",
+                serde_json::json!([account_id, chat_id, { "file": path }]),
+            ),
+            (
+                "send_msg_deferred",
+                serde_json::json!([account_id, chat_id, { "file": path }]),
+            ),
+            (
+                "add_canned_response",
+                serde_json::json!([account_id, "Title", "Text", path]),
+            ),
+            (
+                "update_canned_response",
+                serde_json::json!([account_id, 1, "Title", "Text", path]),
+            ),
+            (
+                "misc_send_msg",
+                serde_json::json!([account_id, chat_id, null, path, null, null]),
+            ),
+            (
+                "misc_set_draft",
+                serde_json::json!([account_id, chat_id, null, path, null]),
+            ),
+        ];
+        for (id, (method, params)) in requests.into_iter().enumerate() {
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": method,
+                "params": params,
+                "id": id,
+            });
+            session.handle_incoming(&request.to_string()).await;
+            let result = receiver.next().await.unwrap();
+            assert!(
+                result.contains("is not allowed"),
+                "{method} did not check the path: {result}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_batch() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new().unwrap().path().into();
+        let mut accounts = Accounts::new(tmp_dir).await?;
+        let account_id = accounts.add_account().await?;
+        let api = CommandApi::new(accounts);
+
+        let request = r#"{"jsonrpc":"2.0","method":"get_all_account_ids","params":[],"id":1}"#;
+        assert_eq!(parse_batch(request), None);
+        assert_eq!(parse_batch(" [] "), Some(Vec::new()));
+
+        // A batch of notifications has no response.
+        let requests = parse_batch(&format!(
+            r#"[{{"jsonrpc":"2.0","method":"is_configured","params":[{account_id}]}},{{"jsonrpc":"2.0","method":"get_all_account_ids","params":[]}}]"#
+        ))
+        .unwrap();
+        assert_eq!(requests.len(), 2);
+        let mut responses = Vec::new();
+        for request in &requests {
+            responses.push(handle_batch_request(api.clone(), request).await);
+        }
+        assert_eq!(batch_response(responses), None);
+
+        // Notifications in a mixed batch are skipped, errors are responses, too.
+        let requests = parse_batch(&format!(
+            r#"[{{"jsonrpc":"2.0","method":"get_all_account_ids","params":[],"id":1}},{{"jsonrpc":"2.0","method":"is_configured","params":[{account_id}]}},{{"jsonrpc":"2.0","method":"is_configured","params":[{account_id}],"id":2}},{{"jsonrpc":"2.0","method":"no_such_method","params":[],"id":3}}]"#
+        ))
+        .unwrap();
+        assert_eq!(requests.len(), 4);
+        let mut responses = Vec::new();
+        for request in &requests {
+            responses.push(handle_batch_request(api.clone(), request).await);
+        }
+        let response: serde_json::Value =
+            serde_json::from_str(&batch_response(responses).unwrap())?;
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(
+            responses[0],
+            serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": [account_id]})
+        );
+        assert_eq!(
+            responses[1],
+            serde_json::json!({"jsonrpc": "2.0", "id": 2, "result": false})
+        );
+        assert_eq!(responses[2]["id"], 3);
+        assert!(responses[2].get("error").is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_event_filter_per_connection() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new().unwrap().path().into();
+        let accounts = Accounts::new(tmp_dir).await?;
+        let api = CommandApi::new(accounts);
+        let first = api.for_connection();
+        let second = api.for_connection();
+
+        first
+            .event_filter()
+            .set(Some([1].into_iter().collect()), None)
+            .await;
+        let event = deltachat::Event {
+            id: 2,
+            seq: 0,
+            typ: deltachat::EventType::Info("info".to_string()),
+        };
+        assert!(!first.event_filter().matches(&event).await);
+        assert!(second.event_filter().matches(&event).await);
+        assert!(api.event_filter().matches(&event).await);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blob_upload_size_limit() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new().unwrap().path().into();
+        let mut accounts = Accounts::new(tmp_dir).await?;
+        let account_id = accounts.add_account().await?;
+        let api = CommandApi::new(accounts).with_max_blob_upload_size(8);
+
+        let (sender, mut receiver) = unbounded::<String>();
+
+        let (client, mut rx) = RpcClient::new();
+        let session = RpcSession::new(client, api);
+        tokio::spawn({
+            async move {
+                while let Some(message) = rx.next().await {
+                    let message = serde_json::to_string(&message)?;
+                    sender.send(message).await?;
+                }
+                let res: Result<(), anyhow::Error> = Ok(());
+                res
+            }
+        });
+
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"blob_upload_start","params":[{account_id},"hello.txt"],"id":1}}"#
+        );
+        let response = r#"{"jsonrpc":"2.0","id":1,"result":1}"#;
+        session.handle_incoming(&request).await;
+        assert_eq!(receiver.next().await, Some(response.to_owned()));
+
+        // "hello" fits into the limit of 8 bytes.
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"blob_upload_append","params":[{account_id},1,"aGVsbG8="],"id":2}}"#
+        );
+        let response = r#"{"jsonrpc":"2.0","id":2,"result":null}"#;
+        session.handle_incoming(&request).await;
+        assert_eq!(receiver.next().await, Some(response.to_owned()));
+
+        // Another "hello" does not.
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"blob_upload_append","params":[{account_id},1,"aGVsbG8="],"id":3}}"#
+        );
+        session.handle_incoming(&request).await;
+        let result = receiver.next().await.unwrap();
+        assert!(result.contains("exceeds the maximum size"), "{result}");
+
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"blob_upload_finish","params":[{account_id},1],"id":4}}"#
+        );
+        session.handle_incoming(&request).await;
+        let result: serde_json::Value = serde_json::from_str(&receiver.next().await.unwrap())?;
+        let path = result["result"].as_str().unwrap();
+        assert_eq!(tokio::fs::read(path).await?, b"hello");
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blob_upload_count_limit() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new().unwrap().path().into();
+        let mut accounts = Accounts::new(tmp_dir).await?;
+        let account_id = accounts.add_account().await?;
+        let api = CommandApi::new(accounts);
+
+        let (sender, mut receiver) = unbounded::<String>();
+
+        let (client, mut rx) = RpcClient::new();
+        let session = RpcSession::new(client, api);
+        tokio::spawn({
+            async move {
+                while let Some(message) = rx.next().await {
+                    let message = serde_json::to_string(&message)?;
+                    sender.send(message).await?;
+                }
+                let res: Result<(), anyhow::Error> = Ok(());
+                res
+            }
+        });
+
+        for id in 1..=16 {
+            let request = format!(
+                r#"{{"jsonrpc":"2.0","method":"blob_upload_start","params":[{account_id},"hello.txt"],"id":{id}}}"#
+            );
+            let response = format!(r#"{{"jsonrpc":"2.0","id":{id},"result":{id}}}"#);
+            session.handle_incoming(&request).await;
+            assert_eq!(receiver.next().await, Some(response));
+        }
+
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"blob_upload_start","params":[{account_id},"hello.txt"],"id":17}}"#
+        );
+        session.handle_incoming(&request).await;
+        let result = receiver.next().await.unwrap();
+        assert!(result.contains("too many unfinished uploads"), "{result}");
+
+        // Finishing an upload allows to start another one.
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"blob_upload_finish","params":[{account_id},1],"id":18}}"#
+        );
+        session.handle_incoming(&request).await;
+        receiver.next().await.unwrap();
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"blob_upload_start","params":[{account_id},"hello.txt"],"id":19}}"#
+        );
+        let response = r#"{"jsonrpc":"2.0","id":19,"result":17}"#;
+        session.handle_incoming(&request).await;
+        assert_eq!(receiver.next().await, Some(response.to_owned()));
+
+        Ok(())
+    }
+
+    /// Blob URLs returned on one connection can be read on another connection to the same server.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blob_url_across_connections() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new().unwrap().path().into();
+        let mut accounts = Accounts::new(tmp_dir).await?;
+        let account_id = accounts.add_account().await?;
+        let blobdir = accounts
+            .get_account(account_id)
+            .unwrap()
+            .get_blobdir()
+            .to_path_buf();
+        tokio::fs::write(blobdir.join("hello.txt"), b"hello").await?;
+        let api = CommandApi::new(accounts);
+        let first = api.for_connection();
+        let second = api.for_connection();
+
+        let mut receivers = Vec::new();
+        let mut sessions = Vec::new();
+        for api in [first, second.clone()] {
+            let (sender, receiver) = unbounded::<String>();
+            let (client, mut rx) = RpcClient::new();
+            sessions.push(RpcSession::new(client, api));
+            receivers.push(receiver);
+            tokio::spawn({
+                async move {
+                    while let Some(message) = rx.next().await {
+                        let message = serde_json::to_string(&message)?;
+                        sender.send(message).await?;
+                    }
+                    let res: Result<(), anyhow::Error> = Ok(());
+                    res
+                }
+            });
+        }
+
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"get_blob_url","params":[{account_id},"$BLOBDIR/hello.txt"],"id":1}}"#
+        );
+        sessions[0].handle_incoming(&request).await;
+        let result: serde_json::Value = serde_json::from_str(&receivers[0].next().await.unwrap())?;
+        let url = result["result"].as_str().unwrap();
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "read_blob_url",
+            "params": [url, 0, 100],
+            "id": 2,
+        });
+        sessions[1].handle_incoming(&request.to_string()).await;
+        let response = r#"{"jsonrpc":"2.0","id":2,"result":"aGVsbG8="}"#;
+        assert_eq!(receivers[1].next().await, Some(response.to_owned()));
+
+        let token = url.split('/').nth_back(1).unwrap();
+        let path = second.get_blob_path(account_id, token, "hello.txt").await?;
+        assert_eq!(path, blobdir.join("hello.txt"));
+        assert!(second
+            .get_blob_path(account_id, "invalid", "hello.txt")
+            .await
+            .is_err());
+
+        Ok(())
+    }
 }
@@ -1,7 +1,8 @@
 use std::net::SocketAddr;
 use std::path::PathBuf;
 
-use axum::{extract::ws::WebSocketUpgrade, response::Response, routing::get, Extension, Router};
+use axum::extract::{self, ws::WebSocketUpgrade};
+use axum::{http::StatusCode, response::Response, routing::get, Extension, Router};
 use yerpc::axum::handle_ws_rpc;
 use yerpc::{RpcClient, RpcSession};
 
@@ -21,17 +22,18 @@ async fn main() -> Result<(), std::io::Error> {
         .unwrap_or(DEFAULT_PORT);
     log::info!("Starting with accounts directory `{path}`.");
     let accounts = Accounts::new(PathBuf::from(&path)).await.unwrap();
-    let state = CommandApi::new(accounts);
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let state = CommandApi::new(accounts).with_blob_http_base(format!("http://{addr}"));
 
     let app = Router::new()
         .route("/ws", get(handler))
+        .route("/blobs/:account_id/:token/:name", get(blob_handler))
         .layer(Extension(state.clone()));
 
     tokio::spawn(async move {
         state.accounts.read().await.start_io().await;
     });
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], port));
     log::info!("JSON-RPC WebSocket server listening on {}", addr);
     axum::Server::bind(&addr)
         .serve(app.into_make_service())
@@ -42,14 +44,40 @@ async fn main() -> Result<(), std::io::Error> {
 }
 
 async fn handler(ws: WebSocketUpgrade, Extension(api): Extension<CommandApi>) -> Response {
+    let api = api.for_connection();
     let (client, out_receiver) = RpcClient::new();
     let session = RpcSession::new(client.clone(), api.clone());
     tokio::spawn(async move {
         let events = api.accounts.read().await.get_event_emitter();
+        let chat_subscriptions = api.chat_subscriptions();
+        let event_filter = api.event_filter();
         while let Some(event) = events.recv().await {
+            if let Some(notification) = chat_subscriptions.chat_event_notification(&event).await {
+                client
+                    .send_notification("chatEvent", Some(notification))
+                    .await
+                    .ok();
+            }
+            if !event_filter.matches(&event).await {
+                continue;
+            }
             let event = event_to_json_rpc_notification(event);
             client.send_notification("event", Some(event)).await.ok();
         }
     });
     handle_ws_rpc(ws, out_receiver, session).await
 }
+
+/// Serves blobs for URLs returned by `get_blob_url()`.
+async fn blob_handler(
+    extract::Path((account_id, token, name)): extract::Path<(u32, String, String)>,
+    Extension(api): Extension<CommandApi>,
+) -> Result<Vec<u8>, StatusCode> {
+    let path = api
+        .get_blob_path(account_id, &token, &name)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    tokio::fs::read(path)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)
+}
@@ -8,6 +8,11 @@ import { TinyEmitter } from "@deltachat/tiny-emitter";
 type DCWireEvent<T extends Event> = {
   event: T;
   contextId: number;
+  /** Sequence number of the event among the events of the account, a gap means events were dropped. */
+  seq: number;
+};
+type DCWireChatEvent<T extends Event> = DCWireEvent<T> & {
+  chatId: number;
 };
 // export type Events = Record<
 //   Event["type"] | "ALL",
@@ -36,6 +41,7 @@ export class BaseDeltaChat<
   rpc: RawClient;
   account?: T.Account;
   private contextEmitters: { [key: number]: TinyEmitter<ContextEvents> } = {};
+  private chatEmitters: { [key: string]: TinyEmitter<ContextEvents> } = {};
   constructor(public transport: Transport) {
     super();
     this.rpc = new RawClient(this.transport);
@@ -55,6 +61,14 @@ export class BaseDeltaChat<
           );
           this.contextEmitters[event.contextId].emit("ALL", event.event);
         }
+      } else if (method === "chatEvent") {
+        const event = request.params! as DCWireChatEvent<Event>;
+        const emitter = this.chatEmitters[`${event.contextId}:${event.chatId}`];
+        if (emitter) {
+          //@ts-ignore
+          emitter.emit(event.event.type, event.event as any);
+          emitter.emit("ALL", event.event);
+        }
       }
     });
   }
@@ -71,6 +85,18 @@ export class BaseDeltaChat<
       return this.contextEmitters[account_id];
     }
   }
+
+  /**
+   * Returns an emitter for the events of a single chat.
+   * Events are only delivered after subscribing with `rpc.subscribeChat()`.
+   */
+  getChatEvents(account_id: number, chat_id: number) {
+    const key = `${account_id}:${chat_id}`;
+    if (!this.chatEmitters[key]) {
+      this.chatEmitters[key] = new TinyEmitter();
+    }
+    return this.chatEmitters[key];
+  }
 }
 
 export type Opts = {
@@ -29,8 +29,8 @@ pub fn new(window: Duration, quota: f64) -> Self {
         Self::new_at(window, quota, SystemTime::now())
     }
 
-    /// Returns a new rate limiter with given current time for testing purposes.
-    fn new_at(window: Duration, quota: f64, now: SystemTime) -> Self {
+    /// Returns a new rate limiter with the given current time.
+    pub fn new_at(window: Duration, quota: f64, now: SystemTime) -> Self {
         Self {
             last_update: now,
             current_value: 0.0,
@@ -49,8 +49,8 @@ fn current_value_at(&self, now: SystemTime) -> f64 {
         f64::max(0.0, self.current_value - rate * elapsed)
     }
 
-    /// Returns true if it is allowed to send a message.
-    fn can_send_at(&self, now: SystemTime) -> bool {
+    /// Returns true if it is allowed to send a message at the given time.
+    pub fn can_send_at(&self, now: SystemTime) -> bool {
         self.current_value_at(now) + 1.0 <= self.quota
     }
 
@@ -61,7 +61,8 @@ pub fn can_send(&self) -> bool {
         self.can_send_at(SystemTime::now())
     }
 
-    fn send_at(&mut self, now: SystemTime) {
+    /// Increases current usage value at the given time.
+    pub fn send_at(&mut self, now: SystemTime) {
         self.current_value = f64::min(self.quota, self.current_value_at(now) + 1.0);
         self.last_update = now;
     }
@@ -75,7 +76,8 @@ pub fn send(&mut self) {
         self.send_at(SystemTime::now())
     }
 
-    fn until_can_send_at(&self, now: SystemTime) -> Duration {
+    /// Calculates the time from `now` until `can_send_at` will return `true`.
+    pub fn until_can_send_at(&self, now: SystemTime) -> Duration {
         let current_value = self.current_value_at(now);
         if current_value + 1.0 <= self.quota {
             Duration::ZERO
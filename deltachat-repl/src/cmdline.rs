@@ -667,6 +667,7 @@ pub async fn cmdline(context: Context, line: &str, chat_id: &mut ChatId) -> Resu
                 chat::MessageListOptions {
                     info_only: false,
                     add_daymarker: true,
+                    corrected_order: false,
                 },
             )
             .await?;
@@ -42,22 +42,37 @@ class EventType(str, Enum):
     INCOMING_MSG = "IncomingMsg"
     INCOMING_MSG_BUNCH = "IncomingMsgBunch"
     MSGS_NOTICED = "MsgsNoticed"
+    NOTIFICATIONS_SUPPRESSED = "NotificationsSuppressed"
     MSG_DELIVERED = "MsgDelivered"
     MSG_FAILED = "MsgFailed"
     MSG_READ = "MsgRead"
+    MSGS_DELETED = "MsgsDeleted"
     CHAT_MODIFIED = "ChatModified"
     CHAT_EPHEMERAL_TIMER_MODIFIED = "ChatEphemeralTimerModified"
+    EPHEMERAL_TIMER_NOT_HONORED = "EphemeralTimerNotHonored"
     CONTACTS_CHANGED = "ContactsChanged"
     LOCATION_CHANGED = "LocationChanged"
     CONFIGURE_PROGRESS = "ConfigureProgress"
     IMEX_PROGRESS = "ImexProgress"
     IMEX_FILE_WRITTEN = "ImexFileWritten"
+    P2P_FILE_PROGRESS = "P2pFileProgress"
     SECUREJOIN_INVITER_PROGRESS = "SecurejoinInviterProgress"
     SECUREJOIN_JOINER_PROGRESS = "SecurejoinJoinerProgress"
+    SECUREJOIN_INVITE_RECEIVED = "SecurejoinInviteReceived"
+    SECUREJOIN_CHALLENGE_RECEIVED = "SecurejoinChallengeReceived"
     CONNECTIVITY_CHANGED = "ConnectivityChanged"
     SELFAVATAR_CHANGED = "SelfavatarChanged"
     WEBXDC_STATUS_UPDATE = "WebxdcStatusUpdate"
     WEBXDC_INSTANCE_DELETED = "WebxdcInstanceDeleted"
+    WEBXDC_ATTENTION = "WebxdcAttention"
+    ACCOUNTS_LOCKED = "AccountsLocked"
+    ACCOUNTS_UNLOCKED = "AccountsUnlocked"
+    INCOMING_CALL_OFFER = "IncomingCallOffer"
+    INCOMING_CALL_ANSWER = "IncomingCallAnswer"
+    INCOMING_CALL_ICE = "IncomingCallIce"
+    CALL_MISSED = "CallMissed"
+    CLOCK_SKEW = "ClockSkew"
+    CANNED_RESPONSES_CHANGED = "CannedResponsesChanged"
 
 
 class ChatType(IntEnum):
@@ -78,6 +93,14 @@ class ChatVisibility(str, Enum):
     PINNED = "Pinned"
 
 
+class ArchiveMode(str, Enum):
+    """Whether new messages unarchive an archived chat"""
+
+    ALWAYS_UNARCHIVE = "AlwaysUnarchive"
+    STAY_ARCHIVED = "StayArchived"
+    UNARCHIVE_ON_MENTION = "UnarchiveOnMention"
+
+
 class DownloadState(str, Enum):
     """Message download state"""
 
@@ -100,6 +123,7 @@ class ViewType(str, Enum):
     VIDEO = "Video"
     FILE = "File"
     VIDEOCHAT_INVITATION = "VideochatInvitation"
+    CALL = "Call"
     WEBXDC = "Webxdc"
 
 
@@ -117,6 +141,7 @@ class SystemMessageType(str, Enum):
     LOCATION_ONLY = "LocationOnly"
     CHAT_PROTECTION_ENABLED = "ChatProtectionEnabled"
     CHAT_PROTECTION_DISABLED = "ChatProtectionDisabled"
+    KEY_GOSSIP = "KeyGossip"
     WEBXDC_STATUS_UPDATE = "WebxdcStatusUpdate"
     EPHEMERAL_TIMER_CHANGED = "EphemeralTimerChanged"
     MULTI_DEVICE_SYNC = "MultiDeviceSync"
@@ -23,9 +23,11 @@ async def test_webxdc(acfactory) -> None:
         "document": None,
         "icon": "icon.png",
         "internetAccess": False,
+        "maxUpdateSize": None,
         "name": "Chess Board",
         "sourceCodeUrl": None,
         "summary": None,
+        "unreadInfoCnt": 0,
     }
 
     status_updates = await message.get_webxdc_status_updates()
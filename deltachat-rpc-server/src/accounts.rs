@@ -0,0 +1,92 @@
+//! Account management subcommands.
+//!
+//! These commands operate directly on the accounts directory
+//! without starting a JSON-RPC session, e.g. to provision bots from shell scripts.
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use deltachat::accounts::Accounts;
+use deltachat::config::Config;
+use tokio::io::{self, AsyncBufReadExt, BufReader};
+
+const USAGE: &str = "Usage:
+  deltachat-rpc-server accounts list
+  deltachat-rpc-server accounts add
+  deltachat-rpc-server accounts remove <id>
+  deltachat-rpc-server accounts configure <id> <addr> [<password>]
+
+If the password is omitted, it is read from the first line of standard input.";
+
+/// Runs `accounts` subcommand with the remaining command line arguments.
+pub(crate) async fn run(args: impl Iterator<Item = OsString>) -> Result<()> {
+    let args = args
+        .map(|arg| {
+            arg.into_string()
+                .map_err(|arg| anyhow!("Invalid argument {:?}", arg))
+        })
+        .collect::<Result<Vec<String>>>()?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let path = std::env::var("DC_ACCOUNTS_PATH").unwrap_or_else(|_| "accounts".to_string());
+    let mut accounts = Accounts::new(PathBuf::from(&path)).await?;
+
+    match args[..] {
+        ["list"] => list(&accounts).await,
+        ["add"] => {
+            let id = accounts.add_account().await?;
+            println!("{id}");
+            Ok(())
+        }
+        ["remove", id] => accounts.remove_account(parse_id(id)?).await,
+        ["configure", id, addr] => {
+            let mut password = String::new();
+            BufReader::new(io::stdin())
+                .read_line(&mut password)
+                .await
+                .context("failed to read password from stdin")?;
+            configure(&accounts, parse_id(id)?, addr, password.trim_end()).await
+        }
+        ["configure", id, addr, password] => {
+            configure(&accounts, parse_id(id)?, addr, password).await
+        }
+        _ => bail!("{USAGE}"),
+    }
+}
+
+fn parse_id(id: &str) -> Result<u32> {
+    id.parse()
+        .with_context(|| format!("Invalid account ID {id:?}"))
+}
+
+/// Prints one line per account with the account ID, address and state.
+async fn list(accounts: &Accounts) -> Result<()> {
+    for id in accounts.get_all() {
+        let ctx = accounts
+            .get_account(id)
+            .with_context(|| format!("account {id} not found"))?;
+        if !ctx.is_open().await {
+            println!("{id}\t\tclosed");
+            continue;
+        }
+        let addr = ctx.get_config(Config::Addr).await?.unwrap_or_default();
+        let state = if ctx.is_configured().await? {
+            "configured"
+        } else {
+            "unconfigured"
+        };
+        println!("{id}\t{addr}\t{state}");
+    }
+    Ok(())
+}
+
+async fn configure(accounts: &Accounts, id: u32, addr: &str, password: &str) -> Result<()> {
+    let ctx = accounts
+        .get_account(id)
+        .with_context(|| format!("account {id} not found"))?;
+    ctx.set_config(Config::Addr, Some(addr)).await?;
+    ctx.set_config(Config::MailPw, Some(password)).await?;
+    ctx.configure().await?;
+    println!("{id}\t{addr}\tconfigured");
+    Ok(())
+}
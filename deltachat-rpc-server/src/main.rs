@@ -1,125 +1,696 @@
 use std::env;
 ///! Delta Chat core RPC server.
 ///!
-///! It speaks JSON Lines over stdio.
+///! It speaks JSON Lines over stdio or, with `--listen`, over TCP or Unix sockets.
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Context as _, Result};
+use anyhow::{anyhow, bail, Context as _, Result};
 use deltachat::constants::DC_VERSION_STR;
-use deltachat_jsonrpc::api::events::event_to_json_rpc_notification;
+use deltachat::{Event, EventType};
+use deltachat_jsonrpc::api::batch::{
+    batch_response, handle_batch_request, parse_batch, INVALID_BATCH_RESPONSE,
+};
+use deltachat_jsonrpc::api::events::{event_to_json_rpc_notification, log_notification};
+use deltachat_jsonrpc::api::metrics::Metrics;
 use deltachat_jsonrpc::api::{Accounts, CommandApi};
 use futures_lite::stream::StreamExt;
-use tokio::io::{self, AsyncBufReadExt, BufReader};
-use tokio::sync::RwLock;
-use tokio::task::JoinHandle;
+use log::LevelFilter;
+use tokio::io::{
+    self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, RwLock, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
 use tokio_util::sync::CancellationToken;
 use yerpc::{RpcClient, RpcSession};
 
+mod accounts;
+
+/// Number of events buffered for each connection before the oldest ones are dropped.
+const EVENTS_CAPACITY: usize = 1000;
+
+/// Default number of requests of a connection handled concurrently,
+/// see `--max-concurrent-requests`.
+const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 100;
+
+/// Options applying to all connections.
+#[derive(Clone, Debug)]
+struct ConnectionOptions {
+    /// Number of requests handled concurrently, see `--max-concurrent-requests`.
+    max_requests: usize,
+
+    /// Whether core log messages are sent as `log` notifications, see `--log-notifications`.
+    log_notifications: bool,
+
+    /// Metrics shared by all connections.
+    metrics: Metrics,
+
+    /// Account the clients are restricted to, see `--single-account`.
+    single_account: Option<u32>,
+}
+
+/// Where the server accepts JSON-RPC clients.
+#[derive(Debug)]
+enum Listen {
+    /// A single client on stdio.
+    Stdio,
+
+    /// Clients connecting to a TCP address, `tcp:HOST:PORT`.
+    Tcp(String),
+
+    /// Clients connecting to a Unix socket, `unix:/path/socket`.
+    Unix(PathBuf),
+}
+
+impl Listen {
+    fn parse(addr: &str) -> Result<Self> {
+        if let Some(addr) = addr.strip_prefix("tcp:") {
+            Ok(Listen::Tcp(addr.to_string()))
+        } else if let Some(path) = addr.strip_prefix("unix:") {
+            Ok(Listen::Unix(PathBuf::from(path)))
+        } else {
+            bail!("Invalid address {addr:?}, expected tcp:HOST:PORT or unix:/path/socket")
+        }
+    }
+}
+
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<()> {
     let mut args = env::args_os();
     let _program_name = args.next().context("no command line arguments found")?;
-    if let Some(first_arg) = args.next() {
-        if first_arg.to_str() == Some("--version") {
-            if let Some(arg) = args.next() {
-                return Err(anyhow!("Unrecognized argument {:?}", arg));
+    let mut log_account = None;
+    let mut log_level = None;
+    let mut listen = Listen::Stdio;
+    let mut max_requests = DEFAULT_MAX_CONCURRENT_REQUESTS;
+    let mut log_notifications = false;
+    let mut metrics_http = None;
+    let mut single_account = None;
+    let mut first = true;
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--version") if first => {
+                if let Some(arg) = args.next() {
+                    return Err(anyhow!("Unrecognized argument {:?}", arg));
+                }
+                eprintln!("{}", &*DC_VERSION_STR);
+                return Ok(());
             }
-            eprintln!("{}", &*DC_VERSION_STR);
-            return Ok(());
-        } else {
-            return Err(anyhow!("Unrecognized option {:?}", first_arg));
+            Some("accounts") if first => return accounts::run(args).await,
+            Some("--call") if first => {
+                let request = args
+                    .next()
+                    .context("--call requires a JSON-RPC request as an argument")?
+                    .into_string()
+                    .map_err(|arg| anyhow!("Invalid request {:?}", arg))?;
+                if let Some(arg) = args.next() {
+                    return Err(anyhow!("Unrecognized argument {:?}", arg));
+                }
+                return call(&request).await;
+            }
+            Some("--log-account") => {
+                let id = args
+                    .next()
+                    .and_then(|id| id.to_str()?.parse::<u32>().ok())
+                    .context("--log-account requires an account ID as an argument")?;
+                log_account = Some(id);
+            }
+            Some("--log-level") => {
+                let level = args
+                    .next()
+                    .and_then(|level| level.to_str()?.parse::<LevelFilter>().ok())
+                    .context("--log-level requires a log level as an argument")?;
+                log_level = Some(level);
+            }
+            Some("--log-notifications") => log_notifications = true,
+            Some("--metrics-http") => {
+                let addr = args
+                    .next()
+                    .and_then(|addr| addr.into_string().ok())
+                    .context("--metrics-http requires an address as an argument")?;
+                metrics_http = Some(addr);
+            }
+            Some("--single-account") => {
+                let id = args
+                    .next()
+                    .and_then(|id| id.to_str()?.parse::<u32>().ok())
+                    .context("--single-account requires an account ID as an argument")?;
+                single_account = Some(id);
+            }
+            Some("--listen") => {
+                let addr = args
+                    .next()
+                    .and_then(|addr| addr.into_string().ok())
+                    .context("--listen requires an address as an argument")?;
+                listen = Listen::parse(&addr)?;
+            }
+            Some("--max-concurrent-requests") => {
+                max_requests = args
+                    .next()
+                    .and_then(|max| max.to_str()?.parse::<usize>().ok())
+                    .filter(|max| *max > 0)
+                    .context(
+                        "--max-concurrent-requests requires a positive number as an argument",
+                    )?;
+            }
+            _ => return Err(anyhow!("Unrecognized option {:?}", arg)),
         }
-    }
-    if let Some(arg) = args.next() {
-        return Err(anyhow!("Unrecognized argument {:?}", arg));
+        first = false;
     }
 
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    init_logger(log_account, log_level);
 
     let path = std::env::var("DC_ACCOUNTS_PATH").unwrap_or_else(|_| "accounts".to_string());
     log::info!("Starting with accounts directory `{}`.", path);
     let accounts = Accounts::new(PathBuf::from(&path)).await?;
+    if let Some(id) = single_account {
+        if accounts.get_account(id).is_none() {
+            bail!("Account {id} does not exist");
+        }
+        log::info!("Restricting clients to account {id}.");
+    }
     let events = accounts.get_event_emitter();
-
-    log::info!("Creating JSON-RPC API.");
     let accounts = Arc::new(RwLock::new(accounts));
-    let state = CommandApi::from_arc(accounts.clone());
+    let options = ConnectionOptions {
+        max_requests,
+        log_notifications,
+        metrics: Metrics::default(),
+        single_account,
+    };
+
+    // The connections share the state of the accounts, e.g. blob tokens and uploads.
+    let mut api = CommandApi::from_arc(accounts.clone()).with_metrics(options.metrics.clone());
+    if let Some(id) = single_account {
+        api = api.with_single_account(id);
+    }
+
+    // Events task distributes core events to the connections.
+    // Connections only receive events emitted after they are established.
+    let (events_sender, stdio_events) = broadcast::channel(EVENTS_CAPACITY);
+    let events_task: JoinHandle<()> = {
+        let events_sender = events_sender.clone();
+        let metrics = options.metrics.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                metrics.observe_event(&event);
+                if !log_notifications || log_notification(&event).is_none() {
+                    log_event(&event);
+                }
+                // There may be no connection at the moment.
+                events_sender.send(event).ok();
+            }
+        })
+    };
+
+    let canceler = CancellationToken::new();
+    {
+        let canceler = canceler.clone();
+        tokio::spawn(async move {
+            if let Err(err) = wait_for_shutdown_signal().await {
+                log::error!("Failed to wait for shutdown signal: {err:#}.");
+            }
+            canceler.cancel();
+        });
+    }
+
+    if let Some(addr) = metrics_http {
+        let listener = TcpListener::bind(&addr)
+            .await
+            .with_context(|| format!("Failed to listen for metrics on {addr}"))?;
+        log::info!(
+            "Serving metrics on http://{}/metrics.",
+            listener.local_addr()?
+        );
+        tokio::spawn(serve_metrics(
+            listener,
+            options.metrics.clone(),
+            canceler.clone(),
+        ));
+    }
+
+    match listen {
+        Listen::Stdio => {
+            serve_connection(
+                api.for_connection(),
+                io::stdin(),
+                io::stdout(),
+                stdio_events,
+                options,
+                canceler.clone(),
+            )
+            .await?;
+        }
+        Listen::Tcp(addr) => {
+            drop(stdio_events);
+            let listener = TcpListener::bind(&addr)
+                .await
+                .with_context(|| format!("Failed to listen on {addr}"))?;
+            log::info!("Listening on tcp:{}.", listener.local_addr()?);
+            let listener = Listener::Tcp(listener);
+            accept_connections(listener, &api, &events_sender, &options, &canceler).await;
+        }
+        Listen::Unix(path) => {
+            drop(stdio_events);
+            let listener = bind_unix(&path)?;
+            log::info!("Listening on unix:{}.", path.display());
+            accept_connections(listener, &api, &events_sender, &options, &canceler).await;
+            std::fs::remove_file(&path).ok();
+        }
+    }
+
+    // See "Thread safety" section in deltachat-ffi/deltachat.h for explanation.
+    canceler.cancel();
+    accounts.read().await.stop_io().await;
+    events_task.abort();
+
+    Ok(())
+}
+
+/// Waits for ctrl-c or, on Unix, SIGTERM.
+async fn wait_for_shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
 
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => {
+                res?;
+                log::info!("got ctrl-c event");
+            }
+            _ = sigterm.recv() => log::info!("got SIGTERM"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await?;
+        log::info!("got ctrl-c event");
+    }
+    Ok(())
+}
+
+type BoxedReader = Box<dyn AsyncRead + Unpin + Send>;
+type BoxedWriter = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// Listening socket accepting JSON-RPC clients.
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl Listener {
+    /// Accepts a client, returning the halves of the connection and the peer address for logging.
+    async fn accept(&self) -> io::Result<(BoxedReader, BoxedWriter, String)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                let (reader, writer) = stream.into_split();
+                Ok((Box::new(reader), Box::new(writer), peer.to_string()))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                let (reader, writer) = stream.into_split();
+                Ok((Box::new(reader), Box::new(writer), format!("{peer:?}")))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path) -> Result<Listener> {
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen on {}", path.display()))?;
+    Ok(Listener::Unix(listener))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(_path: &std::path::Path) -> Result<Listener> {
+    bail!("Unix sockets are not supported on this platform")
+}
+
+/// Accepts clients until the server shuts down, then waits for all connections to close.
+async fn accept_connections(
+    listener: Listener,
+    api: &CommandApi,
+    events_sender: &broadcast::Sender<Event>,
+    options: &ConnectionOptions,
+    canceler: &CancellationToken,
+) {
+    let mut connections = JoinSet::new();
+    loop {
+        let (reader, writer, peer) = tokio::select! {
+            _ = canceler.cancelled() => break,
+            res = listener.accept() => match res {
+                Ok(res) => res,
+                Err(err) => {
+                    // Avoid busy looping on errors such as too many open files.
+                    log::warn!("Failed to accept connection: {err:#}.");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            Some(res) = connections.join_next(), if !connections.is_empty() => {
+                log_connection_result(res);
+                continue;
+            }
+        };
+        log::info!("Accepted connection from {peer}.");
+        connections.spawn(serve_connection(
+            api.for_connection(),
+            reader,
+            writer,
+            events_sender.subscribe(),
+            options.clone(),
+            canceler.clone(),
+        ));
+    }
+    while let Some(res) = connections.join_next().await {
+        log_connection_result(res);
+    }
+}
+
+fn log_connection_result(res: Result<Result<()>, tokio::task::JoinError>) {
+    match res {
+        Ok(Ok(())) => log::info!("Connection closed."),
+        Ok(Err(err)) => log::warn!("Connection failed: {err:#}."),
+        Err(err) => log::error!("Connection task failed: {err:#}."),
+    }
+}
+
+/// Serves a single JSON-RPC client until it disconnects or the server shuts down.
+///
+/// Each connection has its own [`RpcSession`], chat event subscriptions and event filter,
+/// see [`CommandApi::for_connection`],
+/// and receives the core events passing the filter as notifications.
+///
+/// At most `options.max_requests` requests are handled concurrently.
+/// Once the limit is reached, no more requests are read until one finishes,
+/// so a client sending requests faster than they are handled is slowed down
+/// instead of making the server buffer an unlimited number of requests.
+///
+/// With `options.log_notifications`, core log messages are sent as `log` notifications
+/// instead of `Info`, `Warning` and `Error` events.
+///
+/// With `options.single_account`, the client can only use this account
+/// and only receives its events.
+async fn serve_connection(
+    state: CommandApi,
+    reader: impl AsyncRead + Unpin + Send + 'static,
+    mut writer: impl AsyncWrite + Unpin + Send + 'static,
+    mut events: broadcast::Receiver<Event>,
+    options: ConnectionOptions,
+    canceler: CancellationToken,
+) -> Result<()> {
+    let ConnectionOptions {
+        max_requests,
+        log_notifications,
+        metrics,
+        single_account,
+    } = options;
+    let rpc_stats = state.rpc_stats();
+    rpc_stats.set_max_concurrent_requests(max_requests);
+    let request_permits = Arc::new(Semaphore::new(max_requests));
     let (client, mut out_receiver) = RpcClient::new();
     let session = RpcSession::new(client.clone(), state.clone());
-    let canceler = CancellationToken::new();
+    let chat_subscriptions = state.chat_subscriptions();
+    let event_filter = state.event_filter();
+
+    // Stops the tasks of this connection when it is closed, also on errors.
+    let done = canceler.child_token();
+    let _done_guard = done.clone().drop_guard();
 
     // Events task converts core events to JSON-RPC notifications.
+    let events_done = done.clone();
+    let events_metrics = metrics.clone();
     let events_task: JoinHandle<Result<()>> = tokio::spawn(async move {
-        let mut r = Ok(());
-        while let Some(event) = events.recv().await {
-            if r.is_err() {
+        loop {
+            let event = tokio::select! {
+                _ = events_done.cancelled() => break,
+                event = events.recv() => match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        log::warn!("Client is too slow, dropped {count} events.");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            };
+            events_metrics.set_event_queue_depth(events.len());
+            if single_account.map_or(false, |id| id != event.id) {
+                continue;
+            }
+            if let Some(notification) = chat_subscriptions.chat_event_notification(&event).await {
+                client
+                    .send_notification("chatEvent", Some(notification))
+                    .await?;
+            }
+            if log_notifications {
+                if let Some(notification) = log_notification(&event) {
+                    client.send_notification("log", Some(notification)).await?;
+                    continue;
+                }
+            }
+            if !event_filter.matches(&event).await {
                 continue;
             }
             let event = event_to_json_rpc_notification(event);
-            r = client.send_notification("event", Some(event)).await;
+            client.send_notification("event", Some(event)).await?;
         }
-        r?;
         Ok(())
     });
 
-    // Send task prints JSON responses to stdout.
-    let cancelable = canceler.clone();
+    // Responses to batch requests are written as a single line.
+    let (batch_sender, mut batch_receiver) = mpsc::unbounded_channel::<String>();
+
+    // Send task writes JSON responses to the client.
+    let send_done = done.clone();
+    let send_metrics = metrics.clone();
     let send_task: JoinHandle<anyhow::Result<()>> = tokio::spawn(async move {
         loop {
             let message = tokio::select! {
-                _ = cancelable.cancelled() => break,
+                _ = send_done.cancelled() => break,
                 message = out_receiver.next() => match message {
                     None => break,
                     Some(message) => serde_json::to_string(&message)?,
-                }
+                },
+                Some(batch) = batch_receiver.recv() => batch,
             };
             log::trace!("RPC send {}", message);
-            println!("{message}");
+            writer.write_all(message.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+            writer.flush().await?;
+            send_metrics.message_sent();
         }
         Ok(())
     });
 
-    // Receiver task reads JSON requests from stdin.
-    let recv_task: JoinHandle<anyhow::Result<()>> = tokio::spawn(async move {
-        let stdin = io::stdin();
-        let mut lines = BufReader::new(stdin).lines();
-        loop {
-            let message = tokio::select! {
-                _ = tokio::signal::ctrl_c() => {
-                    log::info!("got ctrl-c event");
+    // Read JSON requests from the client.
+    let mut lines = BufReader::new(reader).lines();
+    'read: loop {
+        let message = tokio::select! {
+            _ = canceler.cancelled() => break,
+            message = lines.next_line() => match message? {
+                None => {
+                    log::info!("EOF reached");
                     break;
                 }
-                message = lines.next_line() => match message? {
-                    None => {
-                        log::info!("EOF reached on stdin");
-                        break;
-                    }
-                    Some(message) => message,
-                }
+                Some(message) => message,
+            }
+        };
+        log::trace!("RPC recv {}", message);
+        metrics.message_received();
+        let (requests, is_batch) = match parse_batch(&message) {
+            Some(requests) if requests.is_empty() => {
+                batch_sender.send(INVALID_BATCH_RESPONSE.to_string()).ok();
+                continue;
+            }
+            Some(requests) => (requests, true),
+            None => (vec![message], false),
+        };
+
+        // Every request of a batch counts against `--max-concurrent-requests`.
+        let mut batch_responses = Vec::new();
+        for request in requests {
+            let waiting = rpc_stats.request_queued();
+            let permit = tokio::select! {
+                _ = canceler.cancelled() => break 'read,
+                permit = request_permits.clone().acquire_owned() => permit?,
             };
-            log::trace!("RPC recv {}", message);
+            drop(waiting);
+            let running = rpc_stats.request_started();
             let session = session.clone();
-            tokio::spawn(async move {
-                session.handle_incoming(&message).await;
+            let state = state.clone();
+            let metrics = metrics.clone();
+            let handle = tokio::spawn(async move {
+                let method = request_method(&request);
+                let start = Instant::now();
+                let response = if is_batch {
+                    handle_batch_request(state, &request).await
+                } else {
+                    session.handle_incoming(&request).await;
+                    None
+                };
+                if let Some(method) = method {
+                    metrics.request_handled(&method, start.elapsed());
+                }
+                drop(running);
+                drop(permit);
+                response
             });
+            if is_batch {
+                batch_responses.push(handle);
+            }
         }
-        Ok(())
-    });
 
-    // Wait for the end of stdin / ctrl-c.
-    recv_task.await?.ok();
+        if is_batch {
+            let batch_sender = batch_sender.clone();
+            tokio::spawn(async move {
+                let mut responses = Vec::new();
+                for handle in batch_responses {
+                    responses.push(handle.await.ok().flatten());
+                }
+                // There is no response if the batch only contains notifications.
+                if let Some(response) = batch_response(responses) {
+                    batch_sender.send(response).ok();
+                }
+            });
+        }
+    }
 
-    // See "Thread safety" section in deltachat-ffi/deltachat.h for explanation.
-    // NB: Events are drained by events_task.
-    canceler.cancel();
-    accounts.read().await.stop_io().await;
-    drop(state);
+    done.cancel();
     let (r0, r1) = tokio::join!(events_task, send_task);
     for r in [r0, r1] {
         r??;
     }
+    Ok(())
+}
+
+/// Returns the method of a JSON-RPC request, `None` for batches and invalid requests.
+fn request_method(message: &str) -> Option<String> {
+    let request: serde_json::Value = serde_json::from_str(message).ok()?;
+    Some(request.get("method")?.as_str()?.to_string())
+}
+
+/// Serves the metrics in the Prometheus text format at `/metrics` until the server shuts down.
+async fn serve_metrics(listener: TcpListener, metrics: Metrics, canceler: CancellationToken) {
+    loop {
+        let mut stream = tokio::select! {
+            _ = canceler.cancelled() => break,
+            res = listener.accept() => match res {
+                Ok((stream, _peer)) => stream,
+                Err(err) => {
+                    log::warn!("Failed to accept metrics connection: {err:#}.");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(err) = respond_metrics(&mut stream, &metrics).await {
+                log::warn!("Failed to serve metrics: {err:#}.");
+            }
+        });
+    }
+}
+
+async fn respond_metrics(stream: &mut TcpStream, metrics: &Metrics) -> Result<()> {
+    // Only the request line is needed, headers are ignored.
+    let mut request_line = String::new();
+    let mut reader = BufReader::new((&mut *stream).take(8192));
+    tokio::time::timeout(Duration::from_secs(10), reader.read_line(&mut request_line)).await??;
+    drop(reader);
+
+    let response = if request_line.starts_with("GET /metrics ") {
+        let body = metrics.to_prometheus();
+        format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Initializes the logger.
+///
+/// Log messages of the core are logged with the `account::<id>::core` target
+/// and are disabled unless enabled with `RUST_LOG`, `--log-account` or `--log-level`.
+/// `--log-level` without `--log-account` sets the level for all accounts.
+fn init_logger(log_account: Option<u32>, log_level: Option<LevelFilter>) {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(LevelFilter::Info);
+    builder.filter_module("account::", LevelFilter::Off);
+    builder.parse_env(env_logger::Env::default());
+    match (log_account, log_level) {
+        (Some(id), level) => {
+            // Trailing separator, so account 3 does not match account 30.
+            builder.filter_module(
+                &format!("account::{id}::"),
+                level.unwrap_or(LevelFilter::Trace),
+            );
+        }
+        (None, Some(level)) => {
+            builder.filter_module("account::", level);
+        }
+        (None, None) => {}
+    }
+    builder.init();
+}
+
+/// Logs core log messages and, with trace level, all other events.
+///
+/// With `--log-notifications`, core log messages are sent to the clients instead.
+fn log_event(event: &Event) {
+    let target = format!("account::{}::core", event.id);
+    match &event.typ {
+        EventType::Info(msg) => log::info!(target: &target, "{msg}"),
+        EventType::Warning(msg) => log::warn!(target: &target, "{msg}"),
+        EventType::Error(msg) => log::error!(target: &target, "{msg}"),
+        typ => log::trace!(target: &target, "{typ:?}"),
+    }
+}
+
+/// Executes a single JSON-RPC request and prints the response.
+///
+/// IO is not started, so this is meant for requests
+/// which do not need a network connection, e.g. exporting a backup.
+async fn call(request: &str) -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
 
+    let path = std::env::var("DC_ACCOUNTS_PATH").unwrap_or_else(|_| "accounts".to_string());
+    let accounts = Accounts::new(PathBuf::from(&path)).await?;
+    let accounts = Arc::new(RwLock::new(accounts));
+    let state = CommandApi::from_arc(accounts.clone());
+
+    let (client, mut out_receiver) = RpcClient::new();
+    let session = RpcSession::new(client, state);
+    let request = request.to_string();
+    let handle = tokio::spawn(async move {
+        session.handle_incoming(&request).await;
+    });
+
+    // Notifications have no response, the channel is closed once the session is dropped.
+    let mut failed = false;
+    if let Some(response) = out_receiver.next().await {
+        let response = serde_json::to_value(&response)?;
+        failed = response.get("error").is_some();
+        println!("{response}");
+    }
+    handle.await?;
+    accounts.read().await.stop_io().await;
+
+    if failed {
+        return Err(anyhow!("JSON-RPC request failed"));
+    }
     Ok(())
 }
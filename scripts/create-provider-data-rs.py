@@ -49,7 +49,7 @@ def process_opt(data):
     opt_data = data.get("opt", "")
     for key in opt_data:
         value = str(opt_data[key])
-        if key == "max_smtp_rcpt_to":
+        if key in {"max_smtp_rcpt_to", "max_msg_size"}:
             value = "Some(" + value + ")"
         if value in {"True", "False"}:
             value = value.lower()
@@ -0,0 +1,275 @@
+//! # Account usage statistics.
+//!
+//! Statistics about the messages of the account in a given period,
+//! e.g. for a "year in review" screen.
+//! Everything is computed from the local database with SQL queries,
+//! no data leaves the device.
+
+use anyhow::Result;
+
+use crate::chat::ChatId;
+use crate::constants::{Chattype, DC_CHAT_ID_LAST_SPECIAL};
+use crate::contact::ContactId;
+use crate::context::Context;
+use crate::message::Viewtype;
+
+/// Number of contacts returned in [`AccountStats::top_contacts`].
+const TOP_CONTACTS: usize = 10;
+
+/// Usage statistics of the account in a period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountStats {
+    /// Start of the period, inclusive.
+    pub timestamp_start: i64,
+
+    /// End of the period, exclusive.
+    pub timestamp_end: i64,
+
+    /// Number of messages sent.
+    pub msgs_sent: usize,
+
+    /// Number of messages received.
+    pub msgs_received: usize,
+
+    /// Number of sent and received messages which were end-to-end encrypted.
+    pub msgs_encrypted: usize,
+
+    /// Size of sent and received messages with attachments, in bytes.
+    pub media_bytes: u64,
+
+    /// Messages sent and received per chat, most active chat first.
+    pub chats: Vec<ChatStats>,
+
+    /// Contacts with the most messages exchanged, most active contact first.
+    ///
+    /// Messages received from the contact in any chat
+    /// and messages sent in the 1:1 chat with the contact are counted.
+    pub top_contacts: Vec<ContactStats>,
+}
+
+impl AccountStats {
+    /// Returns the share of end-to-end encrypted messages, between 0.0 and 1.0.
+    pub fn encryption_ratio(&self) -> f64 {
+        let total = self.msgs_sent + self.msgs_received;
+        if total == 0 {
+            0.0
+        } else {
+            self.msgs_encrypted as f64 / total as f64
+        }
+    }
+}
+
+/// Number of messages in a chat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatStats {
+    /// Chat ID.
+    pub chat_id: ChatId,
+
+    /// Number of messages sent to the chat.
+    pub msgs_sent: usize,
+
+    /// Number of messages received in the chat.
+    pub msgs_received: usize,
+}
+
+/// Number of messages exchanged with a contact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContactStats {
+    /// Contact ID.
+    pub contact_id: ContactId,
+
+    /// Number of messages exchanged with the contact.
+    pub msgs: usize,
+}
+
+/// Returns usage statistics of the messages
+/// sent or received from `timestamp_start` (inclusive) to `timestamp_end` (exclusive).
+///
+/// Info messages, hidden messages and messages in the device chat are not counted.
+pub async fn get_account_stats(
+    context: &Context,
+    timestamp_start: i64,
+    timestamp_end: i64,
+) -> Result<AccountStats> {
+    // Messages from real contacts or from self in normal chats.
+    let msgs_condition = "chat_id>?1 AND hidden=0 AND timestamp>=?2 AND timestamp<?3
+         AND (from_id=?4 OR from_id>?5)";
+    let params = (
+        DC_CHAT_ID_LAST_SPECIAL,
+        timestamp_start,
+        timestamp_end,
+        ContactId::SELF,
+        ContactId::LAST_SPECIAL,
+    );
+
+    let (msgs_sent, msgs_received, msgs_encrypted, media_bytes) = context
+        .sql
+        .query_row(
+            &format!(
+                "SELECT IFNULL(SUM(from_id=?4), 0),
+                        IFNULL(SUM(from_id!=?4), 0),
+                        IFNULL(SUM(INSTR(CHAR(10) || param || CHAR(10),
+                                         CHAR(10) || 'c=1' || CHAR(10)) > 0), 0),
+                        IFNULL(SUM(CASE WHEN type NOT IN (?6, ?7) THEN bytes ELSE 0 END), 0)
+                 FROM msgs WHERE {msgs_condition}"
+            ),
+            (
+                params.0,
+                params.1,
+                params.2,
+                params.3,
+                params.4,
+                Viewtype::Unknown,
+                Viewtype::Text,
+            ),
+            |row| {
+                let msgs_sent: usize = row.get(0)?;
+                let msgs_received: usize = row.get(1)?;
+                let msgs_encrypted: usize = row.get(2)?;
+                let media_bytes: i64 = row.get(3)?;
+                Ok((msgs_sent, msgs_received, msgs_encrypted, media_bytes))
+            },
+        )
+        .await?;
+
+    let chats = context
+        .sql
+        .query_map(
+            &format!(
+                "SELECT chat_id, SUM(from_id=?4), SUM(from_id!=?4)
+                 FROM msgs WHERE {msgs_condition}
+                 GROUP BY chat_id
+                 ORDER BY COUNT(*) DESC, chat_id"
+            ),
+            params,
+            |row| {
+                Ok(ChatStats {
+                    chat_id: row.get(0)?,
+                    msgs_sent: row.get(1)?,
+                    msgs_received: row.get(2)?,
+                })
+            },
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await?;
+
+    let top_contacts = context
+        .sql
+        .query_map(
+            &format!(
+                "SELECT contact_id, COUNT(*) AS cnt FROM (
+                   SELECT from_id AS contact_id FROM msgs
+                   WHERE {msgs_condition} AND from_id!=?4
+                   UNION ALL
+                   SELECT chats_contacts.contact_id FROM msgs
+                   INNER JOIN chats ON chats.id=msgs.chat_id AND chats.type=?6
+                   INNER JOIN chats_contacts ON chats_contacts.chat_id=msgs.chat_id
+                   WHERE {msgs_condition} AND from_id=?4 AND chats_contacts.contact_id>?5
+                 )
+                 GROUP BY contact_id
+                 ORDER BY cnt DESC, contact_id
+                 LIMIT ?7"
+            ),
+            (
+                params.0,
+                params.1,
+                params.2,
+                params.3,
+                params.4,
+                Chattype::Single,
+                TOP_CONTACTS,
+            ),
+            |row| {
+                Ok(ContactStats {
+                    contact_id: row.get(0)?,
+                    msgs: row.get(1)?,
+                })
+            },
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await?;
+
+    Ok(AccountStats {
+        timestamp_start,
+        timestamp_end,
+        msgs_sent,
+        msgs_received,
+        msgs_encrypted,
+        media_bytes: u64::try_from(media_bytes).unwrap_or_default(),
+        chats,
+        top_contacts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Message;
+    use crate::test_utils::TestContextManager;
+    use crate::tools::time;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_account_stats() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let fiona = tcm.fiona().await;
+        let start = time() - 10;
+
+        let bob_chat_id = alice.create_chat(&bob).await.id;
+        tcm.send_recv_accept(&alice, &bob, "Hi Bob").await;
+        tcm.send_recv_accept(&bob, &alice, "Hi Alice").await;
+        tcm.send_recv_accept(&bob, &alice, "How are you?").await;
+        let fiona_chat_id = alice.create_chat(&fiona).await.id;
+        tcm.send_recv_accept(&fiona, &alice, "Hello").await;
+
+        let file = alice.get_blobdir().join("file.txt");
+        tokio::fs::write(&file, b"hello").await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        alice.send_msg(fiona_chat_id, &mut msg).await;
+
+        let stats = get_account_stats(&alice, start, time() + 10).await?;
+        assert_eq!(stats.msgs_sent, 2);
+        assert_eq!(stats.msgs_received, 3);
+        assert!(stats.msgs_encrypted > 0);
+        assert!(stats.encryption_ratio() <= 1.0);
+        assert!(stats.media_bytes > 0);
+        assert_eq!(
+            stats.chats,
+            vec![
+                ChatStats {
+                    chat_id: bob_chat_id,
+                    msgs_sent: 1,
+                    msgs_received: 2
+                },
+                ChatStats {
+                    chat_id: fiona_chat_id,
+                    msgs_sent: 1,
+                    msgs_received: 1
+                }
+            ]
+        );
+        let bob_id = alice.add_or_lookup_contact(&bob).await.id;
+        let fiona_id = alice.add_or_lookup_contact(&fiona).await.id;
+        assert_eq!(
+            stats.top_contacts,
+            vec![
+                ContactStats {
+                    contact_id: bob_id,
+                    msgs: 3
+                },
+                ContactStats {
+                    contact_id: fiona_id,
+                    msgs: 2
+                }
+            ]
+        );
+
+        let stats = get_account_stats(&alice, 0, start).await?;
+        assert_eq!(stats.msgs_sent + stats.msgs_received, 0);
+        assert!(stats.chats.is_empty());
+        assert_eq!(stats.encryption_ratio(), 0.0);
+        Ok(())
+    }
+}
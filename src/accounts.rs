@@ -2,12 +2,14 @@
 
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{ensure, Context as _, Result};
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
+use zeroize::Zeroizing;
 
 use crate::context::Context;
 use crate::events::{Event, EventEmitter, EventType, Events};
@@ -23,6 +25,12 @@ pub struct Accounts {
     /// Event channel to emit account manager errors.
     events: Events,
 
+    /// Accounts closed by [`Accounts::lock`] and not unlocked yet,
+    /// mapped to whether IO was running before locking.
+    ///
+    /// `None` if the accounts are not locked.
+    locked: Option<BTreeMap<u32, bool>>,
+
     /// Stock string translations shared by all created contexts.
     ///
     /// This way changing a translation for one context automatically
@@ -74,6 +82,7 @@ pub async fn open(dir: PathBuf) -> Result<Self> {
             config,
             accounts,
             events,
+            locked: None,
             stockstrings,
         })
     }
@@ -141,16 +150,37 @@ pub async fn add_closed_account(&mut self) -> Result<u32> {
 
     /// Remove an account.
     pub async fn remove_account(&mut self, id: u32) -> Result<()> {
+        self.purge_account(id, false).await
+    }
+
+    /// Removes an account, optionally shredding its data.
+    ///
+    /// If `shred` is true, the database, its WAL and all blobs are overwritten with zeros
+    /// before they are unlinked. Together with `secure_delete` being enabled for the database,
+    /// which zeroes deleted content while the account is used, this makes the data of removed
+    /// accounts unrecoverable from the file system. Note that on flash storage overwriting is
+    /// best effort as the storage controller may keep copies of old blocks.
+    pub async fn purge_account(&mut self, id: u32, shred: bool) -> Result<()> {
         let ctx = self
             .accounts
             .remove(&id)
             .with_context(|| format!("no account with id {id}"))?;
         ctx.stop_io().await;
+        // Close the database so that no data is written to it while it is overwritten.
+        ctx.sql.close().await;
         drop(ctx);
+        if let Some(locked) = &mut self.locked {
+            locked.remove(&id);
+        }
 
         if let Some(cfg) = self.config.get_account(id) {
             let account_path = self.dir.join(cfg.dir);
 
+            if shred {
+                shred_dir(&account_path)
+                    .await
+                    .context("failed to shred account data")?;
+            }
             fs::remove_dir_all(&account_path)
                 .await
                 .context("failed to remove account data")?;
@@ -269,6 +299,83 @@ pub async fn stop_io(&self) {
         }
     }
 
+    /// Locks the accounts, e.g. when the app lock of the UI is activated.
+    ///
+    /// Stops IO and closes the databases of all encrypted accounts,
+    /// so neither the data nor the database keys are kept in memory
+    /// until [`Accounts::unlock`] is called with the correct passphrase.
+    /// Unencrypted accounts cannot be protected and stay open.
+    ///
+    /// Emits [`EventType::AccountsLocked`].
+    pub async fn lock(&mut self) -> Result<()> {
+        ensure!(self.locked.is_none(), "accounts are already locked");
+
+        let mut locked = BTreeMap::new();
+        for (id, account) in &self.accounts {
+            if account.sql.is_encrypted().await != Some(true) {
+                continue;
+            }
+            let io_running = account.scheduler.is_running().await;
+            account.stop_io().await;
+            account.sql.close().await;
+            // The cache contains config values such as the mail password.
+            account.sql.config_cache.write().await.clear();
+            locked.insert(*id, io_running);
+        }
+        info!(self, "Locked {} accounts.", locked.len());
+        self.locked = Some(locked);
+        self.emit_event(EventType::AccountsLocked);
+        Ok(())
+    }
+
+    /// Unlocks the accounts locked with [`Accounts::lock`].
+    ///
+    /// Opens all locked accounts which can be decrypted with `passphrase`
+    /// and restarts IO if it was running before.
+    /// If accounts use different passphrases, call this once for each passphrase.
+    ///
+    /// Returns true and emits [`EventType::AccountsUnlocked`]
+    /// once no locked accounts are left.
+    ///
+    /// Copies of the passphrase are zeroized once they are not needed anymore.
+    pub async fn unlock(&mut self, passphrase: String) -> Result<bool> {
+        let passphrase = Zeroizing::new(passphrase);
+        let locked = match &mut self.locked {
+            Some(locked) => locked,
+            None => return Ok(true),
+        };
+
+        let mut unlocked = Vec::new();
+        for (id, io_running) in locked.iter() {
+            let account = match self.accounts.get(id) {
+                Some(account) => account,
+                None => continue,
+            };
+            if account.open(passphrase.to_string()).await? {
+                if *io_running {
+                    account.start_io().await;
+                }
+                unlocked.push(*id);
+            }
+        }
+        for id in unlocked {
+            locked.remove(&id);
+        }
+
+        if locked.is_empty() {
+            self.locked = None;
+            self.emit_event(EventType::AccountsUnlocked);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Returns true if the accounts are locked with [`Accounts::lock`].
+    pub fn is_locked(&self) -> bool {
+        self.locked.is_some()
+    }
+
     /// Notifies all accounts that the network may have become available.
     pub async fn maybe_network(&self) {
         for account in self.accounts.values() {
@@ -285,7 +392,19 @@ pub async fn maybe_network_lost(&self) {
 
     /// Emits a single event.
     pub fn emit_event(&self, event: EventType) {
-        self.events.emit(Event { id: 0, typ: event })
+        self.events.emit(Event {
+            id: 0,
+            seq: 0,
+            typ: event,
+        })
+    }
+
+    /// Sets the interval in which `MsgsChanged` and `ChatModified` events
+    /// of the same chat are coalesced, zero to disable coalescing.
+    ///
+    /// See [`Events::set_coalescing_interval`].
+    pub fn set_event_coalescing_interval(&self, interval: Duration) {
+        self.events.set_coalescing_interval(interval);
     }
 
     /// Returns event emitter.
@@ -506,6 +625,40 @@ pub fn dbfile(&self, accounts_dir: &Path) -> std::path::PathBuf {
     }
 }
 
+/// Overwrites all files in the directory and its subdirectories with zeros.
+async fn shred_dir(dir: &Path) -> Result<()> {
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut read_dir = fs::read_dir(&dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+            } else if file_type.is_file() {
+                shred_file(&entry.path())
+                    .await
+                    .with_context(|| format!("failed to shred {}", entry.path().display()))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Overwrites the file with zeros and flushes it to the disk.
+async fn shred_file(path: &Path) -> Result<()> {
+    let len = fs::metadata(path).await?.len();
+    let mut file = fs::OpenOptions::new().write(true).open(path).await?;
+    let zeros = vec![0u8; 64 * 1024];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(zeros.len() as u64);
+        file.write_all(&zeros[..n as usize]).await?;
+        remaining -= n;
+    }
+    file.sync_all().await?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -785,6 +938,71 @@ async fn test_encrypted_account() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_lock_unlock() -> Result<()> {
+        let dir = tempfile::tempdir().context("failed to create tempdir")?;
+        let p: PathBuf = dir.path().join("accounts");
+        let mut accounts = Accounts::new(p.clone()).await?;
+
+        let encrypted_id = accounts.add_closed_account().await?;
+        let encrypted = accounts.get_account(encrypted_id).unwrap();
+        assert!(encrypted.open("foobar".to_string()).await?);
+        let unencrypted_id = accounts.add_account().await?;
+        let unencrypted = accounts.get_account(unencrypted_id).unwrap();
+
+        assert!(!accounts.is_locked());
+        accounts.lock().await?;
+        assert!(accounts.is_locked());
+        assert!(accounts.lock().await.is_err());
+        assert!(!encrypted.is_open().await);
+        assert!(unencrypted.is_open().await);
+
+        assert!(!accounts.unlock("barfoo".to_string()).await?);
+        assert!(accounts.is_locked());
+        assert!(!encrypted.is_open().await);
+
+        assert!(accounts.unlock("foobar".to_string()).await?);
+        assert!(!accounts.is_locked());
+        assert!(encrypted.is_open().await);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_shred_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("secret");
+        let data = b"secret data".repeat(10_000);
+        fs::write(&path, &data).await?;
+
+        shred_file(&path).await?;
+        let shredded = fs::read(&path).await?;
+        assert_eq!(shredded.len(), data.len());
+        assert!(shredded.iter().all(|&b| b == 0));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_purge_account() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let p: PathBuf = dir.path().join("accounts");
+        let mut accounts = Accounts::new(p.clone()).await?;
+
+        let id = accounts.add_account().await?;
+        let ctx = accounts.get_account(id).unwrap();
+        ctx.set_config(crate::config::Config::Displayname, Some("secret"))
+            .await?;
+        let account_dir = ctx.get_dbfile().parent().unwrap().to_path_buf();
+        let blob = crate::blob::BlobObject::create(&ctx, "file.txt", b"secret blob").await?;
+        assert!(blob.to_abs_path().exists());
+        drop(ctx);
+
+        accounts.purge_account(id, true).await?;
+        assert!(!account_dir.exists());
+        assert!(accounts.get_account(id).is_none());
+        assert!(accounts.purge_account(id, true).await.is_err());
+        Ok(())
+    }
+
     /// Tests that accounts share stock string translations.
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_accounts_share_translations() -> Result<()> {
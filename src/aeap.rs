@@ -0,0 +1,213 @@
+//! # Review of AEAP chat migrations.
+//!
+//! When a verified contact changes their address
+//! using AEAP (Automatic Email Address Porting),
+//! the old contact is replaced with the new one in verified groups and broadcast lists.
+//! Every chat with the old contact is recorded here together with the outcome,
+//! so UIs can show which chats were migrated and which were skipped and why,
+//! and undo a migration that was not wanted.
+
+use anyhow::{ensure, Context as _, Result};
+use deltachat_derive::{FromSql, ToSql};
+use serde::{Deserialize, Serialize};
+
+use crate::chat::{self, ChatId};
+use crate::contact::ContactId;
+use crate::context::Context;
+use crate::peerstate::Peerstate;
+
+/// Outcome of an AEAP transition in a single chat.
+#[derive(
+    Debug,
+    Display,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    FromPrimitive,
+    ToPrimitive,
+    FromSql,
+    ToSql,
+    Serialize,
+    Deserialize,
+)]
+#[repr(u32)]
+pub enum AeapMigrationState {
+    /// The old contact was replaced with the new contact.
+    Migrated = 0,
+
+    /// The migration was undone using [`undo_aeap_migration`].
+    Undone = 1,
+
+    /// The chat is a 1:1 chat, messages to the new address go to a new chat.
+    SkippedSingleChat = 2,
+
+    /// The chat is an unprotected group,
+    /// members can only be replaced automatically in verified groups.
+    SkippedUnprotectedGroup = 3,
+
+    /// The new address is not a valid address.
+    SkippedInvalidAddress = 4,
+
+    /// The chat type does not support migrations, e.g. mailing lists.
+    SkippedUnsupportedChat = 5,
+}
+
+/// Record of an AEAP transition in a single chat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AeapMigration {
+    /// Row ID of the record.
+    pub id: u32,
+
+    /// Timestamp of the message that triggered the transition.
+    pub timestamp: i64,
+
+    /// Chat in which the contact was or would have been replaced.
+    pub chat_id: ChatId,
+
+    /// Contact of the old address.
+    pub old_contact_id: ContactId,
+
+    /// Contact of the new address, `None` if the chat was skipped.
+    pub new_contact_id: Option<ContactId>,
+
+    /// Old address of the contact.
+    pub old_addr: String,
+
+    /// New address of the contact.
+    pub new_addr: String,
+
+    /// Outcome of the transition.
+    pub state: AeapMigrationState,
+
+    /// True if the new contact was a member already before the migration,
+    /// it is not removed when the migration is undone then.
+    pub(crate) new_contact_was_member: bool,
+}
+
+impl AeapMigration {
+    /// Saves a new record to the database.
+    pub(crate) async fn insert(&self, context: &Context) -> Result<()> {
+        context
+            .sql
+            .insert(
+                "INSERT INTO aeap_migrations
+                 (timestamp, chat_id, old_contact_id, new_contact_id, old_addr, new_addr,
+                  state, new_contact_was_member)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                (
+                    self.timestamp,
+                    self.chat_id,
+                    self.old_contact_id,
+                    self.new_contact_id.unwrap_or_default(),
+                    &self.old_addr,
+                    &self.new_addr,
+                    self.state,
+                    self.new_contact_was_member,
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Returns the recorded AEAP transitions, newest first.
+///
+/// If `contact_id` is given, only transitions from or to this contact are returned.
+pub async fn get_aeap_migrations(
+    context: &Context,
+    contact_id: Option<ContactId>,
+) -> Result<Vec<AeapMigration>> {
+    let contact_id = contact_id.unwrap_or_default();
+    context
+        .sql
+        .query_map(
+            "SELECT id, timestamp, chat_id, old_contact_id, new_contact_id, old_addr, new_addr,
+                    state, new_contact_was_member
+             FROM aeap_migrations
+             WHERE ?1=0 OR old_contact_id=?1 OR new_contact_id=?1
+             ORDER BY timestamp DESC, id DESC",
+            (contact_id,),
+            |row| {
+                let new_contact_id: ContactId = row.get(4)?;
+                Ok(AeapMigration {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    chat_id: row.get(2)?,
+                    old_contact_id: row.get(3)?,
+                    new_contact_id: Some(new_contact_id).filter(|id| *id != ContactId::UNDEFINED),
+                    old_addr: row.get(5)?,
+                    new_addr: row.get(6)?,
+                    state: row.get(7)?,
+                    new_contact_was_member: row.get(8)?,
+                })
+            },
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await
+}
+
+/// Undoes an AEAP migration, i.e. puts the old contact back into the chat
+/// in place of the new contact.
+///
+/// The other members are informed like for any other member change,
+/// so their member lists are updated as well.
+/// The old address gets the key of the new address back,
+/// which was moved to the new address by the migration.
+///
+/// Only migrations in state [`AeapMigrationState::Migrated`] can be undone.
+pub async fn undo_aeap_migration(context: &Context, id: u32) -> Result<()> {
+    let migration = get_aeap_migrations(context, None)
+        .await?
+        .into_iter()
+        .find(|migration| migration.id == id)
+        .with_context(|| format!("AEAP migration {id} not found"))?;
+    ensure!(
+        migration.state == AeapMigrationState::Migrated,
+        "AEAP migration {id} is in state {}, cannot undo",
+        migration.state
+    );
+    let new_contact_id = migration
+        .new_contact_id
+        .context("migrated chat has no new contact")?;
+
+    if Peerstate::from_addr(context, &migration.old_addr)
+        .await?
+        .is_none()
+    {
+        if let Some(mut peerstate) = Peerstate::from_addr(context, &migration.new_addr).await? {
+            peerstate.addr = migration.old_addr.clone();
+            peerstate.save_to_db(&context.sql).await?;
+        }
+    }
+
+    // Add the old contact first, so the member lists never lack both contacts
+    // if removing the new contact fails.
+    let added =
+        chat::add_contact_to_chat_ex(context, migration.chat_id, migration.old_contact_id, false)
+            .await?;
+    ensure!(
+        added,
+        "cannot add {} to chat {} again",
+        migration.old_addr,
+        migration.chat_id
+    );
+    if !migration.new_contact_was_member {
+        chat::remove_contact_from_chat(context, migration.chat_id, new_contact_id).await?;
+    }
+    context
+        .sql
+        .execute(
+            "UPDATE aeap_migrations SET state=? WHERE id=?",
+            (AeapMigrationState::Undone, id),
+        )
+        .await?;
+    info!(
+        context,
+        "Undone AEAP migration from {} to {} in chat {}.",
+        migration.old_addr,
+        migration.new_addr,
+        migration.chat_id
+    );
+    Ok(())
+}
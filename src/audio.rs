@@ -0,0 +1,391 @@
+//! # Re-encoding of voice messages.
+//!
+//! Voice messages are recorded in different formats by the apps,
+//! e.g. AAC in an MP4 container on iOS and some Android devices,
+//! not all of which can be played on all platforms.
+//! If [`Config::RecodeVoice`] is enabled,
+//! outgoing voice messages are decoded, mixed down to mono,
+//! normalized in loudness and encoded as Opus in an Ogg container,
+//! which can be played everywhere and is small for speech.
+//!
+//! [`Config::RecodeVoice`]: crate::config::Config::RecodeVoice
+
+use std::io::Cursor;
+
+use anyhow::{ensure, Context as _, Result};
+use audiopus::coder::Encoder;
+use audiopus::{Application, Bitrate, Channels, SampleRate, Signal};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Sample rate of the encoded audio, Opus is always decoded at 48 kHz.
+const SAMPLE_RATE: u32 = 48_000;
+
+/// Number of samples per Opus frame, 20 ms at 48 kHz.
+const FRAME_SIZE: usize = 960;
+
+/// Number of frames per Ogg page, so that players can seek with a precision of one second.
+const FRAMES_PER_PAGE: usize = 50;
+
+/// Bitrate of the encoded audio, which is plenty for speech.
+const BITRATE: i32 = 24_000;
+
+/// Maximum duration of re-encoded voice messages in seconds.
+///
+/// Longer recordings are sent as recorded,
+/// so that the decoded samples do not take up too much memory.
+const MAX_DURATION: u64 = 30 * 60;
+
+/// Loudness the audio is normalized to, as RMS of the samples, about -20 dBFS.
+const TARGET_RMS: f64 = 0.1;
+
+/// Maximum gain of the normalization, so that background noise is not amplified too much.
+const MAX_GAIN: f32 = 10.0;
+
+/// Maximum amplitude after normalization, so that the audio does not clip.
+const MAX_PEAK: f32 = 0.98;
+
+/// Voice message re-encoded by [`recode_voice`].
+#[derive(Debug)]
+pub(crate) struct RecodedVoice {
+    /// Opus audio in an Ogg container.
+    pub data: Vec<u8>,
+
+    /// Duration in milliseconds.
+    pub duration_ms: u64,
+}
+
+/// Re-encodes a voice message to Opus in an Ogg container with normalized loudness.
+///
+/// `extension` is the file extension of the recording, used as a hint to detect the format.
+/// Returns `None` if the recording is Opus already.
+pub(crate) fn recode_voice(data: &[u8], extension: Option<&str>) -> Result<Option<RecodedVoice>> {
+    if is_ogg_opus(data) {
+        return Ok(None);
+    }
+    let (samples, input_rate) = decode_mono(data, extension)?;
+    ensure!(!samples.is_empty(), "Voice message contains no audio");
+    let mut samples = resample(samples, input_rate, SAMPLE_RATE);
+    normalize(&mut samples);
+    Ok(Some(RecodedVoice {
+        data: encode_ogg_opus(&samples, input_rate)?,
+        duration_ms: samples.len() as u64 * 1000 / u64::from(SAMPLE_RATE),
+    }))
+}
+
+/// Returns true if `data` is Opus audio in an Ogg container.
+fn is_ogg_opus(data: &[u8]) -> bool {
+    // The first page of the stream only contains the Opus identification header.
+    data.starts_with(b"OggS")
+        && data[..data.len().min(64)]
+            .windows(8)
+            .any(|window| window == b"OpusHead")
+}
+
+/// Decodes the first audio track of a recording and mixes its channels down to mono.
+///
+/// Returns the samples and their sample rate.
+fn decode_mono(data: &[u8], extension: Option<&str>) -> Result<(Vec<f32>, u32)> {
+    let source = MediaSourceStream::new(Box::new(Cursor::new(data.to_vec())), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .context("Unsupported audio format")?
+        .format;
+    let track = format.default_track().context("No audio track")?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .context("Unknown sample rate")?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .context("Unsupported audio codec")?;
+
+    let max_samples = MAX_DURATION * u64::from(sample_rate);
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(err))
+                if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                break;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // Skip corrupted packets, the rest of the recording may still be fine.
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count();
+        ensure!(channels > 0, "Audio has no channels");
+        let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buf.copy_interleaved_ref(decoded);
+        samples.extend(
+            buf.samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+        ensure!(
+            samples.len() as u64 <= max_samples,
+            "Voice message is longer than {MAX_DURATION} seconds"
+        );
+    }
+    Ok((samples, sample_rate))
+}
+
+/// Resamples mono audio from `from` Hz to `to` Hz.
+///
+/// Linear interpolation is good enough for speech,
+/// which is mostly recorded at 16 kHz to 48 kHz.
+fn resample(samples: Vec<f32>, from: u32, to: u32) -> Vec<f32> {
+    if from == to {
+        return samples;
+    }
+    let len = samples.len() as u64 * u64::from(to) / u64::from(from);
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * f64::from(from) / f64::from(to);
+            let index = pos as usize;
+            let fraction = (pos - index as f64) as f32;
+            let a = samples[index];
+            let b = samples.get(index + 1).copied().unwrap_or(a);
+            a + (b - a) * fraction
+        })
+        .collect()
+}
+
+/// Scales the samples to a loudness of [`TARGET_RMS`]
+/// without exceeding [`MAX_GAIN`] and [`MAX_PEAK`].
+fn normalize(samples: &mut [f32]) {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if samples.is_empty() || peak <= 0.0 {
+        return;
+    }
+    let sum_of_squares: f64 = samples.iter().map(|s| f64::from(*s).powi(2)).sum();
+    let rms = (sum_of_squares / samples.len() as f64).sqrt();
+    let gain = ((TARGET_RMS / rms) as f32)
+        .min(MAX_GAIN)
+        .min(MAX_PEAK / peak);
+    for sample in samples {
+        *sample *= gain;
+    }
+}
+
+/// Encodes 48 kHz mono audio as Opus in an Ogg container.
+///
+/// `input_rate` is the sample rate of the recording, which is stored in the Opus header.
+fn encode_ogg_opus(samples: &[f32], input_rate: u32) -> Result<Vec<u8>> {
+    let mut encoder = Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip)?;
+    encoder.set_bitrate(Bitrate::BitsPerSecond(BITRATE))?;
+    encoder.set_signal(Signal::Voice)?;
+    // Samples of the encoder delay, which the decoder skips.
+    let pre_skip = encoder.lookahead()?;
+
+    let serial = rand::random();
+    let mut writer = PacketWriter::new(Vec::new());
+
+    // Identification and comment header as defined in RFC 7845.
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // Version.
+    head.push(1); // Channel count.
+    head.extend_from_slice(&u16::try_from(pre_skip)?.to_le_bytes());
+    head.extend_from_slice(&input_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // Output gain.
+    head.push(0); // Channel mapping family.
+    writer.write_packet(head.into(), serial, PacketWriteEndInfo::EndPage, 0)?;
+
+    let vendor = b"Delta Chat";
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&u32::try_from(vendor.len())?.to_le_bytes());
+    tags.extend_from_slice(vendor);
+    tags.extend_from_slice(&0u32.to_le_bytes()); // Number of user comments.
+    writer.write_packet(tags.into(), serial, PacketWriteEndInfo::EndPage, 0)?;
+
+    // Encode the pre-skip samples in addition, so that the end of the audio is not cut off.
+    let total = samples.len() + pre_skip as usize;
+    let frame_count = (total - 1) / FRAME_SIZE + 1;
+    let mut frame = [0.0f32; FRAME_SIZE];
+    let mut packet = [0u8; 4000];
+    for i in 0..frame_count {
+        let start = (i * FRAME_SIZE).min(samples.len());
+        let end = (start + FRAME_SIZE).min(samples.len());
+        frame.fill(0.0);
+        frame[..end - start].copy_from_slice(&samples[start..end]);
+        let len = encoder.encode_float(&frame, &mut packet)?;
+
+        // The granule position of the last page is the number of samples
+        // including the pre-skip, which tells the decoder to drop the padding.
+        let (end_info, granule) = if i + 1 == frame_count {
+            (PacketWriteEndInfo::EndStream, total)
+        } else if (i + 1) % FRAMES_PER_PAGE == 0 {
+            (PacketWriteEndInfo::EndPage, (i + 1) * FRAME_SIZE)
+        } else {
+            (PacketWriteEndInfo::NormalPacket, (i + 1) * FRAME_SIZE)
+        };
+        writer.write_packet(packet[..len].into(), serial, end_info, granule as u64)?;
+    }
+    Ok(writer.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat;
+    use crate::config::Config;
+    use crate::message::{Message, Viewtype};
+    use crate::test_utils::TestContext;
+
+    /// Returns a WAV file with a sine tone.
+    fn sine_wav(sample_rate: u32, channels: u16, seconds: u32, amplitude: f32) -> Vec<u8> {
+        let frames = sample_rate * seconds;
+        let data_len = frames * u32::from(channels) * 2;
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&channels.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * u32::from(channels) * 2).to_le_bytes());
+        wav.extend_from_slice(&(channels * 2).to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for i in 0..frames {
+            let t = i as f32 / sample_rate as f32;
+            let sample = (amplitude * (t * 440.0 * std::f32::consts::TAU).sin() * 32767.0) as i16;
+            for _ in 0..channels {
+                wav.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+        wav
+    }
+
+    #[test]
+    fn test_recode_voice() -> Result<()> {
+        let wav = sine_wav(16_000, 2, 2, 0.01);
+        let recoded = recode_voice(&wav, Some("wav"))?.unwrap();
+        assert!(is_ogg_opus(&recoded.data));
+        assert_eq!(recoded.duration_ms, 2000);
+        assert!(recoded.data.len() < wav.len() / 5);
+
+        // Opus is not re-encoded.
+        assert!(recode_voice(&recoded.data, Some("ogg"))?.is_none());
+
+        assert!(recode_voice(b"not audio", Some("m4a")).is_err());
+
+        // The encoded stream decodes to the samples and the pre-skip.
+        let mut reader = ogg::reading::PacketReader::new(Cursor::new(recoded.data));
+        let mut decoder = audiopus::coder::Decoder::new(SampleRate::Hz48000, Channels::Mono)?;
+        let mut output = vec![0.0f32; FRAME_SIZE];
+        let mut granule = 0;
+        let mut decoded = 0;
+        while let Some(packet) = reader.read_packet()? {
+            granule = packet.absgp_page();
+            if packet.data.starts_with(b"Opus") {
+                continue;
+            }
+            decoded += decoder.decode_float(
+                Some((&packet.data[..]).try_into()?),
+                (&mut output[..]).try_into()?,
+                false,
+            )?;
+        }
+        assert!(decoded as u64 >= granule);
+        assert!(granule > 2 * u64::from(SAMPLE_RATE));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_send_recoded_voice() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let chat = t.get_self_chat().await;
+        let file = t.get_blobdir().join("voice.wav");
+        tokio::fs::write(&file, sine_wav(44_100, 1, 1, 0.5)).await?;
+
+        // Voice messages are sent as recorded by default.
+        let mut msg = Message::new(Viewtype::Voice);
+        msg.set_file(file.to_str().unwrap(), None);
+        let msg_id = chat::send_msg(&t, chat.id, &mut msg).await?;
+        let msg = Message::load_from_db(&t, msg_id).await?;
+        assert_eq!(msg.get_filemime().unwrap(), "audio/wav");
+
+        t.set_config_bool(Config::RecodeVoice, true).await?;
+        let mut msg = Message::new(Viewtype::Voice);
+        msg.set_file(file.to_str().unwrap(), None);
+        let msg_id = chat::send_msg(&t, chat.id, &mut msg).await?;
+        let msg = Message::load_from_db(&t, msg_id).await?;
+        assert_eq!(msg.get_viewtype(), Viewtype::Voice);
+        assert_eq!(msg.get_filemime().unwrap(), "audio/ogg");
+        assert_eq!(msg.get_duration(), 1000);
+        assert!(msg.get_filename().unwrap().ends_with(".ogg"));
+        let data = tokio::fs::read(msg.get_file(&t).unwrap()).await?;
+        assert!(is_ogg_opus(&data));
+
+        // Files that cannot be decoded are sent as recorded.
+        let file = t.get_blobdir().join("voice.amr");
+        tokio::fs::write(&file, b"#!AMR\n").await?;
+        let mut msg = Message::new(Viewtype::Voice);
+        msg.set_file(file.to_str().unwrap(), None);
+        let msg_id = chat::send_msg(&t, chat.id, &mut msg).await?;
+        let msg = Message::load_from_db(&t, msg_id).await?;
+        assert!(msg.get_filename().unwrap().ends_with(".amr"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize() {
+        // Quiet audio is amplified at most by `MAX_GAIN`.
+        let mut samples = vec![0.001, -0.001];
+        normalize(&mut samples);
+        assert!((samples[0] - 0.01).abs() < 1e-6);
+
+        let mut samples = vec![0.05, -0.05];
+        normalize(&mut samples);
+        assert!((samples[0] - TARGET_RMS as f32).abs() < 1e-6);
+
+        // Peaks are not clipped.
+        let mut samples = vec![0.0; 100];
+        samples[0] = 0.5;
+        normalize(&mut samples);
+        assert!((samples[0] - MAX_PEAK).abs() < 1e-6);
+
+        let mut samples = vec![0.0; 4];
+        normalize(&mut samples);
+        assert_eq!(samples, vec![0.0; 4]);
+    }
+
+    #[test]
+    fn test_resample() {
+        assert_eq!(
+            resample(vec![0.0, 1.0], 24_000, 48_000),
+            vec![0.0, 0.5, 1.0, 1.0]
+        );
+        assert_eq!(
+            resample(vec![0.0, 1.0, 0.0, 1.0], 48_000, 24_000),
+            vec![0.0, 0.0]
+        );
+    }
+}
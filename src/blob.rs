@@ -7,17 +7,22 @@
 use std::iter::FusedIterator;
 use std::path::{Path, PathBuf};
 
-use anyhow::{format_err, Context as _, Result};
+use anyhow::{bail, ensure, format_err, Context as _, Result};
 use futures::StreamExt;
 use image::{DynamicImage, ImageFormat};
 use num_traits::FromPrimitive;
+use sha2::{Digest, Sha256};
 use tokio::io::AsyncWriteExt;
 use tokio::{fs, io};
 use tokio_stream::wrappers::ReadDirStream;
 
+#[cfg(feature = "recode-voice")]
+use crate::audio;
 use crate::config::Config;
 use crate::constants::{
-    MediaQuality, BALANCED_AVATAR_SIZE, BALANCED_IMAGE_SIZE, WORSE_AVATAR_SIZE, WORSE_IMAGE_SIZE,
+    MediaQuality, AVATAR_THUMBNAIL_SIZE, BALANCED_AVATAR_SIZE, BALANCED_IMAGE_SIZE,
+    MAX_DECODE_IMAGE_ALLOC, MAX_DECODE_IMAGE_DIMENSION, MAX_DECODE_IMAGE_PIXELS, WORSE_AVATAR_SIZE,
+    WORSE_IMAGE_SIZE,
 };
 use crate::context::Context;
 use crate::events::EventType;
@@ -133,6 +138,82 @@ pub async fn create_and_copy(context: &'a Context, src: &Path) -> Result<BlobObj
         Ok(blob)
     }
 
+    /// Creates a new blob object with unique name by moving an existing file
+    /// from the same filesystem into the blob directory.
+    ///
+    /// The name is derived from `suggested_name` as described in [BlobObject::create].
+    pub(crate) async fn create_and_move(
+        context: &'a Context,
+        src: &Path,
+        suggested_name: &str,
+    ) -> Result<BlobObject<'a>> {
+        let (stem, ext) = BlobObject::sanitise_name(suggested_name);
+        let (name, _file) =
+            BlobObject::create_new_file(context, context.get_blobdir(), &stem, &ext).await?;
+        let dst = context.get_blobdir().join(&name);
+        if let Err(err) = fs::rename(src, &dst).await {
+            fs::remove_file(&dst).await.ok();
+            return Err(err).context("failed to move file");
+        }
+
+        let blob = BlobObject {
+            blobdir: context.get_blobdir(),
+            name: format!("$BLOBDIR/{name}"),
+        };
+        context.emit_event(EventType::NewBlobFile(blob.as_name().to_string()));
+        Ok(blob)
+    }
+
+    /// Creates a blob named after the hash of its content.
+    ///
+    /// If a blob with the same content already exists, it is reused instead of
+    /// writing another copy. This is used for avatars which are attached to every
+    /// message of a contact and would otherwise fill up the blobdir with duplicates.
+    ///
+    /// `extension` is appended to the name as is and should include the dot.
+    pub async fn create_and_deduplicate(
+        context: &'a Context,
+        data: &[u8],
+        extension: &str,
+    ) -> Result<BlobObject<'a>> {
+        let blobdir = context.get_blobdir();
+        let hash = Sha256::digest(data);
+        let name = format!("{}{}", hex::encode(&hash[..16]), extension.to_lowercase());
+        ensure!(
+            BlobObject::is_acceptible_blob_name(&name),
+            "bad blob extension: {extension}"
+        );
+        let blob = BlobObject {
+            blobdir,
+            name: format!("$BLOBDIR/{name}"),
+        };
+        let path = blob.to_abs_path();
+        if let Ok(metadata) = fs::metadata(&path).await {
+            if metadata.is_file() && metadata.len() == data.len() as u64 {
+                return Ok(blob);
+            }
+        }
+
+        // Write to a temporary file first, so a concurrent reader never sees
+        // a partially written blob under the final name.
+        let (tmp_name, mut file) =
+            BlobObject::create_new_file(context, blobdir, &name, ".increation").await?;
+        let tmp_path = blobdir.join(tmp_name);
+        let res = async {
+            file.write_all(data).await?;
+            file.flush().await?;
+            fs::rename(&tmp_path, &path).await
+        }
+        .await;
+        if let Err(err) = res {
+            fs::remove_file(&tmp_path).await.ok();
+            return Err(err).context("failed to write deduplicated blob");
+        }
+
+        context.emit_event(EventType::NewBlobFile(blob.as_name().to_string()));
+        Ok(blob)
+    }
+
     /// Creates a blob from a file, possibly copying it to the blobdir.
     ///
     /// If the source file is not a path to into the blob directory
@@ -362,6 +443,84 @@ pub async fn recode_to_image_size(&self, context: &Context) -> Result<()> {
         Ok(())
     }
 
+    /// Scales a JPEG image down until it does not exceed `max_bytes`.
+    pub(crate) async fn recode_to_max_bytes(
+        &self,
+        context: &Context,
+        max_bytes: usize,
+    ) -> Result<()> {
+        let blob_abs = self.to_abs_path();
+        if message::guess_msgtype_from_suffix(Path::new(&blob_abs))
+            != Some((Viewtype::Image, "image/jpeg"))
+        {
+            bail!("Only JPEG images can be scaled down");
+        }
+
+        let img_wh =
+            match MediaQuality::from_i32(context.get_config_int(Config::MediaQuality).await?)
+                .unwrap_or_default()
+            {
+                MediaQuality::Balanced => BALANCED_IMAGE_SIZE,
+                MediaQuality::Worse => WORSE_IMAGE_SIZE,
+            };
+
+        if self
+            .recode_to_size(context, blob_abs, img_wh, Some(max_bytes))?
+            .is_some()
+        {
+            bail!("Internal error: recode_to_size() shouldn't change the name of the JPEG image");
+        }
+        Ok(())
+    }
+
+    /// Returns a small JPEG thumbnail of the image, creating it if it does not exist yet.
+    ///
+    /// The thumbnail is stored next to the image as `<name>-thumb.jpg`,
+    /// so its name is stable as long as the image does not change.
+    pub async fn get_or_create_thumbnail(&self, context: &Context) -> Result<BlobObject<'a>> {
+        let thumb = BlobObject {
+            blobdir: self.blobdir,
+            name: format!("{}-thumb.jpg", self.name),
+        };
+        let thumb_abs = thumb.to_abs_path();
+        if fs::metadata(&thumb_abs).await.is_ok() {
+            return Ok(thumb);
+        }
+
+        let blob_abs = self.to_abs_path();
+        let encoded = tokio::task::block_in_place(|| -> Result<Vec<u8>> {
+            let img = decode_image(context, &blob_abs).context("thumbnail decode failure")?;
+            let img = match self.get_exif_orientation(context) {
+                Ok(90) => img.rotate90(),
+                Ok(180) => img.rotate180(),
+                Ok(270) => img.rotate270(),
+                _ => img,
+            };
+            let mut encoded = Vec::new();
+            encode_img(
+                &img.thumbnail(AVATAR_THUMBNAIL_SIZE, AVATAR_THUMBNAIL_SIZE),
+                &mut encoded,
+            )?;
+            Ok(encoded)
+        })?;
+
+        let (tmp_name, mut file) =
+            BlobObject::create_new_file(context, self.blobdir, thumb.as_file_name(), ".increation")
+                .await?;
+        let tmp_path = self.blobdir.join(tmp_name);
+        let res = async {
+            file.write_all(&encoded).await?;
+            file.flush().await?;
+            fs::rename(&tmp_path, &thumb_abs).await
+        }
+        .await;
+        if let Err(err) = res {
+            fs::remove_file(&tmp_path).await.ok();
+            return Err(err).context("failed to write thumbnail");
+        }
+        Ok(thumb)
+    }
+
     fn recode_to_size(
         &self,
         context: &Context,
@@ -370,7 +529,7 @@ fn recode_to_size(
         max_bytes: Option<usize>,
     ) -> Result<Option<String>> {
         tokio::task::block_in_place(move || {
-            let mut img = image::open(&blob_abs).context("image recode failure")?;
+            let mut img = decode_image(context, &blob_abs).context("image recode failure")?;
             let orientation = self.get_exif_orientation(context);
             let mut encoded = Vec::new();
             let mut changed_name = None;
@@ -463,6 +622,89 @@ pub fn get_exif_orientation(&self, context: &Context) -> Result<i32> {
         }
         Ok(0)
     }
+
+    /// Removes metadata as GPS location and camera model from a JPEG, PNG or WebP image.
+    ///
+    /// Only the EXIF fields selected by [`Config::ExifKeep`] are retained.
+    /// Other files are not changed.
+    /// Returns the names of the removed metadata.
+    ///
+    /// Returns an error if the image cannot be parsed,
+    /// the caller must not send it then as it may still contain metadata.
+    pub(crate) async fn strip_metadata(&self, context: &Context) -> Result<Vec<String>> {
+        let blob_abs = self.to_abs_path();
+        let data = fs::read(&blob_abs).await?;
+        let strip = if data.starts_with(JPEG_SIGNATURE) {
+            strip_jpeg_metadata
+        } else if data.starts_with(PNG_SIGNATURE) {
+            strip_png_metadata
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(&b"WEBP"[..]) {
+            strip_webp_metadata
+        } else {
+            return Ok(Vec::new());
+        };
+
+        let mut keep = Vec::new();
+        let exif_keep = context.get_config(Config::ExifKeep).await?;
+        for name in exif_keep.unwrap_or_default().split_ascii_whitespace() {
+            match name {
+                "orientation" => keep.push(exif::Tag::Orientation),
+                "date" => keep.extend([exif::Tag::DateTime, exif::Tag::DateTimeOriginal]),
+                _ => warn!(context, "Unknown exif_keep value {name:?}."),
+            }
+        }
+
+        let (stripped, removed) = strip(&data, &keep)?;
+        if !removed.is_empty() {
+            info!(
+                context,
+                "Removed metadata from {}: {}.",
+                self.as_name(),
+                removed.join(", ")
+            );
+            fs::write(&blob_abs, stripped)
+                .await
+                .context("failed to write stripped image")?;
+        }
+        Ok(removed)
+    }
+
+    /// Re-encodes a voice message to Opus in an Ogg container, see [`crate::audio`].
+    ///
+    /// Only available with the `recode-voice` feature.
+    ///
+    /// Returns the blob with the re-encoded voice message and its duration in milliseconds,
+    /// or `None` if the voice message is Opus already.
+    #[cfg(feature = "recode-voice")]
+    pub(crate) async fn recode_voice(
+        &self,
+        context: &'a Context,
+    ) -> Result<Option<(BlobObject<'a>, u64)>> {
+        let blob_abs = self.to_abs_path();
+        let extension = blob_abs
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(str::to_lowercase);
+        let data = fs::read(&blob_abs).await?;
+        let recoded =
+            tokio::task::block_in_place(|| audio::recode_voice(&data, extension.as_deref()))?;
+        let recoded = match recoded {
+            Some(recoded) => recoded,
+            None => return Ok(None),
+        };
+        let stem = blob_abs
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        let blob = BlobObject::create(context, &format!("{stem}.ogg"), &recoded.data).await?;
+        info!(
+            context,
+            "Re-encoded voice message {} to {}.",
+            self.as_name(),
+            blob.as_name()
+        );
+        Ok(Some((blob, recoded.duration_ms)))
+    }
 }
 
 impl<'a> fmt::Display for BlobObject<'a> {
@@ -552,6 +794,333 @@ fn next(&mut self) -> Option<Self::Item> {
 
 impl FusedIterator for BlobDirIter<'_> {}
 
+/// Image formats which are decoded for recoding.
+const DECODABLE_IMAGE_FORMATS: &[ImageFormat] = &[
+    ImageFormat::Jpeg,
+    ImageFormat::Png,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+    ImageFormat::Bmp,
+];
+
+/// Reason for refusing to decode an image.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub(crate) enum ImageLimitError {
+    #[error("unknown image format")]
+    UnknownFormat,
+
+    #[error("image format {0:?} is not allowed")]
+    FormatNotAllowed(ImageFormat),
+
+    #[error("image dimensions {width}x{height} exceed the limits")]
+    TooLarge { width: u32, height: u32 },
+}
+
+/// Decodes the image at `path`.
+///
+/// Only formats from [`DECODABLE_IMAGE_FORMATS`] are decoded
+/// and the dimensions are checked before decoding,
+/// so decompression bombs are rejected instead of exhausting the memory.
+fn decode_image(context: &Context, path: &Path) -> Result<DynamicImage> {
+    let reader = image::io::Reader::open(path)?.with_guessed_format()?;
+    let res = match reader.format() {
+        None => Err(ImageLimitError::UnknownFormat),
+        Some(format) if !DECODABLE_IMAGE_FORMATS.contains(&format) => {
+            Err(ImageLimitError::FormatNotAllowed(format))
+        }
+        Some(_) => {
+            let (width, height) = reader.into_dimensions()?;
+            check_image_dimensions(width, height)
+        }
+    };
+    if let Err(err) = res {
+        warn!(
+            context,
+            "Refusing to decode image {}: {err}.",
+            path.display()
+        );
+        return Err(err.into());
+    }
+
+    let mut reader = image::io::Reader::open(path)?.with_guessed_format()?;
+    let mut limits = image::io::Limits::default();
+    limits.max_image_width = Some(MAX_DECODE_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODE_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_IMAGE_ALLOC);
+    reader.limits(limits);
+    Ok(reader.decode()?)
+}
+
+fn check_image_dimensions(width: u32, height: u32) -> Result<(), ImageLimitError> {
+    if width > MAX_DECODE_IMAGE_DIMENSION
+        || height > MAX_DECODE_IMAGE_DIMENSION
+        || u64::from(width) * u64::from(height) > MAX_DECODE_IMAGE_PIXELS
+    {
+        return Err(ImageLimitError::TooLarge { width, height });
+    }
+    Ok(())
+}
+
+const JPEG_SIGNATURE: &[u8] = &[0xff, 0xd8];
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Adds `name` to the names of the removed metadata unless it is there already.
+fn add_removed(removed: &mut Vec<String>, name: impl Into<String>) {
+    let name = name.into();
+    if !removed.contains(&name) {
+        removed.push(name);
+    }
+}
+
+/// Removes all EXIF fields except for `keep` from the TIFF structure `tiff`.
+///
+/// Adds the names of the removed fields to `removed`.
+/// Returns the new TIFF structure or `None` if no field is kept.
+fn strip_exif(
+    tiff: &[u8],
+    keep: &[exif::Tag],
+    removed: &mut Vec<String>,
+) -> Result<Option<Vec<u8>>> {
+    let exif = exif::Reader::new().read_raw(tiff.to_vec())?;
+    let mut writer = exif::experimental::Writer::new();
+    let mut kept = false;
+    for field in exif.fields() {
+        if matches!(
+            field.tag,
+            exif::Tag::ExifIFDPointer | exif::Tag::GPSInfoIFDPointer | exif::Tag::InteropIFDPointer
+        ) {
+            continue;
+        }
+        if field.ifd_num == exif::In::PRIMARY && keep.contains(&field.tag) {
+            writer.push_field(field);
+            kept = true;
+        } else if field.ifd_num == exif::In::PRIMARY {
+            add_removed(removed, field.tag.to_string());
+        } else {
+            add_removed(removed, "Thumbnail");
+        }
+    }
+    if !kept {
+        return Ok(None);
+    }
+    let mut tiff = Cursor::new(Vec::new());
+    writer.write(&mut tiff, exif.little_endian())?;
+    Ok(Some(tiff.into_inner()))
+}
+
+/// Returns the name of the metadata in the JPEG segment with `marker` and `payload`.
+fn jpeg_segment_name(marker: u8, payload: &[u8]) -> String {
+    match marker {
+        0xe0 if payload.starts_with(b"JFXX\0") => "Thumbnail".to_string(),
+        0xe1 if payload.starts_with(b"http://ns.adobe.com/") => "XMP".to_string(),
+        0xe2 if payload.starts_with(b"ICC_PROFILE\0") => "ICC profile".to_string(),
+        0xe2 if payload.starts_with(b"MPF\0") => "MPF".to_string(),
+        0xed => "IPTC".to_string(),
+        0xfe => "Comment".to_string(),
+        _ => format!("APP{}", marker - 0xe0),
+    }
+}
+
+/// Removes the application segments except for JFIF and Adobe segments,
+/// the comments and all EXIF fields except for `keep` from the JPEG `data`.
+///
+/// Data after the end of the image, e.g. further images of an MPO file, is removed as well.
+///
+/// Returns the new JPEG data and the names of the removed metadata.
+fn strip_jpeg_metadata(data: &[u8], keep: &[exif::Tag]) -> Result<(Vec<u8>, Vec<String>)> {
+    ensure!(data.starts_with(JPEG_SIGNATURE), "not a JPEG image");
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(JPEG_SIGNATURE);
+    let mut removed = Vec::new();
+
+    let mut pos = JPEG_SIGNATURE.len();
+    loop {
+        ensure!(
+            pos + 2 <= data.len() && data[pos] == 0xff,
+            "invalid JPEG segment at {pos}"
+        );
+        let marker = data[pos + 1];
+        if marker == 0xd9 {
+            // End of image.
+            stripped.extend_from_slice(&data[pos..pos + 2]);
+            if pos + 2 < data.len() {
+                add_removed(&mut removed, "Trailing data");
+            }
+            break;
+        }
+        ensure!(pos + 4 <= data.len(), "invalid JPEG segment at {pos}");
+        let len = usize::from(u16::from_be_bytes([data[pos + 2], data[pos + 3]]));
+        let end = pos + 2 + len;
+        ensure!(
+            len >= 2 && end <= data.len(),
+            "invalid JPEG segment length at {pos}"
+        );
+        let payload = &data[pos + 4..end];
+
+        match marker {
+            0xe1 if payload.starts_with(b"Exif\0\0") => {
+                if let Some(tiff) = strip_exif(&payload[6..], keep, &mut removed)? {
+                    let len = u16::try_from(tiff.len() + 8).context("EXIF data too large")?;
+                    stripped.extend_from_slice(&[0xff, 0xe1]);
+                    stripped.extend_from_slice(&len.to_be_bytes());
+                    stripped.extend_from_slice(b"Exif\0\0");
+                    stripped.extend_from_slice(&tiff);
+                }
+            }
+            // JFIF and Adobe segments are needed to decode the colors correctly.
+            0xe0 if payload.starts_with(b"JFIF\0") => stripped.extend_from_slice(&data[pos..end]),
+            0xee if payload.starts_with(b"Adobe") => stripped.extend_from_slice(&data[pos..end]),
+            0xe0..=0xef | 0xfe => add_removed(&mut removed, jpeg_segment_name(marker, payload)),
+            _ => stripped.extend_from_slice(&data[pos..end]),
+        }
+        pos = end;
+
+        if marker == 0xda {
+            // Start of scan, copy the compressed image data up to the next marker.
+            // Inside the data, 0xff is followed by 0 or a restart marker.
+            let mut next = pos;
+            while next + 1 < data.len()
+                && !(data[next] == 0xff && !matches!(data[next + 1], 0x00 | 0xd0..=0xd7 | 0xff))
+            {
+                next += 1;
+            }
+            if next + 1 >= data.len() {
+                // The image is truncated.
+                stripped.extend_from_slice(&data[pos..]);
+                break;
+            }
+            stripped.extend_from_slice(&data[pos..next]);
+            pos = next;
+        }
+    }
+    Ok((stripped, removed))
+}
+
+/// Removes XMP and all EXIF fields except for `keep` from the PNG `data`.
+///
+/// Returns the new PNG data and the names of the removed metadata.
+fn strip_png_metadata(data: &[u8], keep: &[exif::Tag]) -> Result<(Vec<u8>, Vec<String>)> {
+    ensure!(data.starts_with(PNG_SIGNATURE), "not a PNG image");
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(PNG_SIGNATURE);
+    let mut removed = Vec::new();
+
+    let mut pos = PNG_SIGNATURE.len();
+    loop {
+        ensure!(pos + 12 <= data.len(), "invalid PNG chunk at {pos}");
+        let len = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+        let len = usize::try_from(len)?;
+        let chunk_type = &data[pos + 4..pos + 8];
+        let end = pos
+            .checked_add(12)
+            .and_then(|end| end.checked_add(len))
+            .filter(|end| *end <= data.len())
+            .with_context(|| format!("invalid PNG chunk length at {pos}"))?;
+        let payload = &data[pos + 8..end - 4];
+
+        match chunk_type {
+            b"eXIf" => {
+                if let Some(tiff) = strip_exif(payload, keep, &mut removed)? {
+                    let len = u32::try_from(tiff.len()).context("EXIF data too large")?;
+                    stripped.extend_from_slice(&len.to_be_bytes());
+                    let start = stripped.len();
+                    stripped.extend_from_slice(b"eXIf");
+                    stripped.extend_from_slice(&tiff);
+                    let crc = crc32fast::hash(&stripped[start..]);
+                    stripped.extend_from_slice(&crc.to_be_bytes());
+                }
+            }
+            b"tEXt" | b"zTXt" | b"iTXt" if payload.starts_with(b"XML:com.adobe.xmp\0") => {
+                add_removed(&mut removed, "XMP");
+            }
+            _ => stripped.extend_from_slice(&data[pos..end]),
+        }
+        pos = end;
+
+        if chunk_type == b"IEND" {
+            if pos < data.len() {
+                add_removed(&mut removed, "Trailing data");
+            }
+            break;
+        }
+    }
+    Ok((stripped, removed))
+}
+
+/// Removes XMP and all EXIF fields except for `keep` from the WebP `data`.
+///
+/// Returns the new WebP data and the names of the removed metadata.
+fn strip_webp_metadata(data: &[u8], keep: &[exif::Tag]) -> Result<(Vec<u8>, Vec<String>)> {
+    ensure!(
+        data.starts_with(b"RIFF") && data.get(8..12) == Some(&b"WEBP"[..]),
+        "not a WebP image"
+    );
+    let riff_len = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let riff_end = usize::try_from(riff_len)?
+        .checked_add(8)
+        .filter(|end| *end <= data.len())
+        .context("invalid WebP file size")?;
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(&data[..12]);
+    let mut removed = Vec::new();
+    let mut vp8x = None;
+    let mut exif_kept = false;
+
+    let mut pos = 12;
+    while pos < riff_end {
+        ensure!(pos + 8 <= riff_end, "invalid WebP chunk at {pos}");
+        let fourcc = &data[pos..pos + 4];
+        let len = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]);
+        let len = usize::try_from(len)?;
+        // Chunks are padded to an even size.
+        let end = (pos + 8)
+            .checked_add(len)
+            .filter(|end| *end <= riff_end)
+            .with_context(|| format!("invalid WebP chunk length at {pos}"))?;
+        let payload = &data[pos + 8..end];
+        let end = (end + len % 2).min(riff_end);
+
+        match fourcc {
+            b"EXIF" => {
+                let tiff = payload.strip_prefix(b"Exif\0\0").unwrap_or(payload);
+                if let Some(tiff) = strip_exif(tiff, keep, &mut removed)? {
+                    let len = u32::try_from(tiff.len()).context("EXIF data too large")?;
+                    stripped.extend_from_slice(b"EXIF");
+                    stripped.extend_from_slice(&len.to_le_bytes());
+                    stripped.extend_from_slice(&tiff);
+                    if tiff.len() % 2 == 1 {
+                        stripped.push(0);
+                    }
+                    exif_kept = true;
+                }
+            }
+            b"XMP " => add_removed(&mut removed, "XMP"),
+            _ => {
+                if fourcc == b"VP8X" {
+                    ensure!(len >= 10, "invalid WebP VP8X chunk");
+                    vp8x = Some(stripped.len());
+                }
+                stripped.extend_from_slice(&data[pos..end]);
+            }
+        }
+        pos = end;
+    }
+    if riff_end < data.len() {
+        add_removed(&mut removed, "Trailing data");
+    }
+
+    // The extended format header flags the EXIF and XMP chunks.
+    if let Some(vp8x) = vp8x {
+        stripped[vp8x + 8] &= !0x04;
+        if !exif_kept {
+            stripped[vp8x + 8] &= !0x08;
+        }
+    }
+    let riff_len = u32::try_from(stripped.len() - 8).context("WebP image too large")?;
+    stripped[4..8].copy_from_slice(&riff_len.to_le_bytes());
+    Ok((stripped, removed))
+}
+
 fn encode_img(img: &DynamicImage, encoded: &mut Vec<u8>) -> anyhow::Result<()> {
     encoded.clear();
     let mut buf = Cursor::new(encoded);
@@ -612,6 +1181,254 @@ async fn test_create() {
         assert_eq!(blob.to_abs_path(), t.get_blobdir().join("foo"));
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_create_and_deduplicate() -> Result<()> {
+        let t = TestContext::new().await;
+        let blob = BlobObject::create_and_deduplicate(&t, b"hello", ".TXT").await?;
+        assert!(blob.as_name().ends_with(".txt"));
+        assert_eq!(fs::read(blob.to_abs_path()).await?, b"hello");
+
+        let blob2 = BlobObject::create_and_deduplicate(&t, b"hello", ".txt").await?;
+        assert_eq!(blob2, blob);
+
+        let blob3 = BlobObject::create_and_deduplicate(&t, b"world", ".txt").await?;
+        assert_ne!(blob3, blob);
+
+        let mut dir = fs::read_dir(t.get_blobdir()).await?;
+        let mut count = 0;
+        while dir.next_entry().await?.is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 2);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_or_create_thumbnail() -> Result<()> {
+        let t = TestContext::new().await;
+        let bytes = include_bytes!("../test-data/image/avatar1000x1000.jpg");
+        let blob = BlobObject::create_and_deduplicate(&t, bytes, ".jpg").await?;
+        let thumb = blob.get_or_create_thumbnail(&t).await?;
+        assert_eq!(thumb.as_name(), format!("{}-thumb.jpg", blob.as_name()));
+        let img = image::open(thumb.to_abs_path())?;
+        assert_eq!(img.width(), AVATAR_THUMBNAIL_SIZE);
+        assert_eq!(img.height(), AVATAR_THUMBNAIL_SIZE);
+
+        // The existing thumbnail is reused.
+        assert_eq!(blob.get_or_create_thumbnail(&t).await?, thumb);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_recode_decompression_bomb() -> Result<()> {
+        let t = TestContext::new().await;
+
+        // Claim a logical screen of 65535x65535 pixels in the GIF header.
+        let mut bytes = include_bytes!("../test-data/image/image100x50.gif").to_vec();
+        bytes[6..10].copy_from_slice(&[0xff; 4]);
+        let mut blob = BlobObject::create(&t, "bomb.gif", &bytes).await?;
+        let err = blob.recode_to_avatar_size(&t).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ImageLimitError>(),
+            Some(&ImageLimitError::TooLarge {
+                width: 65535,
+                height: 65535
+            })
+        );
+        assert!(blob.get_or_create_thumbnail(&t).await.is_err());
+
+        let mut blob = BlobObject::create(&t, "image.pnm", b"P1\n1 1\n1\n").await?;
+        let err = blob.recode_to_avatar_size(&t).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ImageLimitError>(),
+            Some(&ImageLimitError::FormatNotAllowed(ImageFormat::Pnm))
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_strip_metadata() -> Result<()> {
+        let t = TestContext::new().await;
+
+        let fields = [
+            exif::Field {
+                tag: exif::Tag::Orientation,
+                ifd_num: exif::In::PRIMARY,
+                value: exif::Value::Short(vec![6]),
+            },
+            exif::Field {
+                tag: exif::Tag::Model,
+                ifd_num: exif::In::PRIMARY,
+                value: exif::Value::Ascii(vec![b"Camera".to_vec()]),
+            },
+            exif::Field {
+                tag: exif::Tag::DateTimeOriginal,
+                ifd_num: exif::In::PRIMARY,
+                value: exif::Value::Ascii(vec![b"2023:01:01 12:00:00".to_vec()]),
+            },
+        ];
+        let mut writer = exif::experimental::Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false)?;
+        let tiff = tiff.into_inner();
+
+        let jpeg = include_bytes!("../test-data/image/avatar1000x1000.jpg");
+        let mut bytes = jpeg[..2].to_vec();
+        bytes.extend_from_slice(&[0xff, 0xe1]);
+        bytes.extend_from_slice(&(tiff.len() as u16 + 8).to_be_bytes());
+        bytes.extend_from_slice(b"Exif\0\0");
+        bytes.extend_from_slice(&tiff);
+        bytes.extend_from_slice(&jpeg[2..]);
+
+        let blob = BlobObject::create(&t, "image.jpg", &bytes).await?;
+        assert_eq!(blob.get_exif_orientation(&t)?, 90);
+        let removed = blob.strip_metadata(&t).await?;
+        assert_eq!(removed, vec!["Model", "DateTimeOriginal"]);
+        assert_eq!(blob.get_exif_orientation(&t)?, 90);
+        let data = fs::read(blob.to_abs_path()).await?;
+        assert!(data.len() < bytes.len());
+        assert!(!data.windows(6).any(|w| w == b"Camera"));
+        image::load_from_memory(&data)?;
+
+        // Stripping again does not remove anything.
+        assert!(blob.strip_metadata(&t).await?.is_empty());
+
+        t.set_config(Config::ExifKeep, Some("")).await?;
+        assert_eq!(blob.strip_metadata(&t).await?, vec!["Orientation"]);
+        assert_eq!(blob.get_exif_orientation(&t).unwrap_or_default(), 0);
+        Ok(())
+    }
+
+    /// Returns EXIF data with the orientation and the camera model.
+    fn exif_with_model() -> Vec<u8> {
+        let orientation = exif::Field {
+            tag: exif::Tag::Orientation,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Short(vec![6]),
+        };
+        let model = exif::Field {
+            tag: exif::Tag::Model,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Ascii(vec![b"Camera".to_vec()]),
+        };
+        let mut writer = exif::experimental::Writer::new();
+        writer.push_field(&orientation);
+        writer.push_field(&model);
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        tiff.into_inner()
+    }
+
+    fn contains_secret(data: &[u8]) -> bool {
+        data.windows(6).any(|w| w == b"Camera" || w == b"Secret")
+    }
+
+    #[test]
+    fn test_strip_jpeg_segments() -> Result<()> {
+        let jpeg = include_bytes!("../test-data/image/avatar1000x1000.jpg");
+        let mut bytes = jpeg[..2].to_vec();
+        for (marker, payload) in [
+            (0xe1, &b"http://ns.adobe.com/xmp/extension/\0Secret"[..]),
+            (0xed, &b"Photoshop 3.0\0Secret"[..]),
+            (0xe2, &b"MPF\0Secret"[..]),
+            (0xfe, &b"Secret"[..]),
+        ] {
+            bytes.extend_from_slice(&[0xff, marker]);
+            bytes.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+            bytes.extend_from_slice(payload);
+        }
+        bytes.extend_from_slice(&jpeg[2..]);
+        // Second image of an MPO file.
+        bytes.extend_from_slice(b"Secret");
+        bytes.extend_from_slice(jpeg);
+
+        let (stripped, removed) = strip_jpeg_metadata(&bytes, &[])?;
+        assert_eq!(
+            removed,
+            vec!["XMP", "IPTC", "MPF", "Comment", "Trailing data"]
+        );
+        assert!(!contains_secret(&stripped));
+        // The JFIF segment and the image are kept.
+        assert_eq!(stripped, jpeg);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_png_metadata() -> Result<()> {
+        let png = include_bytes!("../test-data/image/avatar64x64.png");
+        // The chunks are inserted after the IHDR chunk.
+        let ihdr_end = PNG_SIGNATURE.len() + 12 + 13;
+        let mut bytes = png[..ihdr_end].to_vec();
+        for (chunk_type, data) in [
+            (b"eXIf", exif_with_model()),
+            (b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0Secret".to_vec()),
+        ] {
+            bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let start = bytes.len();
+            bytes.extend_from_slice(chunk_type);
+            bytes.extend_from_slice(&data);
+            let crc = crc32fast::hash(&bytes[start..]);
+            bytes.extend_from_slice(&crc.to_be_bytes());
+        }
+        bytes.extend_from_slice(&png[ihdr_end..]);
+
+        let (stripped, removed) = strip_png_metadata(&bytes, &[exif::Tag::Orientation])?;
+        assert_eq!(removed, vec!["Model", "XMP"]);
+        assert!(!contains_secret(&stripped));
+        let exif = exif::Reader::new().read_from_container(&mut Cursor::new(&stripped))?;
+        let orientation = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY);
+        assert_eq!(orientation.unwrap().value.get_uint(0), Some(6));
+        image::load_from_memory(&stripped)?;
+
+        let (stripped, removed) = strip_png_metadata(&bytes, &[])?;
+        assert_eq!(removed, vec!["Model", "Orientation", "XMP"]);
+        assert_eq!(stripped, png);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_webp_metadata() -> Result<()> {
+        let webp = include_bytes!("../test-data/image/avatar64x64.webp");
+        // Extended file format with alpha, EXIF and XMP.
+        let mut bytes = b"RIFF\0\0\0\0WEBPVP8X".to_vec();
+        bytes.extend_from_slice(&10u32.to_le_bytes());
+        bytes.extend_from_slice(&[0x1c, 0, 0, 0, 63, 0, 0, 63, 0, 0]);
+        bytes.extend_from_slice(&webp[12..]);
+        for (fourcc, data) in [(b"EXIF", exif_with_model()), (b"XMP ", b"Secret".to_vec())] {
+            bytes.extend_from_slice(fourcc);
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&data);
+            if data.len() % 2 == 1 {
+                bytes.push(0);
+            }
+        }
+        let riff_len = bytes.len() as u32 - 8;
+        bytes[4..8].copy_from_slice(&riff_len.to_le_bytes());
+
+        let (stripped, removed) = strip_webp_metadata(&bytes, &[exif::Tag::Orientation])?;
+        assert_eq!(removed, vec!["Model", "XMP"]);
+        assert!(!contains_secret(&stripped));
+        // The XMP flag is cleared.
+        assert_eq!(stripped[20], 0x18);
+        assert_eq!(
+            u32::from_le_bytes(stripped[4..8].try_into()?) as usize,
+            stripped.len() - 8
+        );
+        let exif = exif::Reader::new().read_from_container(&mut Cursor::new(&stripped))?;
+        let orientation = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY);
+        assert_eq!(orientation.unwrap().value.get_uint(0), Some(6));
+        image::load_from_memory(&stripped)?;
+
+        let (stripped, removed) = strip_webp_metadata(&bytes, &[])?;
+        assert_eq!(removed, vec!["Model", "Orientation", "XMP"]);
+        assert_eq!(stripped[20], 0x10);
+        image::load_from_memory(&stripped)?;
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_lowercase_ext() {
         let t = TestContext::new().await;
@@ -0,0 +1,459 @@
+//! # Blocklists.
+//!
+//! The addresses of blocked contacts can be exported with [`export_blocklist`]
+//! and blocked on another account or device with [`import_blocklist`].
+//!
+//! Besides that, blocklists maintained by others, e.g. by a bot of a community,
+//! can be subscribed to with [`add_blocklist_feed`].
+//! A feed is an HTTPS URL serving a list as exported by [`export_blocklist`],
+//! which must be signed with the key of the contact given when subscribing.
+//! Feeds are fetched once a day; their entries are stored in the `blocklist` table
+//! and messages from unknown senders matching an entry go to a blocked chat and are not shown.
+//! Contacts the user already talked to are never affected by feeds.
+//!
+//! A list has one entry per line, either an address or `@domain` for a whole domain.
+//! Feed entries for the domain of the user's own provider are ignored,
+//! so a feed cannot deny all senders using the same provider as the user.
+//! Empty lines and lines starting with `#` are ignored.
+
+use anyhow::{bail, ensure, Context as _, Result};
+
+use crate::contact::{Contact, ContactAddress, ContactId, Origin};
+use crate::context::Context;
+use crate::key::{DcKey, Fingerprint, SignedPublicKey, SignedSecretKey};
+use crate::keyring::Keyring;
+use crate::peerstate::{Peerstate, PeerstateVerifiedStatus};
+use crate::pgp;
+use crate::provider::get_provider_by_domain;
+use crate::socks::Socks5Config;
+use crate::tools::{time, EmailAddress};
+
+/// Interval between updates of a feed in seconds.
+const FEED_UPDATE_INTERVAL: i64 = 24 * 60 * 60;
+
+/// Maximum size of a feed to download.
+const MAX_FEED_BYTES: usize = 1024 * 1024;
+
+/// Subscribed blocklist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlocklistFeed {
+    /// ID of the feed.
+    pub id: u32,
+
+    /// URL the list is fetched from.
+    pub url: String,
+
+    /// Fingerprint of the key the list must be signed with.
+    pub fingerprint: String,
+
+    /// Number of entries of the last successfully fetched list.
+    pub entries: u32,
+
+    /// Timestamp of the last update attempt, 0 if the feed was not fetched yet.
+    pub last_update: i64,
+
+    /// Error of the last update attempt, empty if it succeeded.
+    pub last_error: String,
+}
+
+/// Returns the addresses of all blocked contacts as a list signed with the own key.
+pub async fn export_blocklist(context: &Context) -> Result<String> {
+    let mut list = "# Delta Chat blocklist\n".to_string();
+    for contact_id in Contact::get_all_blocked(context).await? {
+        let contact = Contact::get_by_id(context, contact_id).await?;
+        // Blocked mailing lists have no address.
+        if EmailAddress::new(contact.get_addr()).is_ok() {
+            list += contact.get_addr();
+            list += "\n";
+        }
+    }
+    let key = SignedSecretKey::load_self(context).await?;
+    pgp::pk_sign(list.as_bytes(), &key)
+}
+
+/// Blocks the addresses of a list exported by [`export_blocklist`]
+/// and returns the number of newly blocked contacts.
+///
+/// Plain lists without signature are accepted, too.
+/// Domain entries cannot be blocked as contacts and are skipped.
+pub async fn import_blocklist(context: &Context, data: &str) -> Result<usize> {
+    let list = if data.trim_start().starts_with("-----BEGIN PGP MESSAGE-----") {
+        let (content, _) = pgp::pk_verify(data.as_bytes(), &Keyring::new())?;
+        String::from_utf8(content).context("Blocklist is not UTF-8")?
+    } else {
+        data.to_string()
+    };
+
+    let mut blocked = 0;
+    for entry in parse_list(&list) {
+        if entry.starts_with('@') {
+            continue;
+        }
+        let addr = match ContactAddress::new(&entry) {
+            Ok(addr) => addr,
+            Err(err) => {
+                warn!(context, "Skipping blocklist entry {entry:?}: {err:#}.");
+                continue;
+            }
+        };
+        let (contact_id, _) = Contact::add_or_lookup(context, "", addr, Origin::Hidden).await?;
+        if contact_id.is_special() || Contact::is_blocked_load(context, contact_id).await? {
+            continue;
+        }
+        Contact::block(context, contact_id).await?;
+        blocked += 1;
+    }
+    info!(
+        context,
+        "Blocked {blocked} contacts from imported blocklist."
+    );
+    Ok(blocked)
+}
+
+/// Subscribes to the blocklist served at `url`, which must be signed with the key of `contact_id`,
+/// and returns the ID of the feed.
+///
+/// The list is fetched the next time feeds are updated.
+pub async fn add_blocklist_feed(
+    context: &Context,
+    url: &str,
+    contact_id: ContactId,
+) -> Result<u32> {
+    ensure!(url.starts_with("https://"), "Blocklist URL must use HTTPS");
+    let contact = Contact::get_by_id(context, contact_id).await?;
+    let fingerprint = Peerstate::from_addr(context, contact.get_addr())
+        .await?
+        .as_ref()
+        .and_then(|peerstate| peerstate.peek_key(PeerstateVerifiedStatus::Unverified))
+        .map(DcKey::fingerprint)
+        .with_context(|| format!("No key of {contact_id} to check the blocklist signature"))?;
+    let id = context
+        .sql
+        .insert(
+            "INSERT INTO blocklist_feeds (url, fingerprint) VALUES (?, ?)",
+            (url, fingerprint.hex()),
+        )
+        .await?;
+    Ok(u32::try_from(id)?)
+}
+
+/// Returns all subscribed blocklists.
+pub async fn get_blocklist_feeds(context: &Context) -> Result<Vec<BlocklistFeed>> {
+    context
+        .sql
+        .query_map(
+            "SELECT id, url, fingerprint, last_update, last_error,
+             (SELECT COUNT(*) FROM blocklist WHERE feed_id=blocklist_feeds.id)
+             FROM blocklist_feeds ORDER BY id",
+            (),
+            |row| {
+                Ok(BlocklistFeed {
+                    id: row.get(0)?,
+                    url: row.get(1)?,
+                    fingerprint: row.get(2)?,
+                    last_update: row.get(3)?,
+                    last_error: row.get(4)?,
+                    entries: row.get(5)?,
+                })
+            },
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await
+}
+
+/// Unsubscribes from a blocklist and removes its entries.
+pub async fn delete_blocklist_feed(context: &Context, id: u32) -> Result<()> {
+    let deleted = context
+        .sql
+        .transaction(|transaction| {
+            transaction.execute("DELETE FROM blocklist WHERE feed_id=?", (id,))?;
+            let deleted = transaction.execute("DELETE FROM blocklist_feeds WHERE id=?", (id,))?;
+            Ok(deleted)
+        })
+        .await?;
+    ensure!(deleted > 0, "Blocklist feed {id} not found");
+    Ok(())
+}
+
+/// Fetches the feeds which were not updated for [`FEED_UPDATE_INTERVAL`].
+///
+/// Errors of single feeds are stored in the feed and do not stop updating the others.
+pub(crate) async fn maybe_update_feeds(context: &Context) -> Result<()> {
+    let feeds = context
+        .sql
+        .query_map(
+            "SELECT id, url FROM blocklist_feeds WHERE last_update<=?",
+            (time() - FEED_UPDATE_INTERVAL,),
+            |row| {
+                let id: u32 = row.get(0)?;
+                let url: String = row.get(1)?;
+                Ok((id, url))
+            },
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await?;
+    for (id, url) in feeds {
+        let res = match download_feed(context, &url).await {
+            Ok(data) => apply_feed(context, id, &data).await,
+            Err(err) => Err(err),
+        };
+        let last_error = match res {
+            Ok(entries) => {
+                info!(context, "Blocklist feed {id} has {entries} entries.");
+                String::new()
+            }
+            Err(err) => {
+                warn!(context, "Failed to update blocklist feed {id}: {err:#}.");
+                format!("{err:#}")
+            }
+        };
+        context
+            .sql
+            .execute(
+                "UPDATE blocklist_feeds SET last_update=?, last_error=? WHERE id=?",
+                (time(), last_error, id),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Downloads at most [`MAX_FEED_BYTES`] from `url`.
+async fn download_feed(context: &Context, url: &str) -> Result<Vec<u8>> {
+    let socks5_config = Socks5Config::from_database(&context.sql).await?;
+    let mut response = crate::http::get_client(socks5_config)?
+        .get(url)
+        .send()
+        .await
+        .context("Blocklist download failed")?
+        .error_for_status()?;
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        ensure!(
+            data.len() + chunk.len() <= MAX_FEED_BYTES,
+            "Blocklist exceeds {MAX_FEED_BYTES} bytes"
+        );
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+/// Checks the signature of a downloaded list and replaces the entries of the feed with it.
+///
+/// Returns the number of entries.
+pub(crate) async fn apply_feed(context: &Context, id: u32, data: &[u8]) -> Result<usize> {
+    let fingerprint: String = context
+        .sql
+        .query_get_value("SELECT fingerprint FROM blocklist_feeds WHERE id=?", (id,))
+        .await?
+        .with_context(|| format!("Blocklist feed {id} not found"))?;
+    let fingerprint: Fingerprint = fingerprint.parse()?;
+
+    let mut keyring: Keyring<SignedPublicKey> = Keyring::new();
+    if let Some(peerstate) = Peerstate::from_fingerprint(context, &fingerprint).await? {
+        for key in [
+            peerstate.public_key,
+            peerstate.gossip_key,
+            peerstate.verified_key,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            keyring.add(key);
+        }
+    }
+    let (content, fingerprints) = pgp::pk_verify(data, &keyring)?;
+    if !fingerprints.contains(&fingerprint) {
+        bail!("Blocklist is not signed by {fingerprint}");
+    }
+    let list = String::from_utf8(content).context("Blocklist is not UTF-8")?;
+    let entries = parse_list(&list);
+
+    let count = entries.len();
+    context
+        .sql
+        .transaction(move |transaction| {
+            transaction.execute("DELETE FROM blocklist WHERE feed_id=?", (id,))?;
+            let mut stmt = transaction
+                .prepare("INSERT OR IGNORE INTO blocklist (feed_id, entry) VALUES (?, ?)")?;
+            for entry in &entries {
+                stmt.execute((id, entry))?;
+            }
+            Ok(())
+        })
+        .await?;
+    Ok(count)
+}
+
+/// Returns true if messages from the contact should not be shown
+/// because its address or domain is on a subscribed blocklist.
+///
+/// Contacts with a known origin, e.g. contacts the user wrote to, are never denied.
+/// Domain entries covering the user's own provider are ignored.
+pub(crate) async fn is_denied(context: &Context, contact_id: ContactId) -> Result<bool> {
+    if contact_id.is_special() {
+        return Ok(false);
+    }
+    let contact = Contact::get_by_id(context, contact_id).await?;
+    if contact.origin.is_known() {
+        return Ok(false);
+    }
+    let addr = contact.get_addr().to_lowercase();
+    let domain = match EmailAddress::new(&addr) {
+        Ok(addr) => addr.domain,
+        Err(_) => return Ok(false),
+    };
+    if is_own_provider(context, &domain).await? {
+        return context
+            .sql
+            .exists("SELECT COUNT(*) FROM blocklist WHERE entry=?", (addr,))
+            .await;
+    }
+    context
+        .sql
+        .exists(
+            "SELECT COUNT(*) FROM blocklist WHERE entry=? OR entry=?",
+            (addr, format!("@{domain}")),
+        )
+        .await
+}
+
+/// Returns true if `domain` is the domain of an own address
+/// or belongs to the same provider as one.
+async fn is_own_provider(context: &Context, domain: &str) -> Result<bool> {
+    let provider = get_provider_by_domain(domain);
+    for self_addr in context.get_all_self_addrs().await? {
+        let self_domain = match EmailAddress::new(&self_addr) {
+            Ok(self_addr) => self_addr.domain.to_lowercase(),
+            Err(_) => continue,
+        };
+        if self_domain == domain {
+            return Ok(true);
+        }
+        if let (Some(provider), Some(self_provider)) =
+            (provider, get_provider_by_domain(&self_domain))
+        {
+            if provider.id == self_provider.id {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Returns the lowercase entries of a list.
+fn parse_list(list: &str) -> Vec<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::Chat;
+    use crate::constants::{Blocked, DC_CHAT_ID_TRASH};
+    use crate::receive_imf::receive_imf;
+    use crate::test_utils::TestContextManager;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_blocklist() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let fiona = tcm.fiona().await;
+
+        let spammer_id = Contact::create(&alice, "", "spammer@example.com").await?;
+        Contact::block(&alice, spammer_id).await?;
+        let list = export_blocklist(&alice).await?;
+
+        // Importing blocks the contacts.
+        assert_eq!(import_blocklist(&bob, &list).await?, 1);
+        let bob_spammer_id =
+            Contact::lookup_id_by_addr(&bob, "spammer@example.com", Origin::Unknown)
+                .await?
+                .unwrap();
+        assert!(Contact::is_blocked_load(&bob, bob_spammer_id).await?);
+        assert_eq!(import_blocklist(&bob, &list).await?, 0);
+        assert_eq!(
+            import_blocklist(&bob, "# comment\n\nSpammer2@Example.com\n@spam.example\n").await?,
+            1
+        );
+
+        // Subscribing requires the key of the maintainer.
+        let alice_id = fiona.add_or_lookup_contact(&alice).await.id;
+        assert!(
+            add_blocklist_feed(&fiona, "https://example.org/blocklist", alice_id)
+                .await
+                .is_err()
+        );
+        let alice_chat_id = alice.create_chat(&fiona).await.id;
+        fiona
+            .recv_msg(&alice.send_text(alice_chat_id, "hi").await)
+            .await;
+        assert!(
+            add_blocklist_feed(&fiona, "http://example.org/blocklist", alice_id)
+                .await
+                .is_err()
+        );
+        let feed_id = add_blocklist_feed(&fiona, "https://example.org/blocklist", alice_id).await?;
+
+        // Lists not signed by the maintainer are rejected.
+        let bob_list = export_blocklist(&bob).await?;
+        assert!(apply_feed(&fiona, feed_id, bob_list.as_bytes())
+            .await
+            .is_err());
+        assert_eq!(apply_feed(&fiona, feed_id, list.as_bytes()).await?, 1);
+        let feeds = get_blocklist_feeds(&fiona).await?;
+        assert_eq!(feeds.len(), 1);
+        assert_eq!(feeds[0].entries, 1);
+
+        let received = receive_imf(
+            &fiona,
+            b"From: spammer@example.com\n\
+              To: fiona@example.net\n\
+              Subject: offer\n\
+              Message-ID: <spam@example.com>\n\
+              Chat-Version: 1.0\n\
+              Date: Sun, 22 Mar 2020 22:37:57 +0000\n\
+              \n\
+              Buy now!\n",
+            false,
+        )
+        .await?
+        .unwrap();
+        assert_ne!(received.chat_id, DC_CHAT_ID_TRASH);
+        let chat = Chat::load_from_db(&fiona, received.chat_id).await?;
+        assert_eq!(chat.blocked, Blocked::Yes);
+        let fiona_spammer_id =
+            Contact::lookup_id_by_addr(&fiona, "spammer@example.com", Origin::Unknown)
+                .await?
+                .unwrap();
+        assert!(is_denied(&fiona, fiona_spammer_id).await?);
+        assert!(!Contact::is_blocked_load(&fiona, fiona_spammer_id).await?);
+
+        // Domain entries do not cover the own provider.
+        let list = pgp::pk_sign(
+            b"@example.com\n@example.net\n",
+            &SignedSecretKey::load_self(&alice).await?,
+        )?;
+        assert_eq!(apply_feed(&fiona, feed_id, list.as_bytes()).await?, 2);
+        for (addr, denied) in [("other@example.com", true), ("stranger@example.net", false)] {
+            let (contact_id, _) = Contact::add_or_lookup(
+                &fiona,
+                "",
+                ContactAddress::new(addr)?,
+                Origin::IncomingUnknownFrom,
+            )
+            .await?;
+            assert_eq!(is_denied(&fiona, contact_id).await?, denied);
+        }
+
+        delete_blocklist_feed(&fiona, feed_id).await?;
+        assert!(get_blocklist_feeds(&fiona).await?.is_empty());
+        assert!(!is_denied(&fiona, fiona_spammer_id).await?);
+        Ok(())
+    }
+}
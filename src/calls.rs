@@ -0,0 +1,550 @@
+//! # Signaling of 1:1 calls.
+//!
+//! UIs negotiate audio and video calls by exchanging opaque payloads,
+//! typically WebRTC session descriptions and ICE candidates,
+//! using [`send_call_offer`], [`send_call_answer`] and [`send_call_ice`].
+//! The media connection itself is up to the UI.
+//!
+//! Signaling is done with hidden chat messages,
+//! so it is end-to-end encrypted whenever the chat is
+//! and does not need a connection between the devices.
+//! The offer is kept as a hidden message in the chat,
+//! its ID identifies the call and the answer and the ICE candidates refer to it.
+//!
+//! As each signal is an email, ICE candidates are not sent one by one.
+//! WebRTC gathers many candidates within a short time,
+//! so the candidates passed to [`send_call_ice`] within [`ICE_BATCH_DELAY`]
+//! are sent together in one message.
+//!
+//! An offer rings for [`RINGING_TIMEOUT`] seconds.
+//! If it is not answered in time, [`EventType::CallMissed`] is emitted
+//! and a missed call is recorded in the chat of the callee.
+//! Offers arriving after the timeout, e.g. when the callee was offline,
+//! are only recorded.
+//!
+//! Records of calls are local messages of [`Viewtype::Call`],
+//! UIs and other call integrations add them with [`add_call_record`]
+//! to keep the call history in the chat.
+
+use std::time::Duration;
+
+use anyhow::{ensure, Context as _, Result};
+use base64::Engine as _;
+
+use crate::chat::{self, Chat, ChatId};
+use crate::constants::{Blocked, Chattype};
+use crate::contact::ContactId;
+use crate::context::Context;
+use crate::events::EventType;
+use crate::headerdef::HeaderDef;
+use crate::message::{self, Message, MessageState, MsgId, Viewtype};
+use crate::mimeparser::{MimeMessage, SystemMessage};
+use crate::param::{Param, Params};
+use crate::stock_str;
+use crate::tools::{create_outgoing_rfc724_mid, time};
+
+/// Time in seconds an unanswered call offer rings.
+pub const RINGING_TIMEOUT: i64 = 60;
+
+/// Time ICE candidates are collected before sending them in one message.
+pub const ICE_BATCH_DELAY: Duration = Duration::from_millis(500);
+
+/// State of a call, see [`Message::get_call_state`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum CallState {
+    /// The call is offered and not answered yet.
+    #[default]
+    Ringing = 0,
+
+    /// The call was answered.
+    Accepted = 1,
+
+    /// The call was not answered in time.
+    Missed = 2,
+
+    /// The call was declined by the callee.
+    Declined = 3,
+}
+
+/// Offers a call in a 1:1 chat.
+///
+/// `payload` is passed to the recipient with [`EventType::IncomingCallOffer`].
+/// Returns the ID of the hidden offer message, which identifies the call.
+pub async fn send_call_offer(context: &Context, chat_id: ChatId, payload: &str) -> Result<MsgId> {
+    let chat = Chat::load_from_db(context, chat_id).await?;
+    ensure!(
+        chat.typ == Chattype::Single && !chat.is_self_talk() && !chat.is_device_talk(),
+        "Calls are only possible in 1:1 chats"
+    );
+
+    let mut msg = Message::new(Viewtype::Text);
+    msg.hidden = true;
+    msg.param.set_cmd(SystemMessage::CallOffer);
+    msg.param.set(Param::Arg, payload);
+    let msg_id = chat::send_msg(context, chat_id, &mut msg).await?;
+    tokio::spawn(ring(context.clone(), msg_id, RINGING_TIMEOUT));
+    Ok(msg_id)
+}
+
+/// Answers the incoming call offered by the message `call_id`.
+///
+/// `payload` is passed to the caller with [`EventType::IncomingCallAnswer`].
+pub async fn send_call_answer(context: &Context, call_id: MsgId, payload: &str) -> Result<MsgId> {
+    let mut offer = load_offer(context, call_id).await?;
+    ensure!(offer.from_id != ContactId::SELF, "Cannot answer own call");
+    ensure!(
+        get_state(&offer) == CallState::Ringing && !is_expired(&offer),
+        "Call {call_id} is not ringing"
+    );
+    offer
+        .param
+        .set_int(Param::CallState, CallState::Accepted as i32);
+    offer.update_param(context).await?;
+    send_signal(context, &offer, SystemMessage::CallAnswer, payload).await
+}
+
+/// Sends an ICE candidate of the call offered by the message `call_id`.
+///
+/// `payload` is passed to the peer with [`EventType::IncomingCallIce`].
+/// The candidate is sent after [`ICE_BATCH_DELAY`]
+/// together with the other candidates of the call passed in the meantime.
+pub async fn send_call_ice(context: &Context, call_id: MsgId, payload: &str) -> Result<()> {
+    load_offer(context, call_id).await?;
+    let mut queue = context.call_ice_candidates.lock().await;
+    let candidates = queue.entry(call_id).or_default();
+    candidates.push(payload.to_string());
+    if candidates.len() == 1 {
+        let context = context.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(ICE_BATCH_DELAY).await;
+            if let Err(err) = flush_ice_candidates(&context, call_id).await {
+                warn!(
+                    context,
+                    "Failed to send ICE candidates of {call_id}: {err:#}."
+                );
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Sends the ICE candidates of the call collected by [`send_call_ice`] in one message.
+async fn flush_ice_candidates(context: &Context, call_id: MsgId) -> Result<()> {
+    let candidates = context.call_ice_candidates.lock().await.remove(&call_id);
+    let candidates = match candidates {
+        Some(candidates) => candidates,
+        None => return Ok(()),
+    };
+    let offer = load_offer(context, call_id).await?;
+    let payload = serde_json::to_string(&candidates)?;
+    send_signal(context, &offer, SystemMessage::CallIceCandidate, &payload).await?;
+    Ok(())
+}
+
+async fn send_signal(
+    context: &Context,
+    offer: &Message,
+    cmd: SystemMessage,
+    payload: &str,
+) -> Result<MsgId> {
+    let mut msg = Message::new(Viewtype::Text);
+    msg.hidden = true;
+    msg.param.set_cmd(cmd);
+    msg.param.set(Param::Arg, payload);
+    msg.in_reply_to = Some(offer.rfc724_mid.clone());
+    chat::send_msg(context, offer.chat_id, &mut msg).await
+}
+
+async fn load_offer(context: &Context, call_id: MsgId) -> Result<Message> {
+    let offer = Message::load_from_db(context, call_id).await?;
+    ensure!(
+        offer.param.get_cmd() == SystemMessage::CallOffer,
+        "{call_id} is not a call offer"
+    );
+    Ok(offer)
+}
+
+fn get_state(offer: &Message) -> CallState {
+    offer.get_call_state().unwrap_or_default()
+}
+
+/// Returns true if the offer would have stopped ringing already.
+fn is_expired(offer: &Message) -> bool {
+    ringing_left(offer) <= 0
+}
+
+/// Returns the remaining ringing time of the offer in seconds.
+fn ringing_left(offer: &Message) -> i64 {
+    let ringing_since = if offer.from_id == ContactId::SELF {
+        offer.timestamp_sort
+    } else {
+        offer.timestamp_sent
+    };
+    ringing_since + RINGING_TIMEOUT - time()
+}
+
+/// Handles a received call signaling message.
+pub(crate) async fn receive_call_msg(
+    context: &Context,
+    mime_parser: &MimeMessage,
+    from_id: ContactId,
+    msg_id: MsgId,
+) -> Result<()> {
+    let payload = mime_parser
+        .get_header(HeaderDef::ChatCallPayload)
+        .map(|value| value.split_ascii_whitespace().collect::<String>())
+        .map(|value| base64::engine::general_purpose::STANDARD.decode(value))
+        .transpose()
+        .context("Invalid call payload")?
+        .map(|payload| String::from_utf8_lossy(&payload).into_owned())
+        .unwrap_or_default();
+
+    if mime_parser.is_system_message == SystemMessage::CallOffer {
+        if from_id == ContactId::SELF {
+            // Sent from another device, the callee answers there.
+            return Ok(());
+        }
+        let offer = load_offer(context, msg_id).await?;
+        let chat = Chat::load_from_db(context, offer.chat_id).await?;
+        let ringing_left = ringing_left(&offer);
+        if chat.typ != Chattype::Single || chat.blocked != Blocked::Not || ringing_left <= 0 {
+            info!(context, "Call offer {msg_id} is not ringing.");
+            return handle_ringing_timeout(context, msg_id).await;
+        }
+        context.emit_event(EventType::IncomingCallOffer { msg_id, payload });
+        tokio::spawn(ring(context.clone(), msg_id, ringing_left));
+        return Ok(());
+    }
+
+    let call_id = match mime_parser.get_header(HeaderDef::InReplyTo) {
+        Some(in_reply_to) => message::rfc724_mid_exists(context, in_reply_to).await?,
+        None => None,
+    }
+    .context("Call offer not found")?;
+    let mut offer = load_offer(context, call_id).await?;
+    match mime_parser.is_system_message {
+        SystemMessage::CallAnswer => {
+            if get_state(&offer) != CallState::Ringing {
+                warn!(context, "Ignoring answer to call {call_id} not ringing.");
+                return Ok(());
+            }
+            offer
+                .param
+                .set_int(Param::CallState, CallState::Accepted as i32);
+            offer.update_param(context).await?;
+            if from_id != ContactId::SELF {
+                context.emit_event(EventType::IncomingCallAnswer {
+                    msg_id: call_id,
+                    payload,
+                });
+            }
+        }
+        SystemMessage::CallIceCandidate => {
+            if from_id != ContactId::SELF {
+                let candidates: Vec<String> =
+                    serde_json::from_str(&payload).context("Invalid ICE candidates")?;
+                for payload in candidates {
+                    context.emit_event(EventType::IncomingCallIce {
+                        msg_id: call_id,
+                        payload,
+                    });
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Waits for `secs` seconds and handles the ringing timeout of the offer.
+async fn ring(context: Context, msg_id: MsgId, secs: i64) {
+    tokio::time::sleep(Duration::from_secs(secs.try_into().unwrap_or_default())).await;
+    if let Err(err) = handle_ringing_timeout(&context, msg_id).await {
+        warn!(
+            context,
+            "Failed to handle ringing timeout of {msg_id}: {err:#}."
+        );
+    }
+}
+
+/// Marks the call as missed if it was not answered yet.
+pub(crate) async fn handle_ringing_timeout(context: &Context, msg_id: MsgId) -> Result<()> {
+    let mut offer = load_offer(context, msg_id).await?;
+    if get_state(&offer) != CallState::Ringing {
+        return Ok(());
+    }
+    offer
+        .param
+        .set_int(Param::CallState, CallState::Missed as i32);
+    offer.update_param(context).await?;
+    if offer.from_id != ContactId::SELF {
+        add_call_record(
+            context,
+            offer.chat_id,
+            true,
+            CallState::Missed,
+            0,
+            offer.timestamp_sent,
+        )
+        .await?;
+    }
+    context.emit_event(EventType::CallMissed { msg_id });
+    Ok(())
+}
+
+/// Adds a record of a call to a 1:1 chat.
+///
+/// The record is a local message of [`Viewtype::Call`] and is not sent.
+/// `timestamp` is the start of the call,
+/// `duration` the duration of an accepted call in milliseconds.
+/// Incoming missed calls are added as fresh messages, so they are notified.
+pub async fn add_call_record(
+    context: &Context,
+    chat_id: ChatId,
+    incoming: bool,
+    state: CallState,
+    duration: i32,
+    timestamp: i64,
+) -> Result<MsgId> {
+    ensure!(state != CallState::Ringing, "Cannot record a ringing call");
+    let chat = Chat::load_from_db(context, chat_id).await?;
+    ensure!(
+        chat.typ == Chattype::Single && !chat.is_self_talk() && !chat.is_device_talk(),
+        "Calls are only possible in 1:1 chats"
+    );
+    let contact_id = *chat::get_chat_contacts(context, chat_id)
+        .await?
+        .first()
+        .context("Chat has no contact")?;
+    let (from_id, to_id, msg_state) = if !incoming {
+        (ContactId::SELF, contact_id, MessageState::OutDelivered)
+    } else if state == CallState::Missed {
+        (contact_id, ContactId::SELF, MessageState::InFresh)
+    } else {
+        (contact_id, ContactId::SELF, MessageState::InSeen)
+    };
+    let mut param = Params::new();
+    param.set_int(Param::CallState, state as i32);
+    if state == CallState::Accepted && duration > 0 {
+        param.set_int(Param::Duration, duration);
+    }
+
+    let row_id = context
+        .sql
+        .insert(
+            "INSERT INTO msgs
+             (rfc724_mid, chat_id, from_id, to_id, timestamp, timestamp_sent, timestamp_rcvd,
+              type, state, txt, param)
+             VALUES (?,?,?,?,?,?,?,?,?,'',?)",
+            (
+                create_outgoing_rfc724_mid(None, "@device"),
+                chat_id,
+                from_id,
+                to_id,
+                timestamp,
+                timestamp,
+                timestamp,
+                Viewtype::Call,
+                msg_state,
+                param.to_string(),
+            ),
+        )
+        .await?;
+    let msg_id = MsgId::new(u32::try_from(row_id)?);
+    chat_id.unarchive_if_not_muted(context, msg_state).await?;
+    chat_id
+        .emit_msg_event(context, msg_id, msg_state == MessageState::InFresh)
+        .await;
+    Ok(msg_id)
+}
+
+/// Returns the summary of a call record, e.g. `Outgoing call, 1:05`.
+pub(crate) async fn get_summary_text(context: &Context, msg: &Message) -> String {
+    let incoming = msg.from_id != ContactId::SELF;
+    let state = msg.get_call_state().unwrap_or_default();
+    let text = match state {
+        CallState::Declined => stock_str::declined_call(context).await,
+        CallState::Missed if incoming => stock_str::missed_call(context).await,
+        _ if incoming => stock_str::incoming_call(context).await,
+        _ => stock_str::outgoing_call(context).await,
+    };
+    let secs = msg.get_duration() / 1000;
+    if state != CallState::Accepted || secs <= 0 {
+        return text;
+    }
+    let duration = if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    };
+    format!("{text}, {duration}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::ProtectionStatus;
+    use crate::test_utils::{SentMessage, TestContext, TestContextManager};
+
+    async fn recv_offer(t: &TestContext, sent: &SentMessage<'_>) -> Message {
+        let received = t.recv_msg_opt(sent).await.unwrap();
+        Message::load_from_db(t, received.msg_ids[0]).await.unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_call_signaling() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let alice_chat_id = alice.create_chat(&bob).await.id;
+        bob.create_chat(&alice).await;
+
+        let alice_call_id = send_call_offer(&alice, alice_chat_id, "offer\r\nsdp").await?;
+        let sent = alice.pop_sent_msg().await;
+        let bob_call = recv_offer(&bob, &sent).await;
+        assert!(bob_call.hidden);
+        let ev = bob
+            .evtracker
+            .get_matching(|ev| matches!(ev, EventType::IncomingCallOffer { .. }))
+            .await;
+        assert_eq!(
+            ev,
+            EventType::IncomingCallOffer {
+                msg_id: bob_call.id,
+                payload: "offer\r\nsdp".to_string()
+            }
+        );
+
+        send_call_answer(&bob, bob_call.id, "answer").await?;
+        let sent = bob.pop_sent_msg().await;
+        alice.recv_msg_opt(&sent).await;
+        let ev = alice
+            .evtracker
+            .get_matching(|ev| matches!(ev, EventType::IncomingCallAnswer { .. }))
+            .await;
+        assert_eq!(
+            ev,
+            EventType::IncomingCallAnswer {
+                msg_id: alice_call_id,
+                payload: "answer".to_string()
+            }
+        );
+        assert!(send_call_answer(&bob, bob_call.id, "answer").await.is_err());
+
+        // Candidates are sent together.
+        send_call_ice(&alice, alice_call_id, "candidate 1").await?;
+        send_call_ice(&alice, alice_call_id, "candidate 2").await?;
+        flush_ice_candidates(&alice, alice_call_id).await?;
+        let sent = alice.pop_sent_msg().await;
+        bob.recv_msg_opt(&sent).await;
+        for payload in ["candidate 1", "candidate 2"] {
+            let ev = bob
+                .evtracker
+                .get_matching(|ev| matches!(ev, EventType::IncomingCallIce { .. }))
+                .await;
+            assert_eq!(
+                ev,
+                EventType::IncomingCallIce {
+                    msg_id: bob_call.id,
+                    payload: payload.to_string()
+                }
+            );
+        }
+        assert!(alice.call_ice_candidates.lock().await.is_empty());
+
+        // Answered calls are not missed.
+        let msgs_cnt = chat::get_chat_msgs(&bob, bob_call.chat_id).await?.len();
+        handle_ringing_timeout(&bob, bob_call.id).await?;
+        assert_eq!(
+            chat::get_chat_msgs(&bob, bob_call.chat_id).await?.len(),
+            msgs_cnt
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_missed_call() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let alice_chat_id = alice.create_chat(&bob).await.id;
+        bob.create_chat(&alice).await;
+
+        send_call_offer(&alice, alice_chat_id, "offer").await?;
+        let sent = alice.pop_sent_msg().await;
+        let bob_call = recv_offer(&bob, &sent).await;
+        handle_ringing_timeout(&bob, bob_call.id).await?;
+        bob.evtracker
+            .get_matching(|ev| matches!(ev, EventType::CallMissed { .. }))
+            .await;
+        let record = bob.get_last_msg_in(bob_call.chat_id).await;
+        assert_eq!(record.get_viewtype(), Viewtype::Call);
+        assert_eq!(record.get_call_state(), Some(CallState::Missed));
+        assert_eq!(record.get_from_id(), bob_call.get_from_id());
+        assert_eq!(record.get_state(), MessageState::InFresh);
+        assert!(send_call_answer(&bob, bob_call.id, "answer").await.is_err());
+
+        // Offers in contact requests do not ring.
+        let fiona = tcm.fiona().await;
+        let fiona_chat_id = fiona.create_chat(&bob).await.id;
+        send_call_offer(&fiona, fiona_chat_id, "offer").await?;
+        let sent = fiona.pop_sent_msg().await;
+        let bob_call = recv_offer(&bob, &sent).await;
+        let record = bob.get_last_msg_in(bob_call.chat_id).await;
+        assert_eq!(record.get_call_state(), Some(CallState::Missed));
+
+        // Calls are only possible in 1:1 chats.
+        let group_id =
+            chat::create_group_chat(&alice, ProtectionStatus::Unprotected, "Group").await?;
+        assert!(send_call_offer(&alice, group_id, "offer").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_call_records() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let chat_id = alice.create_chat(&bob).await.id;
+        let bob_id = alice.add_or_lookup_contact(&bob).await.id;
+        let now = time();
+
+        for (incoming, state, duration, summary) in [
+            (false, CallState::Accepted, 65_000, "Outgoing call, 1:05"),
+            (
+                true,
+                CallState::Accepted,
+                3_725_000,
+                "Incoming call, 1:02:05",
+            ),
+            (false, CallState::Missed, 0, "Outgoing call"),
+            (true, CallState::Missed, 0, "Missed call"),
+            (true, CallState::Declined, 0, "Declined call"),
+        ] {
+            let msg_id = add_call_record(&alice, chat_id, incoming, state, duration, now).await?;
+            let msg = Message::load_from_db(&alice, msg_id).await?;
+            assert_eq!(msg.get_viewtype(), Viewtype::Call);
+            assert_eq!(msg.get_call_state(), Some(state));
+            assert_eq!(msg.get_duration(), duration);
+            let from_id = if incoming { bob_id } else { ContactId::SELF };
+            assert_eq!(msg.get_from_id(), from_id);
+            let summary_text = msg.get_summary(&alice, None).await?.text;
+            assert_eq!(summary_text, summary);
+        }
+        assert_eq!(chat_id.get_fresh_msg_cnt(&alice).await?, 1);
+
+        assert!(
+            add_call_record(&alice, chat_id, true, CallState::Ringing, 0, now)
+                .await
+                .is_err()
+        );
+        let self_chat = alice.get_self_chat().await;
+        assert!(
+            add_call_record(&alice, self_chat.id, false, CallState::Accepted, 0, now)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+}
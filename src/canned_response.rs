@@ -0,0 +1,413 @@
+//! # Canned responses.
+//!
+//! Canned responses are texts, optionally with a file, that are sent frequently,
+//! e.g. answers to common questions.
+//! They can be created from scratch or from an existing message,
+//! see [`save_msg_as_canned_response`],
+//! and inserted into a chat with [`canned_response_to_msg`] or [`send_canned_response`].
+//!
+//! Titles and texts are synchronized to other devices if [`Config::SendSyncMsgs`] is enabled,
+//! files are only stored on the device that added them.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context as _, Result};
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+
+use crate::blob::BlobObject;
+use crate::chat::{self, ChatId};
+use crate::context::Context;
+use crate::events::EventType;
+use crate::message::{Message, MsgId, Viewtype};
+use crate::sync::SyncData;
+use crate::tools::{create_id, get_abs_path, time};
+
+#[cfg(doc)]
+use crate::config::Config;
+
+/// A canned response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CannedResponse {
+    /// Database ID of the canned response.
+    pub id: u32,
+
+    /// Title shown when choosing a canned response.
+    pub title: String,
+
+    /// Text to send.
+    pub text: String,
+
+    /// Blob name of the file to send, if any.
+    file: Option<String>,
+}
+
+impl CannedResponse {
+    /// Returns the path of the file to send, if any.
+    pub fn get_file(&self, context: &Context) -> Option<PathBuf> {
+        self.file.as_ref().map(|file| get_abs_path(context, file))
+    }
+}
+
+/// Canned response as synchronized to other devices.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct CannedResponseData {
+    pub(crate) uid: String,
+    pub(crate) title: String,
+    pub(crate) text: String,
+}
+
+/// Copies `file` to the blobdir if needed and returns the blob name.
+async fn file_to_blob(context: &Context, file: Option<&str>) -> Result<String> {
+    match file {
+        Some(file) if !file.is_empty() => Ok(BlobObject::new_from_path(context, Path::new(file))
+            .await?
+            .as_name()
+            .to_string()),
+        _ => Ok("".to_string()),
+    }
+}
+
+/// Adds a canned response and returns its ID.
+pub async fn add_canned_response(
+    context: &Context,
+    title: &str,
+    text: &str,
+    file: Option<&str>,
+) -> Result<u32> {
+    ensure!(!title.trim().is_empty(), "Canned response has no title");
+    let file = file_to_blob(context, file).await?;
+    let uid = create_id();
+    let id = context
+        .sql
+        .insert(
+            "INSERT INTO canned_responses (uid, title, text, file, timestamp)
+             VALUES (?, ?, ?, ?, ?)",
+            (&uid, title.trim(), text, file, time()),
+        )
+        .await?;
+    let id = u32::try_from(id)?;
+    sync_canned_response(context, id).await?;
+    context.emit_event(EventType::CannedResponsesChanged);
+    Ok(id)
+}
+
+/// Adds the text and the file of a message as canned response and returns its ID.
+pub async fn save_msg_as_canned_response(
+    context: &Context,
+    msg_id: MsgId,
+    title: &str,
+) -> Result<u32> {
+    let msg = Message::load_from_db(context, msg_id).await?;
+    ensure!(
+        !msg.is_view_once(),
+        "View-once {msg_id} cannot be saved as canned response"
+    );
+    let file = msg.get_file(context);
+    add_canned_response(
+        context,
+        title,
+        msg.get_text().as_deref().unwrap_or_default(),
+        file.as_deref().and_then(Path::to_str),
+    )
+    .await
+}
+
+/// Changes title, text and file of a canned response.
+pub async fn update_canned_response(
+    context: &Context,
+    id: u32,
+    title: &str,
+    text: &str,
+    file: Option<&str>,
+) -> Result<()> {
+    ensure!(!title.trim().is_empty(), "Canned response has no title");
+    let file = file_to_blob(context, file).await?;
+    let updated = context
+        .sql
+        .execute(
+            "UPDATE canned_responses SET title=?, text=?, file=?, timestamp=? WHERE id=?",
+            (title.trim(), text, file, time(), id),
+        )
+        .await?;
+    ensure!(updated > 0, "Canned response {id} not found");
+    sync_canned_response(context, id).await?;
+    context.emit_event(EventType::CannedResponsesChanged);
+    Ok(())
+}
+
+/// Deletes a canned response.
+pub async fn delete_canned_response(context: &Context, id: u32) -> Result<()> {
+    let uid: String = context
+        .sql
+        .query_get_value("SELECT uid FROM canned_responses WHERE id=?", (id,))
+        .await?
+        .with_context(|| format!("Canned response {id} not found"))?;
+    let timestamp = time();
+    context
+        .sql
+        .transaction(|transaction| {
+            transaction.execute("DELETE FROM canned_responses WHERE id=?", (id,))?;
+            transaction.execute(
+                "INSERT OR REPLACE INTO canned_responses_deleted (uid, timestamp) VALUES (?, ?)",
+                (&uid, timestamp),
+            )?;
+            Ok(())
+        })
+        .await?;
+    context
+        .add_sync_item(SyncData::DeleteCannedResponse(uid))
+        .await?;
+    context.send_sync_msg().await?;
+    context.emit_event(EventType::CannedResponsesChanged);
+    Ok(())
+}
+
+/// Returns a canned response by its ID.
+pub async fn get_canned_response(context: &Context, id: u32) -> Result<Option<CannedResponse>> {
+    context
+        .sql
+        .query_row_optional(
+            "SELECT id, title, text, file FROM canned_responses WHERE id=?",
+            (id,),
+            row_to_canned_response,
+        )
+        .await
+}
+
+/// Returns all canned responses ordered by title.
+pub async fn get_canned_responses(context: &Context) -> Result<Vec<CannedResponse>> {
+    context
+        .sql
+        .query_map(
+            "SELECT id, title, text, file FROM canned_responses ORDER BY title COLLATE NOCASE, id",
+            (),
+            row_to_canned_response,
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await
+}
+
+fn row_to_canned_response(row: &rusqlite::Row) -> rusqlite::Result<CannedResponse> {
+    let file: String = row.get(3)?;
+    Ok(CannedResponse {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        text: row.get(2)?,
+        file: Some(file).filter(|file| !file.is_empty()),
+    })
+}
+
+/// Creates a message from a canned response,
+/// to be used as draft with [`ChatId::set_draft`] or to be sent.
+pub async fn canned_response_to_msg(context: &Context, id: u32) -> Result<Message> {
+    let response = get_canned_response(context, id)
+        .await?
+        .with_context(|| format!("Canned response {id} not found"))?;
+    let mut msg = if let Some(file) = response.file {
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file, None);
+        msg
+    } else {
+        Message::new(Viewtype::Text)
+    };
+    if !response.text.is_empty() {
+        msg.set_text(Some(response.text));
+    }
+    Ok(msg)
+}
+
+/// Sends a canned response to a chat.
+pub async fn send_canned_response(context: &Context, chat_id: ChatId, id: u32) -> Result<MsgId> {
+    let mut msg = canned_response_to_msg(context, id).await?;
+    chat::send_msg(context, chat_id, &mut msg).await
+}
+
+/// Adds a canned response to the items to be synced and sends out the sync message.
+async fn sync_canned_response(context: &Context, id: u32) -> Result<()> {
+    let data = context
+        .sql
+        .query_row(
+            "SELECT uid, title, text FROM canned_responses WHERE id=?",
+            (id,),
+            |row| {
+                Ok(CannedResponseData {
+                    uid: row.get(0)?,
+                    title: row.get(1)?,
+                    text: row.get(2)?,
+                })
+            },
+        )
+        .await?;
+    context
+        .add_sync_item(SyncData::SetCannedResponse(data))
+        .await?;
+    context.send_sync_msg().await?;
+    Ok(())
+}
+
+/// Adds or updates a canned response received from another device.
+///
+/// Changes older than the local ones or than the deletion of the canned response are ignored,
+/// the local file is kept.
+pub(crate) async fn set_from_sync(
+    context: &Context,
+    data: &CannedResponseData,
+    timestamp: i64,
+) -> Result<()> {
+    let uid = data.uid.clone();
+    let title = data.title.clone();
+    let text = data.text.clone();
+    let changed = context
+        .sql
+        .transaction(move |transaction| {
+            let deleted: Option<i64> = transaction
+                .query_row(
+                    "SELECT timestamp FROM canned_responses_deleted WHERE uid=?",
+                    (&uid,),
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if deleted.map_or(false, |deleted| deleted > timestamp) {
+                return Ok(false);
+            }
+            transaction.execute("DELETE FROM canned_responses_deleted WHERE uid=?", (&uid,))?;
+            let changed = transaction.execute(
+                "INSERT INTO canned_responses (uid, title, text, file, timestamp)
+                 VALUES (?, ?, ?, '', ?)
+                 ON CONFLICT(uid) DO UPDATE
+                 SET title=excluded.title, text=excluded.text, timestamp=excluded.timestamp
+                 WHERE excluded.timestamp>=canned_responses.timestamp",
+                (&uid, &title, &text, timestamp),
+            )?;
+            Ok(changed > 0)
+        })
+        .await?;
+    if changed {
+        context.emit_event(EventType::CannedResponsesChanged);
+    }
+    Ok(())
+}
+
+/// Deletes a canned response deleted on another device.
+///
+/// The deletion is remembered, so that older changes received later do not restore it.
+/// A canned response changed after the deletion is kept.
+pub(crate) async fn delete_from_sync(context: &Context, uid: &str, timestamp: i64) -> Result<()> {
+    let uid = uid.to_string();
+    let deleted = context
+        .sql
+        .transaction(move |transaction| {
+            transaction.execute(
+                "INSERT INTO canned_responses_deleted (uid, timestamp) VALUES (?1, ?2)
+                 ON CONFLICT(uid) DO UPDATE SET timestamp=MAX(timestamp, excluded.timestamp)",
+                (&uid, timestamp),
+            )?;
+            let deleted = transaction.execute(
+                "DELETE FROM canned_responses WHERE uid=? AND timestamp<=?",
+                (&uid, timestamp),
+            )?;
+            Ok(deleted > 0)
+        })
+        .await?;
+    if deleted {
+        context.emit_event(EventType::CannedResponsesChanged);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_utils::TestContextManager;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_canned_responses() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        assert!(get_canned_responses(&alice).await?.is_empty());
+        assert!(add_canned_response(&alice, " ", "text", None)
+            .await
+            .is_err());
+
+        let id = add_canned_response(&alice, "Opening hours", "Mo-Fr 9-17", None).await?;
+        let file = alice.get_blobdir().join("prices.pdf");
+        tokio::fs::write(&file, b"prices").await?;
+        let id2 = add_canned_response(&alice, "prices", "See attachment", file.to_str()).await?;
+        let responses = get_canned_responses(&alice).await?;
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].title, "Opening hours");
+        assert_eq!(responses[0].get_file(&alice), None);
+        assert_eq!(responses[1].id, id2);
+        assert_eq!(responses[1].get_file(&alice), Some(file));
+
+        update_canned_response(&alice, id, "Opening hours", "Mo-Sa 9-17", None).await?;
+        let response = get_canned_response(&alice, id).await?.unwrap();
+        assert_eq!(response.text, "Mo-Sa 9-17");
+
+        let chat_id = alice.create_chat(&bob).await.id;
+        send_canned_response(&alice, chat_id, id2).await?;
+        let msg = bob.recv_msg(&alice.pop_sent_msg().await).await;
+        assert_eq!(msg.get_viewtype(), Viewtype::File);
+        assert_eq!(msg.get_text().unwrap(), "See attachment");
+
+        // Received messages can be saved as canned response, too.
+        let id3 = save_msg_as_canned_response(&bob, msg.id, "Prices").await?;
+        let msg = canned_response_to_msg(&bob, id3).await?;
+        assert_eq!(msg.get_text().unwrap(), "See attachment");
+        assert_eq!(
+            tokio::fs::read(msg.get_file(&bob).unwrap()).await?,
+            b"prices"
+        );
+
+        delete_canned_response(&alice, id).await?;
+        assert_eq!(get_canned_responses(&alice).await?.len(), 1);
+        assert!(get_canned_response(&alice, id).await?.is_none());
+        assert!(delete_canned_response(&alice, id).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_sync_canned_responses() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice1 = tcm.alice().await;
+        let alice2 = tcm.alice().await;
+        for a in [&alice1, &alice2] {
+            a.set_config_bool(Config::SendSyncMsgs, true).await?;
+        }
+
+        let id = add_canned_response(&alice1, "Greeting", "Hello!", None).await?;
+        alice2.recv_msg(&alice1.pop_sent_msg().await).await;
+        let responses = get_canned_responses(&alice2).await?;
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].title, "Greeting");
+        assert_eq!(responses[0].text, "Hello!");
+
+        update_canned_response(&alice1, id, "Greeting", "Hi!", None).await?;
+        alice2.recv_msg(&alice1.pop_sent_msg().await).await;
+        assert_eq!(get_canned_responses(&alice2).await?[0].text, "Hi!");
+
+        delete_canned_response(&alice1, id).await?;
+        alice2.recv_msg(&alice1.pop_sent_msg().await).await;
+        assert!(get_canned_responses(&alice2).await?.is_empty());
+
+        // Changes made before the deletion do not restore the canned response.
+        let data = CannedResponseData {
+            uid: "uid".to_string(),
+            title: "Outdated".to_string(),
+            text: "text".to_string(),
+        };
+        delete_from_sync(&alice2, &data.uid, 1000).await?;
+        set_from_sync(&alice2, &data, 999).await?;
+        assert!(get_canned_responses(&alice2).await?.is_empty());
+
+        // Changes made after the deletion restore it.
+        set_from_sync(&alice2, &data, 1001).await?;
+        assert_eq!(get_canned_responses(&alice2).await?.len(), 1);
+        delete_from_sync(&alice2, &data.uid, 1000).await?;
+        assert_eq!(get_canned_responses(&alice2).await?.len(), 1);
+        Ok(())
+    }
+}
@@ -8,7 +8,9 @@
 use std::time::{Duration, SystemTime};
 
 use anyhow::{bail, ensure, Context as _, Result};
+use chrono::{Datelike, NaiveDateTime};
 use deltachat_derive::{FromSql, ToSql};
+use num_traits::FromPrimitive;
 use serde::{Deserialize, Serialize};
 
 use crate::aheader::EncryptPreference;
@@ -16,8 +18,9 @@
 use crate::color::str_to_color;
 use crate::config::Config;
 use crate::constants::{
-    Blocked, Chattype, DC_CHAT_ID_ALLDONE_HINT, DC_CHAT_ID_ARCHIVED_LINK, DC_CHAT_ID_LAST_SPECIAL,
-    DC_CHAT_ID_TRASH, DC_RESEND_USER_AVATAR_DAYS,
+    Blocked, Chattype, OversizedAttachment, DC_CHAT_ID_ALLDONE_HINT, DC_CHAT_ID_ARCHIVED_LINK,
+    DC_CHAT_ID_LAST_SPECIAL, DC_CHAT_ID_TRASH, DC_DESIRED_TEXT_LINES, DC_DESIRED_TEXT_LINE_LEN,
+    DC_RESEND_USER_AVATAR_DAYS,
 };
 use crate::contact::{Contact, ContactId, Origin, VerifiedStatus};
 use crate::context::Context;
@@ -28,7 +31,7 @@
 use crate::message::{self, Message, MessageState, MsgId, Viewtype};
 use crate::mimefactory::MimeFactory;
 use crate::mimeparser::SystemMessage;
-use crate::param::{Param, Params};
+use crate::param::{Param, Params, ParamsFile};
 use crate::peerstate::{Peerstate, PeerstateVerifiedStatus};
 use crate::receive_imf::ReceivedMsg;
 use crate::scheduler::InterruptInfo;
@@ -36,11 +39,12 @@
 use crate::stock_str;
 use crate::tools::{
     buf_compress, create_id, create_outgoing_rfc724_mid, create_smeared_timestamp,
-    create_smeared_timestamps, get_abs_path, gm2local_offset, improve_single_line_input,
-    strip_rtlo_characters, time, IsNoneOrEmpty,
+    create_smeared_timestamps, create_videochat_room, get_abs_path, gm2local_offset,
+    improve_single_line_input, is_suspicious_name, strip_rtlo_characters, time, truncate_by_lines,
+    IsNoneOrEmpty,
 };
 use crate::webxdc::WEBXDC_SUFFIX;
-use crate::{location, sql};
+use crate::{location, notification, sql};
 
 /// An chat item, such as a message or a marker.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -88,6 +92,38 @@ pub enum ProtectionStatus {
     Protected = 1,
 }
 
+/// Determines whether new messages unarchive an archived chat.
+#[derive(
+    Debug,
+    Default,
+    Display,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    FromPrimitive,
+    ToPrimitive,
+    FromSql,
+    ToSql,
+    Serialize,
+    Deserialize,
+)]
+#[repr(u32)]
+pub enum ArchiveMode {
+    /// Any new message unarchives the chat, unless the chat is muted.
+    #[default]
+    AlwaysUnarchive = 0,
+
+    /// The chat stays archived,
+    /// fresh messages are only counted at the archive link.
+    StayArchived = 1,
+
+    /// The chat is unarchived only by messages mentioning the user,
+    /// i.e. replies to the user's messages or messages containing
+    /// the user's address or `@` followed by the user's display name.
+    UnarchiveOnMention = 2,
+}
+
 /// The reason why messages cannot be sent to the chat.
 ///
 /// The reason is mainly for logging and displaying in debug REPL, thus not translated.
@@ -107,6 +143,9 @@ pub(crate) enum CantSendReason {
 
     /// Not a member of the chat.
     NotAMember,
+
+    /// Only verified contacts are accepted, but the chat has unverified members.
+    NotVerified,
 }
 
 impl fmt::Display for CantSendReason {
@@ -122,6 +161,10 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 write!(f, "mailing list does not have a know post address")
             }
             Self::NotAMember => write!(f, "not a member of the chat"),
+            Self::NotVerified => write!(
+                f,
+                "only verified contacts are accepted, but the chat has unverified members"
+            ),
         }
     }
 }
@@ -199,6 +242,17 @@ pub async fn lookup_by_contact(
             .map(|lookup| lookup.map(|chat| chat.id))
     }
 
+    /// Returns the chat with the given stable identifier, see [`Chat::get_uuid`].
+    pub async fn lookup_by_uuid(context: &Context, uuid: &str) -> Result<Option<Self>> {
+        if uuid.is_empty() {
+            return Ok(None);
+        }
+        context
+            .sql
+            .query_get_value("SELECT id FROM chats WHERE uuid=?;", (uuid,))
+            .await
+    }
+
     /// Returns the [`ChatId`] for the 1:1 chat with `contact_id`.
     ///
     /// If the chat does not yet exist an unblocked chat ([`Blocked::Not`]) is created.
@@ -537,6 +591,82 @@ pub async fn set_visibility(self, context: &Context, visibility: ChatVisibility)
         Ok(())
     }
 
+    /// Enables or disables threaded display of the chat.
+    ///
+    /// The setting is local and not synchronized with other chat members.
+    pub async fn set_threaded(self, context: &Context, threaded: bool) -> Result<()> {
+        ensure!(
+            !self.is_special(),
+            "bad chat_id, can not be special chat: {}",
+            self
+        );
+        let mut chat = Chat::load_from_db(context, self).await?;
+        if threaded {
+            chat.param.set_int(Param::Threaded, 1);
+        } else {
+            chat.param.remove(Param::Threaded);
+        }
+        chat.update_param(context).await?;
+        context.emit_event(EventType::ChatModified(self));
+        Ok(())
+    }
+
+    /// Sets the signature sent as footer of messages in this chat,
+    /// overriding the `selfstatus` config.
+    ///
+    /// `None` uses `selfstatus` again, an empty string disables the footer for this chat.
+    /// The setting is synchronized to other devices.
+    pub async fn set_signature(self, context: &Context, signature: Option<&str>) -> Result<()> {
+        self.set_signature_ex(context, signature, true).await
+    }
+
+    pub(crate) async fn set_signature_ex(
+        self,
+        context: &Context,
+        signature: Option<&str>,
+        sync: bool,
+    ) -> Result<()> {
+        ensure!(
+            !self.is_special(),
+            "bad chat_id, can not be special chat: {}",
+            self
+        );
+        let mut chat = Chat::load_from_db(context, self).await?;
+        match signature {
+            Some(signature) => chat.param.set(Param::Signature, signature),
+            None => chat.param.remove(Param::Signature),
+        };
+        chat.update_param(context).await?;
+        context.emit_event(EventType::ChatModified(self));
+
+        if sync {
+            context.sync_chat_signature(&chat).await?;
+        }
+        Ok(())
+    }
+
+    /// Sets whether new messages unarchive the chat.
+    pub async fn set_archive_mode(
+        self,
+        context: &Context,
+        archive_mode: ArchiveMode,
+    ) -> Result<()> {
+        ensure!(
+            !self.is_special(),
+            "bad chat_id, can not be special chat: {}",
+            self
+        );
+        context
+            .sql
+            .execute(
+                "UPDATE chats SET archive_mode=? WHERE id=?",
+                (archive_mode, self),
+            )
+            .await?;
+        context.emit_event(EventType::ChatModified(self));
+        Ok(())
+    }
+
     /// Unarchives a chat that is archived and not muted.
     /// Needed after a message is added to a chat so that the chat gets a normal visibility again.
     /// `msg_state` is the state of the message. Matters only for incoming messages currently. For
@@ -548,14 +678,33 @@ pub async fn unarchive_if_not_muted(
         self,
         context: &Context,
         msg_state: MessageState,
+    ) -> Result<()> {
+        self.unarchive_if_not_muted_ex(context, msg_state, false)
+            .await
+    }
+
+    /// Same as [`ChatId::unarchive_if_not_muted`],
+    /// `mentioned` is whether the message mentions the user, see [`ArchiveMode`].
+    pub(crate) async fn unarchive_if_not_muted_ex(
+        self,
+        context: &Context,
+        msg_state: MessageState,
+        mentioned: bool,
     ) -> Result<()> {
         if msg_state != MessageState::InFresh {
             context
                 .sql
                 .execute(
                     "UPDATE chats SET archived=0 WHERE id=? AND archived=1 \
-                AND NOT(muted_until=-1 OR muted_until>?)",
-                    (self, time()),
+                AND NOT(muted_until=-1 OR muted_until>?) \
+                AND (archive_mode=? OR (archive_mode=? AND ?))",
+                    (
+                        self,
+                        context.clock.unix_time(),
+                        ArchiveMode::AlwaysUnarchive,
+                        ArchiveMode::UnarchiveOnMention,
+                        mentioned,
+                    ),
                 )
                 .await?;
             return Ok(());
@@ -564,7 +713,12 @@ pub async fn unarchive_if_not_muted(
         if chat.visibility != ChatVisibility::Archived {
             return Ok(());
         }
-        if chat.is_muted() {
+        let stay_archived = match chat.archive_mode {
+            ArchiveMode::AlwaysUnarchive => false,
+            ArchiveMode::StayArchived => true,
+            ArchiveMode::UnarchiveOnMention => !mentioned,
+        };
+        if stay_archived || chat.is_muted_at(context.clock.now()) {
             let unread_cnt = context
                 .sql
                 .count(
@@ -591,9 +745,9 @@ pub async fn unarchive_if_not_muted(
 
     /// Emits an appropriate event for a message. `important` is whether a notification should be
     /// shown.
-    pub(crate) fn emit_msg_event(self, context: &Context, msg_id: MsgId, important: bool) {
+    pub(crate) async fn emit_msg_event(self, context: &Context, msg_id: MsgId, important: bool) {
         if important {
-            context.emit_incoming_msg(self, msg_id);
+            notification::notify_incoming_msg(context, self, msg_id).await;
         } else {
             context.emit_msgs_changed(self, msg_id);
         }
@@ -627,6 +781,21 @@ pub async fn delete(self, context: &Context) -> Result<()> {
             .execute("DELETE FROM chats_contacts WHERE chat_id=?;", (self,))
             .await?;
 
+        context
+            .sql
+            .execute("DELETE FROM chat_bans WHERE chat_id=?;", (self,))
+            .await?;
+
+        context
+            .sql
+            .execute("DELETE FROM ephemeral_timer_acks WHERE chat_id=?;", (self,))
+            .await?;
+
+        context
+            .sql
+            .execute("DELETE FROM verified_key_gossips WHERE chat_id=?;", (self,))
+            .await?;
+
         context
             .sql
             .execute("DELETE FROM chats WHERE id=?;", (self,))
@@ -871,6 +1040,34 @@ pub async fn get_fresh_msg_cnt(self, context: &Context) -> Result<usize> {
         Ok(count)
     }
 
+    /// Stores the corrected order of messages delivered out of order,
+    /// so they are returned in corrected order also without [`MessageListOptions::corrected_order`]
+    /// and the chatlist is sorted accordingly.
+    ///
+    /// Returns the number of messages moved.
+    /// UIs may call this e.g. when a chat is closed, so messages do not move while the user reads.
+    pub async fn repair_msgs_order(self, context: &Context) -> Result<usize> {
+        let moved = context
+            .sql
+            .execute(
+                &format!(
+                    "UPDATE msgs AS m SET timestamp={corrected}
+                     WHERE m.chat_id=? AND {corrected}<m.timestamp",
+                    corrected = corrected_sort_timestamp()
+                ),
+                (self,),
+            )
+            .await?;
+        if moved > 0 {
+            info!(
+                context,
+                "Moved {moved} messages of {self} to their corrected position."
+            );
+            context.emit_msgs_changed(self, MsgId::new(0));
+        }
+        Ok(moved)
+    }
+
     pub(crate) async fn get_param(self, context: &Context) -> Result<Params> {
         let res: Option<String> = context
             .sql
@@ -1030,6 +1227,112 @@ pub async fn get_encryption_info(self, context: &Context) -> Result<String> {
         Ok(ret.trim().to_string())
     }
 
+    /// Sets the number of seconds after which messages of the chat are moved
+    /// from the inbox and the "DeltaChat" folder to the archive folder on the server.
+    ///
+    /// Unlike `delete_server_after`, messages are kept on the server, so other clients
+    /// still can access them. 0 disables archiving for the chat.
+    /// Nothing is moved if the server has no archive folder.
+    pub async fn set_server_archive_after(self, context: &Context, seconds: i64) -> Result<()> {
+        ensure!(!self.is_special(), "Invalid chat ID");
+        ensure!(seconds >= 0, "Invalid server archive timer {seconds}");
+        context
+            .sql
+            .execute(
+                "UPDATE chats SET server_archive_after=? WHERE id=?",
+                (seconds, self),
+            )
+            .await?;
+        context.emit_event(EventType::ChatModified(self));
+        Ok(())
+    }
+
+    /// Returns the number of seconds after which messages of the chat are moved to
+    /// the archive folder on the server, 0 if archiving is disabled.
+    pub async fn get_server_archive_after(self, context: &Context) -> Result<i64> {
+        let seconds = context
+            .sql
+            .query_get_value("SELECT server_archive_after FROM chats WHERE id=?", (self,))
+            .await?;
+        Ok(seconds.unwrap_or_default())
+    }
+
+    /// Sets the message retention policy of the chat.
+    ///
+    /// In addition to ephemeral timers and the account-wide `delete_device_after`
+    /// and `delete_server_after` settings, messages exceeding the policy are deleted
+    /// from the device during housekeeping and from the server by the IMAP loop.
+    pub async fn set_retention(self, context: &Context, retention: Retention) -> Result<()> {
+        ensure!(!self.is_special(), "Invalid chat ID");
+        context
+            .sql
+            .execute(
+                "UPDATE chats SET retention_keep_last=?, retention_server_days=? WHERE id=?",
+                (retention.keep_last, retention.server_days, self),
+            )
+            .await?;
+        context.emit_event(EventType::ChatModified(self));
+        Ok(())
+    }
+
+    /// Returns the message retention policy of the chat.
+    pub async fn get_retention(self, context: &Context) -> Result<Retention> {
+        let retention = context
+            .sql
+            .query_row_optional(
+                "SELECT retention_keep_last, retention_server_days FROM chats WHERE id=?",
+                (self,),
+                |row| {
+                    Ok(Retention {
+                        keep_last: row.get(0)?,
+                        server_days: row.get(1)?,
+                    })
+                },
+            )
+            .await?;
+        Ok(retention.unwrap_or_default())
+    }
+
+    /// Returns the number of messages deleted from the device and from the server
+    /// because of the retention policy of the chat.
+    async fn get_retention_deleted(self, context: &Context) -> Result<(u64, u64)> {
+        let deleted = context
+            .sql
+            .query_row_optional(
+                "SELECT retention_deleted_local, retention_deleted_server FROM chats WHERE id=?",
+                (self,),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .await?;
+        Ok(deleted.unwrap_or_default())
+    }
+
+    /// Requests to fetch up to `count` older messages of the chat from the server.
+    ///
+    /// After configuration only the newest messages are fetched, older ones are fetched
+    /// later in the background. This allows the UI to load history of a chat on demand,
+    /// e.g. when the user scrolls to the top of the chat.
+    ///
+    /// The messages are fetched by the IMAP loop, `IncomingMsgBunch` and `MsgsChanged`
+    /// events are emitted as they arrive.
+    pub async fn load_older_from_server(self, context: &Context, count: usize) -> Result<()> {
+        ensure!(!self.is_special(), "can not load messages of special chats");
+        if count == 0 {
+            return Ok(());
+        }
+        *context
+            .older_msgs_requests
+            .lock()
+            .await
+            .entry(self)
+            .or_default() += count;
+        context
+            .scheduler
+            .interrupt_inbox(InterruptInfo::new(false))
+            .await;
+        Ok(())
+    }
+
     /// Bad evil escape hatch.
     ///
     /// Avoid using this, eventually types should be cleaned up enough
@@ -1154,6 +1457,12 @@ pub struct Chat {
 
     /// If the chat is protected (verified).
     protected: ProtectionStatus,
+
+    /// Whether new messages unarchive the chat.
+    archive_mode: ArchiveMode,
+
+    /// Stable identifier of the chat.
+    uuid: String,
 }
 
 impl Chat {
@@ -1163,7 +1472,8 @@ pub async fn load_from_db(context: &Context, chat_id: ChatId) -> Result<Self> {
             .sql
             .query_row(
                 "SELECT c.type, c.name, c.grpid, c.param, c.archived,
-                    c.blocked, c.locations_send_until, c.muted_until, c.protected
+                    c.blocked, c.locations_send_until, c.muted_until, c.protected,
+                    c.archive_mode, c.uuid
              FROM chats c
              WHERE c.id=?;",
                 (chat_id,),
@@ -1179,6 +1489,8 @@ pub async fn load_from_db(context: &Context, chat_id: ChatId) -> Result<Self> {
                         is_sending_locations: row.get(6)?,
                         mute_duration: row.get(7)?,
                         protected: row.get(8)?,
+                        archive_mode: row.get(9)?,
+                        uuid: row.get(10)?,
                     };
                     Ok(c)
                 },
@@ -1230,6 +1542,18 @@ pub fn is_device_talk(&self) -> bool {
         self.param.exists(Param::Devicetalk)
     }
 
+    /// Returns true if messages of the chat should be displayed grouped by threads,
+    /// see [`ChatId::set_threaded`] and [`MsgId::get_thread`].
+    pub fn is_threaded(&self) -> bool {
+        self.param.get_bool(Param::Threaded).unwrap_or_default()
+    }
+
+    /// Returns the signature set using [`ChatId::set_signature`],
+    /// `None` if the `selfstatus` config is used.
+    pub fn get_signature(&self) -> Option<&str> {
+        self.param.get(Param::Signature)
+    }
+
     /// Returns true if chat is a mailing list.
     pub fn is_mailing_list(&self) -> bool {
         self.typ == Chattype::Mailinglist
@@ -1251,12 +1575,36 @@ pub(crate) async fn why_cant_send(&self, context: &Context) -> Result<Option<Can
             Some(ReadOnlyMailingList)
         } else if !self.is_self_in_chat(context).await? {
             Some(NotAMember)
+        } else if context.get_config_bool(Config::OnlyAcceptVerified).await?
+            && !self.is_protected()
+            && !self.are_all_members_verified(context).await?
+        {
+            Some(NotVerified)
         } else {
             None
         };
         Ok(reason)
     }
 
+    /// Returns true if all members of the chat are bidirectionally verified.
+    ///
+    /// Mailing lists and broadcast lists are never considered verified
+    /// as messages to them are not end-to-end encrypted.
+    async fn are_all_members_verified(&self, context: &Context) -> Result<bool> {
+        match self.typ {
+            Chattype::Single | Chattype::Group => {
+                for contact_id in get_chat_contacts(context, self.id).await? {
+                    let contact = Contact::get_by_id(context, contact_id).await?;
+                    if contact.is_verified(context).await? != VerifiedStatus::BidirectVerified {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Chattype::Broadcast | Chattype::Mailinglist | Chattype::Undefined => Ok(false),
+        }
+    }
+
     /// Returns true if can send to the chat.
     ///
     /// This function can be used by the UI to decide whether to display the input box.
@@ -1361,6 +1709,8 @@ pub async fn get_info(&self, context: &Context) -> Result<ChatInfo> {
             Some(message) => message.text.unwrap_or_default(),
             _ => String::new(),
         };
+        let (retention_deleted_local, retention_deleted_server) =
+            self.id.get_retention_deleted(context).await?;
         Ok(ChatInfo {
             id: self.id,
             type_: self.typ as u32,
@@ -1378,6 +1728,9 @@ pub async fn get_info(&self, context: &Context) -> Result<ChatInfo> {
             draft,
             is_muted: self.is_muted(),
             ephemeral_timer: self.id.get_ephemeral_timer(context).await?,
+            retention: self.id.get_retention(context).await?,
+            retention_deleted_local,
+            retention_deleted_server,
         })
     }
 
@@ -1386,6 +1739,19 @@ pub fn get_visibility(&self) -> ChatVisibility {
         self.visibility
     }
 
+    /// Returns whether new messages unarchive the chat, see [`ChatId::set_archive_mode`].
+    pub fn get_archive_mode(&self) -> ArchiveMode {
+        self.archive_mode
+    }
+
+    /// Returns the stable identifier of the chat.
+    ///
+    /// Unlike the chat ID, the identifier is preserved when the account is exported
+    /// and imported from a backup, so it can be used to refer to the chat from external databases.
+    pub fn get_uuid(&self) -> &str {
+        &self.uuid
+    }
+
     /// Returns true if chat is a contact request.
     ///
     /// Messages cannot be sent to such chat and read receipts are not
@@ -1409,6 +1775,12 @@ pub fn is_protected(&self) -> bool {
         self.protected == ProtectionStatus::Protected
     }
 
+    /// Returns true if the chat name looks like an impersonation attempt,
+    /// e.g. it mixes Latin and Cyrillic letters or contains invisible characters.
+    pub fn is_name_suspicious(&self) -> bool {
+        is_suspicious_name(&self.name)
+    }
+
     /// Returns true if location streaming is enabled in the chat.
     pub fn is_sending_locations(&self) -> bool {
         self.is_sending_locations
@@ -1416,10 +1788,15 @@ pub fn is_sending_locations(&self) -> bool {
 
     /// Returns true if the chat is currently muted.
     pub fn is_muted(&self) -> bool {
+        self.is_muted_at(SystemTime::now())
+    }
+
+    /// Returns true if the chat is muted at the given time.
+    pub(crate) fn is_muted_at(&self, now: SystemTime) -> bool {
         match self.mute_duration {
             MuteDuration::NotMuted => false,
             MuteDuration::Forever => true,
-            MuteDuration::Until(when) => when > SystemTime::now(),
+            MuteDuration::Until(when) => when > now,
         }
     }
 
@@ -1670,6 +2047,9 @@ async fn prepare_msg_raw(
 
             maybe_set_logging_xdc(context, msg, self.id).await?;
         }
+        if msg.param.get_cmd() != SystemMessage::Unknown {
+            msg.id.update_info_group(context).await?;
+        }
         context.scheduler.interrupt_ephemeral_task().await;
         Ok(msg.id)
     }
@@ -1715,6 +2095,18 @@ fn column_result(value: rusqlite::types::ValueRef) -> rusqlite::types::FromSqlRe
     }
 }
 
+/// Message retention policy of a chat.
+///
+/// 0 disables the corresponding limit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Retention {
+    /// Number of newest messages kept on the device, older ones are deleted.
+    pub keep_last: u32,
+
+    /// Number of days after which messages are deleted from the server.
+    pub server_days: u32,
+}
+
 /// The current state of a chat.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[non_exhaustive]
@@ -1775,6 +2167,15 @@ pub struct ChatInfo {
 
     /// Ephemeral message timer.
     pub ephemeral_timer: EphemeralTimer,
+
+    /// Message retention policy.
+    pub retention: Retention,
+
+    /// Number of messages deleted from the device because of the retention policy.
+    pub retention_deleted_local: u64,
+
+    /// Number of messages deleted from the server because of the retention policy.
+    pub retention_deleted_server: u64,
     // ToDo:
     // - [ ] summary,
     // - [ ] lastUpdated,
@@ -2021,6 +2422,39 @@ pub async fn prepare_msg(context: &Context, chat_id: ChatId, msg: &mut Message)
     Ok(msg_id)
 }
 
+/// Re-encodes a voice message if [`Config::RecodeVoice`] is enabled
+/// and returns the blob to send.
+///
+/// Voice messages which cannot be re-encoded are sent as recorded.
+#[cfg(feature = "recode-voice")]
+async fn recode_voice<'a>(
+    context: &'a Context,
+    msg: &mut Message,
+    blob: BlobObject<'a>,
+) -> Result<BlobObject<'a>> {
+    if msg.viewtype != Viewtype::Voice
+        || msg.is_increation()
+        || !context.get_config_bool(Config::RecodeVoice).await?
+    {
+        return Ok(blob);
+    }
+    match blob.recode_voice(context).await {
+        Ok(Some((recoded, duration_ms))) => {
+            msg.param.set(Param::MimeType, "audio/ogg");
+            msg.param.set_i64(Param::Duration, duration_ms as i64);
+            Ok(recoded)
+        }
+        Ok(None) => Ok(blob),
+        Err(err) => {
+            warn!(
+                context,
+                "Cannot recode voice message, sending it as recorded: {err:#}."
+            );
+            Ok(blob)
+        }
+    }
+}
+
 async fn prepare_msg_blob(context: &Context, msg: &mut Message) -> Result<()> {
     if msg.viewtype == Viewtype::Text || msg.viewtype == Viewtype::VideochatInvitation {
         // the caller should check if the message text is empty
@@ -2032,6 +2466,16 @@ async fn prepare_msg_blob(context: &Context, msg: &mut Message) -> Result<()> {
             .with_context(|| format!("attachment missing for message of type #{}", msg.viewtype))?;
 
         if msg.viewtype == Viewtype::Image {
+            if context.get_config_bool(Config::StripExif).await? {
+                // Do not send the image if the metadata may still be there.
+                let removed = blob
+                    .strip_metadata(context)
+                    .await
+                    .context("Cannot strip image metadata")?;
+                if !removed.is_empty() {
+                    msg.param.set(Param::RemovedMetadata, removed.join(" "));
+                }
+            }
             if let Err(err) = blob.recode_to_image_size(context).await {
                 warn!(
                     context,
@@ -2039,6 +2483,8 @@ async fn prepare_msg_blob(context: &Context, msg: &mut Message) -> Result<()> {
                 );
             }
         }
+        #[cfg(feature = "recode-voice")]
+        let blob = recode_voice(context, msg, blob).await?;
         msg.param.set(Param::File, blob.as_name());
 
         if msg.viewtype == Viewtype::File || msg.viewtype == Viewtype::Image {
@@ -2081,42 +2527,224 @@ async fn prepare_msg_blob(context: &Context, msg: &mut Message) -> Result<()> {
             blob.to_abs_path().display(),
             msg.viewtype
         );
+
+        if let Some(attachments) = msg.param.get(Param::Attachments) {
+            let mut blob_names = Vec::new();
+            for file in attachments.lines() {
+                let blob = match ParamsFile::from_param(context, file)? {
+                    ParamsFile::FsPath(path) => BlobObject::new_from_path(context, &path).await?,
+                    ParamsFile::Blob(blob) => blob,
+                };
+                blob_names.push(blob.as_name().to_string());
+            }
+            info!(
+                context,
+                "Attaching {} more files to the message.",
+                blob_names.len()
+            );
+            msg.param.set(Param::Attachments, blob_names.join("\n"));
+        }
     } else {
         bail!("Cannot send messages of type #{}.", msg.viewtype);
     }
+    ensure!(
+        msg.viewtype.has_file() || !msg.param.exists(Param::Attachments),
+        "Cannot attach files to messages of type #{}.",
+        msg.viewtype
+    );
+    if msg.is_view_once() {
+        ensure!(
+            matches!(
+                msg.viewtype,
+                Viewtype::Image | Viewtype::Gif | Viewtype::Voice | Viewtype::Video
+            ),
+            "Cannot send messages of type #{} as view-once.",
+            msg.viewtype
+        );
+    }
     Ok(())
 }
 
-/// Prepares a message to be sent out.
-async fn prepare_msg_common(
-    context: &Context,
-    chat_id: ChatId,
-    msg: &mut Message,
-    change_state_to: MessageState,
-) -> Result<MsgId> {
-    let mut chat = Chat::load_from_db(context, chat_id).await?;
+/// Error returned when a message exceeds the maximum message size of the provider.
+///
+/// Sending such a message fails early instead of being rejected by the SMTP server later,
+/// use [`anyhow::Error::downcast_ref`] to get the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Message of {size} bytes exceeds the size limit of {limit} bytes")]
+pub struct MessageTooLarge {
+    /// Estimated size of the message in bytes.
+    pub size: u64,
+
+    /// Maximum message size in bytes.
+    pub limit: u64,
+}
 
-    // Check if the chat can be sent to.
-    if let Some(reason) = chat.why_cant_send(context).await? {
-        bail!("cannot send to {}: {}", chat_id, reason);
-    }
+/// Rough size of the headers, MIME structure and OpenPGP packets of an email.
+const MSG_OVERHEAD: u64 = 8 * 1024;
 
-    // check current MessageState for drafts (to keep msg_id) ...
-    let update_msg_id = if msg.state == MessageState::OutDraft {
-        msg.hidden = false;
-        if !msg.id.is_special() && msg.chat_id == chat_id {
-            Some(msg.id)
-        } else {
-            None
-        }
-    } else {
-        None
+/// Estimates the size of an email with `files_size` bytes of attachments and `text_size` bytes of text.
+///
+/// Attachments are base64-encoded with a line break every 76 characters.
+/// Encrypted messages are ASCII-armored as a whole,
+/// which is base64 with a line break every 64 characters again.
+fn estimate_msg_size(files_size: u64, text_size: u64, encrypted: bool) -> u64 {
+    let base64 = |size: u64, line_len: u64| {
+        let encoded = (size + 2) / 3 * 4;
+        encoded + encoded / line_len * 2
     };
+    let size = base64(files_size, 76) + text_size + MSG_OVERHEAD;
+    if encrypted {
+        base64(size, 64) + MSG_OVERHEAD
+    } else {
+        size
+    }
+}
 
-    // ... then change the MessageState in the message object
+/// Returns the total size of the files of the message in bytes.
+async fn get_files_size(context: &Context, msg: &Message) -> Result<u64> {
+    let mut size = 0;
+    for file in msg.get_attachments(context) {
+        size += tokio::fs::metadata(&file)
+            .await
+            .with_context(|| format!("Cannot get size of {}", file.display()))?
+            .len();
+    }
+    Ok(size)
+}
+
+/// Checks that the attachments of a message to be sent fit into the maximum message size,
+/// scaling the image down if [`Config::OversizedAttachment`] allows it.
+///
+/// Whether a message is encrypted is only decided when it is sent,
+/// so the size of the encrypted message is checked unless the message is forced to be unencrypted.
+async fn check_msg_size(context: &Context, msg: &Message) -> Result<()> {
+    let limit = match context.get_max_msg_size().await? {
+        Some(limit) => limit,
+        None => return Ok(()),
+    };
+    if !msg.viewtype.has_file() || msg.is_increation() {
+        return Ok(());
+    }
+    let blob = match msg.param.get_blob(Param::File, context, false).await? {
+        Some(blob) => blob,
+        None => return Ok(()),
+    };
+
+    let text_size = msg.text.as_deref().unwrap_or_default().len() as u64;
+    let encrypted = !msg
+        .param
+        .get_bool(Param::ForcePlaintext)
+        .unwrap_or_default();
+    let estimate_size = |files_size| estimate_msg_size(files_size, text_size, encrypted);
+    let files_size = get_files_size(context, msg).await?;
+    let mut size = estimate_size(files_size);
+    if size > limit
+        && msg.viewtype == Viewtype::Image
+        && OversizedAttachment::from_i32(context.get_config_int(Config::OversizedAttachment).await?)
+            .unwrap_or_default()
+            == OversizedAttachment::Downscale
+    {
+        let image_size = tokio::fs::metadata(blob.to_abs_path()).await?.len();
+        let other_size = files_size.saturating_sub(image_size);
+        // The estimated size grows about linearly with the size of the image.
+        let fixed_size = estimate_size(other_size);
+        let bytes_per_image_byte =
+            size.saturating_sub(fixed_size) as f64 / image_size.max(1) as f64;
+        let max_bytes = (limit.saturating_sub(fixed_size) as f64 / bytes_per_image_byte) as u64;
+        match blob
+            .recode_to_max_bytes(context, usize::try_from(max_bytes).unwrap_or(usize::MAX))
+            .await
+        {
+            Ok(()) => {
+                size = estimate_size(get_files_size(context, msg).await?);
+            }
+            Err(err) => warn!(context, "Cannot scale down oversized image: {err:#}."),
+        }
+    }
+    if size > limit {
+        return Err(MessageTooLarge { size, limit }.into());
+    }
+    Ok(())
+}
+
+/// Name of the file a too long text is sent as.
+const LONG_TEXT_FILE_NAME: &str = "message.txt";
+
+/// Size in bytes above which texts are sent as a file, see [`convert_long_text_to_file`].
+///
+/// Shorter texts are sent as they are, recipients see a truncated text then
+/// and can view the full text as HTML.
+pub(crate) const LONG_TEXT_FILE_THRESHOLD: usize = 200_000;
+
+/// Converts a huge text message to a `.txt` attachment.
+///
+/// Texts of more than [`LONG_TEXT_FILE_THRESHOLD`] bytes are usually pasted logs or documents
+/// that are better handled as file.
+/// The text of the message is replaced by the preview the recipients would see otherwise,
+/// so the full text is not sent twice.
+async fn convert_long_text_to_file(context: &Context, msg: &mut Message) -> Result<()> {
+    if msg.viewtype != Viewtype::Text
+        || msg.param.get_cmd() != SystemMessage::Unknown
+        || msg.param.exists(Param::SendHtml)
+        || msg.text.as_ref().map_or(0, |text| text.len()) <= LONG_TEXT_FILE_THRESHOLD
+    {
+        return Ok(());
+    }
+    let text = msg.text.clone().unwrap_or_default();
+    let (preview, was_truncated) = truncate_by_lines(
+        text.clone(),
+        DC_DESIRED_TEXT_LINES,
+        DC_DESIRED_TEXT_LINE_LEN,
+    );
+    if !was_truncated {
+        return Ok(());
+    }
+    let blob = BlobObject::create(context, LONG_TEXT_FILE_NAME, text.as_bytes()).await?;
+    info!(
+        context,
+        "Sending text of {} bytes as {}.",
+        text.len(),
+        blob.as_name()
+    );
+    msg.viewtype = Viewtype::File;
+    msg.param.set(Param::File, blob.as_name());
+    msg.param.set(Param::MimeType, "text/plain");
+    msg.text = Some(preview);
+    Ok(())
+}
+
+/// Prepares a message to be sent out.
+async fn prepare_msg_common(
+    context: &Context,
+    chat_id: ChatId,
+    msg: &mut Message,
+    change_state_to: MessageState,
+) -> Result<MsgId> {
+    let mut chat = Chat::load_from_db(context, chat_id).await?;
+
+    // Check if the chat can be sent to.
+    if let Some(reason) = chat.why_cant_send(context).await? {
+        bail!("cannot send to {}: {}", chat_id, reason);
+    }
+
+    // check current MessageState for drafts (to keep msg_id) ...
+    let update_msg_id = if msg.state == MessageState::OutDraft {
+        msg.hidden = false;
+        if !msg.id.is_special() && msg.chat_id == chat_id {
+            Some(msg.id)
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    // ... then change the MessageState in the message object
     msg.state = change_state_to;
 
+    convert_long_text_to_file(context, msg).await?;
     prepare_msg_blob(context, msg).await?;
+    check_msg_size(context, msg).await?;
     if !msg.hidden {
         chat_id.unarchive_if_not_muted(context, msg.state).await?;
     }
@@ -2199,6 +2827,46 @@ pub async fn send_msg_sync(context: &Context, chat_id: ChatId, msg: &mut Message
     Ok(msg.id)
 }
 
+/// Sends a message object to a chat without waiting for the message to be rendered.
+///
+/// The message is added to the chat and its ID is returned immediately,
+/// rendering the MIME message and encrypting it is deferred to the SMTP loop.
+/// If rendering fails later, #DC_EVENT_MSG_FAILED is emitted.
+///
+/// Sends the event #DC_EVENT_MSGS_CHANGED on success.
+pub async fn send_msg_deferred(
+    context: &Context,
+    chat_id: ChatId,
+    msg: &mut Message,
+) -> Result<MsgId> {
+    ensure!(
+        !msg.param.exists(Param::PrepForwards),
+        "Forwarded messages cannot be sent deferred"
+    );
+    if msg.is_system_message() {
+        if let Some(text) = &msg.text {
+            msg.text = Some(strip_rtlo_characters(text.as_ref()));
+        }
+    }
+
+    set_msg_pending(context, chat_id, msg).await?;
+    context
+        .sql
+        .execute("INSERT INTO smtp_deferred (msg_id) VALUES (?)", (msg.id,))
+        .await?;
+    context.emit_msgs_changed(msg.chat_id, msg.id);
+
+    if msg.param.exists(Param::SetLatitude) {
+        context.emit_event(EventType::LocationChanged(Some(ContactId::SELF)));
+    }
+
+    context
+        .scheduler
+        .interrupt_smtp(InterruptInfo::new(false))
+        .await;
+    Ok(msg.id)
+}
+
 async fn send_msg_inner(context: &Context, chat_id: ChatId, msg: &mut Message) -> Result<MsgId> {
     // protect all system messages againts RTLO attacks
     if msg.is_system_message() {
@@ -2229,6 +2897,13 @@ async fn prepare_send_msg(
     chat_id: ChatId,
     msg: &mut Message,
 ) -> Result<Option<i64>> {
+    set_msg_pending(context, chat_id, msg).await?;
+    let row_id = create_send_msg_job(context, msg.id).await?;
+    Ok(row_id)
+}
+
+/// Adds the message to the chat if needed and sets its state to `OutPending`.
+async fn set_msg_pending(context: &Context, chat_id: ChatId, msg: &mut Message) -> Result<()> {
     // prepare_msg() leaves the message state to OutPreparing, we
     // only have to change the state to OutPending in this case.
     // Otherwise we still have to prepare the message, which will set
@@ -2244,8 +2919,7 @@ async fn prepare_send_msg(
         );
         message::update_msg_state(context, msg.id, MessageState::OutPending).await?;
     }
-    let row_id = create_send_msg_job(context, msg.id).await?;
-    Ok(row_id)
+    Ok(())
 }
 
 /// Constructs a job for sending a message and inserts into `smtp` table.
@@ -2254,7 +2928,7 @@ async fn prepare_send_msg(
 /// group with only self and no BCC-to-self configured.
 ///
 /// The caller has to interrupt SMTP loop or otherwise process a new row.
-async fn create_send_msg_job(context: &Context, msg_id: MsgId) -> Result<Option<i64>> {
+pub(crate) async fn create_send_msg_job(context: &Context, msg_id: MsgId) -> Result<Option<i64>> {
     let mut msg = Message::load_from_db(context, msg_id).await?;
     msg.try_calc_and_set_dimensions(context)
         .await
@@ -2307,6 +2981,16 @@ async fn create_send_msg_job(context: &Context, msg_id: MsgId) -> Result<Option<
         }
     }?;
 
+    if !rendered_msg.is_encrypted && context.get_config_bool(Config::OnlyAcceptVerified).await? {
+        message::set_msg_failed(
+            context,
+            msg_id,
+            "Unencrypted messages are not sent as only verified contacts are accepted.",
+        )
+        .await;
+        bail!("refusing to send unencrypted message {msg_id}, only verified contacts are accepted");
+    }
+
     if needs_encryption && !rendered_msg.is_encrypted {
         /* unrecoverable */
         message::set_msg_failed(
@@ -2364,6 +3048,15 @@ async fn create_send_msg_job(context: &Context, msg_id: MsgId) -> Result<Option<
     msg.subject = rendered_msg.subject.clone();
     msg.update_subject(context).await?;
 
+    // Record the size of the sent message like the size of received messages.
+    context
+        .sql
+        .execute(
+            "UPDATE msgs SET bytes=? WHERE id=?",
+            (rendered_msg.message.len(), msg_id),
+        )
+        .await?;
+
     let row_id = context
         .sql
         .insert(
@@ -2399,25 +3092,57 @@ pub async fn send_text_msg(
     send_msg(context, chat_id, &mut msg).await
 }
 
-/// Sends invitation to a videochat.
+/// Returns the webrtc instances to choose from for videochats.
+///
+/// These are [`Config::WebrtcInstance`] followed by the lines of [`Config::WebrtcInstances`],
+/// empty lines and duplicates are skipped.
+pub async fn get_videochat_instances(context: &Context) -> Result<Vec<String>> {
+    let first = context
+        .get_config(Config::WebrtcInstance)
+        .await?
+        .unwrap_or_default();
+    let more = context
+        .get_config(Config::WebrtcInstances)
+        .await?
+        .unwrap_or_default();
+    let mut instances: Vec<String> = Vec::new();
+    for instance in std::iter::once(first.as_str()).chain(more.lines()) {
+        let instance = instance.trim();
+        if !instance.is_empty() && !instances.iter().any(|i| i == instance) {
+            instances.push(instance.to_string());
+        }
+    }
+    Ok(instances)
+}
+
+/// Sends invitation to a videochat
+/// on the first instance returned by [`get_videochat_instances`].
 pub async fn send_videochat_invitation(context: &Context, chat_id: ChatId) -> Result<MsgId> {
+    let instance = get_videochat_instances(context)
+        .await?
+        .into_iter()
+        .next()
+        .context("webrtc_instance not set")?;
+    send_videochat_invitation_ex(context, chat_id, &instance).await
+}
+
+/// Sends invitation to a videochat on the given webrtc instance.
+///
+/// The instance has the format of [`Config::WebrtcInstance`] and does not need to be configured.
+/// A new room with an unguessable name is created for each invitation.
+pub async fn send_videochat_invitation_ex(
+    context: &Context,
+    chat_id: ChatId,
+    instance: &str,
+) -> Result<MsgId> {
     ensure!(
         !chat_id.is_special(),
         "video chat invitation cannot be sent to special chat: {}",
         chat_id
     );
+    ensure!(!instance.trim().is_empty(), "webrtc instance is empty");
 
-    let instance = if let Some(instance) = context.get_config(Config::WebrtcInstance).await? {
-        if !instance.is_empty() {
-            instance
-        } else {
-            bail!("webrtc_instance is empty");
-        }
-    } else {
-        bail!("webrtc_instance not set");
-    };
-
-    let instance = Message::create_webrtc_instance(&instance, &create_id());
+    let instance = Message::create_webrtc_instance(instance, &create_videochat_room());
 
     let mut msg = Message::new(Viewtype::VideochatInvitation);
     msg.param.set(Param::WebrtcRoom, &instance);
@@ -2428,6 +3153,78 @@ pub async fn send_videochat_invitation(context: &Context, chat_id: ChatId) -> Re
     send_msg(context, chat_id, &mut msg).await
 }
 
+/// Sends media as an album, to be shown as one group by UIs.
+///
+/// Each file is sent as a message of its own, the caption is added to the first one.
+/// All messages get the same grouping ID, see [`Message::get_album_id`] and [`get_album_msgs`].
+pub async fn send_album(
+    context: &Context,
+    chat_id: ChatId,
+    files: &[String],
+    caption: Option<String>,
+) -> Result<Vec<MsgId>> {
+    ensure!(!files.is_empty(), "No media for album");
+    let mut viewtypes = Vec::with_capacity(files.len());
+    for file in files {
+        match message::guess_msgtype_from_suffix(Path::new(file)) {
+            Some((viewtype @ (Viewtype::Image | Viewtype::Gif | Viewtype::Video), _)) => {
+                viewtypes.push(viewtype)
+            }
+            _ => bail!("Cannot send {file} in an album, only images and videos are supported"),
+        }
+    }
+    let album_id = create_id();
+    let mut caption = caption;
+    let mut msg_ids = Vec::with_capacity(files.len());
+    for (file, viewtype) in files.iter().zip(viewtypes) {
+        let mut msg = Message::new(viewtype);
+        msg.set_file(file, None);
+        msg.set_text(caption.take());
+        msg.param.set(Param::Album, &album_id);
+        msg_ids.push(send_msg(context, chat_id, &mut msg).await?);
+    }
+    Ok(msg_ids)
+}
+
+/// Returns the messages of the album `msg_id` belongs to, in the order they were sent.
+///
+/// Returns only `msg_id` if it does not belong to an album.
+pub async fn get_album_msgs(context: &Context, msg_id: MsgId) -> Result<Vec<MsgId>> {
+    let msg = Message::load_from_db(context, msg_id).await?;
+    let album_id = match msg.get_album_id() {
+        Some(album_id) => album_id,
+        None => return Ok(vec![msg_id]),
+    };
+    context
+        .sql
+        .query_map(
+            "SELECT id, param FROM msgs
+             WHERE chat_id=? AND hidden=0 AND param GLOB ?
+             ORDER BY timestamp_sent, id",
+            (
+                msg.chat_id,
+                format!("*{}={album_id}*", Param::Album as u8 as char),
+            ),
+            |row| {
+                let id: MsgId = row.get(0)?;
+                let param: String = row.get(1)?;
+                Ok((id, param))
+            },
+            |rows| {
+                let mut msg_ids = Vec::new();
+                for row in rows {
+                    let (id, param) = row?;
+                    let param: Params = param.parse().unwrap_or_default();
+                    if param.get(Param::Album) == Some(album_id) {
+                        msg_ids.push(id);
+                    }
+                }
+                Ok(msg_ids)
+            },
+        )
+        .await
+}
+
 /// Chat message list request options.
 #[derive(Debug)]
 pub struct MessageListOptions {
@@ -2436,6 +3233,10 @@ pub struct MessageListOptions {
 
     /// Add day markers before each date regarding the local timezone.
     pub add_daymarker: bool,
+
+    /// Sort messages delivered out of order by the time they were sent
+    /// once they are not fresh anymore, see [`corrected_sort_timestamp`].
+    pub corrected_order: bool,
 }
 
 /// Returns all messages belonging to the chat.
@@ -2446,11 +3247,40 @@ pub async fn get_chat_msgs(context: &Context, chat_id: ChatId) -> Result<Vec<Cha
         MessageListOptions {
             info_only: false,
             add_daymarker: false,
+            corrected_order: false,
         },
     )
     .await
 }
 
+/// Returns the SQL expression of the corrected sort timestamp of the message `m`.
+///
+/// Fresh incoming messages are sorted below the messages already seen,
+/// so messages delivered late or fetched after being offline are not overlooked.
+/// Once noticed or seen, these messages are sorted by the time they were sent
+/// as this is their order in the conversation.
+/// Messages with the same timestamp are sorted by their ID, so the order is deterministic.
+///
+/// The time a message was sent is given by the sender,
+/// so a message is not sorted before the creation of the chat
+/// and not before the message it replies to, which the sender had seen already.
+/// This way, a sender can not move a message to an arbitrary position in the past.
+fn corrected_sort_timestamp() -> String {
+    format!(
+        "CASE WHEN m.state IN ({}, {}) AND m.timestamp_sent>0 AND m.timestamp_sent<m.timestamp
+              THEN MIN(m.timestamp, MAX(
+                  m.timestamp_sent,
+                  IFNULL((SELECT c.created_timestamp FROM chats c WHERE c.id=m.chat_id), 0),
+                  IFNULL((SELECT MAX(p.timestamp_sent) FROM msgs p
+                          WHERE p.chat_id=m.chat_id AND p.id!=m.id
+                          AND p.rfc724_mid=TRIM(m.mime_in_reply_to, '<> ')), 0)))
+              ELSE m.timestamp
+         END",
+        MessageState::InNoticed as u32,
+        MessageState::InSeen as u32,
+    )
+}
+
 /// Returns messages belonging to the chat according to the given options.
 pub async fn get_chat_msgs_ex(
     context: &Context,
@@ -2460,7 +3290,13 @@ pub async fn get_chat_msgs_ex(
     let MessageListOptions {
         info_only,
         add_daymarker,
+        corrected_order,
     } = options;
+    let timestamp = if corrected_order {
+        corrected_sort_timestamp()
+    } else {
+        "m.timestamp".to_string()
+    };
     let process_row = if info_only {
         |row: &rusqlite::Row| {
             // is_info logic taken from Message.is_info()
@@ -2531,7 +3367,7 @@ pub async fn get_chat_msgs_ex(
             .sql
             .query_map(
         // GLOB is used here instead of LIKE because it is case-sensitive
-                "SELECT m.id AS id, m.timestamp AS timestamp, m.param AS param, m.from_id AS from_id, m.to_id AS to_id
+                &format!("SELECT m.id AS id, {timestamp} AS timestamp, m.param AS param, m.from_id AS from_id, m.to_id AS to_id
                FROM msgs m
               WHERE m.chat_id=?
                 AND m.hidden=0
@@ -2539,7 +3375,7 @@ pub async fn get_chat_msgs_ex(
                     m.param GLOB \"*S=*\"
                     OR m.from_id == ?
                     OR m.to_id == ?
-                );",
+                );"),
                 (chat_id, ContactId::INFO, ContactId::INFO),
                 process_row,
                 process_rows,
@@ -2549,10 +3385,12 @@ pub async fn get_chat_msgs_ex(
         context
             .sql
             .query_map(
-                "SELECT m.id AS id, m.timestamp AS timestamp
+                &format!(
+                    "SELECT m.id AS id, {timestamp} AS timestamp
                FROM msgs m
               WHERE m.chat_id=?
-                AND m.hidden=0;",
+                AND m.hidden=0;"
+                ),
                 (chat_id,),
                 process_row,
                 process_rows,
@@ -2646,6 +3484,103 @@ pub async fn marknoticed_chat(context: &Context, chat_id: ChatId) -> Result<()>
     Ok(())
 }
 
+/// Marks all messages in the given chats as noticed.
+///
+/// All chats are updated in a single transaction,
+/// `DC_EVENT_MSGS_NOTICED` is emitted only for chats that had fresh messages.
+pub async fn marknoticed_chats(context: &Context, chat_ids: &[ChatId]) -> Result<()> {
+    ensure!(
+        chat_ids.iter().all(|chat_id| !chat_id.is_special()),
+        "bad chat_ids, can not contain special chats"
+    );
+    if chat_ids.is_empty() {
+        return Ok(());
+    }
+
+    let placeholders = sql::repeat_vars(chat_ids.len());
+    let chat_ids = chat_ids.to_vec();
+    let noticed_chat_ids = context
+        .sql
+        .transaction(move |transaction| {
+            let noticed_chat_ids = transaction
+                .prepare(&format!(
+                    "SELECT DISTINCT(chat_id) FROM msgs
+                      WHERE state=10 AND hidden=0 AND chat_id IN ({placeholders})"
+                ))?
+                .query_map(rusqlite::params_from_iter(&chat_ids), |row| {
+                    row.get::<_, ChatId>(0)
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            if noticed_chat_ids.is_empty() {
+                return Ok(noticed_chat_ids);
+            }
+            transaction.execute(
+                &format!(
+                    "UPDATE msgs SET state=13 WHERE state=10 AND hidden=0 AND chat_id IN ({});",
+                    sql::repeat_vars(noticed_chat_ids.len())
+                ),
+                rusqlite::params_from_iter(&noticed_chat_ids),
+            )?;
+            Ok(noticed_chat_ids)
+        })
+        .await?;
+
+    for chat_id in noticed_chat_ids {
+        context.emit_event(EventType::MsgsNoticed(chat_id));
+    }
+
+    Ok(())
+}
+
+/// Archives or unarchives multiple chats.
+///
+/// This is the same as calling [`ChatId::set_visibility`] for every chat,
+/// but all chats are updated in a single transaction
+/// and `DC_EVENT_MSGS_CHANGED` is emitted only once.
+/// Unarchiving does not change the visibility of chats that are not archived,
+/// so pinned chats stay pinned.
+pub async fn archive_chats(context: &Context, chat_ids: &[ChatId], archive: bool) -> Result<()> {
+    ensure!(
+        chat_ids.iter().all(|chat_id| !chat_id.is_special()),
+        "bad chat_ids, can not contain special chats"
+    );
+    if chat_ids.is_empty() {
+        return Ok(());
+    }
+
+    let placeholders = sql::repeat_vars(chat_ids.len());
+    let chat_ids = chat_ids.to_vec();
+    context
+        .sql
+        .transaction(move |transaction| {
+            if archive {
+                transaction.execute(
+                    &format!(
+                        "UPDATE msgs SET state=13 WHERE state=10 AND chat_id IN ({placeholders});"
+                    ),
+                    rusqlite::params_from_iter(&chat_ids),
+                )?;
+                transaction.execute(
+                    &format!("UPDATE chats SET archived=1 WHERE id IN ({placeholders});"),
+                    rusqlite::params_from_iter(&chat_ids),
+                )?;
+            } else {
+                transaction.execute(
+                    &format!(
+                        "UPDATE chats SET archived=0 WHERE archived=1 AND id IN ({placeholders});"
+                    ),
+                    rusqlite::params_from_iter(&chat_ids),
+                )?;
+            }
+            Ok(())
+        })
+        .await?;
+
+    context.emit_msgs_changed_without_ids();
+
+    Ok(())
+}
+
 /// Marks messages preceding outgoing messages as noticed.
 ///
 /// In a chat, if there is an outgoing message, it can be assumed that all previous
@@ -2761,6 +3696,117 @@ pub async fn get_chat_media(
     Ok(list)
 }
 
+/// Chat media request options, see [`get_chat_media_ex`].
+#[derive(Debug, Default)]
+pub struct MediaListOptions {
+    /// Viewtypes to return, all media viewtypes if empty.
+    pub viewtypes: Vec<Viewtype>,
+
+    /// Return only messages with a timestamp greater than or equal to this one.
+    pub since: Option<i64>,
+
+    /// Return only messages with a timestamp lower than this one.
+    pub until: Option<i64>,
+}
+
+/// Media messages of one month, see [`get_chat_media_ex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaMonth {
+    /// Year regarding the local timezone.
+    pub year: i32,
+
+    /// Month from 1 to 12 regarding the local timezone.
+    pub month: u32,
+
+    /// Media messages of the month, oldest first.
+    pub msg_ids: Vec<MsgId>,
+}
+
+/// Returns the media messages matching `options` grouped by month.
+///
+/// If `chat_id` is None, return messages from any chat.
+///
+/// The months are sorted oldest first and contain only months with messages,
+/// so gallery views can show section headers with counts
+/// without loading the messages.
+pub async fn get_chat_media_ex(
+    context: &Context,
+    chat_id: Option<ChatId>,
+    options: MediaListOptions,
+) -> Result<Vec<MediaMonth>> {
+    let MediaListOptions {
+        viewtypes,
+        since,
+        until,
+    } = options;
+    let viewtypes = if viewtypes.is_empty() {
+        vec![
+            Viewtype::Image,
+            Viewtype::Gif,
+            Viewtype::Video,
+            Viewtype::Audio,
+            Viewtype::Voice,
+            Viewtype::File,
+        ]
+    } else {
+        viewtypes
+    };
+    let viewtypes = viewtypes
+        .iter()
+        .map(|viewtype| (*viewtype as u32).to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let cnv_to_local = gm2local_offset();
+    context
+        .sql
+        .query_map(
+            &format!(
+                "SELECT id, timestamp
+                   FROM msgs
+                  WHERE (1=? OR chat_id=?)
+                    AND chat_id != ?
+                    AND type IN ({viewtypes})
+                    AND timestamp >= ?
+                    AND timestamp < ?
+                    AND hidden=0
+                  ORDER BY timestamp, id;"
+            ),
+            (
+                chat_id.is_none(),
+                chat_id.unwrap_or_else(|| ChatId::new(0)),
+                DC_CHAT_ID_TRASH,
+                since.unwrap_or(i64::MIN),
+                until.unwrap_or(i64::MAX),
+            ),
+            |row| Ok((row.get::<_, MsgId>(0)?, row.get::<_, i64>(1)?)),
+            |rows| {
+                let mut months: Vec<MediaMonth> = Vec::new();
+                for row in rows {
+                    let (msg_id, timestamp) = row?;
+                    let local = NaiveDateTime::from_timestamp_opt(
+                        timestamp.saturating_add(cnv_to_local),
+                        0,
+                    )
+                    .unwrap_or_default();
+                    let (year, month) = (local.year(), local.month());
+                    match months.last_mut() {
+                        Some(last) if last.year == year && last.month == month => {
+                            last.msg_ids.push(msg_id);
+                        }
+                        _ => months.push(MediaMonth {
+                            year,
+                            month,
+                            msg_ids: vec![msg_id],
+                        }),
+                    }
+                }
+                Ok(months)
+            },
+        )
+        .await
+}
+
 /// Indicates the direction over which to iterate.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[repr(i32)]
@@ -2882,6 +3928,113 @@ pub async fn create_group_chat(
     Ok(chat_id)
 }
 
+/// Initial settings of a group created with [`create_group_chat_ex`].
+#[derive(Debug, Default, Clone)]
+pub struct GroupParams {
+    /// Name of the group.
+    pub name: String,
+
+    /// Whether the group is protected.
+    ///
+    /// All members of a protected group must be verified.
+    pub protect: ProtectionStatus,
+
+    /// Members of the group in addition to ourself.
+    pub members: Vec<ContactId>,
+
+    /// Path to the group avatar, if any.
+    pub avatar: Option<PathBuf>,
+
+    /// Ephemeral timer of the group.
+    pub ephemeral_timer: EphemeralTimer,
+}
+
+/// Creates a new group chat with the given members and settings.
+///
+/// Unlike calling [`create_group_chat`], [`add_contact_to_chat`],
+/// [`set_chat_profile_image`] and [`ChatId::set_ephemeral_timer`] one after another,
+/// the group is created in a single transaction,
+/// so either all settings are applied or no group is created at all.
+/// The group is unpromoted and no messages are sent;
+/// the first message sent to the group carries the complete member list and settings.
+pub async fn create_group_chat_ex(context: &Context, params: GroupParams) -> Result<ChatId> {
+    let chat_name = improve_single_line_input(&params.name);
+    ensure!(!chat_name.is_empty(), "Invalid chat name");
+
+    let mut contact_ids = vec![ContactId::SELF];
+    for contact_id in params.members {
+        if contact_id == ContactId::SELF || contact_ids.contains(&contact_id) {
+            continue;
+        }
+        ensure!(
+            Contact::real_exists_by_id(context, contact_id).await?,
+            "invalid contact_id {} for adding to group",
+            contact_id
+        );
+        let contact = Contact::get_by_id(context, contact_id).await?;
+        ensure!(
+            !context.is_self_addr(contact.get_addr()).await?,
+            "Invalid attempt to add self e-mail address to group."
+        );
+        if params.protect == ProtectionStatus::Protected
+            && contact.is_verified(context).await? != VerifiedStatus::BidirectVerified
+        {
+            bail!("{} is not verified.", contact.get_display_name());
+        }
+        contact_ids.push(contact_id);
+    }
+
+    let mut param = Params::new();
+    param.set_int(Param::Unpromoted, 1);
+    if let Some(avatar) = params.avatar {
+        let mut image_blob = BlobObject::new_from_path(context, &avatar).await?;
+        image_blob.recode_to_avatar_size(context).await?;
+        param.set(Param::ProfileImage, image_blob.as_name());
+    }
+
+    let grpid = create_id();
+    let timestamp = create_smeared_timestamp(context);
+    let ephemeral_timer = params.ephemeral_timer;
+    let chat_id = context
+        .sql
+        .transaction(move |transaction| {
+            transaction.execute(
+                "INSERT INTO chats
+                 (type, name, grpid, param, created_timestamp, ephemeral_timer)
+                 VALUES(?, ?, ?, ?, ?, ?);",
+                (
+                    Chattype::Group,
+                    chat_name,
+                    grpid,
+                    param.to_string(),
+                    timestamp,
+                    ephemeral_timer,
+                ),
+            )?;
+            let chat_id = ChatId::new(u32::try_from(transaction.last_insert_rowid())?);
+            for contact_id in &contact_ids {
+                transaction.execute(
+                    "INSERT OR IGNORE INTO chats_contacts (chat_id, contact_id) VALUES(?, ?)",
+                    (chat_id, contact_id),
+                )?;
+            }
+            Ok(chat_id)
+        })
+        .await?;
+
+    context.emit_msgs_changed_without_ids();
+    context.emit_event(EventType::ChatModified(chat_id));
+
+    if params.protect == ProtectionStatus::Protected {
+        // Like in `create_group_chat()`, so the members get all keys gossiped.
+        chat_id
+            .inner_set_protection(context, params.protect)
+            .await?;
+    }
+
+    Ok(chat_id)
+}
+
 /// Finds an unused name for a new broadcast list.
 async fn find_unused_broadcast_list_name(context: &Context) -> Result<String> {
     let base_name = stock_str::broadcast_list(context).await;
@@ -2982,6 +4135,18 @@ pub(crate) async fn add_contact_to_chat_ex(
     chat_id: ChatId,
     contact_id: ContactId,
     from_handshake: bool,
+) -> Result<bool> {
+    add_contact_to_chat_ex_unban(context, chat_id, contact_id, from_handshake, false).await
+}
+
+/// Adds a contact to a group, `unban` marks the member-added message as lifting a ban,
+/// see [`unban_member`].
+async fn add_contact_to_chat_ex_unban(
+    context: &Context,
+    chat_id: ChatId,
+    contact_id: ContactId,
+    from_handshake: bool,
+    unban: bool,
 ) -> Result<bool> {
     ensure!(!chat_id.is_special(), "can not add member to special chats");
     let contact = Contact::get_by_id(context, contact_id).await?;
@@ -3001,6 +4166,10 @@ pub(crate) async fn add_contact_to_chat_ex(
         "invalid contact_id {} for adding to group",
         contact_id
     );
+    ensure!(
+        !is_member_banned(context, chat_id, contact_id).await?,
+        "{contact_id} is banned from {chat_id}"
+    );
     ensure!(!chat.is_mailing_list(), "Mailing lists can't be changed");
     ensure!(
         chat.typ != Chattype::Broadcast || contact_id != ContactId::SELF,
@@ -3059,6 +4228,9 @@ pub(crate) async fn add_contact_to_chat_ex(
         msg.param.set_cmd(SystemMessage::MemberAddedToGroup);
         msg.param.set(Param::Arg, contact.get_addr());
         msg.param.set_int(Param::Arg2, from_handshake.into());
+        if unban {
+            msg.param.set_int(Param::Arg3, 1);
+        }
         msg.id = send_msg(context, chat_id, &mut msg).await?;
     }
     context.emit_event(EventType::ChatModified(chat_id));
@@ -3165,6 +4337,31 @@ pub async fn remove_contact_from_chat(
     context: &Context,
     chat_id: ChatId,
     contact_id: ContactId,
+) -> Result<()> {
+    remove_contact_from_chat_ex(context, chat_id, contact_id, false).await
+}
+
+/// Removes a member from a group and bans them,
+/// so they are not added again when other members add them or send messages to them.
+///
+/// The ban is sent to the other members with the member-removed message,
+/// so their devices enforce it as well.
+/// As groups have no admins, any member can ban and unban members,
+/// see [`unban_member`].
+pub async fn remove_member_and_ban(
+    context: &Context,
+    chat_id: ChatId,
+    contact_id: ContactId,
+) -> Result<()> {
+    ensure!(contact_id != ContactId::SELF, "Cannot ban self");
+    remove_contact_from_chat_ex(context, chat_id, contact_id, true).await
+}
+
+async fn remove_contact_from_chat_ex(
+    context: &Context,
+    chat_id: ChatId,
+    contact_id: ContactId,
+    ban: bool,
 ) -> Result<()> {
     ensure!(
         !chat_id.is_special(),
@@ -3207,9 +4404,15 @@ pub async fn remove_contact_from_chat(
                         }
                         msg.param.set_cmd(SystemMessage::MemberRemovedFromGroup);
                         msg.param.set(Param::Arg, contact.get_addr());
+                        if ban {
+                            msg.param.set_int(Param::Arg2, 1);
+                        }
                         msg.id = send_msg(context, chat_id, &mut msg).await?;
                     }
                 }
+                if ban {
+                    ban_member(context, chat_id, contact_id).await?;
+                }
                 // we remove the member from the chat after constructing the
                 // to-be-send message. If between send_msg() and here the
                 // process dies the user will have to re-do the action.  It's
@@ -3235,6 +4438,81 @@ pub async fn remove_contact_from_chat(
     Ok(())
 }
 
+/// Lifts the ban of a member banned with [`remove_member_and_ban`]
+/// and adds them to the group again.
+///
+/// The unban is sent to the other members with the member-added message,
+/// so their devices lift the ban as well.
+pub async fn unban_member(context: &Context, chat_id: ChatId, contact_id: ContactId) -> Result<()> {
+    ensure!(
+        is_member_banned(context, chat_id, contact_id).await?,
+        "{contact_id} is not banned from {chat_id}"
+    );
+    lift_ban(context, chat_id, contact_id).await?;
+    add_contact_to_chat_ex_unban(context, chat_id, contact_id, false, true).await?;
+    Ok(())
+}
+
+/// Lifts the ban of a member, see [`unban_member`].
+pub(crate) async fn lift_ban(
+    context: &Context,
+    chat_id: ChatId,
+    contact_id: ContactId,
+) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "DELETE FROM chat_bans WHERE chat_id=? AND contact_id=?",
+            (chat_id, contact_id),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Bans a member from a group, see [`remove_member_and_ban`].
+pub(crate) async fn ban_member(
+    context: &Context,
+    chat_id: ChatId,
+    contact_id: ContactId,
+) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "INSERT OR IGNORE INTO chat_bans (chat_id, contact_id, timestamp) VALUES (?, ?, ?)",
+            (chat_id, contact_id, time()),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Returns true if the contact was banned from the group with [`remove_member_and_ban`].
+pub async fn is_member_banned(
+    context: &Context,
+    chat_id: ChatId,
+    contact_id: ContactId,
+) -> Result<bool> {
+    context
+        .sql
+        .exists(
+            "SELECT COUNT(*) FROM chat_bans WHERE chat_id=? AND contact_id=?",
+            (chat_id, contact_id),
+        )
+        .await
+}
+
+/// Returns the contacts banned from the group with [`remove_member_and_ban`].
+pub async fn get_banned_members(context: &Context, chat_id: ChatId) -> Result<Vec<ContactId>> {
+    context
+        .sql
+        .query_map(
+            "SELECT contact_id FROM chat_bans WHERE chat_id=? ORDER BY timestamp",
+            (chat_id,),
+            |row| row.get::<_, ContactId>(0),
+            |ids| ids.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await
+}
+
 async fn set_group_explicitly_left(context: &Context, grpid: &str) -> Result<()> {
     if !is_group_explicitly_left(context, grpid).await? {
         context
@@ -3359,13 +4637,20 @@ pub async fn set_chat_profile_image(
 }
 
 /// Forwards multiple messages to a chat.
+///
+/// SMTP loop is interrupted and `DC_EVENT_MSGS_CHANGED` is emitted only once
+/// after all messages are forwarded.
+///
+/// Unlike [`crate::message::delete_msgs`], the messages are not inserted in a single transaction:
+/// each forwarded message is prepared and rendered for sending on its own,
+/// which needs async database access that cannot run inside a transaction.
 pub async fn forward_msgs(context: &Context, msg_ids: &[MsgId], chat_id: ChatId) -> Result<()> {
     ensure!(!msg_ids.is_empty(), "empty msgs_ids: nothing to forward");
     ensure!(!chat_id.is_special(), "can not forward to special chat");
 
-    let mut created_chats: Vec<ChatId> = Vec::new();
     let mut created_msgs: Vec<MsgId> = Vec::new();
     let mut curr_timestamp: i64;
+    let mut interrupt_smtp = false;
 
     chat_id
         .unarchive_if_not_muted(context, MessageState::Undefined)
@@ -3394,6 +4679,9 @@ pub async fn forward_msgs(context: &Context, msg_ids: &[MsgId], chat_id: ChatId)
             if msg.state == MessageState::OutDraft {
                 bail!("cannot forward drafts.");
             }
+            if msg.is_view_once() {
+                bail!("cannot forward view-once messages.");
+            }
 
             let original_param = msg.param.clone();
 
@@ -3414,6 +4702,20 @@ pub async fn forward_msgs(context: &Context, msg_ids: &[MsgId], chat_id: ChatId)
             msg.param.remove(Param::WebxdcSummaryTimestamp);
             msg.in_reply_to = None;
 
+            if chat.is_self_talk() {
+                // Remember where the message comes from,
+                // unless it is saved again from the saved messages.
+                if !msg.param.exists(Param::SavedFromChat) {
+                    msg.param
+                        .set_int(Param::SavedFromChat, msg.chat_id.to_u32() as i32);
+                    msg.param
+                        .set_int(Param::SavedFromContact, msg.from_id.to_u32() as i32);
+                }
+            } else {
+                msg.param.remove(Param::SavedFromChat);
+                msg.param.remove(Param::SavedFromContact);
+            }
+
             // do not leak data as group names; a default subject is generated by mimefactory
             msg.subject = "".to_string();
 
@@ -3444,18 +4746,22 @@ pub async fn forward_msgs(context: &Context, msg_ids: &[MsgId], chat_id: ChatId)
                     .await?;
                 curr_timestamp += 1;
                 if create_send_msg_job(context, new_msg_id).await?.is_some() {
-                    context
-                        .scheduler
-                        .interrupt_smtp(InterruptInfo::new(false))
-                        .await;
+                    interrupt_smtp = true;
                 }
             }
-            created_chats.push(chat_id);
             created_msgs.push(new_msg_id);
         }
     }
-    for (chat_id, msg_id) in created_chats.iter().zip(created_msgs.iter()) {
-        context.emit_msgs_changed(*chat_id, *msg_id);
+    if interrupt_smtp {
+        context
+            .scheduler
+            .interrupt_smtp(InterruptInfo::new(false))
+            .await;
+    }
+    match created_msgs[..] {
+        [] => {}
+        [msg_id] => context.emit_msgs_changed(chat_id, msg_id),
+        _ => context.emit_msgs_changed(chat_id, MsgId::new(0)),
     }
     Ok(())
 }
@@ -3575,6 +4881,12 @@ pub async fn add_device_msg_with_importance(
     if let Some(msg) = msg {
         chat_id = ChatId::get_for_contact(context, ContactId::DEVICE).await?;
 
+        if msg.text.as_deref().unwrap_or_default().is_empty() {
+            if let Some(action) = msg.get_device_action() {
+                msg.text = Some(stock_str::device_action(context, action).await);
+            }
+        }
+
         let rfc724_mid = create_outgoing_rfc724_mid(None, "@device");
         msg.try_calc_and_set_dimensions(context).await.ok();
         prepare_msg_blob(context, msg).await?;
@@ -3643,7 +4955,7 @@ pub async fn add_device_msg_with_importance(
     }
 
     if !msg_id.is_unset() {
-        chat_id.emit_msg_event(context, msg_id, important);
+        chat_id.emit_msg_event(context, msg_id, important).await;
     }
 
     Ok(msg_id)
@@ -3743,6 +5055,9 @@ pub(crate) async fn add_info_msg_with_cmd(
     ).await?;
 
     let msg_id = MsgId::new(row_id.try_into()?);
+    if cmd != SystemMessage::Unknown {
+        msg_id.update_info_group(context).await?;
+    }
     context.emit_msgs_changed(chat_id, msg_id);
 
     Ok(msg_id)
@@ -3792,9 +5107,10 @@ mod tests {
     use crate::chatlist::{get_archived_cnt, Chatlist};
     use crate::constants::{DC_GCL_ARCHIVED_ONLY, DC_GCL_NO_SPECIALS};
     use crate::contact::{Contact, ContactAddress};
-    use crate::message::delete_msgs;
+    use crate::headerdef::HeaderDef;
+    use crate::message::{delete_msgs, DeviceAction};
     use crate::receive_imf::receive_imf;
-    use crate::test_utils::TestContext;
+    use crate::test_utils::{TestContext, TestContextManager};
     use tokio::fs;
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -3819,7 +5135,13 @@ async fn test_chat_info() {
                 "profile_image": "",
                 "draft": "",
                 "is_muted": false,
-                "ephemeral_timer": "Disabled"
+                "ephemeral_timer": "Disabled",
+                "retention": {
+                    "keep_last": 0,
+                    "server_days": 0
+                },
+                "retention_deleted_local": 0,
+                "retention_deleted_server": 0
             }
         "#;
 
@@ -4011,6 +5333,34 @@ async fn test_add_contact_to_chat_ex_add_self() {
         assert_eq!(added, false);
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_info_group() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let chat_id = create_group_chat(&alice, ProtectionStatus::Unprotected, "foo").await?;
+        alice.send_text(chat_id, "hi").await;
+
+        let mut added = Vec::new();
+        for addr in ["bob@example.net", "claire@example.org", "dave@example.org"] {
+            let contact_id = Contact::create(&alice, "", addr).await?;
+            add_contact_to_chat(&alice, chat_id, contact_id).await?;
+            added.push(alice.get_last_msg_in(chat_id).await);
+        }
+        let text_msg = alice.send_text(chat_id, "welcome").await;
+        let contact_id = Contact::create(&alice, "", "fiona@example.net").await?;
+        add_contact_to_chat(&alice, chat_id, contact_id).await?;
+        let single = alice.get_last_msg_in(chat_id).await;
+
+        for msg in &added {
+            let msg = Message::load_from_db(&alice, msg.id).await?;
+            assert_eq!(msg.get_info_type(), SystemMessage::MemberAddedToGroup);
+            assert_eq!(msg.get_info_group(), Some(added[0].id));
+        }
+        let text_msg = Message::load_from_db(&alice, text_msg.sender_msg_id).await?;
+        assert_eq!(text_msg.get_info_group(), None);
+        assert_eq!(single.get_info_group(), None);
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_modify_chat_multi_device() -> Result<()> {
         let a1 = TestContext::new_alice().await;
@@ -4368,6 +5718,41 @@ async fn test_add_device_msg_label_only() {
         assert!(!msg_id.as_ref().unwrap().is_unset());
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_add_device_msg_with_action() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let mut msg = Message::new(Viewtype::Text);
+        msg.set_device_action(DeviceAction::Backup);
+        let msg_id = add_device_msg(&t, None, Some(&mut msg)).await?;
+        let msg = Message::load_from_db(&t, msg_id).await?;
+        assert_eq!(msg.get_device_action(), Some(DeviceAction::Backup));
+        assert_eq!(
+            msg.get_text().unwrap(),
+            "You can create a backup in the settings."
+        );
+
+        let mut msg = Message::new(Viewtype::Text);
+        msg.set_text(Some("Notifications are disabled.".to_string()));
+        msg.set_device_action(DeviceAction::NotificationSettings);
+        let msg_id = add_device_msg(&t, None, Some(&mut msg)).await?;
+        let msg = Message::load_from_db(&t, msg_id).await?;
+        assert_eq!(
+            msg.get_device_action(),
+            Some(DeviceAction::NotificationSettings)
+        );
+        assert_eq!(msg.get_text().unwrap(), "Notifications are disabled.");
+
+        // Actions are only offered by device messages.
+        let mut msg = Message::new(Viewtype::Text);
+        msg.set_text(Some("foo".to_string()));
+        msg.set_device_action(DeviceAction::Backup);
+        let self_chat_id = t.get_self_chat().await.id;
+        let msg_id = send_msg(&t, self_chat_id, &mut msg).await?;
+        let msg = Message::load_from_db(&t, msg_id).await?;
+        assert_eq!(msg.get_device_action(), None);
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_was_device_msg_ever_added() {
         let t = TestContext::new().await;
@@ -4642,6 +6027,88 @@ async fn msg_from_bob(t: &TestContext, num: u32) -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_archive_mode() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        t.set_config(Config::Displayname, Some("Alice")).await?;
+
+        async fn msg_from_bob(t: &TestContext, num: u32, text: &str) -> Result<()> {
+            receive_imf(
+                t,
+                format!(
+                    "From: bob@example.net\n\
+                     To: alice@example.org\n\
+                     Message-ID: <{num}@example.org>\n\
+                     Chat-Version: 1.0\n\
+                     Date: Sun, 22 Mar 2022 19:37:57 +0000\n\
+                     \n\
+                     {text}\n"
+                )
+                .as_bytes(),
+                false,
+            )
+            .await?;
+            Ok(())
+        }
+
+        msg_from_bob(&t, 1, "hello").await?;
+        let chat_id = t.get_last_msg().await.get_chat_id();
+        chat_id.accept(&t).await?;
+        assert_eq!(
+            Chat::load_from_db(&t, chat_id).await?.get_archive_mode(),
+            ArchiveMode::AlwaysUnarchive
+        );
+
+        // The chat stays archived, but fresh messages are counted at the archive link.
+        chat_id
+            .set_archive_mode(&t, ArchiveMode::StayArchived)
+            .await?;
+        chat_id.set_visibility(&t, ChatVisibility::Archived).await?;
+        msg_from_bob(&t, 2, "hello @Alice").await?;
+        assert_eq!(get_archived_cnt(&t).await?, 1);
+        assert_eq!(DC_CHAT_ID_ARCHIVED_LINK.get_fresh_msg_cnt(&t).await?, 1);
+
+        // Only mentions unarchive the chat.
+        chat_id
+            .set_archive_mode(&t, ArchiveMode::UnarchiveOnMention)
+            .await?;
+        assert_eq!(
+            Chat::load_from_db(&t, chat_id).await?.get_archive_mode(),
+            ArchiveMode::UnarchiveOnMention
+        );
+        msg_from_bob(&t, 3, "hello everyone").await?;
+        assert_eq!(get_archived_cnt(&t).await?, 1);
+        msg_from_bob(&t, 4, "hello @alice").await?;
+        assert_eq!(get_archived_cnt(&t).await?, 0);
+
+        // Replies to own messages are mentions as well.
+        chat_id.set_visibility(&t, ChatVisibility::Archived).await?;
+        let sent = t.send_text(chat_id, "hi").await;
+        chat_id.set_visibility(&t, ChatVisibility::Archived).await?;
+        let rfc724_mid = Message::load_from_db(&t, sent.sender_msg_id)
+            .await?
+            .rfc724_mid;
+        receive_imf(
+            &t,
+            format!(
+                "From: bob@example.net\n\
+                 To: alice@example.org\n\
+                 Message-ID: <5@example.org>\n\
+                 In-Reply-To: <{rfc724_mid}>\n\
+                 Chat-Version: 1.0\n\
+                 Date: Sun, 22 Mar 2022 19:37:57 +0000\n\
+                 \n\
+                 reply\n"
+            )
+            .as_bytes(),
+            false,
+        )
+        .await?;
+        assert_eq!(get_archived_cnt(&t).await?, 0);
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_archive_fresh_msgs() -> Result<()> {
         let t = TestContext::new_alice().await;
@@ -4950,6 +6417,37 @@ async fn test_set_mute_duration() {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_mute_expiry_unarchives() -> Result<()> {
+        let t = TestContext::new().await;
+        let chat_id = create_group_chat(&t, ProtectionStatus::Unprotected, "foo").await?;
+        chat_id.set_visibility(&t, ChatVisibility::Archived).await?;
+        set_muted(
+            &t,
+            chat_id,
+            MuteDuration::Until(t.clock.now() + Duration::from_secs(3600)),
+        )
+        .await?;
+
+        // Muted archived chats stay archived.
+        chat_id
+            .unarchive_if_not_muted(&t, MessageState::InSeen)
+            .await?;
+        let chat = Chat::load_from_db(&t, chat_id).await?;
+        assert_eq!(chat.get_visibility(), ChatVisibility::Archived);
+        assert!(chat.is_muted_at(t.clock.now()));
+
+        // After the mute expires, the chat is unarchived.
+        t.clock.advance(Duration::from_secs(7200));
+        assert!(!chat.is_muted_at(t.clock.now()));
+        chat_id
+            .unarchive_if_not_muted(&t, MessageState::InSeen)
+            .await?;
+        let chat = Chat::load_from_db(&t, chat_id).await?;
+        assert_eq!(chat.get_visibility(), ChatVisibility::Normal);
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_add_info_msg() -> Result<()> {
         let t = TestContext::new().await;
@@ -5125,6 +6623,79 @@ async fn test_lookup_self_by_contact_id() {
         assert_eq!(chat.blocked, Blocked::Not);
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_remove_member_and_ban() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let fiona = tcm.fiona().await;
+
+        let alice_chat_id = create_group_chat(&alice, ProtectionStatus::Unprotected, "grp").await?;
+        let alice_bob_id = alice.add_or_lookup_contact(&bob).await.id;
+        let alice_fiona_id = alice.add_or_lookup_contact(&fiona).await.id;
+        add_contact_to_chat(&alice, alice_chat_id, alice_bob_id).await?;
+        add_contact_to_chat(&alice, alice_chat_id, alice_fiona_id).await?;
+        send_text_msg(&alice, alice_chat_id, "hi!".to_string()).await?;
+        let bob_chat_id = bob.recv_msg(&alice.pop_sent_msg().await).await.chat_id;
+        let bob_fiona_id = bob.add_or_lookup_contact(&fiona).await.id;
+        assert!(is_contact_in_chat(&bob, bob_chat_id, bob_fiona_id).await?);
+
+        assert!(
+            remove_member_and_ban(&alice, alice_chat_id, ContactId::SELF)
+                .await
+                .is_err()
+        );
+        remove_member_and_ban(&alice, alice_chat_id, alice_fiona_id).await?;
+        assert!(!is_contact_in_chat(&alice, alice_chat_id, alice_fiona_id).await?);
+        assert_eq!(
+            get_banned_members(&alice, alice_chat_id).await?,
+            vec![alice_fiona_id]
+        );
+        assert!(add_contact_to_chat(&alice, alice_chat_id, alice_fiona_id)
+            .await
+            .is_err());
+
+        // Bob enforces the ban as well.
+        let sent = alice.pop_sent_msg().await;
+        assert_eq!(
+            bob.parse_msg(&sent)
+                .await
+                .get_header(HeaderDef::ChatGroupMemberBanned)
+                .unwrap(),
+            "fiona@example.net"
+        );
+        bob.recv_msg(&sent).await;
+        assert!(!is_contact_in_chat(&bob, bob_chat_id, bob_fiona_id).await?);
+        assert!(is_member_banned(&bob, bob_chat_id, bob_fiona_id).await?);
+        assert!(add_contact_to_chat(&bob, bob_chat_id, bob_fiona_id)
+            .await
+            .is_err());
+
+        // Unbanning adds the member again and lifts the ban on Bob's side, too.
+        assert!(unban_member(&alice, alice_chat_id, alice_bob_id)
+            .await
+            .is_err());
+        unban_member(&alice, alice_chat_id, alice_fiona_id).await?;
+        assert!(!is_member_banned(&alice, alice_chat_id, alice_fiona_id).await?);
+        assert!(is_contact_in_chat(&alice, alice_chat_id, alice_fiona_id).await?);
+        let sent = alice.pop_sent_msg().await;
+        assert_eq!(
+            bob.parse_msg(&sent)
+                .await
+                .get_header(HeaderDef::ChatGroupMemberUnbanned)
+                .unwrap(),
+            "fiona@example.net"
+        );
+        bob.recv_msg(&sent).await;
+        assert!(!is_member_banned(&bob, bob_chat_id, bob_fiona_id).await?);
+        assert!(is_contact_in_chat(&bob, bob_chat_id, bob_fiona_id).await?);
+
+        // Removing without ban is not propagated as ban.
+        remove_contact_from_chat(&alice, alice_chat_id, alice_bob_id).await?;
+        assert!(!is_member_banned(&alice, alice_chat_id, alice_bob_id).await?);
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_group_with_removed_message_id() -> Result<()> {
         // Alice creates a group with Bob, sends a message to bob
@@ -5221,6 +6792,127 @@ async fn test_marknoticed_chat() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_create_group_chat_ex() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob = TestContext::new_bob().await;
+        let bob_id = Contact::create(&alice, "Bob", "bob@example.net").await?;
+        let claire_id = Contact::create(&alice, "Claire", "claire@example.org").await?;
+
+        let file = alice.get_blobdir().join("avatar.png");
+        fs::write(&file, include_bytes!("../test-data/image/avatar64x64.png")).await?;
+        let timer = EphemeralTimer::Enabled { duration: 3600 };
+        let chat_id = create_group_chat_ex(
+            &alice,
+            GroupParams {
+                name: "foo".to_string(),
+                members: vec![bob_id, claire_id, bob_id],
+                avatar: Some(file),
+                ephemeral_timer: timer,
+                ..Default::default()
+            },
+        )
+        .await?;
+        let chat = Chat::load_from_db(&alice, chat_id).await?;
+        assert_eq!(chat.get_name(), "foo");
+        assert!(chat.is_unpromoted());
+        assert!(chat.get_profile_image(&alice).await?.is_some());
+        assert_eq!(chat_id.get_ephemeral_timer(&alice).await?, timer);
+        assert_eq!(get_chat_contacts(&alice, chat_id).await?.len(), 3);
+        assert!(get_chat_msgs(&alice, chat_id).await?.is_empty());
+
+        // Failure does not leave a partially created group behind.
+        let chats_cnt = Chatlist::try_load(&alice, 0, None, None).await?.len();
+        assert!(create_group_chat_ex(
+            &alice,
+            GroupParams {
+                name: "bar".to_string(),
+                protect: ProtectionStatus::Protected,
+                members: vec![bob_id],
+                ..Default::default()
+            },
+        )
+        .await
+        .is_err());
+        assert_eq!(
+            Chatlist::try_load(&alice, 0, None, None).await?.len(),
+            chats_cnt
+        );
+
+        let protected_id = create_group_chat_ex(
+            &alice,
+            GroupParams {
+                name: "protected".to_string(),
+                protect: ProtectionStatus::Protected,
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert!(Chat::load_from_db(&alice, protected_id)
+            .await?
+            .is_protected());
+
+        let sent = alice.send_text(chat_id, "hi").await;
+        let bob_chat_id = bob.recv_msg(&sent).await.chat_id;
+        assert_eq!(get_chat_contacts(&bob, bob_chat_id).await?.len(), 3);
+        assert_eq!(bob_chat_id.get_ephemeral_timer(&bob).await?, timer);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_batch_chat_operations() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let mut chat_ids = Vec::new();
+        for (i, addr) in ["bob@example.net", "claire@example.org", "dom@example.com"]
+            .iter()
+            .enumerate()
+        {
+            chat_ids.push(t.create_chat_with_contact("", addr).await.id);
+            receive_imf(
+                &t,
+                format!(
+                    "From: {addr}\n\
+                     To: alice@example.org\n\
+                     Message-ID: <{i}@example.org>\n\
+                     Chat-Version: 1.0\n\
+                     Date: Fri, 23 Apr 2021 10:00:57 +0000\n\
+                     \n\
+                     hello\n"
+                )
+                .as_bytes(),
+                false,
+            )
+            .await?;
+        }
+        assert_eq!(t.get_fresh_msgs().await?.len(), 3);
+
+        marknoticed_chats(&t, &chat_ids[..2]).await?;
+        assert_eq!(chat_ids[0].get_fresh_msg_cnt(&t).await?, 0);
+        assert_eq!(chat_ids[1].get_fresh_msg_cnt(&t).await?, 0);
+        assert_eq!(chat_ids[2].get_fresh_msg_cnt(&t).await?, 1);
+
+        chat_ids[0]
+            .set_visibility(&t, ChatVisibility::Pinned)
+            .await?;
+        archive_chats(&t, &chat_ids[1..], true).await?;
+        assert_eq!(chat_ids[2].get_fresh_msg_cnt(&t).await?, 0);
+        for chat_id in &chat_ids[1..] {
+            let chat = Chat::load_from_db(&t, *chat_id).await?;
+            assert_eq!(chat.get_visibility(), ChatVisibility::Archived);
+        }
+
+        archive_chats(&t, &chat_ids, false).await?;
+        let chat = Chat::load_from_db(&t, chat_ids[0]).await?;
+        assert_eq!(chat.get_visibility(), ChatVisibility::Pinned);
+        for chat_id in &chat_ids[1..] {
+            let chat = Chat::load_from_db(&t, *chat_id).await?;
+            assert_eq!(chat.get_visibility(), ChatVisibility::Normal);
+        }
+
+        assert!(archive_chats(&t, &[DC_CHAT_ID_TRASH], true).await.is_err());
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_contact_request_fresh_messages() -> Result<()> {
         let t = TestContext::new_alice().await;
@@ -5426,6 +7118,89 @@ async fn test_sticker_gif() -> Result<()> {
         .await
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_oversized_attachment() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let chat_id = t.get_self_chat().await.id;
+        t.set_config(Config::MaxMsgSize, Some("30000")).await?;
+
+        let file = t.get_blobdir().join("file.txt");
+        tokio::fs::write(&file, vec![b'x'; 30000]).await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        let err = send_msg(&t, chat_id, &mut msg).await.unwrap_err();
+        let err = err.downcast_ref::<MessageTooLarge>().unwrap();
+        assert_eq!(err.limit, 30000);
+        // Base64 and ASCII armor.
+        assert!(err.size > 30000 * 16 / 9);
+
+        // Further attachments count as well.
+        let small_file = t.get_blobdir().join("small.txt");
+        tokio::fs::write(&small_file, b"small").await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(small_file.to_str().unwrap(), None);
+        msg.add_attachment(file.to_str().unwrap());
+        let err = send_msg(&t, chat_id, &mut msg).await.unwrap_err();
+        assert!(err.downcast_ref::<MessageTooLarge>().is_some());
+
+        // Unencrypted messages are smaller.
+        t.set_config(Config::MaxMsgSize, Some("60000")).await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        let err = send_msg(&t, chat_id, &mut msg).await.unwrap_err();
+        assert!(err.downcast_ref::<MessageTooLarge>().is_some());
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        msg.param.set_int(Param::ForcePlaintext, 1);
+        send_msg(&t, chat_id, &mut msg).await?;
+        assert!(t.pop_sent_msg().await.payload().len() <= 60000);
+
+        let bytes = include_bytes!("../test-data/image/avatar1000x1000.jpg");
+        let file = t.get_blobdir().join("image.jpg");
+        tokio::fs::write(&file, bytes).await?;
+        let mut msg = Message::new(Viewtype::Image);
+        msg.set_file(file.to_str().unwrap(), None);
+        t.set_config(Config::OversizedAttachment, Some("1")).await?;
+        let err = send_msg(&t, chat_id, &mut msg).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<MessageTooLarge>().unwrap().limit, 60000);
+
+        tokio::fs::write(&file, bytes).await?;
+        let mut msg = Message::new(Viewtype::Image);
+        msg.set_file(file.to_str().unwrap(), None);
+        t.set_config(Config::OversizedAttachment, None).await?;
+        send_msg(&t, chat_id, &mut msg).await?;
+        assert!(t.pop_sent_msg().await.payload().len() <= 60000);
+
+        t.set_config(Config::MaxMsgSize, None).await?;
+        assert_eq!(t.get_max_msg_size().await?, None);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_send_image_with_invalid_metadata() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let chat_id = t.get_self_chat().await.id;
+
+        // The EXIF segment cannot be parsed, so the metadata cannot be removed.
+        let jpeg = include_bytes!("../test-data/image/avatar1000x1000.jpg");
+        let mut bytes = jpeg[..2].to_vec();
+        bytes.extend_from_slice(&[0xff, 0xe1, 0x00, 0x0e]);
+        bytes.extend_from_slice(b"Exif\0\0GPS\0\0\0");
+        bytes.extend_from_slice(&jpeg[2..]);
+        let file = t.get_blobdir().join("image.jpg");
+        tokio::fs::write(&file, &bytes).await?;
+        let mut msg = Message::new(Viewtype::Image);
+        msg.set_file(file.to_str().unwrap(), None);
+        let err = send_msg(&t, chat_id, &mut msg).await.unwrap_err();
+        assert!(format!("{err:#}").contains("Cannot strip image metadata"));
+
+        t.set_config_bool(Config::StripExif, false).await?;
+        let mut msg = Message::new(Viewtype::Image);
+        msg.set_file(file.to_str().unwrap(), None);
+        send_msg(&t, chat_id, &mut msg).await?;
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_sticker_forward() -> Result<()> {
         // create chats
@@ -5478,6 +7253,44 @@ async fn test_forward() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_forward_file_reuses_blob() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob_chat_id = alice
+            .create_chat_with_contact("Bob", "bob@example.net")
+            .await
+            .id;
+        let claire_chat_id = alice
+            .create_chat_with_contact("Claire", "claire@example.org")
+            .await
+            .id;
+
+        let file = alice.get_blobdir().join("large.bin");
+        tokio::fs::write(&file, vec![0x42; 1024 * 1024]).await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        let sent_msg = alice.send_msg(bob_chat_id, &mut msg).await;
+        let orig_msg = Message::load_from_db(&alice, sent_msg.sender_msg_id).await?;
+
+        let count_blobs = || async {
+            let mut dir = tokio::fs::read_dir(alice.get_blobdir()).await.unwrap();
+            let mut count = 0;
+            while dir.next_entry().await.unwrap().is_some() {
+                count += 1;
+            }
+            count
+        };
+        let blobs_before = count_blobs().await;
+
+        forward_msgs(&alice, &[orig_msg.id], claire_chat_id).await?;
+        let forwarded = alice.pop_sent_msg().await;
+        let forwarded_msg = Message::load_from_db(&alice, forwarded.sender_msg_id).await?;
+        assert!(forwarded_msg.is_forwarded());
+        assert_eq!(forwarded_msg.get_file(&alice), orig_msg.get_file(&alice));
+        assert_eq!(count_blobs().await, blobs_before);
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_forward_info_msg() -> Result<()> {
         let t = TestContext::new_alice().await;
@@ -5504,6 +7317,46 @@ async fn test_forward_info_msg() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_save_msg() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob = TestContext::new_bob().await;
+        let alice_chat = alice.create_chat(&bob).await;
+        let sent = alice.send_text(alice_chat.id, "Hi Bob").await;
+        let received = bob.recv_msg(&sent).await;
+
+        let self_chat = bob.get_self_chat().await;
+        forward_msgs(&bob, &[received.id], self_chat.id).await?;
+        let saved = bob.get_last_msg_in(self_chat.id).await;
+        assert_eq!(saved.get_text().unwrap(), "Hi Bob");
+        assert_eq!(saved.get_saved_from_chat_id(), Some(received.chat_id));
+        assert_eq!(saved.get_saved_from_contact_id(), Some(received.from_id));
+
+        // Only saved messages can be tagged.
+        assert!(received.id.add_saved_tag(&bob, "greeting").await.is_err());
+        assert!(saved.id.add_saved_tag(&bob, " ").await.is_err());
+
+        saved.id.add_saved_tag(&bob, "greeting").await?;
+        saved.id.add_saved_tag(&bob, "alice").await?;
+        saved.id.add_saved_tag(&bob, "Greeting").await?;
+        assert_eq!(saved.id.get_saved_tags(&bob).await?, ["alice", "greeting"]);
+        assert_eq!(bob.get_saved_by_tag("GREETING").await?, [saved.id]);
+        assert!(bob.get_saved_by_tag("other").await?.is_empty());
+
+        saved.id.remove_saved_tag(&bob, "greeting").await?;
+        assert_eq!(saved.id.get_saved_tags(&bob).await?, ["alice"]);
+        assert!(bob.get_saved_by_tag("greeting").await?.is_empty());
+
+        // The origin is not leaked when forwarding the saved message.
+        let bob_chat = bob.create_chat(&alice).await;
+        forward_msgs(&bob, &[saved.id], bob_chat.id).await?;
+        let forwarded = bob.get_last_msg_in(bob_chat.id).await;
+        assert_eq!(forwarded.get_saved_from_chat_id(), None);
+        assert_eq!(forwarded.get_saved_from_contact_id(), None);
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_forward_quote() -> Result<()> {
         let alice = TestContext::new_alice().await;
@@ -5764,6 +7617,195 @@ async fn test_resend_info_message_fails() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_send_album() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob = TestContext::new_bob().await;
+        let alice_chat_id = alice.create_chat(&bob).await.id;
+
+        let mut files = Vec::new();
+        for name in ["first.png", "second.png"] {
+            let file = alice.get_blobdir().join(name);
+            fs::write(&file, include_bytes!("../test-data/image/avatar64x64.png")).await?;
+            files.push(file.to_str().unwrap().to_string());
+        }
+        let msg_ids =
+            send_album(&alice, alice_chat_id, &files, Some("holidays".to_string())).await?;
+        assert_eq!(msg_ids.len(), 2);
+        assert_eq!(get_album_msgs(&alice, msg_ids[1]).await?, msg_ids);
+
+        let sent2 = alice.pop_sent_msg().await;
+        let sent1 = alice.pop_sent_msg().await;
+        let rcvd1 = bob.recv_msg(&sent1).await;
+        let rcvd2 = bob.recv_msg(&sent2).await;
+        assert_eq!(rcvd1.get_viewtype(), Viewtype::Image);
+        assert_eq!(rcvd1.get_text().as_deref(), Some("holidays"));
+        assert_eq!(rcvd2.get_text().as_deref(), Some(""));
+        let album_id = rcvd1.get_album_id().unwrap();
+        assert_eq!(rcvd2.get_album_id(), Some(album_id));
+        assert_eq!(get_album_msgs(&bob, rcvd2.id).await?, [rcvd1.id, rcvd2.id]);
+
+        // Other messages do not belong to an album.
+        let rcvd = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "hi").await)
+            .await;
+        assert_eq!(rcvd.get_album_id(), None);
+        assert_eq!(get_album_msgs(&bob, rcvd.id).await?, [rcvd.id]);
+
+        // Each file is sent with the viewtype matching it.
+        let gif = alice.get_blobdir().join("anim.gif");
+        fs::write(&gif, include_bytes!("../test-data/image/image100x50.gif")).await?;
+        let video = alice.get_blobdir().join("clip.mp4");
+        fs::write(&video, b"not really a video").await?;
+        let mixed = [
+            files[0].clone(),
+            gif.to_str().unwrap().to_string(),
+            video.to_str().unwrap().to_string(),
+        ];
+        let msg_ids = send_album(&alice, alice_chat_id, &mixed, None).await?;
+        let mut viewtypes = Vec::new();
+        for msg_id in msg_ids {
+            viewtypes.push(Message::load_from_db(&alice, msg_id).await?.get_viewtype());
+        }
+        assert_eq!(viewtypes, [Viewtype::Image, Viewtype::Gif, Viewtype::Video]);
+
+        // Only images and videos can be sent as album.
+        let file = alice.get_blobdir().join("report.pdf");
+        fs::write(&file, b"%PDF").await?;
+        files.push(file.to_str().unwrap().to_string());
+        assert!(send_album(&alice, alice_chat_id, &files, None)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    async fn get_msg_ids(t: &TestContext, chat_id: ChatId, corrected_order: bool) -> Vec<MsgId> {
+        get_chat_msgs_ex(
+            t,
+            chat_id,
+            MessageListOptions {
+                info_only: false,
+                add_daymarker: false,
+                corrected_order,
+            },
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .filter_map(|item| match item {
+            ChatItem::Message { msg_id } => Some(msg_id),
+            ChatItem::DayMarker { .. } => None,
+        })
+        .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_corrected_msgs_order() -> Result<()> {
+        // (sleep() is needed as otherwise smeared time from Alice looks to Bob like messages from the future which are all set to "now" then)
+        let alice = TestContext::new_alice().await;
+        let bob = TestContext::new_bob().await;
+        let alice_chat_id = alice.create_chat(&bob).await.id;
+        let bob_chat_id = bob.create_chat(&alice).await.id;
+
+        let sent1 = alice.send_text(alice_chat_id, "queued").await;
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let sent2 = alice.send_text(alice_chat_id, "lost").await;
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let sent3 = alice.send_text(alice_chat_id, "third").await;
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+        let msg3 = bob.recv_msg(&sent3).await;
+        marknoticed_chat(&bob, bob_chat_id).await?;
+
+        // The provider delivers the first message late, it is sorted to the end while fresh.
+        let msg1 = bob.recv_msg(&sent1).await;
+        assert_eq!(
+            get_msg_ids(&bob, bob_chat_id, false).await,
+            [msg3.id, msg1.id]
+        );
+        assert_eq!(
+            get_msg_ids(&bob, bob_chat_id, true).await,
+            [msg3.id, msg1.id]
+        );
+
+        marknoticed_chat(&bob, bob_chat_id).await?;
+        assert_eq!(
+            get_msg_ids(&bob, bob_chat_id, false).await,
+            [msg3.id, msg1.id]
+        );
+        assert_eq!(
+            get_msg_ids(&bob, bob_chat_id, true).await,
+            [msg1.id, msg3.id]
+        );
+
+        // Alice resends the lost message.
+        resend_msgs(&alice, &[sent2.sender_msg_id]).await?;
+        let msg2 = bob.recv_msg(&alice.pop_sent_msg().await).await;
+        assert_eq!(msg2.text.as_deref(), Some("lost"));
+        assert_eq!(
+            get_msg_ids(&bob, bob_chat_id, true).await,
+            [msg1.id, msg3.id, msg2.id]
+        );
+        marknoticed_chat(&bob, bob_chat_id).await?;
+        assert_eq!(
+            get_msg_ids(&bob, bob_chat_id, true).await,
+            [msg1.id, msg2.id, msg3.id]
+        );
+
+        // Repairing stores the corrected order.
+        assert_eq!(bob_chat_id.repair_msgs_order(&bob).await?, 2);
+        assert_eq!(
+            get_msg_ids(&bob, bob_chat_id, false).await,
+            [msg1.id, msg2.id, msg3.id]
+        );
+        assert_eq!(bob_chat_id.repair_msgs_order(&bob).await?, 0);
+        assert_eq!(
+            get_msg_ids(&bob, bob_chat_id, true).await,
+            [msg1.id, msg2.id, msg3.id]
+        );
+
+        // Messages claiming to be sent in the past are not sorted before the message they reply to
+        // or before the creation of the chat.
+        let msg4 = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "reply").await)
+            .await;
+        let msg5 = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "backdated").await)
+            .await;
+        let msg2 = Message::load_from_db(&bob, msg2.id).await?;
+        let msg3 = Message::load_from_db(&bob, msg3.id).await?;
+        bob.sql
+            .execute(
+                "UPDATE msgs SET timestamp_sent=1, mime_in_reply_to=? WHERE id=?",
+                (format!("<{}>", msg3.rfc724_mid), msg4.id),
+            )
+            .await?;
+        bob.sql
+            .execute(
+                "UPDATE msgs SET timestamp_sent=1, mime_in_reply_to='' WHERE id=?",
+                (msg5.id,),
+            )
+            .await?;
+        bob.sql
+            .execute(
+                "UPDATE chats SET created_timestamp=? WHERE id=?",
+                (msg2.timestamp_sent, bob_chat_id),
+            )
+            .await?;
+        marknoticed_chat(&bob, bob_chat_id).await?;
+        assert_eq!(
+            get_msg_ids(&bob, bob_chat_id, true).await,
+            [msg1.id, msg2.id, msg5.id, msg3.id, msg4.id]
+        );
+        assert_eq!(bob_chat_id.repair_msgs_order(&bob).await?, 2);
+        assert_eq!(bob_chat_id.repair_msgs_order(&bob).await?, 0);
+        assert_eq!(
+            get_msg_ids(&bob, bob_chat_id, false).await,
+            [msg1.id, msg2.id, msg5.id, msg3.id, msg4.id]
+        );
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_can_send_group() -> Result<()> {
         let alice = TestContext::new_alice().await;
@@ -5927,6 +7969,69 @@ async fn test_chat_get_encryption_info() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_chat_media_ex() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let chat_id = create_group_chat(&t, ProtectionStatus::Unprotected, "foo").await?;
+
+        // 2023-01-15, 2023-01-20 and 2023-03-10, 12:00 UTC.
+        let timestamps = [1673784000, 1674216000, 1678449600];
+        let mut msg_ids = Vec::new();
+        for (i, (viewtype, timestamp)) in [Viewtype::Image, Viewtype::File, Viewtype::Image]
+            .into_iter()
+            .zip(timestamps)
+            .enumerate()
+        {
+            let file = t.get_blobdir().join(format!("{i}.png"));
+            tokio::fs::write(&file, include_bytes!("../test-data/image/avatar64x64.png")).await?;
+            let mut msg = Message::new(viewtype);
+            msg.set_file(file.to_str().unwrap(), None);
+            let msg_id = send_msg(&t, chat_id, &mut msg).await?;
+            t.sql
+                .execute(
+                    "UPDATE msgs SET timestamp=? WHERE id=?",
+                    (timestamp, msg_id),
+                )
+                .await?;
+            msg_ids.push(msg_id);
+        }
+
+        let months = get_chat_media_ex(&t, Some(chat_id), MediaListOptions::default()).await?;
+        assert_eq!(
+            months,
+            vec![
+                MediaMonth {
+                    year: 2023,
+                    month: 1,
+                    msg_ids: vec![msg_ids[0], msg_ids[1]]
+                },
+                MediaMonth {
+                    year: 2023,
+                    month: 3,
+                    msg_ids: vec![msg_ids[2]]
+                }
+            ]
+        );
+
+        let options = MediaListOptions {
+            viewtypes: vec![Viewtype::Image, Viewtype::Gif],
+            ..Default::default()
+        };
+        let months = get_chat_media_ex(&t, None, options).await?;
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0].msg_ids, vec![msg_ids[0]]);
+
+        let options = MediaListOptions {
+            since: Some(timestamps[1]),
+            until: Some(timestamps[2]),
+            ..Default::default()
+        };
+        let months = get_chat_media_ex(&t, Some(chat_id), options).await?;
+        assert_eq!(months.len(), 1);
+        assert_eq!(months[0].msg_ids, vec![msg_ids[1]]);
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_get_chat_media() -> Result<()> {
         let t = TestContext::new_alice().await;
@@ -6121,4 +8226,44 @@ async fn test_blob_renaming() -> Result<()> {
         );
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_send_msg_deferred() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob = TestContext::new_bob().await;
+        let chat_id = alice.create_chat(&bob).await.id;
+
+        let mut msg = Message::new(Viewtype::Text);
+        msg.set_text(Some("deferred".to_string()));
+        let msg_id = send_msg_deferred(&alice, chat_id, &mut msg).await?;
+        let msg = Message::load_from_db(&alice, msg_id).await?;
+        assert_eq!(msg.chat_id, chat_id);
+        assert_eq!(msg.state, MessageState::OutPending);
+        assert!(alice.pop_sent_msg_opt(Duration::ZERO).await.is_none());
+
+        crate::smtp::render_deferred_msgs(&alice).await?;
+        let sent = alice.pop_sent_msg().await;
+        assert_eq!(sent.sender_msg_id, msg_id);
+        let received = bob.recv_msg(&sent).await;
+        assert_eq!(received.text.as_deref(), Some("deferred"));
+
+        // Entries of messages which do not exist anymore do not block the queue.
+        alice
+            .sql
+            .execute("INSERT INTO smtp_deferred (msg_id) VALUES (?)", (12345,))
+            .await?;
+        let mut msg = Message::new(Viewtype::Text);
+        msg.set_text(Some("deferred 2".to_string()));
+        let msg_id = send_msg_deferred(&alice, chat_id, &mut msg).await?;
+        crate::smtp::render_deferred_msgs(&alice).await?;
+        assert_eq!(alice.pop_sent_msg().await.sender_msg_id, msg_id);
+        assert_eq!(
+            alice
+                .sql
+                .count("SELECT COUNT(*) FROM smtp_deferred", ())
+                .await?,
+            0
+        );
+        Ok(())
+    }
 }
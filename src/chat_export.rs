@@ -0,0 +1,277 @@
+//! # Export of chats as HTML.
+//!
+//! [`export_chat_html`] renders the messages of a chat into a static HTML page
+//! that can be opened in any browser without Delta Chat, e.g. for archival.
+//! Files of the messages are copied next to the page, so the export is self-contained.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context as _, Result};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use tokio::fs;
+
+use crate::chat::{self, Chat, ChatId, ChatItem};
+use crate::contact::{Contact, ContactId};
+use crate::context::Context;
+use crate::message::{Message, Viewtype};
+use crate::tools::timestamp_to_str;
+
+/// Name of the page written by [`export_chat_html`].
+const INDEX_HTML: &str = "index.html";
+
+/// Name of the directory the files of the messages are copied to.
+const FILES_DIR: &str = "files";
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:50em;margin:auto;padding:1em}\
+    .msg{margin:.5em 0;padding:.5em;border-radius:.5em;background:#eee}\
+    .msg.out{background:#dfd}\
+    .info{text-align:center;color:#666;font-size:.9em}\
+    .meta{color:#666;font-size:.8em}\
+    .quote{border-left:3px solid #999;padding-left:.5em;color:#444}\
+    .text{white-space:pre-wrap}\
+    img,video{max-width:100%}";
+
+/// Exports all messages of the chat as HTML page into `dest_dir`
+/// and returns the path of the page.
+///
+/// The page contains the texts, quotes and senders of the messages,
+/// attachments are copied to the `files` subdirectory and linked from the page,
+/// images and videos are shown inline.
+/// Webxdc apps are described by their name and summary.
+/// Files of view-once messages are not exported,
+/// missing files are noted on the page.
+///
+/// `dest_dir` is created if it does not exist, it must be empty otherwise.
+pub async fn export_chat_html(
+    context: &Context,
+    chat_id: ChatId,
+    dest_dir: &Path,
+) -> Result<PathBuf> {
+    let chat = Chat::load_from_db(context, chat_id).await?;
+    fs::create_dir_all(dest_dir)
+        .await
+        .with_context(|| format!("Cannot create {}", dest_dir.display()))?;
+    ensure!(
+        fs::read_dir(dest_dir).await?.next_entry().await?.is_none(),
+        "Export directory {} is not empty",
+        dest_dir.display()
+    );
+    let files_dir = dest_dir.join(FILES_DIR);
+    fs::create_dir(&files_dir).await?;
+
+    let name = escaper::encode_minimal(chat.get_name());
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{name}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>{name}</h1>\n"
+    );
+    let items = chat::get_chat_msgs(context, chat_id).await?;
+    for item in items {
+        if let ChatItem::Message { msg_id } = item {
+            let msg = Message::load_from_db(context, msg_id).await?;
+            html += &render_message(context, &msg, &files_dir).await?;
+        }
+    }
+    html += "</body>\n</html>\n";
+
+    let path = dest_dir.join(INDEX_HTML);
+    fs::write(&path, html).await?;
+    info!(context, "Exported {chat_id} to {}.", path.display());
+    Ok(path)
+}
+
+/// Renders a message and copies its files to `files_dir`.
+async fn render_message(context: &Context, msg: &Message, files_dir: &Path) -> Result<String> {
+    let text = escaper::encode_minimal(&msg.get_text().unwrap_or_default()).to_string();
+    let timestamp = timestamp_to_str(msg.get_timestamp());
+    if msg.is_info() {
+        return Ok(format!(
+            "<p class=\"info\">{text} <span class=\"meta\">{timestamp}</span></p>\n"
+        ));
+    }
+
+    let from_id = msg.get_from_id();
+    let sender = Contact::get_by_id(context, from_id).await?;
+    let class = if from_id == ContactId::SELF {
+        "msg out"
+    } else {
+        "msg"
+    };
+    let mut html = format!(
+        "<div class=\"{class}\">\n<div class=\"meta\">{} {timestamp}</div>\n",
+        escaper::encode_minimal(&msg.get_sender_name(&sender))
+    );
+
+    if let Some(quoted_text) = msg.quoted_text() {
+        let author = match msg.quoted_message(context).await? {
+            Some(quote) => {
+                let contact = Contact::get_by_id(context, quote.get_from_id()).await?;
+                format!(
+                    "<div class=\"meta\">{}</div>",
+                    escaper::encode_minimal(&quote.get_sender_name(&contact))
+                )
+            }
+            None => String::new(),
+        };
+        html += &format!(
+            "<blockquote class=\"quote\">{author}<div class=\"text\">{}</div></blockquote>\n",
+            escaper::encode_minimal(&quoted_text)
+        );
+    }
+
+    if msg.get_viewtype() == Viewtype::Webxdc {
+        let info = msg.get_webxdc_info(context).await?;
+        html += &format!(
+            "<p><b>{}</b> {}</p>\n",
+            escaper::encode_minimal(&info.name),
+            escaper::encode_minimal(&info.summary)
+        );
+    }
+
+    if msg.is_view_once() {
+        if msg.get_viewtype().has_file() {
+            html += "<p class=\"meta\">View-once media is not exported.</p>\n";
+        }
+    } else {
+        for (i, file) in msg.get_attachments(context).iter().enumerate() {
+            html += &copy_attachment(msg, i, file, files_dir).await?;
+        }
+    }
+
+    if !text.is_empty() {
+        html += &format!("<div class=\"text\">{text}</div>\n");
+    }
+    html += "</div>\n";
+    Ok(html)
+}
+
+/// Copies the `i`-th attachment of a message to `files_dir` and returns the HTML linking it.
+///
+/// If the file is missing, e.g. because it was deleted from the blob directory,
+/// a note is returned instead.
+async fn copy_attachment(msg: &Message, i: usize, file: &Path, files_dir: &Path) -> Result<String> {
+    let file_name = match file.file_name() {
+        Some(file_name) => file_name.to_string_lossy(),
+        None => return Ok(String::new()),
+    };
+    let name = if i == 0 {
+        msg.get_filename().unwrap_or_else(|| file_name.to_string())
+    } else {
+        file_name.to_string()
+    };
+    // Prefix with the message ID, different messages may have files with the same name.
+    let dest_name = format!("{}-{i}-{file_name}", msg.get_id().to_u32());
+    match fs::copy(file, files_dir.join(&dest_name)).await {
+        Ok(_) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(format!(
+                "<p class=\"meta\">File {} is missing.</p>\n",
+                escaper::encode_minimal(&name)
+            ));
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Cannot copy {}", file.display()));
+        }
+    }
+
+    let href = format!(
+        "{FILES_DIR}/{}",
+        utf8_percent_encode(&dest_name, NON_ALPHANUMERIC)
+    );
+    let link = format!("<a href=\"{href}\">{}</a>", escaper::encode_minimal(&name));
+    Ok(match (i, msg.get_viewtype()) {
+        (0, Viewtype::Image | Viewtype::Gif | Viewtype::Sticker) => {
+            format!(
+                "<p><img src=\"{href}\" alt=\"{}\"><br>{link}</p>\n",
+                escaper::encode_attribute(&name)
+            )
+        }
+        (0, Viewtype::Video) => {
+            format!("<p><video src=\"{href}\" controls></video><br>{link}</p>\n")
+        }
+        (0, Viewtype::Audio | Viewtype::Voice) => {
+            format!("<p><audio src=\"{href}\" controls></audio><br>{link}</p>\n")
+        }
+        _ => format!("<p>{link}</p>\n"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestContextManager;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_export_chat_html() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+
+        let chat_id = alice.create_chat(&bob).await.id;
+        let sent = alice.send_text(chat_id, "Hi <Bob>").await;
+        let bob_msg = bob.recv_msg(&sent).await;
+        let mut reply = Message::new(Viewtype::Text);
+        reply.set_text(Some("Hello".to_string()));
+        reply.set_quote(&bob, Some(&bob_msg)).await?;
+        let bob_chat_id = bob_msg.chat_id;
+        bob_chat_id.accept(&bob).await?;
+        alice
+            .recv_msg(&bob.send_msg(bob_chat_id, &mut reply).await)
+            .await;
+
+        let file = alice.get_blobdir().join("notes.txt");
+        fs::write(&file, b"notes").await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        let msg_id = chat::send_msg(&alice, chat_id, &mut msg).await?;
+
+        let dest_dir = alice.get_blobdir().parent().unwrap().join("export");
+        let path = export_chat_html(&alice, chat_id, &dest_dir).await?;
+        let html = fs::read_to_string(&path).await?;
+        assert!(html.contains("Hi &lt;Bob&gt;"));
+        assert!(html.contains("<blockquote class=\"quote\">"));
+        let dest_name = format!("{}-0-notes.txt", msg_id.to_u32());
+        assert!(html.contains(&dest_name));
+        assert_eq!(
+            fs::read(dest_dir.join(FILES_DIR).join(dest_name)).await?,
+            b"notes"
+        );
+
+        // Exports do not overwrite each other.
+        assert!(export_chat_html(&alice, chat_id, &dest_dir).await.is_err());
+
+        // Links are URL-encoded, missing files are noted.
+        let file = alice.get_blobdir().join("my notes #2.txt");
+        fs::write(&file, b"more notes").await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        let msg_id = chat::send_msg(&alice, chat_id, &mut msg).await?;
+        let file = alice.get_blobdir().join("gone.txt");
+        fs::write(&file, b"gone").await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        chat::send_msg(&alice, chat_id, &mut msg).await?;
+        fs::remove_file(msg.get_file(&alice).unwrap()).await?;
+
+        let dest_dir = alice.get_blobdir().parent().unwrap().join("export2");
+        let html = fs::read_to_string(export_chat_html(&alice, chat_id, &dest_dir).await?).await?;
+        let blob = Message::load_from_db(&alice, msg_id)
+            .await?
+            .get_file(&alice)
+            .unwrap();
+        let dest_name = format!(
+            "{}-0-{}",
+            msg_id.to_u32(),
+            blob.file_name().unwrap().to_str().unwrap()
+        );
+        assert!(fs::metadata(dest_dir.join(FILES_DIR).join(&dest_name))
+            .await
+            .is_ok());
+        assert!(html.contains(&format!(
+            "href=\"{FILES_DIR}/{}\"",
+            utf8_percent_encode(&dest_name, NON_ALPHANUMERIC)
+        )));
+        assert!(html.contains("is missing."));
+        Ok(())
+    }
+}
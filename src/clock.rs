@@ -0,0 +1,78 @@
+//! # Time source.
+//!
+//! Code that waits for or compares against the current time, such as ephemeral message
+//! deletion, rate limiting and mute expiry, takes the time from the [`Clock`] of the context
+//! instead of asking the system directly. This way tests can move time forward
+//! deterministically instead of sleeping.
+
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of the current time.
+pub(crate) trait Clock: fmt::Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+
+    /// Returns the current time as a unix timestamp in seconds.
+    fn unix_time(&self) -> i64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64
+    }
+}
+
+/// Clock returning the system time.
+#[derive(Debug, Default)]
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock which runs together with the system time, but can be moved forward by tests.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct TestClock {
+    /// Offset added to the system time, in milliseconds.
+    offset_ms: std::sync::atomic::AtomicU64,
+}
+
+#[cfg(test)]
+impl TestClock {
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let ms = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        self.offset_ms
+            .fetch_add(ms, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn offset(&self) -> Duration {
+        Duration::from_millis(self.offset_ms.load(std::sync::atomic::Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+impl Clock for TestClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now() + self.offset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::time;
+
+    #[test]
+    fn test_test_clock() {
+        let clock = TestClock::default();
+        assert!((clock.unix_time() - time()).abs() <= 1);
+
+        clock.advance(Duration::from_secs(3600));
+        assert!((clock.unix_time() - time() - 3600).abs() <= 1);
+        assert!(clock.now() > SystemTime::now() + Duration::from_secs(3599));
+    }
+}
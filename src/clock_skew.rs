@@ -0,0 +1,184 @@
+//! # Detection of clock skew.
+//!
+//! A wrong device clock breaks ephemeral timers and the order of messages,
+//! so the device time is compared to the time of the server
+//! taken from the topmost `Received` header of fetched messages:
+//!
+//! - A message cannot be received by the server after it is fetched,
+//!   so the `Received` time minus the device time at fetching is a lower bound of the skew.
+//! - A message sent from this device cannot be received by the server before it was created,
+//!   so the `Received` time of its copy minus its `Date` is an upper bound of the skew.
+//!
+//! The estimated skew, server time minus device time, is stored in [`Config::ClockSkew`]
+//! and only changed if a new bound contradicts it.
+//! As a clock which is fixed can only be noticed from the upper bound,
+//! a wrong estimate of a clock running behind lasts until a message sent from this device
+//! is fetched again, e.g. with [`Config::BccSelf`].
+//!
+//! If the skew exceeds [`CLOCK_SKEW_THRESHOLD`], [`EventType::ClockSkew`] is emitted
+//! and a warning is added to the device chat.
+//! With [`Config::ClockSkewCorrection`], the estimated skew is added to the timestamps
+//! of messages created by core.
+
+use std::sync::atomic::Ordering;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+
+use crate::chat::add_device_msg_with_importance;
+use crate::config::Config;
+use crate::context::Context;
+use crate::events::EventType;
+use crate::headerdef::HeaderDef;
+use crate::message::{Message, Viewtype};
+use crate::mimeparser::MimeMessage;
+use crate::stock_str;
+use crate::tools::time;
+
+/// Skew in seconds above which the user is warned and timestamps are corrected.
+pub const CLOCK_SKEW_THRESHOLD: i64 = 5 * 60;
+
+impl Context {
+    /// Returns the estimated difference between the server time and the device time in seconds,
+    /// positive if the device clock is behind.
+    ///
+    /// Returns 0 if the skew is unknown, see [`crate::clock_skew`].
+    pub async fn get_clock_skew(&self) -> Result<i64> {
+        self.get_config_i64(Config::ClockSkew).await
+    }
+}
+
+/// Returns the offset in seconds to add to timestamps created by core.
+pub(crate) fn get_correction(context: &Context) -> i64 {
+    context.clock_correction.load(Ordering::Relaxed)
+}
+
+/// Updates the correction offset from the configuration.
+///
+/// Must be called when the database is opened
+/// and when [`Config::ClockSkew`] or [`Config::ClockSkewCorrection`] change.
+pub(crate) async fn update_correction(context: &Context) -> Result<()> {
+    let skew = context.get_config_i64(Config::ClockSkew).await?;
+    let correction = if context.get_config_bool(Config::ClockSkewCorrection).await?
+        && skew.abs() >= CLOCK_SKEW_THRESHOLD
+    {
+        skew
+    } else {
+        0
+    };
+    context
+        .clock_correction
+        .store(correction, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Updates the estimated skew from bounds observed when fetching a message.
+pub(crate) async fn observe_skew(
+    context: &Context,
+    lower_bound: Option<i64>,
+    upper_bound: Option<i64>,
+) -> Result<()> {
+    let old_skew = context.get_clock_skew().await?;
+    let mut skew = old_skew;
+    if let Some(lower_bound) = lower_bound {
+        skew = skew.max(lower_bound);
+    }
+    if let Some(upper_bound) = upper_bound {
+        skew = skew.min(upper_bound);
+    }
+    if skew == old_skew {
+        return Ok(());
+    }
+    context
+        .set_config(Config::ClockSkew, Some(&skew.to_string()))
+        .await?;
+
+    if skew.abs() >= CLOCK_SKEW_THRESHOLD && (skew - old_skew).abs() >= CLOCK_SKEW_THRESHOLD {
+        warn!(
+            context,
+            "Device clock is {skew} seconds off the server time."
+        );
+        context.emit_event(EventType::ClockSkew { skew });
+        add_warning(context, skew).await;
+    }
+    Ok(())
+}
+
+/// Updates the estimated skew from a fetched message.
+///
+/// `sent_here` tells whether the message was sent from this device,
+/// only then its `Date` header was set by the device clock.
+pub(crate) async fn observe_received_msg(
+    context: &Context,
+    mime_parser: &MimeMessage,
+    sent_here: bool,
+) -> Result<()> {
+    let received_timestamp = match mime_parser.received_timestamp {
+        Some(received_timestamp) => received_timestamp,
+        None => return Ok(()),
+    };
+    let upper_bound = if sent_here {
+        mime_parser
+            .get_header(HeaderDef::Date)
+            .and_then(|value| mailparse::dateparse(value).ok())
+            .map(|sent_timestamp| received_timestamp - (sent_timestamp - get_correction(context)))
+    } else {
+        None
+    };
+    observe_skew(context, Some(received_timestamp - time()), upper_bound).await
+}
+
+/// Adds a warning about the clock skew to the device chat, at most once a day.
+async fn add_warning(context: &Context, skew: i64) {
+    let mut msg = Message::new(Viewtype::Text);
+    msg.text = Some(stock_str::clock_skew_msg_body(context, skew.abs() / 60).await);
+    if let Some(timestamp) = NaiveDateTime::from_timestamp_opt(time(), 0) {
+        let label = format!("clock-skew-warning-{}", timestamp.format("%Y-%m-%d"));
+        add_device_msg_with_importance(context, Some(&label), Some(&mut msg), true)
+            .await
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::ChatId;
+    use crate::contact::ContactId;
+    use crate::test_utils::TestContext;
+    use crate::tools::create_smeared_timestamp;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_clock_skew() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        assert_eq!(t.get_clock_skew().await?, 0);
+
+        // Bounds consistent with the estimate do not change it.
+        observe_skew(&t, Some(-30), Some(10)).await?;
+        assert_eq!(t.get_clock_skew().await?, 0);
+
+        // The server received a message one hour after the device fetched it.
+        observe_skew(&t, Some(3600), None).await?;
+        assert_eq!(t.get_clock_skew().await?, 3600);
+        let event = t
+            .evtracker
+            .get_matching(|evt| matches!(evt, EventType::ClockSkew { .. }))
+            .await;
+        assert!(matches!(event, EventType::ClockSkew { skew: 3600 }));
+        let device_chat_id = ChatId::get_for_contact(&t, ContactId::DEVICE).await?;
+        let msg = t.get_last_msg_in(device_chat_id).await;
+        assert!(msg.get_text().unwrap().contains("60 minutes"));
+
+        // No correction unless enabled.
+        assert_eq!(get_correction(&t), 0);
+        t.set_config_bool(Config::ClockSkewCorrection, true).await?;
+        assert_eq!(get_correction(&t), 3600);
+        assert!(create_smeared_timestamp(&t) >= time() + 3600);
+
+        // The clock was fixed, a message sent from this device shows it.
+        observe_skew(&t, None, Some(5)).await?;
+        assert_eq!(t.get_clock_skew().await?, 5);
+        assert_eq!(get_correction(&t), 0);
+        Ok(())
+    }
+}
@@ -3,16 +3,19 @@
 use std::env;
 use std::str::FromStr;
 
-use anyhow::{ensure, Context as _, Result};
+use anyhow::{anyhow, ensure, Result};
 use strum::{EnumProperty, IntoEnumIterator};
 use strum_macros::{AsRefStr, Display, EnumIter, EnumProperty, EnumString};
 
 use crate::blob::BlobObject;
+use crate::clock_skew;
 use crate::constants::DC_VERSION_STR;
 use crate::contact::addr_cmp;
 use crate::context::Context;
+use crate::error_code::ErrorCode;
 use crate::events::EventType;
 use crate::mimefactory::RECOMMENDED_FILE_SIZE;
+use crate::notification;
 use crate::provider::{get_provider_by_id, Provider};
 use crate::tools::{get_abs_path, improve_single_line_input, EmailAddress};
 
@@ -119,6 +122,14 @@ pub enum Config {
     #[strum(props(default = "1"))]
     MdnsEnabled,
 
+    /// True if contacts may see when we were last seen.
+    ///
+    /// If disabled, outgoing messages ask the recipients not to use them for last-seen tracking,
+    /// read receipts do not reveal when messages were read
+    /// and, in return, the last-seen status of contacts is not shown either.
+    #[strum(props(default = "1"))]
+    ShareLastSeen,
+
     /// True if "Sent" folder should be watched for changes.
     #[strum(props(default = "0"))]
     SentboxWatch,
@@ -134,6 +145,24 @@ pub enum Config {
     #[strum(props(default = "0"))]
     OnlyFetchMvbox,
 
+    /// Name of the folder for chat messages to use instead of the "DeltaChat" folder,
+    /// unset to use the default.
+    ///
+    /// Applied when the account is configured.
+    MvboxFolder,
+
+    /// Name of the "Sent" folder to use instead of detecting it,
+    /// unset to detect the folder.
+    ///
+    /// Applied when the account is configured and on the next folder scan.
+    SentboxFolder,
+
+    /// Name of the "Trash" folder to use instead of detecting it,
+    /// unset to detect the folder.
+    ///
+    /// Applied when the account is configured and on the next folder scan.
+    TrashFolder,
+
     /// Whether to show classic emails or only chat messages.
     #[strum(props(default = "2"))] // also change ShowEmails.default() on changes
     ShowEmails,
@@ -142,6 +171,39 @@ pub enum Config {
     #[strum(props(default = "0"))] // also change MediaQuality.default() on changes
     MediaQuality,
 
+    /// Maximum size of outgoing messages in bytes.
+    ///
+    /// Overrides the limit of the provider database, 0 or unset to use it.
+    MaxMsgSize,
+
+    /// What to do with attachments exceeding the maximum message size.
+    #[strum(props(default = "0"))] // also change OversizedAttachment.default() on changes
+    OversizedAttachment,
+
+    /// True if files may be offered for direct download by the recipients,
+    /// see [`crate::peer_transfer::send_file_p2p`].
+    #[strum(props(default = "0"))]
+    P2pFileTransfer,
+
+    /// True if metadata as GPS location and camera model
+    /// should be removed from outgoing JPEG, PNG and WebP images.
+    ///
+    /// Images whose metadata cannot be removed are not sent.
+    #[strum(props(default = "1"))]
+    StripExif,
+
+    /// Space-separated list of EXIF metadata retained when `StripExif` is enabled.
+    ///
+    /// Possible values are `orientation` and `date`.
+    #[strum(props(default = "orientation"))]
+    ExifKeep,
+
+    /// True if outgoing voice messages should be re-encoded to Opus with normalized loudness.
+    ///
+    /// Has no effect unless the core is built with the `recode-voice` feature.
+    #[strum(props(default = "0"))]
+    RecodeVoice,
+
     /// If set to "1", on the first time `start_io()` is called after configuring,
     /// the newest existing messages are fetched.
     /// Existing recipients are added to the contact database regardless of this setting.
@@ -153,6 +215,26 @@ pub enum Config {
     #[strum(props(default = "1"))]
     FetchedExistingMsgs,
 
+    /// Maximum number of existing messages fetched from each folder
+    /// if `FetchExistingMsgs` is set.
+    ///
+    /// Bots do not fetch existing messages unless this is set explicitly.
+    #[strum(props(default = "100"))]
+    FetchExistingMsgsCount,
+
+    /// Space-separated list of folders existing messages are fetched from.
+    ///
+    /// Possible values are `mvbox`, `inbox` and `sent`.
+    #[strum(props(default = "mvbox inbox sent"))]
+    FetchExistingMsgsFolders,
+
+    /// Messages which arrived on the server more than this number of seconds ago
+    /// are never downloaded, including messages found when scanning folders.
+    ///
+    /// 0 = no limit.
+    #[strum(props(default = "0"))]
+    FetchMaxAge,
+
     /// Type of the OpenPGP key to generate.
     #[strum(props(default = "0"))]
     KeyGenType,
@@ -237,12 +319,31 @@ pub enum Config {
     /// Configured "Trash" folder.
     ConfiguredTrashFolder,
 
+    /// Configured "Archive" folder.
+    ConfiguredArchiveFolder,
+
     /// Unix timestamp of the last successful configuration.
     ConfiguredTimestamp,
 
     /// ID of the configured provider from the provider database.
     ConfiguredProvider,
 
+    /// True if the account is on a chatmail relay.
+    ///
+    /// Chatmail relays only deliver end-to-end encrypted chat messages
+    /// and do not exchange messages with classic email servers.
+    /// Determined during configuration,
+    /// changes the defaults of `mvbox_move` and `delete_server_after`.
+    #[strum(props(default = "0"))]
+    IsChatmail,
+
+    /// True if IMAP folders are labels, like on Gmail.
+    ///
+    /// Determined during configuration from the `X-GM-EXT-1` capability.
+    /// Providers can also be marked in the provider database.
+    #[strum(props(default = "0"))]
+    ImapLabels,
+
     /// True if account is configured.
     Configured,
 
@@ -265,6 +366,12 @@ pub enum Config {
     /// True if it is a bot account.
     Bot,
 
+    /// Only accept messages from verified contacts and only send encrypted messages.
+    ///
+    /// Messages from unverified senders end up in contact requests.
+    #[strum(props(default = "0"))]
+    OnlyAcceptVerified,
+
     /// Whether we send a warning if the password is wrong (set to false when we send a warning
     /// because we do not want to send a second warning)
     #[strum(props(default = "0"))]
@@ -275,12 +382,70 @@ pub enum Config {
     /// Unset, when quota falls below minimal warning threshold again.
     QuotaExceeding,
 
+    /// Estimated difference between the server time and the device time in seconds,
+    /// see [`crate::clock_skew`].
+    #[strum(props(default = "0"))]
+    ClockSkew,
+
+    /// Whether to add the estimated clock skew to the timestamps of created messages,
+    /// see [`crate::clock_skew`].
+    #[strum(props(default = "0"))]
+    ClockSkewCorrection,
+
     /// address to webrtc instance to use for videochats
     WebrtcInstance,
 
+    /// Additional webrtc instances to choose from for videochats, one per line,
+    /// see [`crate::chat::get_videochat_instances`].
+    WebrtcInstances,
+
+    /// HTTPS endpoint used to search GIFs, see [`crate::gif_search`].
+    GifSearchEndpoint,
+
+    /// API key passed to the GIF search endpoint.
+    GifSearchApiKey,
+
+    /// Approximate maximum number of characters of message summaries, 0 for no limit.
+    #[strum(props(default = "0"))]
+    SummaryMaxChars,
+
+    /// Whether to prefix summaries of media messages with an emoji, e.g. "📷 Image".
+    #[strum(props(default = "0"))]
+    SummaryEmoji,
+
+    /// Whether summaries of group messages are prefixed with the sender name.
+    #[strum(props(default = "1"))]
+    SummaryShowSender,
+
     /// Timestamp of the last time housekeeping was run
     LastHousekeeping,
 
+    /// Random identifier of this device in the device list stored on the server.
+    ///
+    /// Generated on first use and reset on backup import,
+    /// so devices set up from the same backup get different identifiers.
+    DeviceId,
+
+    /// Name of this device shown in the device list of other devices, e.g. "Desktop".
+    DeviceName,
+
+    /// Timestamp of the last update of the device list stored on the server.
+    LastDeviceListUpdate,
+
+    /// Push notification token of this device, see [`Context::set_push_device_token`].
+    PushDeviceToken,
+
+    /// Push notification token which was last registered on the server.
+    RegisteredPushDeviceToken,
+
+    /// Interval in minutes to poll the server instead of using IMAP IDLE.
+    ///
+    /// In polling mode the connection is closed after fetching all folders
+    /// and the device sleeps until the next poll or an interrupt.
+    /// 0 = use IDLE (default).
+    #[strum(props(default = "0"))]
+    PollInterval,
+
     /// To how many seconds to debounce scan_all_folders. Used mainly in tests, to disable debouncing completely.
     #[strum(props(default = "60"))]
     ScanAllFoldersDebounceSecs,
@@ -304,12 +469,93 @@ pub enum Config {
     /// Make all outgoing messages with Autocrypt header "multipart/signed".
     SignUnencrypted,
 
+    /// Subject of the unencrypted outer part of encrypted messages,
+    /// see [`crate::constants::EncryptedSubject`].
+    #[strum(props(default = "0"))] // also change EncryptedSubject.default() on changes
+    EncryptedSubject,
+
+    /// Notify about mentions and replies in muted chats,
+    /// see [`crate::notification::NotificationIntent::Mention`].
+    /// Synchronized to other devices.
+    #[strum(props(default = "1"))]
+    NotifyMutedMentions,
+
+    /// Notify silently about messages from bots,
+    /// 0 = do not notify about messages from bots at all.
+    /// Synchronized to other devices.
+    #[strum(props(default = "1"))]
+    NotifyBots,
+
+    /// Quiet hours in local time as `HH:MM-HH:MM`, e.g. `22:00-07:00`.
+    /// During quiet hours, notifications are suppressed
+    /// and recorded for a summary, see [`crate::notification::get_suppressed_notifications`].
+    /// Unset or empty to disable quiet hours.
+    /// Synchronized to other devices.
+    QuietHours,
+
+    /// Notify about mentions and replies during quiet hours.
+    /// Synchronized to other devices.
+    #[strum(props(default = "1"))]
+    QuietHoursAllowMentions,
+
+    /// Notify about messages from verified contacts during quiet hours.
+    /// Synchronized to other devices.
+    #[strum(props(default = "1"))]
+    QuietHoursAllowVerified,
+
+    /// Add a device message after the quiet hours summarizing the messages
+    /// whose notifications were suppressed during them.
+    #[strum(props(default = "0"))]
+    NotificationDigest,
+
+    /// Address to send reports of spam and abuse to, see [`crate::report`].
+    /// Unset or empty to use `abuse@` the domain of the configured address.
+    AbuseAddr,
+
     /// Let the core save all events to the database.
     /// This value is used internally to remember the MsgId of the logging xdc
     #[strum(props(default = "0"))]
     DebugLogging,
 }
 
+impl Config {
+    /// Returns true if the value is synchronized to other devices.
+    pub(crate) fn is_synced(self) -> bool {
+        matches!(
+            self,
+            Config::NotifyMutedMentions
+                | Config::NotifyBots
+                | Config::QuietHours
+                | Config::QuietHoursAllowMentions
+                | Config::QuietHoursAllowVerified
+        )
+    }
+
+    /// Returns an error if `value` received from another device is invalid for the key.
+    pub(crate) fn check_synced_value(self, value: Option<&str>) -> Result<()> {
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        match self {
+            Config::NotifyMutedMentions
+            | Config::NotifyBots
+            | Config::QuietHoursAllowMentions
+            | Config::QuietHoursAllowVerified => {
+                ensure!(matches!(value, "0" | "1"), "invalid boolean {value:?}");
+            }
+            Config::QuietHours => {
+                ensure!(
+                    value.is_empty() || notification::parse_quiet_hours(value).is_some(),
+                    "invalid quiet hours {value:?}"
+                );
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
 impl Context {
     /// Returns true if configuration value is set for the given key.
     pub async fn config_exists(&self, key: Config) -> Result<bool> {
@@ -338,9 +584,18 @@ pub async fn get_config(&self, key: Config) -> Result<Option<String>> {
             return Ok(value);
         }
 
-        // Default values
+        // Default values.
+        // Other keys are read from the database directly, `get_config()` must not recurse.
+        let is_chatmail = || self.sql.get_raw_config_bool(Config::IsChatmail.as_ref());
+        let bcc_self = || self.sql.get_raw_config_int(Config::BccSelf.as_ref());
         match key {
             Config::ConfiguredInboxFolder => Ok(Some("INBOX".to_owned())),
+            // Chatmail relays only carry chat messages, there is nothing to move them away from.
+            Config::MvboxMove if is_chatmail().await? => Ok(Some("0".to_owned())),
+            // Without other devices, messages on chatmail relays are only needed for delivery.
+            Config::DeleteServerAfter if is_chatmail().await? && bcc_self().await? == Some(0) => {
+                Ok(Some("1".to_owned()))
+            }
             _ => Ok(key.get_str("default").map(|s| s.to_string())),
         }
     }
@@ -378,6 +633,11 @@ pub async fn get_config_bool(&self, key: Config) -> Result<bool> {
         Ok(self.get_config_bool_opt(key).await?.unwrap_or_default())
     }
 
+    /// Returns true if the account is on a chatmail relay.
+    pub async fn is_chatmail(&self) -> Result<bool> {
+        self.get_config_bool(Config::IsChatmail).await
+    }
+
     /// Returns true if movebox ("DeltaChat" folder) should be watched.
     pub(crate) async fn should_watch_mvbox(&self) -> Result<bool> {
         Ok(self.get_config_bool(Config::MvboxMove).await?
@@ -407,6 +667,19 @@ pub async fn get_configured_provider(&self) -> Result<Option<&'static Provider>>
         Ok(None)
     }
 
+    /// Returns the maximum size of outgoing messages in bytes, if known.
+    ///
+    /// The `max_msg_size` config overrides the limit of the configured provider.
+    pub async fn get_max_msg_size(&self) -> Result<Option<u64>> {
+        match self.get_config_parsed::<u64>(Config::MaxMsgSize).await? {
+            Some(limit) if limit > 0 => Ok(Some(limit)),
+            _ => Ok(self
+                .get_configured_provider()
+                .await?
+                .and_then(|provider| provider.opt.max_msg_size)),
+        }
+    }
+
     /// Gets configured "delete_device_after" value.
     ///
     /// `None` means never delete the message, `Some(x)` means delete
@@ -452,6 +725,25 @@ pub async fn set_config(&self, key: Config, value: Option<&str>) -> Result<()> {
                     .set_raw_config(key.as_ref(), value.as_deref())
                     .await?;
             }
+            Config::QuietHours => {
+                let value = value.map(str::trim).filter(|value| !value.is_empty());
+                if let Some(value) = value {
+                    ensure!(
+                        notification::parse_quiet_hours(value).is_some(),
+                        "Invalid quiet hours {value:?}, expected HH:MM-HH:MM"
+                    );
+                }
+                self.sql.set_raw_config(key.as_ref(), value).await?;
+                self.sync_config(key, value).await?;
+            }
+            Config::ClockSkew | Config::ClockSkewCorrection => {
+                self.sql.set_raw_config(key.as_ref(), value).await?;
+                clock_skew::update_correction(self).await?;
+            }
+            _ if key.is_synced() => {
+                self.sql.set_raw_config(key.as_ref(), value).await?;
+                self.sync_config(key, value).await?;
+            }
             _ => {
                 self.sql.set_raw_config(key.as_ref(), value).await?;
             }
@@ -558,7 +850,7 @@ pub(crate) async fn get_secondary_self_addrs(&self) -> Result<Vec<String>> {
     pub async fn get_primary_self_addr(&self) -> Result<String> {
         self.get_config(Config::ConfiguredAddr)
             .await?
-            .context("No self addr configured")
+            .ok_or_else(|| ErrorCode::Configuration.wrap(anyhow!("No self addr configured")))
     }
 }
 
@@ -646,6 +938,32 @@ async fn test_set_config_bool() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_chatmail_defaults() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        assert!(!t.is_chatmail().await?);
+        assert!(t.get_config_bool(Config::MvboxMove).await?);
+        assert_eq!(t.get_config_delete_server_after().await?, None);
+
+        t.set_config_bool(Config::IsChatmail, true).await?;
+        assert!(t.is_chatmail().await?);
+        assert!(!t.get_config_bool(Config::MvboxMove).await?);
+        assert!(!t.should_watch_mvbox().await?);
+
+        // Messages are kept on the server for other devices.
+        t.set_config_bool(Config::BccSelf, true).await?;
+        assert_eq!(t.get_config_delete_server_after().await?, None);
+        t.set_config_bool(Config::BccSelf, false).await?;
+        assert_eq!(t.get_config_delete_server_after().await?, Some(0));
+
+        // Explicitly set values are not overridden.
+        t.set_config_bool(Config::MvboxMove, true).await?;
+        t.set_config(Config::DeleteServerAfter, Some("0")).await?;
+        assert!(t.get_config_bool(Config::MvboxMove).await?);
+        assert_eq!(t.get_config_delete_server_after().await?, None);
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_self_addrs() -> Result<()> {
         let alice = TestContext::new_alice().await;
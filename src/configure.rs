@@ -5,7 +5,7 @@
 mod read_url;
 mod server_params;
 
-use anyhow::{bail, ensure, Context as _, Result};
+use anyhow::{anyhow, ensure, Context as _, Result};
 use auto_mozilla::moz_autoconfigure;
 use auto_outlook::outlk_autodiscover;
 use futures::FutureExt;
@@ -17,11 +17,12 @@
 use crate::config::Config;
 use crate::contact::addr_cmp;
 use crate::context::Context;
+use crate::error_code::ErrorCode;
 use crate::imap::Imap;
 use crate::job;
 use crate::log::LogExt;
 use crate::login_param::{CertificateChecks, LoginParam, ServerLoginParam};
-use crate::message::{Message, Viewtype};
+use crate::message::{self, Message, MsgId, Viewtype};
 use crate::oauth2::get_oauth2_addr;
 use crate::provider::{Protocol, Socket, UsernamePattern};
 use crate::scheduler::InterruptInfo;
@@ -107,9 +108,14 @@ async fn inner_configure(&self) -> Result<()> {
         let success = configure(self, &mut param).await;
         self.set_config(Config::NotifyAboutWrongPw, None).await?;
 
-        on_configure_completed(self, param, old_addr).await?;
+        on_configure_completed(self, param, old_addr.clone()).await?;
 
         success?;
+        requeue_smtp_msgs(self, old_addr.as_deref())
+            .await
+            .context("Cannot requeue messages")
+            .log_err(self)
+            .ok();
         self.set_config(Config::NotifyAboutWrongPw, Some("1"))
             .await?;
         Ok(())
@@ -166,6 +172,86 @@ async fn on_configure_completed(
     Ok(())
 }
 
+/// Numbers of queued messages handled on reconfiguration, see [`requeue_smtp_msgs`].
+#[derive(Debug, Default, PartialEq, Eq)]
+struct RequeueReport {
+    /// Messages left in the queue unchanged.
+    kept: usize,
+
+    /// Messages rendered again to be sent from the new address.
+    rerendered: usize,
+
+    /// Messages which could not be rendered again and were marked as failed.
+    dropped: usize,
+}
+
+/// Keeps the messages queued in the `smtp` table sendable after the account was reconfigured.
+///
+/// Retry counts are reset as sending may have failed because of the old configuration.
+/// Queued messages are already rendered, so if the address changed,
+/// they are rendered again to be sent from the new address.
+/// Messages which cannot be rendered anymore are marked as failed.
+/// Rendered and failed messages are reported in the device chat.
+async fn requeue_smtp_msgs(context: &Context, old_addr: Option<&str>) -> Result<RequeueReport> {
+    context.sql.execute("UPDATE smtp SET retries=0", ()).await?;
+    let msg_ids = context
+        .sql
+        .query_map(
+            "SELECT msg_id FROM smtp GROUP BY msg_id ORDER BY MIN(id)",
+            (),
+            |row| row.get::<_, MsgId>(0),
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await?;
+
+    let mut report = RequeueReport::default();
+    let new_addr = context.get_primary_self_addr().await?;
+    if old_addr.map_or(true, |old_addr| addr_cmp(old_addr, &new_addr)) {
+        report.kept = msg_ids.len();
+        return Ok(report);
+    }
+
+    for msg_id in msg_ids {
+        context
+            .sql
+            .execute("DELETE FROM smtp WHERE msg_id=?", (msg_id,))
+            .await?;
+        if !message::exists(context, msg_id).await? {
+            // Sending of the message was cancelled by deleting it.
+            continue;
+        }
+        match chat::create_send_msg_job(context, msg_id).await {
+            Ok(_) => report.rerendered += 1,
+            Err(err) => {
+                warn!(
+                    context,
+                    "Cannot render queued message {msg_id} again: {err:#}."
+                );
+                message::set_msg_failed(context, msg_id, &format!("{err:#}")).await;
+                report.dropped += 1;
+            }
+        }
+    }
+    info!(
+        context,
+        "Queued messages after changing the address to {new_addr}: {report:?}."
+    );
+
+    let mut lines = Vec::new();
+    if report.rerendered > 0 {
+        lines.push(stock_str::requeued_msgs(context, report.rerendered, &new_addr).await);
+    }
+    if report.dropped > 0 {
+        lines.push(stock_str::requeued_msgs_failed(context, report.dropped).await);
+    }
+    if !lines.is_empty() {
+        let mut msg = Message::new(Viewtype::Text);
+        msg.text = Some(lines.join("\n\n"));
+        chat::add_device_msg(context, None, Some(&mut msg)).await?;
+    }
+    Ok(report)
+}
+
 async fn configure(ctx: &Context, param: &mut LoginParam) -> Result<()> {
     progress!(ctx, 1);
 
@@ -425,7 +511,7 @@ async fn configure(ctx: &Context, param: &mut LoginParam) -> Result<()> {
     }
     let mut imap = match imap {
         Some(imap) => imap,
-        None => bail!(nicer_configuration_error(ctx, errors).await),
+        None => return Err(configuration_error(ctx, errors).await),
     };
 
     progress!(ctx, 850);
@@ -436,12 +522,26 @@ async fn configure(ctx: &Context, param: &mut LoginParam) -> Result<()> {
             param.smtp = smtp_param;
         }
         Err(errors) => {
-            bail!(nicer_configuration_error(ctx, errors).await);
+            return Err(configuration_error(ctx, errors).await);
         }
     }
 
     progress!(ctx, 900);
 
+    let is_chatmail = imap
+        .session
+        .as_ref()
+        .map_or(false, |session| session.is_chatmail());
+    if is_chatmail {
+        info!(ctx, "Server is a chatmail relay.");
+    }
+    ctx.set_config_bool(Config::IsChatmail, is_chatmail).await?;
+    let has_labels = imap
+        .session
+        .as_ref()
+        .map_or(false, |session| session.has_labels());
+    ctx.set_config_bool(Config::ImapLabels, has_labels).await?;
+
     let create_mvbox = ctx.should_watch_mvbox().await?;
 
     imap.configure_folders(ctx, create_mvbox).await?;
@@ -590,6 +690,7 @@ async fn try_imap_one_param(
             return Err(ConfigurationError {
                 config: inf,
                 msg: format!("{err:#}"),
+                code: ErrorCode::of(&err),
             });
         }
         Ok(imap) => imap,
@@ -601,6 +702,7 @@ async fn try_imap_one_param(
             Err(ConfigurationError {
                 config: inf,
                 msg: format!("{err:#}"),
+                code: ErrorCode::of(&err),
             })
         }
         Ok(()) => {
@@ -642,6 +744,7 @@ async fn try_smtp_one_param(
         Err(ConfigurationError {
             config: inf,
             msg: format!("{err:#}"),
+            code: ErrorCode::of(&err),
         })
     } else {
         info!(context, "success: {}", inf);
@@ -659,6 +762,40 @@ pub struct ConfigurationError {
 
     /// Error message.
     msg: String,
+
+    /// Code of the error, see [`ErrorCode::of`].
+    code: Option<ErrorCode>,
+}
+
+impl ConfigurationError {
+    /// Returns true if the server name could not be resolved.
+    fn is_resolve_error(&self) -> bool {
+        let msg = self.msg.to_lowercase();
+        msg.contains("could not resolve")
+            || msg.contains("no dns resolution results")
+            || msg.contains("temporary failure in name resolution")
+            || msg.contains("name or service not known")
+            || msg.contains("failed to lookup address information")
+    }
+}
+
+/// Returns the error of a failed configuration.
+///
+/// It is coded as [`ErrorCode::Network`] if no server could be reached
+/// and as [`ErrorCode::Auth`] if a server rejected the credentials.
+async fn configuration_error(context: &Context, errors: Vec<ConfigurationError>) -> anyhow::Error {
+    let code = if !errors.is_empty()
+        && errors
+            .iter()
+            .all(|e| e.code == Some(ErrorCode::Network) || e.is_resolve_error())
+    {
+        ErrorCode::Network
+    } else if errors.iter().any(|e| e.code == Some(ErrorCode::Auth)) {
+        ErrorCode::Auth
+    } else {
+        ErrorCode::Configuration
+    };
+    code.wrap(anyhow!(nicer_configuration_error(context, errors).await))
 }
 
 async fn nicer_configuration_error(context: &Context, errors: Vec<ConfigurationError>) -> String {
@@ -670,17 +807,7 @@ async fn nicer_configuration_error(context: &Context, errors: Vec<ConfigurationE
         return "no error".to_string();
     };
 
-    if errors.iter().all(|e| {
-        e.msg.to_lowercase().contains("could not resolve")
-            || e.msg.to_lowercase().contains("no dns resolution results")
-            || e.msg
-                .to_lowercase()
-                .contains("temporary failure in name resolution")
-            || e.msg.to_lowercase().contains("name or service not known")
-            || e.msg
-                .to_lowercase()
-                .contains("failed to lookup address information")
-    }) {
+    if errors.iter().all(|e| e.is_resolve_error()) {
         return stock_str::error_no_network(context).await;
     }
 
@@ -718,7 +845,11 @@ pub enum Error {
 mod tests {
     #![allow(clippy::indexing_slicing)]
 
-    use crate::config::Config;
+    use super::*;
+    use crate::chat::ChatId;
+    use crate::contact::{Contact, ContactId};
+    use crate::message::MessageState;
+    use crate::param::Param;
     use crate::test_utils::TestContext;
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -730,4 +861,63 @@ async fn test_no_panic_on_bad_credentials() {
         t.set_config(Config::MailPw, Some("123456")).await.unwrap();
         assert!(t.configure().await.is_err());
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_requeue_smtp_msgs() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob_id = Contact::create(&alice, "", "bob@example.net").await?;
+        let bob_chat_id = ChatId::create_for_contact(&alice, bob_id).await?;
+        let claire_id = Contact::create(&alice, "", "claire@example.com").await?;
+        let claire_chat_id = ChatId::create_for_contact(&alice, claire_id).await?;
+
+        let msg_id = chat::send_text_msg(&alice, bob_chat_id, "hi bob".to_string()).await?;
+        let failing_msg_id =
+            chat::send_text_msg(&alice, claire_chat_id, "hi claire".to_string()).await?;
+        // Rendering the message again fails as there is no key of Claire.
+        let mut msg = Message::load_from_db(&alice, failing_msg_id).await?;
+        msg.param.set_int(Param::GuaranteeE2ee, 1);
+        msg.update_param(&alice).await?;
+        alice.sql.execute("UPDATE smtp SET retries=3", ()).await?;
+
+        // Only the password changed.
+        let report = requeue_smtp_msgs(&alice, Some("alice@example.org")).await?;
+        assert_eq!(
+            report,
+            RequeueReport {
+                kept: 2,
+                rerendered: 0,
+                dropped: 0
+            }
+        );
+        let retries: usize = alice.sql.count("SELECT SUM(retries) FROM smtp", ()).await?;
+        assert_eq!(retries, 0);
+
+        // The address changed.
+        alice.set_primary_self_addr("alice@example.xyz").await?;
+        let report = requeue_smtp_msgs(&alice, Some("alice@example.org")).await?;
+        assert_eq!(
+            report,
+            RequeueReport {
+                kept: 0,
+                rerendered: 1,
+                dropped: 1
+            }
+        );
+        let msg = Message::load_from_db(&alice, failing_msg_id).await?;
+        assert_eq!(msg.get_state(), MessageState::OutFailed);
+
+        let sent = alice.pop_sent_msg().await;
+        assert_eq!(sent.sender_msg_id, msg_id);
+        assert!(sent.payload().contains("alice@example.xyz"));
+        assert!(!sent.payload().contains("alice@example.org"));
+        assert!(alice
+            .pop_sent_msg_opt(std::time::Duration::ZERO)
+            .await
+            .is_none());
+
+        let device_chat_id = ChatId::get_for_contact(&alice, ContactId::DEVICE).await?;
+        let msg = alice.get_last_msg_in(device_chat_id).await;
+        assert!(msg.get_text().unwrap().contains("alice@example.xyz"));
+        Ok(())
+    }
 }
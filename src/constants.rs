@@ -54,6 +54,37 @@ pub enum MediaQuality {
     Worse = 1,
 }
 
+/// Handling of attachments exceeding the maximum message size of the provider.
+#[derive(
+    Debug, Default, Display, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive, FromSql, ToSql,
+)]
+#[repr(u8)]
+pub enum OversizedAttachment {
+    /// Scale images down until they fit, refuse to send other attachments.
+    #[default] // also change Config.OversizedAttachment props(default) on changes
+    Downscale = 0,
+
+    /// Refuse to send the attachment without modifying it.
+    Fail = 1,
+}
+
+/// Subject of the unencrypted outer part of encrypted messages.
+#[derive(
+    Debug, Default, Display, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive, FromSql, ToSql,
+)]
+#[repr(u8)]
+pub enum EncryptedSubject {
+    /// Hide the subject, the outer subject is "...".
+    #[default] // also change Config.EncryptedSubject props(default) on changes
+    Hidden = 0,
+
+    /// Use the chat name as outer subject.
+    ChatName = 1,
+
+    /// Use the real subject as outer subject, e.g. for server-side filtering.
+    Plain = 2,
+}
+
 /// Type of the key to generate.
 #[derive(
     Debug, Default, Display, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive, FromSql, ToSql,
@@ -189,17 +220,26 @@ pub enum Chattype {
 /// if none of these flags are set, the default is chosen
 pub const DC_LP_AUTH_FLAGS: i32 = DC_LP_AUTH_OAUTH2 | DC_LP_AUTH_NORMAL;
 
-/// How many existing messages shall be fetched after configuration.
-pub(crate) const DC_FETCH_EXISTING_MSGS_COUNT: i64 = 100;
+/// How many older messages are fetched from each folder per backfill iteration
+/// after the existing messages have been fetched.
+pub(crate) const DC_BACKFILL_BATCH_SIZE: usize = 50;
 
 // max. width/height of an avatar
 pub(crate) const BALANCED_AVATAR_SIZE: u32 = 256;
 pub(crate) const WORSE_AVATAR_SIZE: u32 = 128;
 
+// max. width/height of avatar thumbnails as shown in contact and chat lists
+pub(crate) const AVATAR_THUMBNAIL_SIZE: u32 = 64;
+
 // max. width/height of images
 pub const BALANCED_IMAGE_SIZE: u32 = 1280;
 pub const WORSE_IMAGE_SIZE: u32 = 640;
 
+// limits for decoding images to be recoded, protecting against decompression bombs
+pub(crate) const MAX_DECODE_IMAGE_DIMENSION: u32 = 16_384;
+pub(crate) const MAX_DECODE_IMAGE_PIXELS: u64 = 50_000_000;
+pub(crate) const MAX_DECODE_IMAGE_ALLOC: u64 = 512 * 1024 * 1024;
+
 // this value can be increased if the folder configuration is changed and must be redone on next program start
 pub(crate) const DC_FOLDERS_CONFIGURED_VERSION: i32 = 4;
 
@@ -258,6 +298,24 @@ fn test_mediaquality_values() {
         assert_eq!(MediaQuality::Worse, MediaQuality::from_i32(1).unwrap());
     }
 
+    #[test]
+    fn test_encryptedsubject_values() {
+        // values may be written to disk and must not change
+        assert_eq!(EncryptedSubject::Hidden, EncryptedSubject::default());
+        assert_eq!(
+            EncryptedSubject::Hidden,
+            EncryptedSubject::from_i32(0).unwrap()
+        );
+        assert_eq!(
+            EncryptedSubject::ChatName,
+            EncryptedSubject::from_i32(1).unwrap()
+        );
+        assert_eq!(
+            EncryptedSubject::Plain,
+            EncryptedSubject::from_i32(2).unwrap()
+        );
+    }
+
     #[test]
     fn test_videochattype_values() {
         // values may be written to disk and must not change
@@ -19,13 +19,14 @@
 use tokio::time::{timeout, Duration};
 
 use crate::aheader::EncryptPreference;
+use crate::blob::BlobObject;
 use crate::chat::ChatId;
 use crate::color::str_to_color;
 use crate::config::Config;
 use crate::constants::{Blocked, Chattype, DC_GCL_ADD_SELF, DC_GCL_VERIFIED_ONLY};
 use crate::context::Context;
 use crate::events::EventType;
-use crate::key::{DcKey, SignedPublicKey};
+use crate::key::{DcKey, Fingerprint, SignedPublicKey};
 use crate::login_param::LoginParam;
 use crate::message::MessageState;
 use crate::mimeparser::AvatarAction;
@@ -33,8 +34,8 @@
 use crate::peerstate::{Peerstate, PeerstateVerifiedStatus};
 use crate::sql::{self, params_iter};
 use crate::tools::{
-    duration_to_str, get_abs_path, improve_single_line_input, strip_rtlo_characters, time,
-    EmailAddress,
+    duration_to_str, get_abs_path, improve_single_line_input, is_suspicious_name,
+    strip_rtlo_characters, time, EmailAddress,
 };
 use crate::{chat, stock_str};
 
@@ -109,7 +110,7 @@ impl ContactId {
 
     /// ID of the contact for device messages.
     pub const DEVICE: ContactId = ContactId::new(5);
-    const LAST_SPECIAL: ContactId = ContactId::new(9);
+    pub(crate) const LAST_SPECIAL: ContactId = ContactId::new(9);
 
     /// Address to go with [`ContactId::DEVICE`].
     ///
@@ -215,6 +216,10 @@ pub struct Contact {
     /// Time when the contact was seen last time, Unix time in seconds.
     last_seen: i64,
 
+    /// True if the last-seen status is not shared with the contact,
+    /// see [`Contact::set_share_last_seen`].
+    hide_last_seen: bool,
+
     /// The origin/source of the contact.
     pub origin: Origin,
 
@@ -223,6 +228,9 @@ pub struct Contact {
 
     /// Last seen message signature for this contact, to be displayed in the profile.
     status: String,
+
+    /// Stable identifier of the contact.
+    uuid: String,
 }
 
 /// Possible origins of a contact.
@@ -337,6 +345,23 @@ fn default() -> Self {
     }
 }
 
+/// Autocrypt key seen for the address of a contact, see [`Contact::get_key_history`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyHistoryEntry {
+    /// Fingerprint of the key.
+    pub fingerprint: Fingerprint,
+
+    /// Timestamp of the first message with the key.
+    pub first_seen: i64,
+
+    /// Timestamp of the last message with the key.
+    pub last_seen: i64,
+
+    /// Whether the message the key was first seen in passed the DKIM check
+    /// according to the Authentication-Results header added by the server.
+    pub dkim_passed: bool,
+}
+
 impl Contact {
     /// Loads a contact snapshot from the database.
     pub async fn load_from_db(context: &Context, contact_id: ContactId) -> Result<Self> {
@@ -344,7 +369,7 @@ pub async fn load_from_db(context: &Context, contact_id: ContactId) -> Result<Se
             .sql
             .query_row(
                 "SELECT c.name, c.addr, c.origin, c.blocked, c.last_seen,
-                c.authname, c.param, c.status
+                c.authname, c.param, c.status, c.uuid, c.hide_last_seen
                FROM contacts c
               WHERE c.id=?;",
                 (contact_id,),
@@ -357,6 +382,8 @@ pub async fn load_from_db(context: &Context, contact_id: ContactId) -> Result<Se
                     let authname: String = row.get(5)?;
                     let param: String = row.get(6)?;
                     let status: Option<String> = row.get(7)?;
+                    let uuid: String = row.get(8)?;
+                    let hide_last_seen: bool = row.get(9)?;
                     let contact = Self {
                         id: contact_id,
                         name,
@@ -364,9 +391,11 @@ pub async fn load_from_db(context: &Context, contact_id: ContactId) -> Result<Se
                         addr,
                         blocked: blocked.unwrap_or_default(),
                         last_seen,
+                        hide_last_seen,
                         origin,
                         param: param.parse().unwrap_or_default(),
                         status: status.unwrap_or_default(),
+                        uuid,
                     };
                     Ok(contact)
                 },
@@ -387,6 +416,10 @@ pub async fn load_from_db(context: &Context, contact_id: ContactId) -> Result<Se
             contact.addr = ContactId::DEVICE_ADDR.to_string();
             contact.status = stock_str::device_messages_hint(context).await;
         }
+        if contact.hide_last_seen || !context.get_config_bool(Config::ShareLastSeen).await? {
+            // Last-seen status is only shown to contacts we share our own one with.
+            contact.last_seen = 0;
+        }
         Ok(contact)
     }
 
@@ -395,6 +428,22 @@ pub fn is_blocked(&self) -> bool {
         self.blocked
     }
 
+    /// Returns `true` if our last-seen status is shared with this contact,
+    /// see [`Contact::set_share_last_seen`].
+    ///
+    /// The `share_last_seen` config option overrides this if disabled.
+    pub fn shares_last_seen(&self) -> bool {
+        !self.hide_last_seen
+    }
+
+    /// Returns `true` if the name set by the contact looks like an impersonation attempt,
+    /// e.g. it mixes Latin and Cyrillic letters or contains invisible characters.
+    ///
+    /// Names set by the user are never flagged.
+    pub fn is_name_suspicious(&self) -> bool {
+        self.name.is_empty() && is_suspicious_name(&self.authname)
+    }
+
     /// Returns last seen timestamp.
     pub fn last_seen(&self) -> i64 {
         self.last_seen
@@ -421,6 +470,27 @@ pub async fn unblock(context: &Context, id: ContactId) -> Result<()> {
         set_block_contact(context, id, false).await
     }
 
+    /// Sets whether our last-seen status is shared with the given contact.
+    ///
+    /// If not shared, messages to the contact, including groups the contact is member of,
+    /// are sent as with the `share_last_seen` config option disabled
+    /// and, in return, the last-seen status of the contact is not shown.
+    pub async fn set_share_last_seen(context: &Context, id: ContactId, share: bool) -> Result<()> {
+        ensure!(
+            !id.is_special(),
+            "Can't set last-seen sharing for special contacts"
+        );
+        context
+            .sql
+            .execute(
+                "UPDATE contacts SET hide_last_seen=? WHERE id=?;",
+                (!share, id),
+            )
+            .await?;
+        context.emit_event(EventType::ContactsChanged(Some(id)));
+        Ok(())
+    }
+
     /// Add a single contact as a result of an _explicit_ user action.
     ///
     /// We assume, the contact name, if any, is entered by the user and is used "as is" therefore,
@@ -499,6 +569,17 @@ pub async fn lookup_id_by_addr(
         Ok(id)
     }
 
+    /// Returns the contact with the given stable identifier, see [`Contact::get_uuid`].
+    pub async fn lookup_id_by_uuid(context: &Context, uuid: &str) -> Result<Option<ContactId>> {
+        if uuid.is_empty() {
+            return Ok(None);
+        }
+        context
+            .sql
+            .query_get_value("SELECT id FROM contacts WHERE uuid=?;", (uuid,))
+            .await
+    }
+
     /// Lookup a contact and create it if it does not exist yet.
     /// The contact is identified by the email-address, a name and an "origin" can be given.
     ///
@@ -1091,6 +1172,14 @@ pub fn get_addr(&self) -> &str {
         &self.addr
     }
 
+    /// Returns the stable identifier of the contact.
+    ///
+    /// Unlike the contact ID, the identifier is preserved when the account is exported
+    /// and imported from a backup.
+    pub fn get_uuid(&self) -> &str {
+        &self.uuid
+    }
+
     /// Get name authorized by the contact.
     pub fn get_authname(&self) -> &str {
         &self.authname
@@ -1153,6 +1242,21 @@ pub async fn get_profile_image(&self, context: &Context) -> Result<Option<PathBu
         Ok(None)
     }
 
+    /// Returns a small thumbnail of the contact's profile image.
+    ///
+    /// The thumbnail is created on first use and keeps its path as long as
+    /// the profile image does not change, so UIs don't need to decode
+    /// the full-size avatar for lists.
+    pub async fn get_profile_image_thumbnail(&self, context: &Context) -> Result<Option<PathBuf>> {
+        let image = match self.get_profile_image(context).await? {
+            Some(image) => image,
+            None => return Ok(None),
+        };
+        let blob = BlobObject::from_path(context, &image)?;
+        let thumb = blob.get_or_create_thumbnail(context).await?;
+        Ok(Some(thumb.to_abs_path()))
+    }
+
     /// Get a color for the contact.
     /// The color is calculated from the contact's email address
     /// and can be used for an fallback avatar with white initials
@@ -1221,6 +1325,41 @@ pub async fn get_verifier_id(&self, context: &Context) -> Result<Option<ContactI
         }
     }
 
+    /// Returns all Autocrypt keys ever seen for the address of the contact,
+    /// most recently seen first.
+    ///
+    /// This allows to audit unexpected key changes.
+    pub async fn get_key_history(&self, context: &Context) -> Result<Vec<KeyHistoryEntry>> {
+        context
+            .sql
+            .query_map(
+                "SELECT fingerprint, first_seen, last_seen, dkim_passed
+                 FROM key_history WHERE addr=? ORDER BY last_seen DESC, first_seen DESC",
+                (self.get_addr(),),
+                |row| {
+                    let fingerprint: String = row.get(0)?;
+                    let first_seen: i64 = row.get(1)?;
+                    let last_seen: i64 = row.get(2)?;
+                    let dkim_passed: bool = row.get(3)?;
+                    Ok((fingerprint, first_seen, last_seen, dkim_passed))
+                },
+                |rows| {
+                    let mut res = Vec::new();
+                    for row in rows {
+                        let (fingerprint, first_seen, last_seen, dkim_passed) = row?;
+                        res.push(KeyHistoryEntry {
+                            fingerprint: fingerprint.parse()?,
+                            first_seen,
+                            last_seen,
+                            dkim_passed,
+                        });
+                    }
+                    Ok(res)
+                },
+            )
+            .await
+    }
+
     /// Returns the number of real (i.e. non-special) contacts in the database.
     pub async fn get_real_cnt(context: &Context) -> Result<usize> {
         if !context.sql.is_open().await {
@@ -1733,6 +1872,30 @@ fn test_split_address_book() {
         )
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_is_name_suspicious() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let (id, _modified) = Contact::add_or_lookup(
+            &t,
+            "P\u{0430}ypal",
+            ContactAddress::new("paypal@example.org")?,
+            Origin::IncomingUnknownFrom,
+        )
+        .await?;
+        let contact = Contact::load_from_db(&t, id).await?;
+        assert!(contact.is_name_suspicious());
+
+        // Names given by the user are trusted.
+        Contact::create(&t, "Paypal scam", "paypal@example.org").await?;
+        let contact = Contact::load_from_db(&t, id).await?;
+        assert!(!contact.is_name_suspicious());
+
+        let bob_id = Contact::create(&t, "", "bob@example.net").await?;
+        let bob = Contact::load_from_db(&t, bob_id).await?;
+        assert!(!bob.is_name_suspicious());
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_get_contacts() -> Result<()> {
         let context = TestContext::new().await;
@@ -2644,6 +2807,54 @@ async fn test_was_seen_recently() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_share_last_seen() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        alice.set_config_bool(Config::ShareLastSeen, false).await?;
+
+        // Bob does not track Alice's last-seen status.
+        let alice_chat = alice.create_chat(&bob).await;
+        let sent_msg = alice.send_text(alice_chat.id, "moin").await;
+        let bob_msg = bob.recv_msg(&sent_msg).await;
+        let contact = Contact::get_by_id(&bob, bob_msg.from_id).await?;
+        assert_eq!(contact.last_seen(), 0);
+        assert!(!contact.was_seen_recently());
+
+        // In return, Alice does not see Bob's last-seen status.
+        let bob_chat = bob.create_chat(&alice).await;
+        let sent_msg = bob.send_text(bob_chat.id, "hi").await;
+        let alice_msg = alice.recv_msg(&sent_msg).await;
+        let contact = Contact::get_by_id(&alice, alice_msg.from_id).await?;
+        assert_eq!(contact.last_seen(), 0);
+        assert!(!contact.was_seen_recently());
+
+        alice.set_config_bool(Config::ShareLastSeen, true).await?;
+        let contact = Contact::get_by_id(&alice, alice_msg.from_id).await?;
+        assert!(contact.was_seen_recently());
+
+        // Alice stops sharing the last-seen status with Bob only.
+        Contact::set_share_last_seen(&alice, contact.id, false).await?;
+        let contact = Contact::get_by_id(&alice, alice_msg.from_id).await?;
+        assert!(!contact.shares_last_seen());
+        assert!(!contact.was_seen_recently());
+        let sent_msg = alice.send_text(alice_chat.id, "hello").await;
+        let bob_msg = bob.recv_msg(&sent_msg).await;
+        let alice_contact = Contact::get_by_id(&bob, bob_msg.from_id).await?;
+        assert_eq!(alice_contact.last_seen(), 0);
+
+        Contact::set_share_last_seen(&alice, contact.id, true).await?;
+        let contact = Contact::get_by_id(&alice, alice_msg.from_id).await?;
+        assert!(contact.was_seen_recently());
+        let sent_msg = alice.send_text(alice_chat.id, "hello again").await;
+        bob.recv_msg(&sent_msg).await;
+        let alice_contact = Contact::get_by_id(&bob, bob_msg.from_id).await?;
+        assert!(alice_contact.was_seen_recently());
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_verified_by_none() -> Result<()> {
         let mut tcm = TestContextManager::new();
@@ -2666,4 +2877,41 @@ async fn test_verified_by_none() -> Result<()> {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_key_history() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+
+        let contact = alice.add_or_lookup_contact(&bob).await;
+        assert!(contact.get_key_history(&alice).await?.is_empty());
+
+        let chat = bob.create_chat(&alice).await;
+        let sent_msg = bob.send_text(chat.id, "moin").await;
+        alice.recv_msg(&sent_msg).await;
+        let sent_msg = bob.send_text(chat.id, "moin again").await;
+        alice.recv_msg(&sent_msg).await;
+
+        let old_fingerprint = SignedPublicKey::load_self(&bob).await?.fingerprint();
+        let history = contact.get_key_history(&alice).await?;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].fingerprint, old_fingerprint);
+        assert!(history[0].first_seen <= history[0].last_seen);
+        assert!(!history[0].dkim_passed);
+
+        // Bob changes his key.
+        let mut keypair = crate::test_utils::fiona_keypair();
+        keypair.addr = crate::tools::EmailAddress::new("bob@example.net")?;
+        crate::key::store_self_keypair(&bob, &keypair, crate::key::KeyPairUse::Default).await?;
+        let sent_msg = bob.send_text(chat.id, "new key").await;
+        alice.recv_msg(&sent_msg).await;
+
+        let history = contact.get_key_history(&alice).await?;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].fingerprint, keypair.public.fingerprint());
+        assert_eq!(history[1].fingerprint, old_fingerprint);
+
+        Ok(())
+    }
 }
@@ -1,10 +1,10 @@
 //! Context module.
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsString;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicI64};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
 
@@ -13,16 +13,20 @@
 use ratelimit::Ratelimit;
 use tokio::sync::{Mutex, RwLock};
 use tokio::task;
+use zeroize::Zeroizing;
 
 use crate::chat::{get_chat_cnt, ChatId};
+use crate::clock::{Clock, SystemClock};
 use crate::config::Config;
 use crate::constants::DC_VERSION_STR;
-use crate::contact::Contact;
+use crate::contact::{Contact, ContactId};
 use crate::debug_logging::DebugEventLogData;
 use crate::events::{Event, EventEmitter, EventType, Events};
 use crate::key::{DcKey, SignedPublicKey};
 use crate::login_param::LoginParam;
 use crate::message::{self, MessageState, MsgId};
+use crate::notification::NotificationIntent;
+use crate::peer_transfer;
 use crate::quota::QuotaInfo;
 use crate::scheduler::SchedulerState;
 use crate::sql::Sql;
@@ -208,6 +212,13 @@ pub struct InnerContext {
     pub(crate) scheduler: SchedulerState,
     pub(crate) ratelimit: RwLock<Ratelimit>,
 
+    /// Source of the current time for time-dependent background work.
+    pub(crate) clock: Arc<dyn Clock>,
+
+    /// Offset in seconds added to the timestamps of created messages,
+    /// see [`crate::clock_skew`].
+    pub(crate) clock_correction: AtomicI64,
+
     /// Recently loaded quota information, if any.
     /// Set to `None` if quota was never tried to load.
     pub(crate) quota: RwLock<Option<QuotaInfo>>,
@@ -218,6 +229,19 @@ pub struct InnerContext {
     /// IMAP UID resync request.
     pub(crate) resync_request: AtomicBool,
 
+    /// Requests to fetch older messages of chats from the server,
+    /// mapped to the number of messages to fetch.
+    ///
+    /// See [`ChatId::load_older_from_server`].
+    pub(crate) older_msgs_requests: Mutex<BTreeMap<ChatId, usize>>,
+
+    /// Files offered peer-to-peer, see [`crate::peer_transfer`].
+    /// Dropping an offer stops offering the file.
+    pub(crate) p2p_offers: Mutex<BTreeMap<MsgId, peer_transfer::Offer>>,
+
+    /// ICE candidates of calls waiting to be sent, see [`crate::calls::send_call_ice`].
+    pub(crate) call_ice_candidates: Mutex<BTreeMap<MsgId, Vec<String>>>,
+
     /// Server ID response if ID capability is supported
     /// and the server returned non-NIL on the inbox connection.
     /// <https://datatracker.ietf.org/doc/html/rfc2971>
@@ -225,6 +249,13 @@ pub struct InnerContext {
 
     pub(crate) last_full_folder_scan: Mutex<Option<Instant>>,
 
+    /// Message-IDs of the messages which are being received right now.
+    ///
+    /// The same message may be downloaded from several folders at the same time,
+    /// e.g. on Gmail while it is moved from the Inbox to the DeltaChat folder.
+    /// Only the first IMAP loop to claim the Message-ID adds the message to the database.
+    pub(crate) receiving_msgs: std::sync::Mutex<HashSet<String>>,
+
     /// ID for this `Context` in the current process.
     ///
     /// This allows for multiple `Context`s open in a single process where each context can
@@ -311,6 +342,17 @@ pub async fn new_closed(
         id: u32,
         events: Events,
         stockstrings: StockStrings,
+    ) -> Result<Context> {
+        Self::new_closed_with_clock(dbfile, id, events, stockstrings, Arc::new(SystemClock)).await
+    }
+
+    /// Creates new context with the given time source without opening the database.
+    pub(crate) async fn new_closed_with_clock(
+        dbfile: &Path,
+        id: u32,
+        events: Events,
+        stockstrings: StockStrings,
+        clock: Arc<dyn Clock>,
     ) -> Result<Context> {
         let mut blob_fname = OsString::new();
         blob_fname.push(dbfile.file_name().unwrap_or_default());
@@ -319,7 +361,8 @@ pub async fn new_closed(
         if !blobdir.exists() {
             tokio::fs::create_dir_all(&blobdir).await?;
         }
-        let context = Context::with_blobdir(dbfile.into(), blobdir, id, events, stockstrings)?;
+        let context =
+            Context::with_blobdir(dbfile.into(), blobdir, id, events, stockstrings, clock)?;
         Ok(context)
     }
 
@@ -328,8 +371,9 @@ pub async fn new_closed(
     /// Returns true if passphrase is correct, false is passphrase is not correct. Fails on other
     /// errors.
     pub async fn open(&self, passphrase: String) -> Result<bool> {
-        if self.sql.check_passphrase(passphrase.clone()).await? {
-            self.sql.open(self, passphrase).await?;
+        let passphrase = Zeroizing::new(passphrase);
+        if self.sql.check_passphrase(passphrase.to_string()).await? {
+            self.sql.open(self, passphrase.to_string()).await?;
             Ok(true)
         } else {
             Ok(false)
@@ -356,6 +400,7 @@ pub(crate) fn with_blobdir(
         id: u32,
         events: Events,
         stockstrings: StockStrings,
+        clock: Arc<dyn Clock>,
     ) -> Result<Context> {
         ensure!(
             blobdir.is_dir(),
@@ -375,13 +420,19 @@ pub(crate) fn with_blobdir(
             translated_stockstrings: stockstrings,
             events,
             scheduler: SchedulerState::new(),
-            ratelimit: RwLock::new(Ratelimit::new(Duration::new(60, 0), 6.0)), // Allow to send 6 messages immediately, no more than once every 10 seconds.
+            ratelimit: RwLock::new(Ratelimit::new_at(Duration::new(60, 0), 6.0, clock.now())), // Allow to send 6 messages immediately, no more than once every 10 seconds.
+            clock,
+            clock_correction: AtomicI64::new(0),
             quota: RwLock::new(None),
             quota_update_request: AtomicBool::new(false),
             resync_request: AtomicBool::new(false),
+            older_msgs_requests: Mutex::new(BTreeMap::new()),
+            p2p_offers: Mutex::new(BTreeMap::new()),
+            call_ice_candidates: Mutex::new(BTreeMap::new()),
             server_id: RwLock::new(None),
             creation_time: std::time::SystemTime::now(),
             last_full_folder_scan: Mutex::new(None),
+            receiving_msgs: std::sync::Mutex::new(HashSet::new()),
             last_error: std::sync::RwLock::new("".to_string()),
             debug_logging: RwLock::new(None),
         };
@@ -400,11 +451,15 @@ pub async fn start_io(&self) {
             return;
         }
         self.scheduler.start(self.clone()).await;
+        if let Err(err) = peer_transfer::resume_offers(self).await {
+            warn!(self, "Failed to resume peer-to-peer offers: {err:#}.");
+        }
     }
 
     /// Stops the IO scheduler.
     pub async fn stop_io(&self) {
         self.scheduler.stop(self).await;
+        peer_transfer::stop_offers(self).await;
     }
 
     /// Restarts the IO scheduler if it was running before
@@ -436,6 +491,17 @@ pub fn get_blobdir(&self) -> &Path {
         self.blobdir.as_path()
     }
 
+    /// Returns the directory for the media of received view-once messages.
+    ///
+    /// It is next to the blobdir rather than inside,
+    /// so the media cannot be accessed like other blobs.
+    pub(crate) fn get_view_once_dir(&self) -> PathBuf {
+        let mut dir_name = OsString::new();
+        dir_name.push(self.blobdir.file_name().unwrap_or_default());
+        dir_name.push("-view-once");
+        self.blobdir.with_file_name(dir_name)
+    }
+
     /// Emits a single event.
     pub fn emit_event(&self, event: EventType) {
         if self
@@ -449,6 +515,7 @@ pub fn emit_event(&self, event: EventType) {
         };
         self.events.emit(Event {
             id: self.id,
+            seq: 0,
             typ: event,
         });
     }
@@ -487,8 +554,13 @@ pub fn emit_msgs_changed(&self, chat_id: ChatId, msg_id: MsgId) {
     }
 
     /// Emits an IncomingMsg event with specified chat and message ids
-    pub fn emit_incoming_msg(&self, chat_id: ChatId, msg_id: MsgId) {
-        self.emit_event(EventType::IncomingMsg { chat_id, msg_id });
+    /// and the decision how the user should be notified.
+    pub fn emit_incoming_msg(&self, chat_id: ChatId, msg_id: MsgId, intent: NotificationIntent) {
+        self.emit_event(EventType::IncomingMsg {
+            chat_id,
+            msg_id,
+            intent,
+        });
     }
 
     /// Returns a receiver for emitted events.
@@ -587,6 +659,7 @@ pub async fn get_info(&self) -> Result<BTreeMap<&'static str, String>> {
             .unwrap_or_else(|| "unknown".to_string());
         let e2ee_enabled = self.get_config_int(Config::E2eeEnabled).await?;
         let mdns_enabled = self.get_config_int(Config::MdnsEnabled).await?;
+        let share_last_seen = self.get_config_int(Config::ShareLastSeen).await?;
         let bcc_self = self.get_config_int(Config::BccSelf).await?;
         let send_sync_msgs = self.get_config_int(Config::SendSyncMsgs).await?;
 
@@ -688,12 +761,14 @@ pub async fn get_info(&self) -> Result<BTreeMap<&'static str, String>> {
         res.insert("sentbox_watch", sentbox_watch.to_string());
         res.insert("mvbox_move", mvbox_move.to_string());
         res.insert("only_fetch_mvbox", only_fetch_mvbox.to_string());
+        res.insert("is_chatmail", self.is_chatmail().await?.to_string());
         res.insert("folders_configured", folders_configured.to_string());
         res.insert("configured_inbox_folder", configured_inbox_folder);
         res.insert("configured_sentbox_folder", configured_sentbox_folder);
         res.insert("configured_mvbox_folder", configured_mvbox_folder);
         res.insert("configured_trash_folder", configured_trash_folder);
         res.insert("mdns_enabled", mdns_enabled.to_string());
+        res.insert("share_last_seen", share_last_seen.to_string());
         res.insert("e2ee_enabled", e2ee_enabled.to_string());
         res.insert(
             "key_gen_type",
@@ -762,6 +837,46 @@ pub async fn get_info(&self) -> Result<BTreeMap<&'static str, String>> {
                 .await?
                 .to_string(),
         );
+        res.insert(
+            "encrypted_subject",
+            self.get_config_int(Config::EncryptedSubject)
+                .await?
+                .to_string(),
+        );
+        res.insert(
+            "notify_muted_mentions",
+            self.get_config_int(Config::NotifyMutedMentions)
+                .await?
+                .to_string(),
+        );
+        res.insert(
+            "notify_bots",
+            self.get_config_int(Config::NotifyBots).await?.to_string(),
+        );
+        res.insert(
+            "quiet_hours",
+            self.get_config(Config::QuietHours)
+                .await?
+                .unwrap_or_else(|| "<unset>".to_string()),
+        );
+        res.insert(
+            "quiet_hours_allow_mentions",
+            self.get_config_int(Config::QuietHoursAllowMentions)
+                .await?
+                .to_string(),
+        );
+        res.insert(
+            "quiet_hours_allow_verified",
+            self.get_config_int(Config::QuietHoursAllowVerified)
+                .await?
+                .to_string(),
+        );
+        res.insert(
+            "notification_digest",
+            self.get_config_int(Config::NotificationDigest)
+                .await?
+                .to_string(),
+        );
 
         res.insert(
             "debug_logging",
@@ -799,7 +914,7 @@ pub async fn get_fresh_msgs(&self) -> Result<Vec<MsgId>> {
                     "   AND NOT(c.muted_until=-1 OR c.muted_until>?)",
                     " ORDER BY m.timestamp DESC,m.id DESC;"
                 ),
-                (MessageState::InFresh, time()),
+                (MessageState::InFresh, self.clock.unix_time()),
                 |row| row.get::<_, MsgId>(0),
                 |rows| {
                     let mut list = Vec::new();
@@ -813,6 +928,42 @@ pub async fn get_fresh_msgs(&self) -> Result<Vec<MsgId>> {
         Ok(list)
     }
 
+    /// Returns all starred messages, newest first, see [`MsgId::set_starred`].
+    pub async fn get_starred_msgs(&self) -> Result<Vec<MsgId>> {
+        self.sql
+            .query_map(
+                "SELECT id FROM msgs
+                 WHERE starred=1 AND chat_id>9 AND hidden=0
+                 ORDER BY timestamp DESC, id DESC",
+                (),
+                |row| row.get::<_, MsgId>(0),
+                |ids| ids.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await
+    }
+
+    /// Returns the messages of the saved messages chat tagged with `tag`,
+    /// newest first.
+    ///
+    /// Tags are added using [`MsgId::add_saved_tag`].
+    pub async fn get_saved_by_tag(&self, tag: &str) -> Result<Vec<MsgId>> {
+        let chat_id = match ChatId::lookup_by_contact(self, ContactId::SELF).await? {
+            Some(chat_id) => chat_id,
+            None => return Ok(Vec::new()),
+        };
+        self.sql
+            .query_map(
+                "SELECT m.id FROM saved_msg_tags t
+                 INNER JOIN msgs m ON m.id=t.msg_id
+                 WHERE t.tag=? AND m.chat_id=?
+                 ORDER BY m.timestamp DESC, m.id DESC",
+                (tag.trim(), chat_id),
+                |row| row.get::<_, MsgId>(0),
+                |ids| ids.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await
+    }
+
     /// Searches for messages containing the query string.
     ///
     /// If `chat_id` is provided this searches only for messages in this chat, if `chat_id`
@@ -906,6 +1057,12 @@ pub async fn is_mvbox(&self, folder_name: &str) -> Result<bool> {
         Ok(mvbox.as_deref() == Some(folder_name))
     }
 
+    /// Returns true if given folder name is the name of the archive folder.
+    pub async fn is_archive(&self, folder_name: &str) -> Result<bool> {
+        let archive = self.get_config(Config::ConfiguredArchiveFolder).await?;
+        Ok(archive.as_deref() == Some(folder_name))
+    }
+
     /// Returns true if given folder name is the name of the trash folder.
     pub async fn is_trash(&self, folder_name: &str) -> Result<bool> {
         let trash = self.get_config(Config::ConfiguredTrashFolder).await?;
@@ -922,6 +1079,17 @@ pub(crate) async fn should_delete_to_trash(&self) -> Result<bool> {
         Ok(false)
     }
 
+    /// Returns true if IMAP folders are labels, like on Gmail.
+    pub(crate) async fn uses_imap_labels(&self) -> Result<bool> {
+        if self.get_config_bool(Config::ImapLabels).await? {
+            return Ok(true);
+        }
+        Ok(self
+            .get_configured_provider()
+            .await?
+            .map_or(false, |provider| provider.opt.imap_labels))
+    }
+
     /// Returns `target` for deleted messages as per `imap` table. Empty string means "delete w/o
     /// moving to trash".
     pub(crate) async fn get_delete_msgs_target(&self) -> Result<String> {
@@ -1156,7 +1324,14 @@ async fn test_with_empty_blobdir() {
         let tmp = tempfile::tempdir().unwrap();
         let dbfile = tmp.path().join("db.sqlite");
         let blobdir = PathBuf::new();
-        let res = Context::with_blobdir(dbfile, blobdir, 1, Events::new(), StockStrings::new());
+        let res = Context::with_blobdir(
+            dbfile,
+            blobdir,
+            1,
+            Events::new(),
+            StockStrings::new(),
+            Arc::new(SystemClock),
+        );
         assert!(res.is_err());
     }
 
@@ -1165,7 +1340,14 @@ async fn test_with_blobdir_not_exists() {
         let tmp = tempfile::tempdir().unwrap();
         let dbfile = tmp.path().join("db.sqlite");
         let blobdir = tmp.path().join("blobs");
-        let res = Context::with_blobdir(dbfile, blobdir, 1, Events::new(), StockStrings::new());
+        let res = Context::with_blobdir(
+            dbfile,
+            blobdir,
+            1,
+            Events::new(),
+            StockStrings::new(),
+            Arc::new(SystemClock),
+        );
         assert!(res.is_err());
     }
 
@@ -50,6 +50,7 @@ pub async fn debug_logging_loop(context: &Context, events: Receiver<DebugEventLo
             Ok(serial) => {
                 context.events.emit(Event {
                     id: context.id,
+                    seq: 0,
                     typ: EventType::WebxdcStatusUpdate {
                         msg_id,
                         status_update_serial: serial,
@@ -104,6 +104,17 @@ pub(crate) async fn prepare_decryption(
     )
     .await?;
 
+    if let Some(header) = &autocrypt_header {
+        save_key_history(
+            context,
+            from,
+            &header.public_key.fingerprint(),
+            message_time,
+            dkim_results.dkim_passed,
+        )
+        .await?;
+    }
+
     Ok(DecryptionInfo {
         from: from.to_string(),
         autocrypt_header,
@@ -342,6 +353,29 @@ pub(crate) async fn get_autocrypt_peerstate(
     Ok(peerstate)
 }
 
+/// Records that the Autocrypt key with the given fingerprint was seen for `addr`,
+/// see [`crate::contact::Contact::get_key_history`].
+async fn save_key_history(
+    context: &Context,
+    addr: &str,
+    fingerprint: &Fingerprint,
+    message_time: i64,
+    dkim_passed: bool,
+) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "INSERT INTO key_history (addr, fingerprint, first_seen, last_seen, dkim_passed)
+             VALUES (?1, ?2, ?3, ?3, ?4)
+             ON CONFLICT (addr, fingerprint) DO UPDATE SET
+             first_seen=MIN(first_seen, excluded.first_seen),
+             last_seen=MAX(last_seen, excluded.last_seen)",
+            (addr, fingerprint.hex(), message_time, dkim_passed),
+        )
+        .await?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,400 @@
+//! # Device list stored on the IMAP server.
+//!
+//! If the server supports the METADATA extension defined in
+//! [RFC 5464](https://tools.ietf.org/html/rfc5464),
+//! every device periodically stores a small record about itself
+//! under a private server annotation.
+//! The record is encrypted to and signed with the own key,
+//! so the server cannot read it and other users cannot forge it.
+//!
+//! Reading the records of all devices allows to show how many devices
+//! are connected to the account and to detect devices
+//! which have not been used for a long time.
+//!
+//! Additionally, synchronization messages carry the identifier of the sending device,
+//! so other own devices are tracked even if the server does not support METADATA.
+
+use anyhow::{ensure, Context as _, Result};
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+
+use crate::chat::add_device_msg;
+use crate::config::Config;
+use crate::context::Context;
+use crate::imap::session::Session as ImapSession;
+use crate::imap::Imap;
+use crate::imex::initiate_key_transfer;
+use crate::key::{rotate_self_keypair, DcKey, SignedPublicKey, SignedSecretKey};
+use crate::keyring::Keyring;
+use crate::message::{DeviceAction, Message, Viewtype};
+use crate::pgp;
+use crate::stock_str;
+use crate::tools::{create_id, time};
+
+/// Server annotation under which the records of all devices are stored.
+const DEVICES_ENTRY: &str = "/private/vendor/deltachat/devices";
+
+/// Devices update their records on the server once per this interval.
+const DEVICE_LIST_UPDATE_INTERVAL: i64 = 24 * 60 * 60;
+
+/// Devices which have not updated their record for this time are considered stale.
+pub const DEVICE_STALE_SECONDS: i64 = 30 * 24 * 60 * 60;
+
+/// Device connected to the account, as announced in the device list on the server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    /// Random identifier of the device.
+    pub id: String,
+
+    /// Name of the device as set by the user, may be empty.
+    pub name: String,
+
+    /// Timestamp of the last record update by the device.
+    pub last_seen: i64,
+}
+
+impl DeviceInfo {
+    /// Returns true if the device did not update its record for [`DEVICE_STALE_SECONDS`].
+    pub fn is_stale(&self) -> bool {
+        self.last_seen.saturating_add(DEVICE_STALE_SECONDS) < time()
+    }
+}
+
+/// Encrypts the record of a device so it can be stored on the server.
+async fn encrypt_record(context: &Context, device: &DeviceInfo) -> Result<String> {
+    let plain = serde_json::to_vec(device)?;
+    let public_keys: Keyring<SignedPublicKey> = Keyring::new_self(context).await?;
+    let secret_key = SignedSecretKey::load_self(context).await?;
+    let ctext = pgp::pk_encrypt(&plain, public_keys, Some(secret_key)).await?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(ctext))
+}
+
+/// Decrypts the record of a device stored on the server.
+///
+/// Records which are not signed with the own key are rejected.
+async fn decrypt_record(context: &Context, value: &str) -> Result<DeviceInfo> {
+    let ctext = base64::engine::general_purpose::STANDARD.decode(value)?;
+    let secret_keys = Keyring::new_self_with_old_keys(context).await?;
+    let public_keys: Keyring<SignedPublicKey> = Keyring::new_self(context).await?;
+    let (plain, signatures) = pgp::pk_decrypt(ctext, &secret_keys, &public_keys)?;
+    ensure!(!signatures.is_empty(), "device record is not signed");
+    let device = serde_json::from_slice(&plain)?;
+    Ok(device)
+}
+
+impl Context {
+    /// Returns the identifier of this device, generating it on first use.
+    pub(crate) async fn get_device_id(&self) -> Result<String> {
+        if let Some(device_id) = self.get_config(Config::DeviceId).await? {
+            return Ok(device_id);
+        }
+        let device_id = create_id();
+        self.set_config(Config::DeviceId, Some(&device_id)).await?;
+        Ok(device_id)
+    }
+
+    /// Updates the record of this device on the server
+    /// and the local copy of the device list
+    /// if the last update is older than a day.
+    ///
+    /// Does nothing if the server does not support the METADATA extension.
+    pub(crate) async fn maybe_update_device_list(&self, imap: &mut Imap) -> Result<()> {
+        let last_update = self.get_config_i64(Config::LastDeviceListUpdate).await?;
+        if last_update.saturating_add(DEVICE_LIST_UPDATE_INTERVAL) > time() {
+            return Ok(());
+        }
+
+        if let Err(err) = imap.prepare(self).await {
+            warn!(self, "could not connect: {:#}", err);
+            return Ok(());
+        }
+        let session = imap.session.as_mut().context("no session")?;
+        if !session.can_metadata() {
+            return Ok(());
+        }
+
+        let device = DeviceInfo {
+            id: self.get_device_id().await?,
+            name: self
+                .get_config(Config::DeviceName)
+                .await?
+                .unwrap_or_default(),
+            last_seen: time(),
+        };
+        let record = encrypt_record(self, &device).await?;
+        session
+            .run_command_and_check_ok(
+                &format!(
+                    "SETMETADATA \"\" ({DEVICES_ENTRY}/{} \"{record}\")",
+                    device.id
+                ),
+                None,
+            )
+            .await
+            .context("failed to store device record")?;
+
+        let devices = self.fetch_device_list(session).await?;
+        self.sql
+            .transaction(move |transaction| {
+                for device in devices {
+                    transaction.execute(
+                        "INSERT INTO devices (id, name, last_seen) VALUES (?, ?, ?)
+                         ON CONFLICT(id) DO UPDATE
+                         SET name=excluded.name, last_seen=MAX(last_seen, excluded.last_seen)",
+                        (device.id, device.name, device.last_seen),
+                    )?;
+                }
+                Ok(())
+            })
+            .await?;
+        self.set_config(Config::LastDeviceListUpdate, Some(&time().to_string()))
+            .await?;
+        Ok(())
+    }
+
+    /// Loads and decrypts the records of all devices from the server.
+    async fn fetch_device_list(&self, session: &mut ImapSession) -> Result<Vec<DeviceInfo>> {
+        let entries = session
+            .get_metadata("", "(DEPTH 1)", DEVICES_ENTRY)
+            .await
+            .context("failed to get device records")?;
+        let mut devices = Vec::new();
+        for entry in entries {
+            if let Some(value) = entry.value {
+                match decrypt_record(self, &value).await {
+                    Ok(device) => devices.push(device),
+                    Err(err) => warn!(self, "Ignoring device record {}: {:#}.", entry.entry, err),
+                }
+            }
+        }
+        Ok(devices)
+    }
+
+    /// Returns the devices connected to the account which are not revoked,
+    /// including this one if the device list was fetched from the server.
+    ///
+    /// The list is empty if the server does not support the METADATA extension
+    /// and no synchronization messages from other devices were received yet.
+    pub async fn get_own_devices(&self) -> Result<Vec<DeviceInfo>> {
+        self.sql
+            .query_map(
+                "SELECT id, name, last_seen FROM devices WHERE revoked=0
+                 ORDER BY last_seen DESC, id",
+                (),
+                |row| {
+                    Ok(DeviceInfo {
+                        id: row.get(0)?,
+                        name: row.get(1)?,
+                        last_seen: row.get(2)?,
+                    })
+                },
+                |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await
+    }
+
+    /// Returns whether there are other devices connected to the account
+    /// which are not stale.
+    ///
+    /// Returns `None` if the device list is not known,
+    /// e.g. because the server does not support the METADATA extension.
+    /// This can be used to decide whether it is worth to keep `bcc_self`
+    /// and synchronization messages enabled.
+    pub async fn has_other_active_devices(&self) -> Result<Option<bool>> {
+        let devices = self.get_own_devices().await?;
+        if devices.is_empty() {
+            return Ok(None);
+        }
+        let device_id = self.get_device_id().await?;
+        Ok(Some(devices.iter().any(|device| {
+            device.id != device_id && !device.is_stale()
+        })))
+    }
+
+    /// Remembers that a message was sent by another own device at `timestamp`.
+    ///
+    /// Returns false if the device is revoked and the message should be ignored.
+    /// Once a device is revoked, messages without device ID are ignored as well,
+    /// otherwise the revoked device could bypass the revocation by omitting it.
+    pub(crate) async fn own_device_seen(
+        &self,
+        device_id: Option<&str>,
+        timestamp: i64,
+    ) -> Result<bool> {
+        let device_id = match device_id {
+            Some(device_id) if !device_id.is_empty() => device_id,
+            _ => {
+                let revocations = self
+                    .sql
+                    .exists("SELECT COUNT(*) FROM devices WHERE revoked=1", ())
+                    .await?;
+                return Ok(!revocations);
+            }
+        };
+        if device_id == self.get_device_id().await? {
+            return Ok(true);
+        }
+        let revoked: Option<bool> = self
+            .sql
+            .query_get_value("SELECT revoked FROM devices WHERE id=?", (device_id,))
+            .await?;
+        if revoked == Some(true) {
+            return Ok(false);
+        }
+        self.sql
+            .execute(
+                "INSERT INTO devices (id, last_seen) VALUES (?, ?)
+                 ON CONFLICT(id) DO UPDATE SET last_seen=MAX(last_seen, excluded.last_seen)",
+                (device_id, timestamp),
+            )
+            .await?;
+        Ok(true)
+    }
+
+    /// Revokes another own device and returns the setup code for the new key.
+    ///
+    /// Synchronization messages from the revoked device are ignored from now on
+    /// and a device message warns about the removed device.
+    ///
+    /// As all devices share the account key, a new key is generated
+    /// and sent to the own devices in an Autocrypt Setup Message,
+    /// the returned setup code has to be entered on the remaining devices to import it.
+    /// Contacts learn the new key from the next messages, verifications have to be redone.
+    /// The old key is kept to decrypt earlier messages.
+    /// Changing the password locks the revoked device out of the server.
+    pub async fn revoke_own_device(&self, device_id: &str) -> Result<String> {
+        ensure!(
+            device_id != self.get_device_id().await?,
+            "Cannot revoke this device"
+        );
+        let name: String = self
+            .sql
+            .query_get_value("SELECT name FROM devices WHERE id=?", (device_id,))
+            .await?
+            .with_context(|| format!("Unknown device {device_id}"))?;
+        self.sql
+            .execute("UPDATE devices SET revoked=1 WHERE id=?", (device_id,))
+            .await?;
+        rotate_self_keypair(self).await?;
+        let setup_code = initiate_key_transfer(self).await?;
+
+        let name = if name.is_empty() {
+            device_id.to_string()
+        } else {
+            name
+        };
+        let mut msg = Message::new(Viewtype::Text);
+        msg.text = Some(stock_str::own_device_revoked(self, &name).await);
+        msg.set_device_action(DeviceAction::AccountSettings);
+        add_device_msg(self, None, Some(&mut msg)).await?;
+        Ok(setup_code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::Chat;
+    use crate::sync::{QrTokenData, SyncData};
+    use crate::test_utils::TestContext;
+    use crate::token::{self, Namespace};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_device_record() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob = TestContext::new_bob().await;
+        let device = DeviceInfo {
+            id: alice.get_device_id().await?,
+            name: "Desktop".to_string(),
+            last_seen: time(),
+        };
+        assert_eq!(alice.get_device_id().await?, device.id);
+
+        let record = encrypt_record(&alice, &device).await?;
+        assert_eq!(decrypt_record(&alice, &record).await?, device);
+        assert!(decrypt_record(&bob, &record).await.is_err());
+
+        // Bob cannot forge records for Alice.
+        let record = encrypt_record(&bob, &device).await?;
+        assert!(decrypt_record(&alice, &record).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_has_other_active_devices() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        assert_eq!(t.has_other_active_devices().await?, None);
+
+        let device_id = t.get_device_id().await?;
+        t.sql
+            .execute(
+                "INSERT INTO devices (id, name, last_seen) VALUES (?, '', ?), ('other', '', ?)",
+                (&device_id, time(), time() - DEVICE_STALE_SECONDS - 1),
+            )
+            .await?;
+        assert_eq!(t.get_own_devices().await?.len(), 2);
+        assert_eq!(t.has_other_active_devices().await?, Some(false));
+
+        t.sql
+            .execute("UPDATE devices SET last_seen=? WHERE id='other'", (time(),))
+            .await?;
+        assert_eq!(t.has_other_active_devices().await?, Some(true));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_revoke_own_device() -> Result<()> {
+        let alice1 = TestContext::new_alice().await;
+        let alice2 = TestContext::new_alice().await;
+        alice2.set_config_bool(Config::SendSyncMsgs, true).await?;
+        let alice2_id = alice2.get_device_id().await?;
+
+        for auth in ["token1", "token2"] {
+            alice2
+                .add_sync_item(SyncData::AddQrToken(QrTokenData {
+                    invitenumber: "in".to_string(),
+                    auth: auth.to_string(),
+                    grpid: None,
+                }))
+                .await?;
+            alice2.send_sync_msg().await?.unwrap();
+            alice1.recv_msg(&alice2.pop_sent_msg().await).await;
+
+            if auth == "token1" {
+                assert!(token::exists(&alice1, Namespace::Auth, auth).await);
+                let devices = alice1.get_own_devices().await?;
+                assert_eq!(devices.len(), 1);
+                assert_eq!(devices[0].id, alice2_id);
+                assert_eq!(alice1.has_other_active_devices().await?, Some(true));
+
+                assert!(alice1
+                    .revoke_own_device(&alice1.get_device_id().await?)
+                    .await
+                    .is_err());
+                let old_key = SignedPublicKey::load_self(&alice1).await?;
+                assert!(alice1.own_device_seen(None, time()).await?);
+                let setup_code = alice1.revoke_own_device(&alice2_id).await?;
+                assert!(alice1.get_own_devices().await?.is_empty());
+                assert!(!alice1.own_device_seen(None, time()).await?);
+
+                // The key is rotated, the old one is kept for decryption.
+                assert_ne!(
+                    SignedPublicKey::load_self(&alice1).await?.fingerprint(),
+                    old_key.fingerprint()
+                );
+                assert_eq!(Keyring::new_self_with_old_keys(&alice1).await?.len(), 2);
+                let setup_msg = alice1.pop_sent_msg().await;
+                assert!(setup_msg.payload().contains("Autocrypt-Setup-Message"));
+                assert_eq!(setup_code.len(), 44);
+                let device_chat_id = alice1.get_last_msg().await.chat_id;
+                assert!(Chat::load_from_db(&alice1, device_chat_id)
+                    .await?
+                    .is_device_talk());
+            } else {
+                // Sync messages from the revoked device are ignored.
+                assert!(!token::exists(&alice1, Namespace::Auth, auth).await);
+            }
+        }
+        Ok(())
+    }
+}
@@ -306,6 +306,8 @@ fn new_peerstates(prefer_encrypt: EncryptPreference) -> Vec<(Option<Peerstate>,
             gossip_key: Some(pub_key.clone()),
             gossip_timestamp: 15,
             gossip_key_fingerprint: Some(pub_key.fingerprint()),
+            gossip_source_addr: None,
+            gossip_source_fingerprint: None,
             verified_key: Some(pub_key.clone()),
             verified_key_fingerprint: Some(pub_key.fingerprint()),
             fingerprint_changed: false,
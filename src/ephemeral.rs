@@ -61,12 +61,18 @@
 //! Server deletion happens by updating the `imap` table based on
 //! the database entries which are expired either according to their
 //! ephemeral message timers or global `delete_server_after` setting.
+//! The global setting can be overridden for single folders
+//! using [`set_folder_delete_server_after`],
+//! e.g. to delete messages from the Inbox at once
+//! but keep them in the DeltaChat folder for some days.
+//! [`get_server_deletions`] reports which messages would be deleted.
 
 use std::cmp::max;
+use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
 use std::num::ParseIntError;
 use std::str::FromStr;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, UNIX_EPOCH};
 
 use anyhow::{ensure, Result};
 use async_channel::Receiver;
@@ -74,17 +80,20 @@
 use tokio::time::timeout;
 
 use crate::chat::{send_msg, ChatId};
+use crate::config::Config;
 use crate::constants::{DC_CHAT_ID_LAST_SPECIAL, DC_CHAT_ID_TRASH};
 use crate::contact::ContactId;
 use crate::context::Context;
 use crate::download::MIN_DELETE_SERVER_AFTER;
 use crate::events::EventType;
+use crate::headerdef::HeaderDef;
 use crate::log::LogExt;
-use crate::message::{Message, MessageState, MsgId, Viewtype};
-use crate::mimeparser::SystemMessage;
+use crate::message::{emit_msgs_deleted, Message, MessageState, MsgId, Viewtype};
+use crate::mimeparser::{parse_message_ids, MimeMessage, SystemMessage};
+use crate::param::Param;
 use crate::sql::{self, params_iter};
 use crate::stock_str;
-use crate::tools::{duration_to_str, time};
+use crate::tools::duration_to_str;
 
 /// Ephemeral timer value.
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
@@ -207,6 +216,52 @@ pub(crate) async fn inner_set_ephemeral_timer(
         Ok(())
     }
 
+    /// Remembers the ephemeral timer used by a chat member in a received message.
+    pub(crate) async fn set_ephemeral_timer_ack(
+        self,
+        context: &Context,
+        contact_id: ContactId,
+        timer: Timer,
+        timestamp: i64,
+    ) -> Result<()> {
+        context
+            .sql
+            .execute(
+                "INSERT INTO ephemeral_timer_acks (chat_id, contact_id, timer, timestamp)
+                 VALUES (?,?,?,?)
+                 ON CONFLICT(chat_id, contact_id)
+                 DO UPDATE SET timer=excluded.timer, timestamp=excluded.timestamp
+                 WHERE excluded.timestamp>=timestamp",
+                (self, contact_id, timer, timestamp),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the chat members who acknowledged the current ephemeral timer.
+    ///
+    /// A member acknowledged the timer if their latest message in the chat
+    /// uses the same timer. Members not listed have not sent a message since the
+    /// timer was changed or use another client which ignores the timer.
+    /// `ContactId::SELF` is never returned.
+    pub async fn get_ephemeral_timer_acks(self, context: &Context) -> Result<Vec<ContactId>> {
+        let timer = self.get_ephemeral_timer(context).await?;
+        context
+            .sql
+            .query_map(
+                "SELECT cc.contact_id
+                 FROM chats_contacts cc
+                 INNER JOIN ephemeral_timer_acks a
+                 ON a.chat_id=cc.chat_id AND a.contact_id=cc.contact_id
+                 WHERE cc.chat_id=? AND cc.contact_id!=? AND a.timer=?
+                 ORDER BY cc.contact_id",
+                (self, ContactId::SELF, timer),
+                |row| row.get::<_, ContactId>(0),
+                |ids| ids.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await
+    }
+
     /// Set ephemeral message timer value in seconds.
     ///
     /// If timer value is 0, disable ephemeral message timer.
@@ -300,7 +355,7 @@ pub(crate) async fn ephemeral_timer(self, context: &Context) -> Result<Timer> {
     /// Starts ephemeral message timer for the message if it is not started yet.
     pub(crate) async fn start_ephemeral_timer(self, context: &Context) -> Result<()> {
         if let Timer::Enabled { duration } = self.ephemeral_timer(context).await? {
-            let ephemeral_timestamp = time().saturating_add(duration.into());
+            let ephemeral_timestamp = context.clock.unix_time().saturating_add(duration.into());
 
             context
                 .sql
@@ -321,7 +376,7 @@ pub(crate) async fn start_ephemeral_timers_msgids(
     context: &Context,
     msg_ids: &[MsgId],
 ) -> Result<()> {
-    let now = time();
+    let now = context.clock.unix_time();
     let count = context
         .sql
         .execute(
@@ -418,6 +473,54 @@ async fn select_expired_messages(
     Ok(rows)
 }
 
+/// Returns true if a received message quotes an ephemeral message
+/// after the sender's device should have deleted it.
+///
+/// The sender's timer for the quoted message starts when the sender read it,
+/// which is only known if the sender sent a read receipt or the quoted message
+/// was sent by the sender themselves. In other cases false is returned.
+pub(crate) async fn quotes_expired_message(
+    context: &Context,
+    mime_parser: &MimeMessage,
+    from_id: ContactId,
+    sent_timestamp: i64,
+) -> Result<bool> {
+    if !mime_parser
+        .parts
+        .iter()
+        .any(|part| part.param.exists(Param::Quote))
+    {
+        return Ok(false);
+    }
+    let in_reply_to = match mime_parser.get_header(HeaderDef::InReplyTo) {
+        Some(in_reply_to) => in_reply_to,
+        None => return Ok(false),
+    };
+    for rfc724_mid in parse_message_ids(in_reply_to) {
+        let deadline: Option<i64> = context
+            .sql
+            .query_get_value(
+                "SELECT MIN(deadline) FROM (
+                   SELECT mdns.timestamp_sent + m.ephemeral_timer AS deadline
+                   FROM msgs m INNER JOIN msgs_mdns mdns ON mdns.msg_id=m.id
+                   WHERE m.rfc724_mid=? AND mdns.contact_id=? AND m.ephemeral_timer>0
+                   UNION ALL
+                   SELECT timestamp_sent + ephemeral_timer AS deadline
+                   FROM msgs
+                   WHERE rfc724_mid=? AND from_id=? AND ephemeral_timer>0
+                 )",
+                (&rfc724_mid, from_id, &rfc724_mid, from_id),
+            )
+            .await?;
+        if let Some(deadline) = deadline {
+            if deadline < sent_timestamp {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
 /// Deletes messages which are expired according to
 /// `delete_device_after` setting or `ephemeral_timestamp` column.
 ///
@@ -425,7 +528,13 @@ async fn select_expired_messages(
 /// if messages are deleted.
 pub(crate) async fn delete_expired_messages(context: &Context, now: i64) -> Result<()> {
     let rows = select_expired_messages(context, now).await?;
+    trash_messages(context, rows).await
+}
 
+/// Moves the messages to the trash chat, removing their content.
+///
+/// Emits relevant `MsgsChanged` and `WebxdcInstanceDeleted` events.
+async fn trash_messages(context: &Context, rows: Vec<(MsgId, ChatId, Viewtype)>) -> Result<()> {
     if !rows.is_empty() {
         info!(context, "Attempting to delete {} messages.", rows.len());
 
@@ -455,6 +564,7 @@ pub(crate) async fn delete_expired_messages(context: &Context, now: i64) -> Resu
             })
             .await?;
 
+        emit_msgs_deleted(context, msgs_changed.iter().copied());
         for (chat_id, msg_id) in msgs_changed {
             context.emit_msgs_changed(chat_id, msg_id);
         }
@@ -467,6 +577,56 @@ pub(crate) async fn delete_expired_messages(context: &Context, now: i64) -> Resu
     Ok(())
 }
 
+/// Deletes messages from the device which exceed the number of messages
+/// to keep according to the retention policy of their chat.
+///
+/// Drafts and hidden messages are neither deleted nor counted.
+/// See [`ChatId::set_retention`].
+pub(crate) async fn delete_retained_messages(context: &Context) -> Result<()> {
+    let rows = context
+        .sql
+        .query_map(
+            r#"
+SELECT m.id, m.chat_id, m.type
+FROM (
+  SELECT id, chat_id, type,
+         ROW_NUMBER() OVER (PARTITION BY chat_id ORDER BY timestamp DESC, id DESC) AS n
+  FROM msgs
+  WHERE chat_id IN (SELECT id FROM chats WHERE retention_keep_last>0)
+    AND hidden=0
+    AND state!=?
+) m
+INNER JOIN chats c ON m.chat_id=c.id
+WHERE m.n > c.retention_keep_last
+"#,
+            (MessageState::OutDraft,),
+            |row| {
+                let id: MsgId = row.get(0)?;
+                let chat_id: ChatId = row.get(1)?;
+                let viewtype: Viewtype = row.get(2)?;
+                Ok((id, chat_id, viewtype))
+            },
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await?;
+
+    let mut deleted: BTreeMap<ChatId, u64> = BTreeMap::new();
+    for (_, chat_id, _) in &rows {
+        *deleted.entry(*chat_id).or_default() += 1;
+    }
+    trash_messages(context, rows).await?;
+    for (chat_id, count) in deleted {
+        context
+            .sql
+            .execute(
+                "UPDATE chats SET retention_deleted_local=retention_deleted_local+? WHERE id=?",
+                (count, chat_id),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
 /// Calculates the next timestamp when a message will be deleted due to
 /// `delete_device_after` setting being set.
 async fn next_delete_device_after_timestamp(context: &Context) -> Result<Option<i64>> {
@@ -545,7 +705,7 @@ pub(crate) async fn ephemeral_loop(context: &Context, interrupt_receiver: Receiv
     loop {
         let ephemeral_timestamp = next_expiration_timestamp(context).await;
 
-        let now = SystemTime::now();
+        let now = context.clock.now();
         let until = if let Some(ephemeral_timestamp) = ephemeral_timestamp {
             UNIX_EPOCH
                 + Duration::from_secs(ephemeral_timestamp.try_into().unwrap_or(u64::MAX))
@@ -567,45 +727,320 @@ pub(crate) async fn ephemeral_loop(context: &Context, interrupt_receiver: Receiv
             }
         }
 
-        delete_expired_messages(context, time())
+        delete_expired_messages(context, context.clock.unix_time())
             .await
             .log_err(context)
             .ok();
     }
 }
 
+/// Reason why a message is deleted from the server.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum ServerDeletionReason {
+    /// The ephemeral timer of the message has expired.
+    Ephemeral,
+
+    /// The message is older than the global `delete_server_after` setting.
+    DeleteServerAfter,
+
+    /// The message is older than the setting of its folder,
+    /// see [`set_folder_delete_server_after`].
+    FolderPolicy,
+
+    /// The message is older than the retention policy of its chat,
+    /// see [`ChatId::set_retention`].
+    Retention,
+}
+
+/// Message which is due for deletion from the server, see [`get_server_deletions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerDeletion {
+    /// ID of the message.
+    pub msg_id: MsgId,
+
+    /// IMAP folder containing the message.
+    pub folder: String,
+
+    /// UID of the message in the folder.
+    pub uid: u32,
+
+    /// Reason for the deletion.
+    pub reason: ServerDeletionReason,
+}
+
+/// Sets how long messages are kept in the given IMAP folder,
+/// overriding the global `delete_server_after` setting for this folder.
+///
+/// The value has the same meaning as `delete_server_after`:
+/// 0 means never delete, 1 means delete at once after download
+/// and other values are the number of seconds after which messages are deleted.
+/// `None` removes the override, so that `delete_server_after` is used again.
+pub async fn set_folder_delete_server_after(
+    context: &Context,
+    folder: &str,
+    delete_server_after: Option<i64>,
+) -> Result<()> {
+    match delete_server_after {
+        Some(delete_server_after) => {
+            ensure!(
+                delete_server_after >= 0,
+                "Invalid delete_server_after value {delete_server_after}"
+            );
+            context
+                .sql
+                .execute(
+                    "INSERT INTO folder_delete_server_after (folder, delete_server_after)
+                     VALUES (?, ?)
+                     ON CONFLICT (folder)
+                     DO UPDATE SET delete_server_after=excluded.delete_server_after",
+                    (folder, delete_server_after),
+                )
+                .await?;
+        }
+        None => {
+            context
+                .sql
+                .execute(
+                    "DELETE FROM folder_delete_server_after WHERE folder=?",
+                    (folder,),
+                )
+                .await?;
+        }
+    }
+    context.scheduler.interrupt_ephemeral_task().await;
+    Ok(())
+}
+
+/// Returns the folders with a setting overriding `delete_server_after`,
+/// see [`set_folder_delete_server_after`].
+pub async fn get_folder_delete_server_after(context: &Context) -> Result<BTreeMap<String, i64>> {
+    context
+        .sql
+        .query_map(
+            "SELECT folder, delete_server_after FROM folder_delete_server_after",
+            (),
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+            |rows| {
+                rows.collect::<Result<BTreeMap<_, _>, _>>()
+                    .map_err(Into::into)
+            },
+        )
+        .await
+}
+
+/// Returns timestamps before which fully and partially downloaded messages
+/// are deleted according to the given `delete_server_after` value.
+fn delete_server_after_thresholds(now: i64, delete_server_after: i64) -> (i64, i64) {
+    let delete_server_after = match delete_server_after {
+        0 => return (0, 0),
+        1 => 0,
+        x => x,
+    };
+    (
+        now - delete_server_after,
+        now - max(delete_server_after, MIN_DELETE_SERVER_AFTER),
+    )
+}
+
+/// Returns messages on the server which are due for deletion,
+/// keyed by the row ID in the `imap` table.
+///
+/// Messages already marked for deletion are skipped.
+/// Retention policies of chats are only checked if `with_retention` is set.
+async fn select_server_deletions(
+    context: &Context,
+    now: i64,
+    target: &str,
+    with_retention: bool,
+) -> Result<BTreeMap<i64, ServerDeletion>> {
+    let (threshold_timestamp, threshold_timestamp_extended) = delete_server_after_thresholds(
+        now,
+        context
+            .get_config_int(Config::DeleteServerAfter)
+            .await?
+            .into(),
+    );
+
+    let mut deletions = BTreeMap::new();
+    let mut add_deletions = |rows: Vec<(i64, ServerDeletion)>| {
+        for (id, deletion) in rows {
+            deletions.entry(id).or_insert(deletion);
+        }
+    };
+
+    add_deletions(
+        context
+            .sql
+            .query_map(
+                "SELECT i.id, m.id, i.folder, i.uid,
+                        m.ephemeral_timestamp != 0 AND m.ephemeral_timestamp <= ?1
+                 FROM imap i
+                 INNER JOIN msgs m ON i.rfc724_mid=m.rfc724_mid
+                 WHERE i.target!=?4
+                 AND ((m.ephemeral_timestamp != 0 AND m.ephemeral_timestamp <= ?1)
+                      OR (i.folder NOT IN (SELECT folder FROM folder_delete_server_after)
+                          AND ((m.download_state = 0 AND m.timestamp < ?2) OR
+                               (m.download_state != 0 AND m.timestamp < ?3))))
+                 GROUP BY i.id",
+                (
+                    now,
+                    threshold_timestamp,
+                    threshold_timestamp_extended,
+                    target,
+                ),
+                |row| {
+                    let reason = if row.get::<_, bool>(4)? {
+                        ServerDeletionReason::Ephemeral
+                    } else {
+                        ServerDeletionReason::DeleteServerAfter
+                    };
+                    Ok((
+                        row.get(0)?,
+                        ServerDeletion {
+                            msg_id: row.get(1)?,
+                            folder: row.get(2)?,
+                            uid: row.get(3)?,
+                            reason,
+                        },
+                    ))
+                },
+                |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await?,
+    );
+
+    for (folder, delete_server_after) in get_folder_delete_server_after(context).await? {
+        let (threshold_timestamp, threshold_timestamp_extended) =
+            delete_server_after_thresholds(now, delete_server_after);
+        add_deletions(
+            context
+                .sql
+                .query_map(
+                    "SELECT i.id, m.id, i.uid
+                     FROM imap i
+                     INNER JOIN msgs m ON i.rfc724_mid=m.rfc724_mid
+                     WHERE i.folder=? AND i.target!=?
+                     AND ((m.download_state = 0 AND m.timestamp < ?) OR
+                          (m.download_state != 0 AND m.timestamp < ?))
+                     GROUP BY i.id",
+                    (
+                        &folder,
+                        target,
+                        threshold_timestamp,
+                        threshold_timestamp_extended,
+                    ),
+                    |row| {
+                        Ok((
+                            row.get(0)?,
+                            ServerDeletion {
+                                msg_id: row.get(1)?,
+                                folder: folder.clone(),
+                                uid: row.get(2)?,
+                                reason: ServerDeletionReason::FolderPolicy,
+                            },
+                        ))
+                    },
+                    |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+                )
+                .await?,
+        );
+    }
+
+    if with_retention {
+        add_deletions(
+            context
+                .sql
+                .query_map(
+                    "SELECT i.id, m.id, i.folder, i.uid
+                     FROM imap i
+                     INNER JOIN msgs m ON i.rfc724_mid=m.rfc724_mid
+                     INNER JOIN chats c ON m.chat_id=c.id
+                     WHERE i.target!=?
+                     AND c.retention_server_days>0
+                     AND m.timestamp < ? - c.retention_server_days*86400
+                     GROUP BY i.id",
+                    (target, now),
+                    |row| {
+                        Ok((
+                            row.get(0)?,
+                            ServerDeletion {
+                                msg_id: row.get(1)?,
+                                folder: row.get(2)?,
+                                uid: row.get(3)?,
+                                reason: ServerDeletionReason::Retention,
+                            },
+                        ))
+                    },
+                    |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+                )
+                .await?,
+        );
+    }
+
+    Ok(deletions)
+}
+
+/// Returns the messages which would be deleted from the server
+/// the next time expired messages are scheduled for deletion,
+/// without deleting anything.
+pub async fn get_server_deletions(context: &Context) -> Result<Vec<ServerDeletion>> {
+    let now = context.clock.unix_time();
+    let target = context.get_delete_msgs_target().await?;
+    let deletions = select_server_deletions(context, now, &target, true).await?;
+    Ok(deletions.into_values().collect())
+}
+
 /// Schedules expired IMAP messages for deletion.
 pub(crate) async fn delete_expired_imap_messages(context: &Context) -> Result<()> {
-    let now = time();
-
-    let (threshold_timestamp, threshold_timestamp_extended) =
-        match context.get_config_delete_server_after().await? {
-            None => (0, 0),
-            Some(delete_server_after) => (
-                now - delete_server_after,
-                now - max(delete_server_after, MIN_DELETE_SERVER_AFTER),
-            ),
-        };
+    let now = context.clock.unix_time();
     let target = context.get_delete_msgs_target().await?;
 
+    let deletions = select_server_deletions(context, now, &target, false).await?;
+    if !deletions.is_empty() {
+        let target = target.clone();
+        context
+            .sql
+            .transaction(move |transaction| {
+                let mut stmt = transaction.prepare("UPDATE imap SET target=? WHERE id=?")?;
+                for id in deletions.keys() {
+                    stmt.execute((&target, id))?;
+                }
+                Ok(())
+            })
+            .await?;
+    }
+
+    // Per-chat retention policies, see `ChatId::set_retention()`.
+    // Messages already marked for deletion are not counted twice,
+    // and a message stored in several folders is counted once.
     context
         .sql
-        .execute(
-            "UPDATE imap
-             SET target=?
-             WHERE rfc724_mid IN (
-               SELECT rfc724_mid FROM msgs
-               WHERE ((download_state = 0 AND timestamp < ?) OR
-                      (download_state != 0 AND timestamp < ?) OR
-                      (ephemeral_timestamp != 0 AND ephemeral_timestamp <= ?))
-             )",
-            (
-                &target,
-                threshold_timestamp,
-                threshold_timestamp_extended,
-                now,
-            ),
-        )
+        .transaction(|transaction| {
+            transaction.execute(
+                "UPDATE chats
+                 SET retention_deleted_server=retention_deleted_server+(
+                   SELECT COUNT(DISTINCT i.rfc724_mid) FROM imap i
+                   INNER JOIN msgs m ON i.rfc724_mid=m.rfc724_mid
+                   WHERE m.chat_id=chats.id AND i.target!=?
+                   AND m.timestamp < ? - chats.retention_server_days*86400
+                 )
+                 WHERE retention_server_days>0",
+                (&target, now),
+            )?;
+            transaction.execute(
+                "UPDATE imap
+                 SET target=?
+                 WHERE target!=?
+                 AND rfc724_mid IN (
+                   SELECT m.rfc724_mid FROM msgs m
+                   INNER JOIN chats c ON m.chat_id=c.id
+                   WHERE c.retention_server_days>0
+                   AND m.timestamp < ? - c.retention_server_days*86400
+                 )",
+                (&target, &target, now),
+            )?;
+            Ok(())
+        })
         .await?;
 
     Ok(())
@@ -630,7 +1065,7 @@ pub(crate) async fn start_ephemeral_timers(context: &Context) -> Result<()> {
     AND ephemeral_timestamp = 0 \
     AND state NOT IN (?, ?, ?)",
             (
-                time(),
+                context.clock.unix_time(),
                 MessageState::InFresh,
                 MessageState::InNoticed,
                 MessageState::OutDraft,
@@ -649,6 +1084,7 @@ mod tests {
     use crate::receive_imf::receive_imf;
     use crate::test_utils::TestContext;
     use crate::timesmearing::MAX_SECONDS_TO_LEND_FROM_FUTURE;
+    use crate::tools::time;
     use crate::{
         chat::{self, create_group_chat, send_text_msg, Chat, ChatItem, ProtectionStatus},
         tools::IsNoneOrEmpty,
@@ -1208,6 +1644,227 @@ async fn remove_uid(context: &Context, id: u32) -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_folder_delete_server_after() -> Result<()> {
+        const HOUR: i64 = 60 * 60;
+        let t = TestContext::new_alice().await;
+        let now = time();
+        for (id, folder, timestamp) in &[
+            (100, "INBOX", now - HOUR),
+            (200, "DeltaChat", now - 2 * HOUR),
+            (300, "DeltaChat", now - 31 * 24 * HOUR),
+            (400, "Archive", now - 2 * HOUR),
+        ] {
+            let message_id = id.to_string();
+            t.sql
+                .execute(
+                    "INSERT INTO msgs (id, rfc724_mid, timestamp) VALUES (?,?,?)",
+                    (id, &message_id, timestamp),
+                )
+                .await?;
+            t.sql
+                .execute(
+                    "INSERT INTO imap (rfc724_mid, folder, uid, target) VALUES (?,?,?,?)",
+                    (&message_id, folder, id, folder),
+                )
+                .await?;
+        }
+
+        t.set_config(Config::DeleteServerAfter, Some(&*(HOUR + 60).to_string()))
+            .await?;
+        set_folder_delete_server_after(&t, "INBOX", Some(1)).await?;
+        set_folder_delete_server_after(&t, "DeltaChat", Some(30 * 24 * HOUR)).await?;
+        assert_eq!(get_folder_delete_server_after(&t).await?.len(), 2);
+
+        // Dry run does not mark anything for deletion.
+        let deletions = get_server_deletions(&t).await?;
+        assert_eq!(
+            deletions
+                .iter()
+                .map(|d| (d.msg_id, d.folder.as_str(), d.reason))
+                .collect::<Vec<_>>(),
+            vec![
+                (MsgId::new(100), "INBOX", ServerDeletionReason::FolderPolicy),
+                (
+                    MsgId::new(300),
+                    "DeltaChat",
+                    ServerDeletionReason::FolderPolicy
+                ),
+                (
+                    MsgId::new(400),
+                    "Archive",
+                    ServerDeletionReason::DeleteServerAfter
+                ),
+            ]
+        );
+        assert_eq!(
+            t.sql
+                .count("SELECT COUNT(*) FROM imap WHERE target=''", ())
+                .await?,
+            0
+        );
+
+        delete_expired_imap_messages(&t).await?;
+        let deleted: Vec<String> = t
+            .sql
+            .query_map(
+                "SELECT rfc724_mid FROM imap WHERE target='' ORDER BY rfc724_mid",
+                (),
+                |row| row.get(0),
+                |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await?;
+        assert_eq!(deleted, vec!["100", "300", "400"]);
+        assert!(get_server_deletions(&t).await?.is_empty());
+
+        // Without the folder setting, the global setting applies to the folder again.
+        set_folder_delete_server_after(&t, "DeltaChat", None).await?;
+        assert_eq!(get_folder_delete_server_after(&t).await?.len(), 1);
+        let deletions = get_server_deletions(&t).await?;
+        assert_eq!(deletions.len(), 1);
+        assert_eq!(deletions[0].msg_id, MsgId::new(200));
+        assert_eq!(deletions[0].reason, ServerDeletionReason::DeleteServerAfter);
+
+        Ok(())
+    }
+
+    /// Tests that deleting messages at once after receiving honors the folder settings.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_folder_delete_server_after_at_once() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        t.set_config(Config::DeleteServerAfter, Some("1")).await?;
+        // Explicitly set to delete at once, the same as the global setting.
+        set_folder_delete_server_after(&t, "INBOX", Some(1)).await?;
+        // Keeps messages despite the global setting.
+        set_folder_delete_server_after(&t, "DeltaChat", Some(3600)).await?;
+
+        for (i, folder) in ["INBOX", "DeltaChat", "Archive"].iter().enumerate() {
+            t.sql
+                .execute(
+                    "INSERT INTO imap (rfc724_mid, folder, uid, target) VALUES (?,?,?,?)",
+                    ("at-once@example.com", folder, i as u32 + 1, folder),
+                )
+                .await?;
+        }
+        receive_imf(
+            &t,
+            b"From: Bob <bob@example.com>\n\
+                    To: Alice <alice@example.org>\n\
+                    Chat-Version: 1.0\n\
+                    Subject: Subject\n\
+                    Message-ID: <at-once@example.com>\n\
+                    Date: Sun, 22 Mar 2020 00:10:00 +0000\n\
+                    \n\
+                    hello\n",
+            false,
+        )
+        .await?;
+
+        let targets: Vec<(String, String)> = t
+            .sql
+            .query_map(
+                "SELECT folder, target FROM imap ORDER BY uid",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+                |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await?;
+        assert_eq!(
+            targets,
+            vec![
+                ("INBOX".to_string(), "".to_string()),
+                ("DeltaChat".to_string(), "DeltaChat".to_string()),
+                ("Archive".to_string(), "".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_retention() -> Result<()> {
+        const DAY: i64 = 24 * 60 * 60;
+        let t = TestContext::new_alice().await;
+        let chat_id = create_group_chat(&t, ProtectionStatus::Unprotected, "Group").await?;
+        for i in 0..5 {
+            send_text_msg(&t, chat_id, format!("Message {i}")).await?;
+        }
+        chat_id
+            .set_retention(
+                &t,
+                chat::Retention {
+                    keep_last: 2,
+                    server_days: 7,
+                },
+            )
+            .await?;
+
+        delete_retained_messages(&t).await?;
+        let msg_ids: Vec<(MsgId, String)> = t
+            .sql
+            .query_map(
+                "SELECT id, rfc724_mid FROM msgs WHERE chat_id=? ORDER BY id",
+                (chat_id,),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+                |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await?;
+        assert_eq!(msg_ids.len(), 2);
+        assert_eq!(
+            Message::load_from_db(&t, msg_ids[0].0).await?.get_text(),
+            Some("Message 3".to_string())
+        );
+
+        // Nothing more is deleted on the next run.
+        delete_retained_messages(&t).await?;
+        let info = Chat::load_from_db(&t, chat_id).await?.get_info(&t).await?;
+        assert_eq!(info.retention_deleted_local, 3);
+        assert_eq!(info.retention_deleted_server, 0);
+
+        t.sql
+            .execute(
+                "UPDATE msgs SET timestamp=? WHERE id=?",
+                (time() - 8 * DAY, msg_ids[0].0),
+            )
+            .await?;
+        for (uid, (_, rfc724_mid)) in msg_ids.iter().enumerate() {
+            t.sql
+                .execute(
+                    "INSERT INTO imap (rfc724_mid, folder, uid, target) VALUES (?,'INBOX',?,'INBOX')",
+                    (rfc724_mid, uid + 1),
+                )
+                .await?;
+        }
+        // A copy of the message in another folder is not counted separately.
+        t.sql
+            .execute(
+                "INSERT INTO imap (rfc724_mid, folder, uid, target) VALUES (?,'DeltaChat',1,'DeltaChat')",
+                (&msg_ids[0].1,),
+            )
+            .await?;
+        delete_expired_imap_messages(&t).await?;
+        delete_expired_imap_messages(&t).await?;
+        assert_eq!(
+            t.sql
+                .count(
+                    "SELECT COUNT(*) FROM imap WHERE target='' AND rfc724_mid=?",
+                    (&msg_ids[0].1,),
+                )
+                .await?,
+            2
+        );
+        assert_eq!(
+            t.sql
+                .count("SELECT COUNT(*) FROM imap WHERE target=''", ())
+                .await?,
+            2
+        );
+        let info = Chat::load_from_db(&t, chat_id).await?.get_info(&t).await?;
+        assert_eq!(info.retention_deleted_server, 1);
+
+        Ok(())
+    }
+
     // Regression test for a bug in the timer rollback protection.
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_ephemeral_timer_references() -> Result<()> {
@@ -1292,4 +1949,67 @@ async fn test_ephemeral_timer_references() -> Result<()> {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_ephemeral_timer_not_honored() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+
+        receive_imf(
+            &alice,
+            b"From: Bob <bob@example.net>\n\
+                    To: Alice <alice@example.org>\n\
+                    Chat-Version: 1.0\n\
+                    Subject: Subject\n\
+                    Message-ID: <first@example.net>\n\
+                    Date: Sun, 22 Mar 2020 00:10:00 +0000\n\
+                    Ephemeral-Timer: 60\n\
+                    \n\
+                    hello\n",
+            false,
+        )
+        .await?;
+        let msg = alice.get_last_msg().await;
+        let chat_id = msg.chat_id;
+        assert_eq!(
+            chat_id.get_ephemeral_timer_acks(&alice).await?,
+            vec![msg.from_id]
+        );
+
+        // Bob quotes his own message long after it expired.
+        receive_imf(
+            &alice,
+            b"From: Bob <bob@example.net>\n\
+                    To: Alice <alice@example.org>\n\
+                    Chat-Version: 1.0\n\
+                    Subject: Subject\n\
+                    Message-ID: <second@example.net>\n\
+                    In-Reply-To: <first@example.net>\n\
+                    Date: Sun, 22 Mar 2020 00:15:00 +0000\n\
+                    Ephemeral-Timer: 60\n\
+                    \n\
+                    > hello\n\
+                    \n\
+                    reply\n",
+            false,
+        )
+        .await?;
+        let reply = alice.get_last_msg().await;
+        alice
+            .evtracker
+            .get_matching(|evt| {
+                matches!(
+                    evt,
+                    EventType::EphemeralTimerNotHonored { msg_id, .. } if *msg_id == reply.id
+                )
+            })
+            .await;
+
+        // Bob did not send a message since the timer was changed.
+        chat_id
+            .inner_set_ephemeral_timer(&alice, Timer::Enabled { duration: 3600 })
+            .await?;
+        assert!(chat_id.get_ephemeral_timer_acks(&alice).await?.is_empty());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,165 @@
+//! # Error codes.
+//!
+//! Errors of the core are [`anyhow::Error`]s with messages meant for humans.
+//! [`ErrorCode::of`] classifies them into a few stable categories,
+//! so that UIs and bots can decide whether to retry or what to show
+//! without parsing the messages.
+//!
+//! Most errors are classified by their causes, e.g. a failed database lookup
+//! or an I/O error of a network connection.
+//! Where the cause is not enough, the error is wrapped with [`ErrorCode::wrap`],
+//! which attaches the code without changing the message of the error.
+
+use std::fmt;
+use std::io;
+
+use crate::chat::MessageTooLarge;
+
+/// Category of an error.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The account is not configured or the configuration is invalid.
+    Configuration,
+
+    /// The server could not be reached.
+    Network,
+
+    /// The server rejected the credentials.
+    Auth,
+
+    /// The requested chat, message, contact or account does not exist.
+    NotFound,
+
+    /// A size limit or quota is exceeded.
+    Quota,
+
+    /// Encryption, decryption or signing failed.
+    Crypto,
+}
+
+impl ErrorCode {
+    /// Returns the code of an error, `None` if the error fits no category.
+    pub fn of(error: &anyhow::Error) -> Option<ErrorCode> {
+        error.chain().find_map(|cause| {
+            if let Some(coded) = cause.downcast_ref::<CodedError>() {
+                Some(coded.code)
+            } else if cause.downcast_ref::<MessageTooLarge>().is_some() {
+                Some(ErrorCode::Quota)
+            } else if cause.downcast_ref::<pgp::errors::Error>().is_some() {
+                Some(ErrorCode::Crypto)
+            } else if cause
+                .downcast_ref::<tokio::time::error::Elapsed>()
+                .is_some()
+            {
+                Some(ErrorCode::Network)
+            } else if let Some(err) = cause.downcast_ref::<rusqlite::Error>() {
+                match err {
+                    rusqlite::Error::QueryReturnedNoRows => Some(ErrorCode::NotFound),
+                    _ => None,
+                }
+            } else if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+                (err.is_connect() || err.is_timeout()).then_some(ErrorCode::Network)
+            } else if let Some(err) = cause.downcast_ref::<io::Error>() {
+                is_network_error(err).then_some(ErrorCode::Network)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Attaches the code to an error.
+    ///
+    /// The returned error is displayed like the wrapped error
+    /// and has the same causes.
+    pub fn wrap(self, error: impl Into<anyhow::Error>) -> anyhow::Error {
+        anyhow::Error::new(CodedError {
+            code: self,
+            error: error.into(),
+        })
+    }
+}
+
+fn is_network_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::AddrNotAvailable
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::TimedOut
+    )
+}
+
+/// Error wrapped by [`ErrorCode::wrap`].
+///
+/// It takes the place of the wrapped error in the chain of causes:
+/// it displays as the wrapped error and its source is the source of the wrapped error.
+#[derive(Debug)]
+struct CodedError {
+    code: ErrorCode,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only the outermost message, the causes are returned by `source()`.
+        fmt::Display::fmt(&*self.error, f)
+    }
+}
+
+impl std::error::Error for CodedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context as _};
+
+    use super::*;
+    use crate::chat::{Chat, ChatId};
+    use crate::message::{Message, MsgId};
+    use crate::test_utils::TestContext;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_error_code() {
+        let t = TestContext::new_alice().await;
+        let err = Message::load_from_db(&t, MsgId::new(1000))
+            .await
+            .unwrap_err();
+        assert_eq!(ErrorCode::of(&err), Some(ErrorCode::NotFound));
+        let err = Chat::load_from_db(&t, ChatId::new(1000)).await.unwrap_err();
+        assert_eq!(ErrorCode::of(&err), Some(ErrorCode::NotFound));
+
+        let err = io::Error::from(io::ErrorKind::ConnectionRefused);
+        let err = anyhow::Error::new(err).context("Failed to connect");
+        assert_eq!(ErrorCode::of(&err), Some(ErrorCode::Network));
+        assert_eq!(
+            ErrorCode::of(&anyhow::Error::new(io::Error::from(
+                io::ErrorKind::NotFound
+            ))),
+            None
+        );
+        assert_eq!(ErrorCode::of(&anyhow!("Something failed")), None);
+
+        let err = ErrorCode::Auth
+            .wrap(anyhow!("Invalid password").context("Cannot login"))
+            .context("Failed to configure");
+        assert_eq!(ErrorCode::of(&err), Some(ErrorCode::Auth));
+        assert_eq!(
+            format!("{err:#}"),
+            "Failed to configure: Cannot login: Invalid password"
+        );
+        assert_eq!(format!("{err}"), "Failed to configure");
+        let err = ErrorCode::Auth.wrap(anyhow!("Invalid password").context("Cannot login"));
+        assert_eq!(format!("{err}"), "Cannot login");
+
+        // Causes of the wrapped error can be found.
+        let err = ErrorCode::Configuration
+            .wrap(anyhow::Error::new(io::Error::from(io::ErrorKind::NotFound)).context("Failed"));
+        assert!(err.chain().any(|cause| cause.is::<io::Error>()));
+    }
+}
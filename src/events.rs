@@ -1,21 +1,79 @@
 //! # Events specification.
 
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use async_channel::{self as channel, Receiver, Sender, TrySendError};
 use serde::Serialize;
+use strum_macros::AsRefStr;
 
 use crate::chat::ChatId;
 use crate::contact::ContactId;
 use crate::ephemeral::Timer as EphemeralTimer;
 use crate::message::MsgId;
+use crate::notification::NotificationIntent;
 use crate::webxdc::StatusUpdateSerial;
 
+/// Number of windows kept by [`Coalescer`] before expired ones are removed.
+const MAX_COALESCING_WINDOWS: usize = 1000;
+
 /// Event channel.
 #[derive(Debug, Clone)]
 pub struct Events {
     receiver: Receiver<Event>,
     sender: Sender<Event>,
+
+    /// Events held back by [`Events::set_coalescing_interval`].
+    coalescer: Arc<Mutex<Coalescer>>,
+
+    /// Last [`Event::seq`] by account ID.
+    sequences: Arc<Mutex<HashMap<u32, u64>>>,
+}
+
+/// Throttles `MsgsChanged` and `ChatModified` events per chat.
+///
+/// The first event of a chat is emitted immediately and opens a window of `interval`.
+/// Further events of the chat within the window are merged into one event,
+/// which is emitted when the window ends and opens the next window.
+#[derive(Debug, Default)]
+struct Coalescer {
+    /// Length of the windows, zero if coalescing is disabled.
+    interval: Duration,
+
+    windows: HashMap<CoalescingKey, CoalescingWindow>,
+}
+
+/// Account, event variant and chat of coalesced events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct CoalescingKey {
+    account_id: u32,
+    modified: bool,
+    chat_id: ChatId,
+}
+
+impl CoalescingKey {
+    fn new(event: &Event) -> Option<Self> {
+        let (modified, chat_id) = match event.typ {
+            EventType::MsgsChanged { chat_id, .. } => (false, chat_id),
+            EventType::ChatModified(chat_id) => (true, chat_id),
+            _ => return None,
+        };
+        Some(Self {
+            account_id: event.id,
+            modified,
+            chat_id,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct CoalescingWindow {
+    end: Instant,
+
+    /// Merged event to emit when the window ends.
+    pending: Option<Event>,
 }
 
 impl Default for Events {
@@ -29,22 +87,126 @@ impl Events {
     pub fn new() -> Self {
         let (sender, receiver) = channel::bounded(1_000);
 
-        Self { receiver, sender }
+        Self {
+            receiver,
+            sender,
+            coalescer: Default::default(),
+            sequences: Default::default(),
+        }
+    }
+
+    /// Sets the interval in which `MsgsChanged` and `ChatModified` events
+    /// of the same chat are coalesced, zero to disable coalescing.
+    ///
+    /// The first event of a chat is emitted immediately,
+    /// further events within the interval are emitted as a single event at its end.
+    /// `MsgsChanged` events of different messages are merged into one with `msg_id` 0,
+    /// i.e. all messages of the chat should be reloaded.
+    /// This keeps UIs responsive during bulk operations emitting thousands of events.
+    pub fn set_coalescing_interval(&self, interval: Duration) {
+        let mut coalescer = self.coalescer.lock().unwrap();
+        coalescer.interval = interval;
+        if interval.is_zero() {
+            let pending: Vec<Event> = coalescer
+                .windows
+                .drain()
+                .filter_map(|(_, window)| window.pending)
+                .collect();
+            drop(coalescer);
+            for event in pending {
+                self.send(event);
+            }
+        }
     }
 
     /// Emits an event.
     pub fn emit(&self, event: Event) {
-        match self.sender.try_send(event) {
-            Ok(()) => {}
-            Err(TrySendError::Full(event)) => {
-                // when we are full, we pop remove the oldest event and push on the new one
-                let _ = self.receiver.try_recv();
-
-                // try again
-                self.emit(event);
+        let key = match CoalescingKey::new(&event) {
+            Some(key) => key,
+            None => return self.send(event),
+        };
+        // Flushing coalesced events requires a runtime.
+        let runtime = match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => runtime,
+            Err(_) => return self.send(event),
+        };
+
+        let mut coalescer = self.coalescer.lock().unwrap();
+        let interval = coalescer.interval;
+        if interval.is_zero() {
+            drop(coalescer);
+            return self.send(event);
+        }
+        let now = Instant::now();
+        if coalescer.windows.len() >= MAX_COALESCING_WINDOWS {
+            coalescer
+                .windows
+                .retain(|_, window| window.end > now || window.pending.is_some());
+        }
+        if let Some(window) = coalescer
+            .windows
+            .get_mut(&key)
+            .filter(|window| window.end > now || window.pending.is_some())
+        {
+            match &mut window.pending {
+                Some(pending) => merge_events(pending, event),
+                None => {
+                    window.pending = Some(event);
+                    let events = self.clone();
+                    let end = window.end;
+                    runtime.spawn(async move {
+                        tokio::time::sleep_until(end.into()).await;
+                        events.flush_window(key);
+                    });
+                }
+            }
+            return;
+        }
+
+        coalescer.windows.insert(
+            key,
+            CoalescingWindow {
+                end: now + interval,
+                pending: None,
+            },
+        );
+        drop(coalescer);
+        self.send(event);
+    }
+
+    /// Emits the pending event of a window which ended and opens the next window.
+    fn flush_window(&self, key: CoalescingKey) {
+        let mut coalescer = self.coalescer.lock().unwrap();
+        let interval = coalescer.interval;
+        if let Some(window) = coalescer.windows.get_mut(&key) {
+            if let Some(event) = window.pending.take() {
+                window.end = Instant::now() + interval;
+                drop(coalescer);
+                self.send(event);
             }
-            Err(TrySendError::Closed(_)) => {
-                unreachable!("unable to emit event, channel disconnected");
+        }
+    }
+
+    /// Numbers the event with the next sequence number of its account and sends it.
+    fn send(&self, mut event: Event) {
+        // Sending while holding the lock keeps the events of an account in order.
+        let mut sequences = self.sequences.lock().unwrap();
+        let seq = sequences.entry(event.id).or_default();
+        *seq += 1;
+        event.seq = *seq;
+        loop {
+            match self.sender.try_send(event) {
+                Ok(()) => break,
+                Err(TrySendError::Full(full)) => {
+                    // when we are full, we pop remove the oldest event and push on the new one
+                    let _ = self.receiver.try_recv();
+
+                    // try again
+                    event = full;
+                }
+                Err(TrySendError::Closed(_)) => {
+                    unreachable!("unable to emit event, channel disconnected");
+                }
             }
         }
     }
@@ -55,6 +217,21 @@ pub fn get_emitter(&self) -> EventEmitter {
     }
 }
 
+/// Merges a coalesced event into the pending one of the same chat.
+fn merge_events(pending: &mut Event, event: Event) {
+    if let (
+        EventType::MsgsChanged { msg_id, .. },
+        EventType::MsgsChanged {
+            msg_id: new_msg_id, ..
+        },
+    ) = (&mut pending.typ, &event.typ)
+    {
+        if msg_id != new_msg_id {
+            *msg_id = MsgId::new(0);
+        }
+    }
+}
+
 /// A receiver of events from a [`Context`].
 ///
 /// See [`Context::get_event_emitter`] to create an instance.  If multiple instances are
@@ -103,6 +280,19 @@ pub struct Event {
     ///
     /// [`Context`]: crate::context::Context
     pub id: u32,
+    /// Sequence number of the event among the events of the [`Context`], starting with 1.
+    ///
+    /// Events are dropped if they are not received fast enough,
+    /// so a gap in the numbers means that events were missed
+    /// and the state shown by the UI should be reloaded.
+    /// Numbers are assigned before events are filtered,
+    /// so consumers that only receive some of the events,
+    /// e.g. JSON-RPC clients with an event filter or chat subscription,
+    /// see gaps for the skipped events and cannot use them to detect dropped events.
+    /// The number is assigned when the event is emitted, so it may be 0 when constructing an event.
+    ///
+    /// [`Context`]: crate::context::Context
+    pub seq: u64,
     /// The event payload.
     ///
     /// These are documented in `deltachat.h` as the `DC_EVENT_*` constants.
@@ -110,7 +300,9 @@ pub struct Event {
 }
 
 /// Event payload.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+///
+/// `as_ref()` returns the name of the variant, e.g. `"IncomingMsg"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, AsRefStr)]
 pub enum EventType {
     /// The library-user may write an informational string to the log.
     ///
@@ -203,6 +395,9 @@ pub enum EventType {
 
         /// ID of the message.
         msg_id: MsgId,
+
+        /// How the user should be notified about the message.
+        intent: NotificationIntent,
     },
 
     /// Downloading a bunch of messages just finished.
@@ -215,6 +410,13 @@ pub enum EventType {
     /// chat id is always set.
     MsgsNoticed(ChatId),
 
+    /// Notifications were suppressed because of quiet hours,
+    /// see [`crate::notification::get_suppressed_notifications`].
+    NotificationsSuppressed {
+        /// Number of suppressed notifications since the last summary.
+        count: usize,
+    },
+
     /// A single message is sent successfully. State changed from  DC_STATE_OUT_PENDING to
     /// DC_STATE_OUT_DELIVERED, see dc_msg_get_state().
     MsgDelivered {
@@ -245,6 +447,18 @@ pub enum EventType {
         msg_id: MsgId,
     },
 
+    /// Messages of a chat were deleted, e.g. by the user or because their ephemeral timer expired.
+    ///
+    /// Messages deleted at once are reported by a single event per chat.
+    /// A #DC_EVENT_MSGS_CHANGED event is emitted as well.
+    MsgsDeleted {
+        /// ID of the chat which the messages belonged to.
+        chat_id: ChatId,
+
+        /// IDs of the deleted messages.
+        msg_ids: Vec<MsgId>,
+    },
+
     /// Chat changed.  The name or the image of a chat group was changed or members were added or removed.
     /// Or the verify state of a chat has changed.
     /// See dc_set_chat_name(), dc_set_chat_profile_image(), dc_add_contact_to_chat()
@@ -263,6 +477,18 @@ pub enum EventType {
         timer: EphemeralTimer,
     },
 
+    /// A received message quotes an ephemeral message after the timer of the message expired,
+    /// so the sender's device probably does not delete ephemeral messages.
+    ///
+    /// The UI may inform the user that the ephemeral timer is not honored.
+    EphemeralTimerNotHonored {
+        /// ID of the chat of the received message.
+        chat_id: ChatId,
+
+        /// ID of the received message quoting the expired message.
+        msg_id: MsgId,
+    },
+
     /// Contact(s) created, renamed, blocked or deleted.
     ///
     /// @param data1 (int) If set, this is the contact_id of an added contact that should be selected.
@@ -301,6 +527,19 @@ pub enum EventType {
     /// @param data2 0
     ImexFileWritten(PathBuf),
 
+    /// Progress of a peer-to-peer file transfer,
+    /// see [`crate::peer_transfer`].
+    ///
+    /// Reported by the sender for each download of the file
+    /// and by the recipient for [`crate::peer_transfer::download_file_p2p`].
+    P2pFileProgress {
+        /// ID of the message offering the file.
+        msg_id: MsgId,
+
+        /// 0=error, 1-999=progress in permille, 1000=success and done
+        progress: usize,
+    },
+
     /// Progress information of a secure-join handshake from the view of the inviter
     /// (Alice, the person who shows the QR code).
     ///
@@ -332,6 +571,26 @@ pub enum EventType {
         progress: usize,
     },
 
+    /// A verified contact forwarded an invite to a protected group.
+    /// The invite can be accepted with [`crate::securejoin::accept_group_invite`].
+    SecurejoinInviteReceived {
+        /// ID of the inviting contact.
+        contact_id: ContactId,
+
+        /// ID of the invite.
+        invite_id: u32,
+    },
+
+    /// The inviter of a group with join gate sent a challenge to answer,
+    /// see [`crate::securejoin::get_join_challenge`].
+    SecurejoinChallengeReceived {
+        /// ID of the inviting contact.
+        contact_id: ContactId,
+
+        /// ID of the challenge.
+        challenge_id: u32,
+    },
+
     /// The connectivity to the server changed.
     /// This means that you should refresh the connectivity view
     /// and possibly the connectivtiy HTML; see dc_get_connectivity() and
@@ -355,4 +614,171 @@ pub enum EventType {
         /// ID of the deleted message.
         msg_id: MsgId,
     },
+
+    /// A webxdc instance wants attention, another member sent an info message.
+    /// UIs may badge the app icon with the number of unread info messages.
+    WebxdcAttention {
+        /// ID of the webxdc instance message.
+        msg_id: MsgId,
+
+        /// Number of unread info messages of the instance,
+        /// reset by [`crate::context::Context::mark_webxdc_infos_seen`].
+        unread_info_cnt: u32,
+    },
+
+    /// The accounts were locked with [`crate::accounts::Accounts::lock`].
+    /// UIs should show their lock screen.
+    AccountsLocked,
+
+    /// All locked accounts were unlocked with [`crate::accounts::Accounts::unlock`].
+    AccountsUnlocked,
+
+    /// A call was offered with [`crate::calls::send_call_offer`],
+    /// the UI should ring until the call is answered or [`EventType::CallMissed`] is emitted.
+    IncomingCallOffer {
+        /// ID of the hidden offer message, identifying the call.
+        msg_id: MsgId,
+
+        /// Payload passed to [`crate::calls::send_call_offer`].
+        payload: String,
+    },
+
+    /// An offered call was answered with [`crate::calls::send_call_answer`].
+    IncomingCallAnswer {
+        /// ID of the offer message.
+        msg_id: MsgId,
+
+        /// Payload passed to [`crate::calls::send_call_answer`].
+        payload: String,
+    },
+
+    /// The peer sent an ICE candidate with [`crate::calls::send_call_ice`].
+    IncomingCallIce {
+        /// ID of the offer message.
+        msg_id: MsgId,
+
+        /// Payload passed to [`crate::calls::send_call_ice`].
+        payload: String,
+    },
+
+    /// An offered call was not answered in time,
+    /// see [`crate::calls::RINGING_TIMEOUT`].
+    CallMissed {
+        /// ID of the offer message.
+        msg_id: MsgId,
+    },
+
+    /// The device clock differs from the server time by more than
+    /// [`crate::clock_skew::CLOCK_SKEW_THRESHOLD`].
+    ///
+    /// A warning is also added to the device chat.
+    ClockSkew {
+        /// Server time minus device time in seconds,
+        /// positive if the device clock is behind.
+        skew: i64,
+    },
+
+    /// Canned responses were added, changed or deleted,
+    /// also on another device, see [`crate::canned_response`].
+    CannedResponsesChanged,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_coalescing() {
+        let events = Events::new();
+        let emitter = events.get_emitter();
+        events.set_coalescing_interval(Duration::from_millis(200));
+        let chat_id = ChatId::new(10);
+        for i in 1..=100 {
+            events.emit(Event {
+                id: 1,
+                seq: 0,
+                typ: EventType::MsgsChanged {
+                    chat_id,
+                    msg_id: MsgId::new(i),
+                },
+            });
+        }
+        events.emit(Event {
+            id: 1,
+            seq: 0,
+            typ: EventType::ChatModified(chat_id),
+        });
+        events.emit(Event {
+            id: 1,
+            seq: 0,
+            typ: EventType::Info("not coalesced".to_string()),
+        });
+
+        // The first events are emitted immediately, followed by the merged event.
+        let mut received = Vec::new();
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_secs(1), emitter.recv()).await
+        {
+            received.push(event.typ);
+        }
+        assert_eq!(
+            received,
+            vec![
+                EventType::MsgsChanged {
+                    chat_id,
+                    msg_id: MsgId::new(1)
+                },
+                EventType::ChatModified(chat_id),
+                EventType::Info("not coalesced".to_string()),
+                EventType::MsgsChanged {
+                    chat_id,
+                    msg_id: MsgId::new(0)
+                },
+            ]
+        );
+
+        events.set_coalescing_interval(Duration::ZERO);
+        events.emit(Event {
+            id: 1,
+            seq: 0,
+            typ: EventType::ChatModified(chat_id),
+        });
+        events.emit(Event {
+            id: 1,
+            seq: 0,
+            typ: EventType::ChatModified(chat_id),
+        });
+        assert_eq!(
+            emitter.recv().await.unwrap().typ,
+            EventType::ChatModified(chat_id)
+        );
+        assert_eq!(
+            emitter.recv().await.unwrap().typ,
+            EventType::ChatModified(chat_id)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_sequence_numbers() {
+        let events = Events::new();
+        let emitter = events.get_emitter();
+        let event = |id| Event {
+            id,
+            seq: 0,
+            typ: EventType::ConnectivityChanged,
+        };
+        events.emit(event(1));
+        events.emit(event(2));
+        events.emit(event(1));
+        assert_eq!(emitter.recv().await.unwrap().seq, 1);
+        assert_eq!(emitter.recv().await.unwrap().seq, 1);
+        let received = emitter.recv().await.unwrap();
+        assert_eq!((received.id, received.seq), (1, 2));
+
+        // Dropping the oldest event of a full channel leaves a gap.
+        for _ in 0..1001 {
+            events.emit(event(1));
+        }
+        assert_eq!(emitter.recv().await.unwrap().seq, 4);
+    }
 }
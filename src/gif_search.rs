@@ -0,0 +1,195 @@
+//! # GIF search.
+//!
+//! GIFs are searched using an HTTPS endpoint configured in [`Config::GifSearchEndpoint`].
+//! All requests, including downloading the chosen GIF, are done by the core
+//! using the configured proxy, so the API key stays in the core
+//! and the UI does not need to contact the provider itself.
+//!
+//! The endpoint is queried as `<endpoint>?q=<query>&limit=<limit>`,
+//! with the API key, if any, passed as a bearer token.
+//! It must respond with a JSON object of the form
+//! `{"results": [{"id": "…", "url": "https://…", "preview_url": "https://…",
+//! "width": 480, "height": 270, "attribution": "…"}]}`,
+//! where `preview_url` and `attribution` are optional.
+
+use anyhow::{bail, ensure, Context as _, Result};
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::blob::BlobObject;
+use crate::chat::{self, ChatId};
+use crate::config::Config;
+use crate::constants::{MAX_DECODE_IMAGE_DIMENSION, MAX_DECODE_IMAGE_PIXELS};
+use crate::context::Context;
+use crate::message::{Message, MsgId, Viewtype};
+use crate::socks::Socks5Config;
+use crate::tools::get_filemeta;
+
+/// Maximum number of results requested from the provider.
+const MAX_GIF_SEARCH_RESULTS: u32 = 50;
+
+/// Maximum size of a GIF to download.
+const MAX_GIF_BYTES: usize = 10 * 1024 * 1024;
+
+/// GIF found by [`search_gifs`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GifSearchResult {
+    /// Identifier of the GIF at the provider.
+    pub id: String,
+
+    /// URL of the GIF to download when sending it.
+    pub url: String,
+
+    /// URL of a smaller version of the GIF to show in the search results.
+    #[serde(default)]
+    pub preview_url: Option<String>,
+
+    /// Width of the GIF in pixels.
+    #[serde(default)]
+    pub width: u32,
+
+    /// Height of the GIF in pixels.
+    #[serde(default)]
+    pub height: u32,
+
+    /// Attribution required by the provider, to be shown next to the results.
+    #[serde(default)]
+    pub attribution: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GifSearchResponse {
+    results: Vec<GifSearchResult>,
+}
+
+/// Searches GIFs matching `query` using the configured provider.
+pub async fn search_gifs(
+    context: &Context,
+    query: &str,
+    limit: u32,
+) -> Result<Vec<GifSearchResult>> {
+    let endpoint = match context.get_config(Config::GifSearchEndpoint).await? {
+        Some(endpoint) if !endpoint.is_empty() => endpoint,
+        _ => bail!("GIF search is not configured"),
+    };
+    ensure!(
+        endpoint.starts_with("https://"),
+        "GIF search endpoint must use HTTPS"
+    );
+    let limit = limit.clamp(1, MAX_GIF_SEARCH_RESULTS).to_string();
+
+    let socks5_config = Socks5Config::from_database(&context.sql).await?;
+    let mut request = crate::http::get_client(socks5_config)?
+        .get(&endpoint)
+        .query(&[("q", query), ("limit", &limit)]);
+    if let Some(api_key) = context.get_config(Config::GifSearchApiKey).await? {
+        request = request.bearer_auth(api_key);
+    }
+    let response = request
+        .send()
+        .await
+        .context("GIF search request failed")?
+        .error_for_status()?;
+    let response: GifSearchResponse = response
+        .json()
+        .await
+        .context("malformed GIF search response")?;
+
+    let results: Vec<_> = response
+        .results
+        .into_iter()
+        .filter(|result| result.url.starts_with("https://"))
+        .collect();
+    info!(context, "GIF search returned {} results.", results.len());
+    Ok(results)
+}
+
+/// Downloads the GIF of a search result and sends it to the chat.
+///
+/// The GIF is checked to be a GIF within the image size limits before sending,
+/// it is not re-encoded to keep the animation.
+pub async fn send_gif(
+    context: &Context,
+    chat_id: ChatId,
+    result: &GifSearchResult,
+) -> Result<MsgId> {
+    ensure!(result.url.starts_with("https://"), "GIF URL must use HTTPS");
+    let data = download_gif(context, &result.url).await?;
+    ensure!(
+        matches!(image::guess_format(&data), Ok(ImageFormat::Gif)),
+        "downloaded file is not a GIF"
+    );
+    let (width, height) = get_filemeta(&data)?;
+    ensure!(
+        width <= MAX_DECODE_IMAGE_DIMENSION
+            && height <= MAX_DECODE_IMAGE_DIMENSION
+            && u64::from(width) * u64::from(height) <= MAX_DECODE_IMAGE_PIXELS,
+        "GIF dimensions {width}x{height} exceed the limits"
+    );
+
+    let blob = BlobObject::create_and_deduplicate(context, &data, ".gif").await?;
+    let mut msg = Message::new(Viewtype::Gif);
+    msg.set_file(blob.as_name(), Some("image/gif"));
+    msg.set_dimension(width as i32, height as i32);
+    chat::send_msg(context, chat_id, &mut msg).await
+}
+
+/// Downloads at most [`MAX_GIF_BYTES`] from `url`.
+async fn download_gif(context: &Context, url: &str) -> Result<Vec<u8>> {
+    let socks5_config = Socks5Config::from_database(&context.sql).await?;
+    let mut response = crate::http::get_client(socks5_config)?
+        .get(url)
+        .send()
+        .await
+        .context("GIF download failed")?
+        .error_for_status()?;
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        ensure!(
+            data.len() + chunk.len() <= MAX_GIF_BYTES,
+            "GIF exceeds {MAX_GIF_BYTES} bytes"
+        );
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestContext;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_search_gifs_not_configured() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        assert!(search_gifs(&t, "cat", 10).await.is_err());
+
+        t.set_config(
+            Config::GifSearchEndpoint,
+            Some("http://gifs.example.org/search"),
+        )
+        .await?;
+        let err = search_gifs(&t, "cat", 10).await.unwrap_err();
+        assert_eq!(err.to_string(), "GIF search endpoint must use HTTPS");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_gif_search_response() -> Result<()> {
+        let response: GifSearchResponse = serde_json::from_str(
+            r#"{"results": [
+                {"id": "1", "url": "https://gifs.example.org/1.gif", "width": 480, "height": 270,
+                 "attribution": "Powered by Example"},
+                {"id": "2", "url": "https://gifs.example.org/2.gif"}
+            ]}"#,
+        )?;
+        assert_eq!(response.results.len(), 2);
+        assert_eq!(response.results[0].width, 480);
+        assert_eq!(
+            response.results[0].attribution.as_deref(),
+            Some("Powered by Example")
+        );
+        assert_eq!(response.results[1].preview_url, None);
+        Ok(())
+    }
+}
@@ -59,6 +59,14 @@ pub enum HeaderDef {
     ChatVoiceMessage,
     ChatGroupMemberRemoved,
     ChatGroupMemberAdded,
+
+    /// Address of the removed member who must not be re-added,
+    /// sent along with `Chat-Group-Member-Removed`.
+    ChatGroupMemberBanned,
+
+    /// Address of the added member whose ban is lifted,
+    /// sent along with `Chat-Group-Member-Added`.
+    ChatGroupMemberUnbanned,
     ChatContent,
 
     /// Duration of the attached media file.
@@ -67,6 +75,31 @@ pub enum HeaderDef {
     ChatDispositionNotificationTo,
     ChatWebrtcRoom,
 
+    /// Ticket to download a file offered peer-to-peer.
+    ChatP2pTicket,
+
+    /// Name of the file offered peer-to-peer.
+    ChatP2pFilename,
+
+    /// Size in bytes of the file offered peer-to-peer.
+    ChatP2pFilebytes,
+
+    /// Media of the message can only be viewed once.
+    ChatViewOnce,
+
+    /// Grouping ID of media sent as an album.
+    ChatAlbum,
+
+    /// Base64-encoded signaling payload of a call message, see [`crate::calls`].
+    ChatCallPayload,
+
+    /// Set to `hidden` if the sender does not share the last-seen status,
+    /// the message must not be used for last-seen tracking then.
+    ChatLastSeen,
+
+    /// Identifier of the sending device, added to messages sent to own devices only.
+    ChatDeviceId,
+
     /// [Autocrypt](https://autocrypt.org/) header.
     Autocrypt,
     AutocryptSetupMessage,
@@ -75,6 +108,16 @@ pub enum HeaderDef {
     SecureJoinFingerprint,
     SecureJoinInvitenumber,
     SecureJoinAuth,
+
+    /// Question of a join gate challenge, see [`crate::securejoin::set_join_gate`].
+    SecureJoinChallenge,
+
+    /// Option selected to answer a join gate challenge.
+    SecureJoinChallengeResponse,
+
+    /// Features of the Secure-Join protocol supported by the joiner,
+    /// `challenge` if join gate challenges can be answered.
+    SecureJoinFeatures,
     Sender,
 
     /// Ephemeral message timer.
@@ -12,16 +12,19 @@
 
 use anyhow::{Context as _, Result};
 use base64::Engine as _;
+use format_flowed::unformat_flowed;
 use futures::future::FutureExt;
 use lettre_email::mime::{self, Mime};
 use lettre_email::PartBuilder;
 use mailparse::ParsedContentType;
 
+use crate::dehtml::dehtml;
 use crate::headerdef::{HeaderDef, HeaderDefMap};
-use crate::message::{Message, MsgId};
+use crate::message::{Message, MessengerMessage, MsgId};
 use crate::mimeparser::parse_message_id;
 use crate::param::Param::SendHtml;
 use crate::plaintext::PlainText;
+use crate::simplify::simplify;
 use crate::{context::Context, message};
 
 impl Message {
@@ -261,6 +264,53 @@ pub async fn get_html(self, context: &Context) -> Result<Option<String>> {
             Ok(None)
         }
     }
+
+    /// Returns the full text of a message.
+    ///
+    /// Received texts exceeding [`crate::constants::DC_DESIRED_TEXT_LEN`]
+    /// are truncated with `[...]`; in this case, the untruncated text is taken
+    /// from the stored mime-message, quotes and footers are removed as for the message text.
+    /// For other messages, this is the same as `Message.get_text()`.
+    /// The corresponding ffi-function is `dc_get_msg_full_text()`.
+    pub async fn get_full_text(self, context: &Context) -> Result<String> {
+        let msg = Message::load_from_db(context, self).await?;
+        if msg.has_html() {
+            let rawmime = message::get_mime_headers(context, self).await?;
+            if !rawmime.is_empty() {
+                match full_text_from_bytes(&rawmime, msg.is_dc_message != MessengerMessage::No)
+                    .await
+                {
+                    Ok(Some(text)) => return Ok(text),
+                    Ok(None) => {}
+                    Err(err) => warn!(context, "get_full_text: parser error: {err:#}"),
+                }
+            }
+        }
+        Ok(msg.text.unwrap_or_default())
+    }
+}
+
+/// Extracts the simplified, untruncated text of a raw mime-message.
+async fn full_text_from_bytes(rawmime: &[u8], is_chat_message: bool) -> Result<Option<String>> {
+    let mut parser = HtmlMsgParser {
+        html: "".to_string(),
+        plain: None,
+    };
+    let parsedmail = mailparse::parse_mail(rawmime)?;
+    parser.collect_texts_recursive(&parsedmail).await?;
+
+    if let Some(plain) = parser.plain {
+        let text = simplify(plain.text, is_chat_message).text;
+        if plain.flowed {
+            Ok(Some(unformat_flowed(&text, plain.delsp)))
+        } else {
+            Ok(Some(text))
+        }
+    } else if parser.html.is_empty() {
+        Ok(None)
+    } else {
+        Ok(dehtml(&parser.html).map(|text| simplify(text, is_chat_message).text))
+    }
 }
 
 /// Wraps HTML text into a new text/html mimepart structure.
@@ -279,10 +329,11 @@ mod tests {
     use crate::chat;
     use crate::chat::forward_msgs;
     use crate::config::Config;
+    use crate::constants::DC_ELLIPSIS;
     use crate::contact::ContactId;
     use crate::message::{MessengerMessage, Viewtype};
     use crate::receive_imf::receive_imf;
-    use crate::test_utils::TestContext;
+    use crate::test_utils::{TestContext, TestContextManager};
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_htmlparse_plain_unspecified() {
@@ -582,4 +633,56 @@ async fn test_cp1252_html() -> Result<()> {
         assert!(html.contains("foo bar ä ö ü ß"));
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_full_text() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let long_text = "Lorem ipsum dolor sit amet.\n".repeat(100);
+
+        let raw = format!(
+            "From: bob@example.net\n\
+             To: alice@example.org\n\
+             Subject: long\n\
+             Message-ID: <long@example.net>\n\
+             Date: Sun, 22 Mar 2020 22:37:57 +0000\n\
+             \n\
+             {long_text}"
+        );
+        receive_imf(&alice, raw.as_bytes(), false).await?;
+        let msg = alice.get_last_msg().await;
+        assert!(msg.has_html());
+        assert!(msg.get_text().unwrap().ends_with(DC_ELLIPSIS));
+        assert_eq!(msg.id.get_full_text(&alice).await?.trim(), long_text.trim());
+
+        // Outgoing long texts are sent as text, the recipient can get the full text.
+        let chat_id = alice.create_chat(&bob).await.id;
+        let msg_id = chat::send_text_msg(&alice, chat_id, long_text.clone()).await?;
+        let msg = Message::load_from_db(&alice, msg_id).await?;
+        assert_eq!(msg.get_viewtype(), Viewtype::Text);
+        let msg = bob.recv_msg(&alice.pop_sent_msg().await).await;
+        assert_eq!(msg.get_viewtype(), Viewtype::Text);
+        assert!(msg.get_text().unwrap().ends_with(DC_ELLIPSIS));
+        assert_eq!(msg.id.get_full_text(&bob).await?.trim(), long_text.trim());
+
+        // Huge texts are sent as a file.
+        let long_text = long_text.repeat(chat::LONG_TEXT_FILE_THRESHOLD / long_text.len() + 1);
+        let msg_id = chat::send_text_msg(&alice, chat_id, long_text.clone()).await?;
+        let msg = Message::load_from_db(&alice, msg_id).await?;
+        assert_eq!(msg.get_viewtype(), Viewtype::File);
+        assert!(msg.get_filename().unwrap().ends_with(".txt"));
+        let msg = bob.recv_msg(&alice.pop_sent_msg().await).await;
+        assert_eq!(msg.get_viewtype(), Viewtype::File);
+        assert!(msg.get_text().unwrap().ends_with(DC_ELLIPSIS));
+        let file = tokio::fs::read_to_string(msg.get_file(&bob).unwrap()).await?;
+        assert_eq!(file, long_text);
+
+        // Short texts are not changed.
+        let msg_id = chat::send_text_msg(&alice, chat_id, "short".to_string()).await?;
+        let msg = Message::load_from_db(&alice, msg_id).await?;
+        assert_eq!(msg.get_viewtype(), Viewtype::Text);
+        assert_eq!(msg_id.get_full_text(&alice).await?, "short");
+        Ok(())
+    }
 }
@@ -17,15 +17,17 @@
 use futures::{StreamExt, TryStreamExt};
 use num_traits::FromPrimitive;
 
-use crate::chat::{self, ChatId, ChatIdBlocked};
+use crate::chat::{self, Chat, ChatId, ChatIdBlocked};
 use crate::config::Config;
 use crate::constants::{
-    Blocked, Chattype, ShowEmails, DC_FETCH_EXISTING_MSGS_COUNT, DC_FOLDERS_CONFIGURED_VERSION,
+    Blocked, Chattype, ShowEmails, DC_BACKFILL_BATCH_SIZE, DC_FOLDERS_CONFIGURED_VERSION,
 };
 use crate::contact::{normalize_name, Contact, ContactAddress, ContactId, Modifier, Origin};
 use crate::context::Context;
+use crate::error_code::ErrorCode;
 use crate::events::EventType;
 use crate::headerdef::{HeaderDef, HeaderDefMap};
+use crate::imap_jobs;
 use crate::job;
 use crate::login_param::{CertificateChecks, LoginParam, ServerLoginParam};
 use crate::message::{self, Message, MessageState, MessengerMessage, MsgId, Viewtype};
@@ -40,17 +42,20 @@
 use crate::socks::Socks5Config;
 use crate::sql;
 use crate::stock_str;
-use crate::tools::create_id;
+use crate::tools::{create_id, time};
 
 pub(crate) mod capabilities;
 mod client;
+mod fetch_policy;
 mod idle;
 pub mod scan_folders;
 pub mod select_folder;
 pub(crate) mod session;
 
 use client::Client;
+use fetch_policy::FetchPolicy;
 use mailparse::SingleInfo;
+use scan_folders::{apply_folder_overrides, get_watched_folder_configs};
 use session::Session;
 
 use self::select_folder::NewlySelected;
@@ -125,6 +130,9 @@ pub enum FolderMeaning {
     Trash,
     Drafts,
 
+    /// Archive folder, old chat messages may be moved there.
+    Archive,
+
     /// Virtual folders.
     ///
     /// On Gmail there are virtual folders marked as \\All, \\Important and \\Flagged.
@@ -144,6 +152,7 @@ pub fn to_config(self) -> Option<Config> {
             FolderMeaning::Sent => Some(Config::ConfiguredSentboxFolder),
             FolderMeaning::Trash => Some(Config::ConfiguredTrashFolder),
             FolderMeaning::Drafts => None,
+            FolderMeaning::Archive => Some(Config::ConfiguredArchiveFolder),
             FolderMeaning::Virtual => None,
         }
     }
@@ -401,9 +410,10 @@ pub async fn connect(&mut self, context: &Context) -> Result<()> {
 
                 warn!(context, "{} ({:#})", message, err);
 
+                let is_auth_error = err.to_string().to_lowercase().contains("authentication");
                 let lock = context.wrong_pw_warning_mutex.lock().await;
                 if self.login_failed_once
-                    && err.to_string().to_lowercase().contains("authentication")
+                    && is_auth_error
                     && context.get_config_bool(Config::NotifyAboutWrongPw).await?
                 {
                     if let Err(e) = context.set_config(Config::NotifyAboutWrongPw, None).await {
@@ -423,7 +433,18 @@ pub async fn connect(&mut self, context: &Context) -> Result<()> {
                     self.login_failed_once = true;
                 }
 
-                Err(format_err!("{}\n\n{:#}", message, err))
+                // Only a rejection of the credentials is an authentication error,
+                // otherwise the cause decides, e.g. a connection reset during login.
+                let code = if is_auth_error {
+                    Some(ErrorCode::Auth)
+                } else {
+                    ErrorCode::of(&err)
+                };
+                let err = format_err!("{}\n\n{:#}", message, err);
+                Err(match code {
+                    Some(code) => code.wrap(err),
+                    None => err,
+                })
             }
         }
     }
@@ -636,6 +657,7 @@ pub(crate) async fn select_with_uidvalidity(
             // Also, the "fall back to fetching" below would need a non-zero mailbox.exists to work.
             set_uid_next(context, folder, 1).await?;
             set_uidvalidity(context, folder, new_uid_validity).await?;
+            set_backfill_uid(context, folder, 0).await?;
             return Ok(false);
         }
 
@@ -670,6 +692,13 @@ pub(crate) async fn select_with_uidvalidity(
 
         set_uid_next(context, folder, new_uid_next).await?;
         set_uidvalidity(context, folder, new_uid_validity).await?;
+        if old_uid_validity == 0 && old_uid_next == 0 {
+            // The folder is selected for the first time,
+            // messages present in the folder now can be backfilled later.
+            set_backfill_uid(context, folder, new_uid_next).await?;
+        } else {
+            set_backfill_uid(context, folder, 0).await?;
+        }
 
         // Collect garbage entries in `imap` table.
         context
@@ -720,18 +749,47 @@ pub(crate) async fn fetch_new_messages(
             return Ok(false);
         }
 
-        let uid_validity = get_uidvalidity(context, folder).await?;
         let old_uid_next = get_uid_next(context, folder).await?;
 
         let msgs = if fetch_existing_msgs {
-            self.prefetch_existing_msgs()
+            let max_count = FetchPolicy::load(context).await?.max_count;
+            let msgs = self
+                .prefetch_existing_msgs(max_count)
                 .await
-                .context("prefetch_existing_msgs")?
+                .context("prefetch_existing_msgs")?;
+            // Older messages are left for the backfill.
+            if let Some(oldest_uid) = msgs.iter().map(|(uid, _)| *uid).min() {
+                set_backfill_uid(context, folder, oldest_uid).await?;
+            }
+            msgs
         } else {
             self.prefetch(old_uid_next).await.context("prefetch")?
         };
         let read_cnt = msgs.len();
 
+        self.fetch_prefetched_msgs(context, folder, folder_meaning, msgs, fetch_existing_msgs)
+            .await?;
+
+        Ok(read_cnt > 0)
+    }
+
+    /// Stores prefetched messages in the `imap` table and downloads the ones
+    /// which should be downloaded.
+    ///
+    /// `uid_next` of the folder is advanced past the processed messages.
+    async fn fetch_prefetched_msgs(
+        &mut self,
+        context: &Context,
+        folder: &str,
+        folder_meaning: FolderMeaning,
+        msgs: Vec<(u32, async_imap::types::Fetch)>,
+        fetch_existing_msgs: bool,
+    ) -> Result<()> {
+        let uid_validity = get_uidvalidity(context, folder).await?;
+        let old_uid_next = get_uid_next(context, folder).await?;
+        let read_cnt = msgs.len();
+        let fetch_policy = FetchPolicy::load(context).await?;
+
         let download_limit = context.download_limit().await?;
         let mut uids_fetch = Vec::<(_, bool /* partially? */)>::with_capacity(msgs.len() + 1);
         let mut uid_message_ids = BTreeMap::new();
@@ -775,6 +833,13 @@ pub(crate) async fn fetch_new_messages(
                 // and then we download the message from there.
                 // Also see `spam_target_folder_cfg()`.
                 && folder_meaning != FolderMeaning::Spam
+                // Never download messages which are too old according to the fetch policy.
+                && !fetch_policy.is_too_old(fetch_response)
+                // Gmail shows the same message in several folders,
+                // download it only once from the preferred one.
+                && !has_preferred_copy(context, &message_id, folder, folder_meaning)
+                    .await
+                    .context("has_preferred_copy")?
                 && prefetch_should_download(
                     context,
                     &headers,
@@ -854,53 +919,198 @@ pub(crate) async fn fetch_new_messages(
 
         chat::mark_old_messages_as_noticed(context, received_msgs).await?;
 
-        Ok(read_cnt > 0)
+        Ok(())
     }
 
     /// Read the recipients from old emails sent by the user and add them as contacts.
     /// This way, we can already offer them some email addresses they can write to.
     ///
-    /// Then, Fetch the last messages DC_FETCH_EXISTING_MSGS_COUNT emails from the server
+    /// Then, fetch the last messages from the folders allowed by the [`FetchPolicy`]
     /// and show them in the chat list.
     pub(crate) async fn fetch_existing_msgs(&mut self, context: &Context) -> Result<()> {
-        if context.get_config_bool(Config::Bot).await? {
+        let fetch_policy = FetchPolicy::load(context).await?;
+        let is_bot = context.get_config_bool(Config::Bot).await?;
+        if is_bot && fetch_policy.max_count == 0 {
             return Ok(()); // Bots don't want those messages
         }
         self.prepare(context).await.context("could not connect")?;
 
-        add_all_recipients_as_contacts(context, self, Config::ConfiguredSentboxFolder)
-            .await
-            .context("failed to get recipients from the sentbox")?;
-        add_all_recipients_as_contacts(context, self, Config::ConfiguredMvboxFolder)
+        if !is_bot {
+            add_all_recipients_as_contacts(context, self, Config::ConfiguredSentboxFolder)
+                .await
+                .context("failed to get recipients from the sentbox")?;
+            add_all_recipients_as_contacts(context, self, Config::ConfiguredMvboxFolder)
+                .await
+                .context("failed to ge recipients from the movebox")?;
+            add_all_recipients_as_contacts(context, self, Config::ConfiguredInboxFolder)
+                .await
+                .context("failed to get recipients from the inbox")?;
+        }
+
+        for meaning in fetch_policy.folders.iter().copied() {
+            if !fetch_policy.fetch_existing_from(meaning) {
+                continue;
+            }
+            let config = match meaning.to_config() {
+                Some(c) => c,
+                None => continue,
+            };
+            if let Some(folder) = context.get_config(config).await? {
+                info!(
+                    context,
+                    "Fetching existing messages from folder \"{}\"", folder
+                );
+                self.fetch_new_messages(context, &folder, meaning, true)
+                    .await
+                    .context("could not fetch existing messages")?;
+            }
+        }
+
+        info!(context, "Done fetching existing messages.");
+        Ok(())
+    }
+
+    /// Fetches messages older than the ones fetched so far.
+    ///
+    /// First serves the requests queued by [`ChatId::load_older_from_server`].
+    /// Then, if the [`FetchPolicy`] allows fetching existing messages, downloads the next
+    /// [`DC_BACKFILL_BATCH_SIZE`] older messages from each watched folder.
+    ///
+    /// Returns true if there are more messages left to backfill.
+    pub(crate) async fn backfill_msgs(&mut self, context: &Context) -> Result<bool> {
+        let requests = std::mem::take(&mut *context.older_msgs_requests.lock().await);
+        let fetch_policy = FetchPolicy::load(context).await?;
+        let background = fetch_policy.max_count > 0;
+        if requests.is_empty() && !background {
+            return Ok(false);
+        }
+        self.prepare(context).await.context("could not connect")?;
+
+        let watched_folders = get_watched_folder_configs(context).await?;
+        let mut folders = Vec::new();
+        for meaning in [
+            FolderMeaning::Mvbox,
+            FolderMeaning::Inbox,
+            FolderMeaning::Sent,
+        ] {
+            let config = match meaning.to_config() {
+                Some(c) => c,
+                None => continue,
+            };
+            if !watched_folders.contains(&config) {
+                continue;
+            }
+            if let Some(folder) = context.get_config(config).await? {
+                folders.push((folder, meaning));
+            }
+        }
+
+        for (chat_id, count) in requests {
+            let search_command = match get_chat_search_command(context, chat_id).await? {
+                Some(search_command) => search_command,
+                None => continue,
+            };
+            for (folder, meaning) in &folders {
+                self.fetch_older_msgs(context, folder, *meaning, Some(&search_command), count)
+                    .await
+                    .with_context(|| format!("failed to load older messages for {chat_id}"))?;
+            }
+        }
+
+        let mut more_pending = false;
+        if background {
+            for (folder, meaning) in &folders {
+                if !fetch_policy.fetch_existing_from(*meaning) {
+                    continue;
+                }
+                self.fetch_older_msgs(context, folder, *meaning, None, DC_BACKFILL_BATCH_SIZE)
+                    .await
+                    .with_context(|| format!("failed to backfill folder {folder}"))?;
+                more_pending |= get_backfill_uid(context, folder).await? > 1;
+            }
+        }
+        Ok(more_pending)
+    }
+
+    /// Fetches up to `count` newest messages from `folder`
+    /// which have lower UIDs than the backfill boundary of the folder.
+    ///
+    /// If `search_command` is set, only messages matching it are fetched
+    /// and the boundary is left untouched. Otherwise the boundary is moved
+    /// below the fetched messages.
+    async fn fetch_older_msgs(
+        &mut self,
+        context: &Context,
+        folder: &str,
+        folder_meaning: FolderMeaning,
+        search_command: Option<&str>,
+        count: usize,
+    ) -> Result<()> {
+        if get_backfill_uid(context, folder).await? <= 1
+            || should_ignore_folder(context, folder, folder_meaning).await?
+        {
+            return Ok(());
+        }
+        self.select_with_uidvalidity(context, folder)
             .await
-            .context("failed to ge recipients from the movebox")?;
-        add_all_recipients_as_contacts(context, self, Config::ConfiguredInboxFolder)
+            .with_context(|| format!("failed to select folder {folder}"))?;
+        // Selecting may have reset the boundary if UIDVALIDITY changed.
+        let backfill_uid = get_backfill_uid(context, folder).await?;
+        if backfill_uid <= 1 {
+            return Ok(());
+        }
+
+        let session = self.session.as_mut().context("no IMAP session")?;
+        let query = match search_command {
+            Some(search_command) => format!("UID 1:{} {}", backfill_uid - 1, search_command),
+            None => format!("UID 1:{}", backfill_uid - 1),
+        };
+        let mut uids: Vec<u32> = session
+            .uid_search(query)
             .await
-            .context("failed to get recipients from the inbox")?;
-
-        if context.get_config_bool(Config::FetchExistingMsgs).await? {
-            for meaning in [
-                FolderMeaning::Mvbox,
-                FolderMeaning::Inbox,
-                FolderMeaning::Sent,
-            ] {
-                let config = match meaning.to_config() {
-                    Some(c) => c,
-                    None => continue,
-                };
-                if let Some(folder) = context.get_config(config).await? {
-                    info!(
-                        context,
-                        "Fetching existing messages from folder \"{}\"", folder
-                    );
-                    self.fetch_new_messages(context, &folder, meaning, true)
-                        .await
-                        .context("could not fetch existing messages")?;
+            .with_context(|| format!("can't search folder {folder}"))?
+            .into_iter()
+            .filter(|uid| *uid < backfill_uid)
+            .collect();
+        uids.sort_unstable();
+        let uids = uids.split_off(uids.len().saturating_sub(count));
+
+        let mut msgs = BTreeMap::new();
+        for (_, set) in build_sequence_sets(&uids)? {
+            let mut list = session
+                .uid_fetch(set, PREFETCH_FLAGS)
+                .await
+                .context("IMAP could not fetch")?;
+            while let Some(msg) = list.try_next().await? {
+                if let Some(msg_uid) = msg.uid {
+                    if msg_uid < backfill_uid {
+                        msgs.insert((msg.internal_date(), msg_uid), msg);
+                    }
                 }
             }
         }
+        let msgs: Vec<_> = msgs.into_iter().map(|((_, uid), msg)| (uid, msg)).collect();
+        let fetch_policy = FetchPolicy::load(context).await?;
+        let all_too_old = msgs.iter().all(|(_, msg)| fetch_policy.is_too_old(msg));
+        info!(
+            context,
+            "Backfilling {} messages from \"{}\".",
+            msgs.len(),
+            folder
+        );
 
-        info!(context, "Done fetching existing messages.");
+        self.fetch_prefetched_msgs(context, folder, folder_meaning, msgs, true)
+            .await?;
+
+        if search_command.is_none() {
+            // If nothing was found or all messages are too old,
+            // there is nothing left to backfill.
+            let new_backfill_uid = match all_too_old {
+                true => 0,
+                false => uids.first().copied().unwrap_or(0),
+            };
+            set_backfill_uid(context, folder, new_backfill_uid).await?;
+        }
         Ok(())
     }
 
@@ -1069,19 +1279,23 @@ async fn move_delete_messages(&mut self, context: &Context, folder: &str) -> Res
             self.select_folder(context, Some(folder)).await?;
 
             // Empty target folder name means messages should be deleted.
-            if target.is_empty() {
-                self.delete_message_batch(context, &uid_set, rowid_set)
+            let res = if target.is_empty() {
+                self.delete_message_batch(context, &uid_set, rowid_set.clone())
                     .await
-                    .with_context(|| format!("cannot delete batch of messages {:?}", &uid_set))?;
+                    .with_context(|| format!("cannot delete batch of messages {:?}", &uid_set))
             } else {
-                self.move_message_batch(context, &uid_set, rowid_set, &target)
+                self.move_message_batch(context, &uid_set, rowid_set.clone(), &target)
                     .await
                     .with_context(|| {
                         format!(
                             "cannot move batch of messages {:?} to folder {:?}",
                             &uid_set, target
                         )
-                    })?;
+                    })
+            };
+            if let Err(err) = res {
+                imap_jobs::set_imap_job_error(context, &rowid_set, &err).await?;
+                return Err(err);
             }
         }
 
@@ -1126,6 +1340,7 @@ pub(crate) async fn store_seen_flags_on_imap(&mut self, context: &Context) -> Re
                     folder,
                     err
                 );
+                imap_jobs::set_imap_job_error(context, &rowid_set, &err).await?;
             } else {
                 info!(
                     context,
@@ -1147,10 +1362,80 @@ pub(crate) async fn store_seen_flags_on_imap(&mut self, context: &Context) -> Re
 
         Ok(())
     }
+
+    /// Stores pending `\Flagged` flag changes for messages in `imap_markflagged` table.
+    pub(crate) async fn store_flagged_flags_on_imap(&mut self, context: &Context) -> Result<()> {
+        for flagged in [true, false] {
+            let rows = context
+                .sql
+                .query_map(
+                    "SELECT imap.id, uid, folder FROM imap, imap_markflagged
+                     WHERE imap.id = imap_markflagged.id AND target = folder AND flagged = ?
+                     ORDER BY folder, uid",
+                    (flagged,),
+                    |row| {
+                        let rowid: i64 = row.get(0)?;
+                        let uid: u32 = row.get(1)?;
+                        let folder: String = row.get(2)?;
+                        Ok((rowid, uid, folder))
+                    },
+                    |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+                )
+                .await?;
+
+            for (folder, rowid_set, uid_set) in UidGrouper::from(rows) {
+                self.select_folder(context, Some(&folder))
+                    .await
+                    .context("failed to select folder")?;
+
+                let res = if flagged {
+                    self.add_flag_finalized_with_set(&uid_set, "\\Flagged")
+                        .await
+                } else {
+                    self.remove_flag_finalized_with_set(&uid_set, "\\Flagged")
+                        .await
+                };
+                if let Err(err) = res {
+                    warn!(
+                        context,
+                        "Cannot store \\Flagged={} for messages {} in folder {}, will retry later: {}.",
+                        flagged,
+                        uid_set,
+                        folder,
+                        err
+                    );
+                } else {
+                    info!(
+                        context,
+                        "Stored \\Flagged={} for messages {} in folder {}.",
+                        flagged,
+                        uid_set,
+                        folder
+                    );
+                    let mut params: Vec<i64> = rowid_set;
+                    let rowid_cnt = params.len();
+                    params.push(flagged as i64);
+                    context
+                        .sql
+                        .execute(
+                            &format!(
+                                "DELETE FROM imap_markflagged WHERE id IN ({}) AND flagged=?",
+                                sql::repeat_vars(rowid_cnt)
+                            ),
+                            rusqlite::params_from_iter(params),
+                        )
+                        .await
+                        .context("cannot remove stored flags from imap_markflagged table")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Imap {
-    /// Synchronizes `\Seen` flags using `CONDSTORE` extension.
+    /// Synchronizes `\Seen` and `\Flagged` flags using `CONDSTORE` extension.
     pub(crate) async fn sync_seen_flags(&mut self, context: &Context, folder: &str) -> Result<()> {
         let session = self
             .session
@@ -1186,6 +1471,7 @@ pub(crate) async fn sync_seen_flags(&mut self, context: &Context, folder: &str)
         }
 
         let mut updated_chat_ids = BTreeSet::new();
+        let mut starred_msgs = Vec::new();
         let uid_validity = get_uidvalidity(context, folder)
             .await
             .with_context(|| format!("failed to get UID validity for folder {folder}"))?;
@@ -1219,6 +1505,16 @@ pub(crate) async fn sync_seen_flags(&mut self, context: &Context, folder: &str)
                     updated_chat_ids.insert(chat_id);
                 }
             }
+            let is_flagged = fetch.flags().any(|flag| flag == Flag::Flagged);
+            if let Some(starred_msg) =
+                set_starred_by_uid(context, folder, uid_validity, uid, is_flagged)
+                    .await
+                    .with_context(|| {
+                        format!("failed to update starred status for msg {folder}/{uid}")
+                    })?
+            {
+                starred_msgs.push(starred_msg);
+            }
 
             if let Some(modseq) = fetch.modseq {
                 if modseq > highest_modseq {
@@ -1235,6 +1531,9 @@ pub(crate) async fn sync_seen_flags(&mut self, context: &Context, folder: &str)
         for updated_chat_id in updated_chat_ids {
             context.emit_event(EventType::MsgsNoticed(updated_chat_id));
         }
+        for (chat_id, msg_id) in starred_msgs {
+            context.emit_msgs_changed(chat_id, msg_id);
+        }
 
         Ok(())
     }
@@ -1313,18 +1612,21 @@ async fn prefetch(&mut self, uid_next: u32) -> Result<Vec<(u32, async_imap::type
         Ok(msgs.into_iter().map(|((_, uid), msg)| (uid, msg)).collect())
     }
 
-    /// Like fetch_after(), but not for new messages but existing ones (the DC_FETCH_EXISTING_MSGS_COUNT newest messages)
-    async fn prefetch_existing_msgs(&mut self) -> Result<Vec<(u32, async_imap::types::Fetch)>> {
+    /// Like fetch_after(), but not for new messages but existing ones (the `count` newest messages)
+    async fn prefetch_existing_msgs(
+        &mut self,
+        count: u32,
+    ) -> Result<Vec<(u32, async_imap::types::Fetch)>> {
         let session = self.session.as_mut().context("no IMAP session")?;
         let exists: i64 = {
             let mailbox = session.selected_mailbox.as_ref().context("no mailbox")?;
             mailbox.exists.into()
         };
 
-        // Fetch last DC_FETCH_EXISTING_MSGS_COUNT (100) messages.
+        // Fetch last `count` messages.
         // Sequence numbers are sequential. If there are 1000 messages in the inbox,
-        // we can fetch the sequence numbers 900-1000 and get the last 100 messages.
-        let first = cmp::max(1, exists - DC_FETCH_EXISTING_MSGS_COUNT);
+        // we can fetch the sequence numbers 901-1000 and get the last 100 messages.
+        let first = cmp::max(1, exists - i64::from(count) + 1);
         let set = format!("{first}:*");
         let mut list = session
             .fetch(&set, PREFETCH_FLAGS)
@@ -1553,6 +1855,18 @@ async fn add_flag_finalized_with_set(&mut self, uid_set: &str, flag: &str) -> Re
         }
         Ok(())
     }
+
+    async fn remove_flag_finalized_with_set(&mut self, uid_set: &str, flag: &str) -> Result<()> {
+        let query = format!("-FLAGS ({flag})");
+        let mut responses = self
+            .uid_store(uid_set, &query)
+            .await
+            .with_context(|| format!("IMAP failed to store: ({uid_set}, {query})"))?;
+        while let Some(_response) = responses.next().await {
+            // Read all the responses
+        }
+        Ok(())
+    }
 }
 
 impl Imap {
@@ -1707,10 +2021,19 @@ pub async fn configure_folders(&mut self, context: &Context, create_mvbox: bool)
         info!(context, "Using \"{}\" as folder-delimiter.", delimiter);
 
         let fallback_folder = format!("INBOX{delimiter}DeltaChat");
+        let mvbox_override = context
+            .get_config(Config::MvboxFolder)
+            .await?
+            .filter(|folder| !folder.is_empty());
+        let mvbox_candidates = match &mvbox_override {
+            Some(folder) => vec![folder.as_str()],
+            None => vec!["DeltaChat", &fallback_folder],
+        };
         let mvbox_folder = self
-            .configure_mvbox(context, &["DeltaChat", &fallback_folder], create_mvbox)
+            .configure_mvbox(context, &mvbox_candidates, create_mvbox)
             .await
             .context("failed to configure mvbox")?;
+        apply_folder_overrides(context, &mut folder_configs).await?;
 
         context
             .set_config(Config::ConfiguredInboxFolder, Some("INBOX"))
@@ -1869,7 +2192,15 @@ pub async fn target_folder_cfg(
     folder_meaning: FolderMeaning,
     headers: &[mailparse::MailHeader<'_>],
 ) -> Result<Option<Config>> {
-    if context.is_mvbox(folder).await? {
+    if context.is_mvbox(folder).await? || context.is_archive(folder).await? {
+        // Never move chat messages out of the archive folder,
+        // they were moved there on purpose.
+        return Ok(None);
+    }
+
+    if context.uses_imap_labels().await? && context.is_sentbox(folder).await? {
+        // Moving a message out of the Sent folder only adds a label,
+        // the message stays in the Sent folder and would be moved again and again.
         return Ok(None);
     }
 
@@ -2008,6 +2339,7 @@ fn get_folder_meaning_by_name(folder_name: &str) -> FolderMeaning {
         "草稿",
         "임시보관함",
     ];
+    const ARCHIVE_NAMES: &[&str] = &["archive", "archives", "Archiv", "Archivio", "Archivo"];
     let lower = folder_name.to_lowercase();
 
     if SENT_NAMES.iter().any(|s| s.to_lowercase() == lower) {
@@ -2016,6 +2348,8 @@ fn get_folder_meaning_by_name(folder_name: &str) -> FolderMeaning {
         FolderMeaning::Spam
     } else if DRAFT_NAMES.iter().any(|s| s.to_lowercase() == lower) {
         FolderMeaning::Drafts
+    } else if ARCHIVE_NAMES.iter().any(|s| s.to_lowercase() == lower) {
+        FolderMeaning::Archive
     } else {
         FolderMeaning::Unknown
     }
@@ -2028,6 +2362,7 @@ fn get_folder_meaning_by_attrs(folder_attrs: &[NameAttribute]) -> FolderMeaning
             NameAttribute::Sent => return FolderMeaning::Sent,
             NameAttribute::Junk => return FolderMeaning::Spam,
             NameAttribute::Drafts => return FolderMeaning::Drafts,
+            NameAttribute::Archive => return FolderMeaning::Archive,
             NameAttribute::All | NameAttribute::Flagged => return FolderMeaning::Virtual,
             NameAttribute::Extension(ref label) => {
                 match label.as_ref() {
@@ -2072,6 +2407,75 @@ pub(crate) fn prefetch_get_or_create_message_id(headers: &[mailparse::MailHeader
         .unwrap_or_else(|| format!("{}{}", GENERATED_PREFIX, create_id()))
 }
 
+/// Returns the priority of a folder for downloading messages
+/// which are stored in several folders at the same time.
+///
+/// On Gmail every message is also stored in "All Mail"
+/// and a message moved to the DeltaChat folder may show up in the Inbox for a while.
+/// Messages are preferably downloaded from the DeltaChat folder,
+/// as chat messages end up there eventually.
+fn download_priority(folder_meaning: FolderMeaning) -> u8 {
+    match folder_meaning {
+        FolderMeaning::Mvbox => 3,
+        FolderMeaning::Inbox => 2,
+        FolderMeaning::Sent => 1,
+        _ => 0,
+    }
+}
+
+/// Returns true if the message with the given Message-ID is also stored
+/// in a watched folder with a higher download priority than `folder`
+/// and is not going to be moved away from there,
+/// so it should be downloaded from that folder instead.
+pub(crate) async fn has_preferred_copy(
+    context: &Context,
+    rfc724_mid: &str,
+    folder: &str,
+    folder_meaning: FolderMeaning,
+) -> Result<bool> {
+    let priority = download_priority(folder_meaning);
+    let watched_folder_configs = get_watched_folder_configs(context).await?;
+    let mut preferred_folders = Vec::new();
+    for (config, meaning) in [
+        (Config::ConfiguredMvboxFolder, FolderMeaning::Mvbox),
+        (Config::ConfiguredInboxFolder, FolderMeaning::Inbox),
+        (Config::ConfiguredSentboxFolder, FolderMeaning::Sent),
+    ] {
+        if download_priority(meaning) <= priority || !watched_folder_configs.contains(&config) {
+            continue;
+        }
+        if let Some(preferred_folder) = context.get_config(config).await? {
+            if preferred_folder != folder {
+                preferred_folders.push(preferred_folder);
+            }
+        }
+    }
+    if preferred_folders.is_empty() {
+        return Ok(false);
+    }
+
+    let exists = context
+        .sql
+        .exists(
+            &format!(
+                "SELECT COUNT(*) FROM imap
+                 WHERE rfc724_mid=? AND folder=target AND folder IN ({})",
+                sql::repeat_vars(preferred_folders.len())
+            ),
+            rusqlite::params_from_iter(
+                std::iter::once(rfc724_mid).chain(preferred_folders.iter().map(|f| f.as_str())),
+            ),
+        )
+        .await?;
+    if exists {
+        info!(
+            context,
+            "Message {rfc724_mid} in {folder} is downloaded from a preferred folder."
+        );
+    }
+    Ok(exists)
+}
+
 /// Returns chat by prefetched headers.
 async fn prefetch_get_chat(
     context: &Context,
@@ -2231,6 +2635,74 @@ async fn mark_seen_by_uid(
     }
 }
 
+/// Updates the starred status of the message corresponding to the given UID
+/// from its IMAP `\Flagged` flag.
+///
+/// Messages with pending local flag changes are not updated.
+/// Returns the chat and message ID if the status was changed.
+async fn set_starred_by_uid(
+    context: &Context,
+    folder: &str,
+    uid_validity: u32,
+    uid: u32,
+    starred: bool,
+) -> Result<Option<(ChatId, MsgId)>> {
+    if let Some((msg_id, chat_id)) = context
+        .sql
+        .query_row_optional(
+            "SELECT id, chat_id FROM msgs
+                 WHERE id > 9 AND starred!=?4 AND rfc724_mid IN (
+                   SELECT rfc724_mid FROM imap
+                   WHERE folder=?1
+                   AND uidvalidity=?2
+                   AND uid=?3
+                   AND NOT EXISTS (SELECT 1 FROM imap_markflagged WHERE imap_markflagged.id=imap.id)
+                   LIMIT 1
+                 )",
+            (&folder, uid_validity, uid, starred),
+            |row| {
+                let msg_id: MsgId = row.get(0)?;
+                let chat_id: ChatId = row.get(1)?;
+                Ok((msg_id, chat_id))
+            },
+        )
+        .await
+        .with_context(|| format!("failed to get msg and chat ID for IMAP message {folder}/{uid}"))?
+    {
+        context
+            .sql
+            .execute("UPDATE msgs SET starred=? WHERE id=?", (starred, msg_id))
+            .await
+            .with_context(|| format!("failed to update msg {msg_id} starred status"))?;
+        Ok(Some((chat_id, msg_id)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Schedule storing the `\Flagged` flag on IMAP by adding all known IMAP messages
+/// corresponding to the given Message-ID to `imap_markflagged` table.
+pub(crate) async fn markflagged_on_imap_table(
+    context: &Context,
+    message_id: &str,
+    flagged: bool,
+) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "INSERT OR REPLACE INTO imap_markflagged (id, flagged)
+             SELECT id, ? FROM imap WHERE rfc724_mid=?",
+            (flagged, message_id),
+        )
+        .await?;
+    context
+        .scheduler
+        .interrupt_inbox(InterruptInfo::new(false))
+        .await;
+
+    Ok(())
+}
+
 /// Schedule marking the message as Seen on IMAP by adding all known IMAP messages corresponding to
 /// the given Message-ID to `imap_markseen` table.
 pub(crate) async fn markseen_on_imap_table(context: &Context, message_id: &str) -> Result<()> {
@@ -2325,6 +2797,100 @@ async fn get_modseq(context: &Context, folder: &str) -> Result<u64> {
         .unwrap_or(0))
 }
 
+/// Marks old messages of chats with server archiving enabled to be moved
+/// from the inbox and the "DeltaChat" folder to the archive folder.
+///
+/// The messages are moved by `move_delete_messages()` on the next iteration.
+/// See [`ChatId::set_server_archive_after`].
+pub(crate) async fn archive_old_imap_messages(context: &Context) -> Result<()> {
+    let archive = match context.get_config(Config::ConfiguredArchiveFolder).await? {
+        Some(archive) => archive,
+        None => return Ok(()),
+    };
+    let inbox = context.get_config(Config::ConfiguredInboxFolder).await?;
+    let mvbox = context.get_config(Config::ConfiguredMvboxFolder).await?;
+
+    context
+        .sql
+        .execute(
+            "UPDATE imap
+             SET target=?
+             WHERE target=folder AND (folder=? OR folder=?)
+             AND rfc724_mid IN (
+               SELECT m.rfc724_mid FROM msgs m
+               INNER JOIN chats c ON m.chat_id=c.id
+               WHERE c.server_archive_after>0 AND m.timestamp < ? - c.server_archive_after
+             )",
+            (archive, inbox, mvbox, time()),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Backfill boundary of the folder.
+///
+/// Messages with UIDs lower than this were not fetched during the initial sync
+/// and can be fetched later. 0 means there is nothing to backfill.
+pub(crate) async fn set_backfill_uid(
+    context: &Context,
+    folder: &str,
+    backfill_uid: u32,
+) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "INSERT INTO imap_sync (folder, backfill_uid) VALUES (?,?)
+                ON CONFLICT(folder) DO UPDATE SET backfill_uid=excluded.backfill_uid",
+            (folder, backfill_uid),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn get_backfill_uid(context: &Context, folder: &str) -> Result<u32> {
+    Ok(context
+        .sql
+        .query_get_value(
+            "SELECT backfill_uid FROM imap_sync WHERE folder=?;",
+            (folder,),
+        )
+        .await?
+        .unwrap_or(0))
+}
+
+/// Compute the imap search expression for all messages belonging to the chat.
+///
+/// Returns `None` for chats which can't be searched for, e.g. the device chat.
+async fn get_chat_search_command(context: &Context, chat_id: ChatId) -> Result<Option<String>> {
+    let chat = Chat::load_from_db(context, chat_id).await?;
+    let search_command = match chat.typ {
+        Chattype::Single => {
+            if chat.is_self_talk() || chat.is_device_talk() {
+                return Ok(None);
+            }
+            let contact_id = match chat::get_chat_contacts(context, chat_id).await?.pop() {
+                Some(contact_id) => contact_id,
+                None => return Ok(None),
+            };
+            let contact = Contact::get_by_id(context, contact_id).await?;
+            let addr = contact.get_addr();
+            format!("OR FROM \"{addr}\" TO \"{addr}\"")
+        }
+        Chattype::Group | Chattype::Broadcast => {
+            if chat.grpid.is_empty() {
+                return Ok(None);
+            }
+            // `Chat-Group-ID` is not visible in encrypted messages,
+            // but the group ID is also contained in the `Message-ID`.
+            let grpid = &chat.grpid;
+            format!("OR HEADER Chat-Group-ID \"{grpid}\" HEADER Message-ID \"Gr.{grpid}.\"")
+        }
+        Chattype::Mailinglist => format!("HEADER List-Id \"{}\"", chat.grpid),
+        Chattype::Undefined => return Ok(None),
+    };
+    Ok(Some(search_command))
+}
+
 /// Compute the imap search expression for all self-sent mails (for all self addresses)
 pub(crate) async fn get_imap_self_sent_search_command(context: &Context) -> Result<String> {
     // See https://www.rfc-editor.org/rfc/rfc3501#section-6.4.4 for syntax of SEARCH and OR
@@ -2493,7 +3059,7 @@ async fn add_all_recipients_as_contacts(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chat::ChatId;
+    use crate::chat::{ChatId, ProtectionStatus};
     use crate::config::Config;
     use crate::contact::Contact;
     use crate::test_utils::TestContext;
@@ -2513,6 +3079,10 @@ fn test_get_folder_meaning_by_name() {
         );
         assert_eq!(get_folder_meaning_by_name("xxx"), FolderMeaning::Unknown);
         assert_eq!(get_folder_meaning_by_name("SPAM"), FolderMeaning::Spam);
+        assert_eq!(
+            get_folder_meaning_by_name("Archive"),
+            FolderMeaning::Archive
+        );
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -2529,6 +3099,11 @@ async fn test_set_uid_next_validity() {
         set_uidvalidity(&t.ctx, "Inbox", 6).await.unwrap();
         assert_eq!(get_uid_next(&t.ctx, "Inbox").await.unwrap(), 5);
         assert_eq!(get_uidvalidity(&t.ctx, "Inbox").await.unwrap(), 6);
+
+        assert_eq!(get_backfill_uid(&t.ctx, "Inbox").await.unwrap(), 0);
+        set_backfill_uid(&t.ctx, "Inbox", 3).await.unwrap();
+        assert_eq!(get_backfill_uid(&t.ctx, "Inbox").await.unwrap(), 3);
+        assert_eq!(get_uid_next(&t.ctx, "Inbox").await.unwrap(), 5);
     }
 
     #[test]
@@ -2771,6 +3346,40 @@ async fn test_target_folder_setupmsg() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_target_folder_imap_labels() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        t.set_config(Config::ConfiguredMvboxFolder, Some("DeltaChat"))
+            .await?;
+        t.set_config(Config::ConfiguredSentboxFolder, Some("[Gmail]/Sent Mail"))
+            .await?;
+        t.set_config_bool(Config::MvboxMove, true).await?;
+        let (headers, _) = mailparse::parse_headers(
+            b"From: alice@example.org\n\
+              To: bob@example.net\n\
+              Chat-Version: 1.0\n\
+              Message-ID: <abc@example.com>\n\
+              \n",
+        )?;
+        assert_eq!(
+            target_folder_cfg(&t, "[Gmail]/Sent Mail", FolderMeaning::Sent, &headers).await?,
+            Some(Config::ConfiguredMvboxFolder)
+        );
+
+        // On Gmail, moving out of the Sent folder only adds a label.
+        t.set_config_bool(Config::ImapLabels, true).await?;
+        assert!(t.uses_imap_labels().await?);
+        assert_eq!(
+            target_folder_cfg(&t, "[Gmail]/Sent Mail", FolderMeaning::Sent, &headers).await?,
+            None
+        );
+        assert_eq!(
+            target_folder_cfg(&t, "INBOX", FolderMeaning::Inbox, &headers).await?,
+            Some(Config::ConfiguredMvboxFolder)
+        );
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_get_imap_search_command() -> Result<()> {
         let t = TestContext::new_alice().await;
@@ -2793,4 +3402,225 @@ async fn test_get_imap_search_command() -> Result<()> {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_archive_old_imap_messages() -> Result<()> {
+        const DAY: i64 = 24 * 60 * 60;
+        let t = TestContext::new_alice().await;
+        let now = time();
+        let chat_id = t.get_self_chat().await.id;
+        let other_chat_id =
+            chat::create_group_chat(&t, ProtectionStatus::Unprotected, "foo").await?;
+        for (id, chat_id, timestamp, folder) in [
+            (1000, chat_id, now - 10 * DAY, "INBOX"),
+            (1010, chat_id, now - 10 * DAY, "DeltaChat"),
+            (1020, chat_id, now - 10 * DAY, "Sent"),
+            (1030, chat_id, now - DAY, "INBOX"),
+            (1040, other_chat_id, now - 10 * DAY, "INBOX"),
+        ] {
+            let message_id = id.to_string();
+            t.sql
+                .execute(
+                    "INSERT INTO msgs (id, rfc724_mid, chat_id, timestamp) VALUES (?,?,?,?)",
+                    (id, &message_id, chat_id, timestamp),
+                )
+                .await?;
+            t.sql
+                .execute(
+                    "INSERT INTO imap (rfc724_mid, folder, uid, target) VALUES (?,?,?,?)",
+                    (&message_id, folder, id, folder),
+                )
+                .await?;
+        }
+        t.set_config(Config::ConfiguredInboxFolder, Some("INBOX"))
+            .await?;
+        t.set_config(Config::ConfiguredMvboxFolder, Some("DeltaChat"))
+            .await?;
+        chat_id.set_server_archive_after(&t, 7 * DAY).await?;
+        assert_eq!(chat_id.get_server_archive_after(&t).await?, 7 * DAY);
+
+        // Nothing is moved without an archive folder.
+        archive_old_imap_messages(&t).await?;
+        assert_eq!(
+            t.sql
+                .count("SELECT COUNT(*) FROM imap WHERE target!=folder", ())
+                .await?,
+            0
+        );
+
+        t.set_config(Config::ConfiguredArchiveFolder, Some("Archive"))
+            .await?;
+        archive_old_imap_messages(&t).await?;
+        let archived: Vec<String> = t
+            .sql
+            .query_map(
+                "SELECT rfc724_mid FROM imap WHERE target='Archive' ORDER BY rfc724_mid",
+                (),
+                |row| row.get(0),
+                |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await?;
+        assert_eq!(archived, vec!["1000", "1010"]);
+
+        // Messages in the archive folder stay there.
+        assert_eq!(
+            target_folder_cfg(&t, "Archive", FolderMeaning::Archive, &[]).await?,
+            None
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_has_preferred_copy_gmail() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        t.set_config(Config::ConfiguredInboxFolder, Some("INBOX"))
+            .await?;
+        t.set_config(Config::ConfiguredMvboxFolder, Some("DeltaChat"))
+            .await?;
+        t.set_config(Config::ConfiguredSentboxFolder, Some("[Gmail]/Sent Mail"))
+            .await?;
+        t.set_config_bool(Config::MvboxMove, true).await?;
+        t.set_config_bool(Config::SentboxWatch, true).await?;
+
+        // Gmail stores every message in "All Mail", an incoming chat message is in the Inbox too
+        // until it is moved to the DeltaChat folder.
+        for (uid, folder, target) in [
+            (1, "INBOX", "DeltaChat"),
+            (2, "[Gmail]/All Mail", "[Gmail]/All Mail"),
+            (3, "DeltaChat", "DeltaChat"),
+        ] {
+            t.sql
+                .execute(
+                    "INSERT INTO imap (rfc724_mid, folder, uid, target) VALUES (?,?,?,?)",
+                    ("chat@example.org", folder, uid, target),
+                )
+                .await?;
+        }
+        assert!(
+            has_preferred_copy(
+                &t,
+                "chat@example.org",
+                "[Gmail]/All Mail",
+                FolderMeaning::Unknown
+            )
+            .await?
+        );
+        assert!(has_preferred_copy(&t, "chat@example.org", "INBOX", FolderMeaning::Inbox).await?);
+        assert!(
+            !has_preferred_copy(&t, "chat@example.org", "DeltaChat", FolderMeaning::Mvbox).await?
+        );
+
+        // An outgoing classic email stays in the Sent folder, it is not downloaded from "All Mail".
+        for (uid, folder) in [(4, "[Gmail]/All Mail"), (5, "[Gmail]/Sent Mail")] {
+            t.sql
+                .execute(
+                    "INSERT INTO imap (rfc724_mid, folder, uid, target) VALUES (?,?,?,?)",
+                    ("email@example.org", folder, uid, folder),
+                )
+                .await?;
+        }
+        assert!(
+            has_preferred_copy(
+                &t,
+                "email@example.org",
+                "[Gmail]/All Mail",
+                FolderMeaning::Unknown
+            )
+            .await?
+        );
+        assert!(
+            !has_preferred_copy(
+                &t,
+                "email@example.org",
+                "[Gmail]/Sent Mail",
+                FolderMeaning::Sent
+            )
+            .await?
+        );
+
+        // The Sent folder copy is not preferred if the Sent folder is not watched.
+        t.set_config_bool(Config::SentboxWatch, false).await?;
+        assert!(
+            !has_preferred_copy(
+                &t,
+                "email@example.org",
+                "[Gmail]/All Mail",
+                FolderMeaning::Unknown
+            )
+            .await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_chat_search_command() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob = TestContext::new_bob().await;
+
+        let chat = alice.create_chat(&bob).await;
+        assert_eq!(
+            get_chat_search_command(&alice, chat.id).await?.unwrap(),
+            r#"OR FROM "bob@example.net" TO "bob@example.net""#
+        );
+
+        let group_id =
+            chat::create_group_chat(&alice, ProtectionStatus::Unprotected, "foo").await?;
+        let grpid = Chat::load_from_db(&alice, group_id).await?.grpid;
+        assert_eq!(
+            get_chat_search_command(&alice, group_id).await?.unwrap(),
+            format!(r#"OR HEADER Chat-Group-ID "{grpid}" HEADER Message-ID "Gr.{grpid}.""#)
+        );
+
+        let self_chat = alice.get_self_chat().await;
+        assert!(get_chat_search_command(&alice, self_chat.id)
+            .await?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_starred_flag_sync() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let chat_id = t
+            .create_chat_with_contact("Bob", "bob@example.net")
+            .await
+            .id;
+        let sent = t.send_text(chat_id, "Star me").await;
+        let msg = Message::load_from_db(&t, sent.sender_msg_id).await?;
+        assert!(!msg.is_starred());
+        t.sql
+            .execute(
+                "INSERT INTO imap (rfc724_mid, folder, uid, uidvalidity, target)
+                 VALUES (?,'INBOX',100,1,'INBOX')",
+                (&msg.rfc724_mid,),
+            )
+            .await?;
+
+        msg.id.set_starred(&t, true).await?;
+        assert!(Message::load_from_db(&t, msg.id).await?.is_starred());
+        assert_eq!(t.get_starred_msgs().await?, vec![msg.id]);
+        assert_eq!(
+            t.sql
+                .count("SELECT COUNT(*) FROM imap_markflagged WHERE flagged=1", ())
+                .await?,
+            1
+        );
+
+        // The pending local change is not overwritten by the server state.
+        assert_eq!(set_starred_by_uid(&t, "INBOX", 1, 100, false).await?, None);
+        assert!(Message::load_from_db(&t, msg.id).await?.is_starred());
+
+        // Another client removes the flag after it was stored on the server.
+        t.sql.execute("DELETE FROM imap_markflagged", ()).await?;
+        assert_eq!(
+            set_starred_by_uid(&t, "INBOX", 1, 100, false).await?,
+            Some((chat_id, msg.id))
+        );
+        assert!(!Message::load_from_db(&t, msg.id).await?.is_starred());
+        assert!(t.get_starred_msgs().await?.is_empty());
+        assert_eq!(set_starred_by_uid(&t, "INBOX", 1, 100, false).await?, None);
+
+        Ok(())
+    }
 }
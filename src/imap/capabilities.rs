@@ -21,6 +21,22 @@ pub(crate) struct Capabilities {
     /// <https://tools.ietf.org/html/rfc7162>
     pub can_condstore: bool,
 
+    /// True if the server has METADATA capability as defined in
+    /// <https://tools.ietf.org/html/rfc5464>
+    pub can_metadata: bool,
+
+    /// True if the server supports registering push notification tokens
+    /// using the XDELTAPUSH capability.
+    pub can_push: bool,
+
+    /// True if the server is a chatmail relay
+    /// announcing the XCHATMAIL capability.
+    pub is_chatmail: bool,
+
+    /// True if folders are labels, as announced by Gmail
+    /// with the X-GM-EXT-1 capability.
+    pub has_labels: bool,
+
     /// Server ID if the server supports ID capability.
     pub server_id: Option<HashMap<String, String>>,
 }
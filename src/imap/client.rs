@@ -58,6 +58,10 @@ async fn determine_capabilities(
         can_move: caps.has_str("MOVE"),
         can_check_quota: caps.has_str("QUOTA"),
         can_condstore: caps.has_str("CONDSTORE"),
+        can_metadata: caps.has_str("METADATA"),
+        can_push: caps.has_str("XDELTAPUSH"),
+        is_chatmail: caps.has_str("XCHATMAIL"),
+        has_labels: caps.has_str("X-GM-EXT-1"),
         server_id,
     };
     Ok(capabilities)
@@ -0,0 +1,140 @@
+//! # Policy for fetching existing and old messages.
+//!
+//! By default the newest existing messages are fetched after configuration and older ones
+//! are backfilled later. Accounts which only care about new messages, such as bots,
+//! can restrict this with [`Config::FetchExistingMsgsCount`], [`Config::FetchMaxAge`]
+//! and [`Config::FetchExistingMsgsFolders`].
+
+use anyhow::Result;
+use async_imap::types::Fetch;
+
+use super::FolderMeaning;
+use crate::config::Config;
+use crate::context::Context;
+use crate::tools::time;
+
+/// Which existing and old messages to fetch from the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FetchPolicy {
+    /// Maximum number of existing messages fetched from each folder after configuration.
+    ///
+    /// 0 means that existing messages are not fetched and not backfilled.
+    pub max_count: u32,
+
+    /// Messages which arrived on the server before this timestamp are never downloaded,
+    /// neither from watched folders nor from scanned ones.
+    pub min_timestamp: Option<i64>,
+
+    /// Folders to fetch existing messages from.
+    pub folders: Vec<FolderMeaning>,
+}
+
+impl FetchPolicy {
+    /// Loads the policy from the account configuration.
+    pub async fn load(context: &Context) -> Result<Self> {
+        let max_count = if !context.get_config_bool(Config::FetchExistingMsgs).await? {
+            0
+        } else if context.get_config_bool(Config::Bot).await?
+            && !context
+                .config_exists(Config::FetchExistingMsgsCount)
+                .await?
+        {
+            // Bots don't want existing messages unless they explicitly ask for them.
+            0
+        } else {
+            context
+                .get_config_parsed(Config::FetchExistingMsgsCount)
+                .await?
+                .unwrap_or_default()
+        };
+
+        let min_timestamp = match context.get_config_i64(Config::FetchMaxAge).await? {
+            max_age if max_age > 0 => Some(time().saturating_sub(max_age)),
+            _ => None,
+        };
+
+        let folders = context
+            .get_config(Config::FetchExistingMsgsFolders)
+            .await?
+            .unwrap_or_default()
+            .split_ascii_whitespace()
+            .filter_map(|name| match name {
+                "mvbox" => Some(FolderMeaning::Mvbox),
+                "inbox" => Some(FolderMeaning::Inbox),
+                "sent" => Some(FolderMeaning::Sent),
+                _ => {
+                    warn!(context, "Unknown folder {:?} in fetch policy.", name);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            max_count,
+            min_timestamp,
+            folders,
+        })
+    }
+
+    /// Returns true if existing messages should be fetched from a folder
+    /// with the given meaning.
+    pub fn fetch_existing_from(&self, folder_meaning: FolderMeaning) -> bool {
+        self.max_count > 0 && self.folders.contains(&folder_meaning)
+    }
+
+    /// Returns true if the message arrived on the server too long ago to be downloaded.
+    pub fn is_too_old(&self, fetch: &Fetch) -> bool {
+        match (self.min_timestamp, fetch.internal_date()) {
+            (Some(min_timestamp), Some(internal_date)) => internal_date.timestamp() < min_timestamp,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestContext;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_fetch_policy_load() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let policy = FetchPolicy::load(&t).await?;
+        assert_eq!(policy.max_count, 100);
+        assert_eq!(policy.min_timestamp, None);
+        assert_eq!(
+            policy.folders,
+            vec![
+                FolderMeaning::Mvbox,
+                FolderMeaning::Inbox,
+                FolderMeaning::Sent
+            ]
+        );
+        assert!(policy.fetch_existing_from(FolderMeaning::Inbox));
+        assert!(!policy.fetch_existing_from(FolderMeaning::Spam));
+
+        t.set_config(Config::FetchMaxAge, Some("3600")).await?;
+        t.set_config(Config::FetchExistingMsgsFolders, Some("inbox foo"))
+            .await?;
+        let policy = FetchPolicy::load(&t).await?;
+        assert!(policy.min_timestamp.unwrap() <= time() - 3600);
+        assert_eq!(policy.folders, vec![FolderMeaning::Inbox]);
+        assert!(!policy.fetch_existing_from(FolderMeaning::Mvbox));
+
+        t.set_config_bool(Config::FetchExistingMsgs, false).await?;
+        assert_eq!(FetchPolicy::load(&t).await?.max_count, 0);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_fetch_policy_bot() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        t.set_config_bool(Config::Bot, true).await?;
+        assert_eq!(FetchPolicy::load(&t).await?.max_count, 0);
+
+        t.set_config(Config::FetchExistingMsgsCount, Some("10"))
+            .await?;
+        assert_eq!(FetchPolicy::load(&t).await?.max_count, 10);
+        Ok(())
+    }
+}
@@ -8,6 +8,7 @@
 use super::session::Session;
 use super::Imap;
 use crate::imap::{client::IMAP_TIMEOUT, FolderMeaning};
+use crate::tools::time;
 use crate::{context::Context, scheduler::InterruptInfo};
 
 const IDLE_TIMEOUT: Duration = Duration::from_secs(23 * 60);
@@ -209,4 +210,37 @@ enum Event {
 
         info
     }
+
+    /// Closes the connection and waits until the next poll or an interrupt.
+    ///
+    /// Used instead of IDLE if the `poll_interval` config is set.
+    pub(crate) async fn poll_idle(
+        &mut self,
+        context: &Context,
+        poll_interval: Duration,
+    ) -> InterruptInfo {
+        use futures::future::FutureExt;
+
+        // Logout is not needed, the server closes the connection on its side eventually.
+        self.session = None;
+
+        let next_poll = time().saturating_add(poll_interval.as_secs() as i64);
+        self.connectivity.set_polling(context, next_poll).await;
+        info!(
+            context,
+            "IMAP-poll: sleeping for {}s until the next poll.",
+            poll_interval.as_secs()
+        );
+
+        let info = tokio::time::sleep(poll_interval)
+            .map(|_| InterruptInfo::new(false))
+            .race(
+                self.idle_interrupt_receiver
+                    .recv()
+                    .map(|info| info.unwrap_or_default()),
+            )
+            .await;
+        info!(context, "IMAP-poll done.");
+        info
+    }
 }
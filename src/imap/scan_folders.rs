@@ -29,6 +29,7 @@ pub(crate) async fn scan_folders(&mut self, context: &Context) -> Result<bool> {
         self.prepare(context).await?;
         let folders = self.list_folders(context).await?;
         let watched_folders = get_watched_folders(context).await?;
+        let uses_imap_labels = context.uses_imap_labels().await?;
 
         let mut folder_configs = BTreeMap::new();
 
@@ -58,10 +59,15 @@ pub(crate) async fn scan_folders(&mut self, context: &Context) -> Result<bool> {
                 _ => folder_meaning,
             };
 
+            // User-defined labels only show messages which are in other folders as well,
+            // scanning them would fetch the same messages again.
+            let is_label = uses_imap_labels && folder_meaning == FolderMeaning::Unknown;
+
             // Don't scan folders that are watched anyway
             if !watched_folders.contains(&folder.name().to_string())
                 && folder_meaning != FolderMeaning::Drafts
                 && folder_meaning != FolderMeaning::Trash
+                && !is_label
             {
                 let session = self.session.as_mut().context("no session")?;
                 // Drain leftover unsolicited EXISTS messages
@@ -83,10 +89,13 @@ pub(crate) async fn scan_folders(&mut self, context: &Context) -> Result<bool> {
             }
         }
 
+        apply_folder_overrides(context, &mut folder_configs).await?;
+
         // Set configs for necessary folders. Or reset if the folder was deleted.
         for conf in [
             Config::ConfiguredSentboxFolder,
             Config::ConfiguredTrashFolder,
+            Config::ConfiguredArchiveFolder,
         ] {
             context
                 .set_config(conf, folder_configs.get(&conf).map(|s| s.as_str()))
@@ -116,6 +125,25 @@ pub async fn list_folders(
     }
 }
 
+/// Replaces detected folders by the folders configured with
+/// [`Config::SentboxFolder`] and [`Config::TrashFolder`].
+pub(crate) async fn apply_folder_overrides(
+    context: &Context,
+    folder_configs: &mut BTreeMap<Config, String>,
+) -> Result<()> {
+    for (override_config, config) in [
+        (Config::SentboxFolder, Config::ConfiguredSentboxFolder),
+        (Config::TrashFolder, Config::ConfiguredTrashFolder),
+    ] {
+        if let Some(folder) = context.get_config(override_config).await? {
+            if !folder.is_empty() {
+                folder_configs.insert(config, folder);
+            }
+        }
+    }
+    Ok(())
+}
+
 pub(crate) async fn get_watched_folder_configs(context: &Context) -> Result<Vec<Config>> {
     let mut res = vec![Config::ConfiguredInboxFolder];
     if context.get_config_bool(Config::SentboxWatch).await? {
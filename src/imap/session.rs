@@ -64,4 +64,20 @@ pub fn can_check_quota(&self) -> bool {
     pub fn can_condstore(&self) -> bool {
         self.capabilities.can_condstore
     }
+
+    pub fn can_metadata(&self) -> bool {
+        self.capabilities.can_metadata
+    }
+
+    pub fn can_push(&self) -> bool {
+        self.capabilities.can_push
+    }
+
+    pub fn is_chatmail(&self) -> bool {
+        self.capabilities.is_chatmail
+    }
+
+    pub fn has_labels(&self) -> bool {
+        self.capabilities.has_labels
+    }
 }
@@ -0,0 +1,232 @@
+//! # Pending IMAP operations.
+//!
+//! Moving and deleting messages on the server and setting the `\Seen` flag
+//! is not done immediately.
+//! Instead, the operations are scheduled in the `imap` and `imap_markseen` tables
+//! and executed by the IMAP loops the next time the folder is processed.
+//! This module allows to inspect these jobs
+//! and to retry or drop jobs which are stuck, e.g. because the target folder was deleted.
+
+use anyhow::{bail, Context as _, Result};
+use rusqlite::types::Value;
+
+use crate::context::Context;
+use crate::sql;
+
+/// Kind of a pending IMAP operation.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum ImapJobKind {
+    /// Move the message to another folder.
+    Move,
+
+    /// Delete the message, possibly by moving it to the trash folder.
+    Delete,
+
+    /// Set the `\Seen` flag.
+    MarkSeen,
+}
+
+/// Pending IMAP operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImapJob {
+    /// Row ID in the `imap` table, used to retry or drop the job.
+    pub id: u32,
+
+    /// Kind of the operation.
+    pub kind: ImapJobKind,
+
+    /// Folder containing the message.
+    pub folder: String,
+
+    /// UID of the message in the folder.
+    pub uid: u32,
+
+    /// Message-ID of the message.
+    pub rfc724_mid: String,
+
+    /// Target folder for [`ImapJobKind::Move`] and deletions to the trash folder.
+    pub target: Option<String>,
+
+    /// Timestamp when the job was scheduled,
+    /// 0 if the job was scheduled before the timestamp was recorded.
+    pub timestamp: i64,
+
+    /// Error of the last failed attempt to execute the job.
+    pub last_error: Option<String>,
+}
+
+/// Returns all pending IMAP operations, oldest first.
+pub async fn get_imap_jobs(context: &Context) -> Result<Vec<ImapJob>> {
+    let trash_folder = context.get_delete_msgs_target().await.unwrap_or_default();
+    context
+        .sql
+        .query_map(
+            "SELECT imap.id, folder, uid, rfc724_mid, target, job_timestamp, job_error,
+                    imap_markseen.timestamp
+             FROM imap
+             LEFT JOIN imap_markseen ON imap.id=imap_markseen.id
+             WHERE target!=folder OR imap_markseen.id IS NOT NULL
+             ORDER BY IFNULL(imap_markseen.timestamp, job_timestamp), imap.id",
+            (),
+            |row| {
+                let target: String = row.get(4)?;
+                let markseen_timestamp: Option<i64> = row.get(7)?;
+                let folder: String = row.get(1)?;
+                let (kind, timestamp) = if target != folder {
+                    let kind = if target.is_empty() || target == trash_folder {
+                        ImapJobKind::Delete
+                    } else {
+                        ImapJobKind::Move
+                    };
+                    (kind, row.get(5)?)
+                } else {
+                    (
+                        ImapJobKind::MarkSeen,
+                        markseen_timestamp.unwrap_or_default(),
+                    )
+                };
+                let last_error: String = row.get(6)?;
+                Ok(ImapJob {
+                    id: row.get(0)?,
+                    kind,
+                    folder,
+                    uid: row.get(2)?,
+                    rfc724_mid: row.get(3)?,
+                    target: Some(target).filter(|t| !t.is_empty() && kind != ImapJobKind::MarkSeen),
+                    timestamp,
+                    last_error: Some(last_error).filter(|e| !e.is_empty()),
+                })
+            },
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await
+}
+
+/// Clears the error of a pending IMAP operation
+/// and makes the IMAP loops try to execute it again.
+pub async fn retry_imap_job(context: &Context, id: u32) -> Result<()> {
+    let updated = context
+        .sql
+        .execute("UPDATE imap SET job_error='' WHERE id=?", (id,))
+        .await?;
+    if updated == 0 {
+        bail!("IMAP job {id} not found");
+    }
+    info!(context, "Retrying IMAP job {id}.");
+    context.scheduler.maybe_network().await;
+    Ok(())
+}
+
+/// Drops a pending IMAP operation without executing it.
+///
+/// The message stays in its folder.
+/// Note that deletions scheduled because of `delete_server_after`
+/// or ephemeral messages are scheduled again the next time expired messages are checked.
+pub async fn drop_imap_job(context: &Context, id: u32) -> Result<()> {
+    let job = get_imap_jobs(context)
+        .await?
+        .into_iter()
+        .find(|job| job.id == id)
+        .with_context(|| format!("IMAP job {id} not found"))?;
+    match job.kind {
+        ImapJobKind::Move | ImapJobKind::Delete => {
+            context
+                .sql
+                .execute(
+                    "UPDATE imap SET target=folder, job_error='' WHERE id=?",
+                    (id,),
+                )
+                .await?;
+        }
+        ImapJobKind::MarkSeen => {
+            context
+                .sql
+                .execute("DELETE FROM imap_markseen WHERE id=?", (id,))
+                .await?;
+        }
+    }
+    info!(
+        context,
+        "Dropped IMAP job {id} ({}) for message {} in folder {}.", job.kind, job.uid, job.folder
+    );
+    Ok(())
+}
+
+/// Records the error of a failed attempt to execute the jobs of the given `imap` rows.
+pub(crate) async fn set_imap_job_error(
+    context: &Context,
+    row_ids: &[i64],
+    error: &anyhow::Error,
+) -> Result<()> {
+    context
+        .sql
+        .execute(
+            &format!(
+                "UPDATE imap SET job_error=? WHERE id IN ({})",
+                sql::repeat_vars(row_ids.len())
+            ),
+            rusqlite::params_from_iter(
+                std::iter::once(Value::from(format!("{error:#}")))
+                    .chain(row_ids.iter().map(|id| Value::from(*id))),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestContext;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_imap_jobs() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        for (id, folder, target) in [
+            (1, "INBOX", "DeltaChat"),
+            (2, "INBOX", ""),
+            (3, "INBOX", "INBOX"),
+            (4, "DeltaChat", "DeltaChat"),
+        ] {
+            t.sql
+                .execute(
+                    "INSERT INTO imap (id, rfc724_mid, folder, uid, target) VALUES (?,?,?,?,?)",
+                    (id, format!("{id}@example.org"), folder, id, target),
+                )
+                .await?;
+        }
+        t.sql
+            .execute("INSERT INTO imap_markseen (id) VALUES (3)", ())
+            .await?;
+
+        let jobs = get_imap_jobs(&t).await?;
+        assert_eq!(
+            jobs.iter()
+                .map(|job| (job.id, job.kind, job.target.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, ImapJobKind::Move, Some("DeltaChat")),
+                (2, ImapJobKind::Delete, None),
+                (3, ImapJobKind::MarkSeen, None),
+            ]
+        );
+        assert!(jobs.iter().all(|job| job.last_error.is_none()));
+
+        set_imap_job_error(&t, &[1], &anyhow::anyhow!("No such folder")).await?;
+        let jobs = get_imap_jobs(&t).await?;
+        assert_eq!(jobs[0].last_error.as_deref(), Some("No such folder"));
+
+        retry_imap_job(&t, 1).await?;
+        let jobs = get_imap_jobs(&t).await?;
+        assert_eq!(jobs[0].last_error, None);
+
+        drop_imap_job(&t, 1).await?;
+        drop_imap_job(&t, 3).await?;
+        let jobs = get_imap_jobs(&t).await?;
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, 2);
+        assert!(drop_imap_job(&t, 4).await.is_err());
+
+        Ok(())
+    }
+}
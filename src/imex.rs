@@ -475,6 +475,12 @@ async fn import_backup(
 
     context.sql.run_migrations(context).await?;
     delete_and_reset_all_device_msgs(context).await?;
+    // This is a new device, do not reuse the device ID and push token of the old one.
+    context.set_config(Config::DeviceId, None).await?;
+    context.set_config(Config::PushDeviceToken, None).await?;
+    context
+        .set_config(Config::RegisteredPushDeviceToken, None)
+        .await?;
 
     Ok(())
 }
@@ -787,6 +793,7 @@ mod tests {
     use tokio::task;
 
     use super::*;
+    use crate::contact::Contact;
     use crate::pgp::{split_armored_data, HEADER_AUTOCRYPT, HEADER_SETUPCODE};
     use crate::stock_str::StockMessage;
     use crate::test_utils::{alice_keypair, TestContext};
@@ -884,6 +891,17 @@ async fn test_export_and_import_backup() -> Result<()> {
 
         let context1 = TestContext::new_alice().await;
         assert!(context1.is_configured().await?);
+        let bob_chat = context1
+            .create_chat_with_contact("Bob", "bob@example.net")
+            .await;
+        let chat_uuid = bob_chat.get_uuid().to_string();
+        assert_eq!(chat_uuid.len(), 32);
+        let contact_id = chat::get_chat_contacts(&context1, bob_chat.id).await?[0];
+        let contact_uuid = Contact::get_by_id(&context1, contact_id)
+            .await?
+            .get_uuid()
+            .to_string();
+        assert_ne!(contact_uuid, chat_uuid);
 
         let context2 = TestContext::new().await;
         assert!(!context2.is_configured().await?);
@@ -928,6 +946,14 @@ async fn test_export_and_import_backup() -> Result<()> {
             context2.get_config(Config::Addr).await?,
             Some("alice@example.org".to_string())
         );
+        assert_eq!(
+            ChatId::lookup_by_uuid(&context2, &chat_uuid).await?,
+            Some(bob_chat.id)
+        );
+        assert_eq!(
+            Contact::lookup_id_by_uuid(&context2, &contact_uuid).await?,
+            Some(contact_id)
+        );
 
         Ok(())
     }
@@ -48,6 +48,7 @@
 
 use crate::blob::BlobDirContents;
 use crate::chat::{add_device_msg, delete_and_reset_all_device_msgs};
+use crate::config::Config;
 use crate::context::Context;
 use crate::message::{Message, Viewtype};
 use crate::qr::Qr;
@@ -416,6 +417,12 @@ async fn get_backup_inner(context: &Context, qr: Qr) -> Result<()> {
         Ok(()) => {
             context.sql.run_migrations(context).await?;
             delete_and_reset_all_device_msgs(context).await?;
+            // This is a new device, do not reuse the device ID and push token of the old one.
+            context.set_config(Config::DeviceId, None).await?;
+            context.set_config(Config::PushDeviceToken, None).await?;
+            context
+                .set_config(Config::RegisteredPushDeviceToken, None)
+                .await?;
             context.emit_event(ReceiveProgress::Completed.into());
             Ok(())
         }
@@ -62,6 +62,10 @@ pub enum Action {
     // Most messages are downloaded automatically on fetch
     // and do not go through this job.
     DownloadMsg = 250,
+
+    // This job fetches the original of a message reported with `report_msg()`
+    // from the server and sends the report.
+    ReportMsg = 260,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -213,6 +217,7 @@ async fn perform_job_action(
 
     let try_res = match job.action {
         Action::DownloadMsg => job.download_msg(context, connection.inbox()).await,
+        Action::ReportMsg => job.report_msg(context, connection.inbox()).await,
     };
 
     info!(context, "Finished immediate try {tries} of job {job}.");
@@ -227,6 +227,26 @@ async fn generate_keypair(context: &Context) -> Result<KeyPair> {
     }
 }
 
+/// Generates a new default keypair, replacing the current one.
+///
+/// The old keys are kept to decrypt earlier messages,
+/// see [`Keyring::new_self_with_old_keys`](crate::keyring::Keyring::new_self_with_old_keys).
+pub(crate) async fn rotate_self_keypair(context: &Context) -> Result<()> {
+    let addr = context.get_primary_self_addr().await?;
+    let addr = EmailAddress::new(&addr)?;
+    let _guard = context.generating_key_mutex.lock().await;
+    let keytype =
+        KeyGenType::from_i32(context.get_config_int(Config::KeyGenType).await?).unwrap_or_default();
+    info!(
+        context,
+        "Rotating keypair, generating new one with type {}", keytype
+    );
+    let keypair = Handle::current()
+        .spawn_blocking(move || crate::pgp::create_keypair(addr, keytype))
+        .await??;
+    store_self_keypair(context, &keypair, KeyPairUse::Default).await
+}
+
 pub(crate) async fn load_keypair(
     context: &Context,
     addr: &EmailAddress,
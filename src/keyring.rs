@@ -3,7 +3,7 @@
 use anyhow::Result;
 
 use crate::context::Context;
-use crate::key::DcKey;
+use crate::key::{DcKey, SignedSecretKey};
 
 /// An in-memory keyring.
 ///
@@ -58,6 +58,29 @@ pub fn keys(&self) -> &[T] {
     }
 }
 
+impl Keyring<SignedSecretKey> {
+    /// Creates a new keyring with all own secret keys, the default one first.
+    ///
+    /// Keys replaced by [`crate::key::rotate_self_keypair`] are kept,
+    /// so messages encrypted to them can still be decrypted.
+    pub async fn new_self_with_old_keys(context: &Context) -> Result<Self> {
+        let mut keyring = Self::new_self(context).await?;
+        let old_keys = context
+            .sql
+            .query_map(
+                "SELECT private_key FROM keypairs WHERE is_default=0",
+                (),
+                |row| row.get::<_, Vec<u8>>(0),
+                |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await?;
+        for bytes in old_keys {
+            keyring.add(SignedSecretKey::from_slice(&bytes)?);
+        }
+        Ok(keyring)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,171 @@
+//! # Detection of late deliveries.
+//!
+//! Providers sometimes queue messages for hours before delivering them,
+//! so they appear out of order in chats.
+//! The delay is measured from the `Date` header set by the sender
+//! to the topmost `Received` header added by our provider,
+//! this way the time a device is offline is not counted.
+//! Messages delivered late are marked with [`crate::param::Param::DeliveryDelay`],
+//! see [`crate::message::Message::get_delivery_delay`],
+//! and counted per domain of the sender to be shown in the connectivity view.
+
+use anyhow::Result;
+
+use crate::context::Context;
+use crate::headerdef::HeaderDef;
+use crate::mimeparser::MimeMessage;
+use crate::tools::{time, EmailAddress};
+
+/// Minimum delay of a message to be considered delivered late, in seconds.
+pub(crate) const LATE_DELIVERY_THRESHOLD: i64 = 2 * 60 * 60;
+
+/// Late deliveries are counted for this time, in seconds.
+const LATE_DELIVERY_MAX_AGE: i64 = 30 * 24 * 60 * 60;
+
+/// Late deliveries from a domain within the last 30 days.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LateDeliveries {
+    /// Domain of the senders.
+    pub domain: String,
+
+    /// Number of messages delivered late.
+    pub count: u32,
+
+    /// Maximum delay in seconds.
+    pub max_delay: i64,
+}
+
+/// Returns the delivery delay in seconds if the message was delivered late.
+pub(crate) fn get_delivery_delay(mime_parser: &MimeMessage) -> Option<i64> {
+    let sent_timestamp = mime_parser
+        .get_header(HeaderDef::Date)
+        .and_then(|value| mailparse::dateparse(value).ok())?;
+    let delay = mime_parser.received_timestamp? - sent_timestamp;
+    if delay >= LATE_DELIVERY_THRESHOLD {
+        Some(delay)
+    } else {
+        None
+    }
+}
+
+/// Counts a message from `from_addr` delivered late.
+pub(crate) async fn add_late_delivery(
+    context: &Context,
+    from_addr: &str,
+    sent_timestamp: i64,
+    delay: i64,
+) -> Result<()> {
+    let domain = EmailAddress::new(from_addr)?.domain.to_lowercase();
+    context
+        .sql
+        .execute(
+            "INSERT INTO late_deliveries (domain, timestamp, delay) VALUES (?, ?, ?)",
+            (domain, sent_timestamp, delay),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Returns the late deliveries of the last 30 days per domain, most affected domains first.
+pub(crate) async fn get_late_deliveries(context: &Context) -> Result<Vec<LateDeliveries>> {
+    context
+        .sql
+        .query_map(
+            "SELECT domain, COUNT(*), MAX(delay) FROM late_deliveries
+             WHERE timestamp>=?
+             GROUP BY domain
+             ORDER BY COUNT(*) DESC, domain",
+            (time() - LATE_DELIVERY_MAX_AGE,),
+            |row| {
+                Ok(LateDeliveries {
+                    domain: row.get(0)?,
+                    count: row.get(1)?,
+                    max_delay: row.get(2)?,
+                })
+            },
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await
+}
+
+/// Deletes late deliveries which are not counted anymore.
+pub(crate) async fn delete_old_late_deliveries(context: &Context) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "DELETE FROM late_deliveries WHERE timestamp<?",
+            (time() - LATE_DELIVERY_MAX_AGE,),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::message::Message;
+    use crate::receive_imf::receive_imf;
+    use crate::test_utils::TestContext;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_late_delivery() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let now = time();
+        let rfc2822 = |timestamp| {
+            chrono::Utc
+                .timestamp_opt(timestamp, 0)
+                .unwrap()
+                .to_rfc2822()
+        };
+        let receive = |rfc724_mid: &'static str, sent: i64, received: i64| {
+            format!(
+                "Received: from mail.example.net by mx.example.org; {}\n\
+                 From: bob@Example.NET\n\
+                 To: alice@example.org\n\
+                 Subject: hi\n\
+                 Message-ID: <{rfc724_mid}>\n\
+                 Date: {}\n\
+                 Chat-Version: 1.0\n\
+                 \n\
+                 hello\n",
+                rfc2822(received),
+                rfc2822(sent)
+            )
+        };
+
+        let raw = receive("1@example.net", now - 3 * 3600, now - 3 * 3600 + 10);
+        let msg = receive_imf(&t, raw.as_bytes(), false).await?.unwrap();
+        let msg = Message::load_from_db(&t, msg.msg_ids[0]).await?;
+        assert_eq!(msg.get_delivery_delay(), None);
+
+        let raw = receive("2@example.net", now - 5 * 3600, now - 3600);
+        let msg = receive_imf(&t, raw.as_bytes(), false).await?.unwrap();
+        let msg = Message::load_from_db(&t, msg.msg_ids[0]).await?;
+        assert_eq!(msg.get_delivery_delay(), Some(4 * 3600));
+
+        let raw = receive("3@example.net", now - 3 * 3600, now);
+        receive_imf(&t, raw.as_bytes(), false).await?;
+        let late = get_late_deliveries(&t).await?;
+        assert_eq!(
+            late,
+            vec![LateDeliveries {
+                domain: "example.net".to_string(),
+                count: 2,
+                max_delay: 4 * 3600,
+            }]
+        );
+
+        // Old late deliveries are not counted.
+        add_late_delivery(&t, "bob@example.com", now - 40 * 24 * 3600, 3 * 3600).await?;
+        assert_eq!(get_late_deliveries(&t).await?.len(), 1);
+        delete_old_late_deliveries(&t).await?;
+        let count: usize = t
+            .sql
+            .count("SELECT COUNT(*) FROM late_deliveries", ())
+            .await?;
+        assert_eq!(count, 2);
+        Ok(())
+    }
+}
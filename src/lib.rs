@@ -50,21 +50,35 @@
 pub(crate) mod events;
 pub use events::*;
 
+pub mod account_stats;
+pub mod aeap;
 mod aheader;
+#[cfg(feature = "recode-voice")]
+mod audio;
 mod blob;
+pub mod blocklist;
+pub mod calls;
+pub mod canned_response;
 pub mod chat;
+pub mod chat_export;
 pub mod chatlist;
+mod clock;
+pub mod clock_skew;
 pub mod config;
 mod configure;
 pub mod constants;
 pub mod contact;
 pub mod context;
 mod decrypt;
+pub mod devices;
 pub mod download;
 mod e2ee;
 pub mod ephemeral;
+pub mod error_code;
+pub mod gif_search;
 mod http;
 mod imap;
+pub mod imap_jobs;
 pub mod imex;
 pub mod release;
 mod scheduler;
@@ -72,20 +86,26 @@
 mod job;
 pub mod key;
 mod keyring;
+mod late_delivery;
 pub mod location;
 mod login_param;
 pub mod message;
+mod message_format;
 mod mimefactory;
 pub mod mimeparser;
+pub mod notification;
 pub mod oauth2;
 mod param;
+pub mod peer_transfer;
 pub mod peerstate;
 pub mod pgp;
 pub mod provider;
+mod push;
 pub mod qr;
 pub mod qr_code_generator;
 pub mod quota;
 pub mod securejoin;
+mod settings;
 mod simplify;
 mod smtp;
 mod socks;
@@ -110,6 +130,8 @@
 
 pub mod accounts;
 pub mod reaction;
+pub mod report;
+pub mod reputation;
 
 /// If set IMAP/incoming and SMTP/outgoing MIME messages will be printed.
 pub const DCC_MIME_DEBUG: &str = "DCC_MIME_DEBUG";
@@ -1,12 +1,14 @@
 //! # Messages and their identifiers.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::{Path, PathBuf};
 
 use anyhow::{ensure, format_err, Context as _, Result};
 use deltachat_derive::{FromSql, ToSql};
+use num_traits::FromPrimitive;
 use serde::{Deserialize, Serialize};
 
+use crate::calls::CallState;
 use crate::chat::{self, Chat, ChatId};
 use crate::config::Config;
 use crate::constants::{
@@ -18,9 +20,9 @@
 use crate::download::DownloadState;
 use crate::ephemeral::{start_ephemeral_timers_msgids, Timer as EphemeralTimer};
 use crate::events::EventType;
-use crate::imap::markseen_on_imap_table;
+use crate::imap::markflagged_on_imap_table;
 use crate::mimeparser::{parse_message_id, DeliveryReport, SystemMessage};
-use crate::param::{Param, Params};
+use crate::param::{Param, Params, ParamsFile};
 use crate::pgp::split_armored_data;
 use crate::reaction::get_msg_reactions;
 use crate::scheduler::InterruptInfo;
@@ -28,10 +30,56 @@
 use crate::stock_str;
 use crate::summary::Summary;
 use crate::tools::{
-    buf_compress, buf_decompress, create_smeared_timestamp, get_filebytes, get_filemeta,
-    gm2local_offset, read_file, time, timestamp_to_str, truncate,
+    buf_compress, buf_decompress, create_smeared_timestamp, delete_file, get_filebytes,
+    get_filemeta, gm2local_offset, read_file, time, timestamp_to_str, truncate,
 };
 
+/// Common table expression selecting the root of the thread of the message `?` as `root(id)`.
+///
+/// The `In-Reply-To` chain is followed as long as the parent message is in the same chat.
+/// The depth is limited in case of reply cycles.
+const THREAD_ROOT_CTE: &str = "WITH RECURSIVE
+    ancestors(id, mime_in_reply_to, chat_id, depth) AS (
+        SELECT id, mime_in_reply_to, chat_id, 0 FROM msgs WHERE id=?
+        UNION ALL
+        SELECT p.id, p.mime_in_reply_to, p.chat_id, a.depth+1 FROM msgs p, ancestors a
+        WHERE a.mime_in_reply_to!='' AND a.depth<1000
+        AND p.chat_id=a.chat_id AND p.hidden=0
+        AND p.rfc724_mid=TRIM(a.mime_in_reply_to, '<>')
+    ),
+    root(id) AS (SELECT id FROM ancestors ORDER BY depth DESC LIMIT 1)";
+
+/// Returns a query running `select` on the messages of the thread of the message `?`,
+/// available as `thread(id, rfc724_mid, chat_id)`, see [`THREAD_ROOT_CTE`].
+fn thread_query(select: &str) -> String {
+    format!(
+        "{THREAD_ROOT_CTE},
+        thread(id, rfc724_mid, chat_id) AS (
+            SELECT m.id, m.rfc724_mid, m.chat_id FROM msgs m, root r WHERE m.id=r.id
+            UNION
+            SELECT m.id, m.rfc724_mid, m.chat_id FROM msgs m, thread t
+            WHERE m.chat_id=t.chat_id AND m.hidden=0
+            AND TRIM(m.mime_in_reply_to, '<>')=t.rfc724_mid
+        )
+        {select}"
+    )
+}
+
+/// Query moving a message into the trash chat and removing its content.
+///
+/// If you change which information is removed here, also change delete_expired_messages() and
+/// which information receive_imf::add_parts() still adds to the db if the chat_id is TRASH.
+const TRASH_MSG_SQL: &str = r#"
+UPDATE msgs 
+SET 
+  chat_id=?, txt='', 
+  subject='', txt_raw='', 
+  mime_headers='', 
+  from_id=0, to_id=0, 
+  param='' 
+WHERE id=?;
+"#;
+
 /// Message ID, including reserved IDs.
 ///
 /// Some message IDs are reserved to identify special message types.
@@ -91,24 +139,7 @@ pub async fn get_state(self, context: &Context) -> Result<MessageState> {
     /// 2. be able to delete the message on the server if we want to
     pub async fn trash(self, context: &Context) -> Result<()> {
         let chat_id = DC_CHAT_ID_TRASH;
-        context
-            .sql
-            .execute(
-                // If you change which information is removed here, also change delete_expired_messages() and
-                // which information receive_imf::add_parts() still adds to the db if the chat_id is TRASH
-                r#"
-UPDATE msgs 
-SET 
-  chat_id=?, txt='', 
-  subject='', txt_raw='', 
-  mime_headers='', 
-  from_id=0, to_id=0, 
-  param='' 
-WHERE id=?;
-"#,
-                (chat_id, self),
-            )
-            .await?;
+        context.sql.execute(TRASH_MSG_SQL, (chat_id, self)).await?;
 
         Ok(())
     }
@@ -129,6 +160,10 @@ pub async fn delete_from_db(self, context: &Context) -> Result<()> {
             .sql
             .execute("DELETE FROM msgs_status_updates WHERE msg_id=?;", (self,))
             .await?;
+        context
+            .sql
+            .execute("DELETE FROM saved_msg_tags WHERE msg_id=?;", (self,))
+            .await?;
         context
             .sql
             .execute("DELETE FROM msgs WHERE id=?;", (self,))
@@ -136,6 +171,213 @@ pub async fn delete_from_db(self, context: &Context) -> Result<()> {
         Ok(())
     }
 
+    /// Returns the media of a view-once message.
+    ///
+    /// The media of received view-once messages is moved out of the blobdir on receiving,
+    /// so this is the only way to retrieve it; [`Message::get_file`] returns `None`.
+    /// The media is not included in backups.
+    ///
+    /// For incoming messages the file is deleted afterwards and the message
+    /// is marked as seen and viewed, so the media can be retrieved only once.
+    pub async fn open_view_once(self, context: &Context) -> Result<Vec<u8>> {
+        let mut msg = Message::load_from_db(context, self).await?;
+        ensure!(msg.is_view_once(), "{self} is not a view-once message");
+        ensure!(
+            !msg.is_view_once_viewed(),
+            "View-once {self} was already viewed"
+        );
+        let path = match msg.param.get(Param::ViewOnceFile) {
+            Some(name) => context.get_view_once_dir().join(name),
+            None => msg
+                .get_file(context)
+                .with_context(|| format!("View-once {self} has no file"))?,
+        };
+        let data = read_file(context, &path).await?;
+
+        if msg.from_id != ContactId::SELF {
+            msg.param.remove(Param::File);
+            msg.param.remove(Param::ViewOnceFile);
+            msg.param.set_int(Param::ViewOnce, 2);
+            msg.update_param(context).await?;
+            if let Err(err) = delete_file(context, &path).await {
+                warn!(context, "Cannot delete view-once media: {err:#}.");
+            }
+            markseen_msgs(context, vec![self]).await?;
+            context.emit_msgs_changed(msg.chat_id, self);
+        }
+        Ok(data)
+    }
+
+    /// Returns the root of the thread the message belongs to.
+    ///
+    /// The thread root is found by following the `In-Reply-To` chain
+    /// as long as the parent message is in the same chat.
+    pub async fn get_thread_root(self, context: &Context) -> Result<MsgId> {
+        let root = context
+            .sql
+            .query_get_value(&format!("{THREAD_ROOT_CTE} SELECT id FROM root"), (self,))
+            .await?;
+        Ok(root.unwrap_or(self))
+    }
+
+    /// Returns all messages of the thread the message belongs to,
+    /// starting with the thread root and ordered by timestamp.
+    pub async fn get_thread(self, context: &Context) -> Result<Vec<MsgId>> {
+        context
+            .sql
+            .query_map(
+                &thread_query(
+                    "SELECT m.id FROM thread t, msgs m WHERE m.id=t.id
+                     ORDER BY m.timestamp, m.id",
+                ),
+                (self,),
+                |row| row.get::<_, MsgId>(0),
+                |ids| ids.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await
+    }
+
+    /// Returns the number of fresh messages in the thread the message belongs to.
+    pub async fn get_thread_fresh_msg_cnt(self, context: &Context) -> Result<usize> {
+        context
+            .sql
+            .count(
+                &thread_query(
+                    "SELECT COUNT(*) FROM thread t, msgs m WHERE m.id=t.id AND m.state=?",
+                ),
+                (self, MessageState::InFresh),
+            )
+            .await
+    }
+
+    /// Stars or unstars the message.
+    ///
+    /// The star is stored as `\Flagged` flag on the IMAP server,
+    /// so it is shared with other devices and other email clients.
+    pub async fn set_starred(self, context: &Context, starred: bool) -> Result<()> {
+        let (chat_id, rfc724_mid): (ChatId, String) = context
+            .sql
+            .query_row_optional(
+                "SELECT chat_id, rfc724_mid FROM msgs WHERE id=?",
+                (self,),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .await?
+            .with_context(|| format!("{self} does not exist"))?;
+        context
+            .sql
+            .execute("UPDATE msgs SET starred=? WHERE id=?", (starred, self))
+            .await?;
+        markflagged_on_imap_table(context, &rfc724_mid, starred).await?;
+        context.emit_msgs_changed(chat_id, self);
+        Ok(())
+    }
+
+    /// Adds the info message to the run of info messages of the same type
+    /// directly preceding it in the chat, if any, see [`Message::get_info_group`].
+    pub(crate) async fn update_info_group(self, context: &Context) -> Result<()> {
+        let msg = Message::load_from_db(context, self).await?;
+        let cmd = msg.get_info_type();
+        if !msg.is_info() || cmd == SystemMessage::Unknown || msg.hidden || msg.chat_id.is_special()
+        {
+            return Ok(());
+        }
+
+        let prev = context
+            .sql
+            .query_row_optional(
+                "SELECT id, param, info_group FROM msgs
+                  WHERE chat_id=? AND hidden=0 AND (timestamp<? OR (timestamp=? AND id<?))
+                  ORDER BY timestamp DESC, id DESC LIMIT 1",
+                (msg.chat_id, msg.timestamp_sort, msg.timestamp_sort, self),
+                |row| {
+                    let id: MsgId = row.get(0)?;
+                    let param: Params = row.get::<_, String>(1)?.parse().unwrap_or_default();
+                    let info_group: MsgId = row.get(2)?;
+                    Ok((id, param.get_cmd(), info_group))
+                },
+            )
+            .await?;
+        let (prev_id, prev_cmd, prev_group) = match prev {
+            Some(prev) => prev,
+            None => return Ok(()),
+        };
+        if prev_cmd != cmd {
+            return Ok(());
+        }
+
+        let group = if prev_group.is_unset() {
+            prev_id
+        } else {
+            prev_group
+        };
+        context
+            .sql
+            .execute(
+                "UPDATE msgs SET info_group=? WHERE id=? OR id=?",
+                (group, prev_id, self),
+            )
+            .await?;
+        if prev_group.is_unset() {
+            context.emit_msgs_changed(msg.chat_id, prev_id);
+        }
+        Ok(())
+    }
+
+    /// Adds a tag to a message in the saved messages chat.
+    ///
+    /// Tags are compared case-insensitively, see [`Context::get_saved_by_tag`].
+    pub async fn add_saved_tag(self, context: &Context, tag: &str) -> Result<()> {
+        let tag = tag.trim();
+        ensure!(
+            !tag.is_empty() && !tag.contains('\n'),
+            "Invalid tag {tag:?}"
+        );
+        let msg = Message::load_from_db(context, self).await?;
+        let chat = Chat::load_from_db(context, msg.chat_id).await?;
+        ensure!(chat.is_self_talk(), "{self} is not a saved message");
+        context
+            .sql
+            .execute(
+                "INSERT OR IGNORE INTO saved_msg_tags (msg_id, tag) VALUES (?, ?)",
+                (self, tag),
+            )
+            .await?;
+        context.emit_msgs_changed(msg.chat_id, self);
+        Ok(())
+    }
+
+    /// Removes a tag from a message in the saved messages chat.
+    pub async fn remove_saved_tag(self, context: &Context, tag: &str) -> Result<()> {
+        let chat_id: ChatId = context
+            .sql
+            .query_get_value("SELECT chat_id FROM msgs WHERE id=?", (self,))
+            .await?
+            .with_context(|| format!("{self} does not exist"))?;
+        context
+            .sql
+            .execute(
+                "DELETE FROM saved_msg_tags WHERE msg_id=? AND tag=?",
+                (self, tag.trim()),
+            )
+            .await?;
+        context.emit_msgs_changed(chat_id, self);
+        Ok(())
+    }
+
+    /// Returns the tags of a message in the saved messages chat, sorted alphabetically.
+    pub async fn get_saved_tags(self, context: &Context) -> Result<Vec<String>> {
+        context
+            .sql
+            .query_map(
+                "SELECT tag FROM saved_msg_tags WHERE msg_id=? ORDER BY tag",
+                (self,),
+                |row| row.get::<_, String>(0),
+                |tags| tags.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await
+    }
+
     pub(crate) async fn set_delivered(self, context: &Context) -> Result<()> {
         update_msg_state(context, self, MessageState::OutDelivered).await?;
         let chat_id: ChatId = context
@@ -276,6 +518,13 @@ pub struct Message {
     pub(crate) location_id: u32,
     pub(crate) error: Option<String>,
     pub(crate) param: Params,
+
+    /// Whether the message is starred, synchronized with the IMAP `\Flagged` flag.
+    pub(crate) starred: bool,
+
+    /// First message of the run of consecutive info messages of the same type
+    /// the message belongs to, see [`Message::get_info_group`].
+    pub(crate) info_group: MsgId,
 }
 
 impl Message {
@@ -321,6 +570,8 @@ pub async fn load_from_db(context: &Context, id: MsgId) -> Result<Message> {
                     "    m.param AS param,",
                     "    m.hidden AS hidden,",
                     "    m.location_id AS location,",
+                    "    m.starred AS starred,",
+                    "    m.info_group AS info_group,",
                     "    c.blocked AS blocked",
                     " FROM msgs m LEFT JOIN chats c ON c.id=m.chat_id",
                     " WHERE m.id=?;"
@@ -372,6 +623,8 @@ pub async fn load_from_db(context: &Context, id: MsgId) -> Result<Message> {
                         param: row.get::<_, String>("param")?.parse().unwrap_or_default(),
                         hidden: row.get("hidden")?,
                         location_id: row.get("location")?,
+                        starred: row.get("starred")?,
+                        info_group: row.get("info_group")?,
                         chat_blocked: row
                             .get::<_, Option<Blocked>>("blocked")?
                             .unwrap_or_default(),
@@ -534,6 +787,36 @@ pub fn get_filename(&self) -> Option<String> {
             .map(|name| name.to_string_lossy().to_string())
     }
 
+    /// Returns the name and the size in bytes of the file offered peer-to-peer,
+    /// see [`crate::peer_transfer`].
+    ///
+    /// Once downloaded, the file is also returned by [`Message::get_file`].
+    pub fn get_p2p_file(&self) -> Option<(String, u64)> {
+        self.param.get(Param::P2pTicket)?;
+        let name = self.param.get(Param::P2pFileName).unwrap_or_default();
+        let name = Path::new(name).file_name()?.to_string_lossy().to_string();
+        let bytes = self.param.get(Param::P2pFileBytes)?.parse().ok()?;
+        Some((name, bytes))
+    }
+
+    /// Returns the delay in seconds if the message was delivered late.
+    ///
+    /// Messages are considered late if they reached our provider
+    /// more than two hours after they were sent.
+    /// UIs may show a marker as such messages may appear out of order.
+    pub fn get_delivery_delay(&self) -> Option<i64> {
+        self.param.get_i64(Param::DeliveryDelay)
+    }
+
+    /// Returns the state of a call record or call offer, see [`crate::calls`].
+    ///
+    /// The duration of accepted calls is returned by [`Message::get_duration`].
+    pub fn get_call_state(&self) -> Option<CallState> {
+        self.param
+            .get_int(Param::CallState)
+            .and_then(CallState::from_i32)
+    }
+
     /// Returns the size of the file in bytes, if applicable.
     pub async fn get_filebytes(&self, context: &Context) -> Result<Option<u64>> {
         if let Some(path) = self.param.get_path(Param::File, context)? {
@@ -568,6 +851,25 @@ pub fn is_bot(&self) -> bool {
         self.param.get_bool(Param::Bot).unwrap_or_default()
     }
 
+    /// Returns the action the UI can offer for a device message,
+    /// e.g. as a button below the message.
+    ///
+    /// Unknown actions, e.g. added by a newer version, are ignored.
+    pub fn get_device_action(&self) -> Option<DeviceAction> {
+        if self.from_id != ContactId::UNDEFINED && self.from_id != ContactId::DEVICE {
+            return None;
+        }
+        self.param.get(Param::DeviceAction)?.parse().ok()
+    }
+
+    /// Sets the action to offer for a message added using [`chat::add_device_msg`].
+    ///
+    /// If the message has no text, the text is set to a description of the action
+    /// for UIs not supporting actions.
+    pub fn set_device_action(&mut self, action: DeviceAction) {
+        self.param.set(Param::DeviceAction, action.as_ref());
+    }
+
     /// Return the ephemeral timer duration for a message.
     pub fn get_ephemeral_timer(&self) -> EphemeralTimer {
         self.ephemeral_timer
@@ -600,7 +902,7 @@ pub async fn get_summary(&self, context: &Context, chat: Option<&Chat>) -> Resul
             None
         };
 
-        Ok(Summary::new(context, self, chat, contact.as_ref()).await)
+        Summary::new(context, self, chat, contact.as_ref()).await
     }
 
     // It's a little unfortunate that the UI has to first call `dc_msg_get_override_sender_name` and then if it was `NULL`, call
@@ -648,6 +950,55 @@ pub fn is_forwarded(&self) -> bool {
         0 != self.param.get_int(Param::Forwarded).unwrap_or_default()
     }
 
+    /// Returns the ID of the chat a saved message was saved from.
+    ///
+    /// Returns `None` if the message was not saved by forwarding it to the saved messages chat.
+    pub fn get_saved_from_chat_id(&self) -> Option<ChatId> {
+        self.param
+            .get_int(Param::SavedFromChat)
+            .map(|id| ChatId::new(id as u32))
+    }
+
+    /// Returns the ID of the original author of a saved message.
+    ///
+    /// Returns `None` if the message was not saved by forwarding it to the saved messages chat.
+    pub fn get_saved_from_contact_id(&self) -> Option<ContactId> {
+        self.param
+            .get_int(Param::SavedFromContact)
+            .map(|id| ContactId::new(id as u32))
+    }
+
+    /// Returns the names of the metadata removed from an outgoing image,
+    /// e.g. `GPSLatitude` or `Model`, see [`Config::StripExif`].
+    ///
+    /// [`Config::StripExif`]: crate::config::Config::StripExif
+    pub fn get_removed_metadata(&self) -> Vec<String> {
+        self.param
+            .get(Param::RemovedMetadata)
+            .unwrap_or_default()
+            .split_ascii_whitespace()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Returns true if the message is starred, see [`MsgId::set_starred`].
+    pub fn is_starred(&self) -> bool {
+        self.starred
+    }
+
+    /// Returns true if the message is a view-once message.
+    ///
+    /// See [`Message::set_view_once`].
+    pub fn is_view_once(&self) -> bool {
+        0 != self.param.get_int(Param::ViewOnce).unwrap_or_default()
+    }
+
+    /// Returns true if the media of a received view-once message was already viewed
+    /// and deleted from the device.
+    pub fn is_view_once_viewed(&self) -> bool {
+        self.param.get_int(Param::ViewOnce) == Some(2)
+    }
+
     /// Returns true if the message is an informational message.
     pub fn is_info(&self) -> bool {
         let cmd = self.param.get_cmd();
@@ -661,6 +1012,16 @@ pub fn get_info_type(&self) -> SystemMessage {
         self.param.get_cmd()
     }
 
+    /// Returns the ID of the first message of the run of consecutive info messages
+    /// of the same type this message belongs to,
+    /// e.g. several "member added" messages in a row.
+    ///
+    /// UIs can collapse consecutive messages with the same info group.
+    /// Returns `None` if the message is not part of such a run.
+    pub fn get_info_group(&self) -> Option<MsgId> {
+        Some(self.info_group).filter(|msg_id| !msg_id.is_unset())
+    }
+
     /// Returns true if the message is a system message.
     pub fn is_system_message(&self) -> bool {
         let cmd = self.param.get_cmd();
@@ -788,6 +1149,24 @@ pub fn get_videochat_type(&self) -> Option<VideochatType> {
         None
     }
 
+    /// Parses the videochat invitation if the message is one.
+    ///
+    /// Returns `None` for other messages and for invitations with a malformed URL.
+    pub fn get_videochat_invitation(&self) -> Option<VideochatInvitation> {
+        if self.viewtype != Viewtype::VideochatInvitation {
+            return None;
+        }
+        let (videochat_type, url) =
+            Message::parse_webrtc_instance(self.param.get(Param::WebrtcRoom)?);
+        let parsed = url::Url::parse(&url).ok()?;
+        Some(VideochatInvitation {
+            videochat_type,
+            host: parsed.host_str()?.to_string(),
+            room: get_videochat_room(&parsed),
+            url,
+        })
+    }
+
     /// Sets or unsets message text.
     pub fn set_text(&mut self, text: Option<String>) {
         self.text = text;
@@ -811,6 +1190,47 @@ pub fn set_file(&mut self, file: impl ToString, filemime: Option<&str>) {
         }
     }
 
+    /// Returns the grouping ID of the album the message belongs to,
+    /// see [`crate::chat::send_album`].
+    pub fn get_album_id(&self) -> Option<&str> {
+        self.param.get(Param::Album)
+    }
+
+    /// Adds another file to a message which already has a file set with [`Message::set_file`].
+    ///
+    /// All files are sent in one `multipart/mixed` email.
+    /// As for [`Message::set_file`], the file will only be used when the message is prepared
+    /// for sending.
+    pub fn add_attachment(&mut self, file: &str) {
+        let attachments = match self.param.get(Param::Attachments) {
+            Some(attachments) => format!("{attachments}\n{file}"),
+            None => file.to_string(),
+        };
+        self.param.set(Param::Attachments, attachments);
+    }
+
+    /// Returns the paths of all files of the message,
+    /// starting with the file returned by [`Message::get_file`].
+    ///
+    /// Received messages get several files
+    /// if they were sent by Delta Chat with [`Message::add_attachment`].
+    pub fn get_attachments(&self, context: &Context) -> Vec<PathBuf> {
+        let mut attachments: Vec<PathBuf> = self.get_file(context).into_iter().collect();
+        for file in self
+            .param
+            .get(Param::Attachments)
+            .unwrap_or_default()
+            .lines()
+        {
+            match ParamsFile::from_param(context, file) {
+                Ok(ParamsFile::FsPath(path)) => attachments.push(path),
+                Ok(ParamsFile::Blob(blob)) => attachments.push(blob.to_abs_path()),
+                Err(err) => warn!(context, "Invalid attachment {file:?}: {err:#}."),
+            }
+        }
+        attachments
+    }
+
     /// Set different sender name for a message.
     /// This overrides the name set by the `set_config()`-option `displayname`.
     pub fn set_override_sender_name(&mut self, name: Option<String>) {
@@ -832,6 +1252,19 @@ pub fn set_duration(&mut self, duration: i32) {
         self.param.set_int(Param::Duration, duration);
     }
 
+    /// Marks an image, GIF, voice or video message as view-once.
+    ///
+    /// The recipient's device deletes the media after it was viewed once,
+    /// see [`MsgId::open_view_once`]. The text of view-once messages is not sent,
+    /// other clients get a text explaining the message instead.
+    pub fn set_view_once(&mut self, view_once: bool) {
+        if view_once {
+            self.param.set_int(Param::ViewOnce, 1);
+        } else {
+            self.param.remove(Param::ViewOnce);
+        }
+    }
+
     /// Marks the message as reaction.
     pub(crate) fn set_reaction(&mut self) {
         self.param.set_int(Param::Reaction, 1);
@@ -980,6 +1413,49 @@ pub fn error(&self) -> Option<String> {
     }
 }
 
+/// Videochat invitation, see [`Message::get_videochat_invitation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideochatInvitation {
+    /// Type of the videochat service.
+    pub videochat_type: VideochatType,
+
+    /// Host of the videochat instance, e.g. `meet.jit.si`.
+    pub host: String,
+
+    /// Name of the room, `None` if the service does not use rooms in URLs.
+    pub room: Option<String>,
+
+    /// URL to join the videochat.
+    pub url: String,
+}
+
+/// Extracts the room from a videochat URL.
+///
+/// Rooms are found in the same places [`Message::create_webrtc_instance`] puts them:
+/// a parameter of the fragment or the query whose name contains `room` or a parameter without name,
+/// otherwise the last path segment.
+fn get_videochat_room(url: &url::Url) -> Option<String> {
+    let from_params = |params: &str| {
+        params.split('&').find_map(|param| {
+            let room = match param.split_once('=') {
+                Some((name, value)) if name.to_lowercase().contains("room") => value,
+                Some(_) => return None,
+                None => param,
+            };
+            Some(room.to_string()).filter(|room| !room.is_empty())
+        })
+    };
+    url.fragment()
+        .and_then(from_params)
+        .or_else(|| url.query().and_then(from_params))
+        .or_else(|| {
+            url.path_segments()?
+                .filter(|segment| !segment.is_empty())
+                .last()
+                .map(|segment| segment.to_string())
+        })
+}
+
 /// State of the message.
 /// For incoming messages, stores the information on whether the message was read or not.
 /// For outgoing message, the message could be pending, already delivered or confirmed.
@@ -1395,44 +1871,72 @@ pub async fn get_mime_headers(context: &Context, msg_id: MsgId) -> Result<Vec<u8
     Ok(headers)
 }
 
+/// Emits one `MsgsDeleted` event per chat for the deleted messages.
+pub(crate) fn emit_msgs_deleted(
+    context: &Context,
+    deleted: impl IntoIterator<Item = (ChatId, MsgId)>,
+) {
+    let mut chats: BTreeMap<ChatId, Vec<MsgId>> = BTreeMap::new();
+    for (chat_id, msg_id) in deleted {
+        chats.entry(chat_id).or_default().push(msg_id);
+    }
+    for (chat_id, msg_ids) in chats {
+        context.emit_event(EventType::MsgsDeleted { chat_id, msg_ids });
+    }
+}
+
 /// Deletes requested messages
 /// by moving them to the trash chat
 /// and scheduling for deletion on IMAP.
+///
+/// All messages are deleted in a single transaction
+/// and only one `DC_EVENT_MSGS_CHANGED` is emitted at the end,
+/// so the function is suitable for deleting large selections of messages.
 pub async fn delete_msgs(context: &Context, msg_ids: &[MsgId]) -> Result<()> {
-    for msg_id in msg_ids.iter() {
-        let msg = Message::load_from_db(context, *msg_id).await?;
-        if msg.location_id > 0 {
-            delete_poi_location(context, msg.location_id).await?;
-        }
-        msg_id
-            .trash(context)
-            .await
-            .with_context(|| format!("Unable to trash message {msg_id}"))?;
+    let mut msgs = Vec::with_capacity(msg_ids.len());
+    for msg_id in msg_ids {
+        msgs.push(Message::load_from_db(context, *msg_id).await?);
+    }
 
+    let target = context.get_delete_msgs_target().await?;
+    context
+        .sql
+        .transaction(|transaction| {
+            for msg in &msgs {
+                if msg.location_id > 0 {
+                    transaction.execute(
+                        "DELETE FROM locations WHERE independent = 1 AND id=?;",
+                        (msg.location_id as i32,),
+                    )?;
+                }
+                transaction
+                    .execute(TRASH_MSG_SQL, (DC_CHAT_ID_TRASH, msg.id))
+                    .with_context(|| format!("Unable to trash message {}", msg.id))?;
+                transaction.execute(
+                    "UPDATE imap SET target=? WHERE rfc724_mid=?",
+                    (&target, &msg.rfc724_mid),
+                )?;
+            }
+            Ok(())
+        })
+        .await?;
+
+    emit_msgs_deleted(context, msgs.iter().map(|msg| (msg.chat_id, msg.id)));
+    for msg in &msgs {
         if msg.viewtype == Viewtype::Webxdc {
-            context.emit_event(EventType::WebxdcInstanceDeleted { msg_id: *msg_id });
+            context.emit_event(EventType::WebxdcInstanceDeleted { msg_id: msg.id });
         }
+    }
 
-        let target = context.get_delete_msgs_target().await?;
-        context
-            .sql
-            .execute(
-                "UPDATE imap SET target=? WHERE rfc724_mid=?",
-                (target, msg.rfc724_mid),
-            )
-            .await?;
-
-        let logging_xdc_id = context
-            .debug_logging
-            .read()
-            .await
-            .as_ref()
-            .map(|dl| dl.msg_id);
-
-        if let Some(id) = logging_xdc_id {
-            if id == *msg_id {
-                set_debug_logging_xdc(context, None).await?;
-            }
+    let logging_xdc_id = context
+        .debug_logging
+        .read()
+        .await
+        .as_ref()
+        .map(|dl| dl.msg_id);
+    if let Some(id) = logging_xdc_id {
+        if msg_ids.contains(&id) {
+            set_debug_logging_xdc(context, None).await?;
         }
     }
 
@@ -1451,18 +1955,10 @@ pub async fn delete_msgs(context: &Context, msg_ids: &[MsgId]) -> Result<()> {
     Ok(())
 }
 
-async fn delete_poi_location(context: &Context, location_id: u32) -> Result<()> {
-    context
-        .sql
-        .execute(
-            "DELETE FROM locations WHERE independent = 1 AND id=?;",
-            (location_id as i32,),
-        )
-        .await?;
-    Ok(())
-}
-
 /// Marks requested messages as seen.
+///
+/// The messages are updated in a single transaction
+/// and the IMAP and SMTP loops are interrupted only once.
 pub async fn markseen_msgs(context: &Context, msg_ids: Vec<MsgId>) -> Result<()> {
     if msg_ids.is_empty() {
         return Ok(());
@@ -1520,7 +2016,9 @@ pub async fn markseen_msgs(context: &Context, msg_ids: Vec<MsgId>) -> Result<()>
             .context("failed to start ephemeral timers")?;
     }
 
+    let mdns_enabled = context.get_config_bool(Config::MdnsEnabled).await?;
     let mut updated_chat_ids = BTreeSet::new();
+    let mut seen_msgs = Vec::new();
     for (
         id,
         curr_chat_id,
@@ -1535,38 +2033,61 @@ pub async fn markseen_msgs(context: &Context, msg_ids: Vec<MsgId>) -> Result<()>
         if curr_blocked == Blocked::Not
             && (curr_state == MessageState::InFresh || curr_state == MessageState::InNoticed)
         {
-            update_msg_state(context, id, MessageState::InSeen).await?;
             info!(context, "Seen message {}.", id);
 
-            markseen_on_imap_table(context, &curr_rfc724_mid).await?;
-
             // Read receipts for system messages are never sent. These messages have no place to
             // display received read receipt anyway.  And since their text is locally generated,
             // quoting them is dangerous as it may contain contact names. E.g., for original message
             // "Group left by me", a read receipt will quote "Group left by <name>", and the name can
             // be a display name stored in address book rather than the name sent in the From field by
             // the user.
-            if curr_param.get_bool(Param::WantsMdn).unwrap_or_default()
-                && curr_param.get_cmd() == SystemMessage::Unknown
-            {
-                let mdns_enabled = context.get_config_bool(Config::MdnsEnabled).await?;
-                if mdns_enabled {
-                    context
-                        .sql
+            let send_mdn = mdns_enabled
+                && curr_param.get_bool(Param::WantsMdn).unwrap_or_default()
+                && curr_param.get_cmd() == SystemMessage::Unknown;
+            seen_msgs.push((id, curr_from_id, curr_rfc724_mid, send_mdn));
+            updated_chat_ids.insert(curr_chat_id);
+        }
+    }
+    if seen_msgs.is_empty() {
+        return Ok(());
+    }
+
+    // Update all messages at once, so that marking many messages as seen
+    // does not write to the database and interrupt the IMAP and SMTP loops for each message.
+    context
+        .sql
+        .transaction(|transaction| {
+            for (id, from_id, rfc724_mid, send_mdn) in &seen_msgs {
+                transaction.execute(
+                    "UPDATE msgs SET state=? WHERE id=?",
+                    (MessageState::InSeen, id),
+                )?;
+                transaction.execute(
+                    "INSERT OR IGNORE INTO imap_markseen (id)
+                     SELECT id FROM imap WHERE rfc724_mid=?",
+                    (rfc724_mid,),
+                )?;
+                if *send_mdn {
+                    transaction
                         .execute(
                             "INSERT INTO smtp_mdns (msg_id, from_id, rfc724_mid) VALUES(?, ?, ?)",
-                            (id, curr_from_id, curr_rfc724_mid),
+                            (id, from_id, rfc724_mid),
                         )
-                        .await
                         .context("failed to insert into smtp_mdns")?;
-                    context
-                        .scheduler
-                        .interrupt_smtp(InterruptInfo::new(false))
-                        .await;
                 }
             }
-            updated_chat_ids.insert(curr_chat_id);
-        }
+            Ok(())
+        })
+        .await?;
+    context
+        .scheduler
+        .interrupt_inbox(InterruptInfo::new(false))
+        .await;
+    if seen_msgs.iter().any(|(_, _, _, send_mdn)| *send_mdn) {
+        context
+            .scheduler
+            .interrupt_smtp(InterruptInfo::new(false))
+            .await;
     }
 
     for updated_chat_id in updated_chat_ids {
@@ -1576,6 +2097,28 @@ pub async fn markseen_msgs(context: &Context, msg_ids: Vec<MsgId>) -> Result<()>
     Ok(())
 }
 
+/// Moves the media of a received view-once message out of the blobdir,
+/// so it can only be retrieved with [`MsgId::open_view_once`].
+pub(crate) async fn seal_view_once_file(context: &Context, param: &mut Params) -> Result<()> {
+    let path = match param.get_path(Param::File, context)? {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let name = path
+        .file_name()
+        .context("View-once media has no file name")?
+        .to_string_lossy()
+        .to_string();
+    let dir = context.get_view_once_dir();
+    tokio::fs::create_dir_all(&dir).await?;
+    tokio::fs::rename(&path, dir.join(&name))
+        .await
+        .with_context(|| format!("Cannot move {} out of the blobdir", path.display()))?;
+    param.remove(Param::File);
+    param.set(Param::ViewOnceFile, name);
+    Ok(())
+}
+
 pub(crate) async fn update_msg_state(
     context: &Context,
     msg_id: MsgId,
@@ -2008,10 +2551,37 @@ pub enum Viewtype {
     /// Message is an invitation to a videochat.
     VideochatInvitation = 70,
 
+    /// Message is a record of a call, see [`crate::calls::add_call_record`].
+    Call = 71,
+
     /// Message is an webxdc instance.
     Webxdc = 80,
 }
 
+/// Action a device message offers to the user.
+///
+/// UIs can render device messages with an action as cards with a button
+/// triggering the action.
+/// The action is stored as a string, e.g. `notification_settings`.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum DeviceAction {
+    /// Create a backup.
+    Backup,
+
+    /// Open the notification settings.
+    NotificationSettings,
+
+    /// Open the account settings, e.g. to change the password.
+    AccountSettings,
+
+    /// Free up storage, e.g. by deleting old messages.
+    ManageStorage,
+
+    /// Update the app.
+    UpdateApp,
+}
+
 impl Viewtype {
     /// Whether a message with this [`Viewtype`] should have a file attachment.
     pub fn has_file(&self) -> bool {
@@ -2026,6 +2596,7 @@ pub fn has_file(&self) -> bool {
             Viewtype::Video => true,
             Viewtype::File => true,
             Viewtype::VideochatInvitation => false,
+            Viewtype::Call => false,
             Viewtype::Webxdc => true,
         }
     }
@@ -2168,6 +2739,80 @@ async fn test_create_webrtc_instance_noroom() {
         assert_eq!(instance, "https://bla.foo/?$NOROOM=123");
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_videochat_invitation() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        alice
+            .set_config(Config::WebrtcInstance, Some("jitsi:meet.jit.si"))
+            .await?;
+        alice
+            .set_config(
+                Config::WebrtcInstances,
+                Some("\nexample.org/p2p/?roomname=$ROOM\njitsi:meet.jit.si\nexample.net$NOROOM\n"),
+            )
+            .await?;
+        let instances = chat::get_videochat_instances(&alice).await?;
+        assert_eq!(
+            instances,
+            vec![
+                "jitsi:meet.jit.si",
+                "example.org/p2p/?roomname=$ROOM",
+                "example.net$NOROOM"
+            ]
+        );
+        let chat_id = alice.create_chat(&bob).await.id;
+
+        chat::send_videochat_invitation(&alice, chat_id).await?;
+        let msg = bob.recv_msg(&alice.pop_sent_msg().await).await;
+        let invitation = msg.get_videochat_invitation().unwrap();
+        assert_eq!(invitation.videochat_type, VideochatType::Jitsi);
+        assert_eq!(invitation.host, "meet.jit.si");
+        let room = invitation.room.unwrap();
+        assert_eq!(room.len(), 25);
+        assert_eq!(invitation.url, format!("https://meet.jit.si/{room}"));
+
+        chat::send_videochat_invitation_ex(&alice, chat_id, &instances[1]).await?;
+        let msg = bob.recv_msg(&alice.pop_sent_msg().await).await;
+        let invitation = msg.get_videochat_invitation().unwrap();
+        assert_eq!(invitation.videochat_type, VideochatType::Unknown);
+        assert_eq!(invitation.host, "example.org");
+        let room = invitation.room.unwrap();
+        assert_eq!(
+            invitation.url,
+            format!("https://example.org/p2p/?roomname={room}")
+        );
+
+        chat::send_videochat_invitation_ex(&alice, chat_id, &instances[2]).await?;
+        let msg = bob.recv_msg(&alice.pop_sent_msg().await).await;
+        let invitation = msg.get_videochat_invitation().unwrap();
+        assert_eq!(invitation.room, None);
+        assert_eq!(invitation.url, "https://example.net");
+
+        assert!(chat::send_videochat_invitation_ex(&alice, chat_id, " ")
+            .await
+            .is_err());
+        let msg = Message::new(Viewtype::Text);
+        assert_eq!(msg.get_videochat_invitation(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_videochat_room() {
+        let room = |url: &str| get_videochat_room(&url::Url::parse(url).unwrap());
+        assert_eq!(room("https://meet.jit.si/abc").as_deref(), Some("abc"));
+        assert_eq!(room("https://bla.foo/abc/").as_deref(), Some("abc"));
+        assert_eq!(room("https://bla.foo#123").as_deref(), Some("123"));
+        assert_eq!(
+            room("https://bla.foo#a=b&room=234&after=cont").as_deref(),
+            Some("234")
+        );
+        assert_eq!(room("https://bla.foo/x?roomname=5").as_deref(), Some("5"));
+        assert_eq!(room("https://bla.foo/?a=b"), None);
+        assert_eq!(room("https://bla.foo"), None);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_get_width_height() {
         let t = test::TestContext::new().await;
@@ -2376,6 +3021,15 @@ async fn test_markseen_msgs() -> Result<()> {
         assert_eq!(alice_chat.id.get_fresh_msg_cnt(&alice).await?, 0);
         assert_eq!(alice.get_fresh_msgs().await?.len(), 0);
 
+        // read receipts are queued for both messages
+        assert_eq!(
+            alice
+                .sql
+                .count("SELECT COUNT(*) FROM smtp_mdns", ())
+                .await?,
+            2
+        );
+
         Ok(())
     }
 
@@ -2499,6 +3153,7 @@ fn test_viewtype_values() {
             Viewtype::VideochatInvitation,
             Viewtype::from_i32(70).unwrap()
         );
+        assert_eq!(Viewtype::Call, Viewtype::from_i32(71).unwrap());
         assert_eq!(Viewtype::Webxdc, Viewtype::from_i32(80).unwrap());
     }
 
@@ -2554,4 +3209,175 @@ async fn test_format_flowed_round_trip() -> Result<()> {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_view_once() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob = TestContext::new_bob().await;
+        let alice_chat = alice.create_chat(&bob).await;
+
+        let bytes = include_bytes!("../test-data/image/avatar64x64.png");
+        let file = alice.get_blobdir().join("image.png");
+        tokio::fs::write(&file, bytes).await?;
+        let mut msg = Message::new(Viewtype::Image);
+        msg.set_file(file.to_str().unwrap(), None);
+        msg.set_view_once(true);
+        let sent = alice.send_msg(alice_chat.id, &mut msg).await;
+
+        let rcvd = bob.recv_msg(&sent).await;
+        assert!(rcvd.is_view_once());
+        assert!(!rcvd.is_view_once_viewed());
+        assert_eq!(rcvd.get_text(), Some("".to_string()));
+        // The media is not accessible in the blobdir.
+        assert!(rcvd.get_file(&bob).is_none());
+        let name = rcvd.param.get(Param::ViewOnceFile).unwrap();
+        assert!(!bob.get_blobdir().join(name).exists());
+        let path = bob.get_view_once_dir().join(name);
+        assert!(path.exists());
+
+        assert_eq!(rcvd.id.open_view_once(&bob).await?, bytes);
+        assert!(!path.exists());
+        let rcvd = Message::load_from_db(&bob, rcvd.id).await?;
+        assert!(rcvd.is_view_once_viewed());
+        assert_eq!(rcvd.get_state(), MessageState::InSeen);
+        assert!(rcvd.get_file(&bob).is_none());
+        assert!(rcvd.id.open_view_once(&bob).await.is_err());
+
+        // The sender keeps the media.
+        assert_eq!(msg.id.open_view_once(&alice).await?, bytes);
+        assert_eq!(msg.id.open_view_once(&alice).await?, bytes);
+
+        // Only media can be sent as view-once.
+        let mut msg = Message::new(Viewtype::Text);
+        msg.set_text(Some("text".to_string()));
+        msg.set_view_once(true);
+        assert!(chat::send_msg(&alice, alice_chat.id, &mut msg)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_thread() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+
+        // Two threads in the same chat: 1 <- 2 <- 4 and 3 <- 5.
+        let mut msg_ids = Vec::new();
+        for (i, in_reply_to) in [(1, ""), (2, "1"), (3, ""), (4, "2"), (5, "3")] {
+            let in_reply_to = if in_reply_to.is_empty() {
+                "".to_string()
+            } else {
+                format!("In-Reply-To: <{in_reply_to}@example.net>\n")
+            };
+            let imf_raw = format!(
+                "From: Bob <bob@example.net>\n\
+                 To: alice@example.org\n\
+                 Chat-Version: 1.0\n\
+                 Message-ID: <{i}@example.net>\n\
+                 {in_reply_to}\
+                 Date: Fri, 29 Jan 2021 21:37:5{i} +0000\n\
+                 \n\
+                 message {i}\n"
+            );
+            let received = receive_imf(&alice, imf_raw.as_bytes(), false)
+                .await?
+                .unwrap();
+            msg_ids.push(received.msg_ids[0]);
+        }
+
+        let first_thread = vec![msg_ids[0], msg_ids[1], msg_ids[3]];
+        let second_thread = vec![msg_ids[2], msg_ids[4]];
+        assert_eq!(msg_ids[3].get_thread_root(&alice).await?, msg_ids[0]);
+        assert_eq!(msg_ids[0].get_thread(&alice).await?, first_thread);
+        assert_eq!(msg_ids[1].get_thread(&alice).await?, first_thread);
+        assert_eq!(msg_ids[4].get_thread(&alice).await?, second_thread);
+
+        assert_eq!(msg_ids[1].get_thread_fresh_msg_cnt(&alice).await?, 3);
+        markseen_msgs(&alice, vec![msg_ids[0]]).await?;
+        assert_eq!(msg_ids[1].get_thread_fresh_msg_cnt(&alice).await?, 2);
+        assert_eq!(msg_ids[2].get_thread_fresh_msg_cnt(&alice).await?, 2);
+
+        let chat_id = Message::load_from_db(&alice, msg_ids[0]).await?.chat_id;
+        assert!(!Chat::load_from_db(&alice, chat_id).await?.is_threaded());
+        chat_id.set_threaded(&alice, true).await?;
+        assert!(Chat::load_from_db(&alice, chat_id).await?.is_threaded());
+        chat_id.set_threaded(&alice, false).await?;
+        assert!(!Chat::load_from_db(&alice, chat_id).await?.is_threaded());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_delete_msgs_emits_msgs_deleted() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let chat = t.create_chat_with_contact("bob", "bob@example.net").await;
+        let msg_id1 = chat::send_text_msg(&t, chat.id, "hi".to_string()).await?;
+        let msg_id2 = chat::send_text_msg(&t, chat.id, "there".to_string()).await?;
+        let other_chat = t.create_chat_with_contact("", "claire@example.org").await;
+        let msg_id3 = chat::send_text_msg(&t, other_chat.id, "hi".to_string()).await?;
+
+        delete_msgs(&t, &[msg_id1, msg_id2, msg_id3]).await?;
+        let mut deleted = BTreeMap::new();
+        while deleted.len() < 2 {
+            let event = t
+                .evtracker
+                .get_matching(|evt| matches!(evt, EventType::MsgsDeleted { .. }))
+                .await;
+            if let EventType::MsgsDeleted { chat_id, msg_ids } = event {
+                assert!(deleted.insert(chat_id, msg_ids).is_none());
+            }
+        }
+        assert_eq!(deleted[&chat.id], vec![msg_id1, msg_id2]);
+        assert_eq!(deleted[&other_chat.id], vec![msg_id3]);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_attachments() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let alice_chat = alice.create_chat(&bob).await;
+
+        let image = include_bytes!("../test-data/image/avatar64x64.png");
+        let image_file = alice.get_blobdir().join("image.png");
+        tokio::fs::write(&image_file, image).await?;
+        let report_file = alice.get_blobdir().join("report.pdf");
+        tokio::fs::write(&report_file, b"%PDF report").await?;
+        let notes_file = alice.get_blobdir().join("notes.txt");
+        tokio::fs::write(&notes_file, b"some notes").await?;
+
+        let mut msg = Message::new(Viewtype::Image);
+        msg.set_text(Some("files of today".to_string()));
+        msg.set_file(image_file.to_str().unwrap(), None);
+        msg.add_attachment(report_file.to_str().unwrap());
+        msg.add_attachment(notes_file.to_str().unwrap());
+        let sent = alice.send_msg(alice_chat.id, &mut msg).await;
+        let msg = Message::load_from_db(&alice, msg.id).await?;
+        let attachments = msg.get_attachments(&alice);
+        assert_eq!(attachments.len(), 3);
+        assert!(attachments
+            .iter()
+            .all(|path| path.starts_with(alice.get_blobdir())));
+
+        let rcvd = bob.recv_msg(&sent).await;
+        assert_eq!(rcvd.get_viewtype(), Viewtype::Image);
+        assert_eq!(rcvd.get_text().as_deref(), Some("files of today"));
+        let attachments = rcvd.get_attachments(&bob);
+        assert_eq!(attachments.len(), 3);
+        assert_eq!(tokio::fs::read(&attachments[0]).await?, image);
+        assert_eq!(tokio::fs::read(&attachments[1]).await?, b"%PDF report");
+        assert_eq!(tokio::fs::read(&attachments[2]).await?, b"some notes");
+        assert_eq!(attachments[2].file_name().unwrap(), "notes.txt");
+
+        // Files can only be added to messages with a file.
+        let mut msg = Message::new(Viewtype::Text);
+        msg.set_text(Some("text".to_string()));
+        msg.add_attachment(notes_file.to_str().unwrap());
+        assert!(chat::send_msg(&alice, alice_chat.id, &mut msg)
+            .await
+            .is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,396 @@
+//! # Plural-aware message formatting.
+//!
+//! Implements the subset of the ICU MessageFormat syntax
+//! needed for translated stock strings:
+//! simple arguments such as `{1}` and plural arguments such as
+//! `{1, plural, =0 {no minutes} one {# minute} other {# minutes}}`.
+//! Arguments are numbered the same way as the `%1$s` placeholders of the stock strings.
+//!
+//! Plural categories are selected according to the CLDR plural rules
+//! of the language set using [`crate::context::Context::set_stock_language`].
+
+use anyhow::{bail, ensure, Context as _, Result};
+use intl_pluralrules::{PluralRuleType, PluralRules};
+use unic_langid::LanguageIdentifier;
+
+/// CLDR plural category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "zero" => Some(Self::Zero),
+            "one" => Some(Self::One),
+            "two" => Some(Self::Two),
+            "few" => Some(Self::Few),
+            "many" => Some(Self::Many),
+            "other" => Some(Self::Other),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the CLDR cardinal plural rules of the language `lang`, e.g. `pt-PT` or `de_DE`.
+///
+/// Falls back to the rules of the language without region
+/// and to the rules of English for unknown languages.
+fn plural_rules(lang: &str) -> PluralRules {
+    let langid: LanguageIdentifier = lang.replace('_', "-").parse().unwrap_or_default();
+    PluralRules::create(langid.clone(), PluralRuleType::CARDINAL)
+        .or_else(|_| {
+            let language = LanguageIdentifier::from_parts(langid.language, None, None, &[]);
+            PluralRules::create(language, PluralRuleType::CARDINAL)
+        })
+        .or_else(|_| {
+            let english: LanguageIdentifier = "en".parse().unwrap_or_default();
+            PluralRules::create(english, PluralRuleType::CARDINAL)
+        })
+        .expect("English plural rules are missing")
+}
+
+/// Returns the CLDR cardinal plural category of `number` in the language `lang`.
+///
+/// Numbers which cannot be parsed are in the `Other` category.
+pub(crate) fn plural_category(lang: &str, number: &str) -> PluralCategory {
+    match plural_rules(lang).select(number.trim()) {
+        Ok(intl_pluralrules::PluralCategory::ZERO) => PluralCategory::Zero,
+        Ok(intl_pluralrules::PluralCategory::ONE) => PluralCategory::One,
+        Ok(intl_pluralrules::PluralCategory::TWO) => PluralCategory::Two,
+        Ok(intl_pluralrules::PluralCategory::FEW) => PluralCategory::Few,
+        Ok(intl_pluralrules::PluralCategory::MANY) => PluralCategory::Many,
+        Ok(intl_pluralrules::PluralCategory::OTHER) | Err(_) => PluralCategory::Other,
+    }
+}
+
+/// Branch selector of a plural argument.
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    /// Exact value, e.g. `=0`.
+    Exact(String),
+
+    /// Plural category, e.g. `one`.
+    Category(PluralCategory),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+
+    /// Simple argument, 1-based.
+    Arg(usize),
+
+    /// `#` inside of a plural branch.
+    Number,
+
+    /// Plural argument with its branches.
+    Plural {
+        arg: usize,
+        branches: Vec<(Selector, Vec<Part>)>,
+    },
+}
+
+/// Parsed message in ICU MessageFormat syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MessageFormat {
+    parts: Vec<Part>,
+}
+
+impl MessageFormat {
+    /// Parses a message.
+    pub(crate) fn parse(pattern: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let parts = parser.parse_parts(false)?;
+        ensure!(
+            parser.pos == parser.chars.len(),
+            "unbalanced '}}' at position {}",
+            parser.pos
+        );
+        Ok(Self { parts })
+    }
+
+    /// Returns the highest argument number used in the message.
+    pub(crate) fn max_arg(&self) -> usize {
+        fn max_arg(parts: &[Part]) -> usize {
+            parts
+                .iter()
+                .map(|part| match part {
+                    Part::Text(_) | Part::Number => 0,
+                    Part::Arg(arg) => *arg,
+                    Part::Plural { arg, branches } => branches
+                        .iter()
+                        .map(|(_, parts)| max_arg(parts))
+                        .fold(*arg, usize::max),
+                })
+                .max()
+                .unwrap_or_default()
+        }
+        max_arg(&self.parts)
+    }
+
+    /// Formats the message using the plural rules of `lang`.
+    ///
+    /// `args[0]` is substituted for `{1}` and so on, missing arguments are left empty.
+    pub(crate) fn format(&self, lang: &str, args: &[&str]) -> String {
+        let mut res = String::new();
+        format_parts(&mut res, &self.parts, lang, args, None);
+        res
+    }
+}
+
+fn format_parts(res: &mut String, parts: &[Part], lang: &str, args: &[&str], number: Option<&str>) {
+    for part in parts {
+        match part {
+            Part::Text(text) => res.push_str(text),
+            Part::Arg(arg) => res.push_str(args.get(arg - 1).copied().unwrap_or_default()),
+            Part::Number => res.push_str(number.unwrap_or("#")),
+            Part::Plural { arg, branches } => {
+                let value = args.get(arg - 1).copied().unwrap_or_default();
+                let category = plural_category(lang, value);
+                let branch = branches
+                    .iter()
+                    .find(|(selector, _)| matches!(selector, Selector::Exact(exact) if exact == value))
+                    .or_else(|| {
+                        branches
+                            .iter()
+                            .find(|(selector, _)| *selector == Selector::Category(category))
+                    })
+                    .or_else(|| {
+                        branches.iter().find(|(selector, _)| {
+                            *selector == Selector::Category(PluralCategory::Other)
+                        })
+                    });
+                if let Some((_, parts)) = branch {
+                    format_parts(res, parts, lang, args, Some(value));
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_whitespace();
+        ensure!(
+            self.peek() == Some(c),
+            "expected '{c}' at position {}",
+            self.pos
+        );
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Reads a keyword or number up to the next delimiter.
+    fn word(&mut self) -> String {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.peek().map_or(false, |c| {
+            !c.is_whitespace() && c != ',' && c != '{' && c != '}'
+        }) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Parses text and arguments up to the closing `}` of a branch or the end.
+    fn parse_parts(&mut self, in_plural: bool) -> Result<Vec<Part>> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '}' => break,
+                '{' => {
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    self.pos += 1;
+                    parts.push(self.parse_argument()?);
+                }
+                '#' if in_plural => {
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    self.pos += 1;
+                    parts.push(Part::Number);
+                }
+                '\'' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some('\'') => {
+                            text.push('\'');
+                            self.pos += 1;
+                        }
+                        Some('{') | Some('}') | Some('#') => {
+                            // Quoted literal text up to the next single apostrophe.
+                            while let Some(c) = self.peek() {
+                                self.pos += 1;
+                                if c == '\'' {
+                                    if self.peek() == Some('\'') {
+                                        text.push('\'');
+                                        self.pos += 1;
+                                    } else {
+                                        break;
+                                    }
+                                } else {
+                                    text.push(c);
+                                }
+                            }
+                        }
+                        _ => text.push('\''),
+                    }
+                }
+                c => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(parts)
+    }
+
+    /// Parses an argument after the opening `{`.
+    fn parse_argument(&mut self) -> Result<Part> {
+        let name = self.word();
+        let arg: usize = name
+            .parse()
+            .ok()
+            .filter(|arg| *arg > 0)
+            .with_context(|| format!("invalid argument {name:?}, expected a number >= 1"))?;
+        self.skip_whitespace();
+        match self.peek() {
+            Some('}') => {
+                self.pos += 1;
+                Ok(Part::Arg(arg))
+            }
+            Some(',') => {
+                self.pos += 1;
+                let typ = self.word();
+                ensure!(typ == "plural", "unsupported argument type {typ:?}");
+                self.expect(',')?;
+                let mut branches = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some('}') {
+                        self.pos += 1;
+                        break;
+                    }
+                    let keyword = self.word();
+                    let selector = if let Some(exact) = keyword.strip_prefix('=') {
+                        Selector::Exact(exact.to_string())
+                    } else if let Some(category) = PluralCategory::from_keyword(&keyword) {
+                        Selector::Category(category)
+                    } else {
+                        bail!("invalid plural selector {keyword:?}");
+                    };
+                    self.expect('{')?;
+                    let parts = self.parse_parts(true)?;
+                    self.expect('}')?;
+                    branches.push((selector, parts));
+                }
+                ensure!(
+                    branches
+                        .iter()
+                        .any(|(selector, _)| *selector == Selector::Category(PluralCategory::Other)),
+                    "plural argument {arg} has no 'other' branch"
+                );
+                Ok(Part::Plural { arg, branches })
+            }
+            _ => bail!("expected '}}' or ',' at position {}", self.pos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plural_category() {
+        use PluralCategory::*;
+        assert_eq!(plural_category("en", "1"), One);
+        assert_eq!(plural_category("en", "1.0"), Other);
+        assert_eq!(plural_category("en-US", "2"), Other);
+        assert_eq!(plural_category("fr", "0"), One);
+        assert_eq!(plural_category("fr", "1.5"), One);
+        assert_eq!(plural_category("ru", "1"), One);
+        assert_eq!(plural_category("ru", "21"), One);
+        assert_eq!(plural_category("ru", "11"), Many);
+        assert_eq!(plural_category("ru", "3"), Few);
+        assert_eq!(plural_category("ru", "5"), Many);
+        assert_eq!(plural_category("ru", "1.5"), Other);
+        assert_eq!(plural_category("pl", "22"), Few);
+        assert_eq!(plural_category("pl", "12"), Many);
+        assert_eq!(plural_category("ar", "0"), Zero);
+        assert_eq!(plural_category("ar", "2"), Two);
+        assert_eq!(plural_category("ar", "105"), Few);
+        assert_eq!(plural_category("ja", "1"), Other);
+        assert_eq!(plural_category("en", "foo"), Other);
+        assert_eq!(plural_category("de_DE", "1"), One);
+        assert_eq!(plural_category("pt-PT", "0"), Other);
+        assert_eq!(plural_category("pt_BR", "0"), One);
+        assert_eq!(plural_category("xx", "1"), One);
+    }
+
+    #[test]
+    fn test_format() -> Result<()> {
+        let format = MessageFormat::parse(
+            "{2} set the timer to {1, plural, =0 {zero} one {# minute} other {# minutes}}.",
+        )?;
+        assert_eq!(format.max_arg(), 2);
+        assert_eq!(
+            format.format("en", &["1", "Bob"]),
+            "Bob set the timer to 1 minute."
+        );
+        assert_eq!(
+            format.format("en", &["5", "Bob"]),
+            "Bob set the timer to 5 minutes."
+        );
+        assert_eq!(
+            format.format("en", &["0", "Bob"]),
+            "Bob set the timer to zero."
+        );
+
+        let format =
+            MessageFormat::parse("{1, plural, one {# минута} few {# минуты} other {# минут}}")?;
+        assert_eq!(format.format("ru", &["2"]), "2 минуты");
+        assert_eq!(format.format("ru", &["25"]), "25 минут");
+
+        let format = MessageFormat::parse("It''s '{1}' and {1}")?;
+        assert_eq!(format.format("en", &["x"]), "It's {1} and x");
+
+        assert!(MessageFormat::parse("{1, plural, one {#}}").is_err());
+        assert!(MessageFormat::parse("{1, select, a {b} other {c}}").is_err());
+        assert!(MessageFormat::parse("{0}").is_err());
+        assert!(MessageFormat::parse("{1").is_err());
+        assert!(MessageFormat::parse("1}").is_err());
+        Ok(())
+    }
+}
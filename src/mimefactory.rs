@@ -7,12 +7,13 @@
 use chrono::TimeZone;
 use format_flowed::{format_flowed, format_flowed_quote};
 use lettre_email::{mime, Address, Header, MimeMultipartType, PartBuilder};
+use num_traits::FromPrimitive;
 use tokio::fs;
 
 use crate::blob::BlobObject;
 use crate::chat::Chat;
 use crate::config::Config;
-use crate::constants::{Chattype, DC_FROM_HANDSHAKE};
+use crate::constants::{Chattype, EncryptedSubject, DC_FROM_HANDSHAKE};
 use crate::contact::Contact;
 use crate::context::{get_version_str, Context};
 use crate::e2ee::EncryptHelper;
@@ -76,6 +77,10 @@ pub struct MimeFactory<'a> {
 
     /// True if the avatar should be attached.
     attach_selfavatar: bool,
+
+    /// False if the last-seen status is not shared with all recipients,
+    /// see [`Contact::set_share_last_seen`].
+    share_last_seen: bool,
 }
 
 /// Result of rendering a message, ready to be submitted to a send job.
@@ -149,6 +154,7 @@ pub async fn from_msg(
 
         let mut recipients = Vec::with_capacity(5);
         let mut req_mdn = false;
+        let mut share_last_seen = context.get_config_bool(Config::ShareLastSeen).await?;
 
         if chat.is_self_talk() {
             recipients.push((from_displayname.to_string(), from_addr.to_string()));
@@ -162,7 +168,7 @@ pub async fn from_msg(
             context
                 .sql
                 .query_map(
-                    "SELECT c.authname, c.addr  \
+                    "SELECT c.authname, c.addr, c.hide_last_seen  \
                  FROM chats_contacts cc  \
                  LEFT JOIN contacts c ON cc.contact_id=c.id  \
                  WHERE cc.chat_id=? AND cc.contact_id>9;",
@@ -170,11 +176,13 @@ pub async fn from_msg(
                     |row| {
                         let authname: String = row.get(0)?;
                         let addr: String = row.get(1)?;
-                        Ok((authname, addr))
+                        let hide_last_seen: bool = row.get(2)?;
+                        Ok((authname, addr, hide_last_seen))
                     },
                     |rows| {
                         for row in rows {
-                            let (authname, addr) = row?;
+                            let (authname, addr, hide_last_seen) = row?;
+                            share_last_seen &= !hide_last_seen;
                             if !recipients_contain_addr(&recipients, &addr) {
                                 recipients.push((authname, addr));
                             }
@@ -212,10 +220,13 @@ pub async fn from_msg(
             from_addr,
             from_displayname,
             sender_displayname,
-            selfstatus: context
-                .get_config(Config::Selfstatus)
-                .await?
-                .unwrap_or_default(),
+            selfstatus: match chat.get_signature() {
+                Some(signature) => signature.to_string(),
+                None => context
+                    .get_config(Config::Selfstatus)
+                    .await?
+                    .unwrap_or_default(),
+            },
             recipients,
             timestamp: msg.timestamp_sort,
             loaded: Loaded::Message { chat },
@@ -226,6 +237,7 @@ pub async fn from_msg(
             last_added_location_id: 0,
             sync_ids_to_delete: None,
             attach_selfavatar,
+            share_last_seen,
         };
         Ok(factory)
     }
@@ -247,7 +259,14 @@ pub async fn from_mdn(
             .get_config(Config::Selfstatus)
             .await?
             .unwrap_or_default();
-        let timestamp = create_smeared_timestamp(context);
+        let share_last_seen =
+            contact.shares_last_seen() && context.get_config_bool(Config::ShareLastSeen).await?;
+        let timestamp = if share_last_seen {
+            create_smeared_timestamp(context)
+        } else {
+            // Do not reveal when the message was read.
+            msg.timestamp_sort
+        };
 
         let res = MimeFactory::<'a> {
             from_addr,
@@ -267,6 +286,7 @@ pub async fn from_mdn(
             last_added_location_id: 0,
             sync_ids_to_delete: None,
             attach_selfavatar: false,
+            share_last_seen,
         };
 
         Ok(res)
@@ -368,7 +388,8 @@ async fn should_do_gossip(&self, context: &Context) -> Result<bool> {
                     // Do gossip in all Securejoin messages not to complicate the code. There's no
                     // need in gossips in "vg-auth-required" messages f.e., but let them be.
                     Ok(cmd == SystemMessage::MemberAddedToGroup
-                        || cmd == SystemMessage::SecurejoinMessage)
+                        || cmd == SystemMessage::SecurejoinMessage
+                        || cmd == SystemMessage::KeyGossip)
                 }
             }
             Loaded::Mdn { .. } => Ok(false),
@@ -521,15 +542,18 @@ pub async fn render(mut self, context: &Context) -> Result<RenderedEmail> {
             .push(Header::new_with_value("To".into(), to).unwrap());
 
         let subject_str = self.subject_str(context).await?;
-        let encoded_subject = if subject_str
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == ' ')
-        // We do not use needs_encoding() here because needs_encoding() returns true if the string contains a space
-        // but we do not want to encode all subjects just because they contain a space.
+        let encoded_subject = encode_subject(&subject_str);
+        let encrypted_subject = match EncryptedSubject::from_i32(
+            context.get_config_int(Config::EncryptedSubject).await?,
+        )
+        .unwrap_or_default()
         {
-            subject_str.clone()
-        } else {
-            encode_words(&subject_str)
+            EncryptedSubject::Hidden => "...".to_string(),
+            EncryptedSubject::ChatName => match &self.loaded {
+                Loaded::Message { chat } => encode_subject(&chat.name),
+                Loaded::Mdn { .. } => "...".to_string(),
+            },
+            EncryptedSubject::Plain => encoded_subject.clone(),
         };
         headers
             .protected
@@ -598,6 +622,12 @@ pub async fn render(mut self, context: &Context) -> Result<RenderedEmail> {
             .unprotected
             .push(Header::new("Chat-Version".to_string(), "1.0".to_string()));
 
+        if !self.share_last_seen {
+            headers
+                .protected
+                .push(Header::new("Chat-Last-Seen".into(), "hidden".into()));
+        }
+
         if self.req_mdn {
             // we use "Chat-Disposition-Notification-To"
             // because replies to "Disposition-Notification-To" are weird in many cases
@@ -667,6 +697,11 @@ pub async fn render(mut self, context: &Context) -> Result<RenderedEmail> {
                     "Content-Type".to_string(),
                     "multipart/report; report-type=status-update".to_string(),
                 ))
+            } else if self.msg.param.get_cmd() == SystemMessage::AbuseReport {
+                PartBuilder::new().header((
+                    "Content-Type".to_string(),
+                    "multipart/report; report-type=feedback-report".to_string(),
+                ))
             } else {
                 PartBuilder::new().message_type(MimeMultipartType::Mixed)
             };
@@ -759,7 +794,7 @@ pub async fn render(mut self, context: &Context) -> Result<RenderedEmail> {
                         .body(encrypted)
                         .build(),
                 )
-                .header(("Subject".to_string(), "...".to_string()))
+                .header(("Subject".to_string(), encrypted_subject))
         } else {
             let message = if headers.hidden.is_empty() {
                 message
@@ -923,6 +958,12 @@ async fn render_message(
                             "Chat-Group-Member-Removed".into(),
                             email_to_remove.into(),
                         ));
+                        if self.msg.param.get_bool(Param::Arg2).unwrap_or_default() {
+                            headers.protected.push(Header::new(
+                                "Chat-Group-Member-Banned".into(),
+                                email_to_remove.into(),
+                            ));
+                        }
                     }
                 }
                 SystemMessage::MemberAddedToGroup => {
@@ -932,6 +973,12 @@ async fn render_message(
                             "Chat-Group-Member-Added".into(),
                             email_to_add.into(),
                         ));
+                        if self.msg.param.get_bool(Param::Arg3).unwrap_or_default() {
+                            headers.protected.push(Header::new(
+                                "Chat-Group-Member-Unbanned".into(),
+                                email_to_add.into(),
+                            ));
+                        }
                     }
                     if 0 != self.msg.param.get_int(Param::Arg2).unwrap_or_default()
                         & DC_FROM_HANDSHAKE
@@ -1030,7 +1077,28 @@ async fn render_message(
                         .push(Header::new("Secure-Join".into(), step.into()));
 
                     let param2 = msg.param.get(Param::Arg2).unwrap_or_default();
-                    if !param2.is_empty() {
+                    if step == "vg-invite" {
+                        if !param2.is_empty() {
+                            headers
+                                .protected
+                                .push(Header::new("Chat-Group-Name".into(), encode_words(&param2)));
+                        }
+                    } else if step == "vg-challenge" || step == "vg-challenge-response" {
+                        headers.protected.push(Header::new(
+                            if step == "vg-challenge" {
+                                "Secure-Join-Challenge".into()
+                            } else {
+                                "Secure-Join-Challenge-Response".into()
+                            },
+                            encode_words(&param2),
+                        ));
+                    } else if !param2.is_empty() {
+                        if step == "vg-request-with-auth" {
+                            headers.protected.push(Header::new(
+                                "Secure-Join-Features".into(),
+                                "challenge".into(),
+                            ));
+                        }
                         headers.protected.push(Header::new(
                             if step == "vg-request-with-auth" || step == "vc-request-with-auth" {
                                 "Secure-Join-Auth".into()
@@ -1067,6 +1135,28 @@ async fn render_message(
                     "protection-disabled".to_string(),
                 ));
             }
+            SystemMessage::KeyGossip => {
+                headers
+                    .protected
+                    .push(Header::new("Chat-Content".into(), "key-gossip".into()));
+            }
+            SystemMessage::CallOffer
+            | SystemMessage::CallAnswer
+            | SystemMessage::CallIceCandidate => {
+                let content = match command {
+                    SystemMessage::CallOffer => "call-offer",
+                    SystemMessage::CallAnswer => "call-answer",
+                    _ => "call-ice",
+                };
+                headers
+                    .protected
+                    .push(Header::new("Chat-Content".into(), content.into()));
+                let payload = self.msg.param.get(Param::Arg).unwrap_or_default();
+                headers.protected.push(Header::new(
+                    "Chat-Call-Payload".into(),
+                    base64::engine::general_purpose::STANDARD.encode(payload),
+                ));
+            }
             _ => {}
         }
 
@@ -1104,6 +1194,33 @@ async fn render_message(
             ));
         }
 
+        if let Some(ticket) = self.msg.param.get(Param::P2pTicket) {
+            headers
+                .protected
+                .push(Header::new("Chat-P2p-Ticket".into(), ticket.into()));
+            if let Some((name, bytes)) = self.msg.get_p2p_file() {
+                headers
+                    .protected
+                    .push(Header::new("Chat-P2p-Filename".into(), name));
+                headers
+                    .protected
+                    .push(Header::new("Chat-P2p-Filebytes".into(), bytes.to_string()));
+            }
+        }
+
+        if let Some(album_id) = self.msg.get_album_id() {
+            headers
+                .protected
+                .push(Header::new("Chat-Album".into(), album_id.into()));
+        }
+
+        if self.msg.is_view_once() {
+            headers
+                .protected
+                .push(Header::new("Chat-View-Once".into(), "1".into()));
+            placeholdertext = Some(stock_str::view_once_fallback(context).await);
+        }
+
         if self.msg.viewtype == Viewtype::Voice
             || self.msg.viewtype == Viewtype::Audio
             || self.msg.viewtype == Viewtype::Video
@@ -1206,10 +1323,47 @@ async fn render_message(
             }
         }
 
-        // add attachment part
-        if self.msg.viewtype.has_file() {
+        if command == SystemMessage::AbuseReport {
+            // RFC 5965: the second part is machine-readable,
+            // the optional third part is the original message, which must not be base64-encoded.
+            let feedback = self.msg.param.get(Param::Arg).unwrap_or_default();
+            parts.push(
+                PartBuilder::new()
+                    .content_type(&"message/feedback-report".parse()?)
+                    .body(feedback),
+            );
+            if let Some(path) = self.msg.param.get_path(Param::File, context)? {
+                let raw = fs::read(path).await?;
+                parts.push(match String::from_utf8(raw) {
+                    Ok(raw) => PartBuilder::new()
+                        .content_type(&"message/rfc822".parse()?)
+                        .body(raw),
+                    Err(err) => {
+                        // Only the header section is sent as text if the message is not UTF-8.
+                        let raw = String::from_utf8_lossy(err.as_bytes());
+                        let headers = match raw.find("\r\n\r\n") {
+                            Some(end) => &raw[..end + 2],
+                            None => &raw[..],
+                        };
+                        PartBuilder::new()
+                            .content_type(&"text/rfc822-headers".parse()?)
+                            .body(headers.to_string())
+                    }
+                });
+            }
+        } else if self.msg.viewtype.has_file() {
+            // add attachment part
             let (file_part, _) = build_body_file(context, self.msg, "").await?;
             parts.push(file_part);
+            for file in self
+                .msg
+                .param
+                .get(Param::Attachments)
+                .unwrap_or_default()
+                .lines()
+            {
+                parts.push(build_body_attachment(context, file).await?);
+            }
         }
 
         if let Some(meta_part) = meta_part {
@@ -1236,6 +1390,10 @@ async fn render_message(
             let ids = self.msg.param.get(Param::Arg2).unwrap_or_default();
             parts.push(context.build_sync_part(json.to_string()));
             self.sync_ids_to_delete = Some(ids.to_string());
+            headers.protected.push(Header::new(
+                "Chat-Device-Id".into(),
+                context.get_device_id().await?,
+            ));
         } else if command == SystemMessage::WebxdcStatusUpdate {
             let json = self.msg.param.get(Param::Arg).unwrap_or_default();
             parts.push(context.build_status_update_part(json));
@@ -1459,6 +1617,27 @@ async fn build_body_file(
     Ok((mail, filename_to_send))
 }
 
+/// Builds the part of an additional attachment, see [`Message::add_attachment`].
+async fn build_body_attachment(context: &Context, file: &str) -> Result<PartBuilder> {
+    let blob = BlobObject::from_name(context, file.to_string())?;
+    let mimetype: mime::Mime = match message::guess_msgtype_from_suffix(blob.as_rel_path()) {
+        Some((_, mimetype)) => mimetype.parse()?,
+        None => mime::APPLICATION_OCTET_STREAM,
+    };
+    let cd_value = format!(
+        "attachment; filename=\"{}\"",
+        maybe_encode_words(blob.as_file_name())
+    );
+    let body = fs::read(blob.to_abs_path()).await?;
+    let encoded_body = wrapped_base64_encode(&body);
+
+    Ok(PartBuilder::new()
+        .content_type(&mimetype)
+        .header(("Content-Disposition", cd_value))
+        .header(("Content-Transfer-Encoding", "base64"))
+        .body(encoded_body))
+}
+
 async fn build_selfavatar_file(context: &Context, path: &str) -> Result<String> {
     let blob = BlobObject::from_path(context, path.as_ref())?;
     let body = fs::read(blob.to_abs_path()).await?;
@@ -1514,6 +1693,19 @@ fn maybe_encode_words(words: &str) -> String {
     }
 }
 
+fn encode_subject(subject: &str) -> String {
+    // We do not use needs_encoding() here because needs_encoding() returns true if the string contains a space
+    // but we do not want to encode all subjects just because they contain a space.
+    if subject
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == ' ')
+    {
+        subject.to_string()
+    } else {
+        encode_words(subject)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use mailparse::{addrparse_header, MailHeaderMap};
@@ -1528,7 +1720,7 @@ mod tests {
     use crate::contact::{ContactAddress, Origin};
     use crate::mimeparser::MimeMessage;
     use crate::receive_imf::receive_imf;
-    use crate::test_utils::{get_chat_msg, TestContext};
+    use crate::test_utils::{get_chat_msg, TestContext, TestContextManager};
     #[test]
     fn test_render_email_address() {
         let display_name = "ä space";
@@ -1634,6 +1826,69 @@ async fn test_manually_set_subject() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_chat_signature() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        t.set_config(Config::Selfstatus, Some("Global status"))
+            .await?;
+        let chat = t.create_chat_with_contact("bob", "bob@example.org").await;
+
+        let sent_msg = t.send_text(chat.id, "Hi").await;
+        assert!(sent_msg.payload().contains("-- \r\nGlobal status"));
+
+        chat.id.set_signature(&t, Some("Chat status")).await?;
+        let sent_msg = t.send_text(chat.id, "Hi").await;
+        assert!(sent_msg.payload().contains("-- \r\nChat status"));
+        assert!(!sent_msg.payload().contains("Global status"));
+
+        chat.id.set_signature(&t, Some("")).await?;
+        let sent_msg = t.send_text(chat.id, "Hi").await;
+        assert!(!sent_msg.payload().contains("-- \r\n"));
+
+        chat.id.set_signature(&t, None).await?;
+        let sent_msg = t.send_text(chat.id, "Hi").await;
+        assert!(sent_msg.payload().contains("-- \r\nGlobal status"));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_encrypted_subject() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        tcm.send_recv_accept(&bob, &alice, "Hi").await;
+        let chat = alice.create_chat(&bob).await;
+
+        for (policy, outer_subject) in [
+            (EncryptedSubject::Hidden, "...".to_string()),
+            (EncryptedSubject::ChatName, chat.name.clone()),
+            (EncryptedSubject::Plain, "Subjeeeeect".to_string()),
+        ] {
+            alice
+                .set_config(Config::EncryptedSubject, Some(&(policy as i32).to_string()))
+                .await?;
+            let mut msg = Message::new(Viewtype::Text);
+            msg.set_text(Some("Hello".to_string()));
+            msg.set_subject("Subjeeeeect".to_string());
+            let sent_msg = alice.send_msg(chat.id, &mut msg).await;
+            let payload = sent_msg.payload();
+            assert_eq!(
+                payload
+                    .match_indices(&format!("Subject: {outer_subject}\r\n"))
+                    .count(),
+                1
+            );
+
+            // The subject is always taken from the encrypted part.
+            let received = bob.recv_msg(&sent_msg).await;
+            assert!(received.get_showpadlock());
+            assert_eq!(received.subject, "Subjeeeeect");
+        }
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_subject_from_mua() {
         // 1.: Receive a mail from an MUA
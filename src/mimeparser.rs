@@ -25,7 +25,7 @@
 use crate::dehtml::dehtml;
 use crate::events::EventType;
 use crate::headerdef::{HeaderDef, HeaderDefMap};
-use crate::key::{DcKey, Fingerprint, SignedPublicKey, SignedSecretKey};
+use crate::key::{DcKey, Fingerprint, SignedPublicKey};
 use crate::keyring::Keyring;
 use crate::message::{self, Viewtype};
 use crate::param::{Param, Params};
@@ -33,7 +33,10 @@
 use crate::simplify::{simplify, SimplifiedText};
 use crate::stock_str;
 use crate::sync::SyncItems;
-use crate::tools::{get_filemeta, parse_receive_headers, strip_rtlo_characters, truncate_by_lines};
+use crate::tools::{
+    get_filemeta, parse_receive_headers, parse_receive_timestamp, strip_rtlo_characters,
+    truncate_by_lines,
+};
 use crate::{location, tools};
 
 /// A parsed MIME message.
@@ -103,6 +106,9 @@ pub(crate) struct MimeMessage {
     pub decoded_data: Vec<u8>,
 
     pub(crate) hop_info: String,
+
+    /// Time the message was received by our provider, if known.
+    pub(crate) received_timestamp: Option<i64>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -171,6 +177,10 @@ pub enum SystemMessage {
     /// Chat protection is disabled.
     ChatProtectionDisabled = 12,
 
+    /// Hidden message sent to a protected group to gossip
+    /// the new verified key of a member after re-verification.
+    KeyGossip = 13,
+
     /// Self-sent-message that contains only json used for multi-device-sync;
     /// if possible, we attach that to other messages as for locations.
     MultiDeviceSync = 20,
@@ -182,6 +192,18 @@ pub enum SystemMessage {
 
     /// Webxdc info added with `info` set in `send_webxdc_status_update()`.
     WebxdcInfoMessage = 32,
+
+    /// Hidden message offering a call, see [`crate::calls`].
+    CallOffer = 40,
+
+    /// Hidden message answering a call offer.
+    CallAnswer = 41,
+
+    /// Hidden message carrying an ICE candidate of a call.
+    CallIceCandidate = 42,
+
+    /// Feedback report about a received message, see [`crate::report`].
+    AbuseReport = 50,
 }
 
 const MIME_AC_SETUP_FILE: &str = "application/autocrypt-setup";
@@ -204,6 +226,7 @@ pub(crate) async fn from_bytes(
             .and_then(|v| mailparse::dateparse(&v).ok())
             .unwrap_or_default();
         let mut hop_info = parse_receive_headers(&mail.get_headers());
+        let received_timestamp = parse_receive_timestamp(&mail.get_headers());
 
         let mut headers = Default::default();
         let mut recipients = Default::default();
@@ -266,7 +289,7 @@ pub(crate) async fn from_bytes(
         headers.remove("chat-verified");
 
         let from = from.context("No from in message")?;
-        let private_keyring: Keyring<SignedSecretKey> = Keyring::new_self(context)
+        let private_keyring = Keyring::new_self_with_old_keys(context)
             .await
             .context("failed to get own keyring")?;
         let mut decryption_info =
@@ -317,6 +340,7 @@ pub(crate) async fn from_bytes(
                     context,
                     message_time,
                     &from.addr,
+                    signatures.iter().next(),
                     &recipients,
                     gossip_headers,
                 )
@@ -406,6 +430,7 @@ pub(crate) async fn from_bytes(
             is_mime_modified: false,
             decoded_data: Vec::new(),
             hop_info,
+            received_timestamp,
         };
 
         match partial {
@@ -491,6 +516,14 @@ fn parse_system_message_headers(&mut self, context: &Context) {
                 self.is_system_message = SystemMessage::ChatProtectionDisabled;
             } else if value == "group-avatar-changed" {
                 self.is_system_message = SystemMessage::GroupImageChanged;
+            } else if value == "key-gossip" {
+                self.is_system_message = SystemMessage::KeyGossip;
+            } else if value == "call-offer" {
+                self.is_system_message = SystemMessage::CallOffer;
+            } else if value == "call-answer" {
+                self.is_system_message = SystemMessage::CallAnswer;
+            } else if value == "call-ice" {
+                self.is_system_message = SystemMessage::CallIceCandidate;
             }
         } else if self.get_header(HeaderDef::ChatGroupMemberRemoved).is_some() {
             self.is_system_message = SystemMessage::MemberRemovedFromGroup;
@@ -525,8 +558,57 @@ fn parse_videochat_headers(&mut self) {
         }
     }
 
-    /// Squashes mutitpart chat messages with attachment into single-part messages.
+    fn parse_p2p_headers(&mut self) {
+        if let Some(ticket) = self.get_header(HeaderDef::ChatP2pTicket).cloned() {
+            let name = self.get_header(HeaderDef::ChatP2pFilename).cloned();
+            let bytes = self.get_header(HeaderDef::ChatP2pFilebytes).cloned();
+            if let Some(part) = self.parts.first_mut() {
+                if part.typ == Viewtype::Text {
+                    part.param.set(Param::P2pTicket, ticket);
+                    part.param.set(Param::P2pFileName, name.unwrap_or_default());
+                    part.param
+                        .set(Param::P2pFileBytes, bytes.unwrap_or_default());
+                }
+            }
+        }
+    }
+
+    /// Squashes multipart messages with several attachments into single-part messages.
     ///
+    /// Delta Chat sends additional files of a message as further parts after the first file,
+    /// see [`crate::message::Message::add_attachment`].
+    /// Classic MUAs send multipart/mixed messages with an optional text followed by the files,
+    /// the text part is missing if the user did not write any text.
+    /// If such a message is detected, the further files are moved to the part of the first file,
+    /// and the other parts dropped.
+    /// Messages with texts between the files are left as they are.
+    #[allow(clippy::indexing_slicing)]
+    fn squash_additional_attachment_parts(&mut self) {
+        let first_file = match self.parts.first() {
+            Some(part) => usize::from(part.typ == Viewtype::Text),
+            None => return,
+        };
+        if self.parts.len() < first_file + 2
+            || !self.parts[first_file..]
+                .iter()
+                .all(|part| part.typ.has_file() && part.param.exists(Param::File))
+        {
+            return;
+        }
+
+        let mut attachments = Vec::new();
+        let mut bytes = 0;
+        for part in self.parts.drain(first_file + 1..) {
+            bytes += part.bytes;
+            attachments.extend(part.param.get(Param::File).map(|file| file.to_string()));
+        }
+        let filepart = &mut self.parts[first_file];
+        filepart.bytes += bytes;
+        filepart
+            .param
+            .set(Param::Attachments, attachments.join("\n"));
+    }
+
     /// Delta Chat sends attachments, such as images, in two-part messages, with the first message
     /// containing a description. If such a message is detected, text from the first part can be
     /// moved to the second part, and the first part dropped.
@@ -543,7 +625,10 @@ fn squash_attachment_parts(&mut self) {
                     | Viewtype::Video
                     | Viewtype::File
                     | Viewtype::Webxdc => true,
-                    Viewtype::Unknown | Viewtype::Text | Viewtype::VideochatInvitation => false,
+                    Viewtype::Unknown
+                    | Viewtype::Text
+                    | Viewtype::VideochatInvitation
+                    | Viewtype::Call => false,
                 };
 
             if need_drop {
@@ -596,6 +681,22 @@ fn parse_attachments(&mut self) {
                     }
                 }
             }
+            if matches!(
+                part.typ,
+                Viewtype::Image | Viewtype::Gif | Viewtype::Voice | Viewtype::Video
+            ) && self.get_header(HeaderDef::ChatViewOnce).map(|s| s.as_str()) == Some("1")
+            {
+                // The text is only a fallback for clients not supporting view-once messages.
+                part.param.set_int(Param::ViewOnce, 1);
+                part.msg.clear();
+            }
+            if part.typ.has_file() {
+                if let Some(album_id) = self.get_header(HeaderDef::ChatAlbum) {
+                    if is_valid_album_id(album_id) {
+                        part.param.set(Param::Album, album_id);
+                    }
+                }
+            }
 
             self.parts.push(part);
         }
@@ -605,7 +706,9 @@ async fn parse_headers(&mut self, context: &Context) -> Result<()> {
         self.parse_system_message_headers(context);
         self.parse_avatar_headers(context).await;
         self.parse_videochat_headers();
+        self.parse_p2p_headers();
         if self.delivery_report.is_none() {
+            self.squash_additional_attachment_parts();
             self.squash_attachment_parts();
         }
 
@@ -719,9 +822,7 @@ async fn avatar_action_from_header(
                 } else {
                     String::new()
                 };
-                match BlobObject::create(context, &format!("avatar{extension}"), &decoded_data)
-                    .await
-                {
+                match BlobObject::create_and_deduplicate(context, &decoded_data, &extension).await {
                     Ok(blob) => Some(AvatarAction::Change(blob.as_name().to_string())),
                     Err(err) => {
                         warn!(
@@ -1680,12 +1781,15 @@ pub async fn get_parent_timestamp(&self, context: &Context) -> Result<Option<i64
 /// Parses `Autocrypt-Gossip` headers from the email and applies them to peerstates.
 /// Params:
 /// from: The address which sent the message currently being parsed
+/// signer: Fingerprint of the key which signed the message,
+///   recorded in the peerstates as the source of gossiped keys
 ///
 /// Returns the set of mail recipient addresses for which valid gossip headers were found.
 async fn update_gossip_peerstates(
     context: &Context,
     message_time: i64,
     from: &str,
+    signer: Option<&Fingerprint>,
     recipients: &[SingleInfo],
     gossip_headers: Vec<String>,
 ) -> Result<HashSet<String>> {
@@ -1722,11 +1826,24 @@ async fn update_gossip_peerstates(
 
         let peerstate;
         if let Some(mut p) = Peerstate::from_addr(context, &header.addr).await? {
-            p.apply_gossip(&header, message_time);
+            p.apply_gossip(&header, message_time, from, signer);
+            let gossip_fingerprint = header.public_key.fingerprint();
+            if let Some(public_key_fingerprint) = &p.public_key_fingerprint {
+                if public_key_fingerprint != &gossip_fingerprint {
+                    warn!(
+                        context,
+                        "Key {} gossiped by {} for {} differs from the Autocrypt key {}.",
+                        gossip_fingerprint.hex(),
+                        from,
+                        &header.addr,
+                        public_key_fingerprint.hex(),
+                    );
+                }
+            }
             p.save_to_db(&context.sql).await?;
             peerstate = p;
         } else {
-            let p = Peerstate::from_gossip(&header, message_time);
+            let p = Peerstate::from_gossip(&header, message_time, from, signer);
             p.save_to_db(&context.sql).await?;
             peerstate = p;
         };
@@ -1805,6 +1922,15 @@ fn is_known(key: &str) -> bool {
     )
 }
 
+/// Checks that an album ID received in the `Chat-Album` header looks like one created by us,
+/// as it is used in database queries.
+fn is_valid_album_id(album_id: &str) -> bool {
+    (1..=32).contains(&album_id.len())
+        && album_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
 /// Parsed MIME part.
 #[derive(Debug, Default, Clone)]
 pub struct Part {
@@ -2742,6 +2868,53 @@ async fn test_parse_inline_attachment() {
         assert_eq!(message.parts[0].msg, "Mail with inline attachment – Hello!");
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_parse_mua_multiple_attachments() {
+        let t = TestContext::new_alice().await;
+        let raw = br#"Date: Thu, 13 Feb 2020 22:41:20 +0000 (UTC)
+From: sender@example.com
+To: receiver@example.com
+Subject: Files
+MIME-Version: 1.0
+Content-Type: multipart/mixed; boundary="==BOUNDARY=="
+
+--==BOUNDARY==
+Content-Type: text/plain; charset=utf-8
+
+Here are the files.
+
+--==BOUNDARY==
+Content-Type: text/plain; charset=utf-8
+Content-Disposition: attachment; filename="first.txt"
+
+first
+--==BOUNDARY==
+Content-Type: text/plain; charset=utf-8
+Content-Disposition: attachment; filename="second.txt"
+
+second
+--==BOUNDARY==--
+"#;
+        let message = MimeMessage::from_bytes(&t, &raw[..], None).await.unwrap();
+        assert_eq!(message.parts.len(), 1);
+        assert_eq!(message.parts[0].typ, Viewtype::File);
+        assert_eq!(message.parts[0].msg, "Files – Here are the files.");
+        let attachments = message.parts[0].param.get(Param::Attachments).unwrap();
+        assert_eq!(attachments.lines().count(), 1);
+
+        // Without text, the files are squashed as well.
+        let raw = String::from_utf8_lossy(raw).replace(
+            "--==BOUNDARY==\nContent-Type: text/plain; charset=utf-8\n\nHere are the files.\n\n",
+            "",
+        );
+        let message = MimeMessage::from_bytes(&t, raw.as_bytes(), None)
+            .await
+            .unwrap();
+        assert_eq!(message.parts.len(), 1);
+        assert_eq!(message.parts[0].typ, Viewtype::File);
+        assert!(message.parts[0].param.exists(Param::Attachments));
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_hide_html_without_content() {
         let t = TestContext::new_alice().await;
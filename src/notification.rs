@@ -0,0 +1,648 @@
+//! # Notification decisions.
+//!
+//! Decides whether an incoming message should be notified
+//! so that all platforms behave the same.
+//! The decision is sent with [`EventType::IncomingMsg`](crate::EventType::IncomingMsg),
+//! stored with the message and can be queried later using [`get_notification_intent`].
+//! The rules are configured using [`Config::NotifyMutedMentions`], [`Config::NotifyBots`]
+//! and the quiet hours [`Config::QuietHours`] and synchronized to other devices.
+//!
+//! Notifications suppressed during quiet hours are recorded,
+//! so UIs can show a summary when the quiet hours are over.
+//! Alternatively, a summary device message is added after the quiet hours
+//! if [`Config::NotificationDigest`] is enabled.
+
+use anyhow::Result;
+use chrono::Timelike;
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::chat::{self, Chat, ChatId};
+use crate::config::Config;
+use crate::contact::{Contact, ContactId, VerifiedStatus};
+use crate::context::Context;
+use crate::events::EventType;
+use crate::log::LogExt;
+use crate::message::{DeviceAction, Message, MessageState, MsgId, Viewtype};
+use crate::param::Param;
+use crate::stock_str;
+use crate::tools::time;
+
+/// How the user should be notified about a message.
+#[derive(
+    Debug,
+    Default,
+    Display,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    FromPrimitive,
+    ToPrimitive,
+    Serialize,
+    Deserialize,
+)]
+#[repr(u32)]
+pub enum NotificationIntent {
+    /// Do not show a notification.
+    Suppress = 0,
+
+    /// Show a notification without sound or vibration,
+    /// e.g. for info messages and messages from bots.
+    Silent = 1,
+
+    /// Show a notification.
+    #[default]
+    Notify = 2,
+
+    /// Show a notification, the message mentions the user
+    /// or replies to a message of the user.
+    ///
+    /// Mentions are notified even in muted chats
+    /// unless [`Config::NotifyMutedMentions`] is disabled.
+    Mention = 3,
+}
+
+/// Returns how the user should be notified about the message.
+///
+/// This is the decision made when the message was received,
+/// later changes of the settings or quiet hours do not change it.
+/// Only fresh incoming messages are notified.
+pub async fn get_notification_intent(
+    context: &Context,
+    msg_id: MsgId,
+) -> Result<NotificationIntent> {
+    let msg = Message::load_from_db(context, msg_id).await?;
+    if msg.state != MessageState::InFresh {
+        return Ok(NotificationIntent::Suppress);
+    }
+    if let Some(intent) = msg
+        .param
+        .get_int(Param::NotificationIntent)
+        .and_then(NotificationIntent::from_i32)
+    {
+        return Ok(intent);
+    }
+    // The message was received before intents were stored.
+    let (intent, _) = decide_notification_intent(context, &msg).await?;
+    Ok(intent)
+}
+
+/// Returns how the user should be notified about the message
+/// and whether the notification is suppressed because of quiet hours.
+async fn decide_notification_intent(
+    context: &Context,
+    msg: &Message,
+) -> Result<(NotificationIntent, bool)> {
+    let intent = get_intent_ignoring_quiet_hours(context, msg).await?;
+    if intent == NotificationIntent::Suppress || !is_quiet_time(context).await? {
+        return Ok((intent, false));
+    }
+
+    if intent == NotificationIntent::Mention
+        && context
+            .get_config_bool(Config::QuietHoursAllowMentions)
+            .await?
+    {
+        return Ok((intent, false));
+    }
+    if context
+        .get_config_bool(Config::QuietHoursAllowVerified)
+        .await?
+        && Contact::load_from_db(context, msg.from_id)
+            .await?
+            .is_verified(context)
+            .await?
+            == VerifiedStatus::BidirectVerified
+    {
+        return Ok((intent, false));
+    }
+    Ok((NotificationIntent::Suppress, true))
+}
+
+async fn get_intent_ignoring_quiet_hours(
+    context: &Context,
+    msg: &Message,
+) -> Result<NotificationIntent> {
+    if msg.state != MessageState::InFresh
+        || msg.hidden
+        || msg.from_id == ContactId::SELF
+        || msg.chat_id.is_special()
+    {
+        return Ok(NotificationIntent::Suppress);
+    }
+
+    let chat = Chat::load_from_db(context, msg.chat_id).await?;
+    let mentioned = is_mention(context, msg).await?;
+    if chat.is_muted_at(context.clock.now()) {
+        return if mentioned && context.get_config_bool(Config::NotifyMutedMentions).await? {
+            Ok(NotificationIntent::Mention)
+        } else {
+            Ok(NotificationIntent::Suppress)
+        };
+    }
+
+    if mentioned {
+        Ok(NotificationIntent::Mention)
+    } else if msg.is_bot() {
+        if context.get_config_bool(Config::NotifyBots).await? {
+            Ok(NotificationIntent::Silent)
+        } else {
+            Ok(NotificationIntent::Suppress)
+        }
+    } else if msg.is_info() {
+        // E.g. group changes and webxdc info messages.
+        Ok(NotificationIntent::Silent)
+    } else {
+        Ok(NotificationIntent::Notify)
+    }
+}
+
+/// Emits [`EventType::IncomingMsg`] for a fresh message with the notification intent
+/// and stores the intent with the message.
+///
+/// If the notification is suppressed because of quiet hours,
+/// the message is recorded and [`EventType::NotificationsSuppressed`] is emitted.
+pub(crate) async fn notify_incoming_msg(context: &Context, chat_id: ChatId, msg_id: MsgId) {
+    let (intent, quiet) = store_notification_intent(context, msg_id)
+        .await
+        .log_err(context)
+        .unwrap_or_default();
+    if quiet {
+        if let Err(err) = record_suppressed_notification(context, chat_id, msg_id).await {
+            warn!(
+                context,
+                "Failed to record suppressed notification: {err:#}."
+            );
+        }
+    }
+    context.emit_incoming_msg(chat_id, msg_id, intent);
+}
+
+async fn store_notification_intent(
+    context: &Context,
+    msg_id: MsgId,
+) -> Result<(NotificationIntent, bool)> {
+    let mut msg = Message::load_from_db(context, msg_id).await?;
+    let (intent, quiet) = decide_notification_intent(context, &msg).await?;
+    msg.param.set_int(Param::NotificationIntent, intent as i32);
+    msg.update_param(context).await?;
+    Ok((intent, quiet))
+}
+
+async fn record_suppressed_notification(
+    context: &Context,
+    chat_id: ChatId,
+    msg_id: MsgId,
+) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "INSERT OR IGNORE INTO suppressed_notifications (msg_id, chat_id, timestamp)
+             VALUES (?, ?, ?)",
+            (msg_id, chat_id, time()),
+        )
+        .await?;
+    let count = get_suppressed_notifications(context).await?.len();
+    context.emit_event(EventType::NotificationsSuppressed { count });
+    Ok(())
+}
+
+/// Returns the messages whose notifications were suppressed because of quiet hours,
+/// oldest first.
+///
+/// Call [`clear_suppressed_notifications`] after showing a summary.
+pub async fn get_suppressed_notifications(context: &Context) -> Result<Vec<MsgId>> {
+    context
+        .sql
+        .query_map(
+            "SELECT s.msg_id FROM suppressed_notifications s
+             INNER JOIN msgs m ON m.id=s.msg_id AND m.chat_id=s.chat_id
+             ORDER BY s.timestamp, s.msg_id",
+            (),
+            |row| row.get::<_, MsgId>(0),
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await
+}
+
+/// Forgets the messages returned by [`get_suppressed_notifications`].
+pub async fn clear_suppressed_notifications(context: &Context) -> Result<()> {
+    context
+        .sql
+        .execute("DELETE FROM suppressed_notifications", ())
+        .await?;
+    Ok(())
+}
+
+/// Adds a device message summarizing the suppressed notifications of messages
+/// which are still unread if [`Config::NotificationDigest`] is enabled.
+///
+/// Called during housekeeping, i.e. about once a day.
+/// Nothing is done during quiet hours, the suppressed notifications are then kept
+/// and summarized by a later housekeeping run.
+pub(crate) async fn maybe_add_digest(context: &Context) -> Result<()> {
+    if !context.get_config_bool(Config::NotificationDigest).await? || is_quiet_time(context).await?
+    {
+        return Ok(());
+    }
+
+    let (msgs, chats) = context
+        .sql
+        .query_row(
+            "SELECT COUNT(*), COUNT(DISTINCT s.chat_id) FROM suppressed_notifications s
+             INNER JOIN msgs m ON m.id=s.msg_id AND m.chat_id=s.chat_id
+             WHERE m.state=?",
+            (MessageState::InFresh,),
+            |row| {
+                let msgs: usize = row.get(0)?;
+                let chats: usize = row.get(1)?;
+                Ok((msgs, chats))
+            },
+        )
+        .await?;
+    if msgs > 0 {
+        let mut msg = Message::new(Viewtype::Text);
+        msg.text = Some(stock_str::notification_digest(context, msgs, chats).await);
+        msg.set_device_action(DeviceAction::NotificationSettings);
+        chat::add_device_msg(context, None, Some(&mut msg)).await?;
+    }
+    clear_suppressed_notifications(context).await
+}
+
+/// Returns true if it is quiet hours now according to [`Config::QuietHours`].
+pub(crate) async fn is_quiet_time(context: &Context) -> Result<bool> {
+    let value = context.get_config(Config::QuietHours).await?;
+    let value = match value.as_deref().map(str::trim) {
+        None | Some("") => return Ok(false),
+        Some(value) => value,
+    };
+    let (start, end) = match parse_quiet_hours(value) {
+        Some(quiet_hours) => quiet_hours,
+        None => {
+            warn!(context, "Ignoring invalid quiet hours {value:?}.");
+            return Ok(false);
+        }
+    };
+    let now = chrono::DateTime::<chrono::Local>::from(context.clock.now());
+    Ok(is_in_quiet_hours(
+        start,
+        end,
+        now.hour() * 60 + now.minute(),
+    ))
+}
+
+/// Parses quiet hours given in local time as `HH:MM-HH:MM`, e.g. `22:00-07:00`,
+/// into the start and end as minutes of the day.
+pub(crate) fn parse_quiet_hours(value: &str) -> Option<(u32, u32)> {
+    let (start, end) = value.split_once('-')?;
+    Some((parse_time_of_day(start)?, parse_time_of_day(end)?))
+}
+
+fn parse_time_of_day(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    if hours < 24 && minutes < 60 {
+        Some(hours * 60 + minutes)
+    } else {
+        None
+    }
+}
+
+/// Returns true if the minute of the day is in the quiet hours,
+/// which may span midnight.
+fn is_in_quiet_hours(start: u32, end: u32, minute: u32) -> bool {
+    if start <= end {
+        start <= minute && minute < end
+    } else {
+        minute >= start || minute < end
+    }
+}
+
+/// Returns true if the message replies to a message of the user or mentions the user.
+async fn is_mention(context: &Context, msg: &Message) -> Result<bool> {
+    if let Some(parent) = msg.parent(context).await? {
+        if parent.from_id == ContactId::SELF {
+            return Ok(true);
+        }
+    }
+    mentions_self(context, msg.text.as_deref().unwrap_or_default()).await
+}
+
+/// Returns true if the text contains the user's address
+/// or `@` followed by the user's display name.
+pub(crate) async fn mentions_self(context: &Context, text: &str) -> Result<bool> {
+    let text = text.to_lowercase();
+    let self_addr = context.get_primary_self_addr().await?.to_lowercase();
+    if contains_word(&text, &self_addr) {
+        return Ok(true);
+    }
+    let self_name = context
+        .get_config(Config::Displayname)
+        .await?
+        .filter(|name| !name.is_empty())
+        .map(|name| format!("@{}", name.to_lowercase()));
+    Ok(self_name.map_or(false, |self_name| contains_word(&text, &self_name)))
+}
+
+/// Returns true if `word` occurs in `text` and is not part of a longer word or address,
+/// so that `@bob` does not match `@bobby` and `bob@example.org` does not match
+/// `notbob@example.org` or `bob@example.org.uk`.
+///
+/// A dot directly after `word` is treated as the end of the sentence.
+fn contains_word(text: &str, word: &str) -> bool {
+    fn is_word_char(c: char) -> bool {
+        c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | '@')
+    }
+
+    text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        if before.map_or(false, |c| is_word_char(c) || c == '.') {
+            return false;
+        }
+        let mut after = text[start + word.len()..].chars();
+        match after.next() {
+            None => true,
+            Some('.') => !after.next().map_or(false, is_word_char),
+            Some(c) => !is_word_char(c),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::{self, MuteDuration};
+    use crate::message::markseen_msgs;
+    use crate::receive_imf::receive_imf;
+    use crate::test_utils::TestContextManager;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_notification_intent() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        bob.set_config(Config::Displayname, Some("Bob")).await?;
+
+        let alice_chat_id = alice.create_chat(&bob).await.id;
+        let sent = alice.send_text(alice_chat_id, "Hi Bob").await;
+        let msg = bob.recv_msg(&sent).await;
+        msg.chat_id.accept(&bob).await?;
+        assert_eq!(
+            get_notification_intent(&bob, msg.id).await?,
+            NotificationIntent::Notify
+        );
+        assert_eq!(
+            get_notification_intent(&alice, sent.sender_msg_id).await?,
+            NotificationIntent::Suppress
+        );
+
+        chat::set_muted(&bob, msg.chat_id, MuteDuration::Forever).await?;
+        let msg = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "Still there?").await)
+            .await;
+        assert_eq!(
+            get_notification_intent(&bob, msg.id).await?,
+            NotificationIntent::Suppress
+        );
+        let mention = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "Hey @Bob").await)
+            .await;
+        assert_eq!(
+            get_notification_intent(&bob, mention.id).await?,
+            NotificationIntent::Mention
+        );
+        bob.set_config_bool(Config::NotifyMutedMentions, false)
+            .await?;
+        // The decision made when receiving the message does not change.
+        assert_eq!(
+            get_notification_intent(&bob, mention.id).await?,
+            NotificationIntent::Mention
+        );
+        let msg = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "Hey @Bob, wake up").await)
+            .await;
+        assert_eq!(
+            get_notification_intent(&bob, msg.id).await?,
+            NotificationIntent::Suppress
+        );
+
+        // Messages which are not fresh anymore are not notified.
+        markseen_msgs(&bob, vec![mention.id]).await?;
+        assert_eq!(
+            get_notification_intent(&bob, mention.id).await?,
+            NotificationIntent::Suppress
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_mentions_self() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let bob = tcm.bob().await;
+        bob.set_config(Config::Displayname, Some("Bob")).await?;
+
+        for text in [
+            "Hey @Bob",
+            "@bob, wake up",
+            "(@Bob)",
+            "Thanks @Bob.",
+            "Ask bob@example.net",
+            "<bob@example.net>",
+            "Mail BOB@example.net.",
+        ] {
+            assert!(mentions_self(&bob, text).await?, "{text:?}");
+        }
+        for text in [
+            "Hey @Bobby",
+            "@bob_builder",
+            "@bob.smith",
+            "mail@bob.org",
+            "Bob",
+            "notbob@example.net",
+            "bob@example.network",
+            "bob@example.net.example.org",
+            "bob@example.net@example.org",
+        ] {
+            assert!(!mentions_self(&bob, text).await?, "{text:?}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_notification_intent_bot() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let received = receive_imf(
+            &alice,
+            b"From: bot@example.net\n\
+              To: alice@example.org\n\
+              Subject: foo\n\
+              Message-ID: <bot@example.net>\n\
+              Chat-Version: 1.0\n\
+              Auto-Submitted: auto-generated\n\
+              Date: Sun, 22 Mar 2020 22:37:57 +0000\n\
+              \n\
+              beep\n",
+            false,
+        )
+        .await?
+        .unwrap();
+        let msg_id = received.msg_ids[0];
+        received.chat_id.accept(&alice).await?;
+        assert_eq!(
+            get_notification_intent(&alice, msg_id).await?,
+            NotificationIntent::Silent
+        );
+        alice.set_config_bool(Config::NotifyBots, false).await?;
+        let received = receive_imf(
+            &alice,
+            b"From: bot@example.net\n\
+              To: alice@example.org\n\
+              Subject: foo\n\
+              Message-ID: <bot2@example.net>\n\
+              Chat-Version: 1.0\n\
+              Auto-Submitted: auto-generated\n\
+              Date: Sun, 22 Mar 2020 22:38:57 +0000\n\
+              \n\
+              beep beep\n",
+            false,
+        )
+        .await?
+        .unwrap();
+        assert_eq!(
+            get_notification_intent(&alice, received.msg_ids[0]).await?,
+            NotificationIntent::Suppress
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_quiet_hours() {
+        assert_eq!(parse_quiet_hours("22:00-07:30"), Some((1320, 450)));
+        assert_eq!(parse_quiet_hours(" 9:05 - 17:00 "), Some((545, 1020)));
+        assert_eq!(parse_quiet_hours("22:00"), None);
+        assert_eq!(parse_quiet_hours("24:00-07:00"), None);
+        assert_eq!(parse_quiet_hours("22:60-07:00"), None);
+        assert_eq!(parse_quiet_hours("foo-bar"), None);
+
+        assert!(is_in_quiet_hours(1320, 420, 1400));
+        assert!(is_in_quiet_hours(1320, 420, 0));
+        assert!(!is_in_quiet_hours(1320, 420, 420));
+        assert!(!is_in_quiet_hours(1320, 420, 720));
+        assert!(is_in_quiet_hours(540, 1020, 540));
+        assert!(!is_in_quiet_hours(540, 1020, 1020));
+        assert!(!is_in_quiet_hours(600, 600, 600));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_quiet_hours() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        bob.set_config(Config::Displayname, Some("Bob")).await?;
+        assert!(bob
+            .set_config(Config::QuietHours, Some("22:00"))
+            .await
+            .is_err());
+
+        let alice_chat_id = alice.create_chat(&bob).await.id;
+        let msg = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "Hi Bob").await)
+            .await;
+        msg.chat_id.accept(&bob).await?;
+
+        // Quiet hours from an hour ago to in an hour.
+        let now = chrono::DateTime::<chrono::Local>::from(bob.clock.now());
+        let minute = now.hour() * 60 + now.minute();
+        let format_minute = |m: u32| format!("{:02}:{:02}", m / 60, m % 60);
+        let quiet_hours = format!(
+            "{}-{}",
+            format_minute((minute + 23 * 60) % (24 * 60)),
+            format_minute((minute + 60) % (24 * 60))
+        );
+        bob.set_config(Config::QuietHours, Some(&quiet_hours))
+            .await?;
+        assert!(is_quiet_time(&bob).await?);
+
+        let msg = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "Still there?").await)
+            .await;
+        assert_eq!(
+            get_notification_intent(&bob, msg.id).await?,
+            NotificationIntent::Suppress
+        );
+        assert_eq!(get_suppressed_notifications(&bob).await?, vec![msg.id]);
+
+        let mention = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "Hey @Bob").await)
+            .await;
+        assert_eq!(
+            get_notification_intent(&bob, mention.id).await?,
+            NotificationIntent::Mention
+        );
+        assert_eq!(get_suppressed_notifications(&bob).await?.len(), 1);
+        bob.set_config_bool(Config::QuietHoursAllowMentions, false)
+            .await?;
+        let mention = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "Hey @Bob, wake up").await)
+            .await;
+        assert_eq!(
+            get_notification_intent(&bob, mention.id).await?,
+            NotificationIntent::Suppress
+        );
+
+        clear_suppressed_notifications(&bob).await?;
+        assert!(get_suppressed_notifications(&bob).await?.is_empty());
+
+        bob.set_config(Config::QuietHours, None).await?;
+        assert!(!is_quiet_time(&bob).await?);
+        assert_eq!(
+            get_notification_intent(&bob, msg.id).await?,
+            NotificationIntent::Suppress
+        );
+        let msg = bob
+            .recv_msg(&alice.send_text(alice_chat_id, "Good morning").await)
+            .await;
+        assert_eq!(
+            get_notification_intent(&bob, msg.id).await?,
+            NotificationIntent::Notify
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_notification_digest() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let alice_chat_id = alice.create_chat(&bob).await.id;
+        for text in ["Hi", "Are you there?"] {
+            let msg = bob
+                .recv_msg(&alice.send_text(alice_chat_id, text).await)
+                .await;
+            record_suppressed_notification(&bob, msg.chat_id, msg.id).await?;
+        }
+        let device_chat_id = ChatId::get_for_contact(&bob, ContactId::DEVICE).await?;
+        let device_msgs = chat::get_chat_msgs(&bob, device_chat_id).await?.len();
+
+        // Disabled by default.
+        maybe_add_digest(&bob).await?;
+        assert_eq!(get_suppressed_notifications(&bob).await?.len(), 2);
+        assert_eq!(
+            chat::get_chat_msgs(&bob, device_chat_id).await?.len(),
+            device_msgs
+        );
+
+        bob.set_config_bool(Config::NotificationDigest, true)
+            .await?;
+        maybe_add_digest(&bob).await?;
+        assert_eq!(
+            bob.get_last_msg_in(device_chat_id).await.text.as_deref(),
+            Some("2 new messages in 1 chat while you were away.")
+        );
+        assert!(get_suppressed_notifications(&bob).await?.is_empty());
+        Ok(())
+    }
+}
@@ -184,6 +184,75 @@ pub enum Param {
 
     /// For Webxdc Message Instances: timestamp of summary update.
     WebxdcSummaryTimestamp = b'Q',
+
+    /// For Webxdc Message Instances: number of info messages from other members
+    /// not yet seen by the user.
+    WebxdcUnreadInfoCount = b'Y',
+
+    /// For Webxdc Info Messages: `href` to pass to the webxdc when the info message is clicked.
+    WebxdcHref = b'X',
+
+    /// For Messages: 1 = media can be viewed only once, 2 = media was viewed and deleted.
+    ViewOnce = b'v',
+
+    /// For Messages: name of the media file of a received view-once message,
+    /// stored outside of the blobdir, see [`crate::message::MsgId::open_view_once`].
+    ViewOnceFile = b'%',
+
+    /// For Chats: 1 = messages are displayed grouped by threads.
+    Threaded = b'Z',
+
+    /// For Messages in the saved messages chat: ID of the chat the message was saved from.
+    SavedFromChat = b'y',
+
+    /// For Messages in the saved messages chat: ID of the original author of the message.
+    SavedFromContact = b'z',
+
+    /// For outgoing images: space-separated names of the metadata removed from the image.
+    RemovedMetadata = b'0',
+
+    /// For Chats: signature overriding the `selfstatus` config,
+    /// an empty value disables the footer.
+    Signature = b'1',
+
+    /// For device messages: action the UI can offer, see [`crate::message::DeviceAction`].
+    DeviceAction = b'2',
+
+    /// For Messages: ticket to download a file offered peer-to-peer,
+    /// see [`crate::peer_transfer`].
+    P2pTicket = b'3',
+
+    /// For Messages: name of the file offered peer-to-peer.
+    P2pFileName = b'4',
+
+    /// For Messages: size in bytes of the file offered peer-to-peer.
+    P2pFileBytes = b'5',
+
+    /// For call offers: state of the call, see [`crate::calls`].
+    CallState = b'6',
+
+    /// For Messages: delay in seconds if the message was delivered late,
+    /// see [`crate::late_delivery`].
+    DeliveryDelay = b'7',
+
+    /// For Messages: additional files attached to the message, one per line,
+    /// see [`crate::message::Message::add_attachment`].
+    Attachments = b'8',
+
+    /// For Messages: grouping ID of the album the media belongs to,
+    /// see [`crate::chat::send_album`].
+    Album = b'9',
+
+    /// For Groups: contacts joining via QR code have to answer a challenge,
+    /// see [`crate::securejoin::set_join_gate`].
+    JoinGate = b'#',
+
+    /// For Messages: set to 1 if DKIM failed although it is known to work for the sender's domain,
+    /// see [`crate::reputation`].
+    DkimFailed = b'$',
+
+    /// For Messages: [`crate::notification::NotificationIntent`] decided when the message was received.
+    NotificationIntent = b'&',
 }
 
 /// An object for handling key=value parameter lists.
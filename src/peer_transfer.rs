@@ -0,0 +1,513 @@
+//! # Peer-to-peer transfer of large files.
+//!
+//! Files exceeding the maximum message size can not be sent by email.
+//! Instead, the sender offers the file using the iroh stack also used by
+//! [`crate::imex::BackupProvider`] and sends a small message carrying the iroh ticket,
+//! the name and the size of the file.
+//! The recipients download the file directly from the sender with [`download_file_p2p`]
+//! while both devices are online.
+//!
+//! Offers are stored in the database with the key of the iroh node,
+//! so they are offered again with the same ticket when I/O is started again,
+//! also after a restart, see [`resume_offers`].
+//! Recipients can retry failed downloads for [`P2P_OFFER_LIFETIME`].
+//!
+//! The iroh protocol only transfers the file as a whole, it can not request ranges.
+//! A download which is interrupted thus starts from the beginning when it is retried.
+//!
+//! Sending files peer-to-peer is opt-in, see [`Config::P2pFileTransfer`].
+
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, ensure, Context as _, Result};
+use iroh::blobs::Collection;
+use iroh::get::DataStream;
+use iroh::progress::ProgressEmitter;
+use iroh::protocol::AuthToken;
+use iroh::provider::{DataSource, Event, Provider, Ticket};
+use iroh::tls::Keypair;
+use tokio::fs::{self, File};
+use tokio::io::{self, AsyncWriteExt, BufWriter};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+use tokio_util::sync::{CancellationToken, DropGuard};
+
+use crate::blob::BlobObject;
+use crate::chat::{self, ChatId};
+use crate::config::Config;
+use crate::context::Context;
+use crate::events::EventType;
+use crate::message::{self, Message, MsgId, Viewtype};
+use crate::param::Param;
+use crate::tools::time;
+
+const MAX_CONCURRENT_DIALS: u8 = 16;
+
+/// How long a file is offered after sending it, in seconds.
+pub(crate) const P2P_OFFER_LIFETIME: i64 = 7 * 24 * 60 * 60;
+
+/// Name of the file in the offered iroh collection.
+const COLLECTION_FILE_NAME: &str = "file";
+
+/// Sends the file of `msg` as a peer-to-peer offer.
+///
+/// The file is not attached to the message,
+/// instead the recipients get a ticket to download it with [`download_file_p2p`].
+/// The file is offered for [`P2P_OFFER_LIFETIME`] while I/O is running.
+pub async fn send_file_p2p(context: &Context, chat_id: ChatId, msg: &mut Message) -> Result<MsgId> {
+    ensure!(
+        context.get_config_bool(Config::P2pFileTransfer).await?,
+        "Peer-to-peer file transfer is disabled"
+    );
+    ensure!(
+        msg.viewtype.has_file(),
+        "Cannot send messages of type #{} peer-to-peer",
+        msg.viewtype
+    );
+    let blob = msg
+        .param
+        .get_blob(Param::File, context, true)
+        .await?
+        .context("Attachment missing")?;
+    let name = msg.get_filename().context("Attachment has no name")?;
+    let path = blob.to_abs_path();
+    let bytes = fs::metadata(&path).await?.len();
+
+    let keypair = Keypair::generate();
+    let (provider, ticket) =
+        spawn_provider(path, keypair.clone(), AuthToken::generate(), 0).await?;
+
+    // The file is kept in the blobdir, but not attached.
+    msg.viewtype = Viewtype::Text;
+    if msg.text.as_deref().unwrap_or_default().is_empty() {
+        msg.text = Some(name.clone());
+    }
+    msg.param.set(Param::File, blob.as_name());
+    msg.param.set(Param::P2pTicket, ticket.to_string());
+    msg.param.set(Param::P2pFileName, name);
+    msg.param.set(Param::P2pFileBytes, bytes.to_string());
+    let msg_id = chat::send_msg(context, chat_id, msg).await?;
+
+    context
+        .sql
+        .execute(
+            "INSERT INTO p2p_offers (msg_id, keypair, timestamp) VALUES (?, ?, ?)",
+            (msg_id, keypair.to_openssh()?.to_string(), time()),
+        )
+        .await?;
+    add_offer(context, msg_id, provider).await;
+    info!(context, "Offering {bytes} bytes peer-to-peer for {msg_id}.");
+    Ok(msg_id)
+}
+
+/// A file offered peer-to-peer, stopped when dropped.
+pub(crate) struct Offer {
+    drop_guard: DropGuard,
+
+    /// Task supervising the provider, see [`watch_provider`].
+    task: JoinHandle<()>,
+}
+
+/// Starts an iroh [`Provider`] offering the file at `path`.
+///
+/// Offering the same file with the same `keypair`, `token` and `port` results in the same ticket,
+/// `port` 0 binds to any free port.
+async fn spawn_provider(
+    path: PathBuf,
+    keypair: Keypair,
+    token: AuthToken,
+    port: u16,
+) -> Result<(Provider, Ticket)> {
+    let (db, hash) = iroh::provider::create_collection(vec![DataSource::with_name(
+        path,
+        COLLECTION_FILE_NAME.to_string(),
+    )])
+    .await?;
+    let provider = Provider::builder(db)
+        .bind_addr((Ipv4Addr::UNSPECIFIED, port).into())
+        .keypair(keypair)
+        .auth_token(token)
+        .spawn()?;
+    let ticket = provider.ticket(hash)?;
+    Ok((provider, ticket))
+}
+
+/// Supervises the provider of an offer until I/O is stopped.
+async fn add_offer(context: &Context, msg_id: MsgId, provider: Provider) {
+    let events = provider.subscribe();
+    let drop_token = CancellationToken::new();
+    let task = tokio::spawn(watch_provider(
+        context.clone(),
+        msg_id,
+        provider,
+        events,
+        drop_token.clone(),
+    ));
+    let offer = Offer {
+        drop_guard: drop_token.drop_guard(),
+        task,
+    };
+    context.p2p_offers.lock().await.insert(msg_id, offer);
+}
+
+/// Offers the files of the stored offers again, called when I/O is started.
+///
+/// Expired offers and offers of deleted messages are removed.
+pub(crate) async fn resume_offers(context: &Context) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "DELETE FROM p2p_offers WHERE timestamp<? OR msg_id NOT IN (SELECT id FROM msgs)",
+            (time() - P2P_OFFER_LIFETIME,),
+        )
+        .await?;
+    let offers = context
+        .sql
+        .query_map(
+            "SELECT msg_id, keypair FROM p2p_offers",
+            (),
+            |row| Ok((row.get::<_, MsgId>(0)?, row.get::<_, String>(1)?)),
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await?;
+    for (msg_id, keypair) in offers {
+        if context.p2p_offers.lock().await.contains_key(&msg_id) {
+            continue;
+        }
+        if let Err(err) = resume_offer(context, msg_id, &keypair).await {
+            warn!(context, "Cannot offer file of {msg_id} again: {err:#}.");
+            context
+                .sql
+                .execute("DELETE FROM p2p_offers WHERE msg_id=?", (msg_id,))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Offers the file of a stored offer again.
+///
+/// Returns an error if the offer can not be resumed anymore.
+/// If the port of the ticket is in use, the offer is kept for the next time I/O is started.
+async fn resume_offer(context: &Context, msg_id: MsgId, keypair: &str) -> Result<()> {
+    let msg = Message::load_from_db(context, msg_id).await?;
+    ensure!(!msg.chat_id.is_trash(), "Message is deleted");
+    let ticket: Ticket = msg
+        .param
+        .get(Param::P2pTicket)
+        .context("Message does not offer a file peer-to-peer")?
+        .parse()?;
+    let path = msg.get_file(context).context("File is missing")?;
+    let port = ticket
+        .addrs()
+        .first()
+        .context("Ticket has no address")?
+        .port();
+    let keypair = Keypair::try_from_openssh(keypair)?;
+    let (provider, new_ticket) =
+        match spawn_provider(path, keypair, ticket.token().clone(), port).await {
+            Ok(res) => res,
+            Err(err) => {
+                warn!(context, "Cannot offer file of {msg_id} now: {err:#}.");
+                return Ok(());
+            }
+        };
+    if new_ticket.hash() != ticket.hash() {
+        provider.shutdown();
+        return Err(anyhow!("File has changed"));
+    }
+    add_offer(context, msg_id, provider).await;
+    info!(context, "Offering file of {msg_id} peer-to-peer again.");
+    Ok(())
+}
+
+/// Supervises the iroh [`Provider`] of an offer, reporting the progress of the transfers.
+///
+/// Unlike for backups, the provider is not shut down after a transfer,
+/// as other recipients may still download the file.
+async fn watch_provider(
+    context: Context,
+    msg_id: MsgId,
+    mut provider: Provider,
+    mut events: broadcast::Receiver<Event>,
+    drop_token: CancellationToken,
+) {
+    let mut total_size = 0;
+    let mut current_size = 0;
+    loop {
+        tokio::select! {
+            biased;
+            res = &mut provider => {
+                if let Err(err) = res {
+                    warn!(context, "Peer-to-peer provider for {msg_id} failed: {err:#}.");
+                }
+                break;
+            },
+            maybe_event = events.recv() => {
+                match maybe_event {
+                    Ok(Event::ClientConnected { .. }) | Ok(Event::RequestReceived { .. }) => {}
+                    Ok(Event::TransferCollectionStarted { total_blobs_size, .. }) => {
+                        total_size = total_blobs_size;
+                        current_size = 0;
+                        context.emit_event(progress_event(msg_id, current_size, total_size));
+                    }
+                    Ok(Event::TransferBlobCompleted { size, .. }) => {
+                        current_size += size;
+                        context.emit_event(progress_event(msg_id, current_size, total_size));
+                    }
+                    Ok(Event::TransferCollectionCompleted { .. }) => {
+                        context.emit_event(EventType::P2pFileProgress { msg_id, progress: 1000 });
+                    }
+                    Ok(Event::TransferAborted { .. }) => {
+                        context.emit_event(EventType::P2pFileProgress { msg_id, progress: 0 });
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            },
+            _ = drop_token.cancelled() => {
+                // The offer was removed already, wait until the port is free again.
+                provider.shutdown();
+                if let Err(err) = (&mut provider).await {
+                    warn!(context, "Peer-to-peer provider for {msg_id} failed: {err:#}.");
+                }
+                return;
+            }
+        }
+    }
+    context.p2p_offers.lock().await.remove(&msg_id);
+}
+
+/// Stops all peer-to-peer offers until [`resume_offers`] is called.
+pub(crate) async fn stop_offers(context: &Context) {
+    let offers = std::mem::take(&mut *context.p2p_offers.lock().await);
+    for (_, offer) in offers {
+        drop(offer.drop_guard);
+        offer.task.await.ok();
+    }
+}
+
+fn progress_event(msg_id: MsgId, current_size: u64, total_size: u64) -> EventType {
+    let progress = if total_size == 0 {
+        1
+    } else {
+        (current_size * 999 / total_size).clamp(1, 999) as usize
+    };
+    EventType::P2pFileProgress { msg_id, progress }
+}
+
+/// Downloads the file offered peer-to-peer by a message.
+///
+/// This connects to the sender, so it only succeeds while the sender is online.
+/// Reports the progress with [`EventType::P2pFileProgress`].
+/// Afterwards, the file is available as the file of the message.
+pub async fn download_file_p2p(context: &Context, msg_id: MsgId) -> Result<()> {
+    let mut msg = Message::load_from_db(context, msg_id).await?;
+    let ticket: Ticket = msg
+        .param
+        .get(Param::P2pTicket)
+        .context("Message does not offer a file peer-to-peer")?
+        .parse()
+        .context("Invalid peer-to-peer ticket")?;
+    ensure!(
+        !msg.param.exists(Param::File),
+        "File of {msg_id} is already downloaded"
+    );
+    let (name, bytes) = msg
+        .get_p2p_file()
+        .context("Peer-to-peer file info missing")?;
+
+    let context_dir = context
+        .get_blobdir()
+        .parent()
+        .ok_or(anyhow!("Context dir not found"))?;
+    let tmp_path = context_dir.join(format!("p2p-{}.part", msg_id.to_u32()));
+    let res = receive_file(context, msg_id, &ticket, bytes, &tmp_path).await;
+    if let Err(err) = res {
+        fs::remove_file(&tmp_path).await.ok();
+        context.emit_event(EventType::P2pFileProgress {
+            msg_id,
+            progress: 0,
+        });
+        return Err(err.context("Peer-to-peer download failed"));
+    }
+
+    let blob = BlobObject::create_and_move(context, &tmp_path, &name).await?;
+    msg.param.set(Param::File, blob.as_name());
+    if let Some((_, mime)) = message::guess_msgtype_from_suffix(&blob.to_abs_path()) {
+        msg.param.set(Param::MimeType, mime);
+    }
+    msg.update_param(context).await?;
+    context.emit_msgs_changed(msg.chat_id, msg_id);
+    context.emit_event(EventType::P2pFileProgress {
+        msg_id,
+        progress: 1000,
+    });
+    Ok(())
+}
+
+async fn receive_file(
+    context: &Context,
+    msg_id: MsgId,
+    ticket: &Ticket,
+    bytes: u64,
+    path: &Path,
+) -> Result<()> {
+    let progress = ProgressEmitter::new(0, 999);
+    spawn_progress_proxy(context.clone(), msg_id, progress.subscribe());
+    let on_connected = || async { Ok(()) };
+    let on_collection = |collection: &Collection| {
+        let size = collection.total_blobs_size();
+        progress.set_total(size);
+        async move {
+            ensure!(
+                size == bytes,
+                "Offered {size} bytes instead of the announced {bytes} bytes"
+            );
+            Ok(())
+        }
+    };
+    let on_blob = |_hash, reader, name| write_file(&progress, path.to_owned(), reader, name);
+
+    let keylog = false; // Do not enable rustls SSLKEYLOGFILE env var functionality
+    let stats = iroh::get::run_ticket(
+        ticket,
+        keylog,
+        MAX_CONCURRENT_DIALS,
+        on_connected,
+        on_collection,
+        on_blob,
+    )
+    .await?;
+    drop(progress);
+    info!(
+        context,
+        "Peer-to-peer download of {msg_id} finished, transfer rate was {} Mbps.",
+        stats.mbits()
+    );
+    Ok(())
+}
+
+async fn write_file(
+    progress: &ProgressEmitter,
+    path: PathBuf,
+    mut reader: DataStream,
+    name: String,
+) -> Result<DataStream> {
+    ensure!(
+        name == COLLECTION_FILE_NAME,
+        "Unexpected blob {name:?} in peer-to-peer offer"
+    );
+    let mut wrapped_reader = progress.wrap_async_read(&mut reader);
+    let file = File::create(&path).await?;
+    let mut file = BufWriter::with_capacity(128 * 1024, file);
+    io::copy(&mut wrapped_reader, &mut file).await?;
+    file.flush().await?;
+    Ok(reader)
+}
+
+/// Spawns a task proxying progress of [`ProgressEmitter`] as [`EventType::P2pFileProgress`].
+fn spawn_progress_proxy(context: Context, msg_id: MsgId, mut rx: broadcast::Receiver<u16>) {
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(step) => context.emit_event(EventType::P2pFileProgress {
+                    msg_id,
+                    progress: usize::from(step).clamp(1, 999),
+                }),
+                Err(RecvError::Closed) => break,
+                Err(RecvError::Lagged(_)) => continue,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestContextManager;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_send_file_p2p() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let chat_id = alice.create_chat(&bob).await.id;
+
+        let file = alice.get_blobdir().join("huge.bin");
+        fs::write(&file, b"pretend this is huge").await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        assert!(send_file_p2p(&alice, chat_id, &mut msg).await.is_err());
+
+        alice.set_config_bool(Config::P2pFileTransfer, true).await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        send_file_p2p(&alice, chat_id, &mut msg).await?;
+        let sent = alice.pop_sent_msg().await;
+
+        let msg = bob.recv_msg(&sent).await;
+        assert_eq!(msg.get_viewtype(), Viewtype::Text);
+        assert_eq!(msg.get_p2p_file(), Some(("huge.bin".to_string(), 20)));
+        assert_eq!(msg.get_file(&bob), None);
+
+        download_file_p2p(&bob, msg.id).await?;
+        let msg = Message::load_from_db(&bob, msg.id).await?;
+        let path = msg.get_file(&bob).unwrap();
+        assert_eq!(fs::read(&path).await?, b"pretend this is huge");
+        bob.evtracker
+            .get_matching(|ev| matches!(ev, EventType::P2pFileProgress { progress: 1000, .. }))
+            .await;
+        assert!(download_file_p2p(&bob, msg.id).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_resume_offers() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        alice.set_config_bool(Config::P2pFileTransfer, true).await?;
+        let chat_id = alice.create_chat(&bob).await.id;
+
+        let file = alice.get_blobdir().join("huge.bin");
+        fs::write(&file, b"pretend this is huge").await?;
+        let mut msg = Message::new(Viewtype::File);
+        msg.set_file(file.to_str().unwrap(), None);
+        let msg_id = send_file_p2p(&alice, chat_id, &mut msg).await?;
+        let msg = bob.recv_msg(&alice.pop_sent_msg().await).await;
+
+        // The offer is stopped together with I/O and resumed with the same ticket.
+        stop_offers(&alice).await;
+        assert!(download_file_p2p(&bob, msg.id).await.is_err());
+        resume_offers(&alice).await?;
+        assert!(alice.p2p_offers.lock().await.contains_key(&msg_id));
+        download_file_p2p(&bob, msg.id).await?;
+        let msg = Message::load_from_db(&bob, msg.id).await?;
+        assert_eq!(
+            fs::read(msg.get_file(&bob).unwrap()).await?,
+            b"pretend this is huge"
+        );
+
+        // Expired offers are removed.
+        stop_offers(&alice).await;
+        alice
+            .sql
+            .execute(
+                "UPDATE p2p_offers SET timestamp=?",
+                (time() - P2P_OFFER_LIFETIME - 1,),
+            )
+            .await?;
+        resume_offers(&alice).await?;
+        assert!(alice.p2p_offers.lock().await.is_empty());
+        assert_eq!(
+            alice
+                .sql
+                .count("SELECT COUNT(*) FROM p2p_offers", ())
+                .await?,
+            0
+        );
+        Ok(())
+    }
+}
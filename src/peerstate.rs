@@ -5,6 +5,7 @@
 use anyhow::{Context as _, Error, Result};
 use num_traits::FromPrimitive;
 
+use crate::aeap::{AeapMigration, AeapMigrationState};
 use crate::aheader::{Aheader, EncryptPreference};
 use crate::chat::{self, Chat};
 use crate::chatlist::Chatlist;
@@ -75,6 +76,13 @@ pub struct Peerstate {
     /// Fingerprint of the contact gossip key.
     pub gossip_key_fingerprint: Option<Fingerprint>,
 
+    /// Address of the peer which gossiped the current gossip key.
+    pub gossip_source_addr: Option<String>,
+
+    /// Fingerprint of the key which signed the message
+    /// containing the current gossip key.
+    pub gossip_source_fingerprint: Option<Fingerprint>,
+
     /// Public key of the contact at the time it was verified,
     /// either directly or via gossip from the verified contact.
     pub verified_key: Option<SignedPublicKey>,
@@ -103,6 +111,8 @@ pub fn from_header(header: &Aheader, message_time: i64) -> Self {
             public_key_fingerprint: Some(header.public_key.fingerprint()),
             gossip_key: None,
             gossip_key_fingerprint: None,
+            gossip_source_addr: None,
+            gossip_source_fingerprint: None,
             gossip_timestamp: 0,
             verified_key: None,
             verified_key_fingerprint: None,
@@ -112,7 +122,15 @@ pub fn from_header(header: &Aheader, message_time: i64) -> Self {
     }
 
     /// Create a peerstate from the `Autocrypt-Gossip` header.
-    pub fn from_gossip(gossip_header: &Aheader, message_time: i64) -> Self {
+    ///
+    /// `source_addr` is the address of the peer which sent the gossip
+    /// and `source_fingerprint` is the fingerprint of the key that signed the message.
+    pub fn from_gossip(
+        gossip_header: &Aheader,
+        message_time: i64,
+        source_addr: &str,
+        source_fingerprint: Option<&Fingerprint>,
+    ) -> Self {
         Peerstate {
             addr: gossip_header.addr.clone(),
             last_seen: 0,
@@ -129,6 +147,8 @@ pub fn from_gossip(gossip_header: &Aheader, message_time: i64) -> Self {
             public_key_fingerprint: None,
             gossip_key: Some(gossip_header.public_key.clone()),
             gossip_key_fingerprint: Some(gossip_header.public_key.fingerprint()),
+            gossip_source_addr: Some(source_addr.to_string()),
+            gossip_source_fingerprint: source_fingerprint.cloned(),
             gossip_timestamp: message_time,
             verified_key: None,
             verified_key_fingerprint: None,
@@ -141,7 +161,8 @@ pub fn from_gossip(gossip_header: &Aheader, message_time: i64) -> Self {
     pub async fn from_addr(context: &Context, addr: &str) -> Result<Option<Peerstate>> {
         let query = "SELECT addr, last_seen, last_seen_autocrypt, prefer_encrypted, public_key, \
                      gossip_timestamp, gossip_key, public_key_fingerprint, gossip_key_fingerprint, \
-                     verified_key, verified_key_fingerprint, verifier \
+                     verified_key, verified_key_fingerprint, verifier, \
+                     gossip_source_addr, gossip_source_fingerprint \
                      FROM acpeerstates \
                      WHERE addr=? COLLATE NOCASE LIMIT 1;";
         Self::from_stmt(context, query, (addr,)).await
@@ -154,7 +175,8 @@ pub async fn from_fingerprint(
     ) -> Result<Option<Peerstate>> {
         let query = "SELECT addr, last_seen, last_seen_autocrypt, prefer_encrypted, public_key, \
                      gossip_timestamp, gossip_key, public_key_fingerprint, gossip_key_fingerprint, \
-                     verified_key, verified_key_fingerprint, verifier \
+                     verified_key, verified_key_fingerprint, verifier, \
+                     gossip_source_addr, gossip_source_fingerprint \
                      FROM acpeerstates  \
                      WHERE public_key_fingerprint=? \
                      OR gossip_key_fingerprint=? \
@@ -174,7 +196,8 @@ pub async fn from_verified_fingerprint_or_addr(
     ) -> Result<Option<Peerstate>> {
         let query = "SELECT addr, last_seen, last_seen_autocrypt, prefer_encrypted, public_key, \
                      gossip_timestamp, gossip_key, public_key_fingerprint, gossip_key_fingerprint, \
-                     verified_key, verified_key_fingerprint, verifier \
+                     verified_key, verified_key_fingerprint, verifier, \
+                     gossip_source_addr, gossip_source_fingerprint \
                      FROM acpeerstates  \
                      WHERE verified_key_fingerprint=? \
                      OR addr=? COLLATE NOCASE \
@@ -221,6 +244,12 @@ async fn from_stmt(
                         .transpose()
                         .unwrap_or_default(),
                     gossip_timestamp: row.get("gossip_timestamp")?,
+                    gossip_source_addr: row.get("gossip_source_addr")?,
+                    gossip_source_fingerprint: row
+                        .get::<_, Option<String>>("gossip_source_fingerprint")?
+                        .map(|s| s.parse::<Fingerprint>())
+                        .transpose()
+                        .unwrap_or_default(),
                     verified_key: row
                         .get("verified_key")
                         .ok()
@@ -308,13 +337,24 @@ pub fn apply_header(&mut self, header: &Aheader, message_time: i64) {
     }
 
     /// Updates peerstate according to the given `Autocrypt-Gossip` header.
-    pub fn apply_gossip(&mut self, gossip_header: &Aheader, message_time: i64) {
+    ///
+    /// `source_addr` is the address of the peer which sent the gossip
+    /// and `source_fingerprint` is the fingerprint of the key that signed the message.
+    pub fn apply_gossip(
+        &mut self,
+        gossip_header: &Aheader,
+        message_time: i64,
+        source_addr: &str,
+        source_fingerprint: Option<&Fingerprint>,
+    ) {
         if self.addr.to_lowercase() != gossip_header.addr.to_lowercase() {
             return;
         }
 
         if message_time > self.gossip_timestamp {
             self.gossip_timestamp = message_time;
+            self.gossip_source_addr = Some(source_addr.to_string());
+            self.gossip_source_fingerprint = source_fingerprint.cloned();
             if self.gossip_key.as_ref() != Some(&gossip_header.public_key) {
                 self.gossip_key = Some(gossip_header.public_key.clone());
                 self.recalc_fingerprint();
@@ -456,8 +496,10 @@ pub async fn save_to_db(&self, sql: &Sql) -> Result<()> {
                 verified_key,
                 verified_key_fingerprint,
                 addr,
-                verifier)
-                VALUES (?,?,?,?,?,?,?,?,?,?,?,?)
+                verifier,
+                gossip_source_addr,
+                gossip_source_fingerprint)
+                VALUES (?,?,?,?,?,?,?,?,?,?,?,?,?,?)
                 ON CONFLICT (addr)
                 DO UPDATE SET
                   last_seen = excluded.last_seen,
@@ -470,7 +512,9 @@ pub async fn save_to_db(&self, sql: &Sql) -> Result<()> {
                   gossip_key_fingerprint = excluded.gossip_key_fingerprint,
                   verified_key = excluded.verified_key,
                   verified_key_fingerprint = excluded.verified_key_fingerprint,
-                  verifier = excluded.verifier",
+                  verifier = excluded.verifier,
+                  gossip_source_addr = excluded.gossip_source_addr,
+                  gossip_source_fingerprint = excluded.gossip_source_fingerprint",
             (
                 self.last_seen,
                 self.last_seen_autocrypt,
@@ -484,6 +528,8 @@ pub async fn save_to_db(&self, sql: &Sql) -> Result<()> {
                 self.verified_key_fingerprint.as_ref().map(|fp| fp.hex()),
                 &self.addr,
                 self.verifier.as_deref().unwrap_or(""),
+                self.gossip_source_addr.as_deref(),
+                self.gossip_source_fingerprint.as_ref().map(|fp| fp.hex()),
             ),
         )
         .await?;
@@ -562,10 +608,23 @@ async fn handle_setup_change(
 
             if let PeerstateChange::Aeap(new_addr) = &change {
                 let chat = Chat::load_from_db(context, *chat_id).await?;
+                let mut migration = AeapMigration {
+                    id: 0,
+                    timestamp,
+                    chat_id: *chat_id,
+                    old_contact_id: contact_id,
+                    new_contact_id: None,
+                    old_addr: self.addr.clone(),
+                    new_addr: new_addr.clone(),
+                    state: AeapMigrationState::SkippedUnsupportedChat,
+                    new_contact_was_member: false,
+                };
 
                 if chat.typ == Chattype::Group && !chat.is_protected() {
                     // Don't add an info_msg to the group, in order not to make the user think
                     // that the address was automatically replaced in the group.
+                    migration.state = AeapMigrationState::SkippedUnprotectedGroup;
+                    migration.insert(context).await?;
                     continue;
                 }
 
@@ -585,10 +644,14 @@ async fn handle_setup_change(
                                 Origin::IncomingUnknownFrom,
                             )
                             .await?;
+                            migration.new_contact_was_member =
+                                chat::is_contact_in_chat(context, *chat_id, new_contact_id).await?;
                             chat::remove_from_chat_contacts_table(context, *chat_id, contact_id)
                                 .await?;
                             chat::add_to_chat_contacts_table(context, *chat_id, &[new_contact_id])
                                 .await?;
+                            migration.new_contact_id = Some(new_contact_id);
+                            migration.state = AeapMigrationState::Migrated;
 
                             context.emit_event(EventType::ChatModified(*chat_id));
                         }
@@ -598,10 +661,14 @@ async fn handle_setup_change(
                                 "New address {:?} is not valid, not doing AEAP: {:#}.",
                                 new_addr,
                                 err
-                            )
+                            );
+                            migration.state = AeapMigrationState::SkippedInvalidAddress;
                         }
                     }
+                } else if chat.typ == Chattype::Single {
+                    migration.state = AeapMigrationState::SkippedSingleChat;
                 }
+                migration.insert(context).await?;
             }
 
             chat::add_info_msg_with_cmd(
@@ -747,6 +814,8 @@ async fn test_peerstate_save_to_db() {
             gossip_key: Some(pub_key.clone()),
             gossip_timestamp: 12,
             gossip_key_fingerprint: Some(pub_key.fingerprint()),
+            gossip_source_addr: Some("bob@example.net".to_string()),
+            gossip_source_fingerprint: Some(pub_key.fingerprint()),
             verified_key: Some(pub_key.clone()),
             verified_key_fingerprint: Some(pub_key.fingerprint()),
             fingerprint_changed: false,
@@ -787,6 +856,8 @@ async fn test_peerstate_double_create() {
             gossip_key: None,
             gossip_timestamp: 12,
             gossip_key_fingerprint: None,
+            gossip_source_addr: None,
+            gossip_source_fingerprint: None,
             verified_key: None,
             verified_key_fingerprint: None,
             fingerprint_changed: false,
@@ -820,6 +891,8 @@ async fn test_peerstate_with_empty_gossip_key_save_to_db() {
             gossip_key: None,
             gossip_timestamp: 12,
             gossip_key_fingerprint: None,
+            gossip_source_addr: None,
+            gossip_source_fingerprint: None,
             verified_key: None,
             verified_key_fingerprint: None,
             fingerprint_changed: false,
@@ -883,6 +956,8 @@ async fn test_peerstate_degrade_reordering() {
             gossip_key: None,
             gossip_timestamp: 0,
             gossip_key_fingerprint: None,
+            gossip_source_addr: None,
+            gossip_source_fingerprint: None,
             verified_key: None,
             verified_key_fingerprint: None,
             fingerprint_changed: false,
@@ -277,6 +277,39 @@ pub fn pk_calc_signature(
     Ok(signature)
 }
 
+/// Creates a signed, not encrypted message containing `plain`.
+pub fn pk_sign(plain: &[u8], private_key_for_signing: &SignedSecretKey) -> Result<String> {
+    let msg = Message::new_literal_bytes("", plain).sign(
+        private_key_for_signing,
+        || "".into(),
+        Default::default(),
+    )?;
+    let encoded_msg = msg.to_armored_string(None)?;
+    Ok(encoded_msg)
+}
+
+/// Returns the content of a message created with [`pk_sign`]
+/// and the fingerprints of all keys from the `public_keys_for_validation` keyring
+/// that have valid signatures there.
+pub fn pk_verify(
+    signed: &[u8],
+    public_keys_for_validation: &Keyring<SignedPublicKey>,
+) -> Result<(Vec<u8>, HashSet<Fingerprint>)> {
+    let (msg, _) = Message::from_armor_single(Cursor::new(signed))?;
+    let msg = msg.decompress()?;
+
+    let mut fingerprints: HashSet<Fingerprint> = Default::default();
+    if let signed_msg @ pgp::composed::Message::Signed { .. } = &msg {
+        for pkey in public_keys_for_validation.keys() {
+            if signed_msg.verify(&pkey.primary_key).is_ok() {
+                fingerprints.insert(DcKey::fingerprint(pkey));
+            }
+        }
+    }
+    let content = msg.get_content()?.context("The message is empty")?;
+    Ok((content, fingerprints))
+}
+
 /// Decrypts the message with keys from the private key keyring.
 ///
 /// Receiver private keys are provided in
@@ -601,4 +634,16 @@ async fn test_decrypt_unsigned() {
         assert_eq!(plain, CLEARTEXT);
         assert_eq!(valid_signatures.len(), 0);
     }
+
+    #[test]
+    fn test_sign_verify() {
+        let signed = pk_sign(CLEARTEXT, &KEYS.alice_secret).unwrap();
+        let mut keyring = Keyring::new();
+        keyring.add(KEYS.alice_public.clone());
+        keyring.add(KEYS.bob_public.clone());
+        let (plain, valid_signatures) = pk_verify(signed.as_bytes(), &keyring).unwrap();
+        assert_eq!(plain, CLEARTEXT);
+        assert_eq!(valid_signatures.len(), 1);
+        assert!(valid_signatures.contains(&DcKey::fingerprint(&KEYS.alice_public)));
+    }
 }
@@ -145,8 +145,19 @@ pub struct ProviderOptions {
     /// Maximum number of recipients the provider allows to send a single email to.
     pub max_smtp_rcpt_to: Option<u16>,
 
+    /// Maximum size of a single email in bytes the provider accepts.
+    pub max_msg_size: Option<u64>,
+
     /// Move messages to the Trash folder instead of marking them "\Deleted".
     pub delete_to_trash: bool,
+
+    /// True if IMAP folders are labels, like on Gmail.
+    ///
+    /// The same message is then shown in every folder it is labeled with,
+    /// user-defined labels are not scanned for messages
+    /// and messages are not moved out of the Sent folder,
+    /// as this only adds a label.
+    pub imap_labels: bool,
 }
 
 impl Default for ProviderOptions {
@@ -154,7 +165,9 @@ fn default() -> Self {
         Self {
             strict_tls: true,
             max_smtp_rcpt_to: None,
+            max_msg_size: None,
             delete_to_trash: false,
+            imap_labels: false,
         }
     }
 }
@@ -0,0 +1,113 @@
+//! # Push notification token registration.
+//!
+//! Mobile builds without a permanent background connection can register
+//! a push notification token, e.g. of a notification proxy service.
+//! The token is stored on the IMAP server using the METADATA extension
+//! if the server announces the `XDELTAPUSH` capability,
+//! so the server can wake up the device when new messages arrive.
+//! On wake-up the UI should call `dc_accounts_maybe_network()`
+//! to let the core fetch new messages.
+
+use anyhow::{ensure, Context as _, Result};
+
+use crate::config::Config;
+use crate::context::Context;
+use crate::imap::Imap;
+use crate::scheduler::InterruptInfo;
+
+/// Server annotation under which the push notification token is stored.
+const DEVICE_TOKEN_ENTRY: &str = "/private/devicetoken";
+
+impl Context {
+    /// Sets the push notification token of this device.
+    ///
+    /// The token is registered on the server in the background
+    /// as soon as the server supports it.
+    /// An empty token unregisters the device from push notifications.
+    pub async fn set_push_device_token(&self, token: &str) -> Result<()> {
+        ensure!(
+            !token.contains(|c: char| c == '"' || c == '\\' || c.is_control()),
+            "Invalid push device token"
+        );
+        let token = Some(token).filter(|token| !token.is_empty());
+        self.set_config(Config::PushDeviceToken, token).await?;
+        self.scheduler
+            .interrupt_inbox(InterruptInfo::new(false))
+            .await;
+        Ok(())
+    }
+
+    /// Returns true if the current push notification token is registered on the server.
+    pub async fn is_push_registered(&self) -> Result<bool> {
+        let token = self.get_config(Config::PushDeviceToken).await?;
+        let registered = self.get_config(Config::RegisteredPushDeviceToken).await?;
+        Ok(token.is_some() && token == registered)
+    }
+
+    /// Registers the push notification token on the server if it was changed.
+    ///
+    /// Does nothing if the server does not support push notifications.
+    pub(crate) async fn maybe_register_push_token(&self, imap: &mut Imap) -> Result<()> {
+        let token = self.get_config(Config::PushDeviceToken).await?;
+        let registered = self.get_config(Config::RegisteredPushDeviceToken).await?;
+        if token == registered {
+            return Ok(());
+        }
+
+        if let Err(err) = imap.prepare(self).await {
+            warn!(self, "could not connect: {:#}", err);
+            return Ok(());
+        }
+        let session = imap.session.as_mut().context("no session")?;
+        if !session.can_push() || !session.can_metadata() {
+            return Ok(());
+        }
+
+        let value = match &token {
+            Some(token) => format!("\"{token}\""),
+            None => "NIL".to_string(),
+        };
+        session
+            .run_command_and_check_ok(
+                &format!("SETMETADATA \"\" ({DEVICE_TOKEN_ENTRY} {value})"),
+                None,
+            )
+            .await
+            .context("failed to register push device token")?;
+        self.set_config(Config::RegisteredPushDeviceToken, token.as_deref())
+            .await?;
+        info!(self, "Push device token registered.");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestContext;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_set_push_device_token() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        assert!(!t.is_push_registered().await?);
+
+        assert!(t.set_push_device_token("foo\"bar").await.is_err());
+        assert!(t.set_push_device_token("foo\nbar").await.is_err());
+
+        t.set_push_device_token("foobar").await?;
+        assert_eq!(
+            t.get_config(Config::PushDeviceToken).await?,
+            Some("foobar".to_string())
+        );
+        assert!(!t.is_push_registered().await?);
+
+        t.set_config(Config::RegisteredPushDeviceToken, Some("foobar"))
+            .await?;
+        assert!(t.is_push_registered().await?);
+
+        t.set_push_device_token("").await?;
+        assert_eq!(t.get_config(Config::PushDeviceToken).await?, None);
+        assert!(!t.is_push_registered().await?);
+        Ok(())
+    }
+}
@@ -1,15 +1,18 @@
 //! # QR code module.
 
 mod dclogin_scheme;
+mod dcprofile_scheme;
 use std::collections::BTreeMap;
 
 use anyhow::{anyhow, bail, ensure, Context as _, Result};
 pub use dclogin_scheme::LoginOptions;
+pub use dcprofile_scheme::{ProfileContact, ProfileGroup, ProvisioningProfile};
 use once_cell::sync::Lazy;
 use percent_encoding::percent_decode_str;
 use serde::Deserialize;
 
 use self::dclogin_scheme::configure_from_login_qr;
+use self::dcprofile_scheme::configure_from_profile;
 use crate::chat::{get_chat_id_by_grpid, ChatIdBlocked};
 use crate::config::Config;
 use crate::constants::Blocked;
@@ -26,6 +29,7 @@
 const OPENPGP4FPR_SCHEME: &str = "OPENPGP4FPR:"; // yes: uppercase
 const DCACCOUNT_SCHEME: &str = "DCACCOUNT:";
 pub(super) const DCLOGIN_SCHEME: &str = "DCLOGIN:";
+pub(super) const DCPROFILE_SCHEME: &str = "DCPROFILE:";
 const DCWEBRTC_SCHEME: &str = "DCWEBRTC:";
 const MAILTO_SCHEME: &str = "mailto:";
 const MATMSG_SCHEME: &str = "MATMSG:";
@@ -235,6 +239,17 @@ pub enum Qr {
         /// Login parameters.
         options: LoginOptions,
     },
+
+    /// `DCPROFILE:` account provisioning profile.
+    ///
+    /// Ask the user if they want to set up the account with the email address.
+    Profile {
+        /// Email address.
+        address: String,
+
+        /// Server settings, display name, avatar and initial contacts and groups.
+        profile: ProvisioningProfile,
+    },
 }
 
 fn starts_with_ignore_case(string: &str, pattern: &str) -> bool {
@@ -256,6 +271,8 @@ pub async fn check_qr(context: &Context, qr: &str) -> Result<Qr> {
         decode_account(qr)?
     } else if starts_with_ignore_case(qr, DCLOGIN_SCHEME) {
         dclogin_scheme::decode_login(qr)?
+    } else if starts_with_ignore_case(qr, DCPROFILE_SCHEME) {
+        dcprofile_scheme::decode_profile(qr)?
     } else if starts_with_ignore_case(qr, DCWEBRTC_SCHEME) {
         decode_webrtc_instance(context, qr)?
     } else if starts_with_ignore_case(qr, DCBACKUP_SCHEME) {
@@ -631,12 +648,30 @@ pub async fn set_config_from_qr(context: &Context, qr: &str) -> Result<()> {
         Qr::Login { address, options } => {
             configure_from_login_qr(context, &address, options).await?
         }
+        Qr::Profile { address, profile } => {
+            configure_from_profile(context, &address, profile).await?;
+        }
         _ => bail!("qr code {:?} does not contain config", qr),
     }
 
     Ok(())
 }
 
+/// Applies a `DCPROFILE:` account provisioning QR code.
+///
+/// The server settings and the display name are applied atomically,
+/// an error is returned if they cannot be applied.
+/// Returns the descriptions of the optional parts which could not be applied,
+/// i.e. the avatar, contacts and groups.
+pub async fn set_profile_from_qr(context: &Context, qr: &str) -> Result<Vec<String>> {
+    match check_qr(context, qr).await? {
+        Qr::Profile { address, profile } => {
+            configure_from_profile(context, &address, profile).await
+        }
+        _ => bail!("QR code {:?} is not a provisioning profile", qr),
+    }
+}
+
 /// Extract address for the mailto scheme.
 ///
 /// Scheme: `mailto:addr...?subject=...&body=..`
@@ -1049,6 +1084,8 @@ async fn test_decode_openpgp_fingerprint() -> Result<()> {
             gossip_key: None,
             gossip_timestamp: 0,
             gossip_key_fingerprint: None,
+            gossip_source_addr: None,
+            gossip_source_fingerprint: None,
             verified_key: None,
             verified_key_fingerprint: None,
             fingerprint_changed: false,
@@ -1290,6 +1327,24 @@ async fn test_decode_and_apply_dclogin_advanced_options() -> Result<()> {
             Some("3".to_owned()) // plain
         );
 
+        set_config_from_qr(
+            &ctx.ctx,
+            "dclogin:username@host?p=1234&v=1&ph=proxy.host&pp=1080&fs=Gesendet&b=1&m=0",
+        )
+        .await?;
+        assert!(ctx.ctx.get_config_bool(Config::Socks5Enabled).await?);
+        assert_eq!(
+            ctx.ctx.get_config(Config::Socks5Host).await?,
+            Some("proxy.host".to_owned())
+        );
+        assert_eq!(ctx.ctx.get_config_int(Config::Socks5Port).await?, 1080);
+        assert_eq!(
+            ctx.ctx.get_config(Config::SentboxFolder).await?,
+            Some("Gesendet".to_owned())
+        );
+        assert!(ctx.ctx.get_config_bool(Config::BccSelf).await?);
+        assert!(!ctx.ctx.get_config_bool(Config::MvboxMove).await?);
+
         Ok(())
     }
 
@@ -57,6 +57,33 @@ pub enum LoginOptions {
 
         /// SMTP certificate checks.
         smtp_certificate_checks: Option<CertificateChecks>,
+
+        /// SOCKS5 proxy host, enables the proxy if set.
+        proxy_host: Option<String>,
+
+        /// SOCKS5 proxy port.
+        proxy_port: Option<u16>,
+
+        /// SOCKS5 proxy username.
+        proxy_username: Option<String>,
+
+        /// SOCKS5 proxy password.
+        proxy_password: Option<String>,
+
+        /// Folder for chat messages instead of the "DeltaChat" folder.
+        mvbox_folder: Option<String>,
+
+        /// "Sent" folder.
+        sentbox_folder: Option<String>,
+
+        /// "Trash" folder.
+        trash_folder: Option<String>,
+
+        /// Whether to send a copy of outgoing messages to self.
+        bcc_self: Option<bool>,
+
+        /// Whether to move chat messages to the "DeltaChat" folder.
+        mvbox_move: Option<bool>,
     },
 }
 
@@ -113,6 +140,17 @@ pub(super) fn decode_login(qr: &str) -> Result<Qr> {
                 smtp_password: parameter_map.get("spw").map(|s| s.to_owned()),
                 smtp_security: parse_socket_security(parameter_map.get("ss"))?,
                 smtp_certificate_checks: parse_certificate_checks(parameter_map.get("sc"))?,
+                proxy_host: parameter_map.get("ph").map(|s| s.to_owned()),
+                proxy_port: parse_port(parameter_map.get("pp"))
+                    .context("could not parse proxy port")?,
+                proxy_username: parameter_map.get("pu").map(|s| s.to_owned()),
+                proxy_password: parameter_map.get("ppw").map(|s| s.to_owned()),
+                mvbox_folder: parameter_map.get("fm").map(|s| s.to_owned()),
+                sentbox_folder: parameter_map.get("fs").map(|s| s.to_owned()),
+                trash_folder: parameter_map.get("ft").map(|s| s.to_owned()),
+                bcc_self: parse_flag(parameter_map.get("b")).context("could not parse bcc_self")?,
+                mvbox_move: parse_flag(parameter_map.get("m"))
+                    .context("could not parse mvbox_move")?,
             },
             Some(Ok(v)) => LoginOptions::UnsuportedVersion(v),
             Some(Err(_)) => bail!("version could not be parsed as number E6"),
@@ -135,6 +173,15 @@ fn parse_port(port: Option<&String>) -> core::result::Result<Option<u16>, std::n
     }
 }
 
+fn parse_flag(flag: Option<&String>) -> Result<Option<bool>> {
+    Ok(match flag.map(|s| s.as_str()) {
+        Some("0") => Some(false),
+        Some("1") => Some(true),
+        Some(other) => bail!("Unknown flag value: {}", other),
+        None => None,
+    })
+}
+
 fn parse_socket_security(security: Option<&String>) -> Result<Option<Socket>> {
     Ok(match security.map(|s| s.as_str()) {
         Some("ssl") => Some(Socket::Ssl),
@@ -180,6 +227,15 @@ pub(crate) async fn configure_from_login_qr(
             smtp_password,
             smtp_security,
             smtp_certificate_checks,
+            proxy_host,
+            proxy_port,
+            proxy_username,
+            proxy_password,
+            mvbox_folder,
+            sentbox_folder,
+            trash_folder,
+            bcc_self,
+            mvbox_move,
         } => {
             context.set_config(Config::MailPw, Some(&mail_pw)).await?;
             if let Some(value) = imap_host {
@@ -242,6 +298,44 @@ pub(crate) async fn configure_from_login_qr(
                     .set_config(Config::SmtpCertificateChecks, Some(&code.to_string()))
                     .await?;
             }
+            if let Some(value) = proxy_host {
+                context.set_config(Config::Socks5Host, Some(&value)).await?;
+                context.set_config_bool(Config::Socks5Enabled, true).await?;
+            }
+            if let Some(value) = proxy_port {
+                context
+                    .set_config(Config::Socks5Port, Some(&value.to_string()))
+                    .await?;
+            }
+            if let Some(value) = proxy_username {
+                context.set_config(Config::Socks5User, Some(&value)).await?;
+            }
+            if let Some(value) = proxy_password {
+                context
+                    .set_config(Config::Socks5Password, Some(&value))
+                    .await?;
+            }
+            if let Some(value) = mvbox_folder {
+                context
+                    .set_config(Config::MvboxFolder, Some(&value))
+                    .await?;
+            }
+            if let Some(value) = sentbox_folder {
+                context
+                    .set_config(Config::SentboxFolder, Some(&value))
+                    .await?;
+            }
+            if let Some(value) = trash_folder {
+                context
+                    .set_config(Config::TrashFolder, Some(&value))
+                    .await?;
+            }
+            if let Some(value) = bcc_self {
+                context.set_config_bool(Config::BccSelf, value).await?;
+            }
+            if let Some(value) = mvbox_move {
+                context.set_config_bool(Config::MvboxMove, value).await?;
+            }
             Ok(())
         }
         _ => bail!(
@@ -273,6 +367,15 @@ macro_rules! login_options_just_pw {
                 smtp_password: None,
                 smtp_security: None,
                 smtp_certificate_checks: None,
+                proxy_host: None,
+                proxy_port: None,
+                proxy_username: None,
+                proxy_password: None,
+                mvbox_folder: None,
+                sentbox_folder: None,
+                trash_folder: None,
+                bcc_self: None,
+                mvbox_move: None,
             }
         };
     }
@@ -381,6 +484,15 @@ fn all_advanced_options() -> anyhow::Result<()> {
                     smtp_password: Some("3242HS".to_owned()),
                     smtp_security: Some(Socket::Plain),
                     smtp_certificate_checks: Some(CertificateChecks::AcceptInvalidCertificates),
+                    proxy_host: None,
+                    proxy_port: None,
+                    proxy_username: None,
+                    proxy_password: None,
+                    mvbox_folder: None,
+                    sentbox_folder: None,
+                    trash_folder: None,
+                    bcc_self: None,
+                    mvbox_move: None,
                 }
             );
         } else {
@@ -389,6 +501,46 @@ fn all_advanced_options() -> anyhow::Result<()> {
         Ok(())
     }
 
+    #[test]
+    fn proxy_folder_and_flag_options() -> anyhow::Result<()> {
+        let result = decode_login(
+            "dclogin:email@host.tld?p=secret&v=1&ph=127.0.0.1&pp=9050&pu=tor&ppw=onion&fm=Chats&fs=Gesendet&ft=Papierkorb&b=1&m=0",
+        )?;
+        if let Qr::Login { options, .. } = result {
+            if let LoginOptions::V1 {
+                proxy_host,
+                proxy_port,
+                proxy_username,
+                proxy_password,
+                mvbox_folder,
+                sentbox_folder,
+                trash_folder,
+                bcc_self,
+                mvbox_move,
+                ..
+            } = options
+            {
+                assert_eq!(proxy_host.as_deref(), Some("127.0.0.1"));
+                assert_eq!(proxy_port, Some(9050));
+                assert_eq!(proxy_username.as_deref(), Some("tor"));
+                assert_eq!(proxy_password.as_deref(), Some("onion"));
+                assert_eq!(mvbox_folder.as_deref(), Some("Chats"));
+                assert_eq!(sentbox_folder.as_deref(), Some("Gesendet"));
+                assert_eq!(trash_folder.as_deref(), Some("Papierkorb"));
+                assert_eq!(bcc_self, Some(true));
+                assert_eq!(mvbox_move, Some(false));
+            } else {
+                bail!("wrong type")
+            }
+        } else {
+            bail!("wrong type")
+        }
+
+        assert!(decode_login("dclogin:email@host.tld?p=secret&v=1&b=yes").is_err());
+        assert!(decode_login("dclogin:email@host.tld?p=secret&v=1&pp=port").is_err());
+        Ok(())
+    }
+
     #[test]
     fn uri_encoded_password() -> anyhow::Result<()> {
         let result = decode_login(
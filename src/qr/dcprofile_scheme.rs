@@ -0,0 +1,365 @@
+//! `DCPROFILE:` scheme for provisioning accounts.
+//!
+//! A provisioning profile carries the server settings of an account as a `dclogin:` URI
+//! together with the display name, avatar and initial contacts and groups,
+//! so organizations can hand out complete accounts with a single QR code.
+//!
+//! The payload is a JSON object, e.g.
+//! `DCPROFILE:{"v":1,"login":"dclogin:alice@example.org?p=secret&v=1","name":"Alice",
+//! "contacts":[{"addr":"bob@example.org","name":"Bob"}],
+//! "groups":[{"name":"Team","members":["bob@example.org"]}]}`.
+
+use std::time::Duration;
+
+use anyhow::{bail, ensure, Context as _, Result};
+use serde::Deserialize;
+
+use super::dclogin_scheme::{configure_from_login_qr, decode_login};
+use super::{LoginOptions, Qr, DCPROFILE_SCHEME};
+use crate::blob::BlobObject;
+use crate::chat::{self, GroupParams};
+use crate::config::Config;
+use crate::contact::{Contact, ContactId, Origin};
+use crate::context::Context;
+use crate::socks::Socks5Config;
+
+/// Maximum size of the avatar downloaded by [`set_avatar_from_url`].
+const MAX_AVATAR_BYTES: usize = 5 * 1024 * 1024;
+
+/// Timeout for downloading the avatar.
+const AVATAR_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Configuration keys written when applying the server settings and the display name.
+const PROFILE_CONFIG_KEYS: [Config; 23] = [
+    Config::Addr,
+    Config::MailPw,
+    Config::MailServer,
+    Config::MailPort,
+    Config::MailUser,
+    Config::MailSecurity,
+    Config::ImapCertificateChecks,
+    Config::SendServer,
+    Config::SendPort,
+    Config::SendUser,
+    Config::SendPw,
+    Config::SendSecurity,
+    Config::SmtpCertificateChecks,
+    Config::Socks5Enabled,
+    Config::Socks5Host,
+    Config::Socks5Port,
+    Config::Socks5User,
+    Config::Socks5Password,
+    Config::MvboxFolder,
+    Config::SentboxFolder,
+    Config::TrashFolder,
+    Config::BccSelf,
+    Config::MvboxMove,
+    Config::Displayname,
+];
+
+/// Account provisioning profile of the `DCPROFILE:` scheme.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvisioningProfile {
+    /// Server settings.
+    pub login: LoginOptions,
+
+    /// Display name.
+    pub display_name: Option<String>,
+
+    /// URL of the avatar, downloaded when the profile is applied.
+    pub avatar_url: Option<String>,
+
+    /// Contacts to add.
+    pub contacts: Vec<ProfileContact>,
+
+    /// Groups to create.
+    pub groups: Vec<ProfileGroup>,
+}
+
+/// Contact of a [`ProvisioningProfile`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ProfileContact {
+    /// Email address.
+    pub addr: String,
+
+    /// Name, may be empty.
+    #[serde(default)]
+    pub name: String,
+}
+
+/// Group of a [`ProvisioningProfile`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ProfileGroup {
+    /// Name of the group.
+    pub name: String,
+
+    /// Email addresses of the members in addition to ourself.
+    #[serde(default)]
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfilePayload {
+    login: String,
+    name: Option<String>,
+    avatar: Option<String>,
+    #[serde(default)]
+    contacts: Vec<ProfileContact>,
+    #[serde(default)]
+    groups: Vec<ProfileGroup>,
+}
+
+/// scheme: `DCPROFILE:{"v":1,"login":"dclogin:...",...}`
+pub(super) fn decode_profile(qr: &str) -> Result<Qr> {
+    let payload = qr
+        .get(DCPROFILE_SCHEME.len()..)
+        .context("invalid DCPROFILE payload")?;
+    let value: serde_json::Value =
+        serde_json::from_str(payload).context("invalid DCPROFILE payload: malformed JSON")?;
+    match value.get("v").and_then(|v| v.as_u64()) {
+        Some(1) => {}
+        Some(_) => bail!(
+            "DeltaChat does not understand this QR Code yet, please update the app and try again."
+        ),
+        None => bail!("invalid DCPROFILE payload: version missing"),
+    }
+    let payload: ProfilePayload =
+        serde_json::from_value(value).context("invalid DCPROFILE payload")?;
+
+    let (address, login) = match decode_login(&payload.login)? {
+        Qr::Login { address, options } => (address, options),
+        _ => bail!("invalid DCPROFILE payload: bad login"),
+    };
+    Ok(Qr::Profile {
+        address,
+        profile: ProvisioningProfile {
+            login,
+            display_name: payload.name.filter(|name| !name.is_empty()),
+            avatar_url: payload.avatar.filter(|url| !url.is_empty()),
+            contacts: payload.contacts,
+            groups: payload.groups,
+        },
+    })
+}
+
+/// Applies a provisioning profile to an unconfigured account.
+///
+/// The server settings and the display name are applied atomically:
+/// if one of them fails, the previous values are restored and an error is returned.
+/// The avatar, contacts and groups are applied afterwards;
+/// the descriptions of the parts which failed are returned.
+pub(crate) async fn configure_from_profile(
+    context: &Context,
+    address: &str,
+    profile: ProvisioningProfile,
+) -> Result<Vec<String>> {
+    ensure!(
+        !context.is_configured().await?,
+        "Cannot apply a provisioning profile to a configured account"
+    );
+
+    let mut old_values = Vec::new();
+    for key in PROFILE_CONFIG_KEYS {
+        old_values.push((key, context.sql.get_raw_config(key.as_ref()).await?));
+    }
+    if let Err(err) = apply_settings(context, address, &profile).await {
+        for (key, value) in old_values {
+            context
+                .sql
+                .set_raw_config(key.as_ref(), value.as_deref())
+                .await?;
+        }
+        return Err(err.context("Failed to apply provisioning profile"));
+    }
+
+    let mut failed = Vec::new();
+    if let Some(url) = &profile.avatar_url {
+        if let Err(err) = set_avatar_from_url(context, url).await {
+            failed.push(format!("Avatar {url}: {err:#}"));
+        }
+    }
+    for contact in &profile.contacts {
+        if let Err(err) = Contact::create(context, &contact.name, &contact.addr).await {
+            failed.push(format!("Contact {}: {err:#}", contact.addr));
+        }
+    }
+    for group in &profile.groups {
+        if let Err(err) = create_group(context, group).await {
+            failed.push(format!("Group {}: {err:#}", group.name));
+        }
+    }
+    for failure in &failed {
+        warn!(context, "Provisioning profile: {failure}.");
+    }
+    Ok(failed)
+}
+
+async fn apply_settings(
+    context: &Context,
+    address: &str,
+    profile: &ProvisioningProfile,
+) -> Result<()> {
+    configure_from_login_qr(context, address, profile.login.clone()).await?;
+    if let Some(name) = &profile.display_name {
+        context.set_config(Config::Displayname, Some(name)).await?;
+    }
+    Ok(())
+}
+
+/// Downloads at most [`MAX_AVATAR_BYTES`] from the HTTPS `url` and sets it as avatar.
+async fn set_avatar_from_url(context: &Context, url: &str) -> Result<()> {
+    let parsed = url::Url::parse(url).context("Invalid avatar URL")?;
+    ensure!(parsed.scheme() == "https", "Avatar URL must use HTTPS");
+    let socks5_config = Socks5Config::from_database(&context.sql).await?;
+    let mut response = crate::http::get_client(socks5_config)?
+        .get(parsed)
+        .timeout(AVATAR_TIMEOUT)
+        .send()
+        .await
+        .context("Avatar download failed")?
+        .error_for_status()?;
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        ensure!(
+            data.len() + chunk.len() <= MAX_AVATAR_BYTES,
+            "Avatar exceeds {MAX_AVATAR_BYTES} bytes"
+        );
+        data.extend_from_slice(&chunk);
+    }
+    let blob = BlobObject::create(context, "avatar", &data).await?;
+    context
+        .set_config(
+            Config::Selfavatar,
+            Some(&blob.to_abs_path().to_string_lossy()),
+        )
+        .await
+}
+
+async fn create_group(context: &Context, group: &ProfileGroup) -> Result<()> {
+    let mut members = Vec::new();
+    for addr in &group.members {
+        let contact_id = match Contact::lookup_id_by_addr(context, addr, Origin::Unknown).await? {
+            Some(contact_id) => contact_id,
+            None => Contact::create(context, "", addr).await?,
+        };
+        if contact_id != ContactId::SELF {
+            members.push(contact_id);
+        }
+    }
+    chat::create_group_chat_ex(
+        context,
+        GroupParams {
+            name: group.name.clone(),
+            members,
+            ..Default::default()
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::{get_chat_contacts, Chat};
+    use crate::chatlist::Chatlist;
+    use crate::qr::{check_qr, set_config_from_qr};
+    use crate::test_utils::TestContext;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_decode_and_apply_profile() -> Result<()> {
+        let t = TestContext::new().await;
+        let qr = r#"DCPROFILE:{"v":1,"login":"dclogin:alice@example.org?p=secret&v=1&ih=imap.example.org","name":"Alice","contacts":[{"addr":"bob@example.net","name":"Bob"},{"addr":"invalid"}],"groups":[{"name":"Team","members":["bob@example.net","fiona@example.net"]}]}"#;
+
+        let profile = match check_qr(&t, qr).await? {
+            Qr::Profile { address, profile } => {
+                assert_eq!(address, "alice@example.org");
+                profile
+            }
+            _ => bail!("Wrong QR type, expected Profile"),
+        };
+        assert_eq!(profile.display_name.as_deref(), Some("Alice"));
+        assert_eq!(profile.contacts.len(), 2);
+
+        let failed = configure_from_profile(&t, "alice@example.org", profile).await?;
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].starts_with("Contact invalid"));
+        assert_eq!(
+            t.get_config(Config::Addr).await?.as_deref(),
+            Some("alice@example.org")
+        );
+        assert_eq!(
+            t.get_config(Config::MailServer).await?.as_deref(),
+            Some("imap.example.org")
+        );
+        assert_eq!(
+            t.get_config(Config::Displayname).await?.as_deref(),
+            Some("Alice")
+        );
+        let bob_id = Contact::lookup_id_by_addr(&t, "bob@example.net", Origin::Unknown)
+            .await?
+            .unwrap();
+        assert_eq!(
+            Contact::get_by_id(&t, bob_id).await?.get_display_name(),
+            "Bob"
+        );
+
+        let chats = Chatlist::try_load(&t, 0, Some("Team"), None).await?;
+        assert_eq!(chats.len(), 1);
+        let chat_id = chats.get_chat_id(0)?;
+        assert!(Chat::load_from_db(&t, chat_id).await?.is_unpromoted());
+        assert_eq!(get_chat_contacts(&t, chat_id).await?.len(), 3);
+
+        // Plain `set_config_from_qr()` refuses configured accounts.
+        t.set_config_bool(Config::Configured, true).await?;
+        assert!(set_config_from_qr(&t, qr).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_profile_settings_atomic() -> Result<()> {
+        let t = TestContext::new().await;
+        assert!(check_qr(
+            &t,
+            r#"DCPROFILE:{"v":1,"login":"dclogin:alice@example.org?v=1"}"#
+        )
+        .await
+        .is_err());
+        assert!(check_qr(&t, r#"DCPROFILE:{"v":2,"login":""}"#)
+            .await
+            .is_err());
+        assert!(check_qr(&t, "DCPROFILE:foo").await.is_err());
+
+        // The address is set before the unsupported login options fail,
+        // it must be restored together with the other settings.
+        t.set_config(Config::MailServer, Some("old.example.org"))
+            .await?;
+        let profile = ProvisioningProfile {
+            login: LoginOptions::UnsuportedVersion(2),
+            display_name: Some("Alice".to_string()),
+            avatar_url: None,
+            contacts: Vec::new(),
+            groups: Vec::new(),
+        };
+        assert!(configure_from_profile(&t, "alice@example.org", profile)
+            .await
+            .is_err());
+        assert_eq!(t.get_config(Config::Addr).await?, None);
+        assert_eq!(t.get_config(Config::Displayname).await?, None);
+        assert_eq!(
+            t.get_config(Config::MailServer).await?.as_deref(),
+            Some("old.example.org")
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_avatar_url_requires_https() -> Result<()> {
+        let t = TestContext::new().await;
+        for url in ["http://example.org/avatar.jpg", "file:///etc/passwd", "foo"] {
+            assert!(set_avatar_from_url(&t, url).await.is_err());
+        }
+        assert_eq!(t.get_config(Config::Selfavatar).await?, None);
+        Ok(())
+    }
+}
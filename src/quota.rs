@@ -12,7 +12,7 @@
 use crate::imap::scan_folders::get_watched_folders;
 use crate::imap::session::Session as ImapSession;
 use crate::imap::Imap;
-use crate::message::{Message, Viewtype};
+use crate::message::{DeviceAction, Message, Viewtype};
 use crate::scheduler::InterruptInfo;
 use crate::tools::time;
 use crate::{stock_str, EventType};
@@ -157,6 +157,7 @@ pub(crate) async fn update_recent_quota(&self, imap: &mut Imap) -> Result<()> {
                             .await?;
                         let mut msg = Message::new(Viewtype::Text);
                         msg.text = Some(stock_str::quota_exceeding(self, highest).await);
+                        msg.set_device_action(DeviceAction::ManageStorage);
                         add_device_msg_with_importance(self, None, Some(&mut msg), true).await?;
                     } else if highest <= QUOTA_ALLCLEAR_PERCENTAGE {
                         self.set_config(Config::QuotaExceeding, None).await?;
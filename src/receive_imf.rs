@@ -10,16 +10,20 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 
+use crate::blocklist;
 use crate::chat::{self, Chat, ChatId, ChatIdBlocked, ProtectionStatus};
 use crate::config::Config;
 use crate::constants::{Blocked, Chattype, ShowEmails, DC_CHAT_ID_TRASH};
 use crate::contact::{
-    may_be_valid_addr, normalize_name, Contact, ContactAddress, ContactId, Origin, VerifiedStatus,
+    addr_cmp, may_be_valid_addr, normalize_name, Contact, ContactAddress, ContactId, Origin,
+    VerifiedStatus,
 };
 use crate::context::Context;
 use crate::debug_logging::maybe_set_logging_xdc_inner;
 use crate::download::DownloadState;
-use crate::ephemeral::{stock_ephemeral_timer_changed, Timer as EphemeralTimer};
+use crate::ephemeral::{
+    quotes_expired_message, stock_ephemeral_timer_changed, Timer as EphemeralTimer,
+};
 use crate::events::EventType;
 use crate::headerdef::{HeaderDef, HeaderDefMap};
 use crate::imap::{markseen_on_imap_table, GENERATED_PREFIX};
@@ -40,7 +44,7 @@
 use crate::tools::{
     buf_compress, extract_grpid_from_rfc724_mid, smeared_time, strip_rtlo_characters,
 };
-use crate::{contact, imap};
+use crate::{calls, clock_skew, contact, imap, late_delivery, notification};
 
 /// This is the struct that is returned after receiving one email (aka MIME message).
 ///
@@ -78,6 +82,38 @@ pub async fn receive_imf(
     receive_imf_inner(context, &rfc724_mid, imf_raw, seen, None, false).await
 }
 
+/// Claim on a Message-ID which is being received.
+///
+/// The claim is released when the guard is dropped.
+struct ReceivingMsgGuard<'a> {
+    context: &'a Context,
+    rfc724_mid: String,
+}
+
+impl<'a> ReceivingMsgGuard<'a> {
+    /// Claims the Message-ID, returns `None` if it is claimed already.
+    fn new(context: &'a Context, rfc724_mid: &str) -> Option<Self> {
+        let mut receiving_msgs = context.receiving_msgs.lock().unwrap();
+        if !receiving_msgs.insert(rfc724_mid.to_string()) {
+            return None;
+        }
+        Some(Self {
+            context,
+            rfc724_mid: rfc724_mid.to_string(),
+        })
+    }
+}
+
+impl Drop for ReceivingMsgGuard<'_> {
+    fn drop(&mut self) {
+        self.context
+            .receiving_msgs
+            .lock()
+            .unwrap()
+            .remove(&self.rfc724_mid);
+    }
+}
+
 /// Receive a message and add it to the database.
 ///
 /// Returns an error on database failure or if the message is broken,
@@ -146,6 +182,19 @@ pub(crate) async fn receive_imf_inner(
 
     info!(context, "Received message has Message-Id: {rfc724_mid}");
 
+    // Another IMAP loop may be receiving the same message from a different folder right now.
+    // The claim is held until the message is added to the database.
+    let _receiving_guard = match ReceivingMsgGuard::new(context, rfc724_mid) {
+        Some(guard) => guard,
+        None => {
+            info!(
+                context,
+                "Message {rfc724_mid} is being received from another folder, doing nothing."
+            );
+            return Ok(None);
+        }
+    };
+
     // check, if the mail is already in our database.
     // make sure, this check is done eg. before securejoin-processing.
     let replace_partial_download =
@@ -161,12 +210,26 @@ pub(crate) async fn receive_imf_inner(
             } else {
                 // the message was probably moved around.
                 info!(context, "Message already in DB, doing nothing.");
+                if !fetching_existing_messages {
+                    let sent_here = msg.from_id == ContactId::SELF && msg.timestamp_rcvd == 0;
+                    clock_skew::observe_received_msg(context, &mime_parser, sent_here)
+                        .await
+                        .log_err(context)
+                        .ok();
+                }
                 return Ok(None);
             }
         } else {
             None
         };
 
+    if !fetching_existing_messages {
+        clock_skew::observe_received_msg(context, &mime_parser, false)
+            .await
+            .log_err(context)
+            .ok();
+    }
+
     let prevent_rename =
         mime_parser.is_mailinglist_message() || mime_parser.get_header(HeaderDef::Sender).is_some();
 
@@ -214,6 +277,24 @@ pub(crate) async fn receive_imf_inner(
         .and_then(|value| mailparse::dateparse(value).ok())
         .map_or(rcvd_timestamp, |value| min(value, rcvd_timestamp + 60));
 
+    // Partially downloaded messages were already checked for late delivery.
+    let delivery_delay = if replace_partial_download.is_none() {
+        late_delivery::get_delivery_delay(&mime_parser)
+    } else {
+        None
+    };
+    if let Some(delay) = delivery_delay {
+        for part in &mut mime_parser.parts {
+            part.param.set_i64(Param::DeliveryDelay, delay);
+        }
+    }
+    let dkim_results = &mime_parser.decryption_info.dkim_results;
+    if incoming && !dkim_results.dkim_passed && dkim_results.dkim_should_work {
+        for part in &mut mime_parser.parts {
+            part.param.set_int(Param::DkimFailed, 1);
+        }
+    }
+
     // Add parts
     let received_msg = add_parts(
         context,
@@ -234,7 +315,23 @@ pub(crate) async fn receive_imf_inner(
     .await
     .context("add_parts error")?;
 
-    if !from_id.is_special() {
+    if let Some(delay) = delivery_delay {
+        info!(
+            context,
+            "Message {rfc724_mid} was delivered {delay} seconds late."
+        );
+        late_delivery::add_late_delivery(context, &mime_parser.from.addr, sent_timestamp, delay)
+            .await
+            .log_err(context)
+            .ok();
+    }
+
+    if !from_id.is_special()
+        && mime_parser
+            .get_header(HeaderDef::ChatLastSeen)
+            .map(|s| s.as_str())
+            != Some("hidden")
+    {
         contact::update_last_seen(context, from_id, sent_timestamp).await?;
     }
 
@@ -270,7 +367,15 @@ pub(crate) async fn receive_imf_inner(
     if let Some(ref sync_items) = mime_parser.sync_items {
         if from_id == ContactId::SELF {
             if mime_parser.was_encrypted() {
-                if let Err(err) = context.execute_sync_items(sync_items).await {
+                let device_id = mime_parser
+                    .get_header(HeaderDef::ChatDeviceId)
+                    .map(|s| s.as_str());
+                if !context.own_device_seen(device_id, sent_timestamp).await? {
+                    warn!(
+                        context,
+                        "Ignoring sync items from revoked or unknown device."
+                    );
+                } else if let Err(err) = context.execute_sync_items(sync_items).await {
                     warn!(context, "receive_imf cannot execute sync items: {err:#}.");
                 }
             } else {
@@ -290,6 +395,17 @@ pub(crate) async fn receive_imf_inner(
         }
     }
 
+    if matches!(
+        mime_parser.is_system_message,
+        SystemMessage::CallOffer | SystemMessage::CallAnswer | SystemMessage::CallIceCandidate
+    ) {
+        if let Err(err) =
+            calls::receive_call_msg(context, &mime_parser, from_id, insert_msg_id).await
+        {
+            warn!(context, "receive_imf cannot handle call message: {err:#}.");
+        }
+    }
+
     if let Some(avatar_action) = &mime_parser.user_avatar {
         if from_id != ContactId::UNDEFINED
             && context
@@ -338,9 +454,12 @@ pub(crate) async fn receive_imf_inner(
     let delete_server_after = context.get_config_delete_server_after().await?;
 
     if !received_msg.msg_ids.is_empty() {
-        if received_msg.needs_delete_job
-            || (delete_server_after == Some(0) && is_partial_download.is_none())
-        {
+        // Folders which are set to delete at once, either by their own setting or by the global one.
+        const DELETE_AT_ONCE_CONDITION: &str =
+            "(folder IN (SELECT folder FROM folder_delete_server_after WHERE delete_server_after=1)
+              OR (?2 AND folder NOT IN (SELECT folder FROM folder_delete_server_after)))";
+        let delete_at_once = delete_server_after == Some(0);
+        if received_msg.needs_delete_job {
             let target = context.get_delete_msgs_target().await?;
             context
                 .sql
@@ -349,6 +468,27 @@ pub(crate) async fn receive_imf_inner(
                     (target, rfc724_mid),
                 )
                 .await?;
+        } else if is_partial_download.is_none()
+            && context
+                .sql
+                .exists(
+                    &format!(
+                        "SELECT COUNT(*) FROM imap WHERE rfc724_mid=?1 AND {DELETE_AT_ONCE_CONDITION}"
+                    ),
+                    (rfc724_mid, delete_at_once),
+                )
+                .await?
+        {
+            let target = context.get_delete_msgs_target().await?;
+            context
+                .sql
+                .execute(
+                    &format!(
+                        "UPDATE imap SET target=?3 WHERE rfc724_mid=?1 AND {DELETE_AT_ONCE_CONDITION}"
+                    ),
+                    (rfc724_mid, delete_at_once, target),
+                )
+                .await?;
         } else if !mime_parser.mdn_reports.is_empty() && mime_parser.has_chat_version() {
             // This is a Delta Chat MDN. Mark as read.
             markseen_on_imap_table(context, rfc724_mid).await?;
@@ -360,7 +500,9 @@ pub(crate) async fn receive_imf_inner(
     } else if !chat_id.is_trash() {
         let fresh = received_msg.state == MessageState::InFresh;
         for msg_id in &received_msg.msg_ids {
-            chat_id.emit_msg_event(context, *msg_id, incoming && fresh);
+            chat_id
+                .emit_msg_event(context, *msg_id, incoming && fresh)
+                .await;
         }
     }
 
@@ -465,6 +607,10 @@ async fn add_parts(
     let is_location_kml = mime_parser.location_kml.is_some();
     let is_mdn = !mime_parser.mdn_reports.is_empty();
     let is_reaction = mime_parser.parts.iter().any(|part| part.is_reaction);
+    let is_call = matches!(
+        mime_parser.is_system_message,
+        SystemMessage::CallOffer | SystemMessage::CallAnswer | SystemMessage::CallIceCandidate
+    );
     let show_emails =
         ShowEmails::from_i32(context.get_config_int(Config::ShowEmails).await?).unwrap_or_default();
 
@@ -527,6 +673,26 @@ async fn add_parts(
             securejoin_seen = false;
         }
 
+        // In verified-only mode messages from unverified senders are never assigned
+        // to groups or accepted chats, they always end up in a contact request.
+        let unverified_sender = chat_id.is_none()
+            && from_id != ContactId::SELF
+            && context.get_config_bool(Config::OnlyAcceptVerified).await?
+            && !is_from_verified_sender(context, mime_parser, from_id).await?;
+        if unverified_sender {
+            info!(
+                context,
+                "Only verified contacts are accepted, {from_id} is not verified."
+            );
+        }
+
+        // Messages from senders on a subscribed blocklist go to a blocked 1:1 chat,
+        // so they are not shown but still there if the user writes to the sender.
+        let denied_sender = chat_id.is_none() && blocklist::is_denied(context, from_id).await?;
+        if denied_sender {
+            info!(context, "{from_id} is on a subscribed blocklist.");
+        }
+
         let test_normal_chat = if from_id == ContactId::UNDEFINED {
             None
         } else {
@@ -539,7 +705,7 @@ async fn add_parts(
             markseen_on_imap_table(context, rfc724_mid).await.ok();
         }
 
-        if chat_id.is_none() {
+        if chat_id.is_none() && !unverified_sender && !denied_sender {
             // try to assign to a chat based on In-Reply-To/References:
 
             if let Some((new_chat_id, new_chat_id_blocked)) =
@@ -562,7 +728,7 @@ async fn add_parts(
             None => Blocked::Request,
         };
 
-        if chat_id.is_none() {
+        if chat_id.is_none() && !unverified_sender && !denied_sender {
             // try to create a group
 
             if let Some((new_chat_id, new_chat_id_blocked)) = create_or_lookup_group(
@@ -623,7 +789,7 @@ async fn add_parts(
             .await?);
         }
 
-        if chat_id.is_none() {
+        if chat_id.is_none() && !unverified_sender && !denied_sender {
             // check if the message belongs to a mailing list
             match mime_parser.get_mailinglist_type() {
                 MailinglistType::ListIdBased => {
@@ -684,6 +850,8 @@ async fn add_parts(
                 let contact = Contact::load_from_db(context, from_id).await?;
                 match contact.is_blocked() {
                     true => Blocked::Yes,
+                    false if denied_sender => Blocked::Yes,
+                    false if unverified_sender => Blocked::Request,
                     false if is_bot => Blocked::Not,
                     false => Blocked::Request,
                 }
@@ -704,6 +872,10 @@ async fn add_parts(
             }
 
             if let Some(chat_id) = chat_id {
+                if unverified_sender && chat_id_blocked == Blocked::Not {
+                    chat_id.set_blocked(context, Blocked::Request).await?;
+                    chat_id_blocked = Blocked::Request;
+                }
                 if chat_id_blocked != Blocked::Not {
                     if chat_id_blocked != create_blocked {
                         chat_id.set_blocked(context, create_blocked).await?;
@@ -726,6 +898,7 @@ async fn add_parts(
             || fetching_existing_messages
             || is_mdn
             || is_reaction
+            || is_call
             || is_location_kml
             || securejoin_seen
             || chat_id_blocked == Blocked::Yes
@@ -880,6 +1053,18 @@ async fn add_parts(
         }
     }
 
+    if mime_parser.is_system_message == SystemMessage::KeyGossip {
+        // Gossiped keys are already processed together with the group changes.
+        chat_id = Some(DC_CHAT_ID_TRASH);
+        info!(context, "Message is a key gossip only (TRASH).");
+    }
+
+    if is_call && mime_parser.is_system_message != SystemMessage::CallOffer {
+        // Call offers are kept as hidden messages, other signaling refers to them.
+        chat_id = Some(DC_CHAT_ID_TRASH);
+        info!(context, "Message is call signaling only (TRASH).");
+    }
+
     let orig_chat_id = chat_id;
     let chat_id = if is_mdn || is_reaction {
         DC_CHAT_ID_TRASH
@@ -904,6 +1089,7 @@ async fn add_parts(
     } else {
         EphemeralTimer::Disabled
     };
+    let sender_ephemeral_timer = ephemeral_timer;
 
     let in_fresh = state == MessageState::InFresh;
     let sort_timestamp = calc_sort_timestamp(context, sent_timestamp, chat_id, in_fresh).await?;
@@ -1094,6 +1280,9 @@ async fn add_parts(
         }
 
         let mut param = part.param.clone();
+        if param.get_int(Param::ViewOnce) == Some(1) {
+            message::seal_view_once_file(context, &mut param).await?;
+        }
         if is_system_message != SystemMessage::Unknown {
             param.set_int(Param::Cmd, is_system_message as i32);
         }
@@ -1160,7 +1349,7 @@ async fn add_parts(
     txt, subject, txt_raw, param, 
     bytes, mime_headers, mime_compressed, mime_in_reply_to,
     mime_references, mime_modified, error, ephemeral_timer,
-    ephemeral_timestamp, download_state, hop_info
+    ephemeral_timestamp, download_state, hop_info, hidden
   )
   VALUES (
     ?,
@@ -1169,7 +1358,7 @@ async fn add_parts(
     ?, ?, ?, ?,
     ?, ?, ?, ?, 1,
     ?, ?, ?, ?,
-    ?, ?, ?, ?
+    ?, ?, ?, ?, ?
   )
 ON CONFLICT (id) DO UPDATE
 SET rfc724_mid=excluded.rfc724_mid, chat_id=excluded.chat_id,
@@ -1179,7 +1368,8 @@ async fn add_parts(
     bytes=excluded.bytes, mime_headers=excluded.mime_headers,
     mime_compressed=excluded.mime_compressed, mime_in_reply_to=excluded.mime_in_reply_to,
     mime_references=excluded.mime_references, mime_modified=excluded.mime_modified, error=excluded.error, ephemeral_timer=excluded.ephemeral_timer,
-    ephemeral_timestamp=excluded.ephemeral_timestamp, download_state=excluded.download_state, hop_info=excluded.hop_info
+    ephemeral_timestamp=excluded.ephemeral_timestamp, download_state=excluded.download_state, hop_info=excluded.hop_info,
+    hidden=excluded.hidden
 "#)?;
                 stmt.execute(params![
                     replace_msg_id,
@@ -1219,7 +1409,8 @@ async fn add_parts(
                     } else {
                         DownloadState::Done
                     },
-                    mime_parser.hop_info
+                    mime_parser.hop_info,
+                    is_call
                 ])?;
                 let row_id = conn.last_insert_rowid();
                 Ok(row_id)
@@ -1247,12 +1438,43 @@ async fn add_parts(
         .await?;
     }
 
+    if mime_parser.is_system_message != SystemMessage::Unknown {
+        for msg_id in &created_db_entries {
+            msg_id.update_info_group(context).await?;
+        }
+    }
+
     if let Some(replace_msg_id) = replace_msg_id {
         // "Replace" placeholder with a message that has no parts.
         replace_msg_id.delete_from_db(context).await?;
     }
 
-    chat_id.unarchive_if_not_muted(context, state).await?;
+    let mentioned = incoming && is_mention(context, mime_parser).await?;
+    chat_id
+        .unarchive_if_not_muted_ex(context, state, mentioned)
+        .await?;
+
+    if incoming
+        && !chat_id.is_special()
+        && is_partial_download.is_none()
+        && !mime_parser.parts.is_empty()
+    {
+        chat_id
+            .set_ephemeral_timer_ack(context, from_id, sender_ephemeral_timer, sent_timestamp)
+            .await?;
+        if let Some(msg_id) = created_db_entries.first() {
+            if quotes_expired_message(context, mime_parser, from_id, sent_timestamp).await? {
+                warn!(
+                    context,
+                    "{from_id} quoted an expired ephemeral message in {msg_id}."
+                );
+                context.emit_event(EventType::EphemeralTimerNotHonored {
+                    chat_id,
+                    msg_id: *msg_id,
+                });
+            }
+        }
+    }
 
     info!(
         context,
@@ -1655,6 +1877,19 @@ async fn apply_group_changes(
                 } else {
                     Some(stock_str::msg_del_member(context, &removed_addr, from_id).await)
                 };
+                let banned = mime_parser
+                    .get_header(HeaderDef::ChatGroupMemberBanned)
+                    .map_or(false, |addr| addr_cmp(addr, &removed_addr));
+                if banned
+                    && contact_id != ContactId::SELF
+                    && chat::is_contact_in_chat(context, chat_id, from_id).await?
+                {
+                    info!(
+                        context,
+                        "{contact_id} is banned from {chat_id} by {from_id}."
+                    );
+                    chat::ban_member(context, chat_id, contact_id).await?;
+                }
             }
             None => warn!(context, "Removed {removed_addr:?} has no contact_id."),
         }
@@ -1666,6 +1901,20 @@ async fn apply_group_changes(
         {
             better_msg = Some(stock_str::msg_add_member(context, &added_member, from_id).await);
             recreate_member_list = true;
+            let unbanned = mime_parser
+                .get_header(HeaderDef::ChatGroupMemberUnbanned)
+                .map_or(false, |addr| addr_cmp(addr, &added_member));
+            if unbanned && chat::is_contact_in_chat(context, chat_id, from_id).await? {
+                if let Some(contact_id) =
+                    Contact::lookup_id_by_addr(context, &added_member, Origin::Unknown).await?
+                {
+                    info!(
+                        context,
+                        "Ban of {contact_id} from {chat_id} is lifted by {from_id}."
+                    );
+                    chat::lift_ban(context, chat_id, contact_id).await?;
+                }
+            }
         } else if let Some(old_name) = mime_parser
             .get_header(HeaderDef::ChatGroupNameChanged)
             // See create_or_lookup_group() for explanation
@@ -1769,6 +2018,8 @@ async fn apply_group_changes(
             if let Some(removed_id) = removed_id {
                 members_to_add.retain(|id| *id != removed_id);
             }
+            let banned = chat::get_banned_members(context, chat_id).await?;
+            members_to_add.retain(|id| !banned.contains(id));
             members_to_add.dedup();
 
             info!(context, "Adding {members_to_add:?} to chat id={chat_id}.");
@@ -2072,6 +2323,31 @@ async fn create_adhoc_group(
     Ok(Some(new_chat_id))
 }
 
+/// Returns true if the sender is bidirectionally verified
+/// and the message is signed with the verified key.
+async fn is_from_verified_sender(
+    context: &Context,
+    mimeparser: &MimeMessage,
+    from_id: ContactId,
+) -> Result<bool> {
+    if from_id == ContactId::SELF {
+        return Ok(true);
+    }
+    if from_id == ContactId::UNDEFINED || !mimeparser.was_encrypted() {
+        return Ok(false);
+    }
+    let contact = Contact::load_from_db(context, from_id).await?;
+    let peerstate = match Peerstate::from_addr(context, contact.get_addr()).await? {
+        Some(peerstate) => peerstate,
+        None => return Ok(false),
+    };
+    Ok(
+        contact.is_verified_ex(context, Some(&peerstate)).await?
+            == VerifiedStatus::BidirectVerified
+            && peerstate.has_verified_key(&mimeparser.signatures),
+    )
+}
+
 async fn check_verified_properties(
     context: &Context,
     mimeparser: &MimeMessage,
@@ -2235,6 +2511,26 @@ async fn get_rfc724_mid_in_list(context: &Context, mid_list: &str) -> Result<Opt
     Ok(None)
 }
 
+/// Returns true if the message mentions the user,
+/// i.e. replies to a message sent by the user or contains the user's address
+/// or `@` followed by the user's display name.
+async fn is_mention(context: &Context, mime_parser: &MimeMessage) -> Result<bool> {
+    if let Some(field) = mime_parser.get_header(HeaderDef::InReplyTo) {
+        if let Some(msg) = get_rfc724_mid_in_list(context, field).await? {
+            if msg.from_id == ContactId::SELF {
+                return Ok(true);
+            }
+        }
+    }
+
+    for part in &mime_parser.parts {
+        if notification::mentions_self(context, &part.msg).await? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
 /// Returns the last message referenced from References: header found in the database.
 ///
 /// If none found, tries In-Reply-To: as a fallback for classic MUAs that don't set the
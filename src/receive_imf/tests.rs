@@ -7,6 +7,7 @@
 use crate::chatlist::Chatlist;
 use crate::constants::DC_GCL_NO_SPECIALS;
 use crate::imap::prefetch_should_download;
+use crate::key::{DcKey, SignedPublicKey};
 use crate::message::Message;
 use crate::test_utils::{get_chat_msg, TestContext, TestContextManager};
 
@@ -2631,7 +2632,9 @@ async fn test_incoming_contact_request() -> Result<()> {
             .get_matching(|evt| matches!(evt, EventType::IncomingMsg { .. }))
             .await;
         match event {
-            EventType::IncomingMsg { chat_id, msg_id } => {
+            EventType::IncomingMsg {
+                chat_id, msg_id, ..
+            } => {
                 assert_eq!(msg.chat_id, chat_id);
                 assert_eq!(msg.id, msg_id);
                 return Ok(());
@@ -3292,3 +3295,115 @@ async fn test_mua_user_adds_recipient_to_single_chat() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_only_accept_verified() -> Result<()> {
+    let mut tcm = TestContextManager::new();
+    let alice = tcm.alice().await;
+    let bob = tcm.bob().await;
+    tcm.send_recv_accept(&alice, &bob, "hi").await;
+    tcm.send_recv_accept(&bob, &alice, "hello").await;
+    alice
+        .set_config_bool(Config::OnlyAcceptVerified, true)
+        .await?;
+
+    // Bob is not verified, so even his encrypted messages end up in a contact request.
+    let bob_chat = bob.create_chat(&alice).await;
+    let sent = bob.send_text(bob_chat.id, "unverified").await;
+    let msg = alice.recv_msg(&sent).await;
+    let alice_chat = Chat::load_from_db(&alice, msg.chat_id).await?;
+    assert!(alice_chat.is_contact_request());
+
+    alice_chat.id.accept(&alice).await?;
+    let alice_chat = Chat::load_from_db(&alice, alice_chat.id).await?;
+    assert_eq!(
+        alice_chat.why_cant_send(&alice).await?,
+        Some(chat::CantSendReason::NotVerified)
+    );
+
+    let bob_addr = bob.get_config(Config::Addr).await?.unwrap();
+    let mut peerstate = Peerstate::from_addr(&alice, &bob_addr).await?.unwrap();
+    peerstate.verified_key = peerstate.public_key.clone();
+    peerstate.verified_key_fingerprint = peerstate.public_key_fingerprint.clone();
+    peerstate.save_to_db(&alice.sql).await?;
+    assert_eq!(alice_chat.why_cant_send(&alice).await?, None);
+
+    // Messages signed with the verified key are accepted.
+    let sent = bob.send_text(bob_chat.id, "verified").await;
+    let msg = alice.recv_msg(&sent).await;
+    assert_eq!(msg.chat_id, alice_chat.id);
+    assert!(!Chat::load_from_db(&alice, msg.chat_id)
+        .await?
+        .is_contact_request());
+    Ok(())
+}
+
+/// Tests that the sender of a gossiped key is recorded in the peerstate.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_gossip_source_is_recorded() -> Result<()> {
+    let mut tcm = TestContextManager::new();
+    let alice = tcm.alice().await;
+    let bob = tcm.bob().await;
+    let fiona = tcm.fiona().await;
+
+    tcm.send_recv_accept(&alice, &bob, "Hi Bob").await;
+    tcm.send_recv_accept(&bob, &alice, "Hi Alice").await;
+    tcm.send_recv_accept(&fiona, &bob, "Hi Bob").await;
+    tcm.send_recv_accept(&bob, &fiona, "Hi Fiona").await;
+
+    let group_id = bob
+        .create_group_with_members(ProtectionStatus::Unprotected, "Group", &[&alice, &fiona])
+        .await;
+    let sent = bob.send_text(group_id, "Hello").await;
+    let msg = alice.recv_msg(&sent).await;
+    assert!(msg.get_showpadlock());
+
+    let fiona_addr = fiona.get_config(Config::Addr).await?.unwrap();
+    let peerstate = Peerstate::from_addr(&alice, &fiona_addr).await?.unwrap();
+    assert_eq!(
+        peerstate.gossip_key_fingerprint,
+        Some(SignedPublicKey::load_self(&fiona).await?.fingerprint())
+    );
+    assert_eq!(
+        peerstate.gossip_source_addr.as_deref(),
+        Some("bob@example.net")
+    );
+    assert_eq!(
+        peerstate.gossip_source_fingerprint,
+        Some(SignedPublicKey::load_self(&bob).await?.fingerprint())
+    );
+    Ok(())
+}
+
+/// Tests that a message received from two folders at the same time,
+/// e.g. from the Inbox and the DeltaChat folder on Gmail, is added only once.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_receive_same_msg_concurrently() -> Result<()> {
+    let t = TestContext::new_alice().await;
+    let raw = b"From: bob@example.net\n\
+                To: alice@example.org\n\
+                Subject: foo\n\
+                Message-ID: <concurrent@example.net>\n\
+                Chat-Version: 1.0\n\
+                Date: Sun, 22 Mar 2020 22:37:57 +0000\n\
+                \n\
+                hello\n";
+
+    let (res1, res2) = tokio::join!(receive_imf(&t, raw, false), receive_imf(&t, raw, false));
+    let received = [res1?, res2?];
+    assert_eq!(received.iter().filter(|r| r.is_some()).count(), 1);
+    assert_eq!(
+        t.sql
+            .count(
+                "SELECT COUNT(*) FROM msgs WHERE rfc724_mid='concurrent@example.net'",
+                ()
+            )
+            .await?,
+        1
+    );
+
+    // The claim is released, receiving the message again is a no-op.
+    assert!(receive_imf(&t, raw, false).await?.is_none());
+    assert!(t.receiving_msgs.lock().unwrap().is_empty());
+    Ok(())
+}
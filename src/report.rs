@@ -0,0 +1,427 @@
+//! # Reporting messages as spam or abuse.
+//!
+//! [`report_msg`] sends a report about a received message
+//! to the address set in [`Config::AbuseAddr`],
+//! by default `abuse@` the domain of the configured address.
+//! The report is an [RFC 5965](https://www.rfc-editor.org/rfc/rfc5965) feedback report,
+//! a `multipart/report; report-type=feedback-report` message
+//! with a human-readable part, a `message/feedback-report` part
+//! and, if the user agrees, the original message as `message/rfc822`.
+//! If the raw message is not stored on the device, see [`Config::SaveMimeHeaders`],
+//! it is fetched from the server before the report is sent.
+//!
+//! Reports are recorded, see [`is_msg_reported`], so a message is not reported twice.
+
+use anyhow::{ensure, Context as _, Result};
+use chrono::TimeZone;
+use deltachat_derive::{FromSql, ToSql};
+use futures::StreamExt;
+
+use crate::blob::BlobObject;
+use crate::chat::{self, ChatIdBlocked};
+use crate::config::Config;
+use crate::constants::Blocked;
+use crate::contact::{Contact, ContactAddress, Origin};
+use crate::context::{get_version_str, Context};
+use crate::imap::Imap;
+use crate::job::{self, Action, Job, Status};
+use crate::message::{self, Message, MsgId, Viewtype};
+use crate::mimeparser::SystemMessage;
+use crate::param::Param;
+use crate::tools::{time, EmailAddress};
+use crate::{job_try, EventType};
+
+/// Reason for reporting a message.
+#[derive(
+    Debug, Default, Display, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive, ToSql, FromSql,
+)]
+#[repr(u32)]
+pub enum ReportCategory {
+    /// Unsolicited bulk message.
+    #[default]
+    Spam = 1,
+
+    /// Harassment or other abusive content.
+    Abuse = 2,
+
+    /// Phishing or other fraud.
+    Fraud = 3,
+
+    /// Malware attached.
+    Virus = 4,
+
+    /// Other reasons.
+    Other = 5,
+}
+
+impl ReportCategory {
+    /// Returns the `Feedback-Type` of RFC 5965.
+    fn feedback_type(self) -> &'static str {
+        match self {
+            ReportCategory::Spam | ReportCategory::Abuse => "abuse",
+            ReportCategory::Fraud => "fraud",
+            ReportCategory::Virus => "virus",
+            ReportCategory::Other => "other",
+        }
+    }
+}
+
+/// Reports a received message to the abuse address.
+///
+/// With `include_original`, the original message is attached.
+/// If it is not stored on the device, it is fetched from the server first
+/// and the report is sent in the background;
+/// if it cannot be fetched, the report is sent without it.
+/// The report is sent in a hidden chat and does not appear in the chatlist.
+pub async fn report_msg(
+    context: &Context,
+    msg_id: MsgId,
+    category: ReportCategory,
+    include_original: bool,
+) -> Result<()> {
+    let msg = Message::load_from_db(context, msg_id).await?;
+    ensure!(
+        !msg.get_from_id().is_special(),
+        "{msg_id} is not a received message"
+    );
+    ensure!(
+        !is_msg_reported(context, msg_id).await?,
+        "{msg_id} was reported already"
+    );
+    let abuse_addr = get_abuse_addr(context).await?;
+
+    let original = if include_original {
+        let raw = message::get_mime_headers(context, msg_id).await?;
+        if raw.is_empty()
+            && context
+                .sql
+                .exists(
+                    "SELECT COUNT(*) FROM imap WHERE rfc724_mid=?",
+                    (&msg.rfc724_mid,),
+                )
+                .await?
+        {
+            record_report(context, &msg, category, &abuse_addr).await?;
+            job::add(context, Job::new(Action::ReportMsg, msg_id.to_u32())).await?;
+            info!(context, "Fetching {msg_id} from the server to report it.");
+            return Ok(());
+        }
+        Some(raw)
+    } else {
+        None
+    };
+    send_report(context, &msg, category, &abuse_addr, original).await?;
+    record_report(context, &msg, category, &abuse_addr).await?;
+    Ok(())
+}
+
+/// Sends the report about `msg`.
+///
+/// `original` is the raw message to attach, `None` if it should not be attached,
+/// and empty if it should be attached but is not available.
+async fn send_report(
+    context: &Context,
+    msg: &Message,
+    category: ReportCategory,
+    abuse_addr: &str,
+    original: Option<Vec<u8>>,
+) -> Result<()> {
+    let from = Contact::get_by_id(context, msg.get_from_id()).await?;
+    let self_addr = context.get_primary_self_addr().await?;
+
+    let mut text = format!(
+        "This is a report of a message received by {self_addr}.\n\
+         The message from {} was reported as {category}.\n",
+        from.get_addr(),
+    );
+    let arrival_date = chrono::Utc
+        .timestamp_opt(msg.get_received_timestamp(), 0)
+        .single()
+        .context("Invalid arrival timestamp")?
+        .to_rfc2822();
+    let feedback = format!(
+        "Feedback-Type: {}\r\n\
+         User-Agent: Delta Chat {}\r\n\
+         Version: 1\r\n\
+         Original-Mail-From: <{}>\r\n\
+         Original-Rcpt-To: <{self_addr}>\r\n\
+         Arrival-Date: {arrival_date}\r\n\
+         Original-Message-ID: <{}>\r\n",
+        category.feedback_type(),
+        get_version_str(),
+        from.get_addr(),
+        msg.rfc724_mid,
+    );
+
+    let mut report = match original {
+        Some(raw) if !raw.is_empty() => {
+            let blob = BlobObject::create(context, "original.eml", &raw).await?;
+            let mut report = Message::new(Viewtype::File);
+            report.set_file(blob.as_name(), Some("message/rfc822"));
+            report
+        }
+        Some(_) => {
+            text += "The original message is not available.\n";
+            Message::new(Viewtype::Text)
+        }
+        None => Message::new(Viewtype::Text),
+    };
+    report.set_text(Some(text));
+    report.set_subject(format!("Report of {}", from.get_addr()));
+    report.param.set_cmd(SystemMessage::AbuseReport);
+    report.param.set(Param::Arg, feedback);
+    report.force_plaintext();
+    report.hidden = true;
+
+    let (contact_id, _) = Contact::add_or_lookup(
+        context,
+        "",
+        ContactAddress::new(abuse_addr)?,
+        Origin::Hidden,
+    )
+    .await?;
+    let chat_id = ChatIdBlocked::get_for_contact(context, contact_id, Blocked::Yes)
+        .await?
+        .id;
+    chat::send_msg(context, chat_id, &mut report)
+        .await
+        .with_context(|| format!("Failed to send report to {abuse_addr}"))?;
+    info!(
+        context,
+        "Reported {} as {category} to {abuse_addr}.", msg.id
+    );
+    Ok(())
+}
+
+/// Records that `msg` was reported, see [`is_msg_reported`].
+async fn record_report(
+    context: &Context,
+    msg: &Message,
+    category: ReportCategory,
+    abuse_addr: &str,
+) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "INSERT INTO msg_reports (msg_id, rfc724_mid, category, addr, timestamp)
+             VALUES (?, ?, ?, ?, ?)",
+            (msg.id, &msg.rfc724_mid, category, abuse_addr, time()),
+        )
+        .await?;
+    Ok(())
+}
+
+impl Job {
+    /// Fetches the original of a reported message from the server and sends the report.
+    /// Called in response to `Action::ReportMsg`.
+    pub(crate) async fn report_msg(&self, context: &Context, imap: &mut Imap) -> Status {
+        if let Err(err) = imap.prepare(context).await {
+            warn!(context, "report: could not connect: {:#}", err);
+            return Status::RetryNow;
+        }
+
+        let msg = job_try!(Message::load_from_db(context, MsgId::new(self.foreign_id)).await);
+        let (category, abuse_addr) = job_try!(
+            context
+                .sql
+                .query_row(
+                    "SELECT category, addr FROM msg_reports WHERE msg_id=?",
+                    (msg.id,),
+                    |row| {
+                        let category: ReportCategory = row.get(0)?;
+                        let addr: String = row.get(1)?;
+                        Ok((category, addr))
+                    }
+                )
+                .await
+        );
+        let raw = match imap.fetch_raw_msg(context, &msg.rfc724_mid).await {
+            Ok(Some(raw)) => raw,
+            Ok(None) => {
+                warn!(context, "report: {} not found on the server.", msg.id);
+                Vec::new()
+            }
+            Err(err) => {
+                warn!(context, "report: could not fetch {}: {:#}", msg.id, err);
+                Vec::new()
+            }
+        };
+        let res = send_report(context, &msg, category, &abuse_addr, Some(raw)).await;
+        if let Err(err) = &res {
+            context.emit_event(EventType::Error(format!("{err:#}")));
+        }
+        Status::Finished(res)
+    }
+}
+
+impl Imap {
+    /// Fetches the raw message with the given Message-ID from the server
+    /// without marking it as seen.
+    async fn fetch_raw_msg(
+        &mut self,
+        context: &Context,
+        rfc724_mid: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        let row = context
+            .sql
+            .query_row_optional(
+                "SELECT uid, folder FROM imap WHERE rfc724_mid=? AND target=folder",
+                (rfc724_mid,),
+                |row| {
+                    let uid: u32 = row.get(0)?;
+                    let folder: String = row.get(1)?;
+                    Ok((uid, folder))
+                },
+            )
+            .await?;
+        let (uid, folder) = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        if self
+            .prepare_imap_operation_on_msg(context, &folder, uid)
+            .await
+            .is_some()
+        {
+            return Ok(None);
+        }
+
+        let session = self.session.as_mut().context("no IMAP session")?;
+        let mut fetches = session
+            .uid_fetch(uid.to_string(), "BODY.PEEK[]")
+            .await
+            .with_context(|| format!("fetching {folder}/{uid}"))?;
+        let mut raw = None;
+        while let Some(fetch) = fetches.next().await {
+            let fetch = fetch?;
+            if fetch.uid == Some(uid) {
+                raw = fetch.body().map(|body| body.to_vec());
+            }
+        }
+        Ok(raw)
+    }
+}
+
+/// Returns true if the message was reported with [`report_msg`].
+pub async fn is_msg_reported(context: &Context, msg_id: MsgId) -> Result<bool> {
+    context
+        .sql
+        .exists("SELECT COUNT(*) FROM msg_reports WHERE msg_id=?", (msg_id,))
+        .await
+}
+
+/// Returns the address to send reports to.
+async fn get_abuse_addr(context: &Context) -> Result<String> {
+    if let Some(addr) = context
+        .get_config(Config::AbuseAddr)
+        .await?
+        .filter(|addr| !addr.is_empty())
+    {
+        return Ok(addr);
+    }
+    let self_addr = context.get_primary_self_addr().await?;
+    Ok(format!("abuse@{}", EmailAddress::new(&self_addr)?.domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chatlist::Chatlist;
+    use crate::test_utils::TestContextManager;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_report_msg() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        alice.set_config_bool(Config::SaveMimeHeaders, true).await?;
+        alice.create_chat(&bob).await;
+
+        let bob_chat_id = bob.create_chat(&alice).await.id;
+        let sent = bob.send_text(bob_chat_id, "spam").await;
+        let msg = alice.recv_msg(&sent).await;
+        assert!(!is_msg_reported(&alice, msg.id).await?);
+        let chats = Chatlist::try_load(&alice, 0, None, None).await?.len();
+
+        report_msg(&alice, msg.id, ReportCategory::Spam, true).await?;
+        assert!(is_msg_reported(&alice, msg.id).await?);
+        let report = alice.pop_sent_msg().await;
+        assert_eq!(report.recipient(), EmailAddress::new("abuse@example.org")?);
+        let payload = report.payload();
+        assert!(payload.contains("multipart/report; report-type=feedback-report"));
+        assert!(payload.contains("Content-Type: message/feedback-report"));
+        assert!(payload.contains("Feedback-Type: abuse"));
+        assert!(payload.contains("Original-Mail-From: <bob@example.net>"));
+        assert!(payload.contains("Content-Type: message/rfc822"));
+        assert_eq!(
+            Chatlist::try_load(&alice, 0, None, None).await?.len(),
+            chats
+        );
+        assert!(report_msg(&alice, msg.id, ReportCategory::Spam, true)
+            .await
+            .is_err());
+
+        // Own messages cannot be reported.
+        let own_msg_id = chat::send_text_msg(&alice, msg.chat_id, "hi".to_string()).await?;
+        assert!(report_msg(&alice, own_msg_id, ReportCategory::Other, false)
+            .await
+            .is_err());
+
+        alice
+            .set_config(Config::AbuseAddr, Some("reports@example.com"))
+            .await?;
+        let sent = bob.send_text(bob_chat_id, "fraud").await;
+        let msg = alice.recv_msg(&sent).await;
+        report_msg(&alice, msg.id, ReportCategory::Fraud, false).await?;
+        let report = alice.pop_sent_msg().await;
+        assert_eq!(
+            report.recipient(),
+            EmailAddress::new("reports@example.com")?
+        );
+        assert!(report.payload().contains("Feedback-Type: fraud"));
+        assert!(!report.payload().contains("message/rfc822"));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_report_msg_not_stored() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let bob_chat_id = bob.create_chat(&alice).await.id;
+
+        // The message is neither stored nor on the server, the report is sent without it.
+        let msg = alice
+            .recv_msg(&bob.send_text(bob_chat_id, "spam").await)
+            .await;
+        report_msg(&alice, msg.id, ReportCategory::Spam, true).await?;
+        let payload = alice.pop_sent_msg().await.payload();
+        assert!(payload.contains("The original message is not available."));
+        assert!(!payload.contains("message/rfc822"));
+
+        // The message is on the server, it is fetched before reporting.
+        let msg = alice
+            .recv_msg(&bob.send_text(bob_chat_id, "more spam").await)
+            .await;
+        alice
+            .sql
+            .execute(
+                "INSERT INTO imap (rfc724_mid, folder, target, uid, uidvalidity)
+                 VALUES (?, 'INBOX', 'INBOX', 1, 1)",
+                (&msg.rfc724_mid,),
+            )
+            .await?;
+        report_msg(&alice, msg.id, ReportCategory::Spam, true).await?;
+        assert!(is_msg_reported(&alice, msg.id).await?);
+        assert!(
+            alice
+                .sql
+                .exists(
+                    "SELECT COUNT(*) FROM jobs WHERE action=? AND foreign_id=?",
+                    (Action::ReportMsg, msg.id),
+                )
+                .await?
+        );
+        Ok(())
+    }
+}
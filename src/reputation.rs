@@ -0,0 +1,249 @@
+//! # Reputation of unknown senders.
+//!
+//! [`get_spam_score`] estimates how likely a contact request is spam,
+//! so that UIs can order contact requests or move likely spam out of the way.
+//! The score is computed locally from a few signals:
+//!
+//! - whether DKIM failed for the latest message
+//!   although it is known to work for the sender's domain, see [`Param::DkimFailed`],
+//! - whether the sender is a member of a group the user is a member of, too,
+//! - whether the sender was introduced by a contact, i.e. its key was gossiped in a group,
+//! - the entropy of the text of the latest message, taking its script into account,
+//!   random-looking or highly repetitive texts are typical for spam.
+//!
+//! The score is only a hint; nothing is blocked or deleted automatically.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::chat::{self, Chat, ChatId};
+use crate::constants::{Blocked, Chattype};
+use crate::contact::{Contact, ContactId};
+use crate::context::Context;
+use crate::message::{Message, MsgId};
+use crate::param::Param;
+
+/// Score of a contact request without any signal.
+const NEUTRAL_SCORE: i32 = 50;
+
+/// Texts with an entropy in this range, in bits per character,
+/// are considered natural language.
+const NATURAL_ENTROPY: std::ops::RangeInclusive<f64> = 2.5..=5.0;
+
+/// Same as [`NATURAL_ENTROPY`] for texts written mostly in Chinese, Japanese or Korean.
+///
+/// Each character of these scripts carries much more information than a letter,
+/// so short natural texts hardly repeat characters and their entropy is close to
+/// the maximum possible for their length.
+/// Only repetitive texts are detected for them.
+const NATURAL_ENTROPY_CJK: std::ops::RangeInclusive<f64> = 2.5..=f64::INFINITY;
+
+/// Returns the spam likelihood of a contact request from 0 (unlikely) to 100 (likely).
+///
+/// Returns `None` if the chat is not a contact request of a single contact.
+pub async fn get_spam_score(context: &Context, chat_id: ChatId) -> Result<Option<u32>> {
+    let chat = Chat::load_from_db(context, chat_id).await?;
+    if !chat.is_contact_request() || chat.typ != Chattype::Single {
+        return Ok(None);
+    }
+    let contact_id = match chat::get_chat_contacts(context, chat_id).await?.first() {
+        Some(contact_id) => *contact_id,
+        None => return Ok(None),
+    };
+
+    let mut score = NEUTRAL_SCORE;
+    if let Some(msg) = get_latest_incoming_msg(context, chat_id).await? {
+        if msg.param.get_bool(Param::DkimFailed).unwrap_or_default() {
+            score += 20;
+        }
+        if let Some(text) = msg.get_text().filter(|text| text.chars().count() >= 20) {
+            if !is_natural_text(&text) {
+                score += 15;
+            }
+        }
+    }
+    if has_shared_group(context, contact_id).await? {
+        score -= 30;
+    }
+    if is_introduced(context, contact_id).await? {
+        score -= 15;
+    }
+    let score = score.clamp(0, 100) as u32;
+    info!(context, "Spam score of {chat_id} is {score}.");
+    Ok(Some(score))
+}
+
+async fn get_latest_incoming_msg(context: &Context, chat_id: ChatId) -> Result<Option<Message>> {
+    let msg_id: Option<MsgId> = context
+        .sql
+        .query_get_value(
+            "SELECT id FROM msgs WHERE chat_id=? AND from_id!=? AND hidden=0
+             ORDER BY timestamp DESC, id DESC LIMIT 1",
+            (chat_id, ContactId::SELF),
+        )
+        .await?;
+    match msg_id {
+        Some(msg_id) => Ok(Some(Message::load_from_db(context, msg_id).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Returns true if the contact is a member of a non-blocked group the user is a member of.
+async fn has_shared_group(context: &Context, contact_id: ContactId) -> Result<bool> {
+    context
+        .sql
+        .exists(
+            "SELECT COUNT(*) FROM chats_contacts cc
+             INNER JOIN chats c ON c.id=cc.chat_id
+             WHERE cc.contact_id=? AND c.type=? AND c.blocked=?
+             AND EXISTS (SELECT 1 FROM chats_contacts WHERE chat_id=c.id AND contact_id=?)",
+            (contact_id, Chattype::Group, Blocked::Not, ContactId::SELF),
+        )
+        .await
+}
+
+/// Returns true if the key of the contact was gossiped by another member of a group.
+async fn is_introduced(context: &Context, contact_id: ContactId) -> Result<bool> {
+    let contact = Contact::get_by_id(context, contact_id).await?;
+    context
+        .sql
+        .exists(
+            "SELECT COUNT(*) FROM acpeerstates
+             WHERE addr=? COLLATE NOCASE AND gossip_key IS NOT NULL",
+            (contact.get_addr(),),
+        )
+        .await
+}
+
+/// Returns true if the entropy of `text` is in the range of natural language for its script.
+fn is_natural_text(text: &str) -> bool {
+    let (cjk, total) = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_ascii_punctuation())
+        .fold((0, 0), |(cjk, total), c| {
+            (cjk + usize::from(is_cjk(c)), total + 1)
+        });
+    let range = if cjk * 2 > total {
+        NATURAL_ENTROPY_CJK
+    } else {
+        NATURAL_ENTROPY
+    };
+    range.contains(&text_entropy(text))
+}
+
+/// Returns true if `c` is a Chinese, Japanese or Korean character.
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3040}'..='\u{30ff}' // Hiragana, Katakana
+            | '\u{3400}'..='\u{4dbf}' // CJK Unified Ideographs Extension A
+            | '\u{4e00}'..='\u{9fff}' // CJK Unified Ideographs
+            | '\u{ac00}'..='\u{d7af}' // Hangul Syllables
+            | '\u{f900}'..='\u{faff}' // CJK Compatibility Ideographs
+            | '\u{20000}'..='\u{2ffff}' // Supplementary Ideographic Planes
+    )
+}
+
+/// Returns the Shannon entropy of the characters of `text` in bits per character.
+fn text_entropy(text: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut total = 0;
+    for c in text.chars() {
+        *counts.entry(c).or_default() += 1;
+        total += 1;
+    }
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::{add_contact_to_chat, create_group_chat, ProtectionStatus};
+    use crate::test_utils::TestContextManager;
+
+    #[test]
+    fn test_text_entropy() {
+        assert_eq!(text_entropy("aaaaaaaa"), 0.0);
+        assert!(NATURAL_ENTROPY.contains(&text_entropy(
+            "Hello, are you coming to the meeting tomorrow?"
+        )));
+        assert!(!NATURAL_ENTROPY.contains(&text_entropy("$$$ WIN WIN WIN $$$ WIN WIN WIN $$$")));
+    }
+
+    #[test]
+    fn test_is_natural_text() {
+        assert!(is_natural_text(
+            "Hello, are you coming to the meeting tomorrow?"
+        ));
+        assert!(!is_natural_text(
+            "x8Kq2Lp9Zt4Vw7Yb3Nc6Md1Rf5Hg0JsGaXuWeTiQo"
+        ));
+        assert!(is_natural_text(
+            "你好，明天下午三点我们在公司楼下的咖啡馆见面，讨论一下新项目的计划吧。我会带上所有的资料和上周的会议记录。"
+        ));
+        assert!(is_natural_text(
+            "明日の午後三時に駅前の喫茶店で会いましょう。新しい企画について相談したいです。"
+        ));
+        assert!(is_natural_text(
+            "내일 오후 세 시에 역 앞 카페에서 만나요. 새 프로젝트에 대해 이야기하고 싶어요."
+        ));
+        assert!(!is_natural_text("哈哈哈哈哈哈哈哈哈哈哈哈哈哈哈哈哈哈哈哈"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_spam_score() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let fiona = tcm.fiona().await;
+
+        let bob_chat_id = bob.create_chat(&alice).await.id;
+        let sent = bob
+            .send_text(
+                bob_chat_id,
+                "Hello, are you coming to the meeting tomorrow?",
+            )
+            .await;
+        let msg = alice.recv_msg(&sent).await;
+        assert_eq!(get_spam_score(&alice, msg.chat_id).await?, Some(50));
+
+        let sent = bob
+            .send_text(bob_chat_id, "$$$ WIN WIN WIN $$$ WIN WIN WIN $$$")
+            .await;
+        alice.recv_msg(&sent).await;
+        assert_eq!(get_spam_score(&alice, msg.chat_id).await?, Some(65));
+
+        // Fiona is in a group with alice.
+        let fiona_chat_id = fiona.create_chat(&alice).await.id;
+        let sent = fiona
+            .send_text(
+                fiona_chat_id,
+                "Hello, are you coming to the meeting tomorrow?",
+            )
+            .await;
+        let fiona_msg = alice.recv_msg(&sent).await;
+        let group_id = create_group_chat(&alice, ProtectionStatus::Unprotected, "group").await?;
+        let alice_fiona_id = alice.add_or_lookup_contact(&fiona).await.id;
+        add_contact_to_chat(&alice, group_id, alice_fiona_id).await?;
+        assert_eq!(get_spam_score(&alice, fiona_msg.chat_id).await?, Some(20));
+
+        // DKIM failing for a domain where it is known to work is suspicious.
+        let mut msg2 = alice.get_last_msg_in(msg.chat_id).await;
+        msg2.param.set_int(Param::DkimFailed, 1);
+        msg2.update_param(&alice).await?;
+        assert_eq!(get_spam_score(&alice, msg.chat_id).await?, Some(85));
+
+        // Accepted chats have no score.
+        msg.chat_id.accept(&alice).await?;
+        assert_eq!(get_spam_score(&alice, msg.chat_id).await?, None);
+        assert_eq!(get_spam_score(&alice, group_id).await?, None);
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
+use std::future::Future;
 use std::iter::{self, once};
 use std::num::NonZeroUsize;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use anyhow::{bail, Context as _, Error, Result};
 use async_channel::{self as channel, Receiver, Sender};
@@ -10,12 +12,13 @@
 use tokio::task;
 
 use self::connectivity::ConnectivityStore;
+use crate::blocklist;
 use crate::config::Config;
 use crate::contact::{ContactId, RecentlySeenLoop};
 use crate::context::Context;
 use crate::ephemeral::{self, delete_expired_imap_messages};
 use crate::events::EventType;
-use crate::imap::{FolderMeaning, Imap};
+use crate::imap::{archive_old_imap_messages, FolderMeaning, Imap};
 use crate::job;
 use crate::location;
 use crate::log::LogExt;
@@ -325,109 +328,153 @@ async fn inbox_loop(ctx: Context, started: Sender<()>, inbox_handlers: ImapConne
             return;
         };
 
+        let mut supervisor = Supervisor::new("inbox");
         let mut info = InterruptInfo::default();
         loop {
-            let job = match job::load_next(&ctx, &info).await {
-                Err(err) => {
-                    error!(ctx, "Failed loading job from the database: {:#}.", err);
-                    None
+            let iteration = inbox_loop_iteration(&ctx, &mut connection, &info);
+            info = match supervisor.run(&ctx, iteration).await {
+                Some(info) => info,
+                None => {
+                    connection.trigger_reconnect(&ctx);
+                    InterruptInfo::default()
                 }
-                Ok(job) => job,
             };
+        }
+    };
+
+    stop_receiver
+        .recv()
+        .map(|_| {
+            info!(ctx, "shutting down inbox loop");
+        })
+        .race(fut)
+        .await;
+}
 
-            match job {
-                Some(job) => {
-                    job::perform_job(&ctx, job::Connection::Inbox(&mut connection), job).await;
-                    info = Default::default();
+/// Implements a single iteration of the inbox loop.
+///
+/// Performs the next job if there is one, otherwise does the periodic maintenance
+/// and fetches the inbox. Returns the interrupt info for the next iteration
+/// or an error if the iteration failed and should be retried after a delay.
+async fn inbox_loop_iteration(
+    ctx: &Context,
+    connection: &mut Imap,
+    info: &InterruptInfo,
+) -> Result<InterruptInfo> {
+    let job = job::load_next(ctx, info)
+        .await
+        .context("Failed loading job from the database")?;
+
+    match job {
+        Some(job) => {
+            job::perform_job(ctx, job::Connection::Inbox(connection), job).await;
+            Ok(InterruptInfo::default())
+        }
+        None => {
+            let quota_requested = ctx.quota_update_request.swap(false, Ordering::Relaxed);
+            if quota_requested {
+                if let Err(err) = ctx.update_recent_quota(connection).await {
+                    warn!(ctx, "Failed to update quota: {:#}.", err);
                 }
-                None => {
-                    let quota_requested = ctx.quota_update_request.swap(false, Ordering::Relaxed);
-                    if quota_requested {
-                        if let Err(err) = ctx.update_recent_quota(&mut connection).await {
-                            warn!(ctx, "Failed to update quota: {:#}.", err);
-                        }
-                    }
+            }
 
-                    let resync_requested = ctx.resync_request.swap(false, Ordering::Relaxed);
-                    if resync_requested {
-                        if let Err(err) = connection.resync_folders(&ctx).await {
-                            warn!(ctx, "Failed to resync folders: {:#}.", err);
-                            ctx.resync_request.store(true, Ordering::Relaxed);
-                        }
+            let resync_requested = ctx.resync_request.swap(false, Ordering::Relaxed);
+            if resync_requested {
+                if let Err(err) = connection.resync_folders(ctx).await {
+                    warn!(ctx, "Failed to resync folders: {:#}.", err);
+                    ctx.resync_request.store(true, Ordering::Relaxed);
+                }
+            }
+
+            maybe_add_time_based_warnings(ctx).await;
+
+            match ctx.get_config_i64(Config::LastHousekeeping).await {
+                Ok(last_housekeeping_time) => {
+                    let next_housekeeping_time =
+                        last_housekeeping_time.saturating_add(60 * 60 * 24);
+                    if next_housekeeping_time <= time() {
+                        sql::housekeeping(ctx).await.log_err(ctx).ok();
                     }
+                }
+                Err(err) => {
+                    warn!(ctx, "Failed to get last housekeeping time: {}", err);
+                }
+            };
+
+            if let Err(err) = ctx.maybe_update_device_list(connection).await {
+                warn!(ctx, "Failed to update device list: {:#}.", err);
+            }
 
-                    maybe_add_time_based_warnings(&ctx).await;
+            if let Err(err) = ctx.maybe_register_push_token(connection).await {
+                warn!(ctx, "Failed to register push device token: {:#}.", err);
+            }
 
-                    match ctx.get_config_i64(Config::LastHousekeeping).await {
-                        Ok(last_housekeeping_time) => {
-                            let next_housekeeping_time =
-                                last_housekeeping_time.saturating_add(60 * 60 * 24);
-                            if next_housekeeping_time <= time() {
-                                sql::housekeeping(&ctx).await.log_err(&ctx).ok();
-                            }
-                        }
-                        Err(err) => {
-                            warn!(ctx, "Failed to get last housekeeping time: {}", err);
-                        }
-                    };
+            if let Err(err) = blocklist::maybe_update_feeds(ctx).await {
+                warn!(ctx, "Failed to update blocklist feeds: {:#}.", err);
+            }
 
-                    match ctx.get_config_bool(Config::FetchedExistingMsgs).await {
-                        Ok(fetched_existing_msgs) => {
-                            if !fetched_existing_msgs {
-                                // Consider it done even if we fail.
-                                //
-                                // This operation is not critical enough to retry,
-                                // especially if the error is persistent.
-                                if let Err(err) =
-                                    ctx.set_config_bool(Config::FetchedExistingMsgs, true).await
-                                {
-                                    warn!(ctx, "Can't set Config::FetchedExistingMsgs: {:#}", err);
-                                }
-
-                                if let Err(err) = connection.fetch_existing_msgs(&ctx).await {
-                                    warn!(ctx, "Failed to fetch existing messages: {:#}", err);
-                                    connection.trigger_reconnect(&ctx);
-                                }
-                            }
+            match ctx.get_config_bool(Config::FetchedExistingMsgs).await {
+                Ok(fetched_existing_msgs) => {
+                    if !fetched_existing_msgs {
+                        // Consider it done even if we fail.
+                        //
+                        // This operation is not critical enough to retry,
+                        // especially if the error is persistent.
+                        if let Err(err) =
+                            ctx.set_config_bool(Config::FetchedExistingMsgs, true).await
+                        {
+                            warn!(ctx, "Can't set Config::FetchedExistingMsgs: {:#}", err);
                         }
-                        Err(err) => {
-                            warn!(ctx, "Can't get Config::FetchedExistingMsgs: {:#}", err);
+
+                        if let Err(err) = connection.fetch_existing_msgs(ctx).await {
+                            warn!(ctx, "Failed to fetch existing messages: {:#}", err);
+                            connection.trigger_reconnect(ctx);
                         }
                     }
+                }
+                Err(err) => {
+                    warn!(ctx, "Can't get Config::FetchedExistingMsgs: {:#}", err);
+                }
+            }
 
-                    info = fetch_idle(&ctx, &mut connection, FolderMeaning::Inbox).await;
+            match connection.backfill_msgs(ctx).await {
+                Ok(true) => {
+                    // Do not go IDLE for long, continue backfilling
+                    // after fetching new messages.
+                    ctx.scheduler
+                        .interrupt_inbox(InterruptInfo::new(false))
+                        .await;
+                }
+                Ok(false) => {}
+                Err(err) => {
+                    warn!(ctx, "Failed to backfill messages: {:#}", err);
+                    connection.trigger_reconnect(ctx);
                 }
             }
-        }
-    };
 
-    stop_receiver
-        .recv()
-        .map(|_| {
-            info!(ctx, "shutting down inbox loop");
-        })
-        .race(fut)
-        .await;
+            fetch_idle(ctx, connection, FolderMeaning::Inbox).await
+        }
+    }
 }
 
 /// Implement a single iteration of IMAP loop.
 ///
 /// This function performs all IMAP operations on a single folder, selecting it if necessary and
-/// handling all the errors. In case of an error, it is logged, but not propagated upwards. If
-/// critical operation fails such as fetching new messages fails, connection is reset via
-/// `trigger_reconnect`, so a fresh one can be opened.
+/// handling all the errors. Non-critical errors are logged, but not propagated upwards. If
+/// critical operation fails such as fetching new messages fails, the error is returned, so the
+/// [`Supervisor`] resets the connection via `trigger_reconnect` and retries after a delay.
 async fn fetch_idle(
     ctx: &Context,
     connection: &mut Imap,
     folder_meaning: FolderMeaning,
-) -> InterruptInfo {
+) -> Result<InterruptInfo> {
     let folder_config = match folder_meaning.to_config() {
         Some(c) => c,
         None => {
             error!(ctx, "Bad folder meaning: {}", folder_meaning);
-            return connection
+            return Ok(connection
                 .fake_idle(ctx, None, FolderMeaning::Unknown)
-                .await;
+                .await);
         }
     };
     let folder = match ctx.get_config(folder_config).await {
@@ -437,9 +484,9 @@ async fn fetch_idle(
                 ctx,
                 "Can not watch {} folder, failed to retrieve config: {:#}", folder_config, err
             );
-            return connection
+            return Ok(connection
                 .fake_idle(ctx, None, FolderMeaning::Unknown)
-                .await;
+                .await);
         }
     };
 
@@ -448,9 +495,9 @@ async fn fetch_idle(
     } else {
         connection.connectivity.set_not_configured(ctx).await;
         info!(ctx, "Can not watch {} folder, not set", folder_config);
-        return connection
+        return Ok(connection
             .fake_idle(ctx, None, FolderMeaning::Unknown)
-            .await;
+            .await);
     };
 
     // connect and fake idle if unable to connect
@@ -461,9 +508,9 @@ async fn fetch_idle(
     {
         warn!(ctx, "{:#}", err);
         connection.trigger_reconnect(ctx);
-        return connection
+        return Ok(connection
             .fake_idle(ctx, Some(watch_folder), folder_meaning)
-            .await;
+            .await);
     }
 
     if folder_config == Config::ConfiguredInboxFolder {
@@ -474,21 +521,22 @@ async fn fetch_idle(
                 .context("store_seen_flags_on_imap")
                 .log_err(ctx)
                 .ok();
+            session
+                .store_flagged_flags_on_imap(ctx)
+                .await
+                .context("store_flagged_flags_on_imap")
+                .log_err(ctx)
+                .ok();
         } else {
             warn!(ctx, "No session even though we just prepared it");
         }
     }
 
     // Fetch the watched folder.
-    if let Err(err) = connection
+    connection
         .fetch_move_delete(ctx, &watch_folder, folder_meaning)
         .await
-        .context("fetch_move_delete")
-    {
-        connection.trigger_reconnect(ctx);
-        warn!(ctx, "{:#}", err);
-        return InterruptInfo::new(false);
-    }
+        .context("fetch_move_delete")?;
 
     // Mark expired messages for deletion. Marked messages will be deleted from the server
     // on the next iteration of `fetch_move_delete`. `delete_expired_imap_messages` is not
@@ -499,6 +547,11 @@ async fn fetch_idle(
         .context("delete_expired_imap_messages")
         .log_err(ctx)
         .ok();
+    archive_old_imap_messages(ctx)
+        .await
+        .context("archive_old_imap_messages")
+        .log_err(ctx)
+        .ok();
 
     // Scan additional folders only after finishing fetching the watched folder.
     //
@@ -519,15 +572,10 @@ async fn fetch_idle(
                 // In most cases this will select the watched folder and return because there are
                 // no new messages. We want to select the watched folder anyway before going IDLE
                 // there, so this does not take additional protocol round-trip.
-                if let Err(err) = connection
+                connection
                     .fetch_move_delete(ctx, &watch_folder, folder_meaning)
                     .await
-                    .context("fetch_move_delete after scan_folders")
-                {
-                    connection.trigger_reconnect(ctx);
-                    warn!(ctx, "{:#}", err);
-                    return InterruptInfo::new(false);
-                }
+                    .context("fetch_move_delete after scan_folders")?;
             }
             Ok(false) => {}
         }
@@ -541,6 +589,18 @@ async fn fetch_idle(
         .log_err(ctx)
         .ok();
 
+    let poll_interval = ctx
+        .get_config_int(Config::PollInterval)
+        .await
+        .log_err(ctx)
+        .unwrap_or_default();
+    if poll_interval > 0 {
+        ctx.emit_event(EventType::ImapInboxIdle);
+        return Ok(connection
+            .poll_idle(ctx, Duration::from_secs(poll_interval as u64 * 60))
+            .await);
+    }
+
     connection.connectivity.set_connected(ctx).await;
 
     ctx.emit_event(EventType::ImapInboxIdle);
@@ -550,36 +610,27 @@ async fn fetch_idle(
                 ctx,
                 "IMAP session does not support IDLE, going to fake idle."
             );
-            return connection
+            return Ok(connection
                 .fake_idle(ctx, Some(watch_folder), folder_meaning)
-                .await;
+                .await);
         }
 
         info!(ctx, "IMAP session supports IDLE, using it.");
-        match session
+        let (session, info) = session
             .idle(
                 ctx,
                 connection.idle_interrupt_receiver.clone(),
                 Some(watch_folder),
             )
             .await
-            .context("idle")
-        {
-            Ok((session, info)) => {
-                connection.session = Some(session);
-                info
-            }
-            Err(err) => {
-                connection.trigger_reconnect(ctx);
-                warn!(ctx, "{:#}", err);
-                InterruptInfo::new(false)
-            }
-        }
+            .context("idle")?;
+        connection.session = Some(session);
+        Ok(info)
     } else {
         warn!(ctx, "No IMAP session, going to fake idle.");
-        connection
+        Ok(connection
             .fake_idle(ctx, Some(watch_folder), folder_meaning)
-            .await
+            .await)
     }
 }
 
@@ -606,8 +657,12 @@ async fn simple_imap_loop(
             return;
         }
 
+        let mut supervisor = Supervisor::new(folder_meaning.to_string());
         loop {
-            fetch_idle(&ctx, &mut connection, folder_meaning).await;
+            let iteration = fetch_idle(&ctx, &mut connection, folder_meaning);
+            if supervisor.run(&ctx, iteration).await.is_none() {
+                connection.trigger_reconnect(&ctx);
+            }
         }
     };
 
@@ -644,7 +699,11 @@ async fn smtp_loop(ctx: Context, started: Sender<()>, smtp_handlers: SmtpConnect
                 warn!(ctx, "send_smtp_messages failed: {:#}", err);
                 timeout = Some(timeout.map_or(30, |timeout: u64| timeout.saturating_mul(3)))
             } else {
-                let duration_until_can_send = ctx.ratelimit.read().await.until_can_send();
+                let duration_until_can_send = ctx
+                    .ratelimit
+                    .read()
+                    .await
+                    .until_can_send_at(ctx.clock.now());
                 if !duration_until_can_send.is_zero() {
                     info!(
                         ctx,
@@ -701,6 +760,73 @@ async fn smtp_loop(ctx: Context, started: Sender<()>, smtp_handlers: SmtpConnect
         .await;
 }
 
+/// Restarts failed iterations of the IMAP loops with backoff.
+///
+/// Without supervision a failing iteration, e.g. because the folder cannot be fetched,
+/// would be retried immediately over and over again.
+///
+/// Panics are not handled here: release builds are compiled with `panic = "abort"`,
+/// so a panic terminates the whole process rather than a single loop.
+/// The SMTP loop has its own retry timeout and is not supervised.
+#[derive(Debug)]
+struct Supervisor {
+    /// Name of the supervised loop for logging.
+    name: String,
+
+    /// Delay before restarting the loop after the last failure,
+    /// `None` if the last iteration succeeded.
+    backoff: Option<Duration>,
+}
+
+impl Supervisor {
+    /// Delay before restarting the loop after the first failure.
+    const MIN_BACKOFF: Duration = Duration::from_secs(5);
+
+    /// Maximum delay before restarting the loop after repeated failures.
+    const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+    fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            backoff: None,
+        }
+    }
+
+    /// Runs a single iteration of the loop.
+    ///
+    /// Returns the result of the iteration or `None` if it failed.
+    /// In this case an error is emitted and the restart is delayed,
+    /// doubling the delay on each consecutive failure.
+    async fn run<T>(
+        &mut self,
+        ctx: &Context,
+        iteration: impl Future<Output = Result<T>>,
+    ) -> Option<T> {
+        match iteration.await {
+            Ok(res) => {
+                self.backoff = None;
+                Some(res)
+            }
+            Err(err) => {
+                let backoff = self
+                    .backoff
+                    .map_or(Self::MIN_BACKOFF, |backoff| backoff * 2)
+                    .min(Self::MAX_BACKOFF);
+                self.backoff = Some(backoff);
+                error!(
+                    ctx,
+                    "{} loop failed: {:#}. Restarting in {}.",
+                    self.name,
+                    err,
+                    duration_to_str(backoff)
+                );
+                tokio::time::sleep(backoff).await;
+                None
+            }
+        }
+    }
+}
+
 impl Scheduler {
     /// Start the scheduler.
     pub async fn start(ctx: Context) -> Result<Self> {
@@ -1,5 +1,5 @@
 use core::fmt;
-use std::{iter::once, ops::Deref, sync::Arc};
+use std::{iter::once, ops::Deref, sync::Arc, time::Duration};
 
 use anyhow::{anyhow, Result};
 use humansize::{format_size, BINARY};
@@ -12,7 +12,7 @@
 };
 use crate::tools::time;
 use crate::{context::Context, log::LogExt};
-use crate::{stock_str, tools};
+use crate::{late_delivery, stock_str, tools};
 
 use super::InnerSchedulerState;
 
@@ -39,6 +39,9 @@ enum DetailedConnectivity {
     InterruptingIdle,
     Connected,
 
+    /// Connection is closed until the next poll at the given timestamp.
+    Polling(i64),
+
     /// The folder was configured not to be watched or configured_*_folder is not set
     NotConfigured,
 }
@@ -52,6 +55,7 @@ fn to_basic(&self) -> Option<Connectivity> {
             DetailedConnectivity::Working => Some(Connectivity::Working),
             DetailedConnectivity::InterruptingIdle => Some(Connectivity::Connected),
             DetailedConnectivity::Connected => Some(Connectivity::Connected),
+            DetailedConnectivity::Polling(_) => Some(Connectivity::Connected),
 
             // Just don't return a connectivity, probably the folder is configured not to be
             // watched or there is e.g. no "Sent" folder, so we are not interested in it
@@ -67,7 +71,8 @@ fn to_icon(&self) -> String {
             DetailedConnectivity::Connecting => "<span class=\"yellow dot\"></span>".to_string(),
             DetailedConnectivity::Working
             | DetailedConnectivity::InterruptingIdle
-            | DetailedConnectivity::Connected => "<span class=\"green dot\"></span>".to_string(),
+            | DetailedConnectivity::Connected
+            | DetailedConnectivity::Polling(_) => "<span class=\"green dot\"></span>".to_string(),
         }
     }
 
@@ -80,6 +85,14 @@ async fn to_string_imap(&self, context: &Context) -> String {
             DetailedConnectivity::InterruptingIdle | DetailedConnectivity::Connected => {
                 stock_str::connected(context).await
             }
+            DetailedConnectivity::Polling(next_poll) => {
+                let remaining = u64::try_from(next_poll.saturating_sub(time())).unwrap_or_default();
+                format!(
+                    "{}, next check in {}",
+                    stock_str::connected(context).await,
+                    tools::duration_to_str(Duration::from_secs(remaining))
+                )
+            }
             DetailedConnectivity::NotConfigured => "Not configured".to_string(),
         }
     }
@@ -96,7 +109,9 @@ async fn to_string_smtp(&self, context: &Context) -> String {
             // We don't know any more than that the last message was sent successfully;
             // since sending the last message, connectivity could have changed, which we don't notice
             // until another message is sent
-            DetailedConnectivity::InterruptingIdle | DetailedConnectivity::Connected => {
+            DetailedConnectivity::InterruptingIdle
+            | DetailedConnectivity::Connected
+            | DetailedConnectivity::Polling(_) => {
                 stock_str::last_msg_sent_successfully(context).await
             }
             DetailedConnectivity::NotConfigured => "Not configured".to_string(),
@@ -111,6 +126,7 @@ fn all_work_done(&self) -> bool {
             DetailedConnectivity::Working => false,
             DetailedConnectivity::InterruptingIdle => false,
             DetailedConnectivity::Connected => true,
+            DetailedConnectivity::Polling(_) => true,
             DetailedConnectivity::NotConfigured => true,
         }
     }
@@ -140,6 +156,10 @@ pub(crate) async fn set_working(&self, context: &Context) {
     pub(crate) async fn set_connected(&self, context: &Context) {
         self.set(context, DetailedConnectivity::Connected).await;
     }
+    pub(crate) async fn set_polling(&self, context: &Context, next_poll: i64) {
+        self.set(context, DetailedConnectivity::Polling(next_poll))
+            .await;
+    }
     pub(crate) async fn set_not_configured(&self, context: &Context) {
         self.set(context, DetailedConnectivity::NotConfigured).await;
     }
@@ -165,16 +185,22 @@ pub(crate) async fn idle_interrupted(inbox: ConnectivityStore, oboxes: Vec<Conne
     // returns Connected. But after dc_maybe_network(), dc_get_connectivity() must not
     // return Connected until DC is completely done with fetching folders; this also
     // includes scan_folders() which happens on the inbox thread.
-    if *connectivity_lock == DetailedConnectivity::Connected
-        || *connectivity_lock == DetailedConnectivity::NotConfigured
-    {
+    if matches!(
+        *connectivity_lock,
+        DetailedConnectivity::Connected
+            | DetailedConnectivity::Polling(_)
+            | DetailedConnectivity::NotConfigured
+    ) {
         *connectivity_lock = DetailedConnectivity::InterruptingIdle;
     }
     drop(connectivity_lock);
 
     for state in oboxes {
         let mut connectivity_lock = state.0.lock().await;
-        if *connectivity_lock == DetailedConnectivity::Connected {
+        if matches!(
+            *connectivity_lock,
+            DetailedConnectivity::Connected | DetailedConnectivity::Polling(_)
+        ) {
             *connectivity_lock = DetailedConnectivity::InterruptingIdle;
         }
     }
@@ -384,6 +410,29 @@ pub async fn get_connectivity_html(&self) -> Result<String> {
         ret += &*escaper::encode_minimal(&detailed.to_string_smtp(self).await);
         ret += "</li></ul>";
 
+        // =============================================================================================
+        // Add e.g.
+        //                              Late Deliveries
+        //                                example.org: 3 messages delivered up to 5 hours late
+        // =============================================================================================
+
+        let late_deliveries = late_delivery::get_late_deliveries(self).await?;
+        if !late_deliveries.is_empty() {
+            let title = stock_str::late_deliveries(self).await;
+            ret += &format!("<h3>{title}</h3><ul>");
+            for late in late_deliveries {
+                let text = stock_str::late_deliveries_from_domain(
+                    self,
+                    &late.domain,
+                    late.count,
+                    late.max_delay / 3600,
+                )
+                .await;
+                ret += &format!("<li>{}</li>", escaper::encode_minimal(&text));
+            }
+            ret += "</ul>";
+        }
+
         // =============================================================================================
         // Add e.g.
         //                              Storage on testrun.org
@@ -6,15 +6,16 @@
 use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 
 use crate::aheader::EncryptPreference;
-use crate::chat::{self, Chat, ChatId, ChatIdBlocked};
+use crate::chat::{self, Chat, ChatId, ChatIdBlocked, ProtectionStatus};
 use crate::config::Config;
-use crate::constants::Blocked;
+use crate::constants::{Blocked, Chattype};
 use crate::contact::{Contact, ContactId, Origin, VerifiedStatus};
 use crate::context::Context;
 use crate::e2ee::ensure_secret_key_exists;
 use crate::events::EventType;
 use crate::headerdef::HeaderDef;
 use crate::key::{DcKey, Fingerprint, SignedPublicKey};
+use crate::log::LogExt;
 use crate::message::{Message, Viewtype};
 use crate::mimeparser::{MimeMessage, SystemMessage};
 use crate::param::Param;
@@ -26,9 +27,21 @@
 
 mod bob;
 mod bobstate;
+mod group_invite;
+mod invite_link;
+mod join_gate;
 mod qrinvite;
 
 use bobstate::BobState;
+pub use group_invite::{accept_group_invite, forward_group_invite, get_group_invite, GroupInvite};
+pub use invite_link::{
+    create_invite_link, delete_invite_link, get_invite_link, get_invite_link_joins,
+    get_invite_links, InviteLink, InviteLinkJoin,
+};
+pub use join_gate::{
+    answer_join_challenge, get_join_challenge, is_join_gate_enabled, set_join_gate, JoinChallenge,
+    CHALLENGE_OPTIONS, MAX_CHALLENGE_ATTEMPTS,
+};
 use qrinvite::QrInvite;
 
 use crate::token::Namespace;
@@ -81,13 +94,7 @@ pub async fn get_securejoin_qr(context: &Context, group: Option<ChatId>) -> Resu
         }
     };
 
-    let self_addr_urlencoded =
-        utf8_percent_encode(&self_addr, NON_ALPHANUMERIC_WITHOUT_DOT).to_string();
-    let self_name_urlencoded =
-        utf8_percent_encode(&self_name, NON_ALPHANUMERIC_WITHOUT_DOT).to_string();
-
     let qr = if let Some(group) = group {
-        // parameters used: a=g=x=i=s=
         let chat = Chat::load_from_db(context, group).await?;
         if chat.grpid.is_empty() {
             bail!(
@@ -95,25 +102,19 @@ pub async fn get_securejoin_qr(context: &Context, group: Option<ChatId>) -> Resu
                 group
             );
         }
-        let group_name = chat.get_name();
-        let group_name_urlencoded = utf8_percent_encode(group_name, NON_ALPHANUMERIC).to_string();
         if sync_token {
             context.sync_qr_code_tokens(Some(chat.id)).await?;
         }
-        format!(
-            "OPENPGP4FPR:{}#a={}&g={}&x={}&i={}&s={}",
-            fingerprint.hex(),
-            self_addr_urlencoded,
-            &group_name_urlencoded,
-            &chat.grpid,
-            &invitenumber,
-            &auth,
-        )
+        format_group_qr(&fingerprint, &self_addr, &chat, &invitenumber, &auth)
     } else {
         // parameters used: a=n=i=s=
         if sync_token {
             context.sync_qr_code_tokens(None).await?;
         }
+        let self_addr_urlencoded =
+            utf8_percent_encode(&self_addr, NON_ALPHANUMERIC_WITHOUT_DOT).to_string();
+        let self_name_urlencoded =
+            utf8_percent_encode(&self_name, NON_ALPHANUMERIC_WITHOUT_DOT).to_string();
         format!(
             "OPENPGP4FPR:{}#a={}&n={}&i={}&s={}",
             fingerprint.hex(),
@@ -129,6 +130,29 @@ pub async fn get_securejoin_qr(context: &Context, group: Option<ChatId>) -> Resu
     Ok(qr)
 }
 
+/// Formats a Secure Join QR code for a group.
+fn format_group_qr(
+    fingerprint: &Fingerprint,
+    self_addr: &str,
+    chat: &Chat,
+    invitenumber: &str,
+    auth: &str,
+) -> String {
+    // parameters used: a=g=x=i=s=
+    let self_addr_urlencoded =
+        utf8_percent_encode(self_addr, NON_ALPHANUMERIC_WITHOUT_DOT).to_string();
+    let group_name_urlencoded = utf8_percent_encode(chat.get_name(), NON_ALPHANUMERIC).to_string();
+    format!(
+        "OPENPGP4FPR:{}#a={}&g={}&x={}&i={}&s={}",
+        fingerprint.hex(),
+        self_addr_urlencoded,
+        &group_name_urlencoded,
+        &chat.grpid,
+        invitenumber,
+        auth,
+    )
+}
+
 async fn get_self_fingerprint(context: &Context) -> Option<Fingerprint> {
     match SignedPublicKey::load_self(context).await {
         Ok(key) => Some(key.fingerprint()),
@@ -411,6 +435,16 @@ pub(crate) async fn handle_securejoin_handshake(
                 .await?;
                 return Ok(HandshakeMessage::Ignore);
             }
+            if invite_link::is_used_up(context, auth_0, contact_id).await? {
+                could_not_establish_secure_connection(
+                    context,
+                    contact_id,
+                    info_chat_id(context, contact_id).await?,
+                    "Invite link used up.",
+                )
+                .await?;
+                return Ok(HandshakeMessage::Ignore);
+            }
             let contact_addr = Contact::load_from_db(context, contact_id)
                 .await?
                 .get_addr()
@@ -446,11 +480,42 @@ pub(crate) async fn handle_securejoin_handshake(
                 match chat::get_chat_id_by_grpid(context, field_grpid).await? {
                     Some((group_chat_id, _, _)) => {
                         secure_connection_established(context, contact_id, group_chat_id).await?;
-                        if let Err(err) =
-                            chat::add_contact_to_chat_ex(context, group_chat_id, contact_id, true)
-                                .await
+                        if join_gate::is_join_gate_enabled(context, group_chat_id).await? {
+                            // the contact is added once the challenge is answered
+                            if join_gate::handle_request(
+                                context,
+                                mime_message,
+                                contact_id,
+                                group_chat_id,
+                                field_grpid,
+                                auth_0,
+                            )
+                            .await?
+                            {
+                                inviter_progress!(context, contact_id, 700);
+                            }
+                            return Ok(HandshakeMessage::Ignore);
+                        }
+                        match invite_link::add_joined_member(
+                            context,
+                            group_chat_id,
+                            contact_id,
+                            auth_0,
+                        )
+                        .await
                         {
-                            error!(context, "failed to add contact: {}", err);
+                            Ok(true) => {}
+                            Ok(false) => {
+                                could_not_establish_secure_connection(
+                                    context,
+                                    contact_id,
+                                    info_chat_id(context, contact_id).await?,
+                                    "Invite link used up.",
+                                )
+                                .await?;
+                                return Ok(HandshakeMessage::Ignore);
+                            }
+                            Err(err) => error!(context, "failed to add contact: {:#}", err),
                         }
                     }
                     None => bail!("Chat {} not found", &field_grpid),
@@ -478,6 +543,14 @@ pub(crate) async fn handle_securejoin_handshake(
             }
             Ok(HandshakeMessage::Ignore) // "Done" would delete the message and break multi-device (the key from Autocrypt-header is needed)
         }
+        "vg-invite" => group_invite::handle_invite(context, mime_message, contact_id).await,
+        "vg-invite-accepted" => {
+            group_invite::handle_invite_accepted(context, mime_message, contact_id).await
+        }
+        "vg-challenge" => join_gate::handle_challenge(context, mime_message, contact_id).await,
+        "vg-challenge-response" => {
+            join_gate::handle_challenge_response(context, mime_message, contact_id).await
+        }
         "vg-member-added" | "vc-contact-confirm" => {
             /*=======================================================
             ====             Bob - the joiner's side             ====
@@ -529,6 +602,68 @@ pub(crate) async fn handle_securejoin_handshake(
     }
 }
 
+/// Sends the new verified key of `addr` to all promoted protected groups with the peer.
+///
+/// Sent gossips are recorded per chat, so the same key is not gossiped to a group twice.
+async fn gossip_verified_key(
+    context: &Context,
+    addr: &str,
+    fingerprint: &Fingerprint,
+) -> Result<()> {
+    let contact_id = match Contact::lookup_id_by_addr(context, addr, Origin::Unknown).await? {
+        Some(contact_id) => contact_id,
+        None => return Ok(()),
+    };
+    let chat_ids = context
+        .sql
+        .query_map(
+            "SELECT c.id FROM chats c
+             INNER JOIN chats_contacts cc ON c.id=cc.chat_id
+             WHERE cc.contact_id=? AND c.type=? AND c.protected=? AND c.blocked=?
+             AND NOT EXISTS (SELECT 1 FROM verified_key_gossips g
+                             WHERE g.chat_id=c.id AND g.contact_id=cc.contact_id AND g.fingerprint=?)",
+            (
+                contact_id,
+                Chattype::Group,
+                ProtectionStatus::Protected,
+                Blocked::Not,
+                fingerprint.hex(),
+            ),
+            |row| row.get::<_, ChatId>(0),
+            |ids| ids.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await?;
+
+    for chat_id in chat_ids {
+        let chat = Chat::load_from_db(context, chat_id).await?;
+        if !chat.is_promoted() || !chat.is_self_in_chat(context).await? {
+            continue;
+        }
+        info!(
+            context,
+            "Gossiping new verified key of {contact_id} to {chat_id}."
+        );
+        let contact = Contact::get_by_id(context, contact_id).await?;
+        let mut msg = Message {
+            viewtype: Viewtype::Text,
+            text: Some(stock_str::contact_verified(context, &contact).await),
+            hidden: true,
+            ..Default::default()
+        };
+        msg.param.set_cmd(SystemMessage::KeyGossip);
+        chat::send_msg(context, chat_id, &mut msg).await?;
+        context
+            .sql
+            .execute(
+                "INSERT OR REPLACE INTO verified_key_gossips (chat_id, contact_id, fingerprint, timestamp)
+                 VALUES (?,?,?,?)",
+                (chat_id, contact_id, fingerprint.hex(), time()),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
 /// observe_securejoin_on_other_device() must be called when a self-sent securejoin message is seen.
 ///
 /// in a multi-device-setup, there may be other devices that "see" the handshake messages.
@@ -727,9 +862,10 @@ async fn mark_peer_as_verified(
     verifier: String,
 ) -> Result<()> {
     if let Some(ref mut peerstate) = Peerstate::from_fingerprint(context, &fingerprint).await? {
+        let old_fingerprint = peerstate.verified_key_fingerprint.clone();
         if let Err(err) = peerstate.set_verified(
             PeerstateKeyType::PublicKey,
-            fingerprint,
+            fingerprint.clone(),
             PeerstateVerifiedStatus::BidirectVerified,
             verifier,
         ) {
@@ -738,6 +874,16 @@ async fn mark_peer_as_verified(
         }
         peerstate.prefer_encrypt = EncryptPreference::Mutual;
         peerstate.save_to_db(&context.sql).await.unwrap_or_default();
+
+        if old_fingerprint.map_or(false, |old_fingerprint| old_fingerprint != fingerprint) {
+            // The peer was re-verified with a new key,
+            // other members of protected groups may still have the old one.
+            gossip_verified_key(context, &peerstate.addr, &fingerprint)
+                .await
+                .context("Failed to gossip new verified key")
+                .log_err(context)
+                .ok();
+        }
         Ok(())
     } else {
         bail!("no peerstate in db for fingerprint {}", fingerprint.hex());
@@ -1021,6 +1167,8 @@ async fn test_setup_contact_bob_knows_alice() -> Result<()> {
             gossip_key: Some(alice_pubkey.clone()),
             gossip_timestamp: 10,
             gossip_key_fingerprint: Some(alice_pubkey.fingerprint()),
+            gossip_source_addr: None,
+            gossip_source_fingerprint: None,
             verified_key: None,
             verified_key_fingerprint: None,
             fingerprint_changed: false,
@@ -1402,4 +1550,55 @@ async fn test_adhoc_group_no_qr() -> Result<()> {
         assert!(get_securejoin_qr(&alice, Some(chat_id)).await.is_err());
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_gossip_verified_key_after_reverification() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        tcm.send_recv_accept(&alice, &bob, "hi").await;
+        tcm.send_recv_accept(&bob, &alice, "hi").await;
+
+        let bob_fp = SignedPublicKey::load_self(&bob).await?.fingerprint();
+        let alice_fp = SignedPublicKey::load_self(&alice).await?.fingerprint();
+        mark_peer_as_verified(&alice, bob_fp, "bob@example.net".to_string()).await?;
+        mark_peer_as_verified(&bob, alice_fp, "alice@example.org".to_string()).await?;
+
+        let alice_chat_id =
+            chat::create_group_chat(&alice, ProtectionStatus::Protected, "Group").await?;
+        let alice_bob_id = alice.add_or_lookup_contact(&bob).await.id;
+        chat::add_contact_to_chat(&alice, alice_chat_id, alice_bob_id).await?;
+        let sent = alice.send_text(alice_chat_id, "Hello").await;
+        bob.recv_msg(&sent).await;
+
+        tcm.section("Bob changes his key and is verified again");
+        let mut keypair = crate::test_utils::fiona_keypair();
+        keypair.addr = EmailAddress::new("bob@example.net")?;
+        crate::key::store_self_keypair(&bob, &keypair, crate::key::KeyPairUse::Default).await?;
+        let bob_chat = bob.create_chat(&alice).await;
+        let sent = bob.send_text(bob_chat.id, "new key").await;
+        alice.recv_msg(&sent).await;
+        let new_fp = keypair.public.fingerprint();
+        mark_peer_as_verified(&alice, new_fp.clone(), "bob@example.net".to_string()).await?;
+
+        let sent = alice.pop_sent_msg().await;
+        let msg = Message::load_from_db(&alice, sent.sender_msg_id).await?;
+        assert_eq!(msg.chat_id, alice_chat_id);
+        assert!(msg.hidden);
+        assert_eq!(msg.param.get_cmd(), SystemMessage::KeyGossip);
+        let gossiped: Option<String> = alice
+            .sql
+            .query_get_value(
+                "SELECT fingerprint FROM verified_key_gossips WHERE chat_id=? AND contact_id=?",
+                (alice_chat_id, alice_bob_id),
+            )
+            .await?;
+        assert_eq!(gossiped, Some(new_fp.hex()));
+
+        // The key gossip is not shown to other members.
+        let received = bob.recv_msg_opt(&sent).await.unwrap();
+        assert!(received.chat_id.is_trash());
+
+        Ok(())
+    }
 }
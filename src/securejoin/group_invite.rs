@@ -0,0 +1,415 @@
+//! Forwarding of group invites to contacts verified by a group member.
+//!
+//! A member of a protected group can invite a contact it has already verified,
+//! e.g. using a setup-contact QR code, without the contact scanning a group QR code:
+//!
+//! 1. The member sends a `vg-invite` message with the group ID and name to the contact.
+//! 2. The contact accepts the invite with [`accept_group_invite`],
+//!    sending a `vg-invite-accepted` message back.
+//! 3. The member adds the contact to the group with a `vg-member-added` message.
+//!
+//! As in the QR code based protocol, the other members and the new member learn
+//! the keys from the gossip of the adding member, which is recorded as verifier.
+
+use anyhow::{ensure, Context as _, Result};
+
+use super::HandshakeMessage;
+use crate::chat::{self, is_contact_in_chat, Chat, ChatId, ChatIdBlocked};
+use crate::constants::{Blocked, Chattype};
+use crate::contact::{Contact, ContactId, VerifiedStatus};
+use crate::context::Context;
+use crate::events::EventType;
+use crate::headerdef::HeaderDef;
+use crate::message::{Message, Viewtype};
+use crate::mimeparser::{MimeMessage, SystemMessage};
+use crate::param::Param;
+use crate::peerstate::Peerstate;
+use crate::tools::time;
+
+/// Group invite received from a verified contact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupInvite {
+    /// ID of the invite, used to accept it.
+    pub id: u32,
+
+    /// Contact who forwarded the invite.
+    pub contact_id: ContactId,
+
+    /// Name of the group.
+    pub group_name: String,
+}
+
+/// Invites a verified contact to a protected group.
+///
+/// The contact is added to the group once it accepts the invite.
+/// [`EventType::SecurejoinInviterProgress`] events are emitted when the contact is added.
+pub async fn forward_group_invite(
+    context: &Context,
+    chat_id: ChatId,
+    contact_id: ContactId,
+) -> Result<()> {
+    let chat = Chat::load_from_db(context, chat_id).await?;
+    ensure!(
+        chat.typ == Chattype::Group && !chat.grpid.is_empty(),
+        "{chat_id} is not a group"
+    );
+    ensure!(
+        chat.is_protected(),
+        "Invites can only be forwarded for protected groups"
+    );
+    ensure!(
+        chat.is_self_in_chat(context).await?,
+        "Cannot invite to {chat_id}, self is not a member"
+    );
+    ensure!(
+        !is_contact_in_chat(context, chat_id, contact_id).await?,
+        "{contact_id} is already a member of {chat_id}"
+    );
+    let contact = Contact::get_by_id(context, contact_id).await?;
+    ensure!(
+        contact.is_verified(context).await? == VerifiedStatus::BidirectVerified,
+        "{contact_id} is not verified"
+    );
+
+    save_invite(context, contact_id, &chat.grpid, chat.get_name(), true).await?;
+    send_invite_msg(
+        context,
+        contact_id,
+        "vg-invite",
+        &chat.grpid,
+        Some(chat.get_name()),
+    )
+    .await
+    .context("Failed to send vg-invite message")?;
+    Ok(())
+}
+
+/// Returns the received group invite with the given ID.
+pub async fn get_group_invite(context: &Context, invite_id: u32) -> Result<Option<GroupInvite>> {
+    context
+        .sql
+        .query_row_optional(
+            "SELECT contact_id, name FROM group_invites WHERE id=? AND outgoing=0",
+            (invite_id,),
+            |row| {
+                Ok(GroupInvite {
+                    id: invite_id,
+                    contact_id: row.get(0)?,
+                    group_name: row.get(1)?,
+                })
+            },
+        )
+        .await
+}
+
+/// Accepts a group invite received from a verified contact.
+///
+/// The group is created once the inviting contact adds us to it.
+pub async fn accept_group_invite(context: &Context, invite_id: u32) -> Result<()> {
+    let (contact_id, grpid): (ContactId, String) = context
+        .sql
+        .query_row_optional(
+            "SELECT contact_id, grpid FROM group_invites WHERE id=? AND outgoing=0",
+            (invite_id,),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .await?
+        .with_context(|| format!("Group invite {invite_id} not found"))?;
+    let contact = Contact::get_by_id(context, contact_id).await?;
+    ensure!(
+        contact.is_verified(context).await? == VerifiedStatus::BidirectVerified,
+        "{contact_id} is not verified anymore"
+    );
+
+    send_invite_msg(context, contact_id, "vg-invite-accepted", &grpid, None)
+        .await
+        .context("Failed to send vg-invite-accepted message")?;
+    delete_invite(context, contact_id, &grpid, false).await?;
+    Ok(())
+}
+
+/// Handles a `vg-invite` message on the side of the invited contact.
+pub(super) async fn handle_invite(
+    context: &Context,
+    mime_message: &MimeMessage,
+    contact_id: ContactId,
+) -> Result<HandshakeMessage> {
+    let grpid = match mime_message.get_header(HeaderDef::SecureJoinGroup) {
+        Some(grpid) => grpid,
+        None => {
+            warn!(
+                context,
+                "Ignoring vg-invite without Secure-Join-Group header."
+            );
+            return Ok(HandshakeMessage::Ignore);
+        }
+    };
+    if !is_verified_sender(context, mime_message, contact_id).await? {
+        warn!(context, "Ignoring vg-invite from unverified {contact_id}.");
+        return Ok(HandshakeMessage::Ignore);
+    }
+    if let Some((chat_id, _, _)) = chat::get_chat_id_by_grpid(context, grpid).await? {
+        if is_contact_in_chat(context, chat_id, ContactId::SELF).await? {
+            info!(
+                context,
+                "Ignoring vg-invite for {chat_id}, already a member."
+            );
+            return Ok(HandshakeMessage::Ignore);
+        }
+    }
+
+    let group_name = mime_message
+        .get_header(HeaderDef::ChatGroupName)
+        .map(|name| name.as_str())
+        .unwrap_or_default();
+    let invite_id = save_invite(context, contact_id, grpid, group_name, false).await?;
+    context.emit_event(EventType::SecurejoinInviteReceived {
+        contact_id,
+        invite_id,
+    });
+    Ok(HandshakeMessage::Ignore)
+}
+
+/// Handles a `vg-invite-accepted` message on the side of the inviting member
+/// by adding the contact to the group.
+pub(super) async fn handle_invite_accepted(
+    context: &Context,
+    mime_message: &MimeMessage,
+    contact_id: ContactId,
+) -> Result<HandshakeMessage> {
+    let grpid = match mime_message.get_header(HeaderDef::SecureJoinGroup) {
+        Some(grpid) => grpid,
+        None => {
+            warn!(
+                context,
+                "Ignoring vg-invite-accepted without Secure-Join-Group header."
+            );
+            return Ok(HandshakeMessage::Ignore);
+        }
+    };
+    let invited = context
+        .sql
+        .exists(
+            "SELECT COUNT(*) FROM group_invites WHERE contact_id=? AND grpid=? AND outgoing=1",
+            (contact_id, grpid),
+        )
+        .await?;
+    if !invited {
+        warn!(
+            context,
+            "Ignoring vg-invite-accepted from {contact_id}, no invite was forwarded."
+        );
+        return Ok(HandshakeMessage::Ignore);
+    }
+    if !is_verified_sender(context, mime_message, contact_id).await? {
+        warn!(
+            context,
+            "Ignoring vg-invite-accepted from unverified {contact_id}."
+        );
+        return Ok(HandshakeMessage::Ignore);
+    }
+    let chat_id = match chat::get_chat_id_by_grpid(context, grpid).await? {
+        Some((chat_id, _, _)) => chat_id,
+        None => {
+            warn!(context, "Ignoring vg-invite-accepted for unknown group.");
+            return Ok(HandshakeMessage::Ignore);
+        }
+    };
+
+    delete_invite(context, contact_id, grpid, true).await?;
+    chat::add_contact_to_chat_ex(context, chat_id, contact_id, true).await?;
+    for progress in [800, 1000] {
+        context.emit_event(EventType::SecurejoinInviterProgress {
+            contact_id,
+            progress,
+        });
+    }
+    Ok(HandshakeMessage::Ignore)
+}
+
+/// Returns true if the contact is verified
+/// and the message is signed with the verified key of the contact.
+async fn is_verified_sender(
+    context: &Context,
+    mime_message: &MimeMessage,
+    contact_id: ContactId,
+) -> Result<bool> {
+    if !mime_message.was_encrypted() {
+        return Ok(false);
+    }
+    let contact = Contact::get_by_id(context, contact_id).await?;
+    let peerstate = match Peerstate::from_addr(context, contact.get_addr()).await? {
+        Some(peerstate) => peerstate,
+        None => return Ok(false),
+    };
+    Ok(
+        contact.is_verified_ex(context, Some(&peerstate)).await?
+            == VerifiedStatus::BidirectVerified
+            && peerstate.has_verified_key(&mime_message.signatures),
+    )
+}
+
+async fn send_invite_msg(
+    context: &Context,
+    contact_id: ContactId,
+    step: &str,
+    grpid: &str,
+    group_name: Option<&str>,
+) -> Result<()> {
+    let mut msg = Message {
+        viewtype: Viewtype::Text,
+        text: Some(format!("Secure-Join: {step}")),
+        hidden: true,
+        ..Default::default()
+    };
+    msg.param.set_cmd(SystemMessage::SecurejoinMessage);
+    msg.param.set(Param::Arg, step);
+    if let Some(group_name) = group_name {
+        msg.param.set(Param::Arg2, group_name);
+    }
+    msg.param.set(Param::Arg4, grpid);
+    msg.param.set_int(Param::GuaranteeE2ee, 1);
+    chat::send_msg(
+        context,
+        ChatIdBlocked::get_for_contact(context, contact_id, Blocked::Yes)
+            .await?
+            .id,
+        &mut msg,
+    )
+    .await?;
+    Ok(())
+}
+
+async fn save_invite(
+    context: &Context,
+    contact_id: ContactId,
+    grpid: &str,
+    group_name: &str,
+    outgoing: bool,
+) -> Result<u32> {
+    context
+        .sql
+        .execute(
+            "INSERT INTO group_invites (contact_id, grpid, name, outgoing, timestamp)
+             VALUES (?,?,?,?,?)
+             ON CONFLICT(contact_id, grpid, outgoing)
+             DO UPDATE SET name=excluded.name, timestamp=excluded.timestamp",
+            (contact_id, grpid, group_name, outgoing, time()),
+        )
+        .await?;
+    let invite_id = context
+        .sql
+        .query_get_value(
+            "SELECT id FROM group_invites WHERE contact_id=? AND grpid=? AND outgoing=?",
+            (contact_id, grpid, outgoing),
+        )
+        .await?
+        .context("Group invite not saved")?;
+    Ok(invite_id)
+}
+
+async fn delete_invite(
+    context: &Context,
+    contact_id: ContactId,
+    grpid: &str,
+    outgoing: bool,
+) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "DELETE FROM group_invites WHERE contact_id=? AND grpid=? AND outgoing=?",
+            (contact_id, grpid, outgoing),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::ProtectionStatus;
+    use crate::key::{DcKey, SignedPublicKey};
+    use crate::securejoin::mark_peer_as_verified;
+    use crate::test_utils::{TestContext, TestContextManager};
+
+    /// Marks `other` as verified by `t`, they must have exchanged keys before.
+    async fn verify(t: &TestContext, other: &TestContext) -> Result<ContactId> {
+        let contact = t.add_or_lookup_contact(other).await;
+        let fingerprint = SignedPublicKey::load_self(other).await?.fingerprint();
+        mark_peer_as_verified(t, fingerprint, contact.get_addr().to_string()).await?;
+        Ok(contact.id)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_forward_group_invite() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let fiona = tcm.fiona().await;
+
+        tcm.send_recv_accept(&alice, &bob, "hi").await;
+        tcm.send_recv_accept(&bob, &alice, "hi").await;
+        tcm.send_recv_accept(&bob, &fiona, "hi").await;
+        tcm.send_recv_accept(&fiona, &bob, "hi").await;
+        let alice_bob_id = verify(&alice, &bob).await?;
+        let bob_alice_id = verify(&bob, &alice).await?;
+        let bob_fiona_id = verify(&bob, &fiona).await?;
+        let fiona_bob_id = verify(&fiona, &bob).await?;
+
+        tcm.section("Alice creates a protected group with Bob");
+        let alice_chat_id =
+            chat::create_group_chat(&alice, ProtectionStatus::Protected, "Group").await?;
+        chat::add_contact_to_chat(&alice, alice_chat_id, alice_bob_id).await?;
+        let sent = alice.send_text(alice_chat_id, "Welcome").await;
+        let bob_chat_id = bob.recv_msg(&sent).await.chat_id;
+        assert!(Chat::load_from_db(&bob, bob_chat_id).await?.is_protected());
+
+        tcm.section("Bob forwards the invite to Fiona");
+        assert!(forward_group_invite(&bob, bob_chat_id, bob_alice_id)
+            .await
+            .is_err());
+        forward_group_invite(&bob, bob_chat_id, bob_fiona_id).await?;
+        fiona.recv_msg(&bob.pop_sent_msg().await).await;
+        let invite_id = match fiona
+            .evtracker
+            .get_matching(|evt| matches!(evt, EventType::SecurejoinInviteReceived { .. }))
+            .await
+        {
+            EventType::SecurejoinInviteReceived {
+                contact_id,
+                invite_id,
+            } => {
+                assert_eq!(contact_id, fiona_bob_id);
+                invite_id
+            }
+            _ => unreachable!(),
+        };
+        let invite = get_group_invite(&fiona, invite_id).await?.unwrap();
+        assert_eq!(invite.contact_id, fiona_bob_id);
+        assert_eq!(invite.group_name, "Group");
+
+        tcm.section("Fiona accepts, Bob adds her to the group");
+        accept_group_invite(&fiona, invite_id).await?;
+        assert!(get_group_invite(&fiona, invite_id).await?.is_none());
+        bob.recv_msg(&fiona.pop_sent_msg().await).await;
+        let sent = bob.pop_sent_msg().await;
+
+        let fiona_chat_id = fiona.recv_msg(&sent).await.chat_id;
+        let fiona_chat = Chat::load_from_db(&fiona, fiona_chat_id).await?;
+        assert!(fiona_chat.is_protected());
+        assert_eq!(
+            fiona_chat.grpid,
+            Chat::load_from_db(&bob, bob_chat_id).await?.grpid
+        );
+
+        alice.recv_msg(&sent).await;
+        let alice_fiona = alice.add_or_lookup_contact(&fiona).await;
+        assert!(is_contact_in_chat(&alice, alice_chat_id, alice_fiona.id).await?);
+        assert_eq!(
+            alice_fiona.get_verifier_addr(&alice).await?,
+            Some("bob@example.net".to_string())
+        );
+
+        Ok(())
+    }
+}
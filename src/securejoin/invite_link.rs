@@ -0,0 +1,412 @@
+//! Invite links with usage limits.
+//!
+//! Besides the QR code returned by [`get_securejoin_qr`](super::get_securejoin_qr),
+//! which can be used by any number of contacts,
+//! a group may have invite links created with [`create_invite_link`].
+//! Each of them has its own invite number and auth token
+//! and can be used to join the group at most `max_uses` times.
+//! Contacts who joined through an invite link are logged,
+//! see [`get_invite_link_joins`].
+//!
+//! Invite links are not synchronized to other devices
+//! as uses are counted on the device that created the link.
+
+use anyhow::{bail, ensure, Context as _, Result};
+
+use super::{format_group_qr, get_self_fingerprint};
+use crate::chat::{self, Chat, ChatId};
+use crate::constants::Chattype;
+use crate::contact::ContactId;
+use crate::context::Context;
+use crate::e2ee::ensure_secret_key_exists;
+use crate::token::Namespace;
+use crate::tools::{create_id, time};
+
+/// Invite link of a group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InviteLink {
+    /// ID of the invite link.
+    pub id: u32,
+
+    /// QR code to join the group.
+    pub qr: String,
+
+    /// Maximum number of contacts who may join through the link.
+    pub max_uses: u32,
+
+    /// Number of contacts who joined through the link.
+    pub uses: u32,
+
+    /// Timestamp of the creation of the link.
+    pub timestamp: i64,
+}
+
+/// Contact who joined a group through an invite link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InviteLinkJoin {
+    /// Joined contact.
+    pub contact_id: ContactId,
+
+    /// Timestamp of the join.
+    pub timestamp: i64,
+}
+
+/// Creates an invite link for a group which can be used at most `max_uses` times
+/// and returns its QR code.
+pub async fn create_invite_link(
+    context: &Context,
+    chat_id: ChatId,
+    max_uses: u32,
+) -> Result<String> {
+    ensure!(max_uses > 0, "Invite link must be usable at least once");
+    let chat = Chat::load_from_db(context, chat_id).await?;
+    ensure!(
+        chat.typ == Chattype::Group && !chat.grpid.is_empty(),
+        "{chat_id} is not a group"
+    );
+    ensure_secret_key_exists(context).await.ok();
+
+    let invitenumber = create_id();
+    let auth = create_id();
+    let timestamp = time();
+    context
+        .sql
+        .transaction(|transaction| {
+            transaction.execute(
+                "INSERT INTO tokens (namespc, foreign_id, token, timestamp, max_uses)
+                 VALUES (?, ?, ?, ?, ?)",
+                (
+                    Namespace::InviteNumber,
+                    chat_id,
+                    &invitenumber,
+                    timestamp,
+                    max_uses,
+                ),
+            )?;
+            transaction.execute(
+                "INSERT INTO tokens (namespc, foreign_id, token, timestamp, max_uses, invitenumber)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                (
+                    Namespace::Auth,
+                    chat_id,
+                    &auth,
+                    timestamp,
+                    max_uses,
+                    &invitenumber,
+                ),
+            )?;
+            Ok(())
+        })
+        .await?;
+    group_qr(context, &chat, &invitenumber, &auth).await
+}
+
+/// Returns the invite links of a group which can still be used, newest first.
+pub async fn get_invite_links(context: &Context, chat_id: ChatId) -> Result<Vec<InviteLink>> {
+    let chat = Chat::load_from_db(context, chat_id).await?;
+    let rows = context
+        .sql
+        .query_map(
+            "SELECT id, token, invitenumber, max_uses, uses, timestamp FROM tokens
+             WHERE namespc=? AND foreign_id=? AND max_uses>0 AND uses<max_uses
+             ORDER BY timestamp DESC, id DESC",
+            (Namespace::Auth, chat_id),
+            |row| {
+                let id: u32 = row.get(0)?;
+                let auth: String = row.get(1)?;
+                let invitenumber: String = row.get(2)?;
+                let max_uses: u32 = row.get(3)?;
+                let uses: u32 = row.get(4)?;
+                let timestamp: i64 = row.get(5)?;
+                Ok((id, auth, invitenumber, max_uses, uses, timestamp))
+            },
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await?;
+
+    let mut links = Vec::new();
+    for (id, auth, invitenumber, max_uses, uses, timestamp) in rows {
+        links.push(InviteLink {
+            id,
+            qr: group_qr(context, &chat, &invitenumber, &auth).await?,
+            max_uses,
+            uses,
+            timestamp,
+        });
+    }
+    Ok(links)
+}
+
+/// Returns an invite link by its ID, also if it is used up.
+pub async fn get_invite_link(context: &Context, id: u32) -> Result<Option<InviteLink>> {
+    let row = context
+        .sql
+        .query_row_optional(
+            "SELECT foreign_id, token, invitenumber, max_uses, uses, timestamp FROM tokens
+             WHERE id=? AND namespc=? AND max_uses>0",
+            (id, Namespace::Auth),
+            |row| {
+                let chat_id: ChatId = row.get(0)?;
+                let auth: String = row.get(1)?;
+                let invitenumber: String = row.get(2)?;
+                let max_uses: u32 = row.get(3)?;
+                let uses: u32 = row.get(4)?;
+                let timestamp: i64 = row.get(5)?;
+                Ok((chat_id, auth, invitenumber, max_uses, uses, timestamp))
+            },
+        )
+        .await?;
+    let (chat_id, auth, invitenumber, max_uses, uses, timestamp) = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let chat = Chat::load_from_db(context, chat_id).await?;
+    Ok(Some(InviteLink {
+        id,
+        qr: group_qr(context, &chat, &invitenumber, &auth).await?,
+        max_uses,
+        uses,
+        timestamp,
+    }))
+}
+
+/// Deletes an invite link, it cannot be used to join the group anymore.
+pub async fn delete_invite_link(context: &Context, id: u32) -> Result<()> {
+    let invitenumber: String = context
+        .sql
+        .query_get_value(
+            "SELECT invitenumber FROM tokens WHERE id=? AND namespc=? AND max_uses>0",
+            (id, Namespace::Auth),
+        )
+        .await?
+        .with_context(|| format!("Invite link {id} not found"))?;
+    context
+        .sql
+        .transaction(|transaction| {
+            transaction.execute(
+                "DELETE FROM tokens WHERE namespc=? AND token=? AND max_uses>0",
+                (Namespace::InviteNumber, invitenumber),
+            )?;
+            transaction.execute("DELETE FROM tokens WHERE id=?", (id,))?;
+            transaction.execute("DELETE FROM invite_link_joins WHERE token_id=?", (id,))?;
+            Ok(())
+        })
+        .await?;
+    Ok(())
+}
+
+/// Returns the contacts who joined through an invite link, in the order they joined.
+pub async fn get_invite_link_joins(context: &Context, id: u32) -> Result<Vec<InviteLinkJoin>> {
+    context
+        .sql
+        .query_map(
+            "SELECT contact_id, timestamp FROM invite_link_joins WHERE token_id=? ORDER BY id",
+            (id,),
+            |row| {
+                Ok(InviteLinkJoin {
+                    contact_id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                })
+            },
+            |rows| rows.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+        )
+        .await
+}
+
+/// Returns true if `auth` belongs to an invite link which was used `max_uses` times
+/// by contacts other than `contact_id`.
+pub(super) async fn is_used_up(
+    context: &Context,
+    auth: &str,
+    contact_id: ContactId,
+) -> Result<bool> {
+    context
+        .sql
+        .exists(
+            "SELECT COUNT(*) FROM tokens t
+             WHERE t.namespc=? AND t.token=? AND t.max_uses>0 AND t.uses>=t.max_uses
+             AND NOT EXISTS (SELECT 1 FROM invite_link_joins j
+                             WHERE j.token_id=t.id AND j.contact_id=?)",
+            (Namespace::Auth, auth, contact_id),
+        )
+        .await
+}
+
+/// Adds a contact who joined with the auth token `auth` to the group.
+///
+/// If `auth` belongs to an invite link, the use is counted once per contact
+/// as the contact is added.
+/// Returns false without adding the contact if the invite link is used up meanwhile.
+pub(super) async fn add_joined_member(
+    context: &Context,
+    chat_id: ChatId,
+    contact_id: ContactId,
+    auth: &str,
+) -> Result<bool> {
+    let id: Option<u32> = context
+        .sql
+        .query_get_value(
+            "SELECT id FROM tokens WHERE namespc=? AND token=? AND max_uses>0",
+            (Namespace::Auth, auth),
+        )
+        .await?;
+    let id = match id {
+        Some(id) => id,
+        None => {
+            chat::add_contact_to_chat_ex(context, chat_id, contact_id, true).await?;
+            return Ok(true);
+        }
+    };
+
+    // Counting the use and checking the limit is one statement,
+    // so the link cannot be used more than `max_uses` times.
+    let counted = context
+        .sql
+        .transaction(move |transaction| {
+            let joined: bool = transaction.query_row(
+                "SELECT COUNT(*) FROM invite_link_joins WHERE token_id=? AND contact_id=?",
+                (id, contact_id),
+                |row| row.get(0),
+            )?;
+            if joined {
+                return Ok(Some(false));
+            }
+            if transaction.execute(
+                "UPDATE tokens SET uses=uses+1 WHERE id=? AND uses<max_uses",
+                (id,),
+            )? == 0
+            {
+                return Ok(None);
+            }
+            transaction.execute(
+                "INSERT INTO invite_link_joins (token_id, contact_id, timestamp) VALUES (?, ?, ?)",
+                (id, contact_id, time()),
+            )?;
+            Ok(Some(true))
+        })
+        .await?;
+    let counted = match counted {
+        Some(counted) => counted,
+        None => {
+            warn!(
+                context,
+                "Invite link {id} is used up, not adding {contact_id} to {chat_id}."
+            );
+            return Ok(false);
+        }
+    };
+
+    if let Err(err) = chat::add_contact_to_chat_ex(context, chat_id, contact_id, true).await {
+        if counted {
+            context
+                .sql
+                .transaction(move |transaction| {
+                    transaction.execute("UPDATE tokens SET uses=uses-1 WHERE id=?", (id,))?;
+                    transaction.execute(
+                        "DELETE FROM invite_link_joins WHERE token_id=? AND contact_id=?",
+                        (id, contact_id),
+                    )?;
+                    Ok(())
+                })
+                .await?;
+        }
+        return Err(err);
+    }
+    if counted {
+        info!(context, "{contact_id} joined through invite link {id}.");
+    }
+    Ok(true)
+}
+
+async fn group_qr(
+    context: &Context,
+    chat: &Chat,
+    invitenumber: &str,
+    auth: &str,
+) -> Result<String> {
+    let fingerprint = match get_self_fingerprint(context).await {
+        Some(fingerprint) => fingerprint,
+        None => bail!("No fingerprint, cannot generate QR code."),
+    };
+    let self_addr = context.get_primary_self_addr().await?;
+    Ok(format_group_qr(
+        &fingerprint,
+        &self_addr,
+        chat,
+        invitenumber,
+        auth,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::{create_group_chat, is_contact_in_chat, ProtectionStatus};
+    use crate::securejoin::{get_securejoin_qr, join_securejoin};
+    use crate::test_utils::{TestContext, TestContextManager};
+
+    async fn join(alice: &TestContext, joiner: &TestContext, qr: &str) {
+        join_securejoin(joiner, qr).await.unwrap();
+        alice.recv_msg(&joiner.pop_sent_msg().await).await;
+        joiner.recv_msg(&alice.pop_sent_msg().await).await;
+        alice.recv_msg(&joiner.pop_sent_msg().await).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_invite_link() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let fiona = tcm.fiona().await;
+
+        let chat_id = create_group_chat(&alice, ProtectionStatus::Unprotected, "group").await?;
+        assert!(create_invite_link(&alice, chat_id, 0).await.is_err());
+        let qr = create_invite_link(&alice, chat_id, 1).await?;
+        let links = get_invite_links(&alice, chat_id).await?;
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].qr, qr);
+        assert_eq!(links[0].max_uses, 1);
+        assert_eq!(links[0].uses, 0);
+        let id = links[0].id;
+        assert_eq!(get_invite_link(&alice, id).await?.unwrap(), links[0]);
+
+        // The regular QR code is not affected by invite links.
+        let regular_qr = get_securejoin_qr(&alice, Some(chat_id)).await?;
+        assert_ne!(regular_qr, qr);
+        assert_eq!(get_invite_links(&alice, chat_id).await?.len(), 1);
+
+        join(&alice, &bob, &qr).await;
+        let alice_bob_id = alice.add_or_lookup_contact(&bob).await.id;
+        assert!(is_contact_in_chat(&alice, chat_id, alice_bob_id).await?);
+        let joins = get_invite_link_joins(&alice, id).await?;
+        assert_eq!(joins.len(), 1);
+        assert_eq!(joins[0].contact_id, alice_bob_id);
+        assert!(get_invite_links(&alice, chat_id).await?.is_empty());
+        assert_eq!(get_invite_link(&alice, id).await?.unwrap().uses, 1);
+
+        // The used up link cannot be used anymore.
+        join(&alice, &fiona, &qr).await;
+        let alice_fiona_id = alice.add_or_lookup_contact(&fiona).await.id;
+        assert!(!is_contact_in_chat(&alice, chat_id, alice_fiona_id).await?);
+        assert_eq!(get_invite_link_joins(&alice, id).await?.len(), 1);
+
+        // Uses are counted once per contact.
+        let auth: String = alice
+            .sql
+            .query_get_value("SELECT token FROM tokens WHERE id=?", (id,))
+            .await?
+            .unwrap();
+        assert!(is_used_up(&alice, &auth, alice_fiona_id).await?);
+        assert!(!is_used_up(&alice, &auth, alice_bob_id).await?);
+        assert!(!add_joined_member(&alice, chat_id, alice_fiona_id, &auth).await?);
+        assert!(add_joined_member(&alice, chat_id, alice_bob_id, &auth).await?);
+        assert_eq!(get_invite_link(&alice, id).await?.unwrap().uses, 1);
+        assert_eq!(get_invite_link_joins(&alice, id).await?.len(), 1);
+
+        delete_invite_link(&alice, id).await?;
+        assert!(get_invite_link_joins(&alice, id).await?.is_empty());
+        assert!(get_invite_link(&alice, id).await?.is_none());
+        assert!(delete_invite_link(&alice, id).await.is_err());
+        Ok(())
+    }
+}
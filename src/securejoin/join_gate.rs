@@ -0,0 +1,606 @@
+//! Join gate for groups joined via QR code.
+//!
+//! QR codes of groups may be published, e.g. on a website, where spam bots can scan them as well.
+//! If the join gate is enabled for a group with [`set_join_gate`],
+//! a joiner is not added right after a valid `vg-request-with-auth` message:
+//!
+//! 1. The inviter sends a `vg-challenge` message asking to select one of [`CHALLENGE_OPTIONS`].
+//!    The option is named in the language of the inviter, e.g. "select the cat",
+//!    so the question does not contain the answer.
+//! 2. The joiner gets [`EventType::SecurejoinChallengeReceived`];
+//!    the UI shows the question and the options returned by [`get_join_challenge`] as buttons
+//!    and sends the selected option with [`answer_join_challenge`]
+//!    in a `vg-challenge-response` message.
+//! 3. If the answer is right, the inviter adds the joiner to the group as usual.
+//!    Otherwise a new challenge is sent;
+//!    after [`MAX_CHALLENGE_ATTEMPTS`] wrong answers, the joiner is not added
+//!    and further requests of the joiner to join the group are refused.
+//!    The joiner can still be added to the group manually.
+//!
+//! Joiners announce that they can answer challenges
+//! with the `Secure-Join-Features: challenge` header of the `vg-request-with-auth` message.
+//! Joiners using older apps are not added,
+//! instead an info message is added to the group, so they can be added manually.
+//!
+//! The challenge stops bots that only run the Secure-Join protocol,
+//! not bots written to answer it.
+
+use anyhow::{ensure, Context as _, Result};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+
+use super::bobstate::BobState;
+use super::invite_link;
+use super::qrinvite::QrInvite;
+use super::HandshakeMessage;
+use crate::chat::{self, Chat, ChatId, ChatIdBlocked};
+use crate::constants::{Blocked, Chattype};
+use crate::contact::ContactId;
+use crate::context::Context;
+use crate::events::EventType;
+use crate::headerdef::HeaderDef;
+use crate::message::{Message, Viewtype};
+use crate::mimeparser::{MimeMessage, SystemMessage};
+use crate::param::Param;
+use crate::stock_str;
+use crate::tools::time;
+
+/// Options a joiner selects from to answer a challenge.
+pub const CHALLENGE_OPTIONS: [&str; 8] = ["🍎", "🚲", "🐱", "🌳", "🏠", "⭐", "🎈", "🔑"];
+
+/// Number of wrong answers after which a joiner is not added.
+pub const MAX_CHALLENGE_ATTEMPTS: u32 = 3;
+
+/// Join gate challenge received from the inviter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinChallenge {
+    /// ID of the challenge, used to answer it.
+    pub id: u32,
+
+    /// Inviting contact.
+    pub contact_id: ContactId,
+
+    /// Question to show, asking to select one of the options.
+    pub question: String,
+
+    /// Options to select from, in the order to show them.
+    pub options: Vec<String>,
+}
+
+/// Enables or disables the join gate of a group.
+///
+/// The join gate only affects contacts joining via QR code.
+pub async fn set_join_gate(context: &Context, chat_id: ChatId, enabled: bool) -> Result<()> {
+    let mut chat = Chat::load_from_db(context, chat_id).await?;
+    ensure!(
+        chat.typ == Chattype::Group && !chat.grpid.is_empty(),
+        "{chat_id} is not a group"
+    );
+    if enabled {
+        chat.param.set_int(Param::JoinGate, 1);
+    } else {
+        chat.param.remove(Param::JoinGate);
+    }
+    chat.update_param(context).await?;
+    context.emit_event(EventType::ChatModified(chat_id));
+    Ok(())
+}
+
+/// Returns true if contacts joining the group via QR code have to answer a challenge.
+pub async fn is_join_gate_enabled(context: &Context, chat_id: ChatId) -> Result<bool> {
+    let chat = Chat::load_from_db(context, chat_id).await?;
+    Ok(chat.param.get_bool(Param::JoinGate).unwrap_or_default())
+}
+
+/// Returns the received challenge with the given ID.
+pub async fn get_join_challenge(
+    context: &Context,
+    challenge_id: u32,
+) -> Result<Option<JoinChallenge>> {
+    context
+        .sql
+        .query_row_optional(
+            "SELECT contact_id, question, options FROM join_challenges WHERE id=? AND outgoing=0",
+            (challenge_id,),
+            |row| {
+                let options: String = row.get(2)?;
+                Ok(JoinChallenge {
+                    id: challenge_id,
+                    contact_id: row.get(0)?,
+                    question: row.get(1)?,
+                    options: options.split(' ').map(|s| s.to_string()).collect(),
+                })
+            },
+        )
+        .await
+}
+
+/// Answers a received challenge with the selected option.
+pub async fn answer_join_challenge(
+    context: &Context,
+    challenge_id: u32,
+    answer: &str,
+) -> Result<()> {
+    ensure!(
+        CHALLENGE_OPTIONS.contains(&answer),
+        "{answer:?} is not a challenge option"
+    );
+    let (contact_id, grpid): (ContactId, String) = context
+        .sql
+        .query_row_optional(
+            "SELECT contact_id, grpid FROM join_challenges WHERE id=? AND outgoing=0",
+            (challenge_id,),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .await?
+        .with_context(|| format!("Join challenge {challenge_id} not found"))?;
+
+    send_challenge_msg(context, contact_id, "vg-challenge-response", answer, &grpid)
+        .await
+        .context("Failed to send vg-challenge-response message")?;
+    context
+        .sql
+        .execute("DELETE FROM join_challenges WHERE id=?", (challenge_id,))
+        .await?;
+    Ok(())
+}
+
+/// Handles a valid `vg-request-with-auth` message for a group with join gate
+/// by sending a challenge to the joiner.
+///
+/// Joiners that answered too many challenges wrongly are refused,
+/// for joiners that cannot answer challenges an info message is added to the group.
+/// `auth` is the auth token used to join, see [`invite_link::add_joined_member`].
+/// Returns true if a challenge was sent.
+pub(super) async fn handle_request(
+    context: &Context,
+    mime_message: &MimeMessage,
+    contact_id: ContactId,
+    chat_id: ChatId,
+    grpid: &str,
+    auth: &str,
+) -> Result<bool> {
+    if is_refused(context, contact_id, grpid).await? {
+        warn!(
+            context,
+            "{contact_id} answered {MAX_CHALLENGE_ATTEMPTS} join challenges wrongly, not adding to {chat_id}."
+        );
+        return Ok(false);
+    }
+    let supports_challenge = mime_message
+        .get_header(HeaderDef::SecureJoinFeatures)
+        .map_or(false, |features| {
+            features.split_whitespace().any(|f| f == "challenge")
+        });
+    if !supports_challenge {
+        info!(
+            context,
+            "{contact_id} cannot answer join challenges, not adding to {chat_id}."
+        );
+        let text = stock_str::join_challenge_unsupported(context, contact_id).await;
+        chat::add_info_msg(context, chat_id, &text, time()).await?;
+        return Ok(false);
+    }
+    context
+        .sql
+        .execute(
+            "INSERT INTO join_challenges (contact_id, grpid, auth, outgoing, timestamp)
+             VALUES (?,?,?,1,?)
+             ON CONFLICT(contact_id, grpid, outgoing)
+             DO UPDATE SET auth=excluded.auth",
+            (contact_id, grpid, auth, time()),
+        )
+        .await?;
+    send_challenge(context, contact_id, grpid)
+        .await
+        .context("failed sending vg-challenge message")?;
+    Ok(true)
+}
+
+/// Returns true if the contact answered [`MAX_CHALLENGE_ATTEMPTS`] challenges
+/// of the group wrongly.
+///
+/// The failed attempts are kept, so that a joiner cannot get more attempts
+/// by requesting to join again.
+async fn is_refused(context: &Context, contact_id: ContactId, grpid: &str) -> Result<bool> {
+    context
+        .sql
+        .exists(
+            "SELECT COUNT(*) FROM join_challenges
+             WHERE contact_id=? AND grpid=? AND outgoing=1 AND attempts>=?",
+            (contact_id, grpid, MAX_CHALLENGE_ATTEMPTS),
+        )
+        .await
+}
+
+/// Sends a challenge to a contact joining a group with join gate.
+///
+/// The number of challenges answered wrongly before is kept.
+async fn send_challenge(context: &Context, contact_id: ContactId, grpid: &str) -> Result<()> {
+    let answer = CHALLENGE_OPTIONS
+        .choose(&mut thread_rng())
+        .context("No challenge options")?;
+    context
+        .sql
+        .execute(
+            "INSERT INTO join_challenges (contact_id, grpid, answer, outgoing, timestamp)
+             VALUES (?,?,?,1,?)
+             ON CONFLICT(contact_id, grpid, outgoing)
+             DO UPDATE SET answer=excluded.answer, timestamp=excluded.timestamp",
+            (contact_id, grpid, answer, time()),
+        )
+        .await?;
+    let question = stock_str::join_challenge_question(context, answer).await;
+    send_challenge_msg(context, contact_id, "vg-challenge", &question, grpid)
+        .await
+        .context("Failed to send vg-challenge message")
+}
+
+/// Handles a `vg-challenge` message on the side of the joiner.
+pub(super) async fn handle_challenge(
+    context: &Context,
+    mime_message: &MimeMessage,
+    contact_id: ContactId,
+) -> Result<HandshakeMessage> {
+    let grpid = match BobState::from_db(&context.sql).await? {
+        Some(bobstate) => match bobstate.invite() {
+            QrInvite::Group {
+                contact_id: inviter,
+                grpid,
+                ..
+            } if *inviter == contact_id => grpid.clone(),
+            _ => {
+                warn!(
+                    context,
+                    "Ignoring vg-challenge from {contact_id}, not joining."
+                );
+                return Ok(HandshakeMessage::Ignore);
+            }
+        },
+        None => {
+            warn!(context, "Ignoring vg-challenge, not joining a group.");
+            return Ok(HandshakeMessage::Ignore);
+        }
+    };
+    if mime_message.get_header(HeaderDef::SecureJoinGroup) != Some(&grpid) {
+        warn!(context, "Ignoring vg-challenge for another group.");
+        return Ok(HandshakeMessage::Ignore);
+    }
+    if !mime_message.was_encrypted() {
+        warn!(context, "Ignoring unencrypted vg-challenge.");
+        return Ok(HandshakeMessage::Ignore);
+    }
+    let question = match mime_message.get_header(HeaderDef::SecureJoinChallenge) {
+        Some(question) => question,
+        None => {
+            warn!(
+                context,
+                "Ignoring vg-challenge without Secure-Join-Challenge header."
+            );
+            return Ok(HandshakeMessage::Ignore);
+        }
+    };
+
+    let mut options = CHALLENGE_OPTIONS;
+    options.shuffle(&mut thread_rng());
+    context
+        .sql
+        .execute(
+            "INSERT INTO join_challenges (contact_id, grpid, question, options, outgoing, timestamp)
+             VALUES (?,?,?,?,0,?)
+             ON CONFLICT(contact_id, grpid, outgoing)
+             DO UPDATE SET question=excluded.question, options=excluded.options,
+             timestamp=excluded.timestamp",
+            (contact_id, &grpid, question, options.join(" "), time()),
+        )
+        .await?;
+    let challenge_id = context
+        .sql
+        .query_get_value(
+            "SELECT id FROM join_challenges WHERE contact_id=? AND grpid=? AND outgoing=0",
+            (contact_id, &grpid),
+        )
+        .await?
+        .context("Join challenge not saved")?;
+    context.emit_event(EventType::SecurejoinChallengeReceived {
+        contact_id,
+        challenge_id,
+    });
+    Ok(HandshakeMessage::Ignore)
+}
+
+/// Handles a `vg-challenge-response` message on the side of the inviter
+/// by adding the contact to the group if the answer is right.
+pub(super) async fn handle_challenge_response(
+    context: &Context,
+    mime_message: &MimeMessage,
+    contact_id: ContactId,
+) -> Result<HandshakeMessage> {
+    let grpid = match mime_message.get_header(HeaderDef::SecureJoinGroup) {
+        Some(grpid) => grpid,
+        None => {
+            warn!(
+                context,
+                "Ignoring vg-challenge-response without Secure-Join-Group header."
+            );
+            return Ok(HandshakeMessage::Ignore);
+        }
+    };
+    let (answer, attempts, auth): (String, u32, String) = match context
+        .sql
+        .query_row_optional(
+            "SELECT answer, attempts, auth FROM join_challenges
+             WHERE contact_id=? AND grpid=? AND outgoing=1",
+            (contact_id, grpid),
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .await?
+    {
+        Some(challenge) => challenge,
+        None => {
+            warn!(
+                context,
+                "Ignoring vg-challenge-response from {contact_id}, no challenge was sent."
+            );
+            return Ok(HandshakeMessage::Ignore);
+        }
+    };
+    if !mime_message.was_encrypted() {
+        warn!(context, "Ignoring unencrypted vg-challenge-response.");
+        return Ok(HandshakeMessage::Ignore);
+    }
+    if attempts >= MAX_CHALLENGE_ATTEMPTS {
+        warn!(
+            context,
+            "Ignoring vg-challenge-response from {contact_id}, too many wrong answers."
+        );
+        return Ok(HandshakeMessage::Ignore);
+    }
+    let chat_id = match chat::get_chat_id_by_grpid(context, grpid).await? {
+        Some((chat_id, _, _)) => chat_id,
+        None => {
+            warn!(context, "Ignoring vg-challenge-response for unknown group.");
+            return Ok(HandshakeMessage::Ignore);
+        }
+    };
+
+    let response = mime_message.get_header(HeaderDef::SecureJoinChallengeResponse);
+    if response != Some(&answer) {
+        // The failed attempt is counted even if the row is kept forever,
+        // so that the joiner cannot start over, see `is_refused()`.
+        context
+            .sql
+            .execute(
+                "UPDATE join_challenges SET attempts=attempts+1
+                 WHERE contact_id=? AND grpid=? AND outgoing=1",
+                (contact_id, grpid),
+            )
+            .await?;
+        if attempts + 1 < MAX_CHALLENGE_ATTEMPTS {
+            info!(context, "Wrong answer to join challenge from {contact_id}.");
+            send_challenge(context, contact_id, grpid).await?;
+        } else {
+            warn!(
+                context,
+                "{contact_id} answered {MAX_CHALLENGE_ATTEMPTS} join challenges wrongly, not adding to {chat_id}."
+            );
+            context.emit_event(EventType::SecurejoinInviterProgress {
+                contact_id,
+                progress: 0,
+            });
+        }
+        return Ok(HandshakeMessage::Ignore);
+    }
+
+    if !invite_link::add_joined_member(context, chat_id, contact_id, &auth).await? {
+        delete_challenge(context, contact_id, grpid).await?;
+        context.emit_event(EventType::SecurejoinInviterProgress {
+            contact_id,
+            progress: 0,
+        });
+        return Ok(HandshakeMessage::Ignore);
+    }
+    delete_challenge(context, contact_id, grpid).await?;
+    for progress in [800, 1000] {
+        context.emit_event(EventType::SecurejoinInviterProgress {
+            contact_id,
+            progress,
+        });
+    }
+    Ok(HandshakeMessage::Ignore)
+}
+
+async fn send_challenge_msg(
+    context: &Context,
+    contact_id: ContactId,
+    step: &str,
+    value: &str,
+    grpid: &str,
+) -> Result<()> {
+    let mut msg = Message {
+        viewtype: Viewtype::Text,
+        text: Some(format!("Secure-Join: {step}")),
+        hidden: true,
+        ..Default::default()
+    };
+    msg.param.set_cmd(SystemMessage::SecurejoinMessage);
+    msg.param.set(Param::Arg, step);
+    msg.param.set(Param::Arg2, value);
+    msg.param.set(Param::Arg4, grpid);
+    msg.param.set_int(Param::GuaranteeE2ee, 1);
+    chat::send_msg(
+        context,
+        ChatIdBlocked::get_for_contact(context, contact_id, Blocked::Yes)
+            .await?
+            .id,
+        &mut msg,
+    )
+    .await?;
+    Ok(())
+}
+
+async fn delete_challenge(context: &Context, contact_id: ContactId, grpid: &str) -> Result<()> {
+    context
+        .sql
+        .execute(
+            "DELETE FROM join_challenges WHERE contact_id=? AND grpid=? AND outgoing=1",
+            (contact_id, grpid),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::{create_group_chat, is_contact_in_chat, ProtectionStatus};
+    use crate::securejoin::{get_securejoin_qr, join_securejoin};
+    use crate::test_utils::TestContextManager;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_join_gate() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+
+        let alice_chat_id =
+            create_group_chat(&alice, ProtectionStatus::Unprotected, "public group").await?;
+        assert!(!is_join_gate_enabled(&alice, alice_chat_id).await?);
+        set_join_gate(&alice, alice_chat_id, true).await?;
+        assert!(is_join_gate_enabled(&alice, alice_chat_id).await?);
+
+        let qr = get_securejoin_qr(&alice, Some(alice_chat_id)).await?;
+        join_securejoin(&bob, &qr).await?;
+        alice.recv_msg(&bob.pop_sent_msg().await).await;
+        bob.recv_msg(&alice.pop_sent_msg().await).await;
+        alice.recv_msg(&bob.pop_sent_msg().await).await;
+        let alice_bob_id = alice.add_or_lookup_contact(&bob).await.id;
+        assert!(!is_contact_in_chat(&alice, alice_chat_id, alice_bob_id).await?);
+
+        // Alice sent a challenge instead of adding Bob.
+        let sent = alice.pop_sent_msg().await;
+        let msg = bob.parse_msg(&sent).await;
+        assert_eq!(
+            msg.get_header(HeaderDef::SecureJoin).unwrap(),
+            "vg-challenge"
+        );
+        bob.recv_msg(&sent).await;
+        let challenge_id = match bob
+            .evtracker
+            .get_matching(|evt| matches!(evt, EventType::SecurejoinChallengeReceived { .. }))
+            .await
+        {
+            EventType::SecurejoinChallengeReceived { challenge_id, .. } => challenge_id,
+            _ => unreachable!(),
+        };
+        let challenge = get_join_challenge(&bob, challenge_id).await?.unwrap();
+        assert_eq!(challenge.options.len(), CHALLENGE_OPTIONS.len());
+        let answer = find_answer(&alice, &challenge.question).await;
+        assert!(!challenge.question.contains(answer));
+
+        // A wrong answer results in a new challenge.
+        let wrong_answer = CHALLENGE_OPTIONS
+            .iter()
+            .find(|option| **option != answer)
+            .unwrap();
+        assert!(answer_join_challenge(&bob, challenge_id, "🙈")
+            .await
+            .is_err());
+        answer_join_challenge(&bob, challenge_id, wrong_answer).await?;
+        assert!(get_join_challenge(&bob, challenge_id).await?.is_none());
+        alice.recv_msg(&bob.pop_sent_msg().await).await;
+        assert!(!is_contact_in_chat(&alice, alice_chat_id, alice_bob_id).await?);
+        bob.recv_msg(&alice.pop_sent_msg().await).await;
+        let challenge_id = match bob
+            .evtracker
+            .get_matching(|evt| matches!(evt, EventType::SecurejoinChallengeReceived { .. }))
+            .await
+        {
+            EventType::SecurejoinChallengeReceived { challenge_id, .. } => challenge_id,
+            _ => unreachable!(),
+        };
+        let challenge = get_join_challenge(&bob, challenge_id).await?.unwrap();
+        let answer = find_answer(&alice, &challenge.question).await;
+
+        // The right answer lets Bob join.
+        answer_join_challenge(&bob, challenge_id, answer).await?;
+        alice.recv_msg(&bob.pop_sent_msg().await).await;
+        assert!(is_contact_in_chat(&alice, alice_chat_id, alice_bob_id).await?);
+        let sent = alice.pop_sent_msg().await;
+        let msg = bob.parse_msg(&sent).await;
+        assert_eq!(
+            msg.get_header(HeaderDef::SecureJoin).unwrap(),
+            "vg-member-added"
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_join_gate_refuses_after_wrong_answers() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let alice_chat_id =
+            create_group_chat(&alice, ProtectionStatus::Unprotected, "public group").await?;
+        set_join_gate(&alice, alice_chat_id, true).await?;
+        let qr = get_securejoin_qr(&alice, Some(alice_chat_id)).await?;
+        join_securejoin(&bob, &qr).await?;
+        alice.recv_msg(&bob.pop_sent_msg().await).await;
+        bob.recv_msg(&alice.pop_sent_msg().await).await;
+        alice.recv_msg(&bob.pop_sent_msg().await).await;
+
+        for _ in 0..MAX_CHALLENGE_ATTEMPTS {
+            bob.recv_msg(&alice.pop_sent_msg().await).await;
+            let challenge_id = match bob
+                .evtracker
+                .get_matching(|evt| matches!(evt, EventType::SecurejoinChallengeReceived { .. }))
+                .await
+            {
+                EventType::SecurejoinChallengeReceived { challenge_id, .. } => challenge_id,
+                _ => unreachable!(),
+            };
+            let challenge = get_join_challenge(&bob, challenge_id).await?.unwrap();
+            let answer = find_answer(&alice, &challenge.question).await;
+            let wrong_answer = CHALLENGE_OPTIONS
+                .iter()
+                .find(|option| **option != answer)
+                .unwrap();
+            answer_join_challenge(&bob, challenge_id, wrong_answer).await?;
+            alice.recv_msg(&bob.pop_sent_msg().await).await;
+        }
+        let alice_bob_id = alice.add_or_lookup_contact(&bob).await.id;
+        let grpid = Chat::load_from_db(&alice, alice_chat_id).await?.grpid;
+        assert!(is_refused(&alice, alice_bob_id, &grpid).await?);
+        assert!(alice
+            .pop_sent_msg_opt(std::time::Duration::ZERO)
+            .await
+            .is_none());
+
+        // Joining again does not give new attempts.
+        join_securejoin(&bob, &qr).await?;
+        while let Some(sent) = bob.pop_sent_msg_opt(std::time::Duration::ZERO).await {
+            alice.recv_msg(&sent).await;
+            if let Some(sent) = alice.pop_sent_msg_opt(std::time::Duration::ZERO).await {
+                let msg = bob.parse_msg(&sent).await;
+                assert_ne!(
+                    msg.get_header(HeaderDef::SecureJoin).unwrap(),
+                    "vg-challenge"
+                );
+                bob.recv_msg(&sent).await;
+            }
+        }
+        assert!(!is_contact_in_chat(&alice, alice_chat_id, alice_bob_id).await?);
+        Ok(())
+    }
+
+    /// Returns the option asked for by a challenge question of `inviter`.
+    async fn find_answer(inviter: &Context, question: &str) -> &'static str {
+        for option in CHALLENGE_OPTIONS {
+            let name = stock_str::join_challenge_option_name(inviter, option).await;
+            if question.ends_with(&format!(" {name}.")) {
+                return option;
+            }
+        }
+        panic!("No option matches {question:?}");
+    }
+}
@@ -0,0 +1,365 @@
+//! # Export and import of app settings.
+//!
+//! Settings are exported as a JSON document
+//! containing the user's preferences and per-chat settings.
+//! Unlike a backup, the document contains no credentials, keys or messages,
+//! so it can be used to carry preferences over to an account set up from scratch.
+//!
+//! Settings files of the Delta Chat apps can be imported as well,
+//! see [`platform`].
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::chat::{self, get_chat_id_by_grpid, ArchiveMode, Chat, ChatId, ChatVisibility};
+use crate::chat::{get_chat_contacts, MuteDuration};
+use crate::config::Config;
+use crate::constants::{Blocked, Chattype, DC_CHAT_ID_LAST_SPECIAL};
+use crate::contact::{Contact, ContactId, Origin};
+use crate::context::Context;
+
+mod platform;
+
+use platform::SettingsFormat;
+
+/// Version of the settings document format.
+const SETTINGS_VERSION: u32 = 1;
+
+/// Configuration keys included in the settings document.
+///
+/// Server settings, credentials and internal state are never exported.
+const EXPORTED_CONFIG_KEYS: &[Config] = &[
+    Config::Displayname,
+    Config::Selfstatus,
+    Config::BccSelf,
+    Config::E2eeEnabled,
+    Config::MdnsEnabled,
+    Config::ShareLastSeen,
+    Config::SentboxWatch,
+    Config::MvboxMove,
+    Config::OnlyFetchMvbox,
+    Config::ShowEmails,
+    Config::MediaQuality,
+    Config::MaxMsgSize,
+    Config::OversizedAttachment,
+    Config::StripExif,
+    Config::ExifKeep,
+    Config::RecodeVoice,
+    Config::DeleteServerAfter,
+    Config::DeleteDeviceAfter,
+    Config::DownloadLimit,
+    Config::OnlyAcceptVerified,
+    Config::WebrtcInstance,
+    Config::WebrtcInstances,
+    Config::GifSearchEndpoint,
+    Config::SummaryMaxChars,
+    Config::SummaryEmoji,
+    Config::SummaryShowSender,
+    Config::PollInterval,
+    Config::SendSyncMsgs,
+    Config::EncryptedSubject,
+    Config::NotifyMutedMentions,
+    Config::NotifyBots,
+    Config::QuietHours,
+    Config::QuietHoursAllowMentions,
+    Config::QuietHoursAllowVerified,
+    Config::NotificationDigest,
+    Config::ClockSkewCorrection,
+];
+
+/// Settings document.
+#[derive(Debug, Serialize, Deserialize)]
+struct Settings {
+    version: u32,
+
+    /// Configuration values which are set explicitly.
+    config: BTreeMap<String, String>,
+
+    /// UI-specific configuration values, see [`Context::set_ui_config`].
+    ui_config: BTreeMap<String, String>,
+
+    /// Chats with non-default settings.
+    chats: Vec<ChatSettings>,
+}
+
+/// Reference to a chat that is stable across devices.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ChatRef {
+    SelfTalk,
+    DeviceTalk,
+    Contact(String),
+    Group(String),
+}
+
+/// Local settings of a single chat.
+#[derive(Debug, Serialize, Deserialize)]
+struct ChatSettings {
+    chat: ChatRef,
+    visibility: ChatVisibility,
+    mute_duration: MuteDuration,
+    archive_mode: ArchiveMode,
+    threaded: bool,
+
+    /// Signature overriding `selfstatus`, see [`ChatId::set_signature`].
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+impl ChatSettings {
+    fn is_default(&self) -> bool {
+        self.visibility == ChatVisibility::Normal
+            && self.mute_duration == MuteDuration::NotMuted
+            && self.archive_mode == ArchiveMode::AlwaysUnarchive
+            && !self.threaded
+            && self.signature.is_none()
+    }
+}
+
+impl Context {
+    /// Exports preferences and per-chat settings as a JSON document.
+    ///
+    /// The document can be imported on another device using [`Context::import_settings`].
+    pub async fn export_settings(&self) -> Result<String> {
+        let mut config = BTreeMap::new();
+        for key in EXPORTED_CONFIG_KEYS {
+            if let Some(value) = self.sql.get_raw_config(key.as_ref()).await? {
+                config.insert(key.to_string(), value);
+            }
+        }
+
+        let ui_config = self
+            .sql
+            .query_map(
+                "SELECT keyname, value FROM config WHERE keyname LIKE 'ui.%'",
+                (),
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+                |rows| {
+                    rows.collect::<Result<BTreeMap<_, _>, _>>()
+                        .map_err(Into::into)
+                },
+            )
+            .await?;
+
+        let chat_ids = self
+            .sql
+            .query_map(
+                "SELECT id FROM chats WHERE id>? AND blocked=? ORDER BY id",
+                (DC_CHAT_ID_LAST_SPECIAL, Blocked::Not),
+                |row| row.get::<_, ChatId>(0),
+                |ids| ids.collect::<Result<Vec<_>, _>>().map_err(Into::into),
+            )
+            .await?;
+        let mut chats = Vec::new();
+        for chat_id in chat_ids {
+            let chat = Chat::load_from_db(self, chat_id).await?;
+            let chat_ref = match self.get_chat_ref(&chat).await? {
+                Some(chat_ref) => chat_ref,
+                None => continue,
+            };
+            let settings = ChatSettings {
+                chat: chat_ref,
+                visibility: chat.get_visibility(),
+                mute_duration: chat.mute_duration.clone(),
+                archive_mode: chat.get_archive_mode(),
+                threaded: chat.is_threaded(),
+                signature: chat.get_signature().map(|s| s.to_string()),
+            };
+            if !settings.is_default() {
+                chats.push(settings);
+            }
+        }
+
+        let settings = Settings {
+            version: SETTINGS_VERSION,
+            config,
+            ui_config,
+            chats,
+        };
+        Ok(serde_json::to_string_pretty(&settings)?)
+    }
+
+    /// Imports a settings document created by [`Context::export_settings`]
+    /// or a settings file of Delta Chat Desktop, Android or iOS.
+    ///
+    /// The format is detected automatically.
+    /// Values missing from the document are left unchanged.
+    /// Settings of chats which do not exist on this device are skipped,
+    /// chats are never created.
+    pub async fn import_settings(&self, data: &str) -> Result<()> {
+        let format = SettingsFormat::detect(data)?;
+        let settings: Settings = if format == SettingsFormat::Core {
+            serde_json::from_str(data)?
+        } else {
+            info!(self, "Importing settings in {format:?} format.");
+            let platform::PlatformSettings { config, ui_config } =
+                platform::parse_settings(format, data)?;
+            Settings {
+                version: SETTINGS_VERSION,
+                config,
+                ui_config,
+                chats: Vec::new(),
+            }
+        };
+        ensure!(
+            settings.version <= SETTINGS_VERSION,
+            "Unsupported settings version {}",
+            settings.version
+        );
+
+        for (key, value) in &settings.config {
+            match Config::from_str(key) {
+                Ok(key) if EXPORTED_CONFIG_KEYS.contains(&key) => {
+                    self.set_config(key, Some(value)).await?;
+                }
+                _ => warn!(self, "Ignoring setting {key:?}."),
+            }
+        }
+        for (key, value) in &settings.ui_config {
+            if key.starts_with("ui.") {
+                self.set_ui_config(key, Some(value)).await?;
+            }
+        }
+
+        for chat_settings in settings.chats {
+            let chat_id = match self.lookup_chat_ref(&chat_settings.chat).await? {
+                Some(chat_id) => chat_id,
+                None => {
+                    info!(self, "Chat {:?} not found, skipping.", chat_settings.chat);
+                    continue;
+                }
+            };
+            chat_id
+                .set_visibility(self, chat_settings.visibility)
+                .await?;
+            chat::set_muted(self, chat_id, chat_settings.mute_duration).await?;
+            chat_id
+                .set_archive_mode(self, chat_settings.archive_mode)
+                .await?;
+            chat_id.set_threaded(self, chat_settings.threaded).await?;
+            chat_id
+                .set_signature_ex(self, chat_settings.signature.as_deref(), false)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Returns a device-independent reference to the chat.
+    pub(crate) async fn get_chat_ref(&self, chat: &Chat) -> Result<Option<ChatRef>> {
+        let chat_ref = match chat.typ {
+            Chattype::Single if chat.is_self_talk() => Some(ChatRef::SelfTalk),
+            Chattype::Single if chat.is_device_talk() => Some(ChatRef::DeviceTalk),
+            Chattype::Single => match get_chat_contacts(self, chat.id).await?.first() {
+                Some(contact_id) => {
+                    let contact = Contact::get_by_id(self, *contact_id).await?;
+                    Some(ChatRef::Contact(contact.get_addr().to_string()))
+                }
+                None => None,
+            },
+            Chattype::Group | Chattype::Broadcast | Chattype::Mailinglist => {
+                Some(ChatRef::Group(chat.grpid.clone())).filter(|_| !chat.grpid.is_empty())
+            }
+            Chattype::Undefined => None,
+        };
+        Ok(chat_ref)
+    }
+
+    /// Looks up an existing chat by its device-independent reference.
+    pub(crate) async fn lookup_chat_ref(&self, chat_ref: &ChatRef) -> Result<Option<ChatId>> {
+        let contact_id = match chat_ref {
+            ChatRef::SelfTalk => ContactId::SELF,
+            ChatRef::DeviceTalk => ContactId::DEVICE,
+            ChatRef::Contact(addr) => {
+                match Contact::lookup_id_by_addr(self, addr, Origin::Unknown).await? {
+                    Some(contact_id) => contact_id,
+                    None => return Ok(None),
+                }
+            }
+            ChatRef::Group(grpid) => {
+                return Ok(get_chat_id_by_grpid(self, grpid)
+                    .await?
+                    .map(|(chat_id, _protected, _blocked)| chat_id));
+            }
+        };
+        ChatId::lookup_by_contact(self, contact_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestContext;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_export_import_settings() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        alice.set_config(Config::Displayname, Some("Alice")).await?;
+        alice.set_config_bool(Config::MdnsEnabled, false).await?;
+        alice.set_ui_config("ui.theme", Some("dark")).await?;
+        let bob_chat_id = alice
+            .create_chat_with_contact("Bob", "bob@example.net")
+            .await
+            .id;
+        bob_chat_id
+            .set_visibility(&alice, ChatVisibility::Pinned)
+            .await?;
+        chat::set_muted(&alice, bob_chat_id, MuteDuration::Forever).await?;
+        let group_id =
+            chat::create_group_chat(&alice, chat::ProtectionStatus::Unprotected, "Group").await?;
+        group_id.set_threaded(&alice, true).await?;
+        let settings = alice.export_settings().await?;
+        assert!(!settings.contains("alice@example.org"));
+
+        let alice2 = TestContext::new_alice().await;
+        let bob_chat_id2 = alice2
+            .create_chat_with_contact("Bob", "bob@example.net")
+            .await
+            .id;
+        alice2.import_settings(&settings).await?;
+        assert_eq!(
+            alice2.get_config(Config::Displayname).await?,
+            Some("Alice".to_string())
+        );
+        assert!(!alice2.get_config_bool(Config::MdnsEnabled).await?);
+        assert_eq!(
+            alice2.get_ui_config("ui.theme").await?,
+            Some("dark".to_string())
+        );
+        let bob_chat2 = Chat::load_from_db(&alice2, bob_chat_id2).await?;
+        assert_eq!(bob_chat2.get_visibility(), ChatVisibility::Pinned);
+        assert_eq!(bob_chat2.mute_duration, MuteDuration::Forever);
+
+        // Credentials and server settings are not imported.
+        let settings = settings.replace("\"config\": {", "\"config\": {\"mail_pw\": \"secret\",");
+        alice2.import_settings(&settings).await?;
+        assert_eq!(alice2.get_config(Config::MailPw).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_import_desktop_settings() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        t.import_settings(
+            r#"{"enterKeySends": true, "locale": "fr", "zoomFactor": 1.2,
+                "lastChats": {"1": 10}}"#,
+        )
+        .await?;
+        assert_eq!(
+            t.get_ui_config("ui.enter_key_sends").await?,
+            Some("1".to_string())
+        );
+        assert_eq!(
+            t.get_ui_config("ui.language").await?,
+            Some("fr".to_string())
+        );
+        assert_eq!(
+            t.get_ui_config("ui.desktop.zoomFactor").await?,
+            Some("1.2".to_string())
+        );
+        assert_eq!(t.get_ui_config("ui.desktop.lastChats").await?, None);
+        Ok(())
+    }
+}
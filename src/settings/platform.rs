@@ -0,0 +1,367 @@
+//! Parsers for the settings files of the Delta Chat apps.
+//!
+//! The apps store their preferences in platform-specific formats:
+//! Desktop uses a JSON `config.json`, Android uses a SharedPreferences XML file
+//! and iOS uses a property list.
+//! Preferences which have an equivalent on all platforms are mapped to common `ui.*` keys
+//! and preferences which are account settings of the core are mapped to their [`Config`] key.
+//! All values are kept under `ui.<platform>.<key>` as well,
+//! so an app on the same platform can pick them up again.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context as _, Result};
+use quick_xml::events::Event;
+
+use crate::config::Config;
+
+/// Settings file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SettingsFormat {
+    /// Document created by [`crate::context::Context::export_settings`].
+    Core,
+
+    /// `config.json` of Delta Chat Desktop.
+    Desktop,
+
+    /// SharedPreferences XML file of Delta Chat Android.
+    Android,
+
+    /// Property list of Delta Chat iOS.
+    Ios,
+}
+
+impl SettingsFormat {
+    /// Detects the format of a settings file.
+    pub(super) fn detect(data: &str) -> Result<Self> {
+        let data = data.trim_start_matches('\u{feff}').trim_start();
+        if data.starts_with('{') {
+            let value: serde_json::Value = serde_json::from_str(data)?;
+            if value.get("version").is_some() && value.get("config").is_some() {
+                Ok(Self::Core)
+            } else {
+                Ok(Self::Desktop)
+            }
+        } else if data.starts_with('<') {
+            if data.contains("<plist") {
+                Ok(Self::Ios)
+            } else if data.contains("<map") {
+                Ok(Self::Android)
+            } else {
+                bail!("Unknown XML settings format")
+            }
+        } else {
+            bail!("Unknown settings format")
+        }
+    }
+
+    fn ui_prefix(self) -> &'static str {
+        match self {
+            Self::Core => "ui.",
+            Self::Desktop => "ui.desktop.",
+            Self::Android => "ui.android.",
+            Self::Ios => "ui.ios.",
+        }
+    }
+
+    /// Returns the common key of a platform-specific preference, if there is one,
+    /// and whether the boolean value of the preference is inverted.
+    fn common_key(self, key: &str) -> Option<(CommonKey, bool)> {
+        let common_key = match (self, key) {
+            (Self::Desktop, "enterKeySends") | (Self::Android, "pref_enter_sends") => {
+                (CommonKey::Ui("ui.enter_key_sends"), false)
+            }
+            (Self::Desktop, "locale")
+            | (Self::Android, "pref_language")
+            | (Self::Ios, "AppleLanguages") => (CommonKey::Ui("ui.language"), false),
+            (Self::Desktop, "notifications") | (Self::Android, "pref_key_enable_notifications") => {
+                (CommonKey::Ui("ui.notifications"), false)
+            }
+            (Self::Ios, "notifications_disabled") => (CommonKey::Ui("ui.notifications"), true),
+            (Self::Desktop, "showNotificationContent") => {
+                (CommonKey::Ui("ui.show_notification_content"), false)
+            }
+            (Self::Android, "pref_read_receipts") => {
+                (CommonKey::Config(Config::MdnsEnabled), false)
+            }
+            (Self::Android, "pref_compression") => (CommonKey::Config(Config::MediaQuality), false),
+            _ => return None,
+        };
+        Some(common_key)
+    }
+}
+
+/// Key a platform-specific preference is mapped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommonKey {
+    /// UI configuration key shared by all apps.
+    Ui(&'static str),
+
+    /// Account setting of the core.
+    Config(Config),
+}
+
+/// Values of a platform-specific settings file.
+#[derive(Debug, Default)]
+pub(super) struct PlatformSettings {
+    /// Account settings of the core.
+    pub(super) config: BTreeMap<String, String>,
+
+    /// UI configuration values.
+    pub(super) ui_config: BTreeMap<String, String>,
+}
+
+/// Parses a platform-specific settings file into account settings and UI configuration values.
+pub(super) fn parse_settings(format: SettingsFormat, data: &str) -> Result<PlatformSettings> {
+    let values = match format {
+        SettingsFormat::Core => bail!("Not a platform-specific settings file"),
+        SettingsFormat::Desktop => parse_desktop(data)?,
+        SettingsFormat::Android => parse_android(data)?,
+        SettingsFormat::Ios => parse_ios(data)?,
+    };
+
+    let mut settings = PlatformSettings::default();
+    for (key, value) in values {
+        if let Some((common_key, inverted)) = format.common_key(&key) {
+            let common_value = match (value.as_str(), inverted) {
+                ("true", false) | ("false", true) => "1".to_string(),
+                ("false", false) | ("true", true) => "0".to_string(),
+                _ => value.clone(),
+            };
+            match common_key {
+                CommonKey::Ui(key) => settings.ui_config.insert(key.to_string(), common_value),
+                CommonKey::Config(key) => settings.config.insert(key.to_string(), common_value),
+            };
+        }
+        settings
+            .ui_config
+            .insert(format!("{}{key}", format.ui_prefix()), value);
+    }
+    Ok(settings)
+}
+
+/// Returns the top-level scalar values of Desktop's `config.json`.
+///
+/// Nested values, such as saved credentials or per-account state, are skipped.
+fn parse_desktop(data: &str) -> Result<Vec<(String, String)>> {
+    let value: serde_json::Value = serde_json::from_str(data)?;
+    let object = value
+        .as_object()
+        .context("Desktop settings are not a JSON object")?;
+    let values = object
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::Bool(b) => b.to_string(),
+                serde_json::Value::Number(n) => n.to_string(),
+                serde_json::Value::String(s) => s.clone(),
+                _ => return None,
+            };
+            Some((key.clone(), value))
+        })
+        .collect();
+    Ok(values)
+}
+
+/// Returns the scalar values of an Android SharedPreferences XML file,
+/// e.g. `<boolean name="pref_enter_sends" value="true" />`.
+fn parse_android(data: &str) -> Result<Vec<(String, String)>> {
+    let mut reader = quick_xml::Reader::from_str(data);
+    reader.trim_text(true);
+
+    let mut values = Vec::new();
+    let mut depth = 0usize;
+    let mut string_name: Option<String> = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(ref e) => {
+                depth += 1;
+                // Strings are elements with text, sets contain nested strings and are skipped.
+                string_name = None;
+                if depth == 2 && e.name().as_ref() == b"string" {
+                    string_name = get_attribute(e, b"name")?;
+                }
+            }
+            Event::Empty(ref e) => {
+                let is_scalar =
+                    matches!(e.name().as_ref(), b"boolean" | b"int" | b"long" | b"float");
+                if depth == 1 && is_scalar {
+                    if let (Some(name), Some(value)) =
+                        (get_attribute(e, b"name")?, get_attribute(e, b"value")?)
+                    {
+                        values.push((name, value));
+                    }
+                }
+            }
+            Event::Text(ref e) => {
+                if let Some(name) = string_name.take() {
+                    values.push((name, e.unescape()?.into_owned()));
+                }
+            }
+            Event::End(_) => {
+                depth = depth.saturating_sub(1);
+                string_name = None;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(values)
+}
+
+/// Returns the scalar values of the top-level dictionary of an iOS property list,
+/// e.g. `<key>notifications_disabled</key><true/>`.
+///
+/// Of the `AppleLanguages` array, only the preferred, i.e. first, language is returned.
+fn parse_ios(data: &str) -> Result<Vec<(String, String)>> {
+    let mut reader = quick_xml::Reader::from_str(data);
+    reader.trim_text(true);
+
+    let mut values = Vec::new();
+    let mut depth = 0usize;
+    let mut key: Option<String> = None;
+    let mut languages_key: Option<String> = None;
+    let mut current_tag = Vec::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(ref e) => {
+                depth += 1;
+                current_tag = e.name().as_ref().to_vec();
+                if depth == 3 && current_tag == b"array" {
+                    languages_key = key.take().filter(|key| key == "AppleLanguages");
+                } else if depth > 3 {
+                    // Value of the current key is an array or a nested dictionary.
+                    key = None;
+                }
+            }
+            Event::Empty(ref e) => {
+                if depth == 2 {
+                    match e.name().as_ref() {
+                        b"true" => {
+                            if let Some(key) = key.take() {
+                                values.push((key, "true".to_string()));
+                            }
+                        }
+                        b"false" => {
+                            if let Some(key) = key.take() {
+                                values.push((key, "false".to_string()));
+                            }
+                        }
+                        _ => key = None,
+                    }
+                }
+            }
+            Event::Text(ref e) => {
+                // `<plist><dict>` is at depth 2, keys and values at depth 3.
+                if depth == 4 && current_tag == b"string" {
+                    if let Some(key) = languages_key.take() {
+                        values.push((key, e.unescape()?.into_owned()));
+                    }
+                } else if depth == 3 {
+                    let text = e.unescape()?.into_owned();
+                    match current_tag.as_slice() {
+                        b"key" => key = Some(text),
+                        b"string" | b"integer" | b"real" => {
+                            if let Some(key) = key.take() {
+                                values.push((key, text));
+                            }
+                        }
+                        _ => key = None,
+                    }
+                }
+            }
+            Event::End(_) => depth = depth.saturating_sub(1),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(values)
+}
+
+fn get_attribute(e: &quick_xml::events::BytesStart, name: &[u8]) -> Result<Option<String>> {
+    match e.try_get_attribute(name)? {
+        Some(attr) => Ok(Some(attr.unescape_value()?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_settings_format() -> Result<()> {
+        assert_eq!(
+            SettingsFormat::detect(r#"{"version": 1, "config": {}}"#)?,
+            SettingsFormat::Core
+        );
+        assert_eq!(
+            SettingsFormat::detect(r#"{"enterKeySends": true}"#)?,
+            SettingsFormat::Desktop
+        );
+        assert_eq!(
+            SettingsFormat::detect(
+                "<?xml version='1.0' encoding='utf-8' standalone='yes' ?>\n<map />"
+            )?,
+            SettingsFormat::Android
+        );
+        assert_eq!(
+            SettingsFormat::detect(r#"<?xml version="1.0"?><plist version="1.0"><dict/></plist>"#)?,
+            SettingsFormat::Ios
+        );
+        assert!(SettingsFormat::detect("foo").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_android() -> Result<()> {
+        let data = r#"<?xml version='1.0' encoding='utf-8' standalone='yes' ?>
+<map>
+    <boolean name="pref_enter_sends" value="true" />
+    <string name="pref_language">de</string>
+    <string name="pref_theme">dark</string>
+    <boolean name="pref_read_receipts" value="false" />
+    <string name="pref_compression">1</string>
+    <int name="pref_font_size" value="16" />
+    <set name="pref_set">
+        <string>a</string>
+    </set>
+</map>"#;
+        let settings = parse_settings(SettingsFormat::Android, data)?;
+        let ui_config = settings.ui_config;
+        assert_eq!(ui_config.get("ui.enter_key_sends").unwrap(), "1");
+        assert_eq!(ui_config.get("ui.language").unwrap(), "de");
+        assert_eq!(ui_config.get("ui.android.pref_theme").unwrap(), "dark");
+        assert_eq!(ui_config.get("ui.android.pref_font_size").unwrap(), "16");
+        assert_eq!(ui_config.get("ui.android.pref_set"), None);
+        assert_eq!(settings.config.get("mdns_enabled").unwrap(), "0");
+        assert_eq!(settings.config.get("media_quality").unwrap(), "1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ios() -> Result<()> {
+        let data = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>notifications_disabled</key>
+    <true/>
+    <key>AppleLanguages</key>
+    <array><string>de</string><string>en</string></array>
+    <key>last_account</key>
+    <integer>3</integer>
+</dict>
+</plist>"#;
+        let ui_config = parse_settings(SettingsFormat::Ios, data)?.ui_config;
+        assert_eq!(
+            ui_config.get("ui.ios.notifications_disabled").unwrap(),
+            "true"
+        );
+        assert_eq!(ui_config.get("ui.notifications").unwrap(), "0");
+        assert_eq!(ui_config.get("ui.ios.last_account").unwrap(), "3");
+        assert_eq!(ui_config.get("ui.ios.AppleLanguages").unwrap(), "de");
+        assert_eq!(ui_config.get("ui.language").unwrap(), "de");
+        Ok(())
+    }
+}
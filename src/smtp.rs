@@ -10,12 +10,14 @@
 use tokio::io::BufStream;
 use tokio::task;
 
+use crate::chat;
 use crate::config::Config;
 use crate::contact::{Contact, ContactId};
+use crate::error_code::ErrorCode;
 use crate::events::EventType;
 use crate::login_param::{CertificateChecks, LoginParam, ServerLoginParam};
 use crate::message::Message;
-use crate::message::{self, MsgId};
+use crate::message::{self, MessageState, MsgId};
 use crate::mimefactory::MimeFactory;
 use crate::net::connect_tcp;
 use crate::net::session::SessionBufStream;
@@ -67,9 +69,11 @@ pub async fn disconnect(&mut self) {
 
     /// Return true if smtp was connected but is not known to
     /// have been successfully used the last 60 seconds
-    pub fn has_maybe_stale_connection(&self) -> bool {
+    pub fn has_maybe_stale_connection(&self, context: &Context) -> bool {
         if let Some(last_success) = self.last_success {
-            SystemTime::now()
+            context
+                .clock
+                .now()
                 .duration_since(last_success)
                 .unwrap_or_default()
                 .as_secs()
@@ -86,7 +90,7 @@ pub fn is_connected(&self) -> bool {
 
     /// Connect using configured parameters.
     pub async fn connect_configured(&mut self, context: &Context) -> Result<()> {
-        if self.has_maybe_stale_connection() {
+        if self.has_maybe_stale_connection(context) {
             info!(context, "Closing stale connection");
             self.disconnect().await;
         }
@@ -329,11 +333,29 @@ pub async fn connect(
                     ],
                 )
             };
-            transport.try_login(&creds, &mechanism).await?;
+            transport
+                .try_login(&creds, &mechanism)
+                .await
+                .map_err(|err| match err {
+                    // 535 Authentication credentials invalid, see RFC 4954.
+                    async_smtp::error::Error::Permanent(ref response)
+                        if matches!(
+                            response.code,
+                            Code {
+                                category: Category::Unspecified3,
+                                detail: Detail::Five,
+                                ..
+                            }
+                        ) =>
+                    {
+                        ErrorCode::Auth.wrap(err)
+                    }
+                    err => err.into(),
+                })?;
         }
 
         self.transport = Some(transport);
-        self.last_success = Some(SystemTime::now());
+        self.last_success = Some(context.clock.now());
 
         context.emit_event(EventType::SmtpConnected(format!(
             "SMTP-LOGIN as {} ok",
@@ -603,7 +625,12 @@ pub(crate) async fn send_msg_to_smtp(
 /// Attempts to send queued MDNs.
 async fn send_mdns(context: &Context, connection: &mut Smtp) -> Result<()> {
     loop {
-        if !context.ratelimit.read().await.can_send() {
+        if !context
+            .ratelimit
+            .read()
+            .await
+            .can_send_at(context.clock.now())
+        {
             info!(context, "Ratelimiter does not allow sending MDNs now");
             return Ok(());
         }
@@ -621,7 +648,12 @@ async fn send_mdns(context: &Context, connection: &mut Smtp) -> Result<()> {
 /// Logs and ignores SMTP errors to ensure that a single SMTP message constantly failing to be sent
 /// does not block other messages in the queue from being sent.
 pub(crate) async fn send_smtp_messages(context: &Context, connection: &mut Smtp) -> Result<()> {
-    let ratelimited = if context.ratelimit.read().await.can_send() {
+    let ratelimited = if context
+        .ratelimit
+        .read()
+        .await
+        .can_send_at(context.clock.now())
+    {
         // add status updates and sync messages to end of sending queue
         context.flush_status_updates().await?;
         context.send_sync_msg().await?;
@@ -630,6 +662,10 @@ pub(crate) async fn send_smtp_messages(context: &Context, connection: &mut Smtp)
         true
     };
 
+    render_deferred_msgs(context)
+        .await
+        .context("failed to render deferred messages")?;
+
     let rowids = context
         .sql
         .query_map(
@@ -663,6 +699,54 @@ pub(crate) async fn send_smtp_messages(context: &Context, connection: &mut Smtp)
     Ok(())
 }
 
+/// Renders messages sent with [`chat::send_msg_deferred`] and adds them to the `smtp` table.
+pub(crate) async fn render_deferred_msgs(context: &Context) -> Result<()> {
+    let msg_ids = context
+        .sql
+        .query_map(
+            "SELECT msg_id FROM smtp_deferred ORDER BY msg_id ASC",
+            (),
+            |row| row.get::<_, MsgId>(0),
+            |msg_ids| {
+                msg_ids
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(Into::into)
+            },
+        )
+        .await?;
+    for msg_id in msg_ids {
+        // The message may have been deleted in the meantime,
+        // in this case the entry is removed without sending anything
+        // so that it does not block the queue.
+        match Message::load_from_db(context, msg_id).await {
+            Ok(msg) if !msg.chat_id.is_trash() && msg.state == MessageState::OutPending => {
+                if let Err(err) = chat::create_send_msg_job(context, msg_id).await {
+                    warn!(
+                        context,
+                        "Failed to render deferred message {msg_id}: {err:#}."
+                    );
+                    let failed = Message::load_from_db(context, msg_id)
+                        .await
+                        .map_or(false, |msg| msg.state == MessageState::OutFailed);
+                    if !failed {
+                        message::set_msg_failed(context, msg_id, &format!("{err:#}")).await;
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(err) => warn!(
+                context,
+                "Cannot load deferred message {msg_id}, dropping it: {err:#}."
+            ),
+        }
+        context
+            .sql
+            .execute("DELETE FROM smtp_deferred WHERE msg_id=?", (msg_id,))
+            .await?;
+    }
+    Ok(())
+}
+
 /// Tries to send MDN for message `msg_id` to `contact_id`.
 ///
 /// Attempts to aggregate additional MDNs for `contact_id` into sent MDN.
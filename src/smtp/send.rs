@@ -39,7 +39,7 @@ pub async fn send(
             // Notify ratelimiter about sent message regardless of whether quota is exceeded or not.
             // Checking whether sending is allowed for low-priority messages should be done by the
             // caller.
-            context.ratelimit.write().await.send();
+            context.ratelimit.write().await.send_at(context.clock.now());
         }
 
         let message_len_bytes = message.len();
@@ -67,7 +67,7 @@ pub async fn send(
                 context.emit_event(EventType::SmtpMessageSent(format!(
                     "Message len={message_len_bytes} was smtp-sent to {recipients_display}"
                 )));
-                self.last_success = Some(std::time::SystemTime::now());
+                self.last_success = Some(context.clock.now());
             } else {
                 warn!(
                     context,
@@ -7,17 +7,21 @@
 use anyhow::{bail, Context as _, Result};
 use rusqlite::{self, config::DbConfig, types::ValueRef, Connection, OpenFlags, Row};
 use tokio::sync::{Mutex, MutexGuard, RwLock};
+use zeroize::Zeroizing;
 
 use crate::blob::BlobObject;
 use crate::chat::{add_device_msg, update_device_icon, update_saved_messages_icon};
+use crate::clock_skew;
 use crate::config::Config;
 use crate::constants::DC_CHAT_ID_TRASH;
 use crate::context::Context;
 use crate::debug_logging::set_debug_logging_xdc;
-use crate::ephemeral::start_ephemeral_timers;
+use crate::ephemeral::{delete_retained_messages, start_ephemeral_timers};
 use crate::imex::BLOBS_BACKUP_NAME;
+use crate::late_delivery;
 use crate::log::LogExt;
 use crate::message::{Message, MsgId, Viewtype};
+use crate::notification;
 use crate::param::{Param, Params};
 use crate::peerstate::{deduplicate_peerstates, Peerstate};
 use crate::stock_str;
@@ -94,6 +98,7 @@ pub fn new(dbfile: PathBuf) -> Sql {
     ///
     /// Fails if database is already open.
     pub async fn check_passphrase(&self, passphrase: String) -> Result<bool> {
+        let passphrase = Zeroizing::new(passphrase);
         if self.is_open().await {
             bail!("Database is already opened.");
         }
@@ -104,7 +109,7 @@ pub async fn check_passphrase(&self, passphrase: String) -> Result<bool> {
         // Test that the key is correct using a single connection.
         let connection = Connection::open(&self.dbfile)?;
         connection
-            .pragma_update(None, "key", &passphrase)
+            .pragma_update(None, "key", passphrase.as_str())
             .context("failed to set PRAGMA key")?;
         let key_is_correct = connection
             .query_row("SELECT count(*) FROM sqlite_master", [], |_row| Ok(()))
@@ -126,7 +131,7 @@ pub(crate) async fn is_encrypted(&self) -> Option<bool> {
     }
 
     /// Closes all underlying Sqlite connections.
-    async fn close(&self) {
+    pub(crate) async fn close(&self) {
         let _ = self.pool.write().await.take();
         // drop closes the connection
     }
@@ -181,6 +186,7 @@ pub(crate) async fn import(&self, path: &Path, passphrase: String) -> Result<()>
 
     /// Creates a new connection pool.
     fn new_pool(dbfile: &Path, passphrase: String) -> Result<Pool> {
+        let passphrase = Zeroizing::new(passphrase);
         let mut connections = Vec::new();
         for _ in 0..3 {
             let connection = new_connection(dbfile, &passphrase)?;
@@ -192,10 +198,13 @@ fn new_pool(dbfile: &Path, passphrase: String) -> Result<Pool> {
     }
 
     async fn try_open(&self, context: &Context, dbfile: &Path, passphrase: String) -> Result<()> {
-        *self.pool.write().await = Some(Self::new_pool(dbfile, passphrase.to_string())?);
+        *self.pool.write().await = Some(Self::new_pool(dbfile, passphrase)?);
 
         self.run_migrations(context).await?;
 
+        // Part of opening, so that the database is closed again if the correction cannot be loaded.
+        clock_skew::update_correction(context).await?;
+
         Ok(())
     }
 
@@ -276,6 +285,7 @@ pub async fn run_migrations(&self, context: &Context) -> Result<()> {
     /// Opens the provided database and runs any necessary migrations.
     /// If a database is already open, this will return an error.
     pub async fn open(&self, context: &Context, passphrase: String) -> Result<()> {
+        let passphrase = Zeroizing::new(passphrase);
         if self.is_open().await {
             error!(
                 context,
@@ -285,7 +295,10 @@ pub async fn open(&self, context: &Context, passphrase: String) -> Result<()> {
         }
 
         let passphrase_nonempty = !passphrase.is_empty();
-        if let Err(err) = self.try_open(context, &self.dbfile, passphrase).await {
+        if let Err(err) = self
+            .try_open(context, &self.dbfile, passphrase.to_string())
+            .await
+        {
             self.close().await;
             Err(err)
         } else {
@@ -704,6 +717,13 @@ pub async fn housekeeping(context: &Context) -> Result<()> {
         );
     }
 
+    if let Err(err) = remove_unused_view_once_files(context).await {
+        warn!(
+            context,
+            "Housekeeping: cannot remove unused view-once files: {:#}.", err
+        );
+    }
+
     if let Err(err) = start_ephemeral_timers(context).await {
         warn!(
             context,
@@ -711,6 +731,13 @@ pub async fn housekeeping(context: &Context) -> Result<()> {
         );
     }
 
+    if let Err(err) = delete_retained_messages(context).await {
+        warn!(
+            context,
+            "Housekeeping: cannot apply retention policies: {:#}.", err
+        );
+    }
+
     if let Err(err) = prune_tombstones(&context.sql).await {
         warn!(
             context,
@@ -722,6 +749,14 @@ pub async fn housekeeping(context: &Context) -> Result<()> {
         warn!(context, "Failed to deduplicate peerstates: {:#}.", err)
     }
 
+    if let Err(err) = notification::maybe_add_digest(context).await {
+        warn!(context, "Failed to add notification digest: {:#}.", err)
+    }
+
+    if let Err(err) = late_delivery::delete_old_late_deliveries(context).await {
+        warn!(context, "Failed to delete old late deliveries: {:#}.", err)
+    }
+
     context.schedule_quota_update().await?;
 
     // Try to clear the freelist to free some space on the disk. This
@@ -782,6 +817,13 @@ pub async fn remove_unused_files(context: &Context) -> Result<()> {
         Param::File,
     )
     .await?;
+    maybe_add_from_param(
+        &context.sql,
+        &mut files_in_use,
+        "SELECT param FROM msgs  WHERE chat_id!=3   AND type!=10;",
+        Param::Attachments,
+    )
+    .await?;
     maybe_add_from_param(
         &context.sql,
         &mut files_in_use,
@@ -820,6 +862,22 @@ pub async fn remove_unused_files(context: &Context) -> Result<()> {
         .await
         .context("housekeeping: failed to SELECT value FROM config")?;
 
+    context
+        .sql
+        .query_map(
+            "SELECT file FROM canned_responses;",
+            (),
+            |row| row.get::<_, String>(0),
+            |rows| {
+                for row in rows {
+                    maybe_add_file(&mut files_in_use, &row?);
+                }
+                Ok(())
+            },
+        )
+        .await
+        .context("housekeeping: failed to SELECT file FROM canned_responses")?;
+
     info!(context, "{} files in use.", files_in_use.len());
     /* go through directories and delete unused files */
     let blobdir = context.get_blobdir();
@@ -840,7 +898,8 @@ pub async fn remove_unused_files(context: &Context) -> Result<()> {
                         && (is_file_in_use(&files_in_use, None, &name_s)
                             || is_file_in_use(&files_in_use, Some(".increation"), &name_s)
                             || is_file_in_use(&files_in_use, Some(".waveform"), &name_s)
-                            || is_file_in_use(&files_in_use, Some("-preview.jpg"), &name_s))
+                            || is_file_in_use(&files_in_use, Some("-preview.jpg"), &name_s)
+                            || is_file_in_use(&files_in_use, Some("-thumb.jpg"), &name_s))
                     {
                         continue;
                     }
@@ -916,6 +975,68 @@ pub async fn remove_unused_files(context: &Context) -> Result<()> {
     Ok(())
 }
 
+/// Deletes the media of view-once messages which were deleted without being opened.
+async fn remove_unused_view_once_files(context: &Context) -> Result<()> {
+    let dir = context.get_view_once_dir();
+    let mut dir_handle = match tokio::fs::read_dir(&dir).await {
+        Ok(dir_handle) => dir_handle,
+        // The directory is created only when view-once media is received.
+        Err(_) => return Ok(()),
+    };
+
+    let mut files_in_use = HashSet::new();
+    context
+        .sql
+        .query_map(
+            "SELECT param FROM msgs WHERE chat_id!=3;",
+            (),
+            |row| row.get::<_, String>(0),
+            |rows| {
+                for row in rows {
+                    let param: Params = row?.parse().unwrap_or_default();
+                    if let Some(name) = param.get(Param::ViewOnceFile) {
+                        files_in_use.insert(name.to_string());
+                    }
+                }
+                Ok(())
+            },
+        )
+        .await?;
+
+    // Avoid deletion of files moved for a message which is being received right now.
+    let keep_files_newer_than = std::time::SystemTime::now()
+        .checked_sub(std::time::Duration::from_secs(60 * 60))
+        .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+    while let Ok(Some(entry)) = dir_handle.next_entry().await {
+        if files_in_use.contains(&*entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let recently_modified = entry
+            .metadata()
+            .await
+            .and_then(|stats| stats.modified())
+            .map_or(false, |t| t > keep_files_newer_than);
+        if recently_modified {
+            continue;
+        }
+        info!(
+            context,
+            "Housekeeping: Deleting unreferenced view-once file {:?}.",
+            entry.file_name()
+        );
+        let path = entry.path();
+        if let Err(err) = delete_file(context, &path).await {
+            warn!(
+                context,
+                "Failed to delete unused view-once file {}: {:#}.",
+                path.display(),
+                err
+            );
+        }
+    }
+    Ok(())
+}
+
 #[allow(clippy::indexing_slicing)]
 fn is_file_in_use(files_in_use: &HashSet<String>, namespc_opt: Option<&str>, name: &str) -> bool {
     let name_to_check = if let Some(namespc) = namespc_opt {
@@ -950,7 +1071,7 @@ async fn maybe_add_from_param(
         |rows| {
             for row in rows {
                 let param: Params = row?.parse().unwrap_or_default();
-                if let Some(file) = param.get(param_id) {
+                for file in param.get(param_id).unwrap_or_default().lines() {
                     maybe_add_file(files_in_use, file);
                 }
             }
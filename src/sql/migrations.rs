@@ -711,6 +711,399 @@ pub async fn run(context: &Context, sql: &Sql) -> Result<(bool, bool, bool, bool
         )
         .await?;
     }
+    if dbversion < 101 {
+        sql.execute_migration(
+            "ALTER TABLE imap_sync
+             ADD COLUMN backfill_uid -- Messages with lower UIDs are not fetched yet
+             INTEGER DEFAULT 0",
+            101,
+        )
+        .await?;
+    }
+    if dbversion < 102 {
+        sql.execute_migration(
+            "ALTER TABLE chats
+             ADD COLUMN server_archive_after -- Seconds after which messages are moved to the archive folder
+             INTEGER NOT NULL DEFAULT 0",
+            102,
+        )
+        .await?;
+    }
+    if dbversion < 103 {
+        sql.execute_migration(
+            "ALTER TABLE chats
+             ADD COLUMN retention_keep_last -- Number of newest messages kept on the device
+             INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE chats
+             ADD COLUMN retention_server_days -- Days after which messages are deleted from the server
+             INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE chats
+             ADD COLUMN retention_deleted_local -- Messages deleted from the device by the retention policy
+             INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE chats
+             ADD COLUMN retention_deleted_server -- Messages deleted from the server by the retention policy
+             INTEGER NOT NULL DEFAULT 0;",
+            103,
+        )
+        .await?;
+    }
+    if dbversion < 104 {
+        sql.execute_migration(
+            "CREATE TABLE ephemeral_timer_acks (
+               chat_id INTEGER NOT NULL,
+               contact_id INTEGER NOT NULL,
+               timer INTEGER NOT NULL, -- Ephemeral timer of the latest message from the contact
+               timestamp INTEGER NOT NULL, -- Sent timestamp of the latest message from the contact
+               PRIMARY KEY (chat_id, contact_id)
+             )",
+            104,
+        )
+        .await?;
+    }
+    if dbversion < 105 {
+        sql.execute_migration(
+            "CREATE TABLE group_invites (
+               id INTEGER PRIMARY KEY AUTOINCREMENT,
+               contact_id INTEGER NOT NULL,
+               grpid TEXT NOT NULL,
+               name TEXT NOT NULL DEFAULT '',
+               outgoing INTEGER NOT NULL, -- 1 if forwarded by us, 0 if received
+               timestamp INTEGER NOT NULL,
+               UNIQUE(contact_id, grpid, outgoing)
+             )",
+            105,
+        )
+        .await?;
+    }
+    if dbversion < 106 {
+        sql.execute_migration(
+            "CREATE TABLE key_history (
+               addr TEXT NOT NULL COLLATE NOCASE,
+               fingerprint TEXT NOT NULL, -- Hex fingerprint of the Autocrypt key
+               first_seen INTEGER NOT NULL,
+               last_seen INTEGER NOT NULL,
+               dkim_passed INTEGER NOT NULL, -- DKIM verdict when the key was first seen
+               PRIMARY KEY (addr, fingerprint)
+             )",
+            106,
+        )
+        .await?;
+    }
+    if dbversion < 107 {
+        sql.execute_migration(
+            "CREATE TABLE verified_key_gossips (
+               chat_id INTEGER NOT NULL,
+               contact_id INTEGER NOT NULL,
+               fingerprint TEXT NOT NULL, -- Hex fingerprint of the gossiped verified key
+               timestamp INTEGER NOT NULL,
+               PRIMARY KEY (chat_id, contact_id)
+             )",
+            107,
+        )
+        .await?;
+    }
+    if dbversion < 108 {
+        sql.execute_migration(
+            "CREATE TABLE saved_msg_tags (
+               msg_id INTEGER NOT NULL,
+               tag TEXT NOT NULL COLLATE NOCASE,
+               PRIMARY KEY (msg_id, tag)
+             )",
+            108,
+        )
+        .await?;
+    }
+    if dbversion < 109 {
+        sql.execute_migration(
+            "ALTER TABLE msgs ADD COLUMN starred INTEGER NOT NULL DEFAULT 0;
+             CREATE TABLE imap_markflagged (
+               id INTEGER PRIMARY KEY,
+               flagged INTEGER NOT NULL, -- 1=add \\Flagged flag, 0=remove it
+               FOREIGN KEY(id) REFERENCES imap(id) ON DELETE CASCADE
+             );",
+            109,
+        )
+        .await?;
+    }
+    if dbversion < 110 {
+        sql.execute_migration(
+            "ALTER TABLE chats ADD COLUMN archive_mode INTEGER NOT NULL DEFAULT 0;",
+            110,
+        )
+        .await?;
+    }
+    if dbversion < 111 {
+        sql.execute_migration(
+            "ALTER TABLE contacts
+             ADD COLUMN hide_last_seen -- 1 if the last-seen status is not shared with the contact
+             INTEGER NOT NULL DEFAULT 0;",
+            111,
+        )
+        .await?;
+    }
+    if dbversion < 112 {
+        // Stable identifiers of chats and contacts which,
+        // unlike row IDs, are not supposed to change.
+        sql.execute_migration(
+            "ALTER TABLE chats ADD COLUMN uuid TEXT NOT NULL DEFAULT '';
+            UPDATE chats SET uuid=lower(hex(randomblob(16)));
+            CREATE INDEX chats_uuid_index ON chats (uuid);
+            CREATE TRIGGER chats_uuid_trigger AFTER INSERT ON chats WHEN NEW.uuid=''
+            BEGIN
+              UPDATE chats SET uuid=lower(hex(randomblob(16))) WHERE id=NEW.id;
+            END;
+            ALTER TABLE contacts ADD COLUMN uuid TEXT NOT NULL DEFAULT '';
+            UPDATE contacts SET uuid=lower(hex(randomblob(16)));
+            CREATE INDEX contacts_uuid_index ON contacts (uuid);
+            CREATE TRIGGER contacts_uuid_trigger AFTER INSERT ON contacts WHEN NEW.uuid=''
+            BEGIN
+              UPDATE contacts SET uuid=lower(hex(randomblob(16))) WHERE id=NEW.id;
+            END;",
+            112,
+        )
+        .await?;
+    }
+    if dbversion < 113 {
+        sql.execute_migration(
+            "CREATE TABLE devices (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL DEFAULT '',
+            last_seen INTEGER NOT NULL DEFAULT 0,
+            revoked INTEGER NOT NULL DEFAULT 0);",
+            113,
+        )
+        .await?;
+    }
+    if dbversion < 114 {
+        sql.execute_migration(
+            "CREATE TABLE smtp_deferred (msg_id INTEGER PRIMARY KEY);",
+            114,
+        )
+        .await?;
+    }
+    if dbversion < 115 {
+        sql.execute_migration(
+            "ALTER TABLE msgs ADD COLUMN info_group INTEGER NOT NULL DEFAULT 0;",
+            115,
+        )
+        .await?;
+    }
+    if dbversion < 116 {
+        sql.execute_migration(
+            "CREATE TABLE aeap_migrations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            chat_id INTEGER NOT NULL,
+            old_contact_id INTEGER NOT NULL,
+            new_contact_id INTEGER NOT NULL DEFAULT 0,
+            old_addr TEXT NOT NULL,
+            new_addr TEXT NOT NULL,
+            state INTEGER NOT NULL,
+            new_contact_was_member INTEGER NOT NULL DEFAULT 0);",
+            116,
+        )
+        .await?;
+    }
+    if dbversion < 117 {
+        sql.execute_migration(
+            "ALTER TABLE acpeerstates ADD COLUMN gossip_source_addr TEXT;
+            ALTER TABLE acpeerstates ADD COLUMN gossip_source_fingerprint TEXT;",
+            117,
+        )
+        .await?;
+    }
+    if dbversion < 118 {
+        sql.execute_migration(
+            "CREATE TABLE folder_delete_server_after (
+            folder TEXT PRIMARY KEY,
+            delete_server_after INTEGER NOT NULL);",
+            118,
+        )
+        .await?;
+    }
+    if dbversion < 119 {
+        // Timestamps and errors of pending IMAP operations, see `imap_jobs` module.
+        sql.execute_migration(
+            "ALTER TABLE imap ADD COLUMN job_timestamp INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE imap ADD COLUMN job_error TEXT NOT NULL DEFAULT '';
+            CREATE TRIGGER imap_job_insert_trigger AFTER INSERT ON imap WHEN NEW.target!=NEW.folder
+            BEGIN
+              UPDATE imap SET job_timestamp=CAST(strftime('%s','now') AS INTEGER) WHERE id=NEW.id;
+            END;
+            CREATE TRIGGER imap_job_update_trigger AFTER UPDATE OF target ON imap
+            WHEN NEW.target!=OLD.target
+            BEGIN
+              UPDATE imap SET job_timestamp=CAST(strftime('%s','now') AS INTEGER), job_error=''
+              WHERE id=NEW.id;
+            END;
+            ALTER TABLE imap_markseen ADD COLUMN timestamp INTEGER NOT NULL DEFAULT 0;
+            CREATE TRIGGER imap_markseen_insert_trigger AFTER INSERT ON imap_markseen
+            BEGIN
+              UPDATE imap_markseen SET timestamp=CAST(strftime('%s','now') AS INTEGER)
+              WHERE rowid=NEW.rowid;
+              UPDATE imap SET job_error='' WHERE id=NEW.id;
+            END;",
+            119,
+        )
+        .await?;
+    }
+    if dbversion < 120 {
+        // Times of the latest changes of synchronized config values,
+        // so that older changes from other devices are ignored, see `Context::sync_config()`.
+        sql.execute_migration(
+            "CREATE TABLE config_timestamps (
+               keyname TEXT PRIMARY KEY,
+               timestamp INTEGER NOT NULL
+             );",
+            120,
+        )
+        .await?;
+    }
+    if dbversion < 121 {
+        // Notifications suppressed during quiet hours, see `notification` module.
+        sql.execute_migration(
+            "CREATE TABLE suppressed_notifications (
+              msg_id INTEGER PRIMARY KEY,
+              chat_id INTEGER NOT NULL,
+              timestamp INTEGER NOT NULL
+            );",
+            121,
+        )
+        .await?;
+    }
+    if dbversion < 122 {
+        // Files offered peer-to-peer, see `peer_transfer` module.
+        sql.execute_migration(
+            "CREATE TABLE p2p_offers (
+               msg_id INTEGER PRIMARY KEY,
+               keypair TEXT NOT NULL, -- key of the iroh node in OpenSSH format
+               timestamp INTEGER NOT NULL
+             );",
+            122,
+        )
+        .await?;
+    }
+    if dbversion < 123 {
+        // Messages delivered late, see `late_delivery` module.
+        sql.execute_migration(
+            "CREATE TABLE late_deliveries (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              domain TEXT NOT NULL,
+              timestamp INTEGER NOT NULL,
+              delay INTEGER NOT NULL
+            );",
+            123,
+        )
+        .await?;
+    }
+    if dbversion < 124 {
+        // Canned responses, see `canned_response` module.
+        // Deleted canned responses are remembered,
+        // so that older changes from other devices do not restore them.
+        sql.execute_migration(
+            "CREATE TABLE canned_responses (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              uid TEXT NOT NULL UNIQUE,
+              title TEXT NOT NULL,
+              text TEXT NOT NULL,
+              file TEXT NOT NULL DEFAULT '',
+              timestamp INTEGER NOT NULL
+            );
+            CREATE TABLE canned_responses_deleted (
+              uid TEXT PRIMARY KEY,
+              timestamp INTEGER NOT NULL
+            );",
+            124,
+        )
+        .await?;
+    }
+    if dbversion < 125 {
+        // Challenges of the join gate, see `securejoin::join_gate` module.
+        sql.execute_migration(
+            "CREATE TABLE join_challenges (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              contact_id INTEGER NOT NULL,
+              grpid TEXT NOT NULL,
+              question TEXT NOT NULL DEFAULT '', -- only for received challenges
+              options TEXT NOT NULL DEFAULT '', -- space-separated, only for received challenges
+              answer TEXT NOT NULL DEFAULT '', -- only for sent challenges
+              attempts INTEGER NOT NULL DEFAULT 0,
+              outgoing INTEGER NOT NULL, -- 1 if sent by us, 0 if received
+              timestamp INTEGER NOT NULL,
+              auth TEXT NOT NULL DEFAULT '', -- auth token the joiner used, only for sent challenges
+              UNIQUE(contact_id, grpid, outgoing)
+            );",
+            125,
+        )
+        .await?;
+    }
+    if dbversion < 126 {
+        // Invite links with usage limits, see `securejoin::invite_link` module.
+        // `invitenumber` is the invite number belonging to an `Auth` token of an invite link.
+        sql.execute_migration(
+            "ALTER TABLE tokens ADD COLUMN max_uses INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE tokens ADD COLUMN uses INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE tokens ADD COLUMN invitenumber TEXT NOT NULL DEFAULT '';
+             CREATE TABLE invite_link_joins (
+               id INTEGER PRIMARY KEY AUTOINCREMENT,
+               token_id INTEGER NOT NULL, -- ID of the `Auth` token in the `tokens` table
+               contact_id INTEGER NOT NULL,
+               timestamp INTEGER NOT NULL
+             );
+             CREATE INDEX invite_link_joins_index1 ON invite_link_joins (token_id);",
+            126,
+        )
+        .await?;
+    }
+    if dbversion < 127 {
+        // Members banned from groups, see `chat::remove_member_and_ban()`.
+        sql.execute_migration(
+            "CREATE TABLE chat_bans (
+               chat_id INTEGER NOT NULL,
+               contact_id INTEGER NOT NULL,
+               timestamp INTEGER NOT NULL,
+               PRIMARY KEY(chat_id, contact_id)
+             );",
+            127,
+        )
+        .await?;
+    }
+    if dbversion < 128 {
+        // Messages reported as spam or abuse, see `report` module.
+        sql.execute_migration(
+            "CREATE TABLE msg_reports (
+               id INTEGER PRIMARY KEY AUTOINCREMENT,
+               msg_id INTEGER NOT NULL,
+               rfc724_mid TEXT NOT NULL,
+               category INTEGER NOT NULL,
+               addr TEXT NOT NULL, -- address the report was sent to
+               timestamp INTEGER NOT NULL
+             );
+             CREATE INDEX msg_reports_index1 ON msg_reports (msg_id);",
+            128,
+        )
+        .await?;
+    }
+    if dbversion < 129 {
+        // Subscribed blocklists, see `blocklist` module.
+        sql.execute_migration(
+            "CREATE TABLE blocklist_feeds (
+               id INTEGER PRIMARY KEY AUTOINCREMENT,
+               url TEXT NOT NULL UNIQUE,
+               fingerprint TEXT NOT NULL, -- key the list must be signed with
+               last_update INTEGER NOT NULL DEFAULT 0,
+               last_error TEXT NOT NULL DEFAULT ''
+             );
+             CREATE TABLE blocklist (
+               feed_id INTEGER NOT NULL,
+               entry TEXT NOT NULL, -- lowercase address or @domain
+               UNIQUE(feed_id, entry)
+             );
+             CREATE INDEX blocklist_index1 ON blocklist (entry);",
+            129,
+        )
+        .await?;
+    }
 
     let new_version = sql
         .get_raw_config_int(VERSION_CFG)
@@ -3,7 +3,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context as _, Result};
 use humansize::{format_size, BINARY};
 use strum::EnumProperty as EnumPropertyTrait;
 use strum_macros::EnumProperty;
@@ -15,15 +15,24 @@
 use crate::config::Config;
 use crate::contact::{Contact, ContactId, Origin};
 use crate::context::Context;
-use crate::message::{Message, Viewtype};
+use crate::message::{DeviceAction, Message, Viewtype};
+use crate::message_format::MessageFormat;
 use crate::param::Param;
-use crate::tools::timestamp_to_str;
+use crate::tools::{bidi_isolate, timestamp_to_str};
 
 /// Storage for string translations.
 #[derive(Debug, Clone)]
 pub struct StockStrings {
     /// Map from stock string ID to the translation.
     translated_stockstrings: Arc<RwLock<HashMap<usize, String>>>,
+
+    /// Map from stock string ID to the translation in ICU MessageFormat syntax.
+    ///
+    /// Takes precedence over [`StockStrings::translated_stockstrings`].
+    stock_formats: Arc<RwLock<HashMap<usize, MessageFormat>>>,
+
+    /// Language used to select plural forms of [`StockStrings::stock_formats`].
+    language: Arc<RwLock<String>>,
 }
 
 /// Stock strings
@@ -410,6 +419,100 @@ pub enum StockMessage {
 
     #[strum(props(fallback = "ℹ️ Account transferred to your second device."))]
     BackupTransferMsgBody = 163,
+
+    #[strum(props(fallback = "This media can only be viewed once. Use Delta Chat to view it."))]
+    ViewOnceFallback = 164,
+
+    #[strum(props(
+        fallback = "⚠️ Device \"%1$s\" was removed, settings changed on it are ignored from now on. Your encryption key was replaced, enter the setup code shown when removing the device on your other devices to import the new key. As the removed device may still have access to your account, consider changing your password."
+    ))]
+    OwnDeviceRevoked = 165,
+
+    #[strum(props(
+        fallback = "%1$s new messages in %2$s chats while you were away.",
+        format = "{1, plural, one {# new message} other {# new messages}} in {2, plural, one {# chat} other {# chats}} while you were away."
+    ))]
+    NotificationDigest = 166,
+
+    #[strum(props(fallback = "You can create a backup in the settings."))]
+    DeviceActionBackup = 167,
+
+    #[strum(props(fallback = "You can change notifications in the settings."))]
+    DeviceActionNotificationSettings = 168,
+
+    #[strum(props(fallback = "You can change your account in the settings."))]
+    DeviceActionAccountSettings = 169,
+
+    #[strum(props(fallback = "You can free up storage by deleting old messages."))]
+    DeviceActionManageStorage = 170,
+
+    #[strum(props(fallback = "Please update the app."))]
+    DeviceActionUpdateApp = 171,
+
+    #[strum(props(fallback = "Missed call"))]
+    MissedCall = 172,
+
+    #[strum(props(fallback = "Outgoing call"))]
+    OutgoingCall = 173,
+
+    #[strum(props(fallback = "Incoming call"))]
+    IncomingCall = 174,
+
+    #[strum(props(fallback = "Declined call"))]
+    DeclinedCall = 175,
+
+    #[strum(props(fallback = "Late Deliveries"))]
+    LateDeliveries = 176,
+
+    #[strum(props(fallback = "%1$s: %2$s messages delivered up to %3$s hours late"))]
+    LateDeliveriesFromDomain = 177,
+
+    #[strum(props(
+        fallback = "⚠️ The clock of your device differs by about %1$s minutes from the time of your server.\n\n\
+                    Adjust your clock ⏰🔧 to ensure messages are ordered correctly \
+                    and disappearing messages work as expected."
+    ))]
+    ClockSkewMsgBody = 178,
+
+    #[strum(props(fallback = "%1$s queued messages will be sent from your new address %2$s."))]
+    RequeuedMsgs = 179,
+
+    #[strum(props(
+        fallback = "⚠️ %1$s queued messages could not be prepared for your new address and were not sent."
+    ))]
+    RequeuedMsgsFailed = 180,
+
+    #[strum(props(fallback = "To join the group, select the %1$s."))]
+    JoinChallengeQuestion = 181,
+
+    #[strum(props(fallback = "apple"))]
+    JoinChallengeApple = 182,
+
+    #[strum(props(fallback = "bicycle"))]
+    JoinChallengeBicycle = 183,
+
+    #[strum(props(fallback = "cat"))]
+    JoinChallengeCat = 184,
+
+    #[strum(props(fallback = "tree"))]
+    JoinChallengeTree = 185,
+
+    #[strum(props(fallback = "house"))]
+    JoinChallengeHouse = 186,
+
+    #[strum(props(fallback = "star"))]
+    JoinChallengeStar = 187,
+
+    #[strum(props(fallback = "balloon"))]
+    JoinChallengeBalloon = 188,
+
+    #[strum(props(fallback = "key"))]
+    JoinChallengeKey = 189,
+
+    #[strum(props(
+        fallback = "%1$s wants to join the group, but their app cannot answer the join challenge. Add them manually if you know them."
+    ))]
+    JoinChallengeUnsupported = 190,
 }
 
 impl StockMessage {
@@ -419,6 +522,12 @@ impl StockMessage {
     fn fallback(self) -> &'static str {
         self.get_str("fallback").unwrap_or_default()
     }
+
+    /// Default untranslated strings with plural forms in ICU MessageFormat syntax,
+    /// used instead of [`StockMessage::fallback`] when the arguments are known.
+    fn fallback_format(self) -> Option<&'static str> {
+        self.get_str("format")
+    }
 }
 
 impl Default for StockStrings {
@@ -432,10 +541,18 @@ impl StockStrings {
     pub fn new() -> Self {
         Self {
             translated_stockstrings: Arc::new(RwLock::new(Default::default())),
+            stock_formats: Arc::new(RwLock::new(Default::default())),
+            language: Arc::new(RwLock::new("en".to_string())),
         }
     }
 
     async fn translated(&self, id: StockMessage) -> String {
+        if let Some(format) = self.stock_formats.read().await.get(&(id as usize)) {
+            // Keep the placeholders for the callers substituting them,
+            // plural arguments use the "other" form then.
+            let language = self.language.read().await;
+            return format.format(&language, &["%1$s", "%2$s", "%3$s"]);
+        }
         self.translated_stockstrings
             .read()
             .await
@@ -445,6 +562,41 @@ async fn translated(&self, id: StockMessage) -> String {
             .to_string()
     }
 
+    /// Returns the translation with the placeholders substituted by `args`.
+    ///
+    /// Unlike substituting the placeholders of [`StockStrings::translated`],
+    /// this selects the plural forms of translations in ICU MessageFormat syntax.
+    async fn translated_args(&self, id: StockMessage, args: &[&str]) -> String {
+        let isolated: Vec<_> = args.iter().map(|arg| bidi_isolate(arg)).collect();
+        let isolated: Vec<&str> = isolated.iter().map(AsRef::as_ref).collect();
+        if let Some(format) = self.stock_formats.read().await.get(&(id as usize)) {
+            let language = self.language.read().await;
+            return format.format(&language, &isolated);
+        }
+        if !self
+            .translated_stockstrings
+            .read()
+            .await
+            .contains_key(&(id as usize))
+        {
+            if let Some(format) = id
+                .fallback_format()
+                .and_then(|format| MessageFormat::parse(format).ok())
+            {
+                return format.format("en", &isolated);
+            }
+        }
+        let mut res = self.translated(id).await;
+        for (i, arg) in args.iter().enumerate() {
+            res = match i {
+                0 => res.replace1(arg),
+                1 => res.replace2(arg),
+                _ => res.replace3(arg),
+            };
+        }
+        res
+    }
+
     async fn set_stock_translation(&self, id: StockMessage, stockstring: String) -> Result<()> {
         if stockstring.contains("%1") && !id.fallback().contains("%1") {
             bail!(
@@ -464,18 +616,50 @@ async fn set_stock_translation(&self, id: StockMessage, stockstring: String) ->
             .write()
             .await
             .insert(id as usize, stockstring);
+        self.stock_formats.write().await.remove(&(id as usize));
+        Ok(())
+    }
+
+    async fn set_stock_format(&self, id: StockMessage, format: &str) -> Result<()> {
+        let parsed = MessageFormat::parse(format)
+            .with_context(|| format!("invalid message format {format:?}"))?;
+        let max_arg = parsed.max_arg();
+        if max_arg > 0 && !id.fallback().contains(&format!("%{max_arg}$")) {
+            bail!(
+                "translation {} contains invalid argument {{{}}}, default is {}",
+                format,
+                max_arg,
+                id.fallback()
+            );
+        }
+        self.stock_formats.write().await.insert(id as usize, parsed);
         Ok(())
     }
+
+    async fn set_stock_language(&self, language: &str) {
+        *self.language.write().await = language.to_string();
+    }
 }
 
 async fn translated(context: &Context, id: StockMessage) -> String {
     context.translated_stockstrings.translated(id).await
 }
 
+async fn translated_args(context: &Context, id: StockMessage, args: &[&str]) -> String {
+    context
+        .translated_stockstrings
+        .translated_args(id, args)
+        .await
+}
+
 /// Helper trait only meant to be implemented for [`String`].
+///
+/// Replacement values are isolated using [`bidi_isolate`]
+/// as they often contain user-provided names.
 trait StockStringMods: AsRef<str> + Sized {
     /// Substitutes the first replacement value if one is present.
     fn replace1(&self, replacement: &str) -> String {
+        let replacement = &bidi_isolate(replacement);
         self.as_ref()
             .replacen("%1$s", replacement, 1)
             .replacen("%1$d", replacement, 1)
@@ -487,6 +671,7 @@ fn replace1(&self, replacement: &str) -> String {
     /// Be aware you probably should have also called [`StockStringMods::replace1`] if
     /// you are calling this.
     fn replace2(&self, replacement: &str) -> String {
+        let replacement = &bidi_isolate(replacement);
         self.as_ref()
             .replacen("%2$s", replacement, 1)
             .replacen("%2$d", replacement, 1)
@@ -498,6 +683,7 @@ fn replace2(&self, replacement: &str) -> String {
     /// Be aware you probably should have also called [`StockStringMods::replace1`] and
     /// [`StockStringMods::replace2`] if you are calling this.
     fn replace3(&self, replacement: &str) -> String {
+        let replacement = &bidi_isolate(replacement);
         self.as_ref()
             .replacen("%3$s", replacement, 1)
             .replacen("%3$d", replacement, 1)
@@ -1047,14 +1233,20 @@ pub(crate) async fn msg_ephemeral_timer_minutes(
     by_contact: ContactId,
 ) -> String {
     if by_contact == ContactId::SELF {
-        translated(context, StockMessage::MsgYouEphemeralTimerMinutes)
-            .await
-            .replace1(minutes)
+        translated_args(
+            context,
+            StockMessage::MsgYouEphemeralTimerMinutes,
+            &[minutes],
+        )
+        .await
     } else {
-        translated(context, StockMessage::MsgEphemeralTimerMinutesBy)
-            .await
-            .replace1(minutes)
-            .replace2(&by_contact.get_stock_name(context).await)
+        let name = by_contact.get_stock_name(context).await;
+        translated_args(
+            context,
+            StockMessage::MsgEphemeralTimerMinutesBy,
+            &[minutes, &name],
+        )
+        .await
     }
 }
 
@@ -1065,14 +1257,15 @@ pub(crate) async fn msg_ephemeral_timer_hours(
     by_contact: ContactId,
 ) -> String {
     if by_contact == ContactId::SELF {
-        translated(context, StockMessage::MsgYouEphemeralTimerHours)
-            .await
-            .replace1(hours)
+        translated_args(context, StockMessage::MsgYouEphemeralTimerHours, &[hours]).await
     } else {
-        translated(context, StockMessage::MsgEphemeralTimerHoursBy)
-            .await
-            .replace1(hours)
-            .replace2(&by_contact.get_stock_name(context).await)
+        let name = by_contact.get_stock_name(context).await;
+        translated_args(
+            context,
+            StockMessage::MsgEphemeralTimerHoursBy,
+            &[hours, &name],
+        )
+        .await
     }
 }
 
@@ -1083,14 +1276,15 @@ pub(crate) async fn msg_ephemeral_timer_days(
     by_contact: ContactId,
 ) -> String {
     if by_contact == ContactId::SELF {
-        translated(context, StockMessage::MsgYouEphemeralTimerDays)
-            .await
-            .replace1(days)
+        translated_args(context, StockMessage::MsgYouEphemeralTimerDays, &[days]).await
     } else {
-        translated(context, StockMessage::MsgEphemeralTimerDaysBy)
-            .await
-            .replace1(days)
-            .replace2(&by_contact.get_stock_name(context).await)
+        let name = by_contact.get_stock_name(context).await;
+        translated_args(
+            context,
+            StockMessage::MsgEphemeralTimerDaysBy,
+            &[days, &name],
+        )
+        .await
     }
 }
 
@@ -1101,14 +1295,15 @@ pub(crate) async fn msg_ephemeral_timer_weeks(
     by_contact: ContactId,
 ) -> String {
     if by_contact == ContactId::SELF {
-        translated(context, StockMessage::MsgYouEphemeralTimerWeeks)
-            .await
-            .replace1(weeks)
+        translated_args(context, StockMessage::MsgYouEphemeralTimerWeeks, &[weeks]).await
     } else {
-        translated(context, StockMessage::MsgEphemeralTimerWeeksBy)
-            .await
-            .replace1(weeks)
-            .replace2(&by_contact.get_stock_name(context).await)
+        let name = by_contact.get_stock_name(context).await;
+        translated_args(
+            context,
+            StockMessage::MsgEphemeralTimerWeeksBy,
+            &[weeks, &name],
+        )
+        .await
     }
 }
 
@@ -1268,6 +1463,134 @@ pub(crate) async fn backup_transfer_msg_body(context: &Context) -> String {
     translated(context, StockMessage::BackupTransferMsgBody).await
 }
 
+/// Stock string: `This media can only be viewed once. Use Delta Chat to view it.`.
+pub(crate) async fn view_once_fallback(context: &Context) -> String {
+    translated(context, StockMessage::ViewOnceFallback).await
+}
+
+/// Stock string: `⚠️ Device "%1$s" was removed, settings changed on it are ignored from now on. …`.
+pub(crate) async fn own_device_revoked(context: &Context, device_name: &str) -> String {
+    translated(context, StockMessage::OwnDeviceRevoked)
+        .await
+        .replace1(device_name)
+}
+
+/// Stock string describing a [`DeviceAction`] for UIs not supporting device actions.
+pub(crate) async fn device_action(context: &Context, action: DeviceAction) -> String {
+    let id = match action {
+        DeviceAction::Backup => StockMessage::DeviceActionBackup,
+        DeviceAction::NotificationSettings => StockMessage::DeviceActionNotificationSettings,
+        DeviceAction::AccountSettings => StockMessage::DeviceActionAccountSettings,
+        DeviceAction::ManageStorage => StockMessage::DeviceActionManageStorage,
+        DeviceAction::UpdateApp => StockMessage::DeviceActionUpdateApp,
+    };
+    translated(context, id).await
+}
+
+/// Stock string: `Missed call`.
+pub(crate) async fn missed_call(context: &Context) -> String {
+    translated(context, StockMessage::MissedCall).await
+}
+
+/// Stock string: `Outgoing call`.
+pub(crate) async fn outgoing_call(context: &Context) -> String {
+    translated(context, StockMessage::OutgoingCall).await
+}
+
+/// Stock string: `Incoming call`.
+pub(crate) async fn incoming_call(context: &Context) -> String {
+    translated(context, StockMessage::IncomingCall).await
+}
+
+/// Stock string: `Declined call`.
+pub(crate) async fn declined_call(context: &Context) -> String {
+    translated(context, StockMessage::DeclinedCall).await
+}
+
+/// Stock string: `Late Deliveries`.
+pub(crate) async fn late_deliveries(context: &Context) -> String {
+    translated(context, StockMessage::LateDeliveries).await
+}
+
+/// Stock string: `%1$s: %2$s messages delivered up to %3$s hours late`.
+pub(crate) async fn late_deliveries_from_domain(
+    context: &Context,
+    domain: &str,
+    count: u32,
+    max_hours: i64,
+) -> String {
+    translated(context, StockMessage::LateDeliveriesFromDomain)
+        .await
+        .replace1(domain)
+        .replace2(&count.to_string())
+        .replace3(&max_hours.to_string())
+}
+
+/// Stock string: `⚠️ The clock of your device differs by about %1$s minutes from the time of your server...`.
+pub(crate) async fn clock_skew_msg_body(context: &Context, minutes: i64) -> String {
+    translated(context, StockMessage::ClockSkewMsgBody)
+        .await
+        .replace1(&minutes.to_string())
+}
+
+/// Stock string: `%1$s queued messages will be sent from your new address %2$s.`.
+pub(crate) async fn requeued_msgs(context: &Context, count: usize, addr: &str) -> String {
+    translated(context, StockMessage::RequeuedMsgs)
+        .await
+        .replace1(&count.to_string())
+        .replace2(addr)
+}
+
+/// Stock string: `⚠️ %1$s queued messages could not be prepared for your new address and were not sent.`.
+pub(crate) async fn requeued_msgs_failed(context: &Context, count: usize) -> String {
+    translated(context, StockMessage::RequeuedMsgsFailed)
+        .await
+        .replace1(&count.to_string())
+}
+
+/// Stock string: `To join the group, select the %1$s.`.
+///
+/// The option is replaced by its name, so the question does not contain the answer.
+pub(crate) async fn join_challenge_question(context: &Context, option: &str) -> String {
+    let name = join_challenge_option_name(context, option).await;
+    translated(context, StockMessage::JoinChallengeQuestion)
+        .await
+        .replace1(&name)
+}
+
+/// Stock strings: names of the [`crate::securejoin::CHALLENGE_OPTIONS`], e.g. `apple`.
+pub(crate) async fn join_challenge_option_name(context: &Context, option: &str) -> String {
+    let id = match option {
+        "🍎" => StockMessage::JoinChallengeApple,
+        "🚲" => StockMessage::JoinChallengeBicycle,
+        "🐱" => StockMessage::JoinChallengeCat,
+        "🌳" => StockMessage::JoinChallengeTree,
+        "🏠" => StockMessage::JoinChallengeHouse,
+        "⭐" => StockMessage::JoinChallengeStar,
+        "🎈" => StockMessage::JoinChallengeBalloon,
+        "🔑" => StockMessage::JoinChallengeKey,
+        _ => return option.to_string(),
+    };
+    translated(context, id).await
+}
+
+/// Stock string: `%1$s wants to join the group, but their app cannot answer the join challenge. Add them manually if you know them.`.
+pub(crate) async fn join_challenge_unsupported(context: &Context, contact_id: ContactId) -> String {
+    translated(context, StockMessage::JoinChallengeUnsupported)
+        .await
+        .replace1(&contact_id.get_stock_name(context).await)
+}
+
+/// Stock string: `%1$s new messages in %2$s chats while you were away.`.
+pub(crate) async fn notification_digest(context: &Context, msgs: usize, chats: usize) -> String {
+    translated_args(
+        context,
+        StockMessage::NotificationDigest,
+        &[&msgs.to_string(), &chats.to_string()],
+    )
+    .await
+}
+
 impl Context {
     /// Set the stock string for the [StockMessage].
     ///
@@ -1278,6 +1601,26 @@ pub async fn set_stock_translation(&self, id: StockMessage, stockstring: String)
         Ok(())
     }
 
+    /// Set the stock string for the [StockMessage] in ICU MessageFormat syntax.
+    ///
+    /// Unlike [`Context::set_stock_translation`], this allows plural forms,
+    /// e.g. `{1, plural, one {# minute} other {# minutes}}`,
+    /// where `{1}` refers to the `%1$s` placeholder of the stock string.
+    pub async fn set_stock_format(&self, id: StockMessage, format: &str) -> Result<()> {
+        self.translated_stockstrings
+            .set_stock_format(id, format)
+            .await
+    }
+
+    /// Sets the language used to select plural forms, e.g. `de` or `pt-BR`.
+    ///
+    /// Defaults to `en`.
+    pub async fn set_stock_language(&self, language: &str) {
+        self.translated_stockstrings
+            .set_stock_language(language)
+            .await
+    }
+
     /// Returns a stock message saying that protection status has changed.
     pub(crate) async fn stock_protection_msg(
         &self,
@@ -1328,6 +1671,17 @@ pub async fn set_stock_translation(&self, id: StockMessage, stockstring: String)
             .await?;
         Ok(())
     }
+
+    /// Set the stock string for the [StockMessage] in ICU MessageFormat syntax,
+    /// see [`Context::set_stock_format`].
+    pub async fn set_stock_format(&self, id: StockMessage, format: &str) -> Result<()> {
+        self.stockstrings.set_stock_format(id, format).await
+    }
+
+    /// Sets the language used to select plural forms, see [`Context::set_stock_language`].
+    pub async fn set_stock_language(&self, language: &str) {
+        self.stockstrings.set_stock_language(language).await
+    }
 }
 
 #[cfg(test)]
@@ -1375,6 +1729,63 @@ async fn test_set_stock_translation_wrong_replacements() {
             .is_err());
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_set_stock_format() -> Result<()> {
+        let t = TestContext::new().await;
+        assert!(t
+            .set_stock_format(StockMessage::NoMessages, "{1} messages")
+            .await
+            .is_err());
+        assert!(t
+            .set_stock_format(
+                StockMessage::MsgYouEphemeralTimerMinutes,
+                "{1, plural, one {x}}"
+            )
+            .await
+            .is_err());
+
+        t.set_stock_language("pl").await;
+        t.set_stock_format(
+            StockMessage::MsgYouEphemeralTimerMinutes,
+            "Ustawiono {1, plural, one {# minutę} few {# minuty} many {# minut} other {# minuty}}.",
+        )
+        .await?;
+        assert_eq!(
+            msg_ephemeral_timer_minutes(&t, "2", ContactId::SELF).await,
+            "Ustawiono 2 minuty."
+        );
+        assert_eq!(
+            msg_ephemeral_timer_minutes(&t, "5", ContactId::SELF).await,
+            "Ustawiono 5 minut."
+        );
+        assert_eq!(
+            msg_ephemeral_timer_minutes(&t, "1.5", ContactId::SELF).await,
+            "Ustawiono 1.5 minuty."
+        );
+
+        // Stock strings without plural support keep the placeholders.
+        t.set_stock_format(StockMessage::ContactVerified, "{1} zweryfikowany.")
+            .await?;
+        let contact_id = Contact::create(&t, "Someone", "someone@example.org").await?;
+        let contact = Contact::load_from_db(&t, contact_id).await?;
+        assert_eq!(
+            contact_verified(&t, &contact).await,
+            "Someone (someone@example.org) zweryfikowany."
+        );
+
+        // Plain translations replace the format.
+        t.set_stock_translation(
+            StockMessage::MsgYouEphemeralTimerMinutes,
+            "%1$s min".to_string(),
+        )
+        .await?;
+        assert_eq!(
+            msg_ephemeral_timer_minutes(&t, "5", ContactId::SELF).await,
+            "5 min"
+        );
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_stock_str() {
         let t = TestContext::new().await;
@@ -1396,6 +1807,18 @@ async fn test_stock_string_repl_str() {
         // We have no string using %1$d to test...
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_stock_string_bidi_isolation() -> Result<()> {
+        let t = TestContext::new().await;
+        let contact_id = Contact::create(&t, "שלום", "someone@example.org").await?;
+        let contact = Contact::load_from_db(&t, contact_id).await?;
+        assert_eq!(
+            contact_verified(&t, &contact).await,
+            "\u{2068}שלום (someone@example.org)\u{2069} verified."
+        );
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_stock_system_msg_simple() {
         let t = TestContext::new().await;
@@ -3,7 +3,11 @@
 use std::borrow::Cow;
 use std::fmt;
 
+use anyhow::Result;
+
+use crate::calls;
 use crate::chat::Chat;
+use crate::config::Config;
 use crate::constants::Chattype;
 use crate::contact::{Contact, ContactId};
 use crate::context::Context;
@@ -11,7 +15,7 @@
 use crate::mimeparser::SystemMessage;
 use crate::param::Param;
 use crate::stock_str;
-use crate::tools::truncate;
+use crate::tools::{bidi_isolate, text_direction, truncate, TextDirection};
 
 /// Prefix displayed before message and separated by ":" in the chatlist.
 #[derive(Debug)]
@@ -55,12 +59,15 @@ pub struct Summary {
 impl Summary {
     /// Constructs chatlist summary
     /// from the provided message, chat and message author contact snapshots.
+    ///
+    /// The summary is built according to [`Config::SummaryMaxChars`],
+    /// [`Config::SummaryEmoji`] and [`Config::SummaryShowSender`].
     pub async fn new(
         context: &Context,
         msg: &Message,
         chat: &Chat,
         contact: Option<&Contact>,
-    ) -> Self {
+    ) -> Result<Self> {
         let prefix = if msg.state == MessageState::OutDraft {
             Some(SummaryPrefix::Draft(stock_str::draft(context).await))
         } else if msg.from_id == ContactId::SELF {
@@ -72,7 +79,10 @@ pub async fn new(
         } else {
             match chat.typ {
                 Chattype::Group | Chattype::Broadcast | Chattype::Mailinglist => {
-                    if msg.is_info() || contact.is_none() {
+                    if msg.is_info()
+                        || contact.is_none()
+                        || !context.get_config_bool(Config::SummaryShowSender).await?
+                    {
                         None
                     } else {
                         msg.get_override_sender_name()
@@ -90,21 +100,64 @@ pub async fn new(
             text = stock_str::reply_noun(context).await
         }
 
-        Self {
+        if context.get_config_bool(Config::SummaryEmoji).await? {
+            if let Some(emoji) = msg.get_summary_emoji() {
+                text = format!("{emoji} {text}");
+            }
+        }
+
+        let max_chars = context.get_config_int(Config::SummaryMaxChars).await?;
+        if max_chars > 0 {
+            text = truncate(&text, max_chars as usize).into_owned();
+        }
+
+        Ok(Self {
             prefix,
             text,
             timestamp: msg.get_timestamp(),
             state: msg.state,
-        }
+        })
     }
 
     /// Returns the [`Summary::text`] attribute truncated to an approximate length.
     pub fn truncated_text(&self, approx_chars: usize) -> Cow<str> {
         truncate(&self.text, approx_chars)
     }
+
+    /// Returns the direction of the [`Summary::text`] attribute,
+    /// so UIs can align it without analyzing the text.
+    pub fn text_direction(&self) -> TextDirection {
+        text_direction(&self.text)
+    }
 }
 
 impl Message {
+    /// Returns the emoji shown before the summary text if [`Config::SummaryEmoji`] is set.
+    fn get_summary_emoji(&self) -> Option<&'static str> {
+        let emoji = match self.viewtype {
+            Viewtype::Image => "\u{1f4f7}",                 // 📷
+            Viewtype::Gif | Viewtype::Video => "\u{1f3a5}", // 🎥
+            Viewtype::Sticker => "\u{1f3f7}",               // 🏷
+            Viewtype::Voice => "\u{1f3a4}",                 // 🎤
+            Viewtype::Audio => "\u{1f3b5}",                 // 🎵
+            Viewtype::File => {
+                if self.param.get_cmd() == SystemMessage::AutocryptSetupMessage {
+                    return None;
+                }
+                "\u{1f4ce}" // 📎
+            }
+            Viewtype::VideochatInvitation | Viewtype::Call => "\u{1f4de}", // 📞
+            Viewtype::Webxdc => "\u{1f4f1}",                               // 📱
+            Viewtype::Text | Viewtype::Unknown => {
+                if self.param.get_cmd() != SystemMessage::LocationOnly {
+                    return None;
+                }
+                "\u{1f4cd}" // 📍
+            }
+        };
+        Some(emoji)
+    }
+
     /// Returns a summary text.
     async fn get_summary_text(&self, context: &Context) -> String {
         let mut append_text = true;
@@ -133,13 +186,17 @@ async fn get_summary_text(&self, context: &Context) -> String {
                     } else {
                         stock_str::file(context).await
                     };
-                    format!("{label} – {file_name}")
+                    format!("{label} – {}", bidi_isolate(&file_name))
                 }
             }
             Viewtype::VideochatInvitation => {
                 append_text = false;
                 stock_str::videochat_invitation(context).await
             }
+            Viewtype::Call => {
+                append_text = false;
+                calls::get_summary_text(context, self).await
+            }
             Viewtype::Webxdc => {
                 append_text = true;
                 self.get_webxdc_info(context)
@@ -323,4 +380,65 @@ async fn test_get_summary_text() {
             "Autocrypt Setup Message" // file name is not added for autocrypt setup messages
         );
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_summary_config() -> Result<()> {
+        let alice = test::TestContext::new_alice().await;
+        let bob = test::TestContext::new_bob().await;
+        let alice_chat_id = alice
+            .create_chat_with_contact("Bob", "bob@example.net")
+            .await
+            .id;
+        let group_id = crate::chat::create_group_chat(
+            &alice,
+            crate::chat::ProtectionStatus::Unprotected,
+            "Group",
+        )
+        .await?;
+        let alice_bob_id = Contact::create(&alice, "Bob", "bob@example.net").await?;
+        crate::chat::add_contact_to_chat(&alice, group_id, alice_bob_id).await?;
+        crate::chat::send_text_msg(&alice, group_id, "hi".to_string()).await?;
+        let group_id = bob.recv_msg(&alice.pop_sent_msg().await).await.chat_id;
+
+        let mut msg = Message::new(Viewtype::Image);
+        msg.set_file("foo.jpg", None);
+        msg.set_text(Some("A very long text about a picture".to_string()));
+        let chat = Chat::load_from_db(&alice, alice_chat_id).await?;
+        let summary = Summary::new(&alice, &msg, &chat, None).await?;
+        assert_eq!(
+            summary.text,
+            "Image \u{2013} A very long text about a picture"
+        );
+
+        alice.set_config_bool(Config::SummaryEmoji, true).await?;
+        alice
+            .set_config(Config::SummaryMaxChars, Some("20"))
+            .await?;
+        let summary = Summary::new(&alice, &msg, &chat, None).await?;
+        assert!(summary.text.starts_with("\u{1f4f7} Image \u{2013} A"));
+        assert!(summary.text.ends_with("[...]"));
+        assert!(summary.text.chars().count() < 30);
+
+        // Labels are localized using stock strings.
+        alice
+            .set_stock_translation(crate::stock_str::StockMessage::Image, "Bild".to_string())
+            .await?;
+        alice.set_config(Config::SummaryMaxChars, None).await?;
+        let summary = Summary::new(&alice, &msg, &chat, None).await?;
+        assert_eq!(
+            summary.text,
+            "\u{1f4f7} Bild \u{2013} A very long text about a picture"
+        );
+
+        // Sender name is shown in groups unless disabled.
+        let group_msg = bob.get_last_msg_in(group_id).await;
+        let summary = group_msg.get_summary(&bob, None).await?;
+        assert!(matches!(summary.prefix, Some(SummaryPrefix::Username(_))));
+        bob.set_config_bool(Config::SummaryShowSender, false)
+            .await?;
+        let summary = group_msg.get_summary(&bob, None).await?;
+        assert!(summary.prefix.is_none());
+        assert_eq!(summary.text, "hi");
+        Ok(())
+    }
 }
@@ -5,6 +5,7 @@
 use lettre_email::PartBuilder;
 use serde::{Deserialize, Serialize};
 
+use crate::canned_response::{self, CannedResponseData};
 use crate::chat::{Chat, ChatId};
 use crate::config::Config;
 use crate::constants::Blocked;
@@ -13,7 +14,10 @@
 use crate::message::{Message, MsgId, Viewtype};
 use crate::mimeparser::SystemMessage;
 use crate::param::Param;
-use crate::sync::SyncData::{AddQrToken, DeleteQrToken};
+use crate::settings::ChatRef;
+use crate::sync::SyncData::{
+    AddQrToken, ChatSignature, ConfigValue, DeleteCannedResponse, DeleteQrToken, SetCannedResponse,
+};
 use crate::token::Namespace;
 use crate::tools::time;
 use crate::{chat, stock_str, token};
@@ -25,10 +29,26 @@ pub(crate) struct QrTokenData {
     pub(crate) grpid: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ChatSignatureData {
+    pub(crate) chat: ChatRef,
+    pub(crate) signature: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ConfigData {
+    pub(crate) key: String,
+    pub(crate) value: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) enum SyncData {
     AddQrToken(QrTokenData),
     DeleteQrToken(QrTokenData),
+    ChatSignature(ChatSignatureData),
+    ConfigValue(ConfigData),
+    SetCannedResponse(CannedResponseData),
+    DeleteCannedResponse(String),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,11 +57,17 @@ pub(crate) struct SyncItem {
     data: SyncData,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub(crate) struct SyncItems {
     items: Vec<SyncItem>,
 }
 
+/// Sync items as sent, each item is parsed separately.
+#[derive(Debug, Deserialize)]
+struct SerializedSyncItems {
+    items: Vec<serde_json::Value>,
+}
+
 impl Context {
     /// Checks if sync messages shall be sent.
     /// Receiving sync messages is currently always enabled;
@@ -121,6 +147,54 @@ pub(crate) async fn sync_qr_code_token_deletion(
         .await
     }
 
+    /// Adds the signature of a chat to the list of items to be synced
+    /// and sends out the sync message.
+    /// If device synchronization is disabled
+    /// or the chat cannot be referenced on other devices, the function does nothing.
+    pub(crate) async fn sync_chat_signature(&self, chat: &Chat) -> Result<()> {
+        if !self.is_sync_sending_enabled().await? {
+            return Ok(());
+        }
+
+        if let Some(chat_ref) = self.get_chat_ref(chat).await? {
+            self.add_sync_item(SyncData::ChatSignature(ChatSignatureData {
+                chat: chat_ref,
+                signature: chat.get_signature().map(|s| s.to_string()),
+            }))
+            .await?;
+            self.send_sync_msg().await?;
+        }
+        Ok(())
+    }
+
+    /// Remembers the time of the change of a configuration value,
+    /// adds the value to the list of items to be synced and sends out the sync message.
+    /// If device synchronization is disabled, the value is not sent.
+    pub(crate) async fn sync_config(&self, key: Config, value: Option<&str>) -> Result<()> {
+        let timestamp = time();
+        self.sql
+            .execute(
+                "INSERT INTO config_timestamps (keyname, timestamp) VALUES (?, ?)
+                 ON CONFLICT(keyname) DO UPDATE SET timestamp=excluded.timestamp",
+                (key.as_ref(), timestamp),
+            )
+            .await?;
+        if !self.is_sync_sending_enabled().await? {
+            return Ok(());
+        }
+
+        self.add_sync_item_with_timestamp(
+            SyncData::ConfigValue(ConfigData {
+                key: key.to_string(),
+                value: value.map(|value| value.to_string()),
+            }),
+            timestamp,
+        )
+        .await?;
+        self.send_sync_msg().await?;
+        Ok(())
+    }
+
     /// Sends out a self-sent message with items to be synchronized, if any.
     pub async fn send_sync_msg(&self) -> Result<Option<MsgId>> {
         if let Some((json, ids)) = self.build_sync_json().await? {
@@ -206,9 +280,56 @@ pub(crate) async fn delete_sync_ids(&self, ids: String) -> Result<()> {
 
     /// Takes a JSON string created by `build_sync_json()`
     /// and construct `SyncItems` from it.
+    ///
+    /// Items which cannot be parsed, e.g. unknown items sent by newer versions,
+    /// are skipped so that they do not prevent the other items from being executed.
     pub(crate) fn parse_sync_items(&self, serialized: String) -> Result<SyncItems> {
-        let sync_items: SyncItems = serde_json::from_str(&serialized)?;
-        Ok(sync_items)
+        let serialized: SerializedSyncItems = serde_json::from_str(&serialized)?;
+        let items = serialized
+            .items
+            .into_iter()
+            .filter_map(|item| match serde_json::from_value(item) {
+                Ok(item) => Some(item),
+                Err(err) => {
+                    warn!(self, "Ignoring sync item which cannot be parsed: {err:#}.");
+                    None
+                }
+            })
+            .collect();
+        Ok(SyncItems { items })
+    }
+
+    /// Sets a configuration value changed on another device.
+    ///
+    /// Invalid values and changes older than the latest local or synchronized change are ignored.
+    async fn set_config_from_sync(&self, data: &ConfigData, timestamp: i64) -> Result<()> {
+        let key = match data.key.parse::<Config>() {
+            Ok(key) if key.is_synced() => key,
+            _ => {
+                warn!(self, "Ignoring sync item for config {:?}.", data.key);
+                return Ok(());
+            }
+        };
+        let value = data.value.as_deref();
+        if let Err(err) = key.check_synced_value(value) {
+            warn!(self, "Ignoring sync item for config {key}: {err:#}.");
+            return Ok(());
+        }
+        let newer = self
+            .sql
+            .execute(
+                "INSERT INTO config_timestamps (keyname, timestamp) VALUES (?, ?)
+                 ON CONFLICT(keyname) DO UPDATE SET timestamp=excluded.timestamp
+                 WHERE excluded.timestamp>=config_timestamps.timestamp",
+                (key.as_ref(), timestamp),
+            )
+            .await?;
+        if newer > 0 {
+            self.sql.set_raw_config(key.as_ref(), value).await?;
+        } else {
+            info!(self, "Ignoring outdated sync item for config {key}.");
+        }
+        Ok(())
     }
 
     /// Execute sync items.
@@ -250,6 +371,21 @@ pub(crate) async fn execute_sync_items(&self, items: &SyncItems) -> Result<()> {
                     token::delete(self, Namespace::InviteNumber, &token.invitenumber).await?;
                     token::delete(self, Namespace::Auth, &token.auth).await?;
                 }
+                ChatSignature(data) => match self.lookup_chat_ref(&data.chat).await? {
+                    Some(chat_id) => {
+                        chat_id
+                            .set_signature_ex(self, data.signature.as_deref(), false)
+                            .await?;
+                    }
+                    None => warn!(self, "Ignoring signature for unknown chat {:?}.", data.chat),
+                },
+                ConfigValue(data) => self.set_config_from_sync(data, item.timestamp).await?,
+                SetCannedResponse(data) => {
+                    canned_response::set_from_sync(self, data, item.timestamp).await?;
+                }
+                DeleteCannedResponse(uid) => {
+                    canned_response::delete_from_sync(self, uid, item.timestamp).await?;
+                }
             }
         }
         Ok(())
@@ -344,36 +480,22 @@ async fn test_parse_sync_items() -> Result<()> {
 
         assert!(t.parse_sync_items(r#"{"badname":[]}"#.to_string()).is_err());
 
-        assert!(t.parse_sync_items(
-                r#"{"items":[{"timestamp":1631781316,"data":{"BadItem":{"invitenumber":"in","auth":"a","grpid":null}}}]}"#
-                    .to_string(),
-            )
-            .is_err());
-
-        assert!(t.parse_sync_items(
-                r#"{"items":[{"timestamp":1631781316,"data":{"AddQrToken":{"invitenumber":"in","auth":123}}}]}"#.to_string(),
-            )
-            .is_err()); // `123` is invalid for `String`
-
-        assert!(t.parse_sync_items(
-                r#"{"items":[{"timestamp":1631781316,"data":{"AddQrToken":{"invitenumber":"in","auth":true}}}]}"#.to_string(),
-            )
-            .is_err()); // `true` is invalid for `String`
-
-        assert!(t.parse_sync_items(
-                r#"{"items":[{"timestamp":1631781316,"data":{"AddQrToken":{"invitenumber":"in","auth":[]}}}]}"#.to_string(),
-            )
-            .is_err()); // `[]` is invalid for `String`
-
-        assert!(t.parse_sync_items(
-                r#"{"items":[{"timestamp":1631781316,"data":{"AddQrToken":{"invitenumber":"in","auth":{}}}}]}"#.to_string(),
-            )
-            .is_err()); // `{}` is invalid for `String`
-
-        assert!(t.parse_sync_items(
-                r#"{"items":[{"timestamp":1631781316,"data":{"AddQrToken":{"invitenumber":"in","grpid":null}}}]}"#.to_string(),
-            )
-            .is_err()); // missing field
+        // Items which cannot be parsed, e.g. items of newer versions, are skipped one by one.
+        for item in [
+            r#"{"BadItem":{"invitenumber":"in","auth":"a","grpid":null}}"#,
+            r#"{"AddQrToken":{"invitenumber":"in","auth":123}}"#, // `123` is invalid for `String`
+            r#"{"AddQrToken":{"invitenumber":"in","auth":true}}"#, // `true` is invalid for `String`
+            r#"{"AddQrToken":{"invitenumber":"in","auth":[]}}"#,  // `[]` is invalid for `String`
+            r#"{"AddQrToken":{"invitenumber":"in","auth":{}}}"#,  // `{}` is invalid for `String`
+            r#"{"AddQrToken":{"invitenumber":"in","grpid":null}}"#, // missing field
+        ] {
+            let sync_items = t.parse_sync_items(format!(
+                r#"{{"items":[{{"timestamp":1631781316,"data":{item}}},
+{{"timestamp":1631781317,"data":{{"DeleteQrToken":{{"invitenumber":"in","auth":"a"}}}}}}]}}"#
+            ))?;
+            assert_eq!(sync_items.items.len(), 1);
+            assert!(matches!(sync_items.items[0].data, DeleteQrToken(_)));
+        }
 
         // empty item list is okay
         assert_eq!(
@@ -491,4 +613,88 @@ async fn test_send_sync_msg() -> Result<()> {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_sync_chat_signature() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let alice2 = TestContext::new_alice().await;
+        alice.set_config_bool(Config::SendSyncMsgs, true).await?;
+        let chat = alice
+            .create_chat_with_contact("Bob", "bob@example.net")
+            .await;
+        let chat2 = alice2
+            .create_chat_with_contact("Bob", "bob@example.net")
+            .await;
+
+        chat.id
+            .set_signature(&alice, Some("Sent from my work phone"))
+            .await?;
+        let sent_msg = alice.pop_sent_msg().await;
+        alice2.recv_msg(&sent_msg).await;
+        let chat2 = Chat::load_from_db(&alice2, chat2.id).await?;
+        assert_eq!(chat2.get_signature(), Some("Sent from my work phone"));
+
+        chat.id.set_signature(&alice, None).await?;
+        let sent_msg = alice.pop_sent_msg().await;
+        alice2.recv_msg(&sent_msg).await;
+        let chat2 = Chat::load_from_db(&alice2, chat2.id).await?;
+        assert_eq!(chat2.get_signature(), None);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_sync_notification_rules() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let alice2 = TestContext::new_alice().await;
+        alice.set_config_bool(Config::SendSyncMsgs, true).await?;
+        assert!(alice2.get_config_bool(Config::NotifyBots).await?);
+
+        alice.set_config_bool(Config::NotifyBots, false).await?;
+        alice2.recv_msg(&alice.pop_sent_msg().await).await;
+        assert!(!alice2.get_config_bool(Config::NotifyBots).await?);
+
+        // Only synchronized configuration values are applied.
+        alice2
+            .execute_sync_items(&alice2.parse_sync_items(
+                r#"{"items":[{"timestamp":1631781318,"data":{"ConfigValue":{"key":"addr","value":"mallory@example.org"}}}]}"#
+                    .to_string(),
+            )?)
+            .await?;
+        assert_eq!(
+            alice2.get_config(Config::Addr).await?.as_deref(),
+            Some("alice@example.org")
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_sync_config_order() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let sync_items = |timestamp: i64, value: &str| {
+            alice.parse_sync_items(format!(
+                r#"{{"items":[{{"timestamp":{timestamp},"data":{{"ConfigValue":{{"key":"notify_bots","value":"{value}"}}}}}}]}}"#
+            ))
+        };
+
+        alice.execute_sync_items(&sync_items(1000, "0")?).await?;
+        assert!(!alice.get_config_bool(Config::NotifyBots).await?);
+
+        // Older changes are ignored.
+        alice.execute_sync_items(&sync_items(999, "1")?).await?;
+        assert!(!alice.get_config_bool(Config::NotifyBots).await?);
+
+        // Invalid values are ignored.
+        alice.execute_sync_items(&sync_items(1001, "yes")?).await?;
+        assert_eq!(
+            alice.get_config(Config::NotifyBots).await?.as_deref(),
+            Some("0")
+        );
+
+        // Local changes are newer than the ones received before.
+        alice.set_config_bool(Config::NotifyBots, true).await?;
+        alice.execute_sync_items(&sync_items(1002, "0")?).await?;
+        assert!(alice.get_config_bool(Config::NotifyBots).await?);
+        Ok(())
+    }
 }
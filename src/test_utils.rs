@@ -23,6 +23,7 @@
     ProtectionStatus,
 };
 use crate::chatlist::Chatlist;
+use crate::clock::TestClock;
 use crate::config::Config;
 use crate::constants::Chattype;
 use crate::constants::{DC_GCL_NO_SPECIALS, DC_MSG_ID_DAYMARKER};
@@ -36,6 +37,8 @@
 use crate::stock_str::StockStrings;
 use crate::tools::EmailAddress;
 
+pub(crate) mod mail_server;
+
 #[allow(non_upper_case_globals)]
 pub const AVATAR_900x900_BYTES: &[u8] = include_bytes!("../test-data/image/avatar900x900.png");
 
@@ -218,6 +221,8 @@ pub struct TestContext {
     pub ctx: Context,
     pub dir: TempDir,
     pub evtracker: EventTracker,
+    /// Clock of the context, can be advanced to fast-forward time-dependent code.
+    pub clock: Arc<TestClock>,
     /// Channels which should receive events from this context.
     event_senders: Arc<RwLock<Vec<Sender<Event>>>>,
     /// Reference to implicit [`LogSink`] so it is dropped together with the context.
@@ -299,9 +304,19 @@ async fn new_internal(name: Option<String>, log_sender: Option<Sender<LogEvent>>
             let mut context_names = CONTEXT_NAMES.write().unwrap();
             context_names.insert(id, name);
         }
-        let ctx = Context::new(&dbfile, id, Events::new(), StockStrings::new())
+        let clock = Arc::new(TestClock::default());
+        let ctx = Context::new_closed_with_clock(
+            &dbfile,
+            id,
+            Events::new(),
+            StockStrings::new(),
+            clock.clone(),
+        )
+        .await
+        .expect("failed to create context");
+        ctx.open("".to_string())
             .await
-            .expect("failed to create context");
+            .expect("failed to open context");
 
         let events = ctx.get_event_emitter();
 
@@ -332,6 +347,7 @@ async fn new_internal(name: Option<String>, log_sender: Option<Sender<LogEvent>>
             ctx,
             dir,
             evtracker: EventTracker(evtracker_receiver),
+            clock,
             event_senders,
             log_sink,
         }
@@ -630,6 +646,7 @@ pub async fn print_chat(&self, chat_id: ChatId) {
             MessageListOptions {
                 info_only: false,
                 add_daymarker: true,
+                corrected_order: false,
             },
         )
         .await
@@ -0,0 +1,1815 @@
+//! In-memory IMAP and SMTP server.
+//!
+//! [`TestMailServer`] listens on localhost without TLS and implements the part of IMAP4rev1
+//! with the IDLE, MOVE and UIDPLUS extensions and of ESMTP which Delta Chat uses.
+//! Messages sent over SMTP to an address which has an account on the server are delivered to
+//! the INBOX of this account, so [`TestContext`](super::TestContext)s with running IO can
+//! exchange messages end-to-end without any external services.
+//!
+//! With [`TestMailServer::set_fault_injection`] the server randomly delays responses, drops
+//! connections and truncates responses to stress-test reconnect and resync logic.
+//! Faults are derived from a seed, so a failing run can be reproduced with the same seed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{bail, Context as _, Result};
+use base64::Engine as _;
+use mailparse::MailHeaderMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::config::Config;
+use crate::context::Context;
+use crate::login_param::{CertificateChecks, LoginParam, ServerLoginParam};
+use crate::provider::Socket;
+use crate::tools::time;
+
+/// Capabilities announced by the IMAP server.
+const IMAP_CAPABILITIES: &str = "IMAP4rev1 IDLE MOVE UIDPLUS";
+
+/// System flags which can be stored on messages.
+const SYSTEM_FLAGS: &str = r"\Answered \Flagged \Deleted \Seen \Draft";
+
+/// Hierarchy delimiter of mailbox names.
+const DELIMITER: char = '/';
+
+/// Message stored in a mailbox of the server.
+#[derive(Debug, Clone)]
+pub(crate) struct StoredMessage {
+    pub uid: u32,
+    pub flags: BTreeSet<String>,
+    pub internal_date: i64,
+    pub body: Vec<u8>,
+}
+
+impl StoredMessage {
+    /// Returns true if the message has the given flag, e.g. `\Seen`.
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f.eq_ignore_ascii_case(flag))
+    }
+}
+
+/// Message received by the SMTP server.
+#[derive(Debug, Clone)]
+pub(crate) struct SmtpMessage {
+    pub from: String,
+    pub recipients: Vec<String>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Mailbox {
+    uid_validity: u32,
+    uid_next: u32,
+    special_use: Option<&'static str>,
+    messages: Vec<StoredMessage>,
+}
+
+impl Mailbox {
+    fn new(uid_validity: u32, special_use: Option<&'static str>) -> Self {
+        Self {
+            uid_validity,
+            uid_next: 1,
+            special_use,
+            messages: Vec::new(),
+        }
+    }
+
+    fn append(&mut self, body: Vec<u8>, flags: BTreeSet<String>, internal_date: i64) -> u32 {
+        let uid = self.uid_next;
+        self.uid_next += 1;
+        self.messages.push(StoredMessage {
+            uid,
+            flags,
+            internal_date,
+            body,
+        });
+        uid
+    }
+
+    fn uids(&self) -> Vec<u32> {
+        self.messages.iter().map(|msg| msg.uid).collect()
+    }
+
+    /// Returns indices of the messages matching the sequence set.
+    fn matching(&self, set: &str, uid_mode: bool) -> Option<Vec<usize>> {
+        let max = if uid_mode {
+            self.messages.last().map_or(0, |msg| msg.uid)
+        } else {
+            self.messages.len() as u32
+        };
+        let ranges = parse_sequence_set(set, max)?;
+        Some(
+            (0..self.messages.len())
+                .filter(|&i| {
+                    let key = if uid_mode {
+                        self.messages[i].uid
+                    } else {
+                        i as u32 + 1
+                    };
+                    ranges.iter().any(|(lo, hi)| *lo <= key && key <= *hi)
+                })
+                .collect(),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Account {
+    password: String,
+    mailboxes: BTreeMap<String, Mailbox>,
+}
+
+/// Faults injected into the responses of the server.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FaultInjection {
+    /// Seed of the random number generator deciding about the faults.
+    pub seed: u64,
+
+    /// Probability that the connection is closed instead of sending a response.
+    pub disconnect_probability: f64,
+
+    /// Probability that only a part of a response is sent before closing the connection.
+    pub truncate_probability: f64,
+
+    /// Maximum delay before sending a response.
+    pub max_latency: Duration,
+
+    /// Maximum number of disconnects and truncations,
+    /// after which the server works normally so that clients eventually succeed.
+    pub max_faults: usize,
+}
+
+#[derive(Debug, Default)]
+struct ServerState {
+    accounts: BTreeMap<String, Account>,
+    outgoing: Vec<SmtpMessage>,
+    last_uid_validity: u32,
+    faults: Option<FaultInjection>,
+
+    /// Number of accepted connections, used to derive a seed for each connection.
+    connections: u64,
+
+    /// Number of injected disconnects and truncations.
+    injected_faults: usize,
+}
+
+impl ServerState {
+    fn next_uid_validity(&mut self) -> u32 {
+        self.last_uid_validity += 1;
+        self.last_uid_validity
+    }
+
+    fn mailbox(&self, user: &str, name: &str) -> Option<&Mailbox> {
+        self.accounts.get(user)?.mailboxes.get(name)
+    }
+
+    fn mailbox_mut(&mut self, user: &str, name: &str) -> Option<&mut Mailbox> {
+        self.accounts.get_mut(user)?.mailboxes.get_mut(name)
+    }
+}
+
+/// State shared between the server handle and all connections.
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<ServerState>,
+
+    /// Generation counter which is increased on every change, used to wake up IDLE.
+    changes: watch::Sender<u64>,
+}
+
+impl Shared {
+    fn read<T>(&self, f: impl FnOnce(&ServerState) -> T) -> T {
+        f(&self.state.lock().unwrap())
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut ServerState) -> T) -> T {
+        let res = f(&mut self.state.lock().unwrap());
+        self.changes.send_modify(|generation| *generation += 1);
+        res
+    }
+
+    /// Wraps the writing half of a new connection, injecting faults if enabled.
+    fn writer(self: &Arc<Self>, inner: OwnedWriteHalf) -> FaultyWriter {
+        let mut state = self.state.lock().unwrap();
+        state.connections += 1;
+        let faults = state.faults.clone().map(|faults| {
+            let rng = StdRng::seed_from_u64(faults.seed.wrapping_add(state.connections));
+            (faults, rng)
+        });
+        FaultyWriter {
+            inner,
+            faults,
+            shared: Arc::clone(self),
+        }
+    }
+
+    /// Counts an injected fault, returns false if the maximum number of faults is reached.
+    ///
+    /// Notifies waiters of [`Shared::changes`], so tests can react to the fault
+    /// without waiting for retry timeouts.
+    fn inject_fault(&self) -> bool {
+        self.update(|state| {
+            let max_faults = state.faults.as_ref().map_or(0, |faults| faults.max_faults);
+            if state.injected_faults >= max_faults {
+                return false;
+            }
+            state.injected_faults += 1;
+            true
+        })
+    }
+}
+
+/// Writer which sends responses to the client, possibly with injected faults.
+#[derive(Debug)]
+struct FaultyWriter {
+    inner: OwnedWriteHalf,
+    faults: Option<(FaultInjection, StdRng)>,
+    shared: Arc<Shared>,
+}
+
+impl FaultyWriter {
+    async fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        if let Some((faults, rng)) = &mut self.faults {
+            let latency = faults.max_latency.mul_f64(rng.gen());
+            let disconnect = rng.gen_bool(faults.disconnect_probability);
+            let truncate = rng.gen_bool(faults.truncate_probability);
+            let truncated_len = rng.gen_range(0..=data.len());
+            tokio::time::sleep(latency).await;
+            if disconnect && self.shared.inject_fault() {
+                bail!("Injected disconnect");
+            }
+            if truncate && self.shared.inject_fault() {
+                self.inner.write_all(&data[..truncated_len]).await?;
+                bail!("Injected truncation");
+            }
+        }
+        self.inner.write_all(data).await?;
+        Ok(())
+    }
+}
+
+/// In-memory mail server for integration tests.
+///
+/// The server is stopped when dropped.
+#[derive(Debug)]
+pub(crate) struct TestMailServer {
+    shared: Arc<Shared>,
+    imap_addr: SocketAddr,
+    smtp_addr: SocketAddr,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for TestMailServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl TestMailServer {
+    /// Starts IMAP and SMTP servers on random localhost ports.
+    pub async fn start() -> Result<Self> {
+        let (changes, _) = watch::channel(0);
+        let shared = Arc::new(Shared {
+            state: Default::default(),
+            changes,
+        });
+        let imap_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let smtp_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let imap_addr = imap_listener.local_addr()?;
+        let smtp_addr = smtp_listener.local_addr()?;
+        let tasks = vec![
+            tokio::spawn(accept_loop(imap_listener, shared.clone(), serve_imap)),
+            tokio::spawn(accept_loop(smtp_listener, shared.clone(), serve_smtp)),
+        ];
+        Ok(Self {
+            shared,
+            imap_addr,
+            smtp_addr,
+            tasks,
+        })
+    }
+
+    /// Returns the port of the IMAP server.
+    pub fn imap_port(&self) -> u16 {
+        self.imap_addr.port()
+    }
+
+    /// Returns the port of the SMTP server.
+    pub fn smtp_port(&self) -> u16 {
+        self.smtp_addr.port()
+    }
+
+    /// Enables or disables fault injection for new connections.
+    pub fn set_fault_injection(&self, faults: Option<FaultInjection>) {
+        self.shared.update(|state| state.faults = faults);
+    }
+
+    /// Returns the number of disconnects and truncations injected so far.
+    pub fn injected_faults(&self) -> usize {
+        self.shared.read(|state| state.injected_faults)
+    }
+
+    /// Creates an account with an empty INBOX.
+    pub fn add_account(&self, addr: &str, password: &str) {
+        self.shared.update(|state| {
+            let uid_validity = state.next_uid_validity();
+            let mut mailboxes = BTreeMap::new();
+            mailboxes.insert("INBOX".to_string(), Mailbox::new(uid_validity, None));
+            state.accounts.insert(
+                addr.to_lowercase(),
+                Account {
+                    password: password.to_string(),
+                    mailboxes,
+                },
+            );
+        });
+    }
+
+    /// Creates a mailbox, optionally with a special-use attribute such as `\Sent`.
+    pub fn create_mailbox(
+        &self,
+        addr: &str,
+        name: &str,
+        special_use: Option<&'static str>,
+    ) -> Result<()> {
+        self.shared.update(|state| {
+            let uid_validity = state.next_uid_validity();
+            let account = state
+                .accounts
+                .get_mut(&addr.to_lowercase())
+                .context("no such account")?;
+            account
+                .mailboxes
+                .entry(normalize_mailbox_name(name))
+                .or_insert_with(|| Mailbox::new(uid_validity, special_use));
+            Ok(())
+        })
+    }
+
+    /// Stores the login parameters of the account in the context and marks it as configured.
+    ///
+    /// IO is not started.
+    pub async fn configure(&self, context: &Context, addr: &str) -> Result<()> {
+        let password = self
+            .shared
+            .read(|state| {
+                state
+                    .accounts
+                    .get(&addr.to_lowercase())
+                    .map(|account| account.password.clone())
+            })
+            .context("no such account")?;
+        let server_param = |port| ServerLoginParam {
+            server: "127.0.0.1".to_string(),
+            user: addr.to_string(),
+            password: password.clone(),
+            port,
+            security: Socket::Plain,
+            oauth2: false,
+            certificate_checks: CertificateChecks::Automatic,
+        };
+        let param = LoginParam {
+            addr: addr.to_string(),
+            imap: server_param(self.imap_port()),
+            smtp: server_param(self.smtp_port()),
+            provider: None,
+            socks5_config: None,
+        };
+        context.set_config(Config::Addr, Some(addr)).await?;
+        param.save_as_configured_params(context).await?;
+        context.set_config(Config::Configured, Some("1")).await?;
+        Ok(())
+    }
+
+    /// Puts a message into the mailbox as if it was delivered from outside.
+    ///
+    /// Returns the UID of the message.
+    pub fn deliver(&self, addr: &str, mailbox: &str, body: &[u8]) -> Result<u32> {
+        self.shared.update(|state| {
+            let mailbox = state
+                .mailbox_mut(&addr.to_lowercase(), &normalize_mailbox_name(mailbox))
+                .context("no such mailbox")?;
+            Ok(mailbox.append(body.to_vec(), BTreeSet::new(), time()))
+        })
+    }
+
+    /// Returns the messages of the mailbox.
+    pub fn messages(&self, addr: &str, mailbox: &str) -> Vec<StoredMessage> {
+        self.shared.read(|state| {
+            state
+                .mailbox(&addr.to_lowercase(), &normalize_mailbox_name(mailbox))
+                .map(|mailbox| mailbox.messages.clone())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Waits until the mailbox contains at least `count` messages and returns them.
+    ///
+    /// Panics after 10 seconds.
+    pub async fn wait_for_messages(
+        &self,
+        addr: &str,
+        mailbox: &str,
+        count: usize,
+    ) -> Vec<StoredMessage> {
+        let mut changes = self.shared.changes.subscribe();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let messages = self.messages(addr, mailbox);
+                if messages.len() >= count {
+                    return messages;
+                }
+                changes.changed().await.unwrap();
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("timeout waiting for {count} messages in {addr}/{mailbox}"))
+    }
+
+    /// Returns all messages received over SMTP.
+    pub fn outgoing(&self) -> Vec<SmtpMessage> {
+        self.shared.read(|state| state.outgoing.clone())
+    }
+
+    /// Changes the UIDVALIDITY of the mailbox and assigns new UIDs to all messages,
+    /// like servers do when the mailbox is restored from a backup.
+    pub fn reset_uid_validity(&self, addr: &str, mailbox: &str) -> Result<()> {
+        self.shared.update(|state| {
+            let uid_validity = state.next_uid_validity();
+            let mailbox = state
+                .mailbox_mut(&addr.to_lowercase(), &normalize_mailbox_name(mailbox))
+                .context("no such mailbox")?;
+            mailbox.uid_validity = uid_validity;
+            mailbox.uid_next = 1;
+            for msg in &mut mailbox.messages {
+                msg.uid = mailbox.uid_next;
+                mailbox.uid_next += 1;
+            }
+            Ok(())
+        })
+    }
+}
+
+async fn accept_loop<F, Fut>(listener: TcpListener, shared: Arc<Shared>, serve: F)
+where
+    F: Fn(Arc<Shared>, TcpStream) -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    // Connections are aborted together with the set when the server is dropped.
+    let mut connections = JoinSet::new();
+    while let Ok((stream, _)) = listener.accept().await {
+        let connection = serve(shared.clone(), stream);
+        connections.spawn(async move {
+            connection.await.ok();
+        });
+    }
+}
+
+fn normalize_mailbox_name(name: &str) -> String {
+    if name.eq_ignore_ascii_case("INBOX") {
+        "INBOX".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Splits off the first space-separated word.
+fn split_word(input: &str) -> (&str, &str) {
+    let input = input.trim_start();
+    match input.split_once(' ') {
+        Some((word, rest)) => (word, rest.trim_start()),
+        None => (input, ""),
+    }
+}
+
+/// Splits off the first IMAP string, either quoted or an atom.
+fn parse_string(input: &str) -> Option<(String, &str)> {
+    let input = input.trim_start();
+    if let Some(rest) = input.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => value.push(chars.next()?.1),
+                '"' => return Some((value, rest[i + 1..].trim_start())),
+                c => value.push(c),
+            }
+        }
+        None
+    } else {
+        let (word, rest) = split_word(input);
+        if word.is_empty() {
+            None
+        } else {
+            Some((word.to_string(), rest))
+        }
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Parses a sequence set such as `1:5,7,9:*` into inclusive ranges.
+fn parse_sequence_set(set: &str, max: u32) -> Option<Vec<(u32, u32)>> {
+    let parse_number = |s: &str| -> Option<u32> {
+        if s == "*" {
+            Some(max)
+        } else {
+            s.parse().ok().filter(|n| *n > 0)
+        }
+    };
+    set.split(',')
+        .map(|part| {
+            let (start, end) = part.split_once(':').unwrap_or((part, part));
+            let start = parse_number(start)?;
+            let end = parse_number(end)?;
+            Some((start.min(end), start.max(end)))
+        })
+        .collect()
+}
+
+/// Matches a LIST pattern where `*` matches anything and `%` anything but the delimiter.
+fn matches_pattern(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| matches_pattern(rest, &name[i..])),
+        Some(('%', rest)) => (0..=name.len())
+            .take_while(|&i| i == 0 || name[i - 1] != DELIMITER)
+            .any(|i| matches_pattern(rest, &name[i..])),
+        Some((c, rest)) => name
+            .split_first()
+            .map_or(false, |(n, name)| n == c && matches_pattern(rest, name)),
+    }
+}
+
+fn format_flags(flags: &BTreeSet<String>) -> String {
+    format!(
+        "({})",
+        flags
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ")
+    )
+}
+
+fn parse_flags(input: &str) -> Vec<String> {
+    input
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split_whitespace()
+        .map(|flag| flag.to_string())
+        .collect()
+}
+
+fn format_internal_date(timestamp: i64) -> String {
+    chrono::NaiveDateTime::from_timestamp_opt(timestamp, 0)
+        .unwrap_or_default()
+        .format("%d-%b-%Y %H:%M:%S +0000")
+        .to_string()
+}
+
+/// Returns the header of the message including the empty line separating it from the body.
+fn header_block(body: &[u8]) -> &[u8] {
+    let mut pos = 0;
+    for line in body.split_inclusive(|&b| b == b'\n') {
+        pos += line.len();
+        if line == b"\r\n" || line == b"\n" {
+            return &body[..pos];
+        }
+    }
+    body
+}
+
+/// Returns header fields including continuation lines and line endings, paired with the names.
+fn header_fields(header: &[u8]) -> Vec<(String, &[u8])> {
+    let mut fields: Vec<(String, std::ops::Range<usize>)> = Vec::new();
+    let mut pos = 0;
+    for line in header.split_inclusive(|&b| b == b'\n') {
+        let start = pos;
+        pos += line.len();
+        if line == b"\r\n" || line == b"\n" {
+            break;
+        }
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            if let Some((_, range)) = fields.last_mut() {
+                range.end = pos;
+                continue;
+            }
+        }
+        let name = line.split(|&b| b == b':').next().unwrap_or_default();
+        fields.push((String::from_utf8_lossy(name).trim().to_string(), start..pos));
+    }
+    fields
+        .into_iter()
+        .map(|(name, range)| (name, &header[range]))
+        .collect()
+}
+
+/// Returns the data of a `BODY[<section>]` fetch item.
+fn section_data(body: &[u8], section: &str) -> Option<Vec<u8>> {
+    let header = header_block(body);
+    let upper = section.to_ascii_uppercase();
+    match upper.as_str() {
+        "" => Some(body.to_vec()),
+        "HEADER" => Some(header.to_vec()),
+        "TEXT" => Some(body[header.len()..].to_vec()),
+        _ => {
+            let (names, negate) = if let Some(names) = upper.strip_prefix("HEADER.FIELDS.NOT ") {
+                (names, true)
+            } else if let Some(names) = upper.strip_prefix("HEADER.FIELDS ") {
+                (names, false)
+            } else {
+                return None;
+            };
+            let names = parse_flags(names);
+            let mut data = Vec::new();
+            for (name, field) in header_fields(header) {
+                let requested = names.iter().any(|n| n.eq_ignore_ascii_case(&name));
+                if requested != negate {
+                    data.extend_from_slice(field);
+                }
+            }
+            data.extend_from_slice(b"\r\n");
+            Some(data)
+        }
+    }
+}
+
+/// Splits a list of FETCH items, keeping sections like `BODY[HEADER.FIELDS (A B)]` together.
+fn parse_fetch_items(items: &str) -> Option<Vec<String>> {
+    let items = items.trim();
+    let inner = match items.strip_prefix('(') {
+        Some(rest) => rest.strip_suffix(')')?,
+        None => items,
+    };
+    let mut res = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in inner.chars() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            _ => {}
+        }
+        if c == ' ' && depth == 0 {
+            if !current.is_empty() {
+                res.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        res.push(current);
+    }
+    if res.len() == 1 && res[0].eq_ignore_ascii_case("FAST") {
+        res = vec![
+            "FLAGS".to_string(),
+            "INTERNALDATE".to_string(),
+            "RFC822.SIZE".to_string(),
+        ];
+    }
+    Some(res)
+}
+
+fn literal(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut res = format!("{name} {{{}}}\r\n", data.len()).into_bytes();
+    res.extend_from_slice(data);
+    res
+}
+
+/// Returns the response data of a single FETCH item.
+fn fetch_item(item: &str, msg: &mut StoredMessage) -> Option<Vec<u8>> {
+    let upper = item.to_ascii_uppercase();
+    let res = match upper.as_str() {
+        "UID" => format!("UID {}", msg.uid).into_bytes(),
+        "FLAGS" => format!("FLAGS {}", format_flags(&msg.flags)).into_bytes(),
+        "INTERNALDATE" => format!(
+            "INTERNALDATE \"{}\"",
+            format_internal_date(msg.internal_date)
+        )
+        .into_bytes(),
+        "RFC822.SIZE" => format!("RFC822.SIZE {}", msg.body.len()).into_bytes(),
+        "RFC822.HEADER" => literal("RFC822.HEADER", header_block(&msg.body)),
+        "RFC822" => {
+            msg.flags.insert(r"\Seen".to_string());
+            literal("RFC822", &msg.body)
+        }
+        _ => {
+            let peek = upper.starts_with("BODY.PEEK[");
+            if !peek && !upper.starts_with("BODY[") {
+                return None;
+            }
+            let start = item.find('[')? + 1;
+            let section = item.get(start..)?.strip_suffix(']')?;
+            let data = section_data(&msg.body, section)?;
+            if !peek {
+                msg.flags.insert(r"\Seen".to_string());
+            }
+            literal(&format!("BODY[{section}]"), &data)
+        }
+    };
+    Some(res)
+}
+
+#[derive(Debug)]
+enum SearchKey {
+    All,
+    Set { set: String, uid: bool },
+    Header { name: String, value: String },
+    Flag { flag: String, set: bool },
+    Not(Box<SearchKey>),
+    Or(Box<SearchKey>, Box<SearchKey>),
+    And(Vec<SearchKey>),
+}
+
+/// Splits SEARCH criteria into parentheses, quoted strings and atoms.
+fn tokenize_search(input: &str) -> Option<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('(') {
+            tokens.push("(".to_string());
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix(')') {
+            tokens.push(")".to_string());
+            rest = r;
+        } else if rest.starts_with('"') {
+            let (value, r) = parse_string(rest)?;
+            tokens.push(value);
+            rest = r;
+        } else {
+            let end = rest.find([' ', '(', ')']).unwrap_or(rest.len());
+            tokens.push(rest[..end].to_string());
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Some(tokens)
+}
+
+fn parse_search_key(tokens: &mut std::vec::IntoIter<String>) -> Option<SearchKey> {
+    let token = tokens.next()?;
+    let flag_key = |flag: &str, set| SearchKey::Flag {
+        flag: flag.to_string(),
+        set,
+    };
+    let key = match token.to_ascii_uppercase().as_str() {
+        "(" => {
+            let mut keys = Vec::new();
+            loop {
+                if tokens.as_slice().first().map(String::as_str) == Some(")") {
+                    tokens.next();
+                    break SearchKey::And(keys);
+                }
+                keys.push(parse_search_key(tokens)?);
+            }
+        }
+        "ALL" => SearchKey::All,
+        "SEEN" => flag_key(r"\Seen", true),
+        "UNSEEN" => flag_key(r"\Seen", false),
+        "DELETED" => flag_key(r"\Deleted", true),
+        "UNDELETED" => flag_key(r"\Deleted", false),
+        "FLAGGED" => flag_key(r"\Flagged", true),
+        "UNFLAGGED" => flag_key(r"\Flagged", false),
+        "ANSWERED" => flag_key(r"\Answered", true),
+        "UNANSWERED" => flag_key(r"\Answered", false),
+        "DRAFT" => flag_key(r"\Draft", true),
+        "UNDRAFT" => flag_key(r"\Draft", false),
+        "KEYWORD" => flag_key(&tokens.next()?, true),
+        "UNKEYWORD" => flag_key(&tokens.next()?, false),
+        name @ ("FROM" | "TO" | "CC" | "BCC" | "SUBJECT") => SearchKey::Header {
+            name: name.to_string(),
+            value: tokens.next()?,
+        },
+        "HEADER" => SearchKey::Header {
+            name: tokens.next()?,
+            value: tokens.next()?,
+        },
+        "UID" => SearchKey::Set {
+            set: tokens.next()?,
+            uid: true,
+        },
+        "NOT" => SearchKey::Not(Box::new(parse_search_key(tokens)?)),
+        "OR" => SearchKey::Or(
+            Box::new(parse_search_key(tokens)?),
+            Box::new(parse_search_key(tokens)?),
+        ),
+        _ if token.starts_with(|c: char| c.is_ascii_digit() || c == '*') => SearchKey::Set {
+            set: token,
+            uid: false,
+        },
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn parse_search(criteria: &str) -> Option<SearchKey> {
+    let mut tokens = tokenize_search(criteria)?.into_iter();
+    let mut keys = Vec::new();
+    while tokens.as_slice().first().is_some() {
+        keys.push(parse_search_key(&mut tokens)?);
+    }
+    Some(SearchKey::And(keys))
+}
+
+impl SearchKey {
+    fn matches(&self, mailbox: &Mailbox, index: usize) -> bool {
+        let msg = &mailbox.messages[index];
+        match self {
+            SearchKey::All => true,
+            SearchKey::Set { set, uid } => mailbox
+                .matching(set, *uid)
+                .map_or(false, |indices| indices.contains(&index)),
+            SearchKey::Header { name, value } => {
+                let value = value.to_lowercase();
+                match mailparse::parse_headers(&msg.body) {
+                    Ok((headers, _)) => headers
+                        .get_all_values(name)
+                        .iter()
+                        .any(|v| v.to_lowercase().contains(&value)),
+                    Err(_) => false,
+                }
+            }
+            SearchKey::Flag { flag, set } => msg.has_flag(flag) == *set,
+            SearchKey::Not(key) => !key.matches(mailbox, index),
+            SearchKey::Or(a, b) => a.matches(mailbox, index) || b.matches(mailbox, index),
+            SearchKey::And(keys) => keys.iter().all(|key| key.matches(mailbox, index)),
+        }
+    }
+}
+
+/// Failed IMAP command.
+enum Failure {
+    No(String),
+    Bad(String),
+}
+
+fn no(text: impl Into<String>) -> Failure {
+    Failure::No(text.into())
+}
+
+fn bad(text: impl Into<String>) -> Failure {
+    Failure::Bad(text.into())
+}
+
+/// Result of an IMAP command, the text of the tagged OK response on success.
+type CommandResult = std::result::Result<String, Failure>;
+
+#[derive(Debug)]
+struct Selected {
+    name: String,
+    read_only: bool,
+
+    /// UIDs of the messages as last announced to the client.
+    uids: Vec<u32>,
+}
+
+struct ImapSession {
+    shared: Arc<Shared>,
+    user: Option<String>,
+    selected: Option<Selected>,
+
+    /// Untagged responses to be sent before the tagged response.
+    out: Vec<u8>,
+}
+
+impl ImapSession {
+    fn untagged(&mut self, response: impl AsRef<[u8]>) {
+        self.out.extend_from_slice(b"* ");
+        self.out.extend_from_slice(response.as_ref());
+        self.out.extend_from_slice(b"\r\n");
+    }
+
+    fn user(&self) -> std::result::Result<String, Failure> {
+        self.user.clone().ok_or_else(|| bad("Not authenticated"))
+    }
+
+    fn selected(&self) -> std::result::Result<(String, String, bool), Failure> {
+        let user = self.user()?;
+        let selected = self
+            .selected
+            .as_ref()
+            .ok_or_else(|| bad("No mailbox selected"))?;
+        Ok((user, selected.name.clone(), selected.read_only))
+    }
+
+    /// Announces messages added or removed by other connections.
+    fn send_updates(&mut self) {
+        let (user, name) = match (&self.user, &self.selected) {
+            (Some(user), Some(selected)) => (user.clone(), selected.name.clone()),
+            _ => return,
+        };
+        let current = self.shared.read(|state| {
+            state
+                .mailbox(&user, &name)
+                .map(Mailbox::uids)
+                .unwrap_or_default()
+        });
+        let mut responses = Vec::new();
+        if let Some(selected) = &mut self.selected {
+            // Going from the highest sequence number down keeps the lower ones valid.
+            for i in (0..selected.uids.len()).rev() {
+                if current.binary_search(&selected.uids[i]).is_err() {
+                    responses.push(format!("{} EXPUNGE", i + 1));
+                    selected.uids.remove(i);
+                }
+            }
+            if current.len() != selected.uids.len() {
+                responses.push(format!("{} EXISTS", current.len()));
+            }
+            selected.uids = current;
+        }
+        for response in responses {
+            self.untagged(response);
+        }
+    }
+
+    /// Removes messages flagged as `\Deleted` from the selected mailbox.
+    fn expunge(&mut self, uid_set: Option<&str>, silent: bool) -> std::result::Result<(), Failure> {
+        let (user, name, read_only) = self.selected()?;
+        if read_only {
+            return Err(no("Mailbox is read-only"));
+        }
+        self.send_updates();
+        let expunged = self.shared.update(|state| {
+            let mailbox = state
+                .mailbox_mut(&user, &name)
+                .ok_or_else(|| no("Mailbox is gone"))?;
+            let allowed = match uid_set {
+                Some(set) => Some(
+                    mailbox
+                        .matching(set, true)
+                        .ok_or_else(|| bad("Invalid set"))?,
+                ),
+                None => None,
+            };
+            let mut expunged = Vec::new();
+            for i in (0..mailbox.messages.len()).rev() {
+                if mailbox.messages[i].has_flag(r"\Deleted")
+                    && allowed
+                        .as_ref()
+                        .map_or(true, |allowed| allowed.contains(&i))
+                {
+                    mailbox.messages.remove(i);
+                    expunged.push(i);
+                }
+            }
+            Ok(expunged)
+        })?;
+        if let Some(selected) = &mut self.selected {
+            for &i in &expunged {
+                selected.uids.remove(i);
+            }
+        }
+        if !silent {
+            for i in expunged {
+                self.untagged(format!("{} EXPUNGE", i + 1));
+            }
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, name: &str, args: &str) -> CommandResult {
+        match name {
+            "CAPABILITY" => {
+                self.untagged(format!("CAPABILITY {IMAP_CAPABILITIES}"));
+                Ok("CAPABILITY completed".to_string())
+            }
+            "NOOP" => {
+                self.send_updates();
+                Ok("NOOP completed".to_string())
+            }
+            "LOGOUT" => {
+                self.untagged("BYE Logging out");
+                Ok("LOGOUT completed".to_string())
+            }
+            "LOGIN" => self.login(args),
+            _ if self.user.is_none() => Err(bad("Not authenticated")),
+            "LIST" => self.list(args),
+            "CREATE" => self.create(args),
+            "SUBSCRIBE" | "UNSUBSCRIBE" => Ok(format!("{name} completed")),
+            "SELECT" => self.select(args, false),
+            "EXAMINE" => self.select(args, true),
+            "STATUS" => self.status(args),
+            "CLOSE" => {
+                let (_, _, read_only) = self.selected()?;
+                if !read_only {
+                    self.expunge(None, true)?;
+                }
+                self.selected = None;
+                Ok("CLOSE completed".to_string())
+            }
+            "EXPUNGE" => {
+                self.expunge(None, false)?;
+                Ok("EXPUNGE completed".to_string())
+            }
+            "FETCH" | "STORE" | "COPY" | "MOVE" | "SEARCH" => {
+                self.message_command(name, args, false)
+            }
+            "UID" => {
+                let (name, args) = split_word(args);
+                let name = name.to_ascii_uppercase();
+                match name.as_str() {
+                    "FETCH" | "STORE" | "COPY" | "MOVE" | "SEARCH" => {
+                        self.message_command(&name, args, true)
+                    }
+                    "EXPUNGE" => {
+                        self.expunge(Some(args.trim()), false)?;
+                        Ok("EXPUNGE completed".to_string())
+                    }
+                    _ => Err(bad("Unknown UID command")),
+                }
+            }
+            _ => Err(bad("Unknown command")),
+        }
+    }
+
+    fn login(&mut self, args: &str) -> CommandResult {
+        let (user, rest) = parse_string(args).ok_or_else(|| bad("Missing user"))?;
+        let (password, _) = parse_string(rest).ok_or_else(|| bad("Missing password"))?;
+        let user = user.to_lowercase();
+        let valid = self.shared.read(|state| {
+            state
+                .accounts
+                .get(&user)
+                .map_or(false, |account| account.password == password)
+        });
+        if !valid {
+            return Err(no("[AUTHENTICATIONFAILED] Authentication failed"));
+        }
+        self.user = Some(user);
+        Ok(format!("[CAPABILITY {IMAP_CAPABILITIES}] LOGIN completed"))
+    }
+
+    fn list(&mut self, args: &str) -> CommandResult {
+        let user = self.user()?;
+        let (_reference, rest) = parse_string(args).ok_or_else(|| bad("Missing reference"))?;
+        let (pattern, _) = parse_string(rest).ok_or_else(|| bad("Missing pattern"))?;
+        if pattern.is_empty() {
+            self.untagged(format!(r#"LIST (\Noselect) "{DELIMITER}" """#));
+            return Ok("LIST completed".to_string());
+        }
+        let pattern: Vec<char> = pattern.chars().collect();
+        let mailboxes = self.shared.read(|state| {
+            state
+                .accounts
+                .get(&user)
+                .map(|account| {
+                    account
+                        .mailboxes
+                        .iter()
+                        .map(|(name, mailbox)| (name.clone(), mailbox.special_use))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        });
+        for (name, special_use) in mailboxes {
+            let chars: Vec<char> = name.chars().collect();
+            if !matches_pattern(&pattern, &chars) {
+                continue;
+            }
+            let attributes = match special_use {
+                Some(special_use) => format!(r"\HasNoChildren {special_use}"),
+                None => r"\HasNoChildren".to_string(),
+            };
+            self.untagged(format!(
+                "LIST ({attributes}) \"{DELIMITER}\" {}",
+                quote(&name)
+            ));
+        }
+        Ok("LIST completed".to_string())
+    }
+
+    fn create(&mut self, args: &str) -> CommandResult {
+        let user = self.user()?;
+        let (name, _) = parse_string(args).ok_or_else(|| bad("Missing mailbox name"))?;
+        let name = normalize_mailbox_name(&name);
+        self.shared.update(|state| {
+            let uid_validity = state.next_uid_validity();
+            let account = state
+                .accounts
+                .get_mut(&user)
+                .ok_or_else(|| no("Account is gone"))?;
+            if account.mailboxes.contains_key(&name) {
+                return Err(no("[ALREADYEXISTS] Mailbox already exists"));
+            }
+            account
+                .mailboxes
+                .insert(name, Mailbox::new(uid_validity, None));
+            Ok("CREATE completed".to_string())
+        })
+    }
+
+    fn select(&mut self, args: &str, read_only: bool) -> CommandResult {
+        let user = self.user()?;
+        self.selected = None;
+        let (name, _) = parse_string(args).ok_or_else(|| bad("Missing mailbox name"))?;
+        let name = normalize_mailbox_name(&name);
+        let (uids, uid_validity, uid_next) = self
+            .shared
+            .read(|state| {
+                state
+                    .mailbox(&user, &name)
+                    .map(|mailbox| (mailbox.uids(), mailbox.uid_validity, mailbox.uid_next))
+            })
+            .ok_or_else(|| no("Mailbox does not exist"))?;
+        self.untagged(format!("FLAGS ({SYSTEM_FLAGS})"));
+        self.untagged(format!(
+            r"OK [PERMANENTFLAGS ({SYSTEM_FLAGS} \*)] Flags permitted"
+        ));
+        self.untagged(format!("{} EXISTS", uids.len()));
+        self.untagged("0 RECENT");
+        self.untagged(format!("OK [UIDVALIDITY {uid_validity}] UIDs valid"));
+        self.untagged(format!("OK [UIDNEXT {uid_next}] Predicted next UID"));
+        self.selected = Some(Selected {
+            name,
+            read_only,
+            uids,
+        });
+        Ok(if read_only {
+            "[READ-ONLY] EXAMINE completed".to_string()
+        } else {
+            "[READ-WRITE] SELECT completed".to_string()
+        })
+    }
+
+    fn status(&mut self, args: &str) -> CommandResult {
+        let user = self.user()?;
+        let (name, items) = parse_string(args).ok_or_else(|| bad("Missing mailbox name"))?;
+        let name = normalize_mailbox_name(&name);
+        let items = parse_flags(items);
+        let values = self
+            .shared
+            .read(|state| {
+                let mailbox = state.mailbox(&user, &name)?;
+                items
+                    .iter()
+                    .map(|item| {
+                        let value = match item.to_ascii_uppercase().as_str() {
+                            "MESSAGES" => mailbox.messages.len() as u32,
+                            "RECENT" => 0,
+                            "UIDNEXT" => mailbox.uid_next,
+                            "UIDVALIDITY" => mailbox.uid_validity,
+                            "UNSEEN" => mailbox
+                                .messages
+                                .iter()
+                                .filter(|msg| !msg.has_flag(r"\Seen"))
+                                .count() as u32,
+                            _ => return None,
+                        };
+                        Some(format!("{} {value}", item.to_ascii_uppercase()))
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| no("Mailbox does not exist or unknown status item"))?;
+        self.untagged(format!("STATUS {} ({})", quote(&name), values.join(" ")));
+        Ok("STATUS completed".to_string())
+    }
+
+    fn message_command(&mut self, name: &str, args: &str, uid_mode: bool) -> CommandResult {
+        let (user, selected, read_only) = self.selected()?;
+        self.send_updates();
+        let prefix = if uid_mode { "UID " } else { "" };
+        match name {
+            "FETCH" => {
+                let (set, items) = split_word(args);
+                let items = parse_fetch_items(items).ok_or_else(|| bad("Invalid FETCH items"))?;
+                let responses = self.shared.update(|state| {
+                    let mailbox = state
+                        .mailbox_mut(&user, &selected)
+                        .ok_or_else(|| no("Mailbox is gone"))?;
+                    let indices = mailbox
+                        .matching(set, uid_mode)
+                        .ok_or_else(|| bad("Invalid sequence set"))?;
+                    let mut responses = Vec::new();
+                    for i in indices {
+                        let msg = &mut mailbox.messages[i];
+                        let mut parts = Vec::new();
+                        if uid_mode && !items.iter().any(|item| item.eq_ignore_ascii_case("UID")) {
+                            parts.push(format!("UID {}", msg.uid).into_bytes());
+                        }
+                        for item in &items {
+                            parts.push(
+                                fetch_item(item, msg)
+                                    .ok_or_else(|| bad(format!("Unsupported item {item}")))?,
+                            );
+                        }
+                        let mut response = format!("{} FETCH (", i + 1).into_bytes();
+                        response.extend_from_slice(&parts.join(&b' '));
+                        response.push(b')');
+                        responses.push(response);
+                    }
+                    Ok(responses)
+                })?;
+                for response in responses {
+                    self.untagged(response);
+                }
+                Ok(format!("{prefix}FETCH completed"))
+            }
+            "STORE" => {
+                if read_only {
+                    return Err(no("Mailbox is read-only"));
+                }
+                let (set, rest) = split_word(args);
+                let (operation, flags) = split_word(rest);
+                let operation = operation.to_ascii_uppercase();
+                let silent = operation.ends_with(".SILENT");
+                let operation = operation.trim_end_matches(".SILENT").to_string();
+                let flags = parse_flags(flags);
+                let responses = self.shared.update(|state| {
+                    let mailbox = state
+                        .mailbox_mut(&user, &selected)
+                        .ok_or_else(|| no("Mailbox is gone"))?;
+                    let indices = mailbox
+                        .matching(set, uid_mode)
+                        .ok_or_else(|| bad("Invalid sequence set"))?;
+                    let mut responses = Vec::new();
+                    for i in indices {
+                        let msg = &mut mailbox.messages[i];
+                        match operation.as_str() {
+                            "+FLAGS" => msg.flags.extend(flags.iter().cloned()),
+                            "-FLAGS" => msg
+                                .flags
+                                .retain(|f| !flags.iter().any(|r| r.eq_ignore_ascii_case(f))),
+                            "FLAGS" => msg.flags = flags.iter().cloned().collect(),
+                            _ => return Err(bad("Invalid STORE operation")),
+                        }
+                        let uid = if uid_mode {
+                            format!("UID {} ", msg.uid)
+                        } else {
+                            String::new()
+                        };
+                        responses.push(format!(
+                            "{} FETCH ({uid}FLAGS {})",
+                            i + 1,
+                            format_flags(&msg.flags)
+                        ));
+                    }
+                    Ok(responses)
+                })?;
+                if !silent {
+                    for response in responses {
+                        self.untagged(response);
+                    }
+                }
+                Ok(format!("{prefix}STORE completed"))
+            }
+            "COPY" | "MOVE" => {
+                let is_move = name == "MOVE";
+                if is_move && read_only {
+                    return Err(no("Mailbox is read-only"));
+                }
+                let (set, rest) = split_word(args);
+                let (target, _) = parse_string(rest).ok_or_else(|| bad("Missing mailbox"))?;
+                let target = normalize_mailbox_name(&target);
+                let (copyuid, expunged) = self.shared.update(|state| {
+                    let source = state
+                        .mailbox(&user, &selected)
+                        .ok_or_else(|| no("Mailbox is gone"))?;
+                    let indices = source
+                        .matching(set, uid_mode)
+                        .ok_or_else(|| bad("Invalid sequence set"))?;
+                    let copies: Vec<StoredMessage> = indices
+                        .iter()
+                        .map(|&i| source.messages[i].clone())
+                        .collect();
+                    let now = time();
+                    let destination = state
+                        .mailbox_mut(&user, &target)
+                        .ok_or_else(|| no("[TRYCREATE] Mailbox does not exist"))?;
+                    let mut source_uids = Vec::new();
+                    let mut destination_uids = Vec::new();
+                    for msg in copies {
+                        source_uids.push(msg.uid.to_string());
+                        let mut flags = msg.flags;
+                        if is_move {
+                            flags.retain(|f| !f.eq_ignore_ascii_case(r"\Deleted"));
+                        }
+                        destination_uids.push(destination.append(msg.body, flags, now).to_string());
+                    }
+                    let copyuid = (!source_uids.is_empty()).then(|| {
+                        format!(
+                            "[COPYUID {} {} {}]",
+                            destination.uid_validity,
+                            source_uids.join(","),
+                            destination_uids.join(",")
+                        )
+                    });
+                    let mut expunged = Vec::new();
+                    if is_move {
+                        let source = state
+                            .mailbox_mut(&user, &selected)
+                            .ok_or_else(|| no("Mailbox is gone"))?;
+                        for &i in indices.iter().rev() {
+                            source.messages.remove(i);
+                            expunged.push(i);
+                        }
+                    }
+                    Ok((copyuid, expunged))
+                })?;
+                if is_move {
+                    if let Some(copyuid) = &copyuid {
+                        self.untagged(format!("OK {copyuid} Moved"));
+                    }
+                    if let Some(selected) = &mut self.selected {
+                        for &i in &expunged {
+                            selected.uids.remove(i);
+                        }
+                    }
+                    for i in expunged {
+                        self.untagged(format!("{} EXPUNGE", i + 1));
+                    }
+                    Ok(format!("{prefix}MOVE completed"))
+                } else {
+                    Ok(match copyuid {
+                        Some(copyuid) => format!("{copyuid} {prefix}COPY completed"),
+                        None => format!("{prefix}COPY completed"),
+                    })
+                }
+            }
+            "SEARCH" => {
+                let key = parse_search(args).ok_or_else(|| bad("Unsupported SEARCH criteria"))?;
+                let results = self
+                    .shared
+                    .read(|state| {
+                        let mailbox = state.mailbox(&user, &selected)?;
+                        Some(
+                            (0..mailbox.messages.len())
+                                .filter(|&i| key.matches(mailbox, i))
+                                .map(|i| {
+                                    if uid_mode {
+                                        mailbox.messages[i].uid
+                                    } else {
+                                        i as u32 + 1
+                                    }
+                                })
+                                .map(|n| format!(" {n}"))
+                                .collect::<String>(),
+                        )
+                    })
+                    .ok_or_else(|| no("Mailbox is gone"))?;
+                self.untagged(format!("SEARCH{results}"));
+                Ok(format!("{prefix}SEARCH completed"))
+            }
+            _ => Err(bad("Unknown command")),
+        }
+    }
+
+    /// Runs the command and returns the tagged response.
+    fn handle(&mut self, tag: &str, name: &str, args: &str) -> String {
+        match self.command(name, args) {
+            Ok(text) => format!("{tag} OK {text}\r\n"),
+            Err(Failure::No(text)) => format!("{tag} NO {text}\r\n"),
+            Err(Failure::Bad(text)) => format!("{tag} BAD {text}\r\n"),
+        }
+    }
+}
+
+async fn serve_imap(shared: Arc<Shared>, stream: TcpStream) -> Result<()> {
+    let (reader, writer) = stream.into_split();
+    let mut writer = shared.writer(writer);
+    // `next_line()` is cancellation safe, which is needed to wait for DONE during IDLE.
+    let mut lines = BufReader::new(reader).lines();
+    let mut changes = shared.changes.subscribe();
+    let mut session = ImapSession {
+        shared,
+        user: None,
+        selected: None,
+        out: Vec::new(),
+    };
+    writer
+        .write_all(
+            format!("* OK [CAPABILITY {IMAP_CAPABILITIES}] Test server ready\r\n").as_bytes(),
+        )
+        .await?;
+
+    while let Some(line) = lines.next_line().await? {
+        let (tag, command) = split_word(&line);
+        let (name, args) = split_word(command);
+        let name = name.to_ascii_uppercase();
+
+        if name == "IDLE" {
+            session.out.extend_from_slice(b"+ idling\r\n");
+            changes.borrow_and_update();
+            session.send_updates();
+            let tagged = loop {
+                writer.write_all(&std::mem::take(&mut session.out)).await?;
+                tokio::select! {
+                    line = lines.next_line() => match line? {
+                        Some(line) if line.eq_ignore_ascii_case("DONE") => {
+                            break format!("{tag} OK IDLE terminated\r\n");
+                        }
+                        Some(_) => break format!("{tag} BAD Expected DONE\r\n"),
+                        None => return Ok(()),
+                    },
+                    res = changes.changed() => {
+                        res?;
+                        session.send_updates();
+                    }
+                }
+            };
+            session.out.extend_from_slice(tagged.as_bytes());
+        } else {
+            let tagged = session.handle(tag, &name, args);
+            session.out.extend_from_slice(tagged.as_bytes());
+        }
+        writer.write_all(&std::mem::take(&mut session.out)).await?;
+
+        if name == "LOGOUT" {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a line including the line ending, returns `None` on EOF.
+async fn read_line(reader: &mut BufReader<OwnedReadHalf>) -> Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line).await? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+/// Returns the address of a `FROM:<addr>` or `TO:<addr>` argument.
+fn parse_path(args: &str, prefix: &str) -> Option<String> {
+    if !args.get(..prefix.len())?.eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+    let rest = args[prefix.len()..].trim_start().strip_prefix('<')?;
+    let end = rest.find('>')?;
+    Some(rest[..end].to_string())
+}
+
+fn decode_base64(line: &[u8]) -> Option<String> {
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(String::from_utf8_lossy(line).trim())
+        .ok()?;
+    String::from_utf8(data).ok()
+}
+
+async fn serve_smtp(shared: Arc<Shared>, stream: TcpStream) -> Result<()> {
+    let (reader, writer) = stream.into_split();
+    let mut writer = shared.writer(writer);
+    let mut reader = BufReader::new(reader);
+    writer
+        .write_all(b"220 localhost ESMTP Test server ready\r\n")
+        .await?;
+
+    let mut user = None;
+    let mut from = None;
+    let mut recipients = Vec::new();
+    while let Some(line) = read_line(&mut reader).await? {
+        let line = String::from_utf8_lossy(&line).trim_end().to_string();
+        let (verb, args) = split_word(&line);
+        let reply = match verb.to_ascii_uppercase().as_str() {
+            "EHLO" => {
+                "250-localhost\r\n250-8BITMIME\r\n250-SMTPUTF8\r\n250 AUTH PLAIN LOGIN".to_string()
+            }
+            "HELO" => "250 localhost".to_string(),
+            "AUTH" => {
+                let (mechanism, initial) = split_word(args);
+                let credentials = match mechanism.to_ascii_uppercase().as_str() {
+                    "PLAIN" => {
+                        let response = if initial.is_empty() {
+                            writer.write_all(b"334 \r\n").await?;
+                            read_line(&mut reader).await?.context("EOF during AUTH")?
+                        } else {
+                            initial.as_bytes().to_vec()
+                        };
+                        decode_base64(&response).and_then(|decoded| {
+                            let mut parts = decoded.split('\0').skip(1);
+                            Some((parts.next()?.to_string(), parts.next()?.to_string()))
+                        })
+                    }
+                    "LOGIN" => {
+                        let username = if initial.is_empty() {
+                            writer.write_all(b"334 VXNlcm5hbWU6\r\n").await?;
+                            read_line(&mut reader).await?.context("EOF during AUTH")?
+                        } else {
+                            initial.as_bytes().to_vec()
+                        };
+                        writer.write_all(b"334 UGFzc3dvcmQ6\r\n").await?;
+                        let password = read_line(&mut reader).await?.context("EOF during AUTH")?;
+                        decode_base64(&username).zip(decode_base64(&password))
+                    }
+                    _ => None,
+                };
+                let valid = credentials.map_or(false, |(username, password)| {
+                    let username = username.to_lowercase();
+                    let valid = shared.read(|state| {
+                        state
+                            .accounts
+                            .get(&username)
+                            .map_or(false, |account| account.password == password)
+                    });
+                    if valid {
+                        user = Some(username);
+                    }
+                    valid
+                });
+                if valid {
+                    "235 2.7.0 Authentication successful".to_string()
+                } else {
+                    "535 5.7.8 Authentication credentials invalid".to_string()
+                }
+            }
+            "MAIL" => {
+                if user.is_none() {
+                    "530 5.7.0 Authentication required".to_string()
+                } else if let Some(addr) = parse_path(args, "FROM:") {
+                    from = Some(addr);
+                    recipients.clear();
+                    "250 2.1.0 Ok".to_string()
+                } else {
+                    "501 5.5.4 Invalid MAIL FROM".to_string()
+                }
+            }
+            "RCPT" => match (&from, parse_path(args, "TO:")) {
+                (None, _) => "503 5.5.1 Need MAIL first".to_string(),
+                (Some(_), Some(addr)) => {
+                    recipients.push(addr);
+                    "250 2.1.5 Ok".to_string()
+                }
+                (Some(_), None) => "501 5.5.4 Invalid RCPT TO".to_string(),
+            },
+            "DATA" => {
+                if from.is_none() || recipients.is_empty() {
+                    "503 5.5.1 Need RCPT first".to_string()
+                } else {
+                    writer
+                        .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                        .await?;
+                    let mut data = Vec::new();
+                    loop {
+                        let line = read_line(&mut reader).await?.context("EOF during DATA")?;
+                        if line == b".\r\n" || line == b".\n" {
+                            break;
+                        }
+                        let line = line.strip_prefix(b".").unwrap_or(&line);
+                        data.extend_from_slice(line);
+                    }
+                    let message = SmtpMessage {
+                        from: from.take().unwrap_or_default(),
+                        recipients: std::mem::take(&mut recipients),
+                        data,
+                    };
+                    shared.update(|state| {
+                        let now = time();
+                        for recipient in &message.recipients {
+                            if let Some(inbox) =
+                                state.mailbox_mut(&recipient.to_lowercase(), "INBOX")
+                            {
+                                inbox.append(message.data.clone(), BTreeSet::new(), now);
+                            }
+                        }
+                        state.outgoing.push(message);
+                    });
+                    "250 2.0.0 Ok: queued".to_string()
+                }
+            }
+            "RSET" => {
+                from = None;
+                recipients.clear();
+                "250 2.0.0 Ok".to_string()
+            }
+            "NOOP" => "250 2.0.0 Ok".to_string(),
+            "QUIT" => {
+                writer.write_all(b"221 2.0.0 Bye\r\n").await?;
+                break;
+            }
+            _ => "502 5.5.2 Command not recognized".to_string(),
+        };
+        writer.write_all(format!("{reply}\r\n").as_bytes()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat;
+    use crate::test_utils::{TestContext, TestContextManager};
+
+    #[test]
+    fn test_parse_sequence_set() {
+        assert_eq!(parse_sequence_set("1:3,5", 10), Some(vec![(1, 3), (5, 5)]));
+        assert_eq!(parse_sequence_set("4:*", 2), Some(vec![(2, 4)]));
+        assert_eq!(parse_sequence_set("0", 2), None);
+    }
+
+    #[test]
+    fn test_section_data() {
+        let body = b"From: alice@example.org\r\n\
+                     Subject: foo\r\n bar\r\n\
+                     Message-ID: <1@example.org>\r\n\
+                     \r\n\
+                     Hello\r\n";
+        assert_eq!(
+            section_data(body, "HEADER.FIELDS (MESSAGE-ID SUBJECT)").unwrap(),
+            b"Subject: foo\r\n bar\r\nMessage-ID: <1@example.org>\r\n\r\n"
+        );
+        assert_eq!(section_data(body, "TEXT").unwrap(), b"Hello\r\n");
+        assert_eq!(section_data(body, "").unwrap(), body);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut mailbox = Mailbox::new(1, None);
+        mailbox.append(
+            b"From: alice@example.org\r\n\r\n".to_vec(),
+            BTreeSet::new(),
+            0,
+        );
+        mailbox.append(
+            b"From: bob@example.net\r\n\r\n".to_vec(),
+            BTreeSet::new(),
+            0,
+        );
+        let search = |criteria: &str| {
+            let key = parse_search(criteria).unwrap();
+            (0..mailbox.messages.len())
+                .filter(|&i| key.matches(&mailbox, i))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(search(r#"FROM "alice@example.org""#), vec![0]);
+        assert_eq!(
+            search(r#"OR (FROM "alice@example.org") (FROM "bob@example.net")"#),
+            vec![0, 1]
+        );
+        assert_eq!(search(r#"UID 2:* NOT FROM "alice""#), vec![1]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_send_and_receive() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let server = TestMailServer::start().await?;
+        for (t, addr) in [(&alice, "alice@example.org"), (&bob, "bob@example.net")] {
+            server.add_account(addr, "password");
+            server.configure(t, addr).await?;
+        }
+        server.create_mailbox("alice@example.org", "Sent", Some(r"\Sent"))?;
+        alice.start_io().await;
+        bob.start_io().await;
+
+        let alice_chat = alice.create_chat(&bob).await;
+        chat::send_text_msg(&alice, alice_chat.id, "Hello Bob!".to_string()).await?;
+
+        bob.evtracker.wait_next_incoming_message().await;
+        let msg = bob.get_last_msg().await;
+        assert_eq!(msg.get_text(), Some("Hello Bob!".to_string()));
+        assert!(server
+            .outgoing()
+            .iter()
+            .any(|msg| msg.from == "alice@example.org"
+                && msg.recipients.contains(&"bob@example.net".to_string())));
+
+        // Chat messages are moved to the DeltaChat folder.
+        server
+            .wait_for_messages("bob@example.net", "DeltaChat", 1)
+            .await;
+
+        // Bob can reply.
+        let bob_chat_id = msg.chat_id;
+        bob_chat_id.accept(&bob).await?;
+        chat::send_text_msg(&bob, bob_chat_id, "Hi Alice!".to_string()).await?;
+        alice.evtracker.wait_next_incoming_message().await;
+        let msg = alice.get_last_msg().await;
+        assert_eq!(msg.get_text(), Some("Hi Alice!".to_string()));
+        assert_eq!(msg.chat_id, alice_chat.id);
+
+        // Folders are detected by their special-use attributes.
+        assert_eq!(
+            alice.get_config(Config::ConfiguredSentboxFolder).await?,
+            Some("Sent".to_string())
+        );
+
+        alice.stop_io().await;
+        bob.stop_io().await;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_fault_injection() -> Result<()> {
+        let mut tcm = TestContextManager::new();
+        let alice = tcm.alice().await;
+        let bob = tcm.bob().await;
+        let server = TestMailServer::start().await?;
+        server.set_fault_injection(Some(FaultInjection {
+            seed: 42,
+            disconnect_probability: 0.05,
+            truncate_probability: 0.05,
+            max_latency: Duration::from_millis(20),
+            max_faults: 3,
+        }));
+        for (t, addr) in [(&alice, "alice@example.org"), (&bob, "bob@example.net")] {
+            server.add_account(addr, "password");
+            server.configure(t, addr).await?;
+            t.start_io().await;
+        }
+
+        let alice_chat = alice.create_chat(&bob).await;
+        for i in 0..3 {
+            chat::send_text_msg(&alice, alice_chat.id, format!("Message {i}")).await?;
+        }
+
+        // Despite the faults, all messages arrive exactly once after reconnecting.
+        // Instead of polling, wake up on incoming messages and on server changes,
+        // which include injected faults.
+        let mut changes = server.shared.changes.subscribe();
+        let mut injected_faults = 0;
+        let received = tokio::time::timeout(Duration::from_secs(60), async {
+            loop {
+                let received = bob
+                    .sql
+                    .count("SELECT COUNT(*) FROM msgs WHERE txt LIKE 'Message %'", ())
+                    .await
+                    .unwrap();
+                if received >= 3 {
+                    break received;
+                }
+                tokio::select! {
+                    _ = changes.changed() => {}
+                    _ = bob.evtracker.recv() => {}
+                }
+                if server.injected_faults() > injected_faults {
+                    injected_faults = server.injected_faults();
+                    // Retry right away instead of waiting for the retry timeouts.
+                    alice.maybe_network().await;
+                    bob.maybe_network().await;
+                }
+            }
+        })
+        .await?;
+        assert_eq!(received, 3);
+        assert!(server.injected_faults() <= 3);
+
+        alice.stop_io().await;
+        bob.stop_io().await;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_uid_validity_reset() -> Result<()> {
+        let t = TestContext::new_alice().await;
+        let server = TestMailServer::start().await?;
+        server.add_account("alice@example.org", "password");
+        server.configure(&t, "alice@example.org").await?;
+        t.set_config_bool(Config::MvboxMove, false).await?;
+        t.start_io().await;
+
+        server.deliver(
+            "alice@example.org",
+            "INBOX",
+            b"From: bob@example.net\r\n\
+              To: alice@example.org\r\n\
+              Subject: first\r\n\
+              Message-ID: <first@example.net>\r\n\
+              Date: Sun, 22 Mar 2020 22:37:57 +0000\r\n\
+              \r\n\
+              First message\r\n",
+        )?;
+        t.evtracker.wait_next_incoming_message().await;
+
+        // After the UIDVALIDITY change the known message is not downloaded again.
+        t.stop_io().await;
+        server.reset_uid_validity("alice@example.org", "INBOX")?;
+        server.deliver(
+            "alice@example.org",
+            "INBOX",
+            b"From: bob@example.net\r\n\
+              To: alice@example.org\r\n\
+              Subject: second\r\n\
+              Message-ID: <second@example.net>\r\n\
+              Date: Sun, 22 Mar 2020 22:38:57 +0000\r\n\
+              \r\n\
+              Second message\r\n",
+        )?;
+        t.start_io().await;
+        t.evtracker.wait_next_incoming_message().await;
+        let msg = t.get_last_msg().await;
+        assert_eq!(msg.get_text(), Some("Second message".to_string()));
+        assert_eq!(msg.chat_id.get_msg_cnt(&t).await?, 2);
+
+        t.stop_io().await;
+        Ok(())
+    }
+}
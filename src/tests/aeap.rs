@@ -2,12 +2,15 @@
 
 use anyhow::Result;
 
+use crate::aeap::{self, AeapMigrationState};
 use crate::chat;
 use crate::chat::ChatId;
 use crate::contact;
 use crate::contact::Contact;
 use crate::contact::ContactId;
 use crate::message::Message;
+use crate::mimeparser::SystemMessage;
+use crate::param::Param;
 use crate::peerstate;
 use crate::peerstate::Peerstate;
 use crate::receive_imf::receive_imf;
@@ -350,6 +353,7 @@ async fn get_last_info_msg(t: &TestContext, chat_id: ChatId) -> Option<Message>
         chat::MessageListOptions {
             info_only: true,
             add_daymarker: false,
+            corrected_order: false,
         },
     )
     .await
@@ -362,6 +366,88 @@ async fn get_last_info_msg(t: &TestContext, chat_id: ChatId) -> Option<Message>
     Some(Message::load_from_db(&t.ctx, *msg_id).await.unwrap())
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_aeap_migrations_review_and_undo() -> Result<()> {
+    let mut tcm = TestContextManager::new();
+    let alice = tcm.alice().await;
+    let bob = tcm.bob().await;
+
+    tcm.send_recv_accept(&alice, &bob, "Hi").await;
+    tcm.send_recv_accept(&bob, &alice, "Hi back").await;
+    mark_as_verified(&alice, &bob).await;
+    mark_as_verified(&bob, &alice).await;
+
+    let unprotected =
+        chat::create_group_chat(&bob, chat::ProtectionStatus::Unprotected, "Group 0").await?;
+    let protected =
+        chat::create_group_chat(&bob, chat::ProtectionStatus::Protected, "Group 1").await?;
+    let old_contact = Contact::create(&bob, "Alice", "alice@example.org").await?;
+    chat::add_contact_to_chat(&bob, unprotected, old_contact).await?;
+    chat::add_contact_to_chat(&bob, protected, old_contact).await?;
+    bob.send_text(protected, "Hi group").await;
+
+    tcm.change_addr(&alice, "fiona@example.net").await;
+    let chat_id = alice.create_chat(&bob).await.id;
+    let sent = alice.send_text(chat_id, "Hello from my new addr!").await;
+    bob.recv_msg(&sent).await;
+
+    let new_contact =
+        Contact::lookup_id_by_addr(&bob, "fiona@example.net", contact::Origin::Unknown)
+            .await?
+            .unwrap();
+    let migrations = aeap::get_aeap_migrations(&bob, Some(old_contact)).await?;
+    assert_eq!(migrations.len(), 3);
+    let state_of = |chat_id: ChatId| {
+        migrations
+            .iter()
+            .find(|migration| migration.chat_id == chat_id)
+            .map(|migration| migration.state)
+    };
+    assert_eq!(
+        state_of(unprotected),
+        Some(AeapMigrationState::SkippedUnprotectedGroup)
+    );
+    assert_eq!(state_of(protected), Some(AeapMigrationState::Migrated));
+    let single_chat = ChatId::lookup_by_contact(&bob, old_contact).await?.unwrap();
+    assert_eq!(
+        state_of(single_chat),
+        Some(AeapMigrationState::SkippedSingleChat)
+    );
+    assert!(chat::is_contact_in_chat(&bob, protected, new_contact).await?);
+
+    let migration = migrations
+        .iter()
+        .find(|migration| migration.chat_id == protected)
+        .unwrap();
+    assert_eq!(migration.new_contact_id, Some(new_contact));
+    assert_eq!(migration.new_addr, "fiona@example.net");
+    aeap::undo_aeap_migration(&bob, migration.id).await?;
+    assert!(chat::is_contact_in_chat(&bob, protected, old_contact).await?);
+    assert!(!chat::is_contact_in_chat(&bob, protected, new_contact).await?);
+
+    // The other members are informed about the changes.
+    let removed = bob.pop_sent_msg().await;
+    let removed = Message::load_from_db(&bob, removed.sender_msg_id).await?;
+    assert_eq!(
+        removed.get_info_type(),
+        SystemMessage::MemberRemovedFromGroup
+    );
+    assert_eq!(removed.param.get(Param::Arg), Some("fiona@example.net"));
+    let added = bob.pop_sent_msg().await;
+    let added = Message::load_from_db(&bob, added.sender_msg_id).await?;
+    assert_eq!(added.get_info_type(), SystemMessage::MemberAddedToGroup);
+    assert_eq!(added.param.get(Param::Arg), Some("alice@example.org"));
+    assert!(Peerstate::from_addr(&bob, "alice@example.org")
+        .await?
+        .is_some());
+    assert!(aeap::undo_aeap_migration(&bob, migration.id).await.is_err());
+
+    let migrations = aeap::get_aeap_migrations(&bob, Some(new_contact)).await?;
+    assert_eq!(migrations.len(), 1);
+    assert_eq!(migrations[0].state, AeapMigrationState::Undone);
+    Ok(())
+}
+
 /// Test that an attacker - here Fiona - can't replay a message sent by Alice
 /// to make Bob think that there was a transition to Fiona's address.
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -58,6 +58,7 @@ pub async fn save(
 /// (eg. when a qr code token is withdrawn, recreated and revived later),
 /// use lookup() for qr-code creation only;
 /// do not use lookup() to check for token validity.
+/// Tokens of invite links with a usage limit are never returned.
 ///
 /// To check if a given token is valid, use exists().
 pub async fn lookup(
@@ -70,7 +71,7 @@ pub async fn lookup(
             context
                 .sql
                 .query_get_value(
-                    "SELECT token FROM tokens WHERE namespc=? AND foreign_id=? ORDER BY timestamp DESC LIMIT 1;",
+                    "SELECT token FROM tokens WHERE namespc=? AND foreign_id=? AND max_uses=0 ORDER BY timestamp DESC LIMIT 1;",
                     (namespace, chat_id),
                 )
                 .await?
@@ -80,7 +81,7 @@ pub async fn lookup(
             context
                 .sql
                 .query_get_value(
-                    "SELECT token FROM tokens WHERE namespc=? AND foreign_id=0 ORDER BY timestamp DESC LIMIT 1;",
+                    "SELECT token FROM tokens WHERE namespc=? AND foreign_id=0 AND max_uses=0 ORDER BY timestamp DESC LIMIT 1;",
                     (namespace,),
                 )
                 .await?
@@ -22,10 +22,11 @@
 use tokio::{fs, io};
 
 use crate::chat::{add_device_msg, add_device_msg_with_importance};
+use crate::clock_skew;
 use crate::constants::{DC_ELLIPSIS, DC_OUTDATED_WARNING_DAYS};
 use crate::context::Context;
 use crate::events::EventType;
-use crate::message::{Message, Viewtype};
+use crate::message::{DeviceAction, Message, Viewtype};
 use crate::stock_str;
 
 /// Shortens a string to a specified length and adds "[...]" to the
@@ -143,14 +144,14 @@ pub(crate) fn gm2local_offset() -> i64 {
 ///
 /// The returned timestamp MUST NOT be sent out.
 pub(crate) fn smeared_time(context: &Context) -> i64 {
-    let now = time();
+    let now = time() + clock_skew::get_correction(context);
     let ts = context.smeared_timestamp.current();
     std::cmp::max(ts, now)
 }
 
 /// Returns a timestamp that is guaranteed to be unique.
 pub(crate) fn create_smeared_timestamp(context: &Context) -> i64 {
-    let now = time();
+    let now = time() + clock_skew::get_correction(context);
     context.smeared_timestamp.create(now)
 }
 
@@ -158,7 +159,7 @@ pub(crate) fn create_smeared_timestamp(context: &Context) -> i64 {
 // the first created timestamps is returned directly,
 // get the other timestamps just by adding 1..count-1
 pub(crate) fn create_smeared_timestamps(context: &Context, count: usize) -> i64 {
-    let now = time();
+    let now = time() + clock_skew::get_correction(context);
     context.smeared_timestamp.create_n(now, count as i64)
 }
 
@@ -222,6 +223,7 @@ async fn maybe_warn_on_outdated(context: &Context, now: i64, approx_compile_time
     if now > approx_compile_time + DC_OUTDATED_WARNING_DAYS * 24 * 60 * 60 {
         let mut msg = Message::new(Viewtype::Text);
         msg.text = Some(stock_str::update_reminder_msg_body(context).await);
+        msg.set_device_action(DeviceAction::UpdateApp);
         if let Some(timestamp) = chrono::NaiveDateTime::from_timestamp_opt(now, 0) {
             add_device_msg(
                 context,
@@ -267,6 +269,24 @@ pub(crate) fn create_id() -> String {
         .collect()
 }
 
+/// Generates a room name for a videochat invitation.
+///
+/// Everybody knowing the name of a room can usually join it,
+/// so the name must not be guessable and carries at least 128 random bits.
+/// Only lowercase letters and digits are used
+/// as some services, e.g. Jitsi Meet, ignore the case of room names.
+pub(crate) fn create_videochat_room() -> String {
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    // 25 characters out of 36 carry 129 bits.
+    const LEN: usize = 25;
+
+    // ThreadRng implements CryptoRng trait and is supposed to be cryptographically secure.
+    let mut rng = thread_rng();
+    (0..LEN)
+        .map(|_| char::from(CHARS[rng.gen_range(0..CHARS.len())]))
+        .collect()
+}
+
 /// Function generates a Message-ID that can be used for a new outgoing message.
 /// - this function is called for all outgoing messages.
 /// - the message ID should be globally unique
@@ -655,6 +675,15 @@ pub(crate) fn parse_receive_headers(headers: &Headers) -> String {
         .join("\n")
 }
 
+/// Returns the time the message was received by our provider,
+/// taken from the topmost "Received" header.
+pub(crate) fn parse_receive_timestamp(headers: &Headers) -> Option<i64> {
+    let header = headers.get_all_headers("Received").into_iter().next()?;
+    let header = from_utf8(header.get_value_raw()).ok()?;
+    let (_, date) = header.rsplit_once(';')?;
+    dateparse(date.trim()).ok()
+}
+
 /// If `collection` contains exactly one element, return this element.
 /// Otherwise, return None.
 pub(crate) fn single_value<T>(collection: impl IntoIterator<Item = T>) -> Option<T> {
@@ -710,6 +739,133 @@ pub(crate) fn strip_rtlo_characters(input_str: &str) -> String {
     input_str.replace(|char| RTLO_CHARACTERS.contains(&char), "")
 }
 
+/// First strong isolate, starts text with its own direction.
+const FSI: char = '\u{2068}';
+
+/// Pop directional isolate, ends [`FSI`].
+const PDI: char = '\u{2069}';
+
+/// Direction of a text as determined by its first strong character.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum TextDirection {
+    /// The text contains no letters, e.g. only digits or emoji.
+    #[default]
+    Neutral = 0,
+
+    /// Left-to-right text.
+    Ltr = 1,
+
+    /// Right-to-left text, e.g. Arabic or Hebrew.
+    Rtl = 2,
+}
+
+/// Returns true for letters of right-to-left scripts.
+fn is_rtl_letter(c: char) -> bool {
+    c.is_alphabetic()
+        && matches!(c,
+            '\u{0590}'..='\u{08FF}'
+            | '\u{FB1D}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFF}'
+            | '\u{10800}'..='\u{10FFF}'
+            | '\u{1E800}'..='\u{1EFFF}')
+}
+
+/// Returns the direction of the first letter of `text`,
+/// skipping isolated parts as done by the Unicode bidi algorithm.
+pub fn text_direction(text: &str) -> TextDirection {
+    let mut isolate_depth = 0usize;
+    for c in text.chars() {
+        match c {
+            '\u{2066}'..='\u{2068}' => isolate_depth += 1,
+            PDI => isolate_depth = isolate_depth.saturating_sub(1),
+            c if isolate_depth == 0 && is_rtl_letter(c) => return TextDirection::Rtl,
+            c if isolate_depth == 0 && c.is_alphabetic() => return TextDirection::Ltr,
+            _ => {}
+        }
+    }
+    TextDirection::Neutral
+}
+
+/// Script of letters which are commonly confused with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfusableScript {
+    Latin,
+    Greek,
+    Cyrillic,
+}
+
+fn confusable_script(c: char) -> Option<ConfusableScript> {
+    match c {
+        'A'..='Z' | 'a'..='z' | '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' => {
+            Some(ConfusableScript::Latin)
+        }
+        '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Some(ConfusableScript::Greek),
+        '\u{0400}'..='\u{052F}'
+        | '\u{1C80}'..='\u{1C8F}'
+        | '\u{2DE0}'..='\u{2DFF}'
+        | '\u{A640}'..='\u{A69F}' => Some(ConfusableScript::Cyrillic),
+        _ => None,
+    }
+}
+
+/// Returns true for characters which are invisible or change the rendering of other characters
+/// without being visible themselves.
+///
+/// Emoji joiners and variation selectors are allowed as they are used in emoji sequences.
+/// The zero width non-joiner and the implicit direction marks are allowed as well,
+/// they are needed to write names in Persian, Arabic or Hebrew correctly.
+fn is_invisible_char(c: char) -> bool {
+    matches!(c,
+        '\u{00AD}'
+        | '\u{034F}'
+        | '\u{115F}'..='\u{1160}'
+        | '\u{17B4}'..='\u{17B5}'
+        | '\u{180E}'
+        | '\u{200B}'
+        | '\u{202A}'..='\u{202E}'
+        | '\u{2060}'..='\u{2064}'
+        | '\u{2066}'..='\u{206F}'
+        | '\u{2800}'
+        | '\u{3164}'
+        | '\u{FEFF}'
+        | '\u{FFA0}'
+        | '\u{E0000}'..='\u{E007F}')
+}
+
+/// Returns true if a name looks like an attempt to impersonate someone,
+/// i.e. it contains invisible characters
+/// or a word mixes Latin, Greek and Cyrillic letters, as in "Pаypal" with a Cyrillic "а".
+pub(crate) fn is_suspicious_name(name: &str) -> bool {
+    if name.chars().any(is_invisible_char) {
+        return true;
+    }
+    name.split_whitespace().any(|word| {
+        let mut scripts = word.chars().filter_map(confusable_script);
+        match scripts.next() {
+            Some(first) => scripts.any(|script| script != first),
+            None => false,
+        }
+    })
+}
+
+/// Wraps user-provided text embedded into a generated string
+/// into a first strong isolate, so that right-to-left names
+/// or stray bidi control characters do not garble the surrounding text.
+///
+/// Text without right-to-left letters and bidi controls is returned unchanged.
+pub(crate) fn bidi_isolate(text: &str) -> Cow<str> {
+    let needs_isolation = text.chars().any(|c| {
+        is_rtl_letter(c)
+            || matches!(c, '\u{061C}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+    });
+    if needs_isolation {
+        Cow::Owned(format!("{FSI}{text}{PDI}"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::indexing_slicing)]
@@ -717,6 +873,42 @@ mod tests {
     use super::*;
     use crate::{message::get_msg_info, receive_imf::receive_imf, test_utils::TestContext};
 
+    #[test]
+    fn test_is_suspicious_name() {
+        assert!(!is_suspicious_name("Alice"));
+        assert!(!is_suspicious_name("Jörg Müller"));
+        assert!(!is_suspicious_name("Алиса Alice"));
+        assert!(!is_suspicious_name("Ελένη"));
+        assert!(!is_suspicious_name("👩\u{200D}💻 Alice ❤\u{FE0F}"));
+        assert!(!is_suspicious_name(""));
+        assert!(!is_suspicious_name("می\u{200C}خواهم")); // Zero width non-joiner
+        assert!(!is_suspicious_name("\u{200F}שרה\u{200E} Cohen"));
+        assert!(!is_suspicious_name("\u{061C}علي"));
+
+        assert!(is_suspicious_name("P\u{0430}ypal")); // Cyrillic "а"
+        assert!(is_suspicious_name("\u{03BF}pen")); // Greek "ο"
+        assert!(is_suspicious_name("Ali\u{200B}ce"));
+        assert!(is_suspicious_name("\u{3164}"));
+        assert!(is_suspicious_name("Alice\u{2066}"));
+    }
+
+    #[test]
+    fn test_bidi_isolate() {
+        assert_eq!(bidi_isolate("Bob"), "Bob");
+        assert_eq!(bidi_isolate("42"), "42");
+        assert_eq!(bidi_isolate("שלום"), "\u{2068}שלום\u{2069}");
+        assert_eq!(bidi_isolate("a\u{202E}b"), "\u{2068}a\u{202E}b\u{2069}");
+
+        assert_eq!(text_direction(""), TextDirection::Neutral);
+        assert_eq!(text_direction("123 😀"), TextDirection::Neutral);
+        assert_eq!(text_direction("Hello"), TextDirection::Ltr);
+        assert_eq!(text_direction("12 مرحبا"), TextDirection::Rtl);
+        assert_eq!(
+            text_direction(&format!("{} joined", bidi_isolate("שלום"))),
+            TextDirection::Ltr
+        );
+    }
+
     #[test]
     fn test_parse_receive_headers() {
         // Test `parse_receive_headers()` with some more-or-less random emails from the test-data
@@ -930,6 +1122,16 @@ fn test_create_id_invalid_chars() {
         }
     }
 
+    #[test]
+    fn test_create_videochat_room() {
+        let room = create_videochat_room();
+        assert_eq!(room.len(), 25);
+        assert!(room
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+        assert_ne!(room, create_videochat_room());
+    }
+
     #[test]
     fn test_extract_grpid_from_rfc724_mid() {
         // Should return None if we pass invalid mid
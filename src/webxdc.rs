@@ -3,7 +3,7 @@
 use std::convert::TryFrom;
 use std::path::Path;
 
-use anyhow::{anyhow, bail, ensure, format_err, Result};
+use anyhow::{anyhow, bail, ensure, format_err, Context as _, Result};
 use deltachat_derive::FromSql;
 use lettre_email::mime;
 use lettre_email::PartBuilder;
@@ -51,8 +51,14 @@
 /// and not showing the .xdc on some devices would be even worse ux.
 const WEBXDC_RECEIVING_LIMIT: u64 = 4194304;
 
+/// Maximal total size in bytes of the uncompressed files in an .xdc archive.
+///
+/// This protects against archives that are small on the wire
+/// but expand to huge files on disk or in memory.
+const WEBXDC_UNCOMPRESSED_LIMIT: u64 = 104857600;
+
 /// Raw information read from manifest.toml
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[non_exhaustive]
 struct WebxdcManifest {
     /// Webxdc name, used on icons or page titles.
@@ -66,6 +72,10 @@ struct WebxdcManifest {
 
     /// If the webxdc requests network access.
     request_internet_access: Option<bool>,
+
+    /// Maximal size in bytes of a single status update.
+    /// Larger updates are rejected on sending and ignored on receiving.
+    max_update_size: Option<u32>,
 }
 
 /// Parsed information from WebxdcManifest and fallbacks.
@@ -87,6 +97,11 @@ pub struct WebxdcInfo {
     /// For example, "7 votes".
     pub summary: String,
 
+    /// Number of info messages sent by other members
+    /// since the user last marked them as seen
+    /// using [`Context::mark_webxdc_infos_seen`].
+    pub unread_info_cnt: u32,
+
     /// URL of webxdc source code or an empty string.
     pub source_code_url: String,
 
@@ -94,6 +109,10 @@ pub struct WebxdcInfo {
     /// It should request access, be encrypted
     /// and sent to self for this.
     pub internet_access: bool,
+
+    /// Maximal size in bytes of a single status update as declared in the manifest,
+    /// `None` if the webxdc declares no limit.
+    pub max_update_size: Option<u32>,
 }
 
 /// Status Update ID.
@@ -154,6 +173,10 @@ pub(crate) struct StatusUpdateItem {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) summary: Option<String>,
+
+    /// `href` passed to the webxdc when the info message is clicked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) href: Option<String>,
 }
 
 /// Update items as passed to the UIs.
@@ -194,16 +217,8 @@ pub(crate) async fn is_webxdc_file(&self, filename: &str, file: &[u8]) -> Result
             return Ok(false);
         }
 
-        let archive = match async_zip::read::mem::ZipFileReader::new(file.to_vec()).await {
-            Ok(archive) => archive,
-            Err(_) => {
-                info!(self, "{} cannot be opened as zip-file", &filename);
-                return Ok(false);
-            }
-        };
-
-        if find_zip_entry(archive.file(), "index.html").is_none() {
-            info!(self, "{} misses index.html", &filename);
+        if let Err(err) = validate_webxdc_archive(file).await {
+            info!(self, "{} is not a valid webxdc: {:#}", &filename, err);
             return Ok(false);
         }
 
@@ -227,24 +242,10 @@ pub(crate) async fn ensure_sendable_webxdc_file(&self, path: &Path) -> Result<()
             );
         }
 
-        let valid = match async_zip::read::fs::ZipFileReader::new(path).await {
-            Ok(archive) => {
-                if find_zip_entry(archive.file(), "index.html").is_none() {
-                    info!(self, "{} misses index.html", filename);
-                    false
-                } else {
-                    true
-                }
-            }
-            Err(_) => {
-                info!(self, "{} cannot be opened as zip-file", filename);
-                false
-            }
-        };
-
-        if !valid {
-            bail!("{} is not a valid webxdc file", filename);
-        }
+        let file = tokio::fs::read(path).await?;
+        validate_webxdc_archive(&file)
+            .await
+            .with_context(|| format!("{filename} is not a valid webxdc file"))?;
 
         Ok(())
     }
@@ -306,9 +307,12 @@ async fn create_status_update_record(
                 bail!("create_status_update_record: no valid update item.");
             };
 
+        let mut param_changed = false;
+        let mut attention = None;
+
         if can_info_msg {
             if let Some(ref info) = status_update_item.info {
-                if let Some(info_msg_id) =
+                let info_msg_id = if let Some(info_msg_id) =
                     self.get_overwritable_info_msg_id(instance, from_id).await?
                 {
                     chat::update_msg_text_and_timestamp(
@@ -319,6 +323,7 @@ async fn create_status_update_record(
                         timestamp,
                     )
                     .await?;
+                    info_msg_id
                 } else {
                     chat::add_info_msg_with_cmd(
                         self,
@@ -330,13 +335,33 @@ async fn create_status_update_record(
                         Some(instance),
                         Some(from_id),
                     )
-                    .await?;
+                    .await?
+                };
+
+                let mut info_msg = Message::load_from_db(self, info_msg_id).await?;
+                if info_msg.param.get(Param::WebxdcHref) != status_update_item.href.as_deref() {
+                    match &status_update_item.href {
+                        Some(href) => info_msg.param.set(Param::WebxdcHref, href),
+                        None => info_msg.param.remove(Param::WebxdcHref),
+                    };
+                    info_msg.update_param(self).await?;
+                }
+
+                if from_id != ContactId::SELF {
+                    let unread_info_cnt = instance
+                        .param
+                        .get_int(Param::WebxdcUnreadInfoCount)
+                        .unwrap_or_default()
+                        .saturating_add(1);
+                    instance
+                        .param
+                        .set_int(Param::WebxdcUnreadInfoCount, unread_info_cnt);
+                    param_changed = true;
+                    attention = Some(u32::try_from(unread_info_cnt).unwrap_or_default());
                 }
             }
         }
 
-        let mut param_changed = false;
-
         if let Some(ref document) = status_update_item.document {
             if instance
                 .param
@@ -364,6 +389,13 @@ async fn create_status_update_record(
             self.emit_msgs_changed(instance.chat_id, instance.id);
         }
 
+        if let Some(unread_info_cnt) = attention {
+            self.emit_event(EventType::WebxdcAttention {
+                msg_id: instance.id,
+                unread_info_cnt,
+            });
+        }
+
         let status_update_serial = self
             .write_status_update_inner(&instance.id, status_update_item)
             .await?;
@@ -410,6 +442,14 @@ pub async fn send_webxdc_status_update(
             bail!("send_webxdc_status_update: is no webxdc message");
         }
 
+        if let Some(max_update_size) = instance.get_webxdc_manifest(self).await?.max_update_size {
+            let update_size = update_str.trim().len();
+            ensure!(
+                update_size <= max_update_size as usize,
+                "send_webxdc_status_update: update of {update_size} bytes exceeds max_update_size of {max_update_size} bytes declared in the manifest"
+            );
+        }
+
         let chat = Chat::load_from_db(self, instance.chat_id).await?;
         if let Some(reason) = chat.why_cant_send(self).await? {
             bail!("cannot send to {}: {}", chat.id, reason);
@@ -548,11 +588,28 @@ pub(crate) async fn receive_status_update(
             bail!("receive_status_update: status sender not chat member.")
         }
 
+        let max_update_size = if instance.viewtype == Viewtype::Webxdc {
+            instance.get_webxdc_manifest(self).await?.max_update_size
+        } else {
+            None
+        };
+
         let updates: StatusUpdates = serde_json::from_str(json)?;
         for update_item in updates.updates {
+            let update_str = serde_json::to_string(&update_item)?;
+            if let Some(max_update_size) = max_update_size {
+                if update_str.len() > max_update_size as usize {
+                    warn!(
+                        self,
+                        "receive_status_update: ignoring update of {} bytes exceeding max_update_size of {max_update_size} bytes.",
+                        update_str.len()
+                    );
+                    continue;
+                }
+            }
             self.create_status_update_record(
                 &mut instance,
-                &serde_json::to_string(&update_item)?,
+                &update_str,
                 timestamp,
                 can_info_msg,
                 from_id,
@@ -563,6 +620,28 @@ pub(crate) async fn receive_status_update(
         Ok(())
     }
 
+    /// Marks the info messages of a webxdc instance as seen,
+    /// resetting the number of unread info messages returned in [`WebxdcInfo`].
+    ///
+    /// UIs should call this when the webxdc is opened.
+    pub async fn mark_webxdc_infos_seen(&self, instance_msg_id: MsgId) -> Result<()> {
+        let mut instance = Message::load_from_db(self, instance_msg_id).await?;
+        if instance.viewtype != Viewtype::Webxdc {
+            bail!("mark_webxdc_infos_seen: is no webxdc message");
+        }
+        if instance
+            .param
+            .get_int(Param::WebxdcUnreadInfoCount)
+            .unwrap_or_default()
+            != 0
+        {
+            instance.param.remove(Param::WebxdcUnreadInfoCount);
+            instance.update_param(self).await?;
+            self.emit_msgs_changed(instance.chat_id, instance.id);
+        }
+        Ok(())
+    }
+
     /// Returns status updates as an JSON-array, ready to be consumed by a webxdc.
     ///
     /// Example: `[{"serial":1, "max_serial":3, "payload":"any update data"},
@@ -664,6 +743,52 @@ fn parse_webxdc_manifest(bytes: &[u8]) -> Result<WebxdcManifest> {
     Ok(manifest)
 }
 
+/// Checks that `file` is an acceptable webxdc archive.
+///
+/// The archive must be a zip-file containing `index.html`,
+/// must not exceed [`WEBXDC_UNCOMPRESSED_LIMIT`] when uncompressed
+/// and `manifest.toml`, if any, must be valid.
+async fn validate_webxdc_archive(file: &[u8]) -> Result<()> {
+    let mut archive = async_zip::read::mem::ZipFileReader::new(file.to_vec())
+        .await
+        .context("cannot be opened as zip-file")?;
+
+    ensure!(
+        find_zip_entry(archive.file(), "index.html").is_some(),
+        "misses index.html"
+    );
+
+    let uncompressed_size: u64 = archive
+        .file()
+        .entries()
+        .iter()
+        .map(|ent| ent.entry().uncompressed_size())
+        .sum();
+    ensure!(
+        uncompressed_size <= WEBXDC_UNCOMPRESSED_LIMIT,
+        "uncompressed size of {uncompressed_size} bytes exceeds limit of {WEBXDC_UNCOMPRESSED_LIMIT} bytes"
+    );
+
+    if let Some((i, _)) = find_zip_entry(archive.file(), "manifest.toml") {
+        let mut reader = archive.entry(i).await?;
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await?;
+        parse_webxdc_manifest(&buf).context("malformed manifest.toml")?;
+    }
+
+    Ok(())
+}
+
+/// Reads manifest.toml from the archive,
+/// a missing or malformed manifest results in the default manifest.
+async fn read_webxdc_manifest(archive: &mut async_zip::read::fs::ZipFileReader) -> WebxdcManifest {
+    if let Ok(bytes) = get_blob(archive, "manifest.toml").await {
+        parse_webxdc_manifest(&bytes).unwrap_or_default()
+    } else {
+        WebxdcManifest::default()
+    }
+}
+
 async fn get_blob(archive: &mut async_zip::read::fs::ZipFileReader, name: &str) -> Result<Vec<u8>> {
     let (i, _) = find_zip_entry(archive.file(), name)
         .ok_or_else(|| anyhow!("no entry found for {}", name))?;
@@ -726,30 +851,22 @@ pub async fn get_webxdc_blob(&self, context: &Context, name: &str) -> Result<Vec
         get_blob(&mut archive, name).await
     }
 
+    /// Returns the `href` of a webxdc info message.
+    ///
+    /// When the info message is clicked, UIs should open the webxdc instance
+    /// returned by [`Message::parent`] and pass the `href` to it.
+    pub fn get_webxdc_href(&self) -> Option<String> {
+        self.param
+            .get(Param::WebxdcHref)
+            .map(|href| href.to_string())
+    }
+
     /// Return info from manifest.toml or from fallbacks.
     pub async fn get_webxdc_info(&self, context: &Context) -> Result<WebxdcInfo> {
         ensure!(self.viewtype == Viewtype::Webxdc, "No webxdc instance.");
         let mut archive = self.get_webxdc_archive(context).await?;
 
-        let mut manifest = if let Ok(bytes) = get_blob(&mut archive, "manifest.toml").await {
-            if let Ok(manifest) = parse_webxdc_manifest(&bytes) {
-                manifest
-            } else {
-                WebxdcManifest {
-                    name: None,
-                    min_api: None,
-                    source_code_url: None,
-                    request_internet_access: None,
-                }
-            }
-        } else {
-            WebxdcManifest {
-                name: None,
-                min_api: None,
-                source_code_url: None,
-                request_internet_access: None,
-            }
-        };
+        let mut manifest = read_webxdc_manifest(&mut archive).await;
 
         if let Some(ref name) = manifest.name {
             let name = name.trim();
@@ -789,14 +906,26 @@ pub async fn get_webxdc_info(&self, context: &Context) -> Result<WebxdcInfo> {
                     .unwrap_or_default()
                     .to_string()
             },
+            unread_info_cnt: self
+                .param
+                .get_int(Param::WebxdcUnreadInfoCount)
+                .and_then(|cnt| u32::try_from(cnt).ok())
+                .unwrap_or_default(),
             source_code_url: if let Some(url) = manifest.source_code_url {
                 url
             } else {
                 "".to_string()
             },
             internet_access,
+            max_update_size: manifest.max_update_size,
         })
     }
+
+    /// Returns the manifest of a webxdc instance.
+    async fn get_webxdc_manifest(&self, context: &Context) -> Result<WebxdcManifest> {
+        let mut archive = self.get_webxdc_archive(context).await?;
+        Ok(read_webxdc_manifest(&mut archive).await)
+    }
 }
 
 #[cfg(test)]
@@ -854,6 +983,13 @@ async fn test_is_webxdc_file() -> Result<()> {
             )
             .await?
         );
+        assert!(
+            !t.is_webxdc_file(
+                "good-ext-malformed-manifest.xdc",
+                include_bytes!("../test-data/webxdc/with-malformed-manifest.xdc")
+            )
+            .await?
+        );
         assert!(
             t.is_webxdc_file(
                 "good-ext-good-zip.xdc",
@@ -864,6 +1000,100 @@ async fn test_is_webxdc_file() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_validate_webxdc_archive() -> Result<()> {
+        let err = validate_webxdc_archive(include_bytes!("../test-data/message/issue_523.txt"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "cannot be opened as zip-file");
+
+        let err = validate_webxdc_archive(include_bytes!("../test-data/webxdc/no-index-html.xdc"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "misses index.html");
+
+        let err = validate_webxdc_archive(include_bytes!(
+            "../test-data/webxdc/with-malformed-manifest.xdc"
+        ))
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "malformed manifest.toml");
+
+        validate_webxdc_archive(include_bytes!(
+            "../test-data/webxdc/with-max-update-size.xdc"
+        ))
+        .await?;
+
+        // Sending reports the reason as well.
+        let t = TestContext::new_alice().await;
+        let chat_id = create_group_chat(&t, ProtectionStatus::Unprotected, "foo").await?;
+        let mut instance = create_webxdc_instance(
+            &t,
+            "malformed.xdc",
+            include_bytes!("../test-data/webxdc/with-malformed-manifest.xdc"),
+        )
+        .await?;
+        instance.viewtype = Viewtype::Webxdc;
+        let err = send_msg(&t, chat_id, &mut instance).await.unwrap_err();
+        assert!(format!("{err:#}").contains("malformed manifest.toml"));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_webxdc_max_update_size() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob = TestContext::new_bob().await;
+        let alice_chat = alice.create_chat(&bob).await;
+        let mut alice_instance = create_webxdc_instance(
+            &alice,
+            "limited.xdc",
+            include_bytes!("../test-data/webxdc/with-max-update-size.xdc"),
+        )
+        .await?;
+        send_msg(&alice, alice_chat.id, &mut alice_instance).await?;
+        let bob_instance = bob.recv_msg(&alice.pop_sent_msg().await).await;
+        assert_eq!(
+            bob_instance.get_webxdc_info(&bob).await?.max_update_size,
+            Some(100)
+        );
+
+        let big_payload = "x".repeat(100);
+        assert!(alice
+            .send_webxdc_status_update(
+                alice_instance.id,
+                &format!(r#"{{"payload":"{big_payload}"}}"#),
+                "descr"
+            )
+            .await
+            .is_err());
+        alice
+            .send_webxdc_status_update(alice_instance.id, r#"{"payload":1}"#, "descr")
+            .await?;
+        alice.flush_status_updates().await?;
+        bob.recv_msg_opt(&alice.pop_sent_msg().await).await;
+        assert_eq!(
+            bob.get_webxdc_status_updates(bob_instance.id, StatusUpdateSerial(0))
+                .await?,
+            r#"[{"payload":1,"serial":1,"max_serial":1}]"#
+        );
+
+        // Oversized updates from senders not honoring the limit are ignored.
+        bob.receive_status_update(
+            ContactId::SELF,
+            bob_instance.id,
+            &format!(r#"{{"updates":[{{"payload":"{big_payload}"}}]}}"#),
+        )
+        .await?;
+        assert_eq!(
+            bob.get_webxdc_status_updates(bob_instance.id, StatusUpdateSerial(0))
+                .await?,
+            r#"[{"payload":1,"serial":1,"max_serial":1}]"#
+        );
+
+        Ok(())
+    }
+
     async fn create_webxdc_instance(t: &TestContext, name: &str, bytes: &[u8]) -> Result<Message> {
         let file = t.get_blobdir().join(name);
         tokio::fs::write(&file, bytes).await?;
@@ -2170,6 +2400,59 @@ async fn test_webxdc_info_msg() -> Result<()> {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_webxdc_notification_hints() -> Result<()> {
+        let alice = TestContext::new_alice().await;
+        let bob = TestContext::new_bob().await;
+
+        let alice_chat = alice.create_chat(&bob).await;
+        let alice_instance = send_webxdc_instance(&alice, alice_chat.id).await?;
+        let bob_instance = bob.recv_msg(&alice.pop_sent_msg().await).await;
+
+        for i in 1..=2 {
+            alice
+                .send_webxdc_status_update(
+                    alice_instance.id,
+                    &format!(r#"{{"info":"move {i}", "href":"#move{i}", "payload":{i}}}"#),
+                    "descr text",
+                )
+                .await?;
+            alice.flush_status_updates().await?;
+            bob.recv_msg(&alice.pop_sent_msg().await).await;
+        }
+
+        // Own info messages do not need attention.
+        let info = alice_instance.get_webxdc_info(&alice).await?;
+        assert_eq!(info.unread_info_cnt, 0);
+        assert_eq!(
+            alice.get_last_msg().await.get_webxdc_href(),
+            Some("#move2".to_string())
+        );
+
+        let event = bob
+            .evtracker
+            .get_matching(|evt| matches!(evt, EventType::WebxdcAttention { .. }))
+            .await;
+        assert_eq!(
+            event,
+            EventType::WebxdcAttention {
+                msg_id: bob_instance.id,
+                unread_info_cnt: 1
+            }
+        );
+        let info_msg = bob.get_last_msg().await;
+        assert_eq!(info_msg.get_text(), Some("move 2".to_string()));
+        assert_eq!(info_msg.get_webxdc_href(), Some("#move2".to_string()));
+        let bob_instance = Message::load_from_db(&bob, bob_instance.id).await?;
+        assert_eq!(bob_instance.get_webxdc_info(&bob).await?.unread_info_cnt, 2);
+
+        bob.mark_webxdc_infos_seen(bob_instance.id).await?;
+        let bob_instance = Message::load_from_db(&bob, bob_instance.id).await?;
+        assert_eq!(bob_instance.get_webxdc_info(&bob).await?.unread_info_cnt, 0);
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_webxdc_info_msg_cleanup_series() -> Result<()> {
         let alice = TestContext::new_alice().await;